@@ -0,0 +1,6 @@
+window.ui = SwaggerUIBundle({
+  url: '/api/openapi.json',
+  dom_id: '#swagger-ui',
+  // Same-origin requests carry the session cookie.
+  withCredentials: true,
+});
@@ -0,0 +1,30 @@
+// Map for the viewer page; the slug and base URL come from #map's data attributes.
+const container = document.getElementById('map');
+const slug = container.dataset.slug;
+const baseUrl = container.dataset.baseUrl;
+const map = new maplibregl.Map({
+  container,
+  style: `${baseUrl}/tiles/${slug}/style.json${window.location.search}`,
+});
+map.addControl(new maplibregl.NavigationControl());
+
+map.once('load', () => {
+  const bounds = map.getStyle().sources[slug]?.bounds;
+  if (bounds) {
+    map.fitBounds(bounds, { padding: 40, maxZoom: 16, duration: 0 });
+  }
+});
+
+map.on('click', (event) => {
+  const feature = map.queryRenderedFeatures(event.point)[0];
+  if (!feature) return;
+  const table = document.createElement('table');
+  for (const [key, value] of Object.entries(feature.properties)) {
+    const row = table.insertRow();
+    const name = document.createElement('th');
+    name.textContent = key;
+    row.appendChild(name);
+    row.insertCell().textContent = String(value);
+  }
+  new maplibregl.Popup().setLngLat(event.lngLat).setDOMContent(table).addTo(map);
+});
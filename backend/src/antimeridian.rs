@@ -0,0 +1,158 @@
+//! Antimeridian splitting
+//!
+//! A geometry crossing ±180° is stored with longitudes on both sides, e.g. a
+//! line from 179° to -179°, and renders as a smear across the whole world. An
+//! upload sent with `splitAntimeridian=true`, stored in
+//! `files.split_antimeridian`, has such geometries split into the parts east
+//! and west of the antimeridian on import, and on every append after.
+//!
+//! A geometry crosses when its longitudes span more than 180°. It is moved to
+//! 0°..360° by adding 360° to its negative longitudes, clipped on either side
+//! of 180°, and the eastern part moved back by 360°. This only applies to
+//! datasets in geographic coordinates, and misreads geometries that really are
+//! that wide, which is why it is opt-in.
+
+use crate::columns::quote_identifier;
+
+/// Whether imports into `file_id` split geometries at the antimeridian.
+pub fn splits_antimeridian(conn: &duckdb::Connection, file_id: &str) -> Result<bool, String> {
+    conn.query_row(
+        "SELECT coalesce(split_antimeridian, FALSE) FROM files WHERE id = ?",
+        duckdb::params![file_id],
+        |row| row.get(0),
+    )
+    .map_err(|e| format!("File lookup failed: {e}"))
+}
+
+/// Split the features of `table_name` after `after_fid` that cross the
+/// antimeridian, if `file_id` asks for it and `crs` is geographic. Returns how
+/// many were split.
+pub fn split_new_features(
+    conn: &duckdb::Connection,
+    file_id: &str,
+    table_name: &str,
+    crs: Option<&str>,
+    after_fid: i64,
+) -> Result<usize, String> {
+    let geographic = crs
+        .is_none_or(|crs| matches!(crs.to_ascii_uppercase().as_str(), "EPSG:4326" | "OGC:CRS84"));
+    if !geographic || !splits_antimeridian(conn, file_id)? {
+        return Ok(0);
+    }
+
+    let failed = |e: duckdb::Error| format!("Failed to split at the antimeridian: {e}");
+    let table = quote_identifier(table_name);
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT fid, ST_AsGeoJSON(geom) FROM {table}
+             WHERE fid > ? AND geom IS NOT NULL AND ST_XMax(geom) - ST_XMin(geom) > 180"
+        ))
+        .map_err(failed)?;
+    let crossing = stmt
+        .query_map(duckdb::params![after_fid], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(failed)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(failed)?;
+
+    for (fid, geometry) in &crossing {
+        let mut shifted: serde_json::Value =
+            serde_json::from_str(geometry).map_err(|e| e.to_string())?;
+        shift_longitudes(&mut shifted, &|lon| {
+            if lon < 0.0 {
+                lon + 360.0
+            } else {
+                lon
+            }
+        });
+        let (west, east): (Option<String>, Option<String>) = conn
+            .query_row(
+                "SELECT ST_AsGeoJSON(ST_Intersection(g, ST_MakeEnvelope(0, -90, 180, 90))),
+                        ST_AsGeoJSON(ST_Intersection(g, ST_MakeEnvelope(180, -90, 360, 90)))
+                 FROM (SELECT ST_GeomFromGeoJSON(?) AS g)",
+                duckdb::params![shifted.to_string()],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(failed)?;
+        let east = east
+            .map(|east| {
+                let mut east: serde_json::Value = serde_json::from_str(&east)?;
+                shift_longitudes(&mut east, &|lon| lon - 360.0);
+                Ok::<_, serde_json::Error>(east.to_string())
+            })
+            .transpose()
+            .map_err(|e| e.to_string())?;
+        conn.execute(
+            &format!(
+                "UPDATE {table} SET geom = coalesce(ST_Union(west, east), west, east)
+                 FROM (SELECT ST_GeomFromGeoJSON(?) AS west, ST_GeomFromGeoJSON(?) AS east)
+                 WHERE fid = ?"
+            ),
+            duckdb::params![west, east, fid],
+        )
+        .map_err(failed)?;
+    }
+    Ok(crossing.len())
+}
+
+/// Apply `shift` to the longitude of every position of a GeoJSON geometry.
+fn shift_longitudes(geometry: &mut serde_json::Value, shift: &dyn Fn(f64) -> f64) {
+    match geometry {
+        serde_json::Value::Array(items) => {
+            if let Some(lon) = items.first_mut().filter(|item| item.is_number()) {
+                if let Some(shifted) = lon
+                    .as_f64()
+                    .and_then(|value| serde_json::Number::from_f64(shift(value)))
+                {
+                    *lon = serde_json::Value::Number(shifted);
+                }
+            } else {
+                for item in items {
+                    shift_longitudes(item, shift);
+                }
+            }
+        }
+        serde_json::Value::Object(members) => {
+            for (key, value) in members.iter_mut() {
+                if key == "coordinates" || key == "geometries" {
+                    shift_longitudes(value, shift);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn longitudes_of_every_position_are_shifted() {
+        let mut geometry = serde_json::json!({
+            "type": "GeometryCollection",
+            "geometries": [
+                { "type": "Point", "coordinates": [-179.5, 10.0, 3.0] },
+                { "type": "LineString", "coordinates": [[179.0, -5.0], [-179.0, 5.0]] }
+            ]
+        });
+        shift_longitudes(&mut geometry, &|lon| {
+            if lon < 0.0 {
+                lon + 360.0
+            } else {
+                lon
+            }
+        });
+        assert_eq!(
+            geometry,
+            serde_json::json!({
+                "type": "GeometryCollection",
+                "geometries": [
+                    { "type": "Point", "coordinates": [180.5, 10.0, 3.0] },
+                    { "type": "LineString", "coordinates": [[179.0, -5.0], [181.0, 5.0]] }
+                ]
+            })
+        );
+    }
+}
@@ -0,0 +1,278 @@
+//! API keys for programmatic clients
+//!
+//! Users mint named keys through `/api/tokens` and send them as
+//! `Authorization: Bearer <key>`. A key is shown once, when it is created; only
+//! its SHA-256 is stored, which is enough for random keys of this length.
+//! `bearer_auth` runs inside the session layer and, for a valid key, signs the
+//! request in as the key's user without touching the session, so role and
+//! ownership checks behave as they do for cookie logins.
+
+use axum::{
+    extract::{Path as AxumPath, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::{delete, get},
+    Json, Router,
+};
+use axum_login::AuthSession;
+use chrono::Utc;
+use duckdb::OptionalExt;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+use crate::auth::{AuthBackend, Role, User};
+use crate::http_errors::{bad_request, internal_error};
+use crate::models::{ApiTokenItem, CreateApiTokenRequest};
+use crate::{AppState, ErrorResponse};
+
+const KEY_PREFIX: &str = "mf_";
+/// Characters of a key kept in the clear so users can tell keys apart.
+const DISPLAY_PREFIX_LENGTH: usize = 10;
+const MAX_TOKEN_NAME_LENGTH: usize = 64;
+/// `last_used_at` is only rewritten once it is this stale, so a busy client
+/// does not take the writer on every request.
+const LAST_USED_RESOLUTION_SECS: i64 = 60;
+
+pub fn build_tokens_router() -> Router<AppState> {
+    Router::new()
+        .route("/api/tokens", get(list_tokens).post(create_token))
+        .route("/api/tokens/{id}", delete(revoke_token))
+}
+
+pub fn generate_api_key() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    format!("{KEY_PREFIX}{}", hex::encode(bytes))
+}
+
+pub fn hash_api_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+/// The key from an `Authorization: Bearer <key>` header value.
+fn bearer_key(header_value: &str) -> Option<&str> {
+    let (scheme, key) = header_value.trim().split_once(' ')?;
+    scheme
+        .eq_ignore_ascii_case("bearer")
+        .then_some(key.trim())
+        .filter(|key| !key.is_empty())
+}
+
+fn invalid_key() -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        Json(ErrorResponse {
+            error: "Invalid API key".to_string(),
+        }),
+    )
+        .into_response()
+}
+
+/// Authenticate requests carrying a bearer key. Requests without one fall
+/// through to the session check.
+pub async fn bearer_auth(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(header_value) = request.headers().get(header::AUTHORIZATION) else {
+        return next.run(request).await;
+    };
+    let Some(key) = header_value.to_str().ok().and_then(bearer_key) else {
+        return invalid_key();
+    };
+
+    let hash = hash_api_key(key);
+    let found = match state.read_pool.get().await {
+        Ok(conn) => conn
+            .query_row(
+                "SELECT u.id, u.username, u.password_hash, u.role, t.last_used_at
+                 FROM api_tokens t
+                 JOIN users u ON u.id = t.user_id
+                 WHERE t.token_hash = ?",
+                duckdb::params![&hash],
+                |row| {
+                    let user = User {
+                        id: row.get(0)?,
+                        username: row.get(1)?,
+                        password_hash: row.get(2)?,
+                        role: row.get(3)?,
+                    };
+                    let last_used_at: Option<chrono::NaiveDateTime> = row.get(4)?;
+                    Ok((user, last_used_at))
+                },
+            )
+            .optional(),
+        Err(e) => return internal_error(e).into_response(),
+    };
+    let (user, last_used_at) = match found {
+        Ok(Some(found)) => found,
+        Ok(None) => return invalid_key(),
+        Err(e) => return internal_error(e).into_response(),
+    };
+    let now = Utc::now().naive_utc();
+    if last_used_at
+        .is_none_or(|at| now - at >= chrono::Duration::seconds(LAST_USED_RESOLUTION_SECS))
+    {
+        let conn = state.db.lock().await;
+        let _ = conn.execute(
+            "UPDATE api_tokens SET last_used_at = ? WHERE token_hash = ?",
+            duckdb::params![now, &hash],
+        );
+    }
+
+    match request
+        .extensions_mut()
+        .get_mut::<AuthSession<AuthBackend>>()
+    {
+        Some(auth_session) => auth_session.user = Some(user),
+        None => return internal_error("Auth session layer is missing").into_response(),
+    }
+    next.run(request).await
+}
+
+fn validate_token_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Token name cannot be empty".to_string());
+    }
+    if name.chars().count() > MAX_TOKEN_NAME_LENGTH {
+        return Err(format!(
+            "Token name must be {MAX_TOKEN_NAME_LENGTH} characters or less"
+        ));
+    }
+    Ok(name.to_string())
+}
+
+fn token_from_row(row: &duckdb::Row<'_>) -> Result<ApiTokenItem, duckdb::Error> {
+    let created_at: chrono::NaiveDateTime = row.get(3)?;
+    let last_used_at: Option<chrono::NaiveDateTime> = row.get(4)?;
+    Ok(ApiTokenItem {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        prefix: row.get(2)?,
+        created_at: created_at.and_utc().to_rfc3339(),
+        last_used_at: last_used_at.map(|ts| ts.and_utc().to_rfc3339()),
+        token: None,
+    })
+}
+
+/// The caller's own keys; unauthenticated test routers see none.
+async fn list_tokens(
+    State(state): State<AppState>,
+    auth_session: AuthSession<AuthBackend>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let user_id = auth_session.user.map(|user| user.id).unwrap_or_default();
+    let conn = state.db.lock().await;
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, name, prefix, created_at, last_used_at
+             FROM api_tokens
+             WHERE user_id = ?
+             ORDER BY created_at DESC",
+        )
+        .map_err(internal_error)?;
+    let tokens: Vec<ApiTokenItem> = stmt
+        .query_map(duckdb::params![user_id], token_from_row)
+        .map_err(internal_error)?
+        .collect::<Result<_, _>>()
+        .map_err(internal_error)?;
+    Ok(Json(tokens))
+}
+
+async fn create_token(
+    State(state): State<AppState>,
+    auth_session: AuthSession<AuthBackend>,
+    Json(req): Json<CreateApiTokenRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let Some(user) = auth_session.user else {
+        return Err(bad_request("API keys belong to a signed-in user"));
+    };
+    let name = validate_token_name(&req.name).map_err(|e| bad_request(&e))?;
+
+    let key = generate_api_key();
+    let id = uuid::Uuid::new_v4().to_string();
+    let conn = state.db.lock().await;
+    conn.execute(
+        "INSERT INTO api_tokens (id, user_id, name, token_hash, prefix, created_at)
+         VALUES (?, ?, ?, ?, ?, ?)",
+        duckdb::params![
+            &id,
+            &user.id,
+            &name,
+            hash_api_key(&key),
+            &key[..DISPLAY_PREFIX_LENGTH],
+            Utc::now().naive_utc()
+        ],
+    )
+    .map_err(internal_error)?;
+    let mut token = conn
+        .query_row(
+            "SELECT id, name, prefix, created_at, last_used_at FROM api_tokens WHERE id = ?",
+            duckdb::params![&id],
+            token_from_row,
+        )
+        .map_err(internal_error)?;
+    token.token = Some(key);
+
+    Ok((StatusCode::CREATED, Json(token)))
+}
+
+/// Revoke one of the caller's keys; admins may revoke anyone's.
+async fn revoke_token(
+    State(state): State<AppState>,
+    auth_session: AuthSession<AuthBackend>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let owner_id = auth_session
+        .user
+        .filter(|user| user.role() != Role::Admin)
+        .map(|user| user.id);
+    let owner_filter = if owner_id.is_some() {
+        "AND user_id = ?"
+    } else {
+        ""
+    };
+    let conn = state.db.lock().await;
+    let deleted = conn
+        .execute(
+            &format!("DELETE FROM api_tokens WHERE id = ? {owner_filter}"),
+            duckdb::params_from_iter(std::iter::once(&id).chain(owner_id.iter())),
+        )
+        .map_err(internal_error)?;
+    if deleted == 0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "API key not found".to_string(),
+            }),
+        ));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_random_and_hashed() {
+        let key = generate_api_key();
+        assert!(key.starts_with(KEY_PREFIX));
+        assert_eq!(key.len(), KEY_PREFIX.len() + 64);
+        assert_ne!(key, generate_api_key());
+        assert_eq!(hash_api_key(&key), hash_api_key(&key));
+        assert_ne!(hash_api_key(&key), key);
+    }
+
+    #[test]
+    fn bearer_header_is_parsed() {
+        assert_eq!(bearer_key("Bearer mf_abc"), Some("mf_abc"));
+        assert_eq!(bearer_key("bearer  mf_abc "), Some("mf_abc"));
+        assert_eq!(bearer_key("Basic dXNlcjpwdw=="), None);
+        assert_eq!(bearer_key("Bearer "), None);
+        assert_eq!(bearer_key("mf_abc"), None);
+    }
+}
@@ -0,0 +1,304 @@
+//! Append uploads into an existing dataset
+//!
+//! The upload is read into a temporary staging table, its columns are matched
+//! against the target's `dataset_columns` by original name, and the rows are
+//! inserted with fresh fids after the target's current maximum. Geometries are
+//! reprojected when the upload's CRS differs from the target's. Target columns
+//! missing from the upload are left NULL; upload columns the target does not
+//! have reject the append. The target's data is kept as a dataset version
+//! before rows are added, and restored if adding them fails.
+
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::antimeridian::split_new_features;
+use crate::columns::{
+    load_dataset_columns, quote_identifier, quote_literal, resolve_column, DatasetColumn,
+};
+use crate::import::{detect_crs, gdal_source_path};
+use crate::shapefile::{cpg_encoding, read_options};
+use crate::versions::{restore_retained_version, retain_current_version};
+use crate::zm::flatten_new_features;
+
+/// Query string of `POST /api/uploads`.
+#[derive(Debug, Default, Deserialize)]
+pub struct UploadQuery {
+    pub mode: Option<String>,
+    pub target: Option<String>,
+}
+
+impl UploadQuery {
+    /// The dataset to append to, or `None` for a regular upload.
+    pub fn append_target(&self) -> Result<Option<&str>, String> {
+        match (self.mode.as_deref(), self.target.as_deref()) {
+            (None | Some("create"), None) => Ok(None),
+            (None | Some("create"), Some(_)) => Err("target requires mode=append".to_string()),
+            (Some("append"), Some(target)) if !target.is_empty() => Ok(Some(target)),
+            (Some("append"), _) => Err("mode=append requires a target file id".to_string()),
+            (Some(mode), _) => Err(format!("Unknown upload mode '{mode}'")),
+        }
+    }
+}
+
+/// Whether a staged DuckDB column type can be stored in a target column.
+pub fn is_compatible_type(target_type: &str, source_type: &str) -> bool {
+    const SMALL_INTEGERS: &[&str] = &["INTEGER", "SMALLINT", "TINYINT", "USMALLINT", "UTINYINT"];
+    const INTEGERS: &[&str] = &[
+        "BIGINT",
+        "INTEGER",
+        "SMALLINT",
+        "TINYINT",
+        "UINTEGER",
+        "USMALLINT",
+        "UTINYINT",
+    ];
+    let source = source_type.to_ascii_uppercase();
+    let source = source.as_str();
+
+    match target_type {
+        // Imports coerce every other type to VARCHAR, so text accepts anything.
+        "VARCHAR" => true,
+        "BOOLEAN" => source == "BOOLEAN",
+        "INTEGER" => SMALL_INTEGERS.contains(&source),
+        "BIGINT" => INTEGERS.contains(&source) || source == "UBIGINT",
+        "DOUBLE" | "FLOAT" => {
+            INTEGERS.contains(&source) || matches!(source, "UBIGINT" | "DOUBLE" | "FLOAT")
+        }
+        _ => false,
+    }
+}
+
+/// Match staged `(name, type)` columns to target columns; returns the staged
+/// column name paired with the target column it fills.
+pub fn match_append_columns<'a>(
+    target: &'a [DatasetColumn],
+    staged: &[(String, String)],
+) -> Result<Vec<(String, &'a DatasetColumn)>, String> {
+    let mut matched: Vec<(String, &DatasetColumn)> = Vec::with_capacity(staged.len());
+    for (name, data_type) in staged {
+        let column = resolve_column(target, name)
+            .ok_or_else(|| format!("Column '{name}' does not exist in the target dataset"))?;
+        if matched
+            .iter()
+            .any(|(_, existing)| existing.normalized == column.normalized)
+        {
+            return Err(format!(
+                "Column '{}' is given more than once",
+                column.original
+            ));
+        }
+        if !is_compatible_type(&column.mvt_type, data_type) {
+            return Err(format!(
+                "Column '{name}' has type {data_type}, but '{}' is {}",
+                column.original, column.mvt_type
+            ));
+        }
+        matched.push((name.clone(), column));
+    }
+    Ok(matched)
+}
+
+/// Append the features of `file_path` to the target table; returns how many
+/// rows were added. A shapefile is read with `encoding` when given and else
+/// with its `.cpg`. The staging table is dropped whether or not this succeeds.
+pub fn append_spatial_data(
+    conn: &duckdb::Connection,
+    target_id: &str,
+    target_table: &str,
+    target_crs: &str,
+    upload_id: &str,
+    file_path: &Path,
+    encoding: Option<&str>,
+) -> Result<u64, String> {
+    let abs_path = gdal_source_path(file_path)?;
+    let source_crs = detect_crs(conn, &abs_path).unwrap_or_else(|| "EPSG:4326".to_string());
+    let encoding = match encoding {
+        Some(encoding) => Some(encoding.to_string()),
+        None => cpg_encoding(file_path, None),
+    };
+
+    let staging = quote_identifier(&format!("append_{upload_id}"));
+    conn.execute(
+        &format!(
+            "CREATE TEMP TABLE {staging} AS SELECT * FROM ST_Read({}{})",
+            quote_literal(&abs_path),
+            read_options(encoding.as_deref())
+        ),
+        [],
+    )
+    .map_err(|e| format!("Spatial import failed: {}", e))?;
+
+    let last_fid: i64 = conn
+        .query_row(
+            &format!(
+                "SELECT COALESCE(MAX(fid), 0) FROM {}",
+                quote_identifier(target_table)
+            ),
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Metadata query failed: {}", e))?;
+    let result = insert_staged_sql(
+        conn,
+        &staging,
+        target_id,
+        target_table,
+        target_crs,
+        &source_crs,
+    )
+    .and_then(|insert| {
+        // Once the upload is known to fit, keep the data it is added to.
+        let retained = retain_current_version(conn, target_id)
+            .map_err(|e| format!("Failed to keep the previous version: {e}"))?;
+        let appended = conn
+            .execute(&insert, [])
+            .map_err(|e| format!("Append failed: {}", e))
+            .and_then(|appended| {
+                flatten_new_features(conn, target_id, target_table, last_fid)?;
+                split_new_features(conn, target_id, target_table, Some(target_crs), last_fid)?;
+                Ok(appended as u64)
+            });
+        if let (Err(_), Some(version)) = (&appended, retained) {
+            if let Err(e) = restore_retained_version(conn, target_id, version) {
+                tracing::error!(file_id = %target_id, error = %e, "Failed to restore the previous version");
+            }
+        }
+        appended
+    });
+    let _ = conn.execute(&format!("DROP TABLE IF EXISTS {staging}"), []);
+    result
+}
+
+/// The `INSERT` adding the staged rows to the target, once their columns are
+/// matched against it.
+fn insert_staged_sql(
+    conn: &duckdb::Connection,
+    staging: &str,
+    target_id: &str,
+    target_table: &str,
+    target_crs: &str,
+    source_crs: &str,
+) -> Result<String, String> {
+    let mut stmt = conn
+        .prepare(&format!("DESCRIBE {staging}"))
+        .map_err(|e| format!("Metadata query failed: {}", e))?;
+    let staged_iter = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(|e| format!("Metadata query failed: {}", e))?;
+    let mut staged: Vec<(String, String)> = Vec::new();
+    for column in staged_iter {
+        staged.push(column.map_err(|e| format!("Metadata query failed: {}", e))?);
+    }
+
+    let geometry_column = staged
+        .iter()
+        .position(|(_, data_type)| data_type.eq_ignore_ascii_case("GEOMETRY"))
+        .map(|index| staged.remove(index).0)
+        .ok_or_else(|| "Upload has no geometry column".to_string())?;
+
+    let target_columns = load_dataset_columns(conn, target_id)
+        .map_err(|e| format!("Metadata query failed: {}", e))?;
+    let matched = match_append_columns(&target_columns, &staged)?;
+
+    let table = quote_identifier(target_table);
+    let mut geometry = quote_identifier(&geometry_column);
+    if !source_crs.eq_ignore_ascii_case(target_crs) {
+        geometry = format!(
+            "ST_Transform({geometry}, {}, {}, always_xy := true)",
+            quote_literal(source_crs),
+            quote_literal(target_crs)
+        );
+    }
+
+    let mut targets = vec!["fid".to_string(), "geom".to_string()];
+    let mut exprs = vec![
+        format!("(SELECT COALESCE(MAX(fid), 0) FROM {table}) + row_number() OVER ()::BIGINT"),
+        geometry,
+    ];
+    for (staged_name, column) in &matched {
+        targets.push(quote_identifier(&column.normalized));
+        exprs.push(format!(
+            "CAST({} AS {})",
+            quote_identifier(staged_name),
+            column.mvt_type
+        ));
+    }
+
+    Ok(format!(
+        "INSERT INTO {table} ({}) SELECT {} FROM {staging}",
+        targets.join(", "),
+        exprs.join(", ")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(original: &str, mvt_type: &str) -> DatasetColumn {
+        DatasetColumn {
+            normalized: original.to_lowercase().replace(' ', "_"),
+            original: original.to_string(),
+            mvt_type: mvt_type.to_string(),
+        }
+    }
+
+    fn staged(name: &str, data_type: &str) -> (String, String) {
+        (name.to_string(), data_type.to_string())
+    }
+
+    #[test]
+    fn upload_mode_requires_matching_target() {
+        let query = |mode: Option<&str>, target: Option<&str>| UploadQuery {
+            mode: mode.map(str::to_string),
+            target: target.map(str::to_string),
+        };
+        assert_eq!(query(None, None).append_target(), Ok(None));
+        assert_eq!(query(Some("create"), None).append_target(), Ok(None));
+        assert_eq!(
+            query(Some("append"), Some("abc")).append_target(),
+            Ok(Some("abc"))
+        );
+        assert!(query(Some("append"), None).append_target().is_err());
+        assert!(query(Some("append"), Some("")).append_target().is_err());
+        assert!(query(None, Some("abc")).append_target().is_err());
+        assert!(query(Some("merge"), Some("abc")).append_target().is_err());
+    }
+
+    #[test]
+    fn compatible_types_never_narrow() {
+        assert!(is_compatible_type("VARCHAR", "TIMESTAMP"));
+        assert!(is_compatible_type("BIGINT", "INTEGER"));
+        assert!(is_compatible_type("DOUBLE", "BIGINT"));
+        assert!(is_compatible_type("BOOLEAN", "boolean"));
+        assert!(!is_compatible_type("INTEGER", "BIGINT"));
+        assert!(!is_compatible_type("BIGINT", "DOUBLE"));
+        assert!(!is_compatible_type("DOUBLE", "VARCHAR"));
+        assert!(!is_compatible_type("BOOLEAN", "INTEGER"));
+    }
+
+    #[test]
+    fn columns_match_by_original_name() {
+        let target = vec![column("Road Name", "VARCHAR"), column("lanes", "INTEGER")];
+
+        let matched = match_append_columns(&target, &[staged("road name", "VARCHAR")]).unwrap();
+        assert_eq!(matched.len(), 1);
+        assert_eq!(matched[0].0, "road name");
+        assert_eq!(matched[0].1.normalized, "road_name");
+
+        assert!(match_append_columns(&target, &[staged("width", "DOUBLE")]).is_err());
+        assert!(match_append_columns(&target, &[staged("lanes", "VARCHAR")]).is_err());
+        assert!(match_append_columns(
+            &target,
+            &[
+                staged("Road Name", "VARCHAR"),
+                staged("road_name", "VARCHAR")
+            ]
+        )
+        .is_err());
+        assert!(match_append_columns(&target, &[]).unwrap().is_empty());
+    }
+}
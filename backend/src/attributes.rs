@@ -0,0 +1,236 @@
+//! Attribute-only dataset updates
+//!
+//! Rewrites property columns of an imported dataset in place from a table of
+//! new values matched by a key column. Geometries, `fid`s and anything built on
+//! them (tiles, indexes) are left untouched, so refreshing statistics does not
+//! require a full re-import. The values being replaced are kept as a dataset
+//! version first.
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::columns::{
+    load_dataset_columns, quote_identifier, quote_literal, resolve_column, DatasetColumn,
+};
+use crate::models::AttributeUpdateResponse;
+use crate::versions::{restore_retained_version, retain_current_version};
+
+/// Why an attribute update was rejected.
+#[derive(Debug)]
+pub enum AttributeUpdateError {
+    /// The uploaded table does not fit the dataset (client error).
+    Invalid(String),
+    /// Database failure while applying the update.
+    Internal(String),
+}
+
+/// File types accepted as attribute sources.
+pub fn attribute_source_reader(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    match ext.as_str() {
+        "csv" => Some("read_csv_auto"),
+        "geojson" | "json" | "geojsonl" | "geojsons" => Some("ST_Read"),
+        _ => None,
+    }
+}
+
+/// Apply the rows of `source_path` to the dataset's property columns.
+///
+/// Every non-geometry column of the source must name a dataset column (original
+/// or normalized name); `key` must be one of them and unique in the source.
+/// Runs in a single transaction: either all matched rows are updated or none.
+pub fn apply_attribute_update(
+    conn: &duckdb::Connection,
+    source_id: &str,
+    table_name: &str,
+    source_path: &Path,
+    key: &str,
+) -> Result<AttributeUpdateResponse, AttributeUpdateError> {
+    let internal = |e: duckdb::Error| AttributeUpdateError::Internal(e.to_string());
+    let invalid = AttributeUpdateError::Invalid;
+
+    let reader = attribute_source_reader(source_path)
+        .ok_or_else(|| invalid("Unsupported attribute file type. Use .csv or .geojson".into()))?;
+    let abs_path = std::fs::canonicalize(source_path)
+        .map_err(|e| AttributeUpdateError::Internal(e.to_string()))?
+        .to_string_lossy()
+        .to_string();
+
+    let columns = load_dataset_columns(conn, source_id).map_err(internal)?;
+    let key_column = resolve_column(&columns, key)
+        .ok_or_else(|| invalid(format!("Unknown key column '{key}'")))?
+        .clone();
+
+    let staging_name = format!("attribute_update_{source_id}");
+    conn.execute_batch(&format!(
+        "CREATE OR REPLACE TEMP TABLE {} AS SELECT * FROM {reader}({})",
+        quote_identifier(&staging_name),
+        quote_literal(&abs_path)
+    ))
+    .map_err(|e| invalid(format!("Cannot read attribute file: {e}")))?;
+
+    let result = update_from_staging(
+        conn,
+        source_id,
+        table_name,
+        &staging_name,
+        &columns,
+        &key_column,
+    );
+    let _ = conn.execute_batch(&format!(
+        "DROP TABLE IF EXISTS {}",
+        quote_identifier(&staging_name)
+    ));
+    result
+}
+
+fn update_from_staging(
+    conn: &duckdb::Connection,
+    source_id: &str,
+    table_name: &str,
+    staging_name: &str,
+    columns: &[DatasetColumn],
+    key_column: &DatasetColumn,
+) -> Result<AttributeUpdateResponse, AttributeUpdateError> {
+    let internal = |e: duckdb::Error| AttributeUpdateError::Internal(e.to_string());
+    let invalid = AttributeUpdateError::Invalid;
+    let staging = quote_identifier(staging_name);
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT name, type FROM pragma_table_info({})",
+            quote_literal(staging_name)
+        ))
+        .map_err(internal)?;
+    let source_columns = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(internal)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(internal)?;
+
+    // Pair each source column with the dataset column it updates.
+    let mut key_source = None;
+    let mut assignments = Vec::new();
+    let mut updated_columns = Vec::new();
+    let mut seen = HashSet::new();
+    for (name, data_type) in &source_columns {
+        if data_type.eq_ignore_ascii_case("GEOMETRY") {
+            continue;
+        }
+        let target = resolve_column(columns, name)
+            .ok_or_else(|| invalid(format!("Column '{name}' does not exist in the dataset")))?;
+        if !seen.insert(target.normalized.clone()) {
+            return Err(invalid(format!(
+                "Column '{}' is provided more than once",
+                target.original
+            )));
+        }
+        let value = format!("CAST(s.{} AS {})", quote_identifier(name), target.mvt_type);
+        if target.normalized == key_column.normalized {
+            key_source = Some(value);
+        } else {
+            assignments.push(format!(
+                "{} = {value}",
+                quote_identifier(&target.normalized)
+            ));
+            updated_columns.push(target.original.clone());
+        }
+    }
+
+    let key_source = key_source.ok_or_else(|| {
+        invalid(format!(
+            "Attribute file must contain the key column '{}'",
+            key_column.original
+        ))
+    })?;
+    if assignments.is_empty() {
+        return Err(invalid(
+            "Attribute file has no columns to update besides the key".into(),
+        ));
+    }
+
+    let (rows, distinct_keys, null_keys): (i64, i64, i64) = conn
+        .query_row(
+            &format!(
+                "SELECT COUNT(*), COUNT(DISTINCT {key_source}), COUNT(*) - COUNT({key_source}) FROM {staging} s"
+            ),
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .map_err(|e| invalid(format!("Invalid key values: {e}")))?;
+    if null_keys > 0 {
+        return Err(invalid(format!(
+            "Key column '{}' contains empty values",
+            key_column.original
+        )));
+    }
+    if distinct_keys != rows {
+        return Err(invalid(format!(
+            "Key column '{}' contains duplicate values",
+            key_column.original
+        )));
+    }
+
+    let table = quote_identifier(table_name);
+    let key_target = quote_identifier(&key_column.normalized);
+
+    // Kept before the transaction: retaining opens a transaction of its own.
+    let retained = retain_current_version(conn, source_id).map_err(internal)?;
+    conn.execute_batch("BEGIN TRANSACTION").map_err(internal)?;
+    let updated = conn.execute(
+        &format!(
+            "UPDATE {table} SET {} FROM {staging} s WHERE {table}.{key_target} = {key_source}",
+            assignments.join(", ")
+        ),
+        [],
+    );
+    let updated = match updated {
+        Ok(count) => count,
+        Err(e) => {
+            let _ = conn.execute_batch("ROLLBACK");
+            // Nothing changed, so the version kept above is the current data.
+            if let Some(version) = retained {
+                restore_retained_version(conn, source_id, version).map_err(internal)?;
+            }
+            return Err(invalid(format!("Attribute update failed: {e}")));
+        }
+    };
+    conn.execute_batch("COMMIT").map_err(internal)?;
+
+    let unmatched: i64 = conn
+        .query_row(
+            &format!(
+                "SELECT COUNT(*) FROM {staging} s WHERE NOT EXISTS (SELECT 1 FROM {table} t WHERE t.{key_target} = {key_source})"
+            ),
+            [],
+            |row| row.get(0),
+        )
+        .map_err(internal)?;
+
+    Ok(AttributeUpdateResponse {
+        updated: updated as u64,
+        unmatched: unmatched.max(0) as u64,
+        columns: updated_columns,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attribute_sources_are_csv_or_geojson() {
+        assert_eq!(
+            attribute_source_reader(Path::new("stats.CSV")),
+            Some("read_csv_auto")
+        );
+        assert_eq!(
+            attribute_source_reader(Path::new("stats.geojson")),
+            Some("ST_Read")
+        );
+        assert_eq!(attribute_source_reader(Path::new("stats.zip")), None);
+        assert_eq!(attribute_source_reader(Path::new("stats")), None);
+    }
+}
@@ -3,6 +3,8 @@ use duckdb::OptionalExt;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
+use crate::ldap::{LdapConfig, LdapOutcome, LDAP_PASSWORD_HASH};
+
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct User {
     pub id: String,
@@ -12,6 +14,53 @@ pub struct User {
     pub role: String,
 }
 
+/// Roles, from least to most privileged. Each role can do everything the
+/// ones before it can.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Read datasets, tiles and exports.
+    Viewer,
+    /// Also upload, edit and publish data.
+    Editor,
+    /// Also manage users.
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Admin => "admin",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Role> {
+        match value {
+            "viewer" => Some(Role::Viewer),
+            "editor" => Some(Role::Editor),
+            "admin" => Some(Role::Admin),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl User {
+    /// The stored role; anything unrecognised gets the least privilege.
+    pub fn role(&self) -> Role {
+        Role::parse(&self.role).unwrap_or(Role::Viewer)
+    }
+}
+
 impl AuthUser for User {
     type Id = String;
 
@@ -27,11 +76,71 @@ impl AuthUser for User {
 #[derive(Clone)]
 pub struct AuthBackend {
     db: Arc<Mutex<duckdb::Connection>>,
+    ldap: Option<Arc<LdapConfig>>,
 }
 
 impl AuthBackend {
     pub fn new(db: Arc<Mutex<duckdb::Connection>>) -> Self {
-        Self { db }
+        Self { db, ldap: None }
+    }
+
+    /// Check passwords against a directory first; see `crate::ldap`.
+    pub fn with_ldap(mut self, config: LdapConfig) -> Self {
+        self.ldap = Some(Arc::new(config));
+        self
+    }
+
+    /// Create or update the local user for a directory sign-in. Only rows a
+    /// directory sign-in created are touched: a local account with the same
+    /// name is never taken over, and the sign-in is refused instead.
+    async fn sync_ldap_user(&self, username: &str, role: Role) -> Result<User, AuthError> {
+        let conn = self.db.lock().await;
+        let existing: Option<(String, String)> = conn
+            .query_row(
+                "SELECT id, password_hash FROM users WHERE username = ?",
+                duckdb::params![username],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(|e| AuthError::Database(e.to_string()))?;
+        let id = match existing {
+            Some((id, password_hash)) if password_hash == LDAP_PASSWORD_HASH => {
+                conn.execute(
+                    "UPDATE users SET role = ? WHERE id = ? AND password_hash = ?",
+                    duckdb::params![role.as_str(), &id, LDAP_PASSWORD_HASH],
+                )
+                .map_err(|e| AuthError::Database(e.to_string()))?;
+                id
+            }
+            Some(_) => {
+                tracing::warn!(
+                    username,
+                    "Directory sign-in refused: a local account has the same name"
+                );
+                return Err(AuthError::InvalidCredentials);
+            }
+            None => {
+                let id = uuid::Uuid::new_v4().to_string();
+                conn.execute(
+                    "INSERT INTO users (id, username, password_hash, role, created_at) VALUES (?, ?, ?, ?, ?)",
+                    duckdb::params![
+                        &id,
+                        username,
+                        LDAP_PASSWORD_HASH,
+                        role.as_str(),
+                        chrono::Utc::now().naive_utc()
+                    ],
+                )
+                .map_err(|e| AuthError::Database(e.to_string()))?;
+                id
+            }
+        };
+        Ok(User {
+            id,
+            username: username.to_string(),
+            password_hash: LDAP_PASSWORD_HASH.to_string(),
+            role: role.as_str().to_string(),
+        })
     }
 }
 
@@ -66,6 +175,19 @@ impl AuthnBackend for AuthBackend {
         creds: (String, String),
     ) -> Result<Option<Self::User>, Self::Error> {
         let (username, password) = creds;
+        if let Some(config) = &self.ldap {
+            match crate::ldap::authenticate(config, &username, &password).await {
+                Ok(LdapOutcome::Authenticated(role)) => {
+                    return self.sync_ldap_user(&username, role).await.map(Some)
+                }
+                Ok(LdapOutcome::InvalidCredentials) => return Err(AuthError::InvalidCredentials),
+                // Not in the directory: try local accounts.
+                Ok(LdapOutcome::UnknownUser) => {}
+                // An unreachable directory authenticates no one, but local
+                // accounts such as the `/api/auth/init` admin still work.
+                Err(e) => tracing::warn!(error = %e, "LDAP unavailable; trying local accounts"),
+            }
+        }
         let conn = self.db.lock().await;
 
         let mut stmt = conn
@@ -85,6 +207,10 @@ impl AuthnBackend for AuthBackend {
             .map_err(|e: duckdb::Error| AuthError::Database(e.to_string()))?;
 
         if let Some(user) = user_result {
+            // Directory users have no local password.
+            if user.password_hash == LDAP_PASSWORD_HASH {
+                return Err(AuthError::InvalidCredentials);
+            }
             let is_valid = crate::password::verify_password(&password, &user.password_hash)
                 .map_err(|e| AuthError::PasswordHash(e.to_string()))?;
 
@@ -161,6 +287,23 @@ mod tests {
         ).unwrap();
     }
 
+    #[test]
+    fn roles_are_ordered_by_privilege() {
+        assert!(Role::Viewer < Role::Editor && Role::Editor < Role::Admin);
+        for role in [Role::Viewer, Role::Editor, Role::Admin] {
+            assert_eq!(Role::parse(role.as_str()), Some(role));
+        }
+        assert_eq!(Role::parse("root"), None);
+
+        let user = User {
+            id: "1".to_string(),
+            username: "legacy".to_string(),
+            password_hash: String::new(),
+            role: "unknown".to_string(),
+        };
+        assert_eq!(user.role(), Role::Viewer);
+    }
+
     #[tokio::test]
     async fn test_authenticate_success() {
         let (backend, _temp_dir) = create_test_backend().await;
@@ -203,6 +346,64 @@ mod tests {
         assert!(matches!(result.unwrap_err(), AuthError::InvalidCredentials));
     }
 
+    #[tokio::test]
+    async fn directory_users_are_synced_without_a_local_password() {
+        let (backend, _temp_dir) = create_test_backend().await;
+
+        let created = backend.sync_ldap_user("dora", Role::Editor).await.unwrap();
+        assert_eq!(created.role(), Role::Editor);
+        // A later sign-in keeps the id and picks up group changes.
+        let updated = backend.sync_ldap_user("dora", Role::Admin).await.unwrap();
+        assert_eq!(updated.id, created.id);
+        assert_eq!(
+            backend.get_user(&created.id).await.unwrap().unwrap().role(),
+            Role::Admin
+        );
+
+        let result = backend
+            .authenticate(("dora".to_string(), LDAP_PASSWORD_HASH.to_string()))
+            .await;
+        assert!(matches!(result, Err(AuthError::InvalidCredentials)));
+    }
+
+    #[tokio::test]
+    async fn directory_sign_ins_do_not_take_over_local_accounts() {
+        let (backend, _temp_dir) = create_test_backend().await;
+        create_test_user(&backend, "testuser", "Test123!@#", "viewer").await;
+
+        let result = backend.sync_ldap_user("testuser", Role::Admin).await;
+        assert!(matches!(result, Err(AuthError::InvalidCredentials)));
+        let user = backend
+            .authenticate(("testuser".to_string(), "Test123!@#".to_string()))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(user.role(), Role::Viewer);
+    }
+
+    #[tokio::test]
+    async fn local_accounts_sign_in_while_the_directory_is_down() {
+        let (backend, _temp_dir) = create_test_backend().await;
+        create_test_user(&backend, "testuser", "Test123!@#", "admin").await;
+        let backend = backend.with_ldap(LdapConfig {
+            url: "ldap://127.0.0.1:1".to_string(),
+            bind_dn: None,
+            bind_password: None,
+            user_base_dn: "dc=example,dc=org".to_string(),
+            user_filter: crate::ldap::DEFAULT_USER_FILTER.to_string(),
+            group_attribute: crate::ldap::DEFAULT_GROUP_ATTRIBUTE.to_string(),
+            admin_group: None,
+            editor_group: None,
+            default_role: Role::Viewer,
+        });
+
+        let user = backend
+            .authenticate(("testuser".to_string(), "Test123!@#".to_string()))
+            .await
+            .unwrap();
+        assert!(user.is_some());
+    }
+
     #[tokio::test]
     async fn test_get_user() {
         let (backend, _temp_dir) = create_test_backend().await;
@@ -0,0 +1,215 @@
+//! Role and ownership checks for API routes
+//!
+//! API routes are grouped by the least role they need. Each group is wrapped in
+//! `require_role` inside the login check, so anonymous requests still get 401
+//! and signed-in users without the role get 403. Routes under a file are also
+//! wrapped in `require_file_access` with the access they need: the user who
+//! uploaded the file, or an admin, owns it, owners can share it with other
+//! users for reading or editing, and members of the organization a file belongs
+//! to may edit it. Files from before ownership was recorded have no owner and
+//! are left to admins.
+
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path as AxumPath, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use axum_login::AuthSession;
+use duckdb::OptionalExt;
+use serde::{Deserialize, Serialize};
+
+use crate::auth::{AuthBackend, Role, User};
+use crate::http_errors::internal_error;
+use crate::{AppState, ErrorResponse};
+
+/// What a user may do with a file, from least to most.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileAccess {
+    Read,
+    Edit,
+    /// Owners (and admins) may also publish and share the file.
+    Own,
+}
+
+impl FileAccess {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileAccess::Read => "read",
+            FileAccess::Edit => "edit",
+            FileAccess::Own => "own",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "read" => Some(FileAccess::Read),
+            "edit" => Some(FileAccess::Edit),
+            "own" => Some(FileAccess::Own),
+            _ => None,
+        }
+    }
+}
+
+/// Whether `user` may change something owned by `owner_id`.
+pub fn can_change(user: &User, owner_id: Option<&str>) -> bool {
+    user.role() == Role::Admin || owner_id == Some(user.id.as_str())
+}
+
+/// `user`'s access to a file owned by `owner_id` and shared with them as `share`.
+pub fn access_level(
+    user: &User,
+    owner_id: Option<&str>,
+    share: Option<FileAccess>,
+) -> Option<FileAccess> {
+    if can_change(user, owner_id) {
+        Some(FileAccess::Own)
+    } else {
+        share
+    }
+}
+
+pub fn not_owner(what: &str) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::FORBIDDEN,
+        Json(ErrorResponse {
+            error: format!("Only the {what}'s owner or an admin can change it"),
+        }),
+    )
+}
+
+pub fn access_denied(required: FileAccess) -> (StatusCode, Json<ErrorResponse>) {
+    let error = match required {
+        FileAccess::Read => "You do not have access to this file",
+        FileAccess::Edit => "You need edit access to change this file",
+        FileAccess::Own => return not_owner("file"),
+    };
+    (
+        StatusCode::FORBIDDEN,
+        Json(ErrorResponse {
+            error: error.to_string(),
+        }),
+    )
+}
+
+/// `user`'s access to a file; `None` when the file does not exist.
+pub fn file_access(
+    conn: &duckdb::Connection,
+    user: &User,
+    file_id: &str,
+) -> Result<Option<Option<FileAccess>>, duckdb::Error> {
+    let row: Option<(Option<String>, Option<String>, bool)> = conn
+        .query_row(
+            "SELECT f.owner_id, s.access, m.user_id IS NOT NULL
+             FROM files f
+             LEFT JOIN file_shares s ON s.file_id = f.id AND s.user_id = ?
+             LEFT JOIN org_members m ON m.org_id = f.org_id AND m.user_id = ?
+             WHERE f.id = ?",
+            duckdb::params![&user.id, &user.id, file_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?;
+    Ok(row.map(|(owner_id, share, org_member)| {
+        let share = share.as_deref().and_then(FileAccess::parse);
+        let org = org_member.then_some(FileAccess::Edit);
+        access_level(user, owner_id.as_deref(), share.max(org))
+    }))
+}
+
+pub async fn require_role(
+    State(required): State<Role>,
+    auth_session: AuthSession<AuthBackend>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(user) = auth_session.user else {
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(ErrorResponse {
+                error: "Authentication required".to_string(),
+            }),
+        )
+            .into_response();
+    };
+    if user.role() < required {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse {
+                error: format!("This action requires the {required} role"),
+            }),
+        )
+            .into_response();
+    }
+    next.run(request).await
+}
+
+/// Guard for routes with an `{id}` file parameter, needing at least the
+/// access in its state. Missing files pass through so the handler reports them
+/// as usual.
+pub async fn require_file_access(
+    State((state, required)): State<(AppState, FileAccess)>,
+    auth_session: AuthSession<AuthBackend>,
+    AxumPath(params): AxumPath<HashMap<String, String>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (Some(user), Some(file_id)) = (auth_session.user.as_ref(), params.get("id")) else {
+        return next.run(request).await;
+    };
+    let access = match state.read_pool.get().await {
+        Ok(conn) => file_access(&conn, user, file_id),
+        Err(e) => Err(e),
+    };
+    match access {
+        Ok(Some(access)) if access < Some(required) => access_denied(required).into_response(),
+        Ok(_) => next.run(request).await,
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(id: &str, role: &str) -> User {
+        User {
+            id: id.to_string(),
+            username: id.to_string(),
+            password_hash: String::new(),
+            role: role.to_string(),
+        }
+    }
+
+    #[test]
+    fn owners_and_admins_can_change_files() {
+        assert!(can_change(&user("u1", "editor"), Some("u1")));
+        assert!(!can_change(&user("u2", "editor"), Some("u1")));
+        assert!(!can_change(&user("u2", "editor"), None));
+        assert!(can_change(&user("root", "admin"), Some("u1")));
+        assert!(can_change(&user("root", "admin"), None));
+    }
+
+    #[test]
+    fn shares_grant_at_most_edit_access() {
+        let editor = user("u2", "editor");
+        assert_eq!(access_level(&editor, Some("u1"), None), None);
+        assert_eq!(
+            access_level(&editor, Some("u1"), Some(FileAccess::Read)),
+            Some(FileAccess::Read)
+        );
+        assert_eq!(
+            access_level(&editor, Some("u2"), Some(FileAccess::Read)),
+            Some(FileAccess::Own)
+        );
+        assert_eq!(
+            access_level(&user("root", "admin"), Some("u1"), None),
+            Some(FileAccess::Own)
+        );
+        assert!(Some(FileAccess::Edit) < Some(FileAccess::Own));
+        assert!(None < Some(FileAccess::Read));
+    }
+}
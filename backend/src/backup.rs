@@ -0,0 +1,391 @@
+//! Backup and restore
+//!
+//! `POST /api/admin/backup` (and `mapflow backup`) writes a zip archive with a
+//! copy of the DuckDB file and the upload directory. The copy is taken under
+//! the writer lock right after a `CHECKPOINT`, so nothing changes it meanwhile;
+//! a checkpoint skipped because of running reads leaves its changes in the WAL,
+//! which is copied along. Uploads are added afterwards, which is safe because
+//! stored files are never changed once their row exists. The tile cache and earlier backups are
+//! left out. Archives are kept in `<upload_dir>/backups` for download.
+//!
+//! Restoring replaces the database file, so it is only offered offline, as
+//! `mapflow restore <archive>` with the server stopped. Upload paths are stored
+//! as configured, so restore with the same `DB_PATH` and `UPLOAD_DIR`.
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use axum::{
+    extract::{Path as AxumPath, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::http_errors::{bad_request, internal_error};
+use crate::migrations::{latest_version, schema_version};
+use crate::models::{AppState, BackupInfo};
+use crate::postgis::POSTGIS_STAGING_DIR;
+use crate::scan::QUARANTINE_DIR;
+use crate::tile_cache::TILE_CACHE_DIR;
+use crate::ErrorResponse;
+
+pub const BACKUP_DIR: &str = "backups";
+/// Upload subdirectories that are not backed up: derived data, backups,
+/// quarantined uploads and PostGIS copies in progress.
+pub const SKIPPED_UPLOAD_DIRS: &[&str] = &[
+    TILE_CACHE_DIR,
+    BACKUP_DIR,
+    QUARANTINE_DIR,
+    POSTGIS_STAGING_DIR,
+];
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const DATABASE_ENTRY: &str = "mapflow.duckdb";
+const WAL_ENTRY: &str = "mapflow.duckdb.wal";
+const UPLOADS_PREFIX: &str = "uploads/";
+
+pub fn build_backup_router() -> Router<AppState> {
+    Router::new()
+        .route("/api/admin/backup", post(create_backup))
+        .route("/api/admin/backups/{name}", get(download_backup))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BackupManifest {
+    schema_version: u32,
+    created_at: String,
+}
+
+/// What `restore_backup` put in place.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestoreReport {
+    pub schema_version: u32,
+    pub created_at: String,
+    pub uploads: usize,
+}
+
+pub fn backup_root(upload_dir: &Path) -> PathBuf {
+    upload_dir.join(BACKUP_DIR)
+}
+
+/// Archive names the server hands out: `mapflow-<timestamp>.zip`.
+fn valid_backup_name(name: &str) -> bool {
+    name.starts_with("mapflow-")
+        && name.ends_with(".zip")
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+        && !name.contains("..")
+}
+
+pub fn backup_file_name() -> String {
+    format!("mapflow-{}.zip", Utc::now().format("%Y%m%dT%H%M%SZ"))
+}
+
+pub(crate) fn wal_path(db_path: &Path) -> PathBuf {
+    let mut wal = db_path.as_os_str().to_owned();
+    wal.push(".wal");
+    PathBuf::from(wal)
+}
+
+pub(crate) fn database_path(conn: &duckdb::Connection) -> Result<PathBuf, String> {
+    let path: Option<String> = conn
+        .query_row(
+            "SELECT path FROM duckdb_databases() WHERE database_name = current_database()",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    path.map(PathBuf::from)
+        .ok_or_else(|| "In-memory databases cannot be backed up".to_string())
+}
+
+/// Write a backup archive to `output`. Returns its size in bytes.
+pub async fn write_backup(state: &AppState, output: &Path) -> Result<u64, String> {
+    let dir = output.parent().unwrap_or(Path::new("."));
+    tokio::fs::create_dir_all(dir)
+        .await
+        .map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+    let snapshot = dir.join(format!(".{}.duckdb", uuid::Uuid::new_v4()));
+    let snapshot_wal = wal_path(&snapshot);
+
+    let copied = async {
+        let conn = state.db.lock().await;
+        let db_path = database_path(&conn)?;
+        conn.execute_batch("CHECKPOINT")
+            .map_err(|e| format!("Checkpoint failed: {e}"))?;
+        let version = schema_version(&conn).map_err(|e| e.to_string())?;
+        // Writes wait for the lock, so the files stay as checkpointed.
+        tokio::fs::copy(&db_path, &snapshot)
+            .await
+            .map_err(|e| format!("Failed to copy the database: {e}"))?;
+        match tokio::fs::copy(wal_path(&db_path), &snapshot_wal).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("Failed to copy the database WAL: {e}")),
+        }
+        Ok(BackupManifest {
+            schema_version: version,
+            created_at: Utc::now().to_rfc3339(),
+        })
+    };
+
+    let result = match copied.await {
+        Ok(manifest) => {
+            let upload_dir = state.upload_dir.clone();
+            let output = output.to_path_buf();
+            let snapshot = snapshot.clone();
+            tokio::task::spawn_blocking(move || {
+                write_archive(&output, &snapshot, &upload_dir, &manifest)
+            })
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|written| written.map_err(|e| format!("Failed to write backup: {e}")))
+        }
+        Err(e) => Err(e),
+    };
+    let _ = tokio::fs::remove_file(&snapshot).await;
+    let _ = tokio::fs::remove_file(&snapshot_wal).await;
+    result
+}
+
+fn write_archive(
+    output: &Path,
+    database: &Path,
+    upload_dir: &Path,
+    manifest: &BackupManifest,
+) -> io::Result<u64> {
+    let partial = output.with_extension("zip.partial");
+    let mut zip = ZipWriter::new(File::create(&partial)?);
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .large_file(true);
+
+    zip.start_file(MANIFEST_ENTRY, options)?;
+    zip.write_all(&serde_json::to_vec_pretty(manifest).expect("manifest serializes"))?;
+    zip.start_file(DATABASE_ENTRY, options)?;
+    io::copy(&mut File::open(database)?, &mut zip)?;
+    if let Ok(mut wal) = File::open(wal_path(database)) {
+        zip.start_file(WAL_ENTRY, options)?;
+        io::copy(&mut wal, &mut zip)?;
+    }
+    if upload_dir.is_dir() {
+        add_uploads(&mut zip, options, upload_dir, upload_dir)?;
+    }
+    zip.finish()?;
+
+    std::fs::rename(&partial, output)?;
+    Ok(std::fs::metadata(output)?.len())
+}
+
+fn add_uploads(
+    zip: &mut ZipWriter<File>,
+    options: FileOptions,
+    root: &Path,
+    dir: &Path,
+) -> io::Result<()> {
+    let mut entries = std::fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let path = entry.path();
+        let relative = path.strip_prefix(root).expect("entry is under the root");
+        if dir == root
+            && SKIPPED_UPLOAD_DIRS
+                .iter()
+                .any(|skipped| relative == Path::new(skipped))
+        {
+            continue;
+        }
+        let name = relative
+            .components()
+            .map(|part| part.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        if entry.file_type()?.is_dir() {
+            add_uploads(zip, options, root, &path)?;
+        } else {
+            zip.start_file(format!("{UPLOADS_PREFIX}{name}"), options)?;
+            io::copy(&mut File::open(&path)?, zip)?;
+        }
+    }
+    Ok(())
+}
+
+/// Put the database and uploads of `archive` in place. An existing database is
+/// only replaced with `force`; the server must not be running.
+pub fn restore_backup(
+    archive: &Path,
+    db_path: &Path,
+    upload_dir: &Path,
+    force: bool,
+) -> Result<RestoreReport, String> {
+    let file =
+        File::open(archive).map_err(|e| format!("Cannot open {}: {e}", archive.display()))?;
+    let mut zip = ZipArchive::new(file).map_err(|_| "Not a MapFlow backup archive".to_string())?;
+    let manifest: BackupManifest = zip
+        .by_name(MANIFEST_ENTRY)
+        .ok()
+        .and_then(|entry| serde_json::from_reader(entry).ok())
+        .ok_or("Not a MapFlow backup archive")?;
+    if manifest.schema_version > latest_version() {
+        return Err(format!(
+            "The backup has schema version {}, newer than this build supports ({})",
+            manifest.schema_version,
+            latest_version()
+        ));
+    }
+    if db_path.exists() && !force {
+        return Err(format!(
+            "{} already exists; pass --force to replace it",
+            db_path.display()
+        ));
+    }
+
+    if let Some(parent) = db_path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let partial = db_path.with_extension("duckdb.partial");
+    if !extract_entry(&mut zip, DATABASE_ENTRY, &partial)? {
+        return Err("The backup has no database".to_string());
+    }
+    // A WAL left by the replaced database must not be replayed onto the backup.
+    let wal = wal_path(db_path);
+    match std::fs::remove_file(&wal) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(format!("Failed to remove {}: {e}", wal.display())),
+    }
+    std::fs::rename(&partial, db_path).map_err(|e| e.to_string())?;
+    extract_entry(&mut zip, WAL_ENTRY, &wal)?;
+
+    let mut uploads = 0;
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i).map_err(|e| e.to_string())?;
+        // `enclosed_name` rejects absolute paths and `..`.
+        let Some(relative) = entry
+            .enclosed_name()
+            .and_then(|name| name.strip_prefix(UPLOADS_PREFIX).ok())
+            .map(Path::to_path_buf)
+        else {
+            continue;
+        };
+        if !entry.is_file() {
+            continue;
+        }
+        let target = upload_dir.join(relative);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let mut out = File::create(&target).map_err(|e| e.to_string())?;
+        io::copy(&mut entry, &mut out)
+            .map_err(|e| format!("Failed to restore {}: {e}", target.display()))?;
+        uploads += 1;
+    }
+
+    Ok(RestoreReport {
+        schema_version: manifest.schema_version,
+        created_at: manifest.created_at,
+        uploads,
+    })
+}
+
+/// Write the entry `name` to `target`; `false` when the archive lacks it.
+fn extract_entry(zip: &mut ZipArchive<File>, name: &str, target: &Path) -> Result<bool, String> {
+    let Ok(mut entry) = zip.by_name(name) else {
+        return Ok(false);
+    };
+    let mut out = File::create(target).map_err(|e| e.to_string())?;
+    io::copy(&mut entry, &mut out)
+        .map_err(|e| format!("Failed to restore {}: {e}", target.display()))?;
+    Ok(true)
+}
+
+async fn create_backup(
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let name = backup_file_name();
+    let size = write_backup(&state, &backup_root(&state.upload_dir).join(&name))
+        .await
+        .map_err(internal_error)?;
+    tracing::info!(%name, size, "Wrote backup");
+    Ok((
+        StatusCode::CREATED,
+        Json(BackupInfo {
+            download_url: format!("/api/admin/backups/{name}"),
+            name,
+            size,
+            created_at: Utc::now().to_rfc3339(),
+        }),
+    ))
+}
+
+async fn download_backup(
+    State(state): State<AppState>,
+    AxumPath(name): AxumPath<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    if !valid_backup_name(&name) {
+        return Err(bad_request("Invalid backup name"));
+    }
+    let data = match tokio::fs::read(backup_root(&state.upload_dir).join(&name)).await {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    error: "Backup not found".to_string(),
+                }),
+            ))
+        }
+        Err(e) => return Err(internal_error(e)),
+    };
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{name}\""),
+            ),
+        ],
+        data,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_generated_names_are_served() {
+        assert!(valid_backup_name(&backup_file_name()));
+        assert!(valid_backup_name("mapflow-20260204T100000Z.zip"));
+        assert!(!valid_backup_name("mapflow-../../mapflow.duckdb.zip"));
+        assert!(!valid_backup_name("mapflow-a/b.zip"));
+        assert!(!valid_backup_name("other.zip"));
+    }
+
+    #[test]
+    fn archives_without_a_manifest_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("other.zip");
+        let mut zip = ZipWriter::new(File::create(&archive).unwrap());
+        zip.start_file("readme.txt", FileOptions::default())
+            .unwrap();
+        zip.write_all(b"hello").unwrap();
+        zip.finish().unwrap();
+
+        let db_path = dir.path().join("db/mapflow.duckdb");
+        assert_eq!(
+            restore_backup(&archive, &db_path, &dir.path().join("uploads"), false),
+            Err("Not a MapFlow backup archive".to_string())
+        );
+        assert!(!db_path.exists());
+    }
+}
@@ -0,0 +1,208 @@
+//! Headless admin operations
+//!
+//! What the `mapflow` subcommands do besides `serve`. They work on the
+//! database and upload directory directly, so user management, imports and
+//! exports don't need a running server or an admin session; each reuses the
+//! code path of the matching API endpoint. Restoring a backup has no endpoint:
+//! it replaces the database, so it only runs here.
+
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use tokio::fs;
+
+use crate::auth::Role;
+use crate::backup::{backup_file_name, restore_backup, write_backup, RestoreReport};
+use crate::export::{export_dataset, ExportFormat};
+use crate::features::parse_bbox;
+use crate::models::{AppState, TileSeedRequest, UserItem};
+use crate::tile_seed::{create_seed_job, run_seed_job};
+
+/// Create a local account, as `POST /api/users` does.
+pub async fn create_user(
+    state: &AppState,
+    username: &str,
+    password: &str,
+    role: Role,
+) -> Result<UserItem, String> {
+    crate::users::insert_user(state, username, password, role, |_| Ok(()))
+        .await
+        .map_err(|(_, error)| error.0.error)
+}
+
+/// Set a new password for `username`. Their existing sessions stop working.
+pub async fn reset_password(
+    state: &AppState,
+    username: &str,
+    password: &str,
+) -> Result<(), String> {
+    crate::validate_password_complexity(password).map_err(|e| format!("Invalid password: {e}"))?;
+    let password_hash = crate::hash_password(password).map_err(|e| e.to_string())?;
+
+    let conn = state.db.lock().await;
+    let updated = conn
+        .execute(
+            "UPDATE users SET password_hash = ? WHERE username = ?",
+            duckdb::params![&password_hash, username],
+        )
+        .map_err(|e| format!("Failed to update password: {e}"))?;
+    if updated == 0 {
+        return Err(format!("User '{username}' not found"));
+    }
+    Ok(())
+}
+
+/// Copy `source` into the upload directory and import it as a new dataset,
+/// waiting for the import to finish. Returns the new file id.
+pub async fn import_file(
+    state: &AppState,
+    source: &Path,
+    owner: Option<&str>,
+) -> Result<String, String> {
+    let file_name = source
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| format!("Invalid file name: {}", source.display()))?;
+    let file_type = crate::upload_file_type(file_name).ok_or(crate::UNSUPPORTED_UPLOAD_TYPE)?;
+
+    let owner_id = match owner {
+        Some(username) => {
+            let conn = state.db.lock().await;
+            let id: String = conn
+                .query_row(
+                    "SELECT id FROM users WHERE username = ?",
+                    duckdb::params![username],
+                    |row| row.get(0),
+                )
+                .map_err(|_| format!("User '{username}' not found"))?;
+            Some(id)
+        }
+        None => None,
+    };
+
+    let file_id = crate::create_id();
+    let dir = state.upload_dir.join(&file_id);
+    fs::create_dir_all(&dir)
+        .await
+        .map_err(|e| format!("Failed to create upload dir: {e}"))?;
+    let file_path = dir.join(file_name);
+    let size = fs::copy(source, &file_path)
+        .await
+        .map_err(|e| format!("Failed to copy {}: {e}", source.display()))?;
+
+    let validation = crate::validate_upload(file_type, &file_path).await;
+    let name = Path::new(file_name)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(file_name);
+    {
+        let conn = state.db.lock().await;
+        conn.execute(
+            "INSERT INTO files (id, name, type, size, uploaded_at, status, path, error, is_public, owner_id)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, FALSE, ?)",
+            duckdb::params![
+                &file_id,
+                name,
+                file_type,
+                size as i64,
+                Utc::now().to_rfc3339(),
+                if validation.is_ok() { "uploaded" } else { "failed" },
+                crate::storage_path_string(&file_path),
+                validation.as_ref().err(),
+                &owner_id,
+            ],
+        )
+        .map_err(|e| format!("Failed to register dataset: {e}"))?;
+    }
+    validation?;
+
+    crate::run_import(&state.db, &file_id, &file_path, file_type).await?;
+    Ok(file_id)
+}
+
+/// Export a ready dataset to `output`, or to `<id>.<ext>` in the working
+/// directory. Returns the written path.
+pub async fn export_file(
+    state: &AppState,
+    file_id: &str,
+    format: &str,
+    output: Option<PathBuf>,
+) -> Result<PathBuf, String> {
+    let format = ExportFormat::parse(format).ok_or("Unsupported export format. Use gpkg")?;
+    {
+        let conn = state.db.lock().await;
+        let status: String = conn
+            .query_row(
+                "SELECT status FROM files WHERE id = ?",
+                duckdb::params![file_id],
+                |row| row.get(0),
+            )
+            .map_err(|_| format!("File '{file_id}' not found"))?;
+        if status != "ready" {
+            return Err("File is not ready for export".to_string());
+        }
+    }
+
+    let output =
+        output.unwrap_or_else(|| PathBuf::from(format!("{file_id}.{}", format.extension())));
+    // GDAL refuses to overwrite an existing GeoPackage.
+    if output.exists() {
+        return Err(format!("{} already exists", output.display()));
+    }
+    export_dataset(&state.db, file_id, format, &output).await?;
+    Ok(output)
+}
+
+/// Render a dataset's tiles into the tile cache, as `POST /api/files/:id/seed`
+/// does, and wait for it. `bbox` is `minx,miny,maxx,maxy`; the dataset's
+/// extent when unset. Returns the number of tiles seeded.
+pub async fn seed_tiles(
+    state: &AppState,
+    file_id: &str,
+    bbox: Option<&str>,
+    min_zoom: u8,
+    max_zoom: u8,
+    on_progress: impl FnMut(u64, u64),
+) -> Result<u64, String> {
+    let req = TileSeedRequest {
+        bbox: bbox.map(parse_bbox).transpose()?,
+        min_zoom,
+        max_zoom,
+    };
+    let job = create_seed_job(state, file_id, &req)
+        .await
+        .map_err(|(_, error)| error.0.error)?;
+    run_seed_job(state, &job, on_progress).await
+}
+
+/// Write a backup archive, as `POST /api/admin/backup` does. Returns its path
+/// and size in bytes.
+pub async fn backup(state: &AppState, output: Option<PathBuf>) -> Result<(PathBuf, u64), String> {
+    let output = output.unwrap_or_else(|| PathBuf::from(backup_file_name()));
+    let size = write_backup(state, &output).await?;
+    Ok((output, size))
+}
+
+/// Restore a backup archive into `db_path` and `upload_dir`, then bring the
+/// schema up to date. The server must be stopped; an existing database is only
+/// replaced with `force`.
+pub async fn restore(
+    archive: &Path,
+    db_path: &Path,
+    upload_dir: &Path,
+    force: bool,
+) -> Result<RestoreReport, String> {
+    let (archive, db_path, upload_dir) = (
+        archive.to_path_buf(),
+        db_path.to_path_buf(),
+        upload_dir.to_path_buf(),
+    );
+    tokio::task::spawn_blocking(move || {
+        let report = restore_backup(&archive, &db_path, &upload_dir, force)?;
+        // Opening the database applies any migrations the backup predates.
+        drop(crate::init_database(&db_path));
+        Ok(report)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
@@ -0,0 +1,147 @@
+use std::collections::HashSet;
+
+/// One property column of an imported dataset, as recorded in `dataset_columns`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatasetColumn {
+    pub normalized: String,
+    pub original: String,
+    pub mvt_type: String,
+}
+
+pub fn load_dataset_columns(
+    conn: &duckdb::Connection,
+    source_id: &str,
+) -> Result<Vec<DatasetColumn>, duckdb::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT normalized_name, original_name, mvt_type\n         FROM dataset_columns\n         WHERE source_id = ?\n         ORDER BY ordinal",
+    )?;
+    let rows = stmt.query_map(duckdb::params![source_id], |row| {
+        Ok(DatasetColumn {
+            normalized: row.get(0)?,
+            original: row.get(1)?,
+            mvt_type: row.get(2)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Resolve a user-facing column name: exact original name first, then the
+/// normalized name, then a case-insensitive match on either.
+pub fn resolve_column<'a>(columns: &'a [DatasetColumn], name: &str) -> Option<&'a DatasetColumn> {
+    columns
+        .iter()
+        .find(|c| c.original == name)
+        .or_else(|| columns.iter().find(|c| c.normalized == name))
+        .or_else(|| {
+            columns.iter().find(|c| {
+                c.original.eq_ignore_ascii_case(name) || c.normalized.eq_ignore_ascii_case(name)
+            })
+        })
+}
+
+/// Pick the output name for each property column: the original name when it is
+/// usable, otherwise the normalized one. Names are compared case-insensitively
+/// (DuckDB and GeoPackage both ignore case) and `fid`/`geom` stay reserved.
+pub fn output_column_aliases(columns: &[(String, String)]) -> Vec<(String, String)> {
+    let mut used: HashSet<String> = ["fid".to_string(), "geom".to_string()].into();
+    let mut aliases = Vec::with_capacity(columns.len());
+
+    for (normalized, original) in columns {
+        let original_key = original.trim().to_lowercase();
+        let alias = if !original_key.is_empty() && !used.contains(&original_key) {
+            original.clone()
+        } else {
+            normalized.clone()
+        };
+        used.insert(alias.to_lowercase());
+        aliases.push((normalized.clone(), alias));
+    }
+
+    aliases
+}
+
+pub fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+pub fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(normalized: &str, original: &str) -> DatasetColumn {
+        DatasetColumn {
+            normalized: normalized.to_string(),
+            original: original.to_string(),
+            mvt_type: "VARCHAR".to_string(),
+        }
+    }
+
+    #[test]
+    fn resolve_prefers_exact_original_name() {
+        let columns = vec![column("name", "name"), column("name_2", "NAME")];
+        assert_eq!(
+            resolve_column(&columns, "NAME").unwrap().normalized,
+            "name_2"
+        );
+        assert_eq!(resolve_column(&columns, "name").unwrap().normalized, "name");
+        assert_eq!(resolve_column(&columns, "Name").unwrap().normalized, "name");
+        assert!(resolve_column(&columns, "other").is_none());
+    }
+
+    #[test]
+    fn resolve_falls_back_to_normalized_name() {
+        let columns = vec![column("road_name", "Road Name")];
+        assert_eq!(
+            resolve_column(&columns, "road_name").unwrap().original,
+            "Road Name"
+        );
+    }
+
+    #[test]
+    fn quote_helpers_escape_embedded_quotes() {
+        assert_eq!(quote_identifier("a\"b"), "\"a\"\"b\"");
+        assert_eq!(quote_literal("it's"), "'it''s'");
+    }
+
+    fn cols(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(n, o)| (n.to_string(), o.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn aliases_restore_original_names() {
+        let aliases = output_column_aliases(&cols(&[
+            ("road_name", "Road Name"),
+            ("speed_km_h", "speed (km/h)"),
+        ]));
+        assert_eq!(
+            aliases,
+            cols(&[("road_name", "Road Name"), ("speed_km_h", "speed (km/h)")])
+        );
+    }
+
+    #[test]
+    fn aliases_fall_back_on_case_insensitive_conflicts() {
+        let aliases = output_column_aliases(&cols(&[
+            ("name", "name"),
+            ("name_2", "NAME"),
+            ("col_fid", "FID"),
+            ("col_1", ""),
+        ]));
+        assert_eq!(
+            aliases,
+            cols(&[
+                ("name", "name"),
+                ("name_2", "name_2"),
+                ("col_fid", "col_fid"),
+                ("col_1", "col_1"),
+            ])
+        );
+    }
+}
@@ -0,0 +1,345 @@
+//! Compressed uploads
+//!
+//! An upload named `<file>.gz` is a gzipped `<file>` of a supported type, such
+//! as `roads.geojson.gz`. One named `.tar.gz` or `.tgz` is a gzipped tarball
+//! holding a single dataset file, or the files of one shapefile, which are
+//! zipped as a shapefile upload would be. Either is unpacked into its upload
+//! directory before validation and the archive removed, so the rest of the
+//! pipeline sees the dataset file alone. Tarball entries are flattened to
+//! their file names, and one named like the archive or the shapefile zip is
+//! refused rather than written over it. The unpacked size is bounded by the
+//! upload size limit as it is written, whatever the archive claims, so a small
+//! archive cannot expand to fill the disk.
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+/// Shapefile parts gathered into a zip when a tarball holds a `.shp`.
+const SHAPEFILE_PARTS: [&str; 6] = ["shp", "shx", "dbf", "prj", "cpg", "qix"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    TarGzip,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum UnpackError {
+    /// The unpacked files would exceed the upload size limit.
+    TooLarge,
+    Invalid(String),
+}
+
+impl Compression {
+    /// How an upload is compressed, from its name, with the name of what it
+    /// unpacks to: the inner file for gzip and the bare stem for tarballs.
+    pub fn of(file_name: &str) -> Option<(Self, String)> {
+        let lower = file_name.to_ascii_lowercase();
+        for suffix in [".tar.gz", ".tgz"] {
+            if lower.ends_with(suffix) && lower.len() > suffix.len() {
+                let stem = &file_name[..file_name.len() - suffix.len()];
+                return Some((Compression::TarGzip, stem.to_string()));
+            }
+        }
+        let inner = &file_name[..file_name.len().saturating_sub(3)];
+        (lower.ends_with(".gz") && !inner.is_empty())
+            .then(|| (Compression::Gzip, inner.to_string()))
+    }
+}
+
+/// Unpack a compressed upload next to itself and remove it, returning the
+/// dataset file it held. At most `max_size` bytes are written.
+pub fn unpack_upload(
+    archive_path: &Path,
+    compression: Compression,
+    inner_name: &str,
+    max_size: u64,
+) -> Result<PathBuf, UnpackError> {
+    let dir = archive_path
+        .parent()
+        .ok_or_else(|| UnpackError::Invalid("Upload has no directory".to_string()))?;
+    let file = File::open(archive_path).map_err(invalid)?;
+    let unpacked = match compression {
+        Compression::Gzip => {
+            let target = dir.join(inner_name);
+            write_limited(GzDecoder::new(file), &target, max_size).map(|_| target)
+        }
+        Compression::TarGzip => {
+            let archive_name = archive_path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or_default();
+            let reserved = [archive_name.to_string(), format!("{inner_name}.zip")];
+            unpack_tarball(GzDecoder::new(file), dir, inner_name, &reserved, max_size)
+        }
+    };
+    let _ = std::fs::remove_file(archive_path);
+    unpacked
+}
+
+fn invalid(e: std::io::Error) -> UnpackError {
+    UnpackError::Invalid(format!("Unable to decompress upload: {e}"))
+}
+
+/// Copy `reader` to `target`, failing once more than `limit` bytes arrive.
+fn write_limited(reader: impl Read, target: &Path, limit: u64) -> Result<u64, UnpackError> {
+    let mut output = File::create(target).map_err(invalid)?;
+    let written = std::io::copy(&mut reader.take(limit + 1), &mut output).map_err(invalid)?;
+    if written > limit {
+        drop(output);
+        let _ = std::fs::remove_file(target);
+        return Err(UnpackError::TooLarge);
+    }
+    output.flush().map_err(invalid)?;
+    Ok(written)
+}
+
+/// Unpack the files of a tarball into `dir`; `reserved` names the archive and
+/// the shapefile zip, which no entry may replace.
+fn unpack_tarball(
+    reader: impl Read,
+    dir: &Path,
+    stem: &str,
+    reserved: &[String],
+    max_size: u64,
+) -> Result<PathBuf, UnpackError> {
+    let mut archive = tar::Archive::new(reader);
+    let mut remaining = max_size;
+    let mut names: Vec<String> = Vec::new();
+    let cleanup = |names: &[String]| {
+        for name in names {
+            let _ = std::fs::remove_file(dir.join(name));
+        }
+    };
+
+    let entries = archive.entries().map_err(invalid)?;
+    for entry in entries {
+        let entry = entry.map_err(invalid)?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        // Entries are flattened into the upload directory; hidden files and
+        // macOS resource forks are not data.
+        let path = entry.path().map_err(invalid)?.into_owned();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if name.starts_with('.') || path.components().any(|c| c.as_os_str() == "__MACOSX") {
+            continue;
+        }
+        if reserved
+            .iter()
+            .any(|other| other.eq_ignore_ascii_case(name))
+        {
+            cleanup(&names);
+            return Err(UnpackError::Invalid(format!(
+                "Archive holds a file named {name}, which the upload itself uses"
+            )));
+        }
+        if names.iter().any(|seen| seen.eq_ignore_ascii_case(name)) {
+            cleanup(&names);
+            return Err(UnpackError::Invalid(format!(
+                "Archive holds more than one file named {name}"
+            )));
+        }
+        let name = name.to_string();
+        match write_limited(entry, &dir.join(&name), remaining) {
+            Ok(written) => remaining -= written,
+            Err(e) => {
+                cleanup(&names);
+                return Err(e);
+            }
+        }
+        names.push(name);
+    }
+
+    let dataset = pick_dataset(dir, stem, &names);
+    let keep = dataset.as_ref().ok().and_then(|path| path.file_name());
+    cleanup(
+        &names
+            .iter()
+            .filter(|name| keep != Some(std::ffi::OsStr::new(name)))
+            .cloned()
+            .collect::<Vec<_>>(),
+    );
+    dataset
+}
+
+fn extension(name: &str) -> String {
+    Path::new(name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+/// The dataset file among a tarball's unpacked `names`: a zip of its
+/// shapefile parts, or its one file of a supported type.
+fn pick_dataset(dir: &Path, stem: &str, names: &[String]) -> Result<PathBuf, UnpackError> {
+    if names.iter().any(|name| extension(name) == "shp") {
+        let parts: Vec<&String> = names
+            .iter()
+            .filter(|name| SHAPEFILE_PARTS.contains(&extension(name).as_str()))
+            .collect();
+        let target = dir.join(format!("{stem}.zip"));
+        zip_files(dir, &parts, &target)
+            .map_err(|e| UnpackError::Invalid(format!("Unable to package shapefile: {e}")))?;
+        return Ok(target);
+    }
+
+    let datasets: Vec<&String> = names
+        .iter()
+        .filter(|name| crate::upload_file_type(name).is_some())
+        .collect();
+    match datasets.as_slice() {
+        [name] => Ok(dir.join(name)),
+        [] => Err(UnpackError::Invalid(
+            "Archive holds no supported dataset file".to_string(),
+        )),
+        _ => Err(UnpackError::Invalid(
+            "Archive holds more than one dataset; upload them one at a time".to_string(),
+        )),
+    }
+}
+
+fn zip_files(dir: &Path, names: &[&String], target: &Path) -> std::io::Result<()> {
+    let mut zip = ZipWriter::new(File::create(target)?);
+    for name in names {
+        zip.start_file(name.as_str(), FileOptions::default())?;
+        std::io::copy(&mut File::open(dir.join(name))?, &mut zip)?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+
+    fn gzip(bytes: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn tarball(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, bytes) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(bytes.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, *bytes).unwrap();
+        }
+        gzip(&builder.into_inner().unwrap())
+    }
+
+    #[test]
+    fn compression_is_read_from_the_name() {
+        assert_eq!(
+            Compression::of("roads.geojson.gz"),
+            Some((Compression::Gzip, "roads.geojson".to_string()))
+        );
+        assert_eq!(
+            Compression::of("City.TAR.GZ"),
+            Some((Compression::TarGzip, "City".to_string()))
+        );
+        assert_eq!(
+            Compression::of("city.tgz"),
+            Some((Compression::TarGzip, "city".to_string()))
+        );
+        assert_eq!(Compression::of("roads.geojson"), None);
+        assert_eq!(Compression::of(".gz"), None);
+    }
+
+    #[test]
+    fn gzip_is_unpacked_within_the_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("roads.geojson.gz");
+        std::fs::write(&archive, gzip(&[b' '; 4096])).unwrap();
+
+        assert_eq!(
+            unpack_upload(&archive, Compression::Gzip, "roads.geojson", 1024),
+            Err(UnpackError::TooLarge)
+        );
+        assert!(!dir.path().join("roads.geojson").exists());
+
+        std::fs::write(&archive, gzip(b"{}")).unwrap();
+        let unpacked = unpack_upload(&archive, Compression::Gzip, "roads.geojson", 1024).unwrap();
+        assert_eq!(std::fs::read(&unpacked).unwrap(), b"{}");
+        assert!(!archive.exists());
+    }
+
+    #[test]
+    fn tarball_yields_its_dataset() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("city.tar.gz");
+
+        std::fs::write(
+            &archive,
+            tarball(&[("data/roads.geojson", b"{}"), ("README.txt", b"hi")]),
+        )
+        .unwrap();
+        let unpacked = unpack_upload(&archive, Compression::TarGzip, "city", 1024).unwrap();
+        assert_eq!(unpacked, dir.path().join("roads.geojson"));
+        assert!(!dir.path().join("README.txt").exists());
+
+        std::fs::write(
+            &archive,
+            tarball(&[("a.shp", b"shp"), ("a.shx", b"shx"), ("a.dbf", b"dbf")]),
+        )
+        .unwrap();
+        let unpacked = unpack_upload(&archive, Compression::TarGzip, "city", 1024).unwrap();
+        assert_eq!(unpacked, dir.path().join("city.zip"));
+        assert!(!dir.path().join("a.shp").exists());
+
+        std::fs::write(
+            &archive,
+            tarball(&[("a.geojson", b"{}"), ("b.kml", b"<kml/>")]),
+        )
+        .unwrap();
+        assert!(matches!(
+            unpack_upload(&archive, Compression::TarGzip, "city", 1024),
+            Err(UnpackError::Invalid(_))
+        ));
+
+        std::fs::write(
+            &archive,
+            tarball(&[("a.geojson", &[b' '; 800]), ("b.txt", &[b' '; 800])]),
+        )
+        .unwrap();
+        assert_eq!(
+            unpack_upload(&archive, Compression::TarGzip, "city", 1024),
+            Err(UnpackError::TooLarge)
+        );
+        assert!(!dir.path().join("a.geojson").exists());
+    }
+
+    #[test]
+    fn tarball_entries_cannot_replace_the_upload() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("city.tar.gz");
+
+        for name in ["data/city.tar.gz", "CITY.zip"] {
+            std::fs::write(
+                &archive,
+                tarball(&[("a.shp", b"shp"), (name, b"x"), ("a.dbf", b"dbf")]),
+            )
+            .unwrap();
+            assert!(
+                matches!(
+                    unpack_upload(&archive, Compression::TarGzip, "city", 1024),
+                    Err(UnpackError::Invalid(_))
+                ),
+                "{name}"
+            );
+            assert!(!dir.path().join("a.shp").exists());
+        }
+    }
+}
@@ -1,43 +1,296 @@
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::auth::Role;
+use crate::ldap::{LdapConfig, DEFAULT_GROUP_ATTRIBUTE, DEFAULT_USER_FILTER};
+use crate::logging::LogFormat;
+use crate::password_reset::ResetDelivery;
+use crate::scan::UploadScanner;
+use crate::settings::normalize_base_url;
+
 const DEFAULT_MAX_SIZE_MB: u64 = 200;
+const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 30;
 const BYTES_PER_MB: u64 = 1024 * 1024;
 
-/// Read CORS allowed origins from environment variable
-/// Format: comma-separated list of origins (e.g., "http://localhost:5173,https://example.com")
-/// Defaults to allowing development origins if not set
-pub fn read_cors_origins() -> Vec<String> {
-    std::env::var("CORS_ALLOWED_ORIGINS")
-        .ok()
-        .map(|value| {
-            value
-                .split(',')
-                .map(|s| s.trim().to_string())
-                .filter(|s| !s.is_empty())
-                .collect()
-        })
-        .unwrap_or_else(|| {
-            // Default to development origins
-            vec![
-                "http://localhost:5173".to_string(), // Vite dev server
-                "http://localhost:3000".to_string(), // Production preview
-            ]
-        })
+/// Read when `MAPFLOW_CONFIG` is unset; a missing file means defaults.
+pub const DEFAULT_CONFIG_PATH: &str = "mapflow.toml";
+/// Points at a config file that must exist.
+pub const CONFIG_PATH_ENV: &str = "MAPFLOW_CONFIG";
+
+/// Startup configuration.
+///
+/// Precedence is built-in defaults < `mapflow.toml` < environment variables:
+/// every key can be set in the file (snake_case, e.g. `upload_max_size_mb`)
+/// and overridden by the upper-case environment variable of the same name.
+/// Unparseable environment values are ignored; unknown or invalid file keys
+/// are an error, so typos don't go unnoticed.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub port: u16,
+    pub db_path: PathBuf,
+    pub upload_dir: PathBuf,
+    /// Built frontend served for non-API paths, when it exists.
+    pub web_dist: PathBuf,
+    pub upload_max_size_mb: u64,
+    pub cors_allowed_origins: Vec<String>,
+    pub cookie_secure: bool,
+    pub seed_demo: bool,
+    /// Seconds between expiry-only session writes. Session data changes are
+    /// always written immediately.
+    pub session_write_interval_secs: u64,
+    /// `tracing` filter directives, e.g. `info` or `backend=debug,info`.
+    pub log_level: String,
+    pub log_format: LogFormat,
+    /// Connections serving tiles and read-only queries next to the writer.
+    pub read_pool_size: usize,
+    /// Seconds in-flight requests get to finish after SIGTERM/SIGINT.
+    pub shutdown_timeout_secs: u64,
+    /// Scanner run on each upload before import, e.g. `clamdscan --no-summary
+    /// {path}`; see `scan.rs`.
+    pub upload_scan_command: Option<String>,
+    /// clamd `host:port` to scan uploads with instead of a command.
+    pub upload_scan_clamd: Option<String>,
+    /// PostGIS connection string datasets can be exported to.
+    pub postgis_export_connection: Option<String>,
+    /// Schemas of that database exports may write to.
+    pub postgis_export_schemas: Vec<String>,
+    /// Origin generated links start with, e.g. `https://maps.example.com`,
+    /// until an admin saves another in the runtime settings.
+    pub public_base_url: Option<String>,
+    /// Without a base URL, build absolute links in public responses from the
+    /// request's `X-Forwarded-Host`/`-Proto` or `Host`. Only for deployments
+    /// behind a proxy that sets them; otherwise those links stay relative.
+    pub trust_forwarded_headers: bool,
+    /// Let `POST /api/uploads/url` and `/api/uploads/postgis` connect to
+    /// loopback and private network addresses, which are refused by default;
+    /// see `remote.rs`.
+    pub remote_import_allow_private_hosts: bool,
+    /// Program that delivers self-service password reset tokens, e.g. a mail
+    /// script; without one only an admin can have a token issued. See
+    /// `password_reset.rs`.
+    pub password_reset_command: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            port: 3000,
+            db_path: PathBuf::from(crate::DEFAULT_DB_PATH),
+            upload_dir: PathBuf::from("./uploads"),
+            web_dist: PathBuf::from("frontend/dist"),
+            upload_max_size_mb: DEFAULT_MAX_SIZE_MB,
+            // Development origins: the Vite dev server and the production preview.
+            cors_allowed_origins: vec![
+                "http://localhost:5173".to_string(),
+                "http://localhost:3000".to_string(),
+            ],
+            cookie_secure: false,
+            seed_demo: false,
+            session_write_interval_secs: crate::DEFAULT_SESSION_WRITE_INTERVAL.as_secs(),
+            log_level: "info".to_string(),
+            log_format: LogFormat::Text,
+            read_pool_size: crate::DEFAULT_READ_POOL_SIZE,
+            shutdown_timeout_secs: DEFAULT_SHUTDOWN_TIMEOUT_SECS,
+            upload_scan_command: None,
+            upload_scan_clamd: None,
+            postgis_export_connection: None,
+            postgis_export_schemas: vec!["public".to_string()],
+            public_base_url: None,
+            trust_forwarded_headers: false,
+            remote_import_allow_private_hosts: false,
+            password_reset_command: None,
+        }
+    }
 }
 
-pub fn read_cookie_secure() -> bool {
-    std::env::var("COOKIE_SECURE")
-        .ok()
-        .and_then(|value| value.parse::<bool>().ok())
-        .unwrap_or(false)
+impl Config {
+    /// Load `MAPFLOW_CONFIG` (or `./mapflow.toml` if present) and apply the
+    /// process environment on top.
+    pub fn load() -> Result<Config, String> {
+        let explicit = std::env::var(CONFIG_PATH_ENV)
+            .ok()
+            .filter(|path| !path.is_empty());
+        let mut config = match &explicit {
+            Some(path) => Config::from_file(Path::new(path))?,
+            None if Path::new(DEFAULT_CONFIG_PATH).exists() => {
+                Config::from_file(Path::new(DEFAULT_CONFIG_PATH))?
+            }
+            None => Config::default(),
+        };
+        config.apply_env(|name| std::env::var(name).ok());
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Config, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        Config::from_toml(&text).map_err(|e| format!("Invalid {}: {e}", path.display()))
+    }
+
+    pub fn from_toml(text: &str) -> Result<Config, String> {
+        let mut config: Config = toml::from_str(text).map_err(|e| e.to_string())?;
+        if config.upload_max_size_mb == 0 {
+            return Err("upload_max_size_mb must be positive".to_string());
+        }
+        if config.upload_scan_command.is_some() && config.upload_scan_clamd.is_some() {
+            return Err("Set only one of upload_scan_command and upload_scan_clamd".to_string());
+        }
+        if let Some(url) = &config.public_base_url {
+            config.public_base_url =
+                normalize_base_url(url).map_err(|e| format!("public_base_url {e}"))?;
+        }
+        Ok(config)
+    }
+
+    /// Override fields from environment variables, looked up through `var`.
+    pub fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) {
+        let var = |name: &str| var(name).filter(|value| !value.is_empty());
+        let parsed = |name: &str| var(name).and_then(|value| value.parse().ok());
+
+        if let Some(port) = parsed("PORT") {
+            self.port = port;
+        }
+        if let Some(path) = var("DB_PATH") {
+            self.db_path = PathBuf::from(path);
+        }
+        if let Some(path) = var("UPLOAD_DIR") {
+            self.upload_dir = PathBuf::from(path);
+        }
+        if let Some(path) = var("WEB_DIST") {
+            self.web_dist = PathBuf::from(path);
+        }
+        if let Some(size) = parsed("UPLOAD_MAX_SIZE_MB").filter(|size: &u64| *size > 0) {
+            self.upload_max_size_mb = size;
+        }
+        // Comma-separated, e.g. "http://localhost:5173,https://example.com".
+        if let Some(origins) = var("CORS_ALLOWED_ORIGINS") {
+            self.cors_allowed_origins = origins
+                .split(',')
+                .map(|origin| origin.trim().to_string())
+                .filter(|origin| !origin.is_empty())
+                .collect();
+        }
+        if let Some(secure) = parsed("COOKIE_SECURE") {
+            self.cookie_secure = secure;
+        }
+        if let Some(seed) = parsed("SEED_DEMO") {
+            self.seed_demo = seed;
+        }
+        if let Some(secs) = parsed("SESSION_WRITE_INTERVAL_SECS") {
+            self.session_write_interval_secs = secs;
+        }
+        if let Some(level) = var("LOG_LEVEL") {
+            self.log_level = level;
+        }
+        if let Some(format) = var("LOG_FORMAT").and_then(|value| LogFormat::parse(&value)) {
+            self.log_format = format;
+        }
+        if let Some(size) = parsed("READ_POOL_SIZE").filter(|size: &usize| *size > 0) {
+            self.read_pool_size = size;
+        }
+        if let Some(secs) = parsed("SHUTDOWN_TIMEOUT_SECS") {
+            self.shutdown_timeout_secs = secs;
+        }
+        // Either variable replaces a scanner set in the file.
+        if let Some(command) = var("UPLOAD_SCAN_COMMAND") {
+            self.upload_scan_command = Some(command);
+            self.upload_scan_clamd = None;
+        }
+        if let Some(address) = var("UPLOAD_SCAN_CLAMD") {
+            self.upload_scan_clamd = Some(address);
+            self.upload_scan_command = None;
+        }
+        if let Some(connection) = var("POSTGIS_EXPORT_CONNECTION") {
+            self.postgis_export_connection = Some(connection);
+        }
+        // Comma-separated, e.g. "public,staging".
+        if let Some(schemas) = var("POSTGIS_EXPORT_SCHEMAS") {
+            self.postgis_export_schemas = schemas
+                .split(',')
+                .map(|schema| schema.trim().to_string())
+                .filter(|schema| !schema.is_empty())
+                .collect();
+        }
+        if let Some(url) = var("PUBLIC_BASE_URL").and_then(|url| normalize_base_url(&url).ok()) {
+            self.public_base_url = url;
+        }
+        if let Some(trust) = parsed("TRUST_FORWARDED_HEADERS") {
+            self.trust_forwarded_headers = trust;
+        }
+        if let Some(allow) = parsed("REMOTE_IMPORT_ALLOW_PRIVATE_HOSTS") {
+            self.remote_import_allow_private_hosts = allow;
+        }
+        if let Some(command) = var("PASSWORD_RESET_COMMAND") {
+            self.password_reset_command = Some(command);
+        }
+    }
+
+    /// Upload limit in bytes, with its label for error messages.
+    pub fn max_size(&self) -> (u64, String) {
+        let bytes = self.upload_max_size_mb.saturating_mul(BYTES_PER_MB);
+        (bytes, format_bytes(bytes))
+    }
+
+    pub fn session_write_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.session_write_interval_secs)
+    }
+
+    pub fn shutdown_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.shutdown_timeout_secs)
+    }
+
+    pub fn upload_scanner(&self) -> Option<UploadScanner> {
+        match (&self.upload_scan_command, &self.upload_scan_clamd) {
+            (Some(command), _) => UploadScanner::command(command),
+            (None, Some(address)) => Some(UploadScanner::Clamd(address.trim().to_string())),
+            (None, None) => None,
+        }
+    }
+
+    pub fn password_reset_delivery(&self) -> Option<ResetDelivery> {
+        self.password_reset_command
+            .as_deref()
+            .and_then(ResetDelivery::command)
+    }
 }
 
-pub fn read_max_size_config() -> (u64, String) {
-    let max_size_mb = std::env::var("UPLOAD_MAX_SIZE_MB")
-        .ok()
-        .and_then(|value| value.parse::<u64>().ok())
-        .filter(|value| *value > 0)
-        .unwrap_or(DEFAULT_MAX_SIZE_MB);
-    let bytes = max_size_mb.saturating_mul(BYTES_PER_MB);
-    (bytes, format_bytes(bytes))
+/// Directory settings when `AUTH_BACKEND=ldap`, `None` for local accounts.
+/// Fails on an unknown backend or a missing required variable.
+pub fn read_ldap_config() -> Result<Option<LdapConfig>, String> {
+    let optional = |name: &str| {
+        std::env::var(name)
+            .ok()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+    let required = |name: &str| optional(name).ok_or_else(|| format!("{name} is required"));
+
+    match optional("AUTH_BACKEND").as_deref() {
+        None | Some("local") => return Ok(None),
+        Some("ldap") => {}
+        Some(other) => return Err(format!("Unknown AUTH_BACKEND '{other}'")),
+    }
+    let default_role = match optional("LDAP_DEFAULT_ROLE") {
+        Some(role) => {
+            Role::parse(&role).ok_or_else(|| format!("Unknown LDAP_DEFAULT_ROLE '{role}'"))?
+        }
+        None => Role::Viewer,
+    };
+    Ok(Some(LdapConfig {
+        url: required("LDAP_URL")?,
+        bind_dn: optional("LDAP_BIND_DN"),
+        bind_password: optional("LDAP_BIND_PASSWORD"),
+        user_base_dn: required("LDAP_USER_BASE_DN")?,
+        user_filter: optional("LDAP_USER_FILTER")
+            .unwrap_or_else(|| DEFAULT_USER_FILTER.to_string()),
+        group_attribute: optional("LDAP_GROUP_ATTRIBUTE")
+            .unwrap_or_else(|| DEFAULT_GROUP_ATTRIBUTE.to_string()),
+        admin_group: optional("LDAP_ADMIN_GROUP"),
+        editor_group: optional("LDAP_EDITOR_GROUP"),
+        default_role,
+    }))
 }
 
 pub fn format_bytes(bytes: u64) -> String {
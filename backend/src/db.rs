@@ -18,13 +18,87 @@ const DEV_SPATIAL_EXTENSION_RELATIVE_PATH: &str = "backend/extensions/spatial.du
 
 static SPATIAL_INSTALL_LOCK: OnceLock<StdMutex<()>> = OnceLock::new();
 
+fn fail_processing_files(conn: &duckdb::Connection) -> Result<usize, duckdb::Error> {
+    conn.execute(
+        "UPDATE files SET status = 'failed', error = ?, failed_at = ? WHERE status = 'processing'",
+        duckdb::params![
+            PROCESSING_RECONCILIATION_ERROR,
+            chrono::Utc::now().naive_utc()
+        ],
+    )
+}
+
+fn fail_export_jobs(conn: &duckdb::Connection) -> Result<usize, duckdb::Error> {
+    conn.execute(
+        "UPDATE export_jobs SET status = 'failed', error = ? WHERE status IN ('pending', 'processing')",
+        duckdb::params![PROCESSING_RECONCILIATION_ERROR],
+    )
+}
+
+fn fail_seed_jobs(conn: &duckdb::Connection) -> Result<usize, duckdb::Error> {
+    conn.execute(
+        "UPDATE tile_seed_jobs SET status = 'failed', error = ? WHERE status IN ('pending', 'processing')",
+        duckdb::params![PROCESSING_RECONCILIATION_ERROR],
+    )
+}
+
 pub async fn reconcile_processing_files(
     db: &Arc<Mutex<duckdb::Connection>>,
+) -> Result<usize, duckdb::Error> {
+    fail_processing_files(&*db.lock().await)
+}
+
+pub async fn reconcile_export_jobs(
+    db: &Arc<Mutex<duckdb::Connection>>,
+) -> Result<usize, duckdb::Error> {
+    fail_export_jobs(&*db.lock().await)
+}
+
+pub async fn reconcile_seed_jobs(
+    db: &Arc<Mutex<duckdb::Connection>>,
+) -> Result<usize, duckdb::Error> {
+    fail_seed_jobs(&*db.lock().await)
+}
+
+/// Imports, exports and tile seeding still running when the server stops.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct InterruptedJobs {
+    pub files: usize,
+    pub exports: usize,
+    pub seeds: usize,
+}
+
+/// Last step before exit: fail the jobs that will not finish, marked as the
+/// startup reconciliation would mark them, then checkpoint so the next start
+/// has no WAL to replay.
+pub async fn shutdown_database(
+    db: &Arc<Mutex<duckdb::Connection>>,
+) -> Result<InterruptedJobs, duckdb::Error> {
+    let conn = db.lock().await;
+    let interrupted = InterruptedJobs {
+        files: fail_processing_files(&conn)?,
+        exports: fail_export_jobs(&conn)?,
+        seeds: fail_seed_jobs(&conn)?,
+    };
+    conn.execute_batch("CHECKPOINT")?;
+    Ok(interrupted)
+}
+
+/// How often the server looks for expired public links.
+pub const PUBLISH_EXPIRY_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Make files whose public link has expired private again. The
+/// `published_files` row stays, so the slug keeps answering 410 until the file
+/// is unpublished or published again.
+pub async fn expire_published_files(
+    db: &Arc<Mutex<duckdb::Connection>>,
 ) -> Result<usize, duckdb::Error> {
     let conn = db.lock().await;
     conn.execute(
-        "UPDATE files SET status = 'failed', error = ? WHERE status = 'processing'",
-        duckdb::params![PROCESSING_RECONCILIATION_ERROR],
+        "UPDATE files SET is_public = FALSE
+         WHERE is_public = TRUE
+           AND id IN (SELECT file_id FROM published_files WHERE expires_at <= ?)",
+        duckdb::params![chrono::Utc::now().naive_utc()],
     )
 }
 
@@ -36,101 +110,15 @@ pub fn init_database(db_path: &Path) -> duckdb::Connection {
     let conn = duckdb::Connection::open(db_path).expect("Failed to open database");
 
     ensure_spatial_extension(&conn).expect("Failed to install and load spatial extension");
-
-    conn.execute_batch(
-        r"
-        CREATE TABLE IF NOT EXISTS files (
-            id VARCHAR PRIMARY KEY,
-            name VARCHAR NOT NULL,
-            type VARCHAR NOT NULL,
-            size BIGINT NOT NULL,
-            uploaded_at TIMESTAMP NOT NULL,
-            status VARCHAR NOT NULL,
-            crs VARCHAR,
-            path VARCHAR NOT NULL,
-            table_name VARCHAR,
-            error VARCHAR,
-            is_public BOOLEAN DEFAULT FALSE,
-            tile_format VARCHAR,
-            minzoom INTEGER,
-            maxzoom INTEGER,
-            tile_bounds VARCHAR
-        );
-
-        CREATE TABLE IF NOT EXISTS published_files (
-            file_id VARCHAR PRIMARY KEY,
-            slug VARCHAR UNIQUE NOT NULL,
-            published_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
-            FOREIGN KEY (file_id) REFERENCES files(id)
-        );
-        ",
-    )
-    .expect("Failed to create files table");
-
-    // Add new columns for MBTiles support (if they don't exist)
-    // These ALTER TABLE statements are idempotent - they will fail silently if columns exist
-    let _ = conn.execute("ALTER TABLE files ADD COLUMN tile_format VARCHAR", []);
-    let _ = conn.execute("ALTER TABLE files ADD COLUMN minzoom INTEGER", []);
-    let _ = conn.execute("ALTER TABLE files ADD COLUMN maxzoom INTEGER", []);
-    let _ = conn.execute("ALTER TABLE files ADD COLUMN tile_bounds VARCHAR", []);
-
-    conn.execute_batch(
-        r"
-        CREATE TABLE IF NOT EXISTS dataset_columns (
-            source_id VARCHAR NOT NULL,
-            normalized_name VARCHAR NOT NULL,
-            original_name VARCHAR NOT NULL,
-            ordinal BIGINT NOT NULL,
-            mvt_type VARCHAR NOT NULL,
-            PRIMARY KEY (source_id, normalized_name)
-        );
-
-        CREATE INDEX IF NOT EXISTS idx_dataset_columns_source
-            ON dataset_columns(source_id);
-        ",
-    )
-    .expect("Failed to create dataset metadata tables");
-
-    conn.execute_batch(
-        r"
-        CREATE TABLE IF NOT EXISTS users (
-            id VARCHAR PRIMARY KEY,
-            username VARCHAR UNIQUE NOT NULL,
-            password_hash VARCHAR NOT NULL,
-            role VARCHAR NOT NULL,
-            created_at TIMESTAMP NOT NULL
-        );
-
-        CREATE INDEX IF NOT EXISTS idx_users_username
-            ON users(username);
-        ",
-    )
-    .expect("Failed to create users table");
-
-    conn.execute_batch(
-        r"
-        CREATE TABLE IF NOT EXISTS sessions (
-            id VARCHAR PRIMARY KEY,
-            data VARCHAR NOT NULL,
-            expiry_date TIMESTAMP NOT NULL,
-            created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
-        );
-
-        CREATE INDEX IF NOT EXISTS idx_sessions_expiry_date
-            ON sessions(expiry_date);
-        ",
-    )
-    .expect("Failed to create sessions table");
-
-    conn.execute_batch(
-        r"
-        CREATE TABLE IF NOT EXISTS system_settings (
-            key VARCHAR PRIMARY KEY,
-            value VARCHAR NOT NULL
-        );
-        ",
-    )
-    .expect("Failed to create system_settings table");
+    crate::migrations::migrate(&conn)
+        .unwrap_or_else(|e| panic!("Failed to migrate database schema: {e}"));
+
+    // Tables imported before R-tree indexes were added get one now.
+    match crate::spatial_index::ensure_spatial_indexes(&conn) {
+        Ok(0) => {}
+        Ok(created) => tracing::info!(created, "Created spatial indexes on existing datasets"),
+        Err(e) => tracing::warn!(error = %e, "Failed to check spatial indexes"),
+    }
 
     conn
 }
@@ -309,6 +297,16 @@ pub fn set_initialized(conn: &duckdb::Connection) -> Result<(), duckdb::Error> {
     Ok(())
 }
 
+/// Record that a dataset's tiles changed (edited features, new tile options).
+/// Clients include the version in tile URLs, so cached tiles are not reused.
+pub fn bump_data_version(conn: &duckdb::Connection, file_id: &str) -> Result<(), duckdb::Error> {
+    conn.execute(
+        "UPDATE files SET data_version = COALESCE(data_version, 0) + 1 WHERE id = ?",
+        duckdb::params![file_id],
+    )?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,252 @@
+//! Dataset export jobs
+//!
+//! Exports run in the background like imports: a job row starts as `pending`,
+//! moves to `processing` and ends as `ready` (file written next to the upload)
+//! or `failed`. Exported property columns carry their original names again, and
+//! GeoPackages carry the dataset's tile options as layer metadata.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use duckdb::OptionalExt;
+use tokio::sync::Mutex;
+
+use crate::columns::{output_column_aliases, quote_identifier, quote_literal};
+use crate::models::{ExportJob, TileOptions};
+use crate::tile_options::load_tile_options;
+
+/// `md_standard_uri` of the GeoPackage metadata row that carries tile options.
+pub const TILE_OPTIONS_METADATA_URI: &str = "urn:mapflow:tile-options";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Gpkg,
+}
+
+impl ExportFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "gpkg" | "geopackage" => Some(Self::Gpkg),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Gpkg => "gpkg",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Gpkg => "gpkg",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Gpkg => "application/geopackage+sqlite3",
+        }
+    }
+
+    fn gdal_driver(self) -> &'static str {
+        match self {
+            Self::Gpkg => "GPKG",
+        }
+    }
+}
+
+pub fn export_download_url(job_id: &str) -> String {
+    format!("/api/exports/{job_id}/download")
+}
+
+pub fn load_export_job(
+    conn: &duckdb::Connection,
+    job_id: &str,
+) -> Result<Option<ExportJob>, duckdb::Error> {
+    conn.query_row(
+        "SELECT id, file_id, format, status, created_at, finished_at, error, path FROM export_jobs WHERE id = ?",
+        duckdb::params![job_id],
+        |row| {
+            let id: String = row.get(0)?;
+            let status: String = row.get(3)?;
+            let created_at: chrono::NaiveDateTime = row.get(4)?;
+            let finished_at: Option<chrono::NaiveDateTime> = row.get(5)?;
+            // Exports to PostGIS write no file.
+            let path: Option<String> = row.get(7)?;
+            let download_url = (status == "ready" && path.is_some())
+                .then(|| export_download_url(&id));
+            Ok(ExportJob {
+                file_id: row.get(1)?,
+                format: row.get(2)?,
+                created_at: created_at.and_utc().to_rfc3339(),
+                finished_at: finished_at.map(|ts| ts.and_utc().to_rfc3339()),
+                error: row.get(6)?,
+                download_url,
+                status,
+                id,
+            })
+        },
+    )
+    .optional()
+}
+
+/// A dataset's property columns as (normalized, original) names, in order.
+pub fn column_names(
+    conn: &duckdb::Connection,
+    file_id: &str,
+) -> Result<Vec<(String, String)>, String> {
+    let mut cols_stmt = conn
+        .prepare(
+            "SELECT normalized_name, original_name\n             FROM dataset_columns\n             WHERE source_id = ?\n             ORDER BY ordinal",
+        )
+        .map_err(|e| format!("Metadata query failed: {}", e))?;
+    let columns = cols_stmt
+        .query_map(duckdb::params![file_id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(|e| format!("Metadata query failed: {}", e))?;
+    columns
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Metadata query failed: {}", e))
+}
+
+/// A dataset's property columns, selected under their original names.
+pub fn exported_columns(conn: &duckdb::Connection, file_id: &str) -> Result<Vec<String>, String> {
+    let columns = column_names(conn, file_id)?;
+    Ok(output_column_aliases(&columns)
+        .iter()
+        .map(|(normalized, alias)| {
+            format!(
+                "{} AS {}",
+                quote_identifier(normalized),
+                quote_identifier(alias)
+            )
+        })
+        .collect())
+}
+
+pub async fn export_dataset(
+    db: &Arc<Mutex<duckdb::Connection>>,
+    file_id: &str,
+    format: ExportFormat,
+    out_path: &Path,
+) -> Result<(), String> {
+    let out_path = out_path
+        .to_str()
+        .ok_or_else(|| format!("Export path is not valid UTF-8: {}", out_path.display()))?
+        .to_string();
+
+    let conn = db.lock().await;
+
+    let (name, crs, table_name): (String, Option<String>, Option<String>) = conn
+        .query_row(
+            "SELECT name, crs, table_name FROM files WHERE id = ?",
+            duckdb::params![file_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .map_err(|e| format!("File lookup failed: {}", e))?;
+    let table_name = table_name.ok_or("File has no imported table")?;
+
+    let mut select_exprs = exported_columns(&conn, file_id)?;
+    select_exprs.push("geom".to_string());
+
+    // CRS is written as layer SRS metadata; the geometries are exported untransformed.
+    let copy_sql = format!(
+        "COPY (SELECT {} FROM {} ORDER BY fid) TO {} WITH (FORMAT GDAL, DRIVER {}, SRS {}, LAYER_NAME {})",
+        select_exprs.join(", "),
+        quote_identifier(&table_name),
+        quote_literal(&out_path),
+        quote_literal(format.gdal_driver()),
+        quote_literal(crs.as_deref().unwrap_or("EPSG:4326")),
+        quote_literal(&name),
+    );
+
+    conn.execute_batch(&copy_sql)
+        .map_err(|e| format!("Export failed: {}", e))?;
+
+    let tile_options = load_tile_options(&conn, file_id)
+        .map_err(|e| format!("Tile options lookup failed: {}", e))?
+        .unwrap_or_default();
+    drop(conn);
+
+    if !tile_options.is_empty() {
+        write_gpkg_tile_options(Path::new(&out_path), &name, &tile_options)
+            .map_err(|e| format!("Failed to write tile options: {}", e))?;
+    }
+
+    Ok(())
+}
+
+/// Store tile options in the GeoPackage metadata extension, referenced from the
+/// exported layer, so a re-import can restore the dataset's tile configuration.
+fn write_gpkg_tile_options(
+    path: &Path,
+    layer_name: &str,
+    options: &TileOptions,
+) -> rusqlite::Result<()> {
+    let json = serde_json::to_string(options).expect("tile options serialize");
+    let conn = rusqlite::Connection::open(path)?;
+
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS gpkg_metadata (
+            id INTEGER CONSTRAINT m_pk PRIMARY KEY ASC NOT NULL,
+            md_scope TEXT NOT NULL DEFAULT 'dataset',
+            md_standard_uri TEXT NOT NULL,
+            mime_type TEXT NOT NULL DEFAULT 'text/xml',
+            metadata TEXT NOT NULL DEFAULT ''
+        );
+        CREATE TABLE IF NOT EXISTS gpkg_metadata_reference (
+            reference_scope TEXT NOT NULL,
+            table_name TEXT,
+            column_name TEXT,
+            row_id_value INTEGER,
+            timestamp DATETIME NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+            md_file_id INTEGER NOT NULL,
+            md_parent_id INTEGER,
+            CONSTRAINT crmr_mfi_fk FOREIGN KEY (md_file_id) REFERENCES gpkg_metadata(id),
+            CONSTRAINT crmr_mpi_fk FOREIGN KEY (md_parent_id) REFERENCES gpkg_metadata(id)
+        );
+        CREATE TABLE IF NOT EXISTS gpkg_extensions (
+            table_name TEXT,
+            column_name TEXT,
+            extension_name TEXT NOT NULL,
+            definition TEXT NOT NULL,
+            scope TEXT NOT NULL,
+            CONSTRAINT ge_tce UNIQUE (table_name, column_name, extension_name)
+        );
+        INSERT OR IGNORE INTO gpkg_extensions (table_name, column_name, extension_name, definition, scope)
+        VALUES
+            ('gpkg_metadata', NULL, 'gpkg_metadata', 'http://www.geopackage.org/spec120/#extension_metadata', 'read-write'),
+            ('gpkg_metadata_reference', NULL, 'gpkg_metadata', 'http://www.geopackage.org/spec120/#extension_metadata', 'read-write');",
+    )?;
+
+    conn.execute(
+        "INSERT INTO gpkg_metadata (md_scope, md_standard_uri, mime_type, metadata)
+         VALUES ('dataset', ?1, 'application/json', ?2)",
+        rusqlite::params![TILE_OPTIONS_METADATA_URI, json],
+    )?;
+    let metadata_id = conn.last_insert_rowid();
+    conn.execute(
+        "INSERT INTO gpkg_metadata_reference (reference_scope, table_name, md_file_id)
+         VALUES ('table', ?1, ?2)",
+        rusqlite::params![layer_name, metadata_id],
+    )?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_accepts_gpkg_aliases() {
+        assert_eq!(ExportFormat::parse("gpkg"), Some(ExportFormat::Gpkg));
+        assert_eq!(
+            ExportFormat::parse(" GeoPackage "),
+            Some(ExportFormat::Gpkg)
+        );
+        assert_eq!(ExportFormat::parse("shp"), None);
+    }
+}
@@ -0,0 +1,270 @@
+//! Feature editing
+//!
+//! Property edits come in as JSON keyed by original column names. Each value is
+//! checked against the column's recorded `mvt_type` before anything is written,
+//! so a bad value rejects the whole edit instead of relying on DuckDB's implicit
+//! casts. Geometries are GeoJSON (WGS84) and are reprojected to the table CRS.
+
+use duckdb::types::Value;
+
+use crate::columns::{quote_identifier, quote_literal, resolve_column, DatasetColumn};
+
+/// Convert a JSON value for `column`; `null` clears the property.
+pub fn json_to_column_value(
+    column: &DatasetColumn,
+    value: &serde_json::Value,
+) -> Result<Value, String> {
+    let mismatch = || {
+        format!(
+            "Value for '{}' must be a {}",
+            column.original, column.mvt_type
+        )
+    };
+    if value.is_null() {
+        return Ok(Value::Null);
+    }
+
+    match column.mvt_type.as_str() {
+        "INTEGER" => value
+            .as_i64()
+            .and_then(|v| i32::try_from(v).ok())
+            .map(Value::Int)
+            .ok_or_else(mismatch),
+        "BIGINT" => value.as_i64().map(Value::BigInt).ok_or_else(mismatch),
+        "DOUBLE" | "FLOAT" => value.as_f64().map(Value::Double).ok_or_else(mismatch),
+        "BOOLEAN" => value.as_bool().map(Value::Boolean).ok_or_else(mismatch),
+        _ => value
+            .as_str()
+            .map(|s| Value::Text(s.to_string()))
+            .ok_or_else(mismatch),
+    }
+}
+
+/// Resolve and type-check every property of an edit.
+pub fn parse_property_updates(
+    columns: &[DatasetColumn],
+    properties: &serde_json::Map<String, serde_json::Value>,
+) -> Result<Vec<(DatasetColumn, Value)>, String> {
+    if properties.is_empty() {
+        return Err("No properties to update".to_string());
+    }
+
+    let mut updates: Vec<(DatasetColumn, Value)> = Vec::with_capacity(properties.len());
+    for (name, value) in properties {
+        let column =
+            resolve_column(columns, name).ok_or_else(|| format!("Unknown property '{name}'"))?;
+        if updates
+            .iter()
+            .any(|(existing, _)| existing.normalized == column.normalized)
+        {
+            return Err(format!(
+                "Property '{}' is given more than once",
+                column.original
+            ));
+        }
+        updates.push((column.clone(), json_to_column_value(column, value)?));
+    }
+    Ok(updates)
+}
+
+const GEOMETRY_TYPES: &[&str] = &[
+    "Point",
+    "MultiPoint",
+    "LineString",
+    "MultiLineString",
+    "Polygon",
+    "MultiPolygon",
+    "GeometryCollection",
+];
+
+/// Check the shape of a GeoJSON geometry object and return it as text for
+/// `ST_GeomFromGeoJSON`. Coordinate validity is left to DuckDB.
+pub fn geojson_geometry_text(geometry: &serde_json::Value) -> Result<String, String> {
+    let kind = geometry
+        .get("type")
+        .and_then(|t| t.as_str())
+        .ok_or_else(|| "Geometry must be a GeoJSON geometry object".to_string())?;
+    if !GEOMETRY_TYPES.contains(&kind) {
+        return Err(format!("Unsupported geometry type '{kind}'"));
+    }
+    let member = if kind == "GeometryCollection" {
+        "geometries"
+    } else {
+        "coordinates"
+    };
+    if !geometry.get(member).is_some_and(|v| v.is_array()) {
+        return Err(format!("{kind} geometry must have '{member}'"));
+    }
+    Ok(geometry.to_string())
+}
+
+/// SQL expression turning a bound GeoJSON geometry into the table's CRS.
+fn geometry_param_sql(source_crs: &str) -> String {
+    format!(
+        "ST_Transform(ST_GeomFromGeoJSON(?), 'EPSG:4326', {}, always_xy := true)",
+        quote_literal(source_crs)
+    )
+}
+
+/// Replace one feature's geometry; returns the number of rows changed.
+pub fn replace_feature_geometry(
+    conn: &duckdb::Connection,
+    table_name: &str,
+    source_crs: &str,
+    fid: i64,
+    geometry: &str,
+) -> Result<usize, duckdb::Error> {
+    conn.execute(
+        &format!(
+            "UPDATE {} SET geom = {} WHERE fid = ?",
+            quote_identifier(table_name),
+            geometry_param_sql(source_crs)
+        ),
+        duckdb::params![geometry, fid],
+    )
+}
+
+/// Insert a feature with the next free fid and return that fid.
+pub fn insert_feature(
+    conn: &duckdb::Connection,
+    table_name: &str,
+    source_crs: &str,
+    geometry: &str,
+    values: &[(DatasetColumn, Value)],
+) -> Result<i64, duckdb::Error> {
+    let table = quote_identifier(table_name);
+    let mut targets = vec!["fid".to_string(), "geom".to_string()];
+    let mut exprs = vec![
+        format!("(SELECT COALESCE(MAX(fid), 0) + 1 FROM {table})"),
+        geometry_param_sql(source_crs),
+    ];
+    for (column, _) in values {
+        targets.push(quote_identifier(&column.normalized));
+        exprs.push(format!("CAST(? AS {})", column.mvt_type));
+    }
+
+    let mut params = vec![Value::Text(geometry.to_string())];
+    params.extend(values.iter().map(|(_, value)| value.clone()));
+
+    conn.query_row(
+        &format!(
+            "INSERT INTO {table} ({}) SELECT {} RETURNING fid",
+            targets.join(", "),
+            exprs.join(", ")
+        ),
+        duckdb::params_from_iter(params.iter()),
+        |row| row.get(0),
+    )
+}
+
+/// Apply property updates to one feature; returns the number of rows changed.
+pub fn update_feature_properties(
+    conn: &duckdb::Connection,
+    table_name: &str,
+    fid: i64,
+    updates: &[(DatasetColumn, Value)],
+) -> Result<usize, duckdb::Error> {
+    let assignments: Vec<String> = updates
+        .iter()
+        .map(|(column, _)| {
+            format!(
+                "{} = CAST(? AS {})",
+                quote_identifier(&column.normalized),
+                column.mvt_type
+            )
+        })
+        .collect();
+    let mut params: Vec<Value> = updates.iter().map(|(_, value)| value.clone()).collect();
+    params.push(Value::BigInt(fid));
+
+    conn.execute(
+        &format!(
+            "UPDATE {} SET {} WHERE fid = ?",
+            quote_identifier(table_name),
+            assignments.join(", ")
+        ),
+        duckdb::params_from_iter(params.iter()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(original: &str, mvt_type: &str) -> DatasetColumn {
+        DatasetColumn {
+            normalized: original.to_lowercase().replace(' ', "_"),
+            original: original.to_string(),
+            mvt_type: mvt_type.to_string(),
+        }
+    }
+
+    #[test]
+    fn values_are_checked_against_column_types() {
+        let lanes = column("lanes", "INTEGER");
+        assert_eq!(
+            json_to_column_value(&lanes, &serde_json::json!(3)),
+            Ok(Value::Int(3))
+        );
+        assert!(json_to_column_value(&lanes, &serde_json::json!(2.5)).is_err());
+        assert!(json_to_column_value(&lanes, &serde_json::json!("3")).is_err());
+        assert!(json_to_column_value(&lanes, &serde_json::json!(5_000_000_000i64)).is_err());
+
+        let speed = column("speed", "DOUBLE");
+        assert_eq!(
+            json_to_column_value(&speed, &serde_json::json!(50)),
+            Ok(Value::Double(50.0))
+        );
+
+        let oneway = column("oneway", "BOOLEAN");
+        assert!(json_to_column_value(&oneway, &serde_json::json!("true")).is_err());
+
+        let name = column("Road Name", "VARCHAR");
+        assert_eq!(
+            json_to_column_value(&name, &serde_json::json!("Main")),
+            Ok(Value::Text("Main".to_string()))
+        );
+        assert!(json_to_column_value(&name, &serde_json::json!(1)).is_err());
+        assert_eq!(
+            json_to_column_value(&name, &serde_json::Value::Null),
+            Ok(Value::Null)
+        );
+    }
+
+    #[test]
+    fn geometry_must_be_a_geojson_geometry() {
+        let point = serde_json::json!({ "type": "Point", "coordinates": [1.0, 2.0] });
+        assert_eq!(geojson_geometry_text(&point).unwrap(), point.to_string());
+        let collection = serde_json::json!({ "type": "GeometryCollection", "geometries": [point] });
+        assert!(geojson_geometry_text(&collection).is_ok());
+
+        for invalid in [
+            serde_json::json!({ "type": "Feature", "geometry": null }),
+            serde_json::json!({ "type": "Point" }),
+            serde_json::json!({ "type": "GeometryCollection", "coordinates": [] }),
+            serde_json::json!([1, 2]),
+            serde_json::Value::Null,
+        ] {
+            assert!(geojson_geometry_text(&invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn parse_rejects_unknown_duplicate_or_empty_edits() {
+        let columns = vec![column("Road Name", "VARCHAR"), column("lanes", "INTEGER")];
+        let edit = |value: serde_json::Value| value.as_object().unwrap().clone();
+
+        let updates =
+            parse_property_updates(&columns, &edit(serde_json::json!({"Road Name": "A"}))).unwrap();
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].0.normalized, "road_name");
+
+        assert!(parse_property_updates(&columns, &edit(serde_json::json!({}))).is_err());
+        assert!(parse_property_updates(&columns, &edit(serde_json::json!({"fid": 2}))).is_err());
+        assert!(parse_property_updates(
+            &columns,
+            &edit(serde_json::json!({"Road Name": "A", "road_name": "B"}))
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,356 @@
+//! Attribute table queries
+//!
+//! Paging, sorting and filtering over a dataset's property columns. Rows are
+//! returned as plain attribute records, or as a GeoJSON FeatureCollection (WGS84)
+//! when `format=geojson`; `bbox` restricts either to features intersecting a
+//! WGS84 bounding box, compared with the stored geometries as its envelope in
+//! the dataset's CRS. GeoJSON coordinates keep the decimals of the dataset's
+//! `precision` tile option.
+
+use duckdb::types::ValueRef;
+use serde::Deserialize;
+
+use crate::columns::{quote_identifier, resolve_column, DatasetColumn};
+
+pub const DEFAULT_FEATURE_LIMIT: u32 = 100;
+pub const MAX_FEATURE_LIMIT: u32 = 1000;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct FeatureListQuery {
+    pub limit: Option<u32>,
+    pub offset: Option<u64>,
+    pub sort: Option<String>,
+    pub filter: Option<String>,
+    pub bbox: Option<String>,
+    pub format: Option<String>,
+}
+
+/// `GET /api/files/:id/sample`: the first `limit` features by `fid`.
+#[derive(Debug, Default, Deserialize)]
+pub struct SampleQuery {
+    pub limit: Option<u32>,
+}
+
+impl From<SampleQuery> for FeatureListQuery {
+    fn from(query: SampleQuery) -> Self {
+        Self {
+            limit: query.limit,
+            format: Some("geojson".to_string()),
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeatureFormat {
+    Json,
+    GeoJson,
+}
+
+impl FeatureListQuery {
+    pub fn limit(&self) -> Result<u32, String> {
+        match self.limit {
+            None => Ok(DEFAULT_FEATURE_LIMIT),
+            Some(limit) if (1..=MAX_FEATURE_LIMIT).contains(&limit) => Ok(limit),
+            Some(_) => Err(format!("limit must be between 1 and {MAX_FEATURE_LIMIT}")),
+        }
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.offset.unwrap_or(0)
+    }
+
+    pub fn format(&self) -> Result<FeatureFormat, String> {
+        match self.format.as_deref().map(str::trim) {
+            None | Some("") | Some("json") => Ok(FeatureFormat::Json),
+            Some("geojson") => Ok(FeatureFormat::GeoJson),
+            Some(other) => Err(format!(
+                "Unsupported format '{other}' (expected json or geojson)"
+            )),
+        }
+    }
+
+    pub fn bbox(&self) -> Result<Option<[f64; 4]>, String> {
+        self.bbox
+            .as_deref()
+            .filter(|b| !b.trim().is_empty())
+            .map(parse_bbox)
+            .transpose()
+    }
+}
+
+/// Parse `minx,miny,maxx,maxy` in WGS84 degrees.
+pub fn parse_bbox(value: &str) -> Result<[f64; 4], String> {
+    const INVALID: &str = "bbox must be minx,miny,maxx,maxy in WGS84 degrees";
+
+    let parts = value
+        .split(',')
+        .map(|part| part.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| INVALID.to_string())?;
+    let [minx, miny, maxx, maxy] = parts[..] else {
+        return Err(INVALID.to_string());
+    };
+
+    let lon_ok = |v: f64| (-180.0..=180.0).contains(&v);
+    let lat_ok = |v: f64| (-90.0..=90.0).contains(&v);
+    if !(lon_ok(minx) && lon_ok(maxx) && lat_ok(miny) && lat_ok(maxy)) {
+        return Err(INVALID.to_string());
+    }
+    if minx > maxx || miny > maxy {
+        return Err("bbox min values must not exceed max values".to_string());
+    }
+
+    Ok([minx, miny, maxx, maxy])
+}
+
+/// Points along each edge of a bbox ring, so that the box keeps its shape
+/// when its edges curve in another CRS.
+const BBOX_EDGE_POINTS: usize = 16;
+
+/// A WGS84 `bbox` as a closed WKT polygon, densified along its edges.
+pub fn bbox_polygon_wkt([minx, miny, maxx, maxy]: [f64; 4]) -> String {
+    let corners = [(minx, miny), (maxx, miny), (maxx, maxy), (minx, maxy)];
+    let mut points = Vec::with_capacity(4 * BBOX_EDGE_POINTS + 1);
+    for (i, &(x0, y0)) in corners.iter().enumerate() {
+        let (x1, y1) = corners[(i + 1) % corners.len()];
+        for step in 0..BBOX_EDGE_POINTS {
+            let t = step as f64 / BBOX_EDGE_POINTS as f64;
+            points.push(format!("{} {}", x0 + (x1 - x0) * t, y0 + (y1 - y0) * t));
+        }
+    }
+    points.push(format!("{minx} {miny}"));
+    format!("POLYGON(({}))", points.join(", "))
+}
+
+/// The envelope of a WGS84 `bbox` in the dataset CRS `crs`, worked out once
+/// so the filter compares the stored geometries (and can use their spatial
+/// index) instead of transforming every row.
+pub fn bbox_in_crs(
+    conn: &duckdb::Connection,
+    bbox: [f64; 4],
+    crs: &str,
+) -> Result<[f64; 4], duckdb::Error> {
+    if crs.eq_ignore_ascii_case("EPSG:4326") {
+        return Ok(bbox);
+    }
+    conn.query_row(
+        "SELECT ST_XMin(g), ST_YMin(g), ST_XMax(g), ST_YMax(g)
+         FROM (SELECT ST_Transform(ST_GeomFromText(?), 'EPSG:4326', ?, always_xy := true) AS g)",
+        duckdb::params![bbox_polygon_wkt(bbox), crs],
+        |row| Ok([row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?]),
+    )
+}
+
+/// Build the ORDER BY clause for `sort=<field>` / `sort=-<field>`.
+/// Rows are always tie-broken by `fid` so pages are stable.
+pub fn order_by_clause(columns: &[DatasetColumn], sort: Option<&str>) -> Result<String, String> {
+    let Some(sort) = sort.map(str::trim).filter(|s| !s.is_empty()) else {
+        return Ok("fid".to_string());
+    };
+
+    let (field, direction) = match sort.strip_prefix('-') {
+        Some(field) => (field, "DESC"),
+        None => (sort.strip_prefix('+').unwrap_or(sort), "ASC"),
+    };
+
+    if field.eq_ignore_ascii_case("fid") && resolve_column(columns, field).is_none() {
+        return Ok(format!("fid {direction}"));
+    }
+
+    let column =
+        resolve_column(columns, field).ok_or_else(|| format!("Unknown sort field '{field}'"))?;
+    Ok(format!(
+        "{} {direction} NULLS LAST, fid",
+        quote_identifier(&column.normalized)
+    ))
+}
+
+/// Convert a DuckDB cell into the JSON value returned by the feature endpoints.
+pub fn value_ref_to_json(value: ValueRef<'_>) -> serde_json::Value {
+    match value {
+        ValueRef::Null => serde_json::Value::Null,
+        ValueRef::Boolean(v) => serde_json::Value::Bool(v),
+        ValueRef::TinyInt(v) => serde_json::Value::Number(v.into()),
+        ValueRef::SmallInt(v) => serde_json::Value::Number(v.into()),
+        ValueRef::Int(v) => serde_json::Value::Number(v.into()),
+        ValueRef::BigInt(v) => serde_json::Value::Number(v.into()),
+        ValueRef::UTinyInt(v) => serde_json::Value::Number(v.into()),
+        ValueRef::USmallInt(v) => serde_json::Value::Number(v.into()),
+        ValueRef::UInt(v) => serde_json::Value::Number(v.into()),
+        ValueRef::UBigInt(v) => serde_json::Value::Number(v.into()),
+        ValueRef::Float(v) => serde_json::Number::from_f64(v as f64)
+            .map(serde_json::Value::Number)
+            .unwrap_or(serde_json::Value::Null),
+        ValueRef::Double(v) => serde_json::Number::from_f64(v)
+            .map(serde_json::Value::Number)
+            .unwrap_or(serde_json::Value::Null),
+        ValueRef::Text(bytes) => {
+            serde_json::Value::String(String::from_utf8_lossy(bytes).to_string())
+        }
+        ValueRef::Blob(bytes) => serde_json::Value::String(format!("0x{}", hex::encode(bytes))),
+        other => serde_json::Value::String(format!("{other:?}")),
+    }
+}
+
+/// Round the coordinates of a GeoJSON geometry to `places` decimals, for
+/// datasets with a `precision` tile option.
+pub fn round_coordinates(geometry: &mut serde_json::Value, places: u8) {
+    let scale = 10f64.powi(i32::from(places));
+    match geometry {
+        serde_json::Value::Number(number) => {
+            if let Some(rounded) = number
+                .as_f64()
+                .and_then(|value| serde_json::Number::from_f64((value * scale).round() / scale))
+            {
+                *number = rounded;
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for item in items {
+                round_coordinates(item, places);
+            }
+        }
+        serde_json::Value::Object(members) => {
+            for (key, value) in members.iter_mut() {
+                if key == "coordinates" || key == "geometries" {
+                    round_coordinates(value, places);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns() -> Vec<DatasetColumn> {
+        vec![
+            DatasetColumn {
+                normalized: "road_name".to_string(),
+                original: "Road Name".to_string(),
+                mvt_type: "VARCHAR".to_string(),
+            },
+            DatasetColumn {
+                normalized: "lanes".to_string(),
+                original: "lanes".to_string(),
+                mvt_type: "INTEGER".to_string(),
+            },
+        ]
+    }
+
+    #[test]
+    fn order_by_defaults_to_fid() {
+        assert_eq!(order_by_clause(&columns(), None).unwrap(), "fid");
+        assert_eq!(order_by_clause(&columns(), Some(" ")).unwrap(), "fid");
+        assert_eq!(
+            order_by_clause(&columns(), Some("-fid")).unwrap(),
+            "fid DESC"
+        );
+    }
+
+    #[test]
+    fn order_by_resolves_original_names_and_direction() {
+        assert_eq!(
+            order_by_clause(&columns(), Some("-Road Name")).unwrap(),
+            "\"road_name\" DESC NULLS LAST, fid"
+        );
+        assert_eq!(
+            order_by_clause(&columns(), Some("LANES")).unwrap(),
+            "\"lanes\" ASC NULLS LAST, fid"
+        );
+        assert!(order_by_clause(&columns(), Some("geom")).is_err());
+    }
+
+    #[test]
+    fn bbox_polygons_are_closed_and_follow_the_edges() {
+        let wkt = bbox_polygon_wkt([0.0, 0.0, 16.0, 8.0]);
+        let points: Vec<&str> = wkt
+            .trim_start_matches("POLYGON((")
+            .trim_end_matches("))")
+            .split(", ")
+            .collect();
+        assert_eq!(points.len(), 4 * BBOX_EDGE_POINTS + 1);
+        assert_eq!(points.first(), points.last());
+        assert_eq!(points[1], "1 0");
+        assert_eq!(points[BBOX_EDGE_POINTS], "16 0");
+        assert_eq!(points[2 * BBOX_EDGE_POINTS], "16 8");
+    }
+
+    #[test]
+    fn parse_bbox_accepts_valid_wgs84_boxes() {
+        assert_eq!(
+            parse_bbox("-10, -5.5,10,5.5").unwrap(),
+            [-10.0, -5.5, 10.0, 5.5]
+        );
+        assert_eq!(parse_bbox("0,0,0,0").unwrap(), [0.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn parse_bbox_rejects_malformed_or_out_of_range() {
+        for input in [
+            "1,2,3",
+            "1,2,3,4,5",
+            "a,b,c,d",
+            "NaN,0,1,1",
+            "-181,0,0,1",
+            "0,-91,1,1",
+            "10,0,5,1",
+        ] {
+            assert!(parse_bbox(input).is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn format_defaults_to_json() {
+        let query = |format: Option<&str>| FeatureListQuery {
+            format: format.map(str::to_string),
+            ..Default::default()
+        };
+        assert_eq!(query(None).format().unwrap(), FeatureFormat::Json);
+        assert_eq!(
+            query(Some("geojson")).format().unwrap(),
+            FeatureFormat::GeoJson
+        );
+        assert!(query(Some("csv")).format().is_err());
+    }
+
+    #[test]
+    fn limit_is_bounded() {
+        let query = |limit| FeatureListQuery {
+            limit,
+            ..Default::default()
+        };
+        assert_eq!(query(None).limit().unwrap(), DEFAULT_FEATURE_LIMIT);
+        assert_eq!(
+            query(Some(MAX_FEATURE_LIMIT)).limit().unwrap(),
+            MAX_FEATURE_LIMIT
+        );
+        assert!(query(Some(0)).limit().is_err());
+        assert!(query(Some(MAX_FEATURE_LIMIT + 1)).limit().is_err());
+    }
+
+    #[test]
+    fn coordinates_are_rounded_to_the_precision() {
+        let mut geometry = serde_json::json!({
+            "type": "GeometryCollection",
+            "geometries": [
+                { "type": "Point", "coordinates": [116.123456789, -39.987654321] },
+                { "type": "LineString", "coordinates": [[1.25, 2.0], [3.00049, 4.9996]] }
+            ]
+        });
+        round_coordinates(&mut geometry, 3);
+        assert_eq!(
+            geometry,
+            serde_json::json!({
+                "type": "GeometryCollection",
+                "geometries": [
+                    { "type": "Point", "coordinates": [116.123, -39.988] },
+                    { "type": "LineString", "coordinates": [[1.25, 2.0], [3.0, 5.0]] }
+                ]
+            })
+        );
+    }
+}
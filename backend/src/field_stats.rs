@@ -0,0 +1,138 @@
+//! Per-column statistics for data-driven styling
+//!
+//! Numeric columns report min/max/mean; text and boolean columns report their
+//! distinct count and most frequent values. All aggregates run in DuckDB.
+//! The full value list (with counts) backs categorical legends and filter
+//! dropdowns.
+
+use serde::Deserialize;
+
+use crate::columns::{quote_identifier, DatasetColumn};
+use crate::features::value_ref_to_json;
+use crate::models::{FieldStatsResponse, ValueCount};
+
+pub const TOP_VALUES_LIMIT: u32 = 10;
+pub const DEFAULT_VALUES_LIMIT: u32 = 100;
+pub const MAX_VALUES_LIMIT: u32 = 1000;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct FieldValuesQuery {
+    pub limit: Option<u32>,
+}
+
+impl FieldValuesQuery {
+    pub fn limit(&self) -> Result<u32, String> {
+        match self.limit {
+            None => Ok(DEFAULT_VALUES_LIMIT),
+            Some(limit) if (1..=MAX_VALUES_LIMIT).contains(&limit) => Ok(limit),
+            Some(_) => Err(format!("limit must be between 1 and {MAX_VALUES_LIMIT}")),
+        }
+    }
+}
+
+pub fn is_numeric_type(mvt_type: &str) -> bool {
+    matches!(mvt_type, "BIGINT" | "INTEGER" | "DOUBLE" | "FLOAT")
+}
+
+/// Distinct non-null values of `column`, most frequent first (ties by value).
+pub fn value_counts(
+    conn: &duckdb::Connection,
+    table_name: &str,
+    column: &DatasetColumn,
+    limit: u32,
+) -> Result<Vec<ValueCount>, duckdb::Error> {
+    let col = quote_identifier(&column.normalized);
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {col}, COUNT(*) AS n FROM {} WHERE {col} IS NOT NULL GROUP BY {col} ORDER BY n DESC, {col} LIMIT {limit}",
+        quote_identifier(table_name)
+    ))?;
+    let mut rows = stmt.query([])?;
+
+    let mut values = Vec::new();
+    while let Some(row) = rows.next()? {
+        let count: i64 = row.get(1)?;
+        values.push(ValueCount {
+            value: value_ref_to_json(row.get_ref(0)?),
+            count: count.max(0) as u64,
+        });
+    }
+    Ok(values)
+}
+
+pub fn field_stats(
+    conn: &duckdb::Connection,
+    table_name: &str,
+    column: &DatasetColumn,
+) -> Result<FieldStatsResponse, duckdb::Error> {
+    let col = quote_identifier(&column.normalized);
+    let table = quote_identifier(table_name);
+
+    let mut stats = FieldStatsResponse {
+        name: column.original.clone(),
+        field_type: column.mvt_type.clone(),
+        count: 0,
+        null_count: 0,
+        min: None,
+        max: None,
+        mean: None,
+        distinct_count: None,
+        top_values: None,
+    };
+
+    if is_numeric_type(&column.mvt_type) {
+        conn.query_row(
+            &format!(
+                "SELECT COUNT({col}), COUNT(*) - COUNT({col}), MIN({col})::DOUBLE, MAX({col})::DOUBLE, AVG({col})::DOUBLE FROM {table}"
+            ),
+            [],
+            |row| {
+                stats.count = row.get::<_, i64>(0)?.max(0) as u64;
+                stats.null_count = row.get::<_, i64>(1)?.max(0) as u64;
+                stats.min = row.get(2)?;
+                stats.max = row.get(3)?;
+                stats.mean = row.get::<_, Option<f64>>(4)?.filter(|m| m.is_finite());
+                Ok(())
+            },
+        )?;
+    } else {
+        conn.query_row(
+            &format!(
+                "SELECT COUNT({col}), COUNT(*) - COUNT({col}), COUNT(DISTINCT {col}) FROM {table}"
+            ),
+            [],
+            |row| {
+                stats.count = row.get::<_, i64>(0)?.max(0) as u64;
+                stats.null_count = row.get::<_, i64>(1)?.max(0) as u64;
+                stats.distinct_count = Some(row.get::<_, i64>(2)?.max(0) as u64);
+                Ok(())
+            },
+        )?;
+        stats.top_values = Some(value_counts(conn, table_name, column, TOP_VALUES_LIMIT)?);
+    }
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numeric_types_match_imported_column_types() {
+        for t in ["BIGINT", "INTEGER", "DOUBLE", "FLOAT"] {
+            assert!(is_numeric_type(t), "{t}");
+        }
+        for t in ["VARCHAR", "BOOLEAN", "GEOMETRY"] {
+            assert!(!is_numeric_type(t), "{t}");
+        }
+    }
+
+    #[test]
+    fn values_limit_defaults_and_bounds() {
+        let query = |limit| FieldValuesQuery { limit };
+        assert_eq!(query(None).limit().unwrap(), DEFAULT_VALUES_LIMIT);
+        assert_eq!(query(Some(1)).limit().unwrap(), 1);
+        assert!(query(Some(0)).limit().is_err());
+        assert!(query(Some(MAX_VALUES_LIMIT + 1)).limit().is_err());
+    }
+}
@@ -0,0 +1,339 @@
+//! Live file list
+//!
+//! `GET /api/files/events` is a Server-Sent Events stream of changes to the
+//! files the caller can see, so the dashboard stays current without every tab
+//! polling `GET /api/files`. `created` and `updated` events carry the
+//! `FileItem` as the list returns it, `deleted` carries `{id}`.
+//!
+//! While any stream is open, one poller per process compares the catalog (the
+//! files list plus who may see which file) against its last snapshot every
+//! `FILE_EVENTS_INTERVAL`. That catches every writer (uploads, imports, the
+//! CLI, retention purges, sharing changes) without each reporting its changes.
+//! On a change it wakes the streams through a broadcast channel, and each one
+//! reloads its own list and sends the difference. A stream subscribes before
+//! reading its first list, so a client that loads `GET /api/files` once the
+//! stream is open misses nothing. Streams end once their session is signed
+//! out, expires or its user's password or role changes.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::sse::{Event, KeepAlive, Sse},
+    Json,
+};
+use axum_login::{AuthSession, AuthnBackend};
+use futures_util::stream::{self, Stream};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::time::{Interval, MissedTickBehavior};
+use tower_sessions::{session::Id, SessionStore};
+
+use crate::auth::{AuthBackend, User};
+use crate::http_errors::internal_error;
+use crate::models::{AppState, FileItem, FileListQuery};
+use crate::read_pool::ReadPool;
+use crate::{load_file_items, visible_files_owner, ErrorResponse};
+
+pub const FILE_EVENTS_INTERVAL: Duration = Duration::from_secs(1);
+/// How often a stream checks that its session is still signed in.
+pub const SESSION_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Who may see which file, besides the files themselves.
+const VISIBILITY_SQL: &str = "SELECT 'owner', id, COALESCE(owner_id, '') FROM files
+    UNION ALL SELECT 'share', file_id, user_id FROM file_shares
+    UNION ALL SELECT 'member', org_id, user_id FROM org_members
+    ORDER BY ALL";
+
+#[derive(PartialEq)]
+struct CatalogSnapshot {
+    files: Vec<FileItem>,
+    visibility: Vec<(String, String, String)>,
+}
+
+async fn catalog_snapshot(read_pool: &ReadPool) -> Result<CatalogSnapshot, String> {
+    let conn = read_pool.get().await.map_err(|e| e.to_string())?;
+    let files =
+        load_file_items(&conn, None, &FileListQuery::default()).map_err(|e| e.to_string())?;
+    let mut stmt = conn.prepare(VISIBILITY_SQL).map_err(|e| e.to_string())?;
+    let visibility = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .and_then(|rows| rows.collect::<Result<_, _>>())
+        .map_err(|e| e.to_string())?;
+    Ok(CatalogSnapshot { files, visibility })
+}
+
+/// Wakes the open streams when the catalog changes.
+#[derive(Clone)]
+pub struct FileEvents {
+    changed: broadcast::Sender<()>,
+    /// Whether the poller is running; it stops when the last stream closes.
+    polling: Arc<Mutex<bool>>,
+}
+
+impl Default for FileEvents {
+    fn default() -> Self {
+        let (changed, _) = broadcast::channel(16);
+        Self {
+            changed,
+            polling: Arc::default(),
+        }
+    }
+}
+
+impl FileEvents {
+    fn subscribe(&self, read_pool: &ReadPool) -> broadcast::Receiver<()> {
+        let mut polling = self.polling.lock().unwrap_or_else(PoisonError::into_inner);
+        let receiver = self.changed.subscribe();
+        if !*polling {
+            *polling = true;
+            tokio::spawn(self.clone().poll(read_pool.clone()));
+        }
+        receiver
+    }
+
+    async fn poll(self, read_pool: ReadPool) {
+        let mut interval = tokio::time::interval(FILE_EVENTS_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut last: Option<CatalogSnapshot> = None;
+        loop {
+            interval.tick().await;
+            {
+                // Decided under the lock `subscribe` takes, so a new stream
+                // either keeps this poller going or starts the next one.
+                let mut polling = self.polling.lock().unwrap_or_else(PoisonError::into_inner);
+                if self.changed.receiver_count() == 0 {
+                    *polling = false;
+                    return;
+                }
+            }
+            match catalog_snapshot(&read_pool).await {
+                // The first snapshot also wakes the streams: something may
+                // have changed between their first list and it.
+                Ok(snapshot) if last.as_ref() != Some(&snapshot) => {
+                    let _ = self.changed.send(());
+                    last = Some(snapshot);
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!(error = %e, "Failed to refresh file events"),
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum FileChange {
+    Created(FileItem),
+    Updated(FileItem),
+    Deleted(String),
+}
+
+impl FileChange {
+    fn into_event(self) -> Result<Event, axum::Error> {
+        match self {
+            FileChange::Created(item) => Event::default().event("created").json_data(item),
+            FileChange::Updated(item) => Event::default().event("updated").json_data(item),
+            FileChange::Deleted(id) => Event::default()
+                .event("deleted")
+                .json_data(serde_json::json!({ "id": id })),
+        }
+    }
+}
+
+/// Replace `known` with `items` and return what changed, deletions first.
+fn diff_files(known: &mut HashMap<String, FileItem>, items: Vec<FileItem>) -> Vec<FileChange> {
+    let mut previous = std::mem::take(known);
+    let mut changes = Vec::new();
+    let mut upserts = Vec::new();
+    for item in items {
+        match previous.remove(&item.id) {
+            None => upserts.push(FileChange::Created(item.clone())),
+            Some(old) if old != item => upserts.push(FileChange::Updated(item.clone())),
+            Some(_) => {}
+        }
+        known.insert(item.id.clone(), item);
+    }
+    let mut deleted: Vec<String> = previous.into_keys().collect();
+    deleted.sort();
+    changes.extend(deleted.into_iter().map(FileChange::Deleted));
+    changes.extend(upserts);
+    changes
+}
+
+/// The signed-in session a stream belongs to.
+struct SessionCheck {
+    user: User,
+    session_id: Option<Id>,
+    interval: Interval,
+}
+
+impl SessionCheck {
+    async fn still_valid(&self, state: &AppState) -> bool {
+        if let Some(session_id) = &self.session_id {
+            if !matches!(state.session_store.load(session_id).await, Ok(Some(_))) {
+                return false;
+            }
+        }
+        match state.auth_backend.get_user(&self.user.id).await {
+            Ok(Some(user)) => {
+                user.password_hash == self.user.password_hash && user.role == self.user.role
+            }
+            Ok(None) => false,
+            // A database hiccup is not a sign-out.
+            Err(_) => true,
+        }
+    }
+}
+
+struct Watch {
+    state: AppState,
+    owner_id: Option<String>,
+    known: HashMap<String, FileItem>,
+    pending: VecDeque<FileChange>,
+    changed: broadcast::Receiver<()>,
+    session: Option<SessionCheck>,
+}
+
+impl Watch {
+    async fn refresh(&mut self) {
+        let items = match self.state.read_pool.get().await {
+            Ok(conn) => load_file_items(&conn, self.owner_id.as_deref(), &FileListQuery::default())
+                .map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        match items {
+            Ok(items) => self.pending.extend(diff_files(&mut self.known, items)),
+            Err(e) => tracing::warn!(error = %e, "Failed to refresh file events"),
+        }
+    }
+
+    /// Wait for the catalog to change; `false` once the stream should end.
+    /// The session is checked on a timer and before every refresh.
+    async fn wait(&mut self) -> bool {
+        let Watch {
+            state,
+            changed,
+            session,
+            ..
+        } = self;
+        let session_ended = async {
+            match session.as_mut() {
+                Some(session) => loop {
+                    session.interval.tick().await;
+                    if !session.still_valid(state).await {
+                        break;
+                    }
+                },
+                None => std::future::pending().await,
+            }
+        };
+        let woke = tokio::select! {
+            changed = changed.recv() => !matches!(changed, Err(RecvError::Closed)),
+            () = session_ended => false,
+        };
+        match session {
+            Some(session) if woke => session.still_valid(state).await,
+            _ => woke,
+        }
+    }
+}
+
+pub async fn file_events(
+    State(state): State<AppState>,
+    auth_session: AuthSession<AuthBackend>,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, (StatusCode, Json<ErrorResponse>)>
+{
+    let owner_id = visible_files_owner(&auth_session);
+    let changed = state.file_events.subscribe(&state.read_pool);
+    let items = {
+        let conn = state.read_pool.get().await.map_err(internal_error)?;
+        load_file_items(&conn, owner_id.as_deref(), &FileListQuery::default())
+            .map_err(internal_error)?
+    };
+    let session = auth_session.user.clone().map(|user| {
+        let mut interval = tokio::time::interval(SESSION_CHECK_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        // The first tick completes at once; the session was just checked.
+        interval.reset();
+        SessionCheck {
+            user,
+            session_id: auth_session.session.id(),
+            interval,
+        }
+    });
+
+    let watch = Watch {
+        state,
+        owner_id,
+        known: items
+            .into_iter()
+            .map(|item| (item.id.clone(), item))
+            .collect(),
+        pending: VecDeque::new(),
+        changed,
+        session,
+    };
+    let events = stream::unfold(watch, |mut watch| async move {
+        loop {
+            if let Some(change) = watch.pending.pop_front() {
+                return Some((change.into_event(), watch));
+            }
+            if !watch.wait().await {
+                return None;
+            }
+            watch.refresh().await;
+        }
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: &str, status: &str) -> FileItem {
+        FileItem {
+            id: id.to_string(),
+            name: id.to_string(),
+            file_type: "geojson".to_string(),
+            size: 1,
+            uploaded_at: "2026-02-04T10:00:00+00:00".to_string(),
+            status: status.to_string(),
+            crs: None,
+            path: format!("./uploads/{id}/{id}.geojson"),
+            table_name: None,
+            error: None,
+            is_public: Some(false),
+            public_slug: None,
+            org_id: None,
+            geometry_type: None,
+            feature_count: None,
+            folder: None,
+            tags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn diff_reports_created_updated_and_deleted_files() {
+        let mut known = HashMap::new();
+        assert_eq!(
+            diff_files(&mut known, vec![item("a", "uploaded"), item("b", "ready")]),
+            vec![
+                FileChange::Created(item("a", "uploaded")),
+                FileChange::Created(item("b", "ready")),
+            ]
+        );
+        assert!(diff_files(&mut known, vec![item("a", "uploaded"), item("b", "ready")]).is_empty());
+        assert_eq!(
+            diff_files(&mut known, vec![item("c", "uploaded"), item("a", "ready")]),
+            vec![
+                FileChange::Deleted("b".to_string()),
+                FileChange::Created(item("c", "uploaded")),
+                FileChange::Updated(item("a", "ready")),
+            ]
+        );
+        assert_eq!(known.len(), 2);
+    }
+}
@@ -0,0 +1,565 @@
+//! Attribute filter expressions
+//!
+//! A small CQL-like language used by the `filter` query parameter:
+//!
+//! ```text
+//! class = 'primary' AND (lanes >= 2 OR name LIKE 'Main%')
+//! "Road Name" IS NOT NULL
+//! kind IN ('bus', 'tram') AND NOT oneway = true
+//! ```
+//!
+//! Field names refer to original column names (double-quote names with spaces).
+//! The expression compiles to a SQL fragment with `?` placeholders; values are
+//! always bound as parameters and fields resolve through `dataset_columns`,
+//! so user input never reaches the SQL text.
+
+use duckdb::types::Value;
+
+use crate::columns::{quote_identifier, resolve_column, DatasetColumn};
+
+const MAX_FILTER_LENGTH: usize = 2000;
+const MAX_NESTING: usize = 32;
+
+/// A compiled filter: a boolean SQL expression plus its positional parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledFilter {
+    pub sql: String,
+    pub params: Vec<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    QuotedIdent(String),
+    Str(String),
+    Number(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Comma,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '(' => {
+                tokens.push(Token::LParen);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                i += 1;
+            }
+            ',' => {
+                tokens.push(Token::Comma);
+                i += 1;
+            }
+            '\'' | '"' => {
+                // Quotes are escaped by doubling them, as in SQL.
+                let mut value = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err("Unterminated quoted value in filter".to_string()),
+                        Some(&q) if q == c && chars.get(i + 1) == Some(&c) => {
+                            value.push(c);
+                            i += 2;
+                        }
+                        Some(&q) if q == c => {
+                            i += 1;
+                            break;
+                        }
+                        Some(&other) => {
+                            value.push(other);
+                            i += 1;
+                        }
+                    }
+                }
+                tokens.push(if c == '\'' {
+                    Token::Str(value)
+                } else {
+                    Token::QuotedIdent(value)
+                });
+            }
+            '=' => {
+                tokens.push(Token::Op("="));
+                i += 1;
+            }
+            '!' if chars.get(i + 1) == Some(&'=') => {
+                tokens.push(Token::Op("<>"));
+                i += 2;
+            }
+            '<' | '>' => {
+                let next = chars.get(i + 1).copied();
+                let op = match (c, next) {
+                    ('<', Some('=')) => "<=",
+                    ('<', Some('>')) => "<>",
+                    ('>', Some('=')) => ">=",
+                    ('<', _) => "<",
+                    _ => ">",
+                };
+                i += op.len();
+                tokens.push(Token::Op(op));
+            }
+            c if c.is_ascii_digit() || c == '-' || c == '.' => {
+                let start = i;
+                i += 1;
+                while i < chars.len()
+                    && (chars[i].is_ascii_digit()
+                        || matches!(chars[i], '.' | 'e' | 'E')
+                        || (matches!(chars[i], '+' | '-') && matches!(chars[i - 1], 'e' | 'E')))
+                {
+                    i += 1;
+                }
+                let literal: String = chars[start..i].iter().collect();
+                if literal.parse::<f64>().is_err() {
+                    return Err(format!("Invalid number '{literal}' in filter"));
+                }
+                tokens.push(Token::Number(literal));
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                tokens.push(Token::Ident(chars[start..i].iter().collect()));
+            }
+            other => return Err(format!("Unexpected character '{other}' in filter")),
+        }
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Literal {
+    Str(String),
+    Number(String),
+    Bool(bool),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare {
+        field: String,
+        op: &'static str,
+        value: Literal,
+    },
+    Like {
+        field: String,
+        pattern: String,
+        case_insensitive: bool,
+        negated: bool,
+    },
+    IsNull {
+        field: String,
+        negated: bool,
+    },
+    In {
+        field: String,
+        values: Vec<Literal>,
+        negated: bool,
+    },
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(word)) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if self.peek_keyword(keyword) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), String> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(format!("Expected {keyword} in filter"))
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, String> {
+        let mut left = self.parse_and()?;
+        while self.eat_keyword("OR") {
+            let right = self.parse_and()?;
+            left = Expr::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, String> {
+        let mut left = self.parse_not()?;
+        while self.eat_keyword("AND") {
+            let right = self.parse_not()?;
+            left = Expr::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, String> {
+        if self.eat_keyword("NOT") {
+            return Ok(Expr::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, String> {
+        if self.peek() == Some(&Token::LParen) {
+            self.pos += 1;
+            self.depth += 1;
+            if self.depth > MAX_NESTING {
+                return Err("Filter is nested too deeply".to_string());
+            }
+            let expr = self.parse_or()?;
+            if self.next() != Some(Token::RParen) {
+                return Err("Expected ')' in filter".to_string());
+            }
+            self.depth -= 1;
+            return Ok(expr);
+        }
+
+        let field = match self.next() {
+            Some(Token::Ident(name)) | Some(Token::QuotedIdent(name)) => name,
+            _ => return Err("Expected a field name in filter".to_string()),
+        };
+
+        if self.eat_keyword("IS") {
+            let negated = self.eat_keyword("NOT");
+            self.expect_keyword("NULL")?;
+            return Ok(Expr::IsNull { field, negated });
+        }
+
+        let negated = self.eat_keyword("NOT");
+        if self.peek_keyword("LIKE") || self.peek_keyword("ILIKE") {
+            let case_insensitive = self.peek_keyword("ILIKE");
+            self.pos += 1;
+            let pattern = match self.next() {
+                Some(Token::Str(pattern)) => pattern,
+                _ => return Err("LIKE expects a quoted pattern".to_string()),
+            };
+            return Ok(Expr::Like {
+                field,
+                pattern,
+                case_insensitive,
+                negated,
+            });
+        }
+        if self.eat_keyword("IN") {
+            if self.next() != Some(Token::LParen) {
+                return Err("IN expects a parenthesized list".to_string());
+            }
+            let mut values = vec![self.parse_literal()?];
+            loop {
+                match self.next() {
+                    Some(Token::Comma) => values.push(self.parse_literal()?),
+                    Some(Token::RParen) => break,
+                    _ => return Err("Expected ',' or ')' in IN list".to_string()),
+                }
+            }
+            return Ok(Expr::In {
+                field,
+                values,
+                negated,
+            });
+        }
+        if negated {
+            return Err("NOT must be followed by LIKE, ILIKE or IN here".to_string());
+        }
+
+        let op = match self.next() {
+            Some(Token::Op(op)) => op,
+            _ => return Err(format!("Expected a comparison after '{field}'")),
+        };
+        let value = self.parse_literal()?;
+        Ok(Expr::Compare { field, op, value })
+    }
+
+    fn parse_literal(&mut self) -> Result<Literal, String> {
+        match self.next() {
+            Some(Token::Str(value)) => Ok(Literal::Str(value)),
+            Some(Token::Number(value)) => Ok(Literal::Number(value)),
+            Some(Token::Ident(word)) if word.eq_ignore_ascii_case("true") => {
+                Ok(Literal::Bool(true))
+            }
+            Some(Token::Ident(word)) if word.eq_ignore_ascii_case("false") => {
+                Ok(Literal::Bool(false))
+            }
+            _ => Err("Expected a quoted string, number or boolean in filter".to_string()),
+        }
+    }
+}
+
+fn parse(input: &str) -> Result<Expr, String> {
+    if input.len() > MAX_FILTER_LENGTH {
+        return Err(format!(
+            "Filter is too long (max {MAX_FILTER_LENGTH} characters)"
+        ));
+    }
+    let tokens = tokenize(input)?;
+    if tokens.is_empty() {
+        return Err("Filter is empty".to_string());
+    }
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        depth: 0,
+    };
+    let expr = parser.parse_or()?;
+    if parser.pos < parser.tokens.len() {
+        return Err("Unexpected trailing input in filter".to_string());
+    }
+    Ok(expr)
+}
+
+/// The column a filter field refers to: a dataset property or the feature id.
+struct Target {
+    sql: String,
+    mvt_type: String,
+}
+
+fn resolve_target(columns: &[DatasetColumn], field: &str) -> Result<Target, String> {
+    if let Some(column) = resolve_column(columns, field) {
+        return Ok(Target {
+            sql: quote_identifier(&column.normalized),
+            mvt_type: column.mvt_type.clone(),
+        });
+    }
+    if field.eq_ignore_ascii_case("fid") {
+        return Ok(Target {
+            sql: "fid".to_string(),
+            mvt_type: "BIGINT".to_string(),
+        });
+    }
+    Err(format!("Unknown field '{field}' in filter"))
+}
+
+/// Bind a literal using the column's type so comparisons never rely on implicit casts.
+fn bind_literal(target: &Target, field: &str, literal: &Literal) -> Result<Value, String> {
+    let mismatch = || format!("Value for '{field}' must be a {}", target.mvt_type);
+    match target.mvt_type.as_str() {
+        "BIGINT" | "INTEGER" => match literal {
+            Literal::Number(n) => n.parse::<i64>().map(Value::BigInt).map_err(|_| mismatch()),
+            _ => Err(mismatch()),
+        },
+        "DOUBLE" | "FLOAT" => match literal {
+            Literal::Number(n) => n.parse::<f64>().map(Value::Double).map_err(|_| mismatch()),
+            _ => Err(mismatch()),
+        },
+        "BOOLEAN" => match literal {
+            Literal::Bool(b) => Ok(Value::Boolean(*b)),
+            _ => Err(mismatch()),
+        },
+        _ => Ok(Value::Text(match literal {
+            Literal::Str(s) | Literal::Number(s) => s.clone(),
+            Literal::Bool(b) => b.to_string(),
+        })),
+    }
+}
+
+fn emit(expr: &Expr, columns: &[DatasetColumn], params: &mut Vec<Value>) -> Result<String, String> {
+    Ok(match expr {
+        Expr::And(left, right) => format!(
+            "({} AND {})",
+            emit(left, columns, params)?,
+            emit(right, columns, params)?
+        ),
+        Expr::Or(left, right) => format!(
+            "({} OR {})",
+            emit(left, columns, params)?,
+            emit(right, columns, params)?
+        ),
+        Expr::Not(inner) => format!("(NOT {})", emit(inner, columns, params)?),
+        Expr::Compare { field, op, value } => {
+            let target = resolve_target(columns, field)?;
+            params.push(bind_literal(&target, field, value)?);
+            format!("({} {op} ?)", target.sql)
+        }
+        Expr::Like {
+            field,
+            pattern,
+            case_insensitive,
+            negated,
+        } => {
+            let target = resolve_target(columns, field)?;
+            if target.mvt_type != "VARCHAR" {
+                return Err(format!("LIKE is only supported on text field '{field}'"));
+            }
+            params.push(Value::Text(pattern.clone()));
+            let not = if *negated { "NOT " } else { "" };
+            let like = if *case_insensitive { "ILIKE" } else { "LIKE" };
+            format!("({} {not}{like} ?)", target.sql)
+        }
+        Expr::IsNull { field, negated } => {
+            let target = resolve_target(columns, field)?;
+            let not = if *negated { "NOT " } else { "" };
+            format!("({} IS {not}NULL)", target.sql)
+        }
+        Expr::In {
+            field,
+            values,
+            negated,
+        } => {
+            let target = resolve_target(columns, field)?;
+            for value in values {
+                params.push(bind_literal(&target, field, value)?);
+            }
+            let placeholders = vec!["?"; values.len()].join(", ");
+            let not = if *negated { "NOT " } else { "" };
+            format!("({} {not}IN ({placeholders}))", target.sql)
+        }
+    })
+}
+
+/// Parse and compile a filter against a dataset's columns.
+pub fn compile_filter(input: &str, columns: &[DatasetColumn]) -> Result<CompiledFilter, String> {
+    let expr = parse(input)?;
+    let mut params = Vec::new();
+    let sql = emit(&expr, columns, &mut params)?;
+    Ok(CompiledFilter { sql, params })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns() -> Vec<DatasetColumn> {
+        [
+            ("class", "class", "VARCHAR"),
+            ("lanes", "lanes", "INTEGER"),
+            ("speed", "Speed", "DOUBLE"),
+            ("oneway", "oneway", "BOOLEAN"),
+            ("road_name", "Road Name", "VARCHAR"),
+        ]
+        .iter()
+        .map(|(normalized, original, mvt_type)| DatasetColumn {
+            normalized: normalized.to_string(),
+            original: original.to_string(),
+            mvt_type: mvt_type.to_string(),
+        })
+        .collect()
+    }
+
+    #[test]
+    fn compiles_simple_equality_with_bound_value() {
+        let compiled = compile_filter("class='primary'", &columns()).unwrap();
+        assert_eq!(compiled.sql, "(\"class\" = ?)");
+        assert_eq!(compiled.params, vec![Value::Text("primary".to_string())]);
+    }
+
+    #[test]
+    fn compiles_boolean_logic_and_precedence() {
+        let compiled = compile_filter(
+            "class = 'primary' OR lanes >= 2 AND NOT oneway = true",
+            &columns(),
+        )
+        .unwrap();
+        assert_eq!(
+            compiled.sql,
+            "((\"class\" = ?) OR ((\"lanes\" >= ?) AND (NOT (\"oneway\" = ?))))"
+        );
+        assert_eq!(
+            compiled.params,
+            vec![
+                Value::Text("primary".to_string()),
+                Value::BigInt(2),
+                Value::Boolean(true),
+            ]
+        );
+    }
+
+    #[test]
+    fn resolves_quoted_original_names() {
+        let compiled =
+            compile_filter("\"Road Name\" ILIKE 'main%' AND Speed < 50.5", &columns()).unwrap();
+        assert_eq!(
+            compiled.sql,
+            "((\"road_name\" ILIKE ?) AND (\"speed\" < ?))"
+        );
+        assert_eq!(
+            compiled.params,
+            vec![Value::Text("main%".to_string()), Value::Double(50.5)]
+        );
+    }
+
+    #[test]
+    fn compiles_in_and_null_checks() {
+        let compiled = compile_filter(
+            "class NOT IN ('a', 'b') AND road_name IS NOT NULL AND fid != 3",
+            &columns(),
+        )
+        .unwrap();
+        assert_eq!(
+            compiled.sql,
+            "(((\"class\" NOT IN (?, ?)) AND (\"road_name\" IS NOT NULL)) AND (fid <> ?))"
+        );
+        assert_eq!(compiled.params.len(), 3);
+    }
+
+    #[test]
+    fn string_quotes_are_unescaped_not_injected() {
+        let compiled = compile_filter("class = 'it''s'' OR 1=1 --'", &columns()).unwrap();
+        assert_eq!(compiled.sql, "(\"class\" = ?)");
+        assert_eq!(
+            compiled.params,
+            vec![Value::Text("it's' OR 1=1 --".to_string())]
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_fields_and_type_mismatches() {
+        assert!(compile_filter("nope = 1", &columns())
+            .unwrap_err()
+            .contains("Unknown field"));
+        assert!(compile_filter("lanes = 'two'", &columns()).is_err());
+        assert!(compile_filter("oneway = 1", &columns()).is_err());
+        assert!(compile_filter("lanes LIKE '1%'", &columns()).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        for input in [
+            "",
+            "class =",
+            "class = 'open",
+            "(class = 'a'",
+            "class = 'a' extra",
+            "class ; drop table files",
+            "class NOT = 'a'",
+        ] {
+            assert!(compile_filter(input, &columns()).is_err(), "{input}");
+        }
+        let deep = format!("{}class = 'a'{}", "(".repeat(40), ")".repeat(40));
+        assert!(compile_filter(&deep, &columns()).is_err());
+    }
+}
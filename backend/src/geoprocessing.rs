@@ -0,0 +1,608 @@
+//! Geoprocessing
+//!
+//! `POST /api/files/{id}/process/<operation>` runs a spatial operation over a
+//! vector dataset and stores the result as a new dataset of type `derived`,
+//! owned by the caller. The file is created at once and filled in the
+//! background like an import, moving through `processing` to `ready` or
+//! `failed`; the source is left as it is. Each operation only has to turn the
+//! source's table into the SELECT the new table is created from, and
+//! properties keep their original names.
+//!
+//! `buffer` grows every geometry by `distance` meters, or shrinks polygons by
+//! a negative one. Each geometry is buffered in the UTM zone of its centroid,
+//! so the distance holds whatever the dataset's CRS, and the result is stored
+//! in the source's CRS.
+//!
+//! `dissolve` merges the features sharing a value of `field` into one, with
+//! that value as its only property, to build region outlines from parcel- or
+//! admin-level data. Features without a value are merged together too.
+//!
+//! `centroids` replaces every geometry with a point, its `centroid` or, with
+//! `method=pointOnSurface`, a point on its surface, which unlike the centroid
+//! of a C-shaped polygon always lies inside it; the usual way to place labels.
+//! Features keep their ids and properties.
+//!
+//! `reproject` stores a copy of the dataset in the CRS given by `to`, by
+//! default Web Mercator. Tiles of data in any other CRS transform every
+//! geometry on every request, so a Web Mercator copy serves them far faster.
+//!
+//! `POST /api/process/spatial-join` takes a `target` and a `join` dataset and
+//! adds to each target feature the properties of every join feature its
+//! geometry `intersects` or lies `within`, answering questions like which
+//! parcel each well is in. Every target feature is kept, once per match or
+//! once with empty join properties when nothing matches, so features get new
+//! ids. Join geometries are compared in the target's CRS, and join properties
+//! whose names the target already uses are prefixed with `join_`.
+
+use std::collections::HashSet;
+
+use axum::{
+    extract::{Path as AxumPath, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::post,
+    Json, Router,
+};
+use axum_login::AuthSession;
+use chrono::Utc;
+use serde::Deserialize;
+use tokio::fs;
+
+use crate::authz::{access_denied, file_access, FileAccess};
+use crate::columns::{
+    load_dataset_columns, output_column_aliases, quote_identifier, quote_literal, resolve_column,
+    DatasetColumn,
+};
+use crate::export::{column_names, exported_columns};
+use crate::http_errors::{bad_request, internal_error};
+use crate::import::load_dataset_table;
+use crate::models::{
+    AppState, BufferRequest, CentroidsRequest, DissolveRequest, FileItem, SpatialJoinRequest,
+};
+use crate::ready_file::load_ready_file;
+use crate::{create_id, storage_path_string, track_import, AuthBackend, ErrorResponse};
+
+/// Largest buffer distance, in meters; UTM zones distort beyond a few hundred
+/// kilometers.
+pub const MAX_BUFFER_DISTANCE: f64 = 100_000.0;
+/// CRS `reproject` writes when none is given.
+pub const DEFAULT_REPROJECT_CRS: &str = "EPSG:3857";
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ReprojectQuery {
+    pub to: Option<String>,
+}
+
+impl ReprojectQuery {
+    /// The target CRS as `AUTH:CODE`, with the authority upper-cased.
+    pub fn crs(&self) -> Result<String, String> {
+        let Some(to) = self.to.as_deref().map(str::trim) else {
+            return Ok(DEFAULT_REPROJECT_CRS.to_string());
+        };
+        let valid = to.split_once(':').is_some_and(|(authority, code)| {
+            !authority.is_empty()
+                && authority.chars().all(|c| c.is_ascii_alphabetic())
+                && !code.is_empty()
+                && code.chars().all(|c| c.is_ascii_alphanumeric() || c == '.')
+        });
+        if !valid {
+            return Err("to must be a CRS such as EPSG:3857".to_string());
+        }
+        Ok(to.to_ascii_uppercase())
+    }
+}
+
+pub fn build_processing_router() -> Router<AppState> {
+    Router::new()
+        .route("/api/files/{id}/process/buffer", post(buffer_file))
+        .route("/api/files/{id}/process/dissolve", post(dissolve_file))
+        .route("/api/files/{id}/process/centroids", post(centroids_file))
+        .route("/api/files/{id}/process/reproject", post(reproject_file))
+}
+
+/// Which point `centroids` derives from each geometry.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PointMethod {
+    #[default]
+    Centroid,
+    PointOnSurface,
+}
+
+impl PointMethod {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "centroid" => Some(Self::Centroid),
+            "pointonsurface" => Some(Self::PointOnSurface),
+            _ => None,
+        }
+    }
+
+    fn sql_function(self) -> &'static str {
+        match self {
+            Self::Centroid => "ST_Centroid",
+            Self::PointOnSurface => "ST_PointOnSurface",
+        }
+    }
+}
+
+/// How a spatial join matches target features to join features.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SpatialPredicate {
+    #[default]
+    Intersects,
+    /// The target geometry lies within the join geometry.
+    Within,
+}
+
+impl SpatialPredicate {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "intersects" => Some(Self::Intersects),
+            "within" => Some(Self::Within),
+            _ => None,
+        }
+    }
+
+    fn sql_function(self) -> &'static str {
+        match self {
+            Self::Intersects => "ST_Intersects",
+            Self::Within => "ST_Within",
+        }
+    }
+}
+
+/// A ready vector dataset to derive a new one from.
+#[derive(Debug)]
+struct ProcessSource {
+    id: String,
+    name: String,
+    table_name: String,
+    crs: String,
+}
+
+fn load_source(
+    conn: &duckdb::Connection,
+    id: &str,
+) -> Result<ProcessSource, (StatusCode, Json<ErrorResponse>)> {
+    let file = load_ready_file(conn, id, "MBTiles and GeoTIFF files cannot be processed")?;
+    Ok(ProcessSource {
+        id: id.to_string(),
+        name: file.name,
+        table_name: file.table_name,
+        crs: file.crs,
+    })
+}
+
+/// Fail unless the caller may read `id`; missing files are left to
+/// `load_source`.
+fn check_read_access(
+    conn: &duckdb::Connection,
+    auth_session: &AuthSession<AuthBackend>,
+    id: &str,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    if let Some(user) = &auth_session.user {
+        if let Some(access) = file_access(conn, user, id).map_err(internal_error)? {
+            if access < Some(FileAccess::Read) {
+                return Err(access_denied(FileAccess::Read));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The requested name of a derived dataset, or `default` when none is given.
+fn derived_name(name: Option<&str>, default: impl FnOnce() -> String) -> String {
+    name.map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(default)
+}
+
+/// The source's property columns under their original names, each followed
+/// by a comma, to lead a select list.
+fn leading_columns(conn: &duckdb::Connection, source_id: &str) -> Result<String, String> {
+    Ok(exported_columns(conn, source_id)?
+        .iter()
+        .map(|column| format!("{column}, "))
+        .collect())
+}
+
+/// Add a `derived` dataset named `name` for the caller and fill it in the
+/// background from the SELECT that `select` builds, in `crs`.
+async fn create_derived_dataset(
+    state: &AppState,
+    auth_session: &AuthSession<AuthBackend>,
+    operation: &str,
+    name: String,
+    crs: String,
+    select: impl FnOnce(&duckdb::Connection) -> Result<String, String> + Send + 'static,
+) -> Result<(StatusCode, Json<FileItem>), (StatusCode, Json<ErrorResponse>)> {
+    let owner_id = auth_session.user.as_ref().map(|user| user.id.clone());
+    let id = create_id();
+    let dir = state.upload_dir.join(&id);
+    fs::create_dir_all(&dir).await.map_err(internal_error)?;
+    // Nothing is stored here; the directory keeps the thumbnail and exports.
+    let file_path = dir.join(format!("{operation}.derived"));
+    let rel_string = storage_path_string(&file_path);
+    let uploaded_at = Utc::now().to_rfc3339();
+
+    let conn = state.db.lock().await;
+    conn.execute(
+        "INSERT INTO files (id, name, type, size, uploaded_at, status, path, is_public, owner_id)
+         VALUES (?, ?, 'derived', 0, ?, 'uploaded', ?, FALSE, ?)",
+        duckdb::params![&id, &name, &uploaded_at, &rel_string, &owner_id],
+    )
+    .map_err(internal_error)?;
+    drop(conn);
+
+    let db = state.db.clone();
+    let task_id = id.clone();
+    tokio::spawn(async move {
+        let process = async {
+            let conn = db.lock().await;
+            let select_sql = select(&conn)?;
+            load_dataset_table(&conn, &task_id, Some(&crs), &select_sql)
+        };
+        let _ = track_import(&db, &task_id, &file_path, process).await;
+    });
+
+    let meta = FileItem {
+        id,
+        name,
+        file_type: "derived".to_string(),
+        size: 0,
+        uploaded_at,
+        status: "uploaded".to_string(),
+        crs: None,
+        path: rel_string,
+        table_name: None,
+        error: None,
+        is_public: Some(false),
+        public_slug: None,
+        org_id: None,
+        geometry_type: None,
+        feature_count: None,
+        folder: None,
+        tags: Vec::new(),
+    };
+    Ok((StatusCode::CREATED, Json(meta)))
+}
+
+fn validate_distance(distance: f64) -> Result<f64, String> {
+    if !distance.is_finite() || distance == 0.0 {
+        return Err("distance must be a non-zero number of meters".to_string());
+    }
+    if distance.abs() > MAX_BUFFER_DISTANCE {
+        return Err(format!(
+            "distance must be at most {MAX_BUFFER_DISTANCE} meters"
+        ));
+    }
+    Ok(distance)
+}
+
+/// SELECT of the source's features buffered by `distance` meters in the UTM
+/// zone of their centroid, in the source's CRS.
+fn buffer_select(source: &ProcessSource, columns: &str, distance: f64) -> String {
+    format!(
+        "SELECT fid, {columns}ST_Transform(
+                    ST_Buffer(ST_Transform(geom, {crs}, mapflow_utm, always_xy := true), {distance}),
+                    mapflow_utm, {crs}, always_xy := true) AS geom
+         FROM (
+             SELECT *, 'EPSG:' || ((CASE WHEN ST_Y(mapflow_centroid) < 0 THEN 32700 ELSE 32600 END)
+                 + least(60, floor((ST_X(mapflow_centroid) + 180) / 6) + 1)::INTEGER) AS mapflow_utm
+             FROM (
+                 SELECT *,
+                     ST_Centroid(ST_Transform(geom, {crs}, 'EPSG:4326', always_xy := true)) AS mapflow_centroid
+                 FROM {table}
+             )
+         )
+         ORDER BY fid",
+        crs = quote_literal(&source.crs),
+        table = quote_identifier(&source.table_name),
+    )
+}
+
+/// Buffer a dataset's features into a new dataset.
+async fn buffer_file(
+    State(state): State<AppState>,
+    auth_session: AuthSession<AuthBackend>,
+    AxumPath(id): AxumPath<String>,
+    Json(req): Json<BufferRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let distance = validate_distance(req.distance).map_err(|e| bad_request(&e))?;
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    let source = load_source(&conn, &id)?;
+    drop(conn);
+
+    let name = derived_name(req.name.as_deref(), || {
+        format!("{} (buffer {distance} m)", source.name)
+    });
+    let crs = source.crs.clone();
+    create_derived_dataset(&state, &auth_session, "buffer", name, crs, move |conn| {
+        let columns = leading_columns(conn, &source.id)?;
+        Ok(buffer_select(&source, &columns, distance))
+    })
+    .await
+}
+
+/// SELECT of one feature per value of `column`, the union of the features
+/// with that value.
+fn dissolve_select(source: &ProcessSource, column: &DatasetColumn) -> String {
+    let field = quote_identifier(&column.normalized);
+    let aliases = output_column_aliases(&[(column.normalized.clone(), column.original.clone())]);
+    format!(
+        "SELECT row_number() OVER (ORDER BY {field} NULLS LAST)::BIGINT AS fid,
+                {field} AS {alias}, ST_Union_Agg(geom) AS geom
+         FROM {table}
+         GROUP BY {field}
+         ORDER BY fid",
+        alias = quote_identifier(&aliases[0].1),
+        table = quote_identifier(&source.table_name),
+    )
+}
+
+/// Merge a dataset's features by the value of a field into a new dataset.
+async fn dissolve_file(
+    State(state): State<AppState>,
+    auth_session: AuthSession<AuthBackend>,
+    AxumPath(id): AxumPath<String>,
+    Json(req): Json<DissolveRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    let source = load_source(&conn, &id)?;
+    let columns = load_dataset_columns(&conn, &id).map_err(internal_error)?;
+    drop(conn);
+    let column = resolve_column(&columns, req.field.trim())
+        .ok_or_else(|| bad_request(&format!("Unknown field '{}'", req.field.trim())))?
+        .clone();
+
+    let name = derived_name(req.name.as_deref(), || {
+        format!("{} (dissolved by {})", source.name, column.original)
+    });
+    let crs = source.crs.clone();
+    create_derived_dataset(&state, &auth_session, "dissolve", name, crs, move |_| {
+        Ok(dissolve_select(&source, &column))
+    })
+    .await
+}
+
+/// Replace a dataset's geometries with points into a new dataset.
+async fn centroids_file(
+    State(state): State<AppState>,
+    auth_session: AuthSession<AuthBackend>,
+    AxumPath(id): AxumPath<String>,
+    Json(req): Json<CentroidsRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let method = match req.method.as_deref() {
+        None => PointMethod::default(),
+        Some(value) => PointMethod::parse(value)
+            .ok_or_else(|| bad_request("method must be centroid or pointOnSurface"))?,
+    };
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    let source = load_source(&conn, &id)?;
+    drop(conn);
+
+    let name = derived_name(req.name.as_deref(), || format!("{} (points)", source.name));
+    let crs = source.crs.clone();
+    create_derived_dataset(&state, &auth_session, "centroids", name, crs, move |conn| {
+        let columns = leading_columns(conn, &source.id)?;
+        Ok(format!(
+            "SELECT fid, {columns}{}(geom) AS geom FROM {} ORDER BY fid",
+            method.sql_function(),
+            quote_identifier(&source.table_name)
+        ))
+    })
+    .await
+}
+
+/// Copy a dataset into another CRS as a new dataset.
+async fn reproject_file(
+    State(state): State<AppState>,
+    auth_session: AuthSession<AuthBackend>,
+    AxumPath(id): AxumPath<String>,
+    Query(query): Query<ReprojectQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let to = query.crs().map_err(|e| bad_request(&e))?;
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    let source = load_source(&conn, &id)?;
+    if source.crs.eq_ignore_ascii_case(&to) {
+        return Err(bad_request(&format!("Dataset is already in {to}")));
+    }
+    conn.query_row(
+        "SELECT ST_Transform(ST_Point(0, 0), 'EPSG:4326', ?, always_xy := true)",
+        duckdb::params![to],
+        |_| Ok(()),
+    )
+    .map_err(|_| bad_request(&format!("Unknown CRS '{to}'")))?;
+    drop(conn);
+
+    let name = format!("{} ({to})", source.name);
+    let crs = to.clone();
+    create_derived_dataset(&state, &auth_session, "reproject", name, crs, move |conn| {
+        let columns = leading_columns(conn, &source.id)?;
+        Ok(format!(
+            "SELECT fid, {columns}ST_Transform(geom, {}, {}, always_xy := true) AS geom
+             FROM {} ORDER BY fid",
+            quote_literal(&source.crs),
+            quote_literal(&to),
+            quote_identifier(&source.table_name)
+        ))
+    })
+    .await
+}
+
+/// Select list of a spatial join's property columns: the target's (`t`)
+/// under their original names, then the join's (`j`) under theirs, prefixed
+/// with `join_` when the name is taken.
+fn spatial_join_columns(target: &[(String, String)], join: &[(String, String)]) -> Vec<String> {
+    let target_aliases = output_column_aliases(target);
+    let mut used: HashSet<String> = ["fid".to_string(), "geom".to_string()].into();
+    used.extend(target_aliases.iter().map(|(_, alias)| alias.to_lowercase()));
+    let mut columns: Vec<String> = target_aliases
+        .iter()
+        .map(|(normalized, alias)| {
+            format!(
+                "t.{} AS {}",
+                quote_identifier(normalized),
+                quote_identifier(alias)
+            )
+        })
+        .collect();
+
+    for (normalized, alias) in output_column_aliases(join) {
+        let mut name = alias.clone();
+        let mut suffix = 1;
+        while used.contains(&name.to_lowercase()) {
+            name = match suffix {
+                1 => format!("join_{alias}"),
+                n => format!("join_{alias}_{n}"),
+            };
+            suffix += 1;
+        }
+        used.insert(name.to_lowercase());
+        columns.push(format!(
+            "j.{} AS {}",
+            quote_identifier(&normalized),
+            quote_identifier(&name)
+        ));
+    }
+    columns
+}
+
+/// SELECT of every target feature with the properties of each join feature
+/// it matches, in the target's CRS.
+fn spatial_join_select(
+    target: &ProcessSource,
+    join: &ProcessSource,
+    columns: &[String],
+    predicate: SpatialPredicate,
+) -> String {
+    let join_table = quote_identifier(&join.table_name);
+    let join_rows = if join.crs.eq_ignore_ascii_case(&target.crs) {
+        join_table
+    } else {
+        format!(
+            "(SELECT * EXCLUDE (geom),
+                  ST_Transform(geom, {}, {}, always_xy := true) AS geom
+              FROM {join_table})",
+            quote_literal(&join.crs),
+            quote_literal(&target.crs)
+        )
+    };
+    let leading: String = columns.iter().map(|column| format!("{column}, ")).collect();
+    format!(
+        "SELECT row_number() OVER (ORDER BY t.fid, j.fid)::BIGINT AS fid, {leading}t.geom AS geom
+         FROM {target} AS t
+         LEFT JOIN {join_rows} AS j ON {predicate}(t.geom, j.geom)
+         ORDER BY fid",
+        target = quote_identifier(&target.table_name),
+        predicate = predicate.sql_function(),
+    )
+}
+
+/// Join the properties of one dataset's features onto another's by location,
+/// into a new dataset.
+pub async fn spatial_join(
+    State(state): State<AppState>,
+    auth_session: AuthSession<AuthBackend>,
+    Json(req): Json<SpatialJoinRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let predicate = match req.predicate.as_deref() {
+        None => SpatialPredicate::default(),
+        Some(value) => SpatialPredicate::parse(value)
+            .ok_or_else(|| bad_request("predicate must be intersects or within"))?,
+    };
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    check_read_access(&conn, &auth_session, &req.target)?;
+    check_read_access(&conn, &auth_session, &req.join)?;
+    let target = load_source(&conn, &req.target)?;
+    let join = load_source(&conn, &req.join)?;
+    drop(conn);
+
+    let name = derived_name(req.name.as_deref(), || {
+        format!("{} (joined with {})", target.name, join.name)
+    });
+    let crs = target.crs.clone();
+    create_derived_dataset(
+        &state,
+        &auth_session,
+        "spatial-join",
+        name,
+        crs,
+        move |conn| {
+            let columns = spatial_join_columns(
+                &column_names(conn, &target.id)?,
+                &column_names(conn, &join.id)?,
+            );
+            Ok(spatial_join_select(&target, &join, &columns, predicate))
+        },
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffer_distance_is_checked() {
+        assert_eq!(validate_distance(250.0), Ok(250.0));
+        assert_eq!(validate_distance(-10.0), Ok(-10.0));
+        assert!(validate_distance(0.0).is_err());
+        assert!(validate_distance(f64::NAN).is_err());
+        assert!(validate_distance(MAX_BUFFER_DISTANCE + 1.0).is_err());
+        assert_eq!(
+            derived_name(Some("  "), || "roads (buffer 5 m)".to_string()),
+            "roads (buffer 5 m)"
+        );
+        assert_eq!(derived_name(Some(" zones "), String::new), "zones");
+    }
+
+    #[test]
+    fn join_properties_are_renamed_when_the_target_has_them() {
+        let pairs = |names: &[(&str, &str)]| -> Vec<(String, String)> {
+            names
+                .iter()
+                .map(|(normalized, original)| (normalized.to_string(), original.to_string()))
+                .collect()
+        };
+        let columns = spatial_join_columns(
+            &pairs(&[("name", "Name"), ("join_name", "join_name")]),
+            &pairs(&[("name", "name"), ("zone", "Zone"), ("fid_2", "fid")]),
+        );
+        assert_eq!(
+            columns,
+            [
+                r#"t."name" AS "Name""#,
+                r#"t."join_name" AS "join_name""#,
+                r#"j."name" AS "join_name_2""#,
+                r#"j."zone" AS "Zone""#,
+                r#"j."fid_2" AS "fid_2""#,
+            ]
+        );
+        assert_eq!(
+            SpatialPredicate::parse(" Within "),
+            Some(SpatialPredicate::Within)
+        );
+        assert_eq!(SpatialPredicate::parse("touches"), None);
+        assert_eq!(
+            PointMethod::parse("pointOnSurface"),
+            Some(PointMethod::PointOnSurface)
+        );
+        assert_eq!(PointMethod::parse("center"), None);
+    }
+
+    #[test]
+    fn reprojection_target_is_checked() {
+        let crs = |to: Option<&str>| {
+            ReprojectQuery {
+                to: to.map(str::to_string),
+            }
+            .crs()
+        };
+        assert_eq!(crs(None), Ok("EPSG:3857".to_string()));
+        assert_eq!(crs(Some(" epsg:32633 ")), Ok("EPSG:32633".to_string()));
+        assert!(crs(Some("3857")).is_err());
+        assert!(crs(Some("EPSG:3857'; --")).is_err());
+    }
+}
@@ -0,0 +1,791 @@
+//! GeoTIFF raster handling
+//!
+//! `.tif` uploads are kept as uploaded and served as 256-pixel PNG tiles drawn
+//! from the raster on request, so Cloud-Optimized GeoTIFFs are read a few
+//! internal tiles (and the overview nearest the tile's resolution) at a time.
+//!
+//! ## Supported rasters
+//! - North-up georeferencing from `ModelPixelScale` + `ModelTiepoint` or a
+//!   `ModelTransformation` without rotation
+//! - A CRS given as an EPSG code in the GeoKey directory
+//! - 8-bit gray, gray + alpha, RGB and RGBA images, drawn as they are
+//! - Single-band 16/32/64-bit rasters such as DEMs, drawn in gray stretched
+//!   over the value range measured at import
+//!
+//! `GDAL_NODATA` pixels and pixels outside the raster are transparent. Tiles
+//! are reprojected by transforming a 17 x 17 grid of tile points to the
+//! raster's CRS in DuckDB and interpolating between them, and resampled by
+//! nearest neighbour.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use duckdb::{Connection, OptionalExt};
+use tiff::decoder::{ChunkType, Decoder, DecodingResult};
+use tiff::tags::Tag;
+use tiff::ColorType;
+use tiny_skia::Pixmap;
+
+use crate::columns::quote_literal;
+use crate::read_pool::ReadConnection;
+use crate::tiles::{mercator_pixel_size, WEB_MERCATOR_HALF_WORLD};
+
+/// Width and height of a drawn tile, in pixels.
+const TILE_SIZE: u32 = 256;
+/// Tile pixels between the points of the reprojection grid.
+const GRID_STEP: u32 = 16;
+/// Highest zoom offered for even the finest raster.
+const MAX_ZOOM: i32 = 22;
+/// Largest width or height of a GeoTIFF stored in strips rather than tiles.
+const MAX_STRIPPED_SIZE: u32 = 4096;
+
+/// GeoKey ids and values from the GeoTIFF specification.
+const GT_MODEL_TYPE: u16 = 1024;
+const GT_RASTER_TYPE: u16 = 1025;
+const GEOGRAPHIC_TYPE: u16 = 2048;
+const PROJECTED_CS_TYPE: u16 = 3072;
+const MODEL_TYPE_PROJECTED: u16 = 1;
+const MODEL_TYPE_GEOGRAPHIC: u16 = 2;
+const RASTER_PIXEL_IS_POINT: u16 = 2;
+const USER_DEFINED: u16 = 32767;
+
+/// `NewSubfileType` bits of an overview and of a transparency mask.
+const REDUCED_RESOLUTION: u32 = 1;
+const TRANSPARENCY_MASK: u32 = 4;
+
+/// How pixel samples become colours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Bands {
+    Gray,
+    GrayAlpha,
+    Rgb,
+    Rgba,
+    /// One band of wider values, stretched over the raster's value range.
+    Scalar,
+}
+
+impl Bands {
+    fn from_color_type(color_type: ColorType) -> Result<Self, String> {
+        match color_type {
+            ColorType::Gray(8) => Ok(Self::Gray),
+            ColorType::GrayA(8) => Ok(Self::GrayAlpha),
+            ColorType::RGB(8) => Ok(Self::Rgb),
+            ColorType::RGBA(8) => Ok(Self::Rgba),
+            ColorType::Gray(16 | 32 | 64) => Ok(Self::Scalar),
+            other => Err(format!("Unsupported GeoTIFF pixel layout: {:?}", other)),
+        }
+    }
+
+    fn samples(self) -> usize {
+        match self {
+            Self::Gray | Self::Scalar => 1,
+            Self::GrayAlpha => 2,
+            Self::Rgb => 3,
+            Self::Rgba => 4,
+        }
+    }
+}
+
+/// Size, georeferencing and pixel layout of a GeoTIFF's full-resolution image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeoRaster {
+    pub width: u32,
+    pub height: u32,
+    /// `EPSG:<code>`.
+    pub crs: String,
+    /// CRS coordinates of the raster's top-left corner.
+    origin: (f64, f64),
+    /// Pixel width and height in CRS units; rows run south.
+    pixel_size: (f64, f64),
+    nodata: Option<f64>,
+    bands: Bands,
+}
+
+impl GeoRaster {
+    /// `[minx, miny, maxx, maxy]` in the raster's CRS.
+    pub fn bounds(&self) -> [f64; 4] {
+        let (x, y) = self.origin;
+        [
+            x,
+            y - f64::from(self.height) * self.pixel_size.1,
+            x + f64::from(self.width) * self.pixel_size.0,
+            y,
+        ]
+    }
+
+    /// Full-resolution pixel position of a CRS coordinate.
+    fn pixel(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        (
+            (x - self.origin.0) / self.pixel_size.0,
+            (self.origin.1 - y) / self.pixel_size.1,
+        )
+    }
+}
+
+fn open(path: &Path) -> Result<Decoder<BufReader<File>>, String> {
+    let file = File::open(path).map_err(|e| format!("Cannot open GeoTIFF: {}", e))?;
+    Decoder::new(BufReader::new(file)).map_err(|e| format!("Invalid GeoTIFF file: {}", e))
+}
+
+/// Read the georeferencing of the decoder's current (first) image.
+fn read_raster(decoder: &mut Decoder<BufReader<File>>) -> Result<GeoRaster, String> {
+    let (width, height) = decoder
+        .dimensions()
+        .map_err(|e| format!("Invalid GeoTIFF file: {}", e))?;
+    let bands = Bands::from_color_type(
+        decoder
+            .colortype()
+            .map_err(|e| format!("Invalid GeoTIFF file: {}", e))?,
+    )?;
+    let planar: Option<u16> = decoder
+        .find_tag_unsigned(Tag::PlanarConfiguration)
+        .map_err(|e| format!("Invalid GeoTIFF file: {}", e))?;
+    if planar == Some(2) && bands.samples() > 1 {
+        return Err("Band-interleaved (planar) GeoTIFFs are not supported".to_string());
+    }
+
+    let has_geo_keys = decoder
+        .find_tag(Tag::GeoKeyDirectoryTag)
+        .map_err(|e| format!("Invalid GeoTIFF file: {}", e))?
+        .is_some();
+    if !has_geo_keys {
+        return Err("GeoTIFF has no GeoKey directory; is the file georeferenced?".to_string());
+    }
+    let geo_keys = decoder
+        .get_tag_u16_vec(Tag::GeoKeyDirectoryTag)
+        .map_err(|e| format!("Invalid GeoTIFF GeoKey directory: {}", e))?;
+    let crs = crs_from_geo_keys(&geo_keys)?;
+
+    let (mut origin, pixel_size) = match read_f64s(decoder, Tag::ModelTransformationTag)? {
+        Some(matrix) => transformation_georeferencing(&matrix)?,
+        None => {
+            let scale = read_f64s(decoder, Tag::ModelPixelScaleTag)?
+                .ok_or("GeoTIFF has no pixel scale or transformation")?;
+            let tiepoint = read_f64s(decoder, Tag::ModelTiepointTag)?
+                .ok_or("GeoTIFF has no tie point or transformation")?;
+            tiepoint_georeferencing(&scale, &tiepoint)?
+        }
+    };
+    if geo_key(&geo_keys, GT_RASTER_TYPE) == Some(RASTER_PIXEL_IS_POINT) {
+        // Coordinates name pixel centres; move to the corner.
+        origin = (origin.0 - pixel_size.0 / 2.0, origin.1 + pixel_size.1 / 2.0);
+    }
+
+    let nodata = decoder
+        .get_tag_ascii_string(Tag::GdalNodata)
+        .ok()
+        .and_then(|value| value.trim_matches(char::from(0)).trim().parse().ok());
+
+    Ok(GeoRaster {
+        width,
+        height,
+        crs,
+        origin,
+        pixel_size,
+        nodata,
+        bands,
+    })
+}
+
+/// A tag's values as `f64`, or `None` when the image lacks it.
+fn read_f64s(decoder: &mut Decoder<BufReader<File>>, tag: Tag) -> Result<Option<Vec<f64>>, String> {
+    let Some(value) = decoder
+        .find_tag(tag)
+        .map_err(|e| format!("Invalid GeoTIFF file: {}", e))?
+    else {
+        return Ok(None);
+    };
+    value
+        .into_f64_vec()
+        .map(Some)
+        .map_err(|e| format!("Invalid GeoTIFF {:?} tag: {}", tag, e))
+}
+
+/// Value of an inline GeoKey; keys stored in other tags are not needed here.
+fn geo_key(directory: &[u16], id: u16) -> Option<u16> {
+    directory
+        .get(4..)?
+        .chunks_exact(4)
+        .find(|entry| entry[0] == id && entry[1] == 0)
+        .map(|entry| entry[3])
+}
+
+fn crs_from_geo_keys(directory: &[u16]) -> Result<String, String> {
+    let code = match geo_key(directory, GT_MODEL_TYPE) {
+        Some(MODEL_TYPE_PROJECTED) => geo_key(directory, PROJECTED_CS_TYPE),
+        Some(MODEL_TYPE_GEOGRAPHIC) => geo_key(directory, GEOGRAPHIC_TYPE),
+        _ => None,
+    };
+    match code {
+        Some(code) if code != 0 && code != USER_DEFINED => Ok(format!("EPSG:{code}")),
+        _ => Err("GeoTIFF CRS must be given as an EPSG code".to_string()),
+    }
+}
+
+fn tiepoint_georeferencing(
+    scale: &[f64],
+    tiepoint: &[f64],
+) -> Result<((f64, f64), (f64, f64)), String> {
+    let (&[sx, sy, ..], &[i, j, _, x, y, ..]) = (scale, tiepoint) else {
+        return Err("GeoTIFF pixel scale or tie point is incomplete".to_string());
+    };
+    if !(sx > 0.0 && sy > 0.0) {
+        return Err("GeoTIFF pixel scale must be positive".to_string());
+    }
+    Ok(((x - i * sx, y + j * sy), (sx, sy)))
+}
+
+fn transformation_georeferencing(matrix: &[f64]) -> Result<((f64, f64), (f64, f64)), String> {
+    let &[a, b, _, d, e, f, _, h, ..] = matrix else {
+        return Err("GeoTIFF transformation is incomplete".to_string());
+    };
+    if b != 0.0 || e != 0.0 {
+        return Err("Rotated GeoTIFFs are not supported".to_string());
+    }
+    if !(a > 0.0 && f < 0.0) {
+        return Err("GeoTIFF must be north-up".to_string());
+    }
+    Ok(((d, h), (a, -f)))
+}
+
+/// Check that an upload is a GeoTIFF MapFlow can draw.
+pub fn validate_geotiff(file_path: &Path) -> Result<(), String> {
+    let mut decoder = open(file_path)?;
+    let raster = read_raster(&mut decoder)?;
+    // A tile reads whole strips, each as wide as the image, and decodes them
+    // again on every request; only tiled images stay cheap at any size.
+    let too_big = |(width, height): (u32, u32)| width.max(height) > MAX_STRIPPED_SIZE;
+    if decoder.get_chunk_type() == ChunkType::Strip && too_big((raster.width, raster.height)) {
+        return Err(format!(
+            "GeoTIFFs over {MAX_STRIPPED_SIZE} pixels a side must be tiled; convert it to a Cloud-Optimized GeoTIFF, e.g. with `gdal_translate -of COG`"
+        ));
+    }
+    Ok(())
+}
+
+/// One image of the file: the full-resolution image or an overview.
+#[derive(Debug, Clone, Copy)]
+struct Level {
+    index: usize,
+    width: u32,
+    height: u32,
+}
+
+/// The full-resolution image and its overviews, finest first.
+fn read_levels(decoder: &mut Decoder<BufReader<File>>, raster: &GeoRaster) -> Vec<Level> {
+    let mut levels = vec![Level {
+        index: 0,
+        width: raster.width,
+        height: raster.height,
+    }];
+    let mut index = 0;
+    while decoder.more_images() && decoder.next_image().is_ok() {
+        index += 1;
+        let subfile_type: u32 = decoder
+            .find_tag_unsigned(Tag::NewSubfileType)
+            .ok()
+            .flatten()
+            .unwrap_or(0);
+        let same_layout = decoder
+            .colortype()
+            .is_ok_and(|color_type| Bands::from_color_type(color_type) == Ok(raster.bands));
+        if subfile_type & REDUCED_RESOLUTION == 0
+            || subfile_type & TRANSPARENCY_MASK != 0
+            || !same_layout
+        {
+            continue;
+        }
+        if let Ok((width, height)) = decoder.dimensions() {
+            levels.push(Level {
+                index,
+                width,
+                height,
+            });
+        }
+    }
+    levels.sort_by_key(|level| std::cmp::Reverse(level.width));
+    levels
+}
+
+/// Reads pixels of one image, decoding its strips or tiles as they are needed.
+struct LevelReader<'a> {
+    decoder: &'a mut Decoder<BufReader<File>>,
+    level: Level,
+    chunk_size: (u32, u32),
+    chunks_across: u32,
+    chunks: HashMap<u32, Option<(u32, DecodingResult)>>,
+}
+
+impl<'a> LevelReader<'a> {
+    fn new(decoder: &'a mut Decoder<BufReader<File>>, level: Level) -> Result<Self, String> {
+        decoder
+            .seek_to_image(level.index)
+            .map_err(|e| format!("Cannot read GeoTIFF image: {}", e))?;
+        let chunk_size = decoder.chunk_dimensions();
+        Ok(Self {
+            decoder,
+            level,
+            chunk_size,
+            chunks_across: level.width.div_ceil(chunk_size.0.max(1)),
+            chunks: HashMap::new(),
+        })
+    }
+
+    /// Samples of pixel `(col, row)`; `None` outside the image or in an
+    /// unreadable chunk.
+    fn pixel(&mut self, col: u32, row: u32, samples: usize) -> Option<Vec<f64>> {
+        let (data, index) = self.locate(col, row)?;
+        (index * samples..(index + 1) * samples)
+            .map(|i| sample(data, i))
+            .collect()
+    }
+
+    /// The value of pixel `(col, row)` of a single-band image.
+    fn value(&mut self, col: u32, row: u32) -> Option<f64> {
+        let (data, index) = self.locate(col, row)?;
+        sample(data, index)
+    }
+
+    /// The decoded chunk holding pixel `(col, row)` and the pixel's index in it.
+    fn locate(&mut self, col: u32, row: u32) -> Option<(&DecodingResult, usize)> {
+        if col >= self.level.width || row >= self.level.height {
+            return None;
+        }
+        let (chunk_width, chunk_height) = self.chunk_size;
+        let index = (row / chunk_height) * self.chunks_across + col / chunk_width;
+        let decoder = &mut *self.decoder;
+        let chunk = self.chunks.entry(index).or_insert_with(|| {
+            let data_width = decoder.chunk_data_dimensions(index).0;
+            decoder
+                .read_chunk(index)
+                .ok()
+                .map(|data| (data_width, data))
+        });
+        let (data_width, data) = chunk.as_ref()?;
+        Some((
+            data,
+            ((row % chunk_height) * data_width + col % chunk_width) as usize,
+        ))
+    }
+}
+
+fn sample(data: &DecodingResult, i: usize) -> Option<f64> {
+    Some(match data {
+        DecodingResult::U8(values) => f64::from(*values.get(i)?),
+        DecodingResult::U16(values) => f64::from(*values.get(i)?),
+        DecodingResult::U32(values) => f64::from(*values.get(i)?),
+        DecodingResult::U64(values) => *values.get(i)? as f64,
+        DecodingResult::I8(values) => f64::from(*values.get(i)?),
+        DecodingResult::I16(values) => f64::from(*values.get(i)?),
+        DecodingResult::I32(values) => f64::from(*values.get(i)?),
+        DecodingResult::I64(values) => *values.get(i)? as f64,
+        DecodingResult::F32(values) => f64::from(*values.get(i)?),
+        DecodingResult::F64(values) => *values.get(i)?,
+        #[allow(unreachable_patterns)]
+        _ => return None,
+    })
+}
+
+/// Straight-alpha RGBA of a pixel's samples; `None` for no-data.
+fn color(raster: &GeoRaster, range: Option<[f64; 2]>, samples: &[f64]) -> Option<[u8; 4]> {
+    let is_nodata = |value: &f64| raster.nodata == Some(*value);
+    let byte = |value: f64| value.clamp(0.0, 255.0) as u8;
+    match (raster.bands, samples) {
+        (Bands::Gray, &[v]) if !is_nodata(&v) => Some([byte(v), byte(v), byte(v), 255]),
+        (Bands::GrayAlpha, &[v, a]) if !is_nodata(&v) => Some([byte(v), byte(v), byte(v), byte(a)]),
+        (Bands::Rgb, &[r, g, b]) if !samples.iter().all(is_nodata) => {
+            Some([byte(r), byte(g), byte(b), 255])
+        }
+        (Bands::Rgba, &[r, g, b, a]) if !samples[..3].iter().all(is_nodata) => {
+            Some([byte(r), byte(g), byte(b), byte(a)])
+        }
+        (Bands::Scalar, &[v]) if v.is_finite() && !is_nodata(&v) => {
+            let [min, max] = range?;
+            let t = if max > min {
+                (v - min) / (max - min)
+            } else {
+                0.5
+            };
+            let gray = byte((t * 255.0).round());
+            Some([gray, gray, gray, 255])
+        }
+        _ => None,
+    }
+}
+
+/// Smallest and largest valid value of a single-band raster, read from its
+/// coarsest overview (or the image itself when it has none).
+fn measure_range(
+    decoder: &mut Decoder<BufReader<File>>,
+    raster: &GeoRaster,
+) -> Result<Option<[f64; 2]>, String> {
+    if raster.bands != Bands::Scalar {
+        return Ok(None);
+    }
+    let levels = read_levels(decoder, raster);
+    let level = *levels.last().expect("the full-resolution level");
+    let mut reader = LevelReader::new(decoder, level)?;
+    let (mut min, mut max) = (f64::INFINITY, f64::NEG_INFINITY);
+    for row in 0..level.height {
+        for col in 0..level.width {
+            let Some(value) = reader.value(col, row) else {
+                continue;
+            };
+            if !value.is_finite() || raster.nodata == Some(value) {
+                continue;
+            }
+            min = min.min(value);
+            max = max.max(value);
+        }
+        // Rows above are done with; keep only the chunks still needed.
+        if (row + 1) % reader.chunk_size.1 == 0 {
+            reader.chunks.clear();
+        }
+    }
+    Ok((min <= max).then_some([min, max]))
+}
+
+/// Transform points from Web Mercator to `crs`; unprojectable points come
+/// back as NaN.
+fn transform_from_mercator(
+    conn: &Connection,
+    points: &[(f64, f64)],
+    crs: &str,
+) -> Result<Vec<(f64, f64)>, duckdb::Error> {
+    if crs == "EPSG:3857" {
+        return Ok(points.to_vec());
+    }
+    let values = points
+        .iter()
+        .enumerate()
+        .map(|(i, (x, y))| format!("({i}, {x:?}, {y:?})"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let sql = format!(
+        "SELECT ST_X(p), ST_Y(p) FROM (
+            SELECT i, ST_Transform(ST_Point(x, y), 'EPSG:3857', {}, always_xy := true) AS p
+            FROM (VALUES {values}) v(i, x, y)
+        ) ORDER BY i",
+        quote_literal(crs)
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, Option<f64>>(0)?.unwrap_or(f64::NAN),
+            row.get::<_, Option<f64>>(1)?.unwrap_or(f64::NAN),
+        ))
+    })?;
+    rows.collect()
+}
+
+/// `[minx, miny, maxx, maxy]` of the raster in WGS84.
+fn wgs84_bounds(conn: &Connection, raster: &GeoRaster) -> Result<[f64; 4], duckdb::Error> {
+    let [minx, miny, maxx, maxy] = raster.bounds();
+    if raster.crs == "EPSG:4326" {
+        return Ok([minx, miny, maxx, maxy]);
+    }
+    conn.query_row(
+        "SELECT ST_XMin(g), ST_YMin(g), ST_XMax(g), ST_YMax(g) FROM (
+            SELECT ST_Transform(ST_MakeEnvelope(?, ?, ?, ?), ?, 'EPSG:4326', always_xy := true) AS g
+        )",
+        duckdb::params![minx, miny, maxx, maxy, &raster.crs],
+        |row| Ok([row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?]),
+    )
+}
+
+/// Lowest zoom at which a tile pixel is no larger than a raster pixel.
+fn native_zoom(bounds: &[f64; 4], width: u32) -> i32 {
+    let degrees_per_pixel = (bounds[2] - bounds[0]) / f64::from(width);
+    let meters_per_pixel = degrees_per_pixel / 360.0 * 2.0 * WEB_MERCATOR_HALF_WORLD;
+    (0..MAX_ZOOM)
+        .find(|&z| mercator_pixel_size(TILE_SIZE, f64::from(z)) <= meters_per_pixel)
+        .unwrap_or(MAX_ZOOM)
+}
+
+/// Record an uploaded GeoTIFF as a PNG tile source: its CRS, WGS84 bounds,
+/// zoom range up to its native resolution and, for single-band rasters, the
+/// value range tiles are stretched over.
+pub async fn import_geotiff(
+    db: &std::sync::Arc<tokio::sync::Mutex<duckdb::Connection>>,
+    source_id: &str,
+    file_path: &Path,
+) -> Result<(), String> {
+    let path = file_path.to_path_buf();
+    let (raster, range) = tokio::task::spawn_blocking(move || {
+        let mut decoder = open(&path)?;
+        let raster = read_raster(&mut decoder)?;
+        let range = measure_range(&mut decoder, &raster)?;
+        Ok::<_, String>((raster, range))
+    })
+    .await
+    .map_err(|e| format!("GeoTIFF import failed: {}", e))??;
+
+    let conn = db.lock().await;
+    let bounds = wgs84_bounds(&conn, &raster)
+        .map_err(|e| format!("Cannot transform GeoTIFF bounds from {}: {}", raster.crs, e))?;
+    if bounds.iter().any(|value| !value.is_finite()) {
+        return Err(format!(
+            "GeoTIFF bounds cannot be transformed from {}",
+            raster.crs
+        ));
+    }
+    conn.execute(
+        "UPDATE files SET crs = ?, tile_format = 'png', minzoom = 0, maxzoom = ?, tile_bounds = ?, raster_range = ? WHERE id = ?",
+        duckdb::params![
+            &raster.crs,
+            native_zoom(&bounds, raster.width),
+            serde_json::json!(bounds).to_string(),
+            range.map(|range| serde_json::json!(range).to_string()),
+            source_id
+        ],
+    )
+    .map_err(|e| format!("Failed to update file metadata: {}", e))?;
+
+    Ok(())
+}
+
+/// What tiles of a GeoTIFF upload need from its `files` row.
+pub struct GeoTiffSource {
+    range: Option<[f64; 2]>,
+}
+
+/// The GeoTIFF behind `file_id`, or `None` when it is another kind of file.
+pub fn load_geotiff_source(
+    conn: &Connection,
+    file_id: &str,
+) -> Result<Option<GeoTiffSource>, duckdb::Error> {
+    let range: Option<Option<String>> = conn
+        .query_row(
+            "SELECT raster_range FROM files WHERE id = ? AND type = 'geotiff'",
+            duckdb::params![file_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(range.map(|range| GeoTiffSource {
+        range: range.and_then(|range| serde_json::from_str(&range).ok()),
+    }))
+}
+
+/// Draw tile `z/x/y` of a GeoTIFF; `Ok(None)` when no raster pixel falls on it.
+pub async fn render_geotiff_tile(
+    conn: ReadConnection,
+    file_path: PathBuf,
+    source: GeoTiffSource,
+    tile: (i32, i32, i32),
+) -> Result<Option<Vec<u8>>, String> {
+    conn.run(move |conn| draw_tile(conn, &file_path, source.range, tile))
+        .await
+}
+
+fn draw_tile(
+    conn: &Connection,
+    file_path: &Path,
+    range: Option<[f64; 2]>,
+    (z, x, y): (i32, i32, i32),
+) -> Result<Option<Vec<u8>>, String> {
+    let mut decoder = open(file_path)?;
+    let raster = read_raster(&mut decoder)?;
+
+    // Raster pixel positions of the grid points of the tile.
+    let tile_span = 2.0 * WEB_MERCATOR_HALF_WORLD / f64::from(1u32 << z);
+    let (left, top) = (
+        -WEB_MERCATOR_HALF_WORLD + f64::from(x) * tile_span,
+        WEB_MERCATOR_HALF_WORLD - f64::from(y) * tile_span,
+    );
+    let steps = TILE_SIZE / GRID_STEP + 1;
+    let points: Vec<(f64, f64)> = (0..steps)
+        .flat_map(|j| (0..steps).map(move |i| (i, j)))
+        .map(|(i, j)| {
+            let fraction = |step: u32| f64::from(step * GRID_STEP) / f64::from(TILE_SIZE);
+            (
+                left + fraction(i) * tile_span,
+                top - fraction(j) * tile_span,
+            )
+        })
+        .collect();
+    let grid: Vec<(f64, f64)> = transform_from_mercator(conn, &points, &raster.crs)
+        .map_err(|e| format!("Cannot transform tile to {}: {}", raster.crs, e))?
+        .into_iter()
+        .map(|point| raster.pixel(point))
+        .collect();
+    let grid_point = |i: u32, j: u32| grid[(j * steps + i) as usize];
+
+    // Use the coarsest image that still has a pixel per tile pixel.
+    let (first, last) = (grid_point(0, steps / 2), grid_point(steps - 1, steps / 2));
+    let raster_pixels_per_tile_pixel =
+        (last.0 - first.0).hypot(last.1 - first.1) / f64::from(TILE_SIZE);
+    let levels = read_levels(&mut decoder, &raster);
+    let level = levels
+        .iter()
+        .rev()
+        .find(|level| {
+            f64::from(raster.width) / f64::from(level.width) <= raster_pixels_per_tile_pixel
+        })
+        .copied()
+        .unwrap_or(levels[0]);
+    let scale = (
+        f64::from(level.width) / f64::from(raster.width),
+        f64::from(level.height) / f64::from(raster.height),
+    );
+    let mut reader = LevelReader::new(&mut decoder, level)?;
+
+    let mut pixmap = Pixmap::new(TILE_SIZE, TILE_SIZE).ok_or("Invalid tile size")?;
+    let mut drawn = false;
+    let data = pixmap.data_mut();
+    for py in 0..TILE_SIZE {
+        for px in 0..TILE_SIZE {
+            // Interpolate the raster position of the pixel centre in its grid cell.
+            let (u, v) = (
+                (f64::from(px) + 0.5) / f64::from(GRID_STEP),
+                (f64::from(py) + 0.5) / f64::from(GRID_STEP),
+            );
+            let (i, j) = (u.floor() as u32, v.floor() as u32);
+            let (fu, fv) = (u.fract(), v.fract());
+            let lerp = |a: (f64, f64), b: (f64, f64), t: f64| {
+                (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+            };
+            let (col, row) = lerp(
+                lerp(grid_point(i, j), grid_point(i + 1, j), fu),
+                lerp(grid_point(i, j + 1), grid_point(i + 1, j + 1), fu),
+                fv,
+            );
+            if !(col >= 0.0 && row >= 0.0) {
+                continue;
+            }
+            let Some(samples) = reader.pixel(
+                (col * scale.0) as u32,
+                (row * scale.1) as u32,
+                raster.bands.samples(),
+            ) else {
+                continue;
+            };
+            let Some([r, g, b, a]) = color(&raster, range, &samples) else {
+                continue;
+            };
+            let premultiply = |c: u8| (u16::from(c) * u16::from(a) / 255) as u8;
+            let offset = ((py * TILE_SIZE + px) * 4) as usize;
+            data[offset..offset + 4].copy_from_slice(&[
+                premultiply(r),
+                premultiply(g),
+                premultiply(b),
+                a,
+            ]);
+            drawn |= a > 0;
+        }
+    }
+    if !drawn {
+        return Ok(None);
+    }
+    pixmap.encode_png().map(Some).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raster(bands: Bands, nodata: Option<f64>) -> GeoRaster {
+        GeoRaster {
+            width: 4,
+            height: 2,
+            crs: "EPSG:4326".to_string(),
+            origin: (10.0, 50.0),
+            pixel_size: (0.5, 0.25),
+            nodata,
+            bands,
+        }
+    }
+
+    #[test]
+    fn reads_epsg_codes_from_geo_keys() {
+        let geographic = [
+            1,
+            1,
+            0,
+            2,
+            GT_MODEL_TYPE,
+            0,
+            1,
+            2,
+            GEOGRAPHIC_TYPE,
+            0,
+            1,
+            4326,
+        ];
+        assert_eq!(crs_from_geo_keys(&geographic).unwrap(), "EPSG:4326");
+        let projected = [
+            1,
+            1,
+            0,
+            2,
+            GT_MODEL_TYPE,
+            0,
+            1,
+            1,
+            PROJECTED_CS_TYPE,
+            0,
+            1,
+            32633,
+        ];
+        assert_eq!(crs_from_geo_keys(&projected).unwrap(), "EPSG:32633");
+        let user_defined = [
+            1,
+            1,
+            0,
+            2,
+            GT_MODEL_TYPE,
+            0,
+            1,
+            1,
+            PROJECTED_CS_TYPE,
+            0,
+            1,
+            USER_DEFINED,
+        ];
+        assert!(crs_from_geo_keys(&user_defined).is_err());
+    }
+
+    #[test]
+    fn georeferences_from_tie_point_or_transformation() {
+        let from_tiepoint =
+            tiepoint_georeferencing(&[0.5, 0.25, 0.0], &[2.0, 4.0, 0.0, 11.0, 49.0, 0.0]).unwrap();
+        assert_eq!(from_tiepoint, ((10.0, 50.0), (0.5, 0.25)));
+        let matrix = [
+            0.5, 0.0, 0.0, 10.0, 0.0, -0.25, 0.0, 50.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+        ];
+        assert_eq!(
+            transformation_georeferencing(&matrix).unwrap(),
+            from_tiepoint
+        );
+        let mut rotated = matrix;
+        rotated[1] = 0.1;
+        assert!(transformation_georeferencing(&rotated).is_err());
+
+        let raster = raster(Bands::Gray, None);
+        assert_eq!(raster.bounds(), [10.0, 49.5, 12.0, 50.0]);
+        assert_eq!(raster.pixel((11.0, 49.5)), (2.0, 2.0));
+    }
+
+    #[test]
+    fn colors_samples_and_skips_nodata() {
+        let gray = raster(Bands::Gray, Some(0.0));
+        assert_eq!(color(&gray, None, &[200.0]), Some([200, 200, 200, 255]));
+        assert_eq!(color(&gray, None, &[0.0]), None);
+
+        let dem = raster(Bands::Scalar, Some(-9999.0));
+        let range = Some([100.0, 300.0]);
+        assert_eq!(color(&dem, range, &[200.0]), Some([128, 128, 128, 255]));
+        assert_eq!(color(&dem, range, &[500.0]), Some([255, 255, 255, 255]));
+        assert_eq!(color(&dem, range, &[-9999.0]), None);
+        assert_eq!(color(&dem, range, &[f64::NAN]), None);
+    }
+
+    #[test]
+    fn native_zoom_matches_pixel_size() {
+        // A 256-pixel-wide world is zoom 0; half a degree per pixel is
+        // between zooms 1 and 2.
+        assert_eq!(native_zoom(&[-180.0, -85.0, 180.0, 85.0], 256), 0);
+        assert_eq!(native_zoom(&[0.0, 0.0, 360.0, 1.0], 720), 2);
+    }
+}
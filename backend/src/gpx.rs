@@ -0,0 +1,35 @@
+//! GPX imports
+//!
+//! GDAL reads a GPX file as several layers, and reading it as one dataset
+//! keeps only the first (waypoints), losing tracks and routes. Each non-empty
+//! layer of [`GPX_LAYERS`] is imported as a dataset of its own instead; see
+//! `layers.rs`. Waypoints and track points keep their `ele` and `time`
+//! columns; the elevation of tracks and routes is in their Z values, kept only
+//! with `zValues=elevation` (see `zm.rs`).
+
+use std::path::Path;
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+
+use crate::layers::{import_gdal_layers, non_empty_layers};
+
+/// GDAL layers imported from a GPX file, in order. Route points repeat the
+/// routes' vertices without adding attributes, so they are left out.
+pub const GPX_LAYERS: [&str; 4] = ["waypoints", "routes", "tracks", "track_points"];
+const GPX_CRS: &str = "EPSG:4326";
+
+pub async fn import_gpx(
+    db: &Arc<Mutex<duckdb::Connection>>,
+    file_id: &str,
+    file_path: &Path,
+) -> Result<(), String> {
+    import_gdal_layers(db, file_id, file_path, GPX_CRS, |conn, abs_path| {
+        let layers = non_empty_layers(conn, abs_path, GPX_LAYERS.map(str::to_string));
+        if layers.is_empty() {
+            return Err("GPX file has no waypoints, routes or tracks".to_string());
+        }
+        Ok(layers)
+    })
+    .await
+}
@@ -0,0 +1,216 @@
+//! Health check and probes
+//!
+//! `GET /health` runs the checks a working instance depends on: DuckDB
+//! answers a query, the spatial extension is loaded, and the upload directory
+//! accepts new files. Each check reports its own status so a failing probe
+//! says what is broken; any failure turns the response into a 503.
+//!
+//! For orchestrators there are two cheaper probes. `/livez` only says the
+//! process is serving requests. `/readyz` says the instance should get
+//! traffic: startup finished and the database answers. `Startup` serves both
+//! while the database is opened, the spatial extension installed and
+//! interrupted jobs reconciled, and forwards to the real router once `finish`
+//! hands it over.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use tower::ServiceExt;
+
+use crate::models::{CheckStatus, HealthResponse};
+use crate::AppState;
+
+/// How long the check waits for the database lock before calling it busy.
+const DB_LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn status_of(result: Result<(), String>) -> CheckStatus {
+    match result {
+        Ok(()) => CheckStatus {
+            status: "ok".to_string(),
+            error: None,
+        },
+        Err(error) => CheckStatus {
+            status: "error".to_string(),
+            error: Some(error),
+        },
+    }
+}
+
+fn check_upload_dir(upload_dir: &Path) -> Result<(), String> {
+    let probe = upload_dir.join(format!(".health-{}", uuid::Uuid::new_v4()));
+    std::fs::write(&probe, b"ok").map_err(|e| format!("Upload directory is not writable: {e}"))?;
+    let _ = std::fs::remove_file(&probe);
+    Ok(())
+}
+
+fn check_database(conn: &duckdb::Connection) -> Result<(), String> {
+    conn.query_row("SELECT 1", [], |row| row.get::<_, i32>(0))
+        .map(|_| ())
+        .map_err(|e| format!("Database query failed: {e}"))
+}
+
+fn probe_response(code: StatusCode) -> Response {
+    let status = if code.is_success() {
+        "ok"
+    } else {
+        "unavailable"
+    };
+    (code, Json(serde_json::json!({ "status": status }))).into_response()
+}
+
+pub async fn livez() -> Response {
+    probe_response(StatusCode::OK)
+}
+
+/// Ready once the database answers; the routers serving this only exist
+/// after startup.
+pub async fn readyz(State(state): State<AppState>) -> Response {
+    let ready = match tokio::time::timeout(DB_LOCK_TIMEOUT, state.db.lock()).await {
+        Ok(conn) => check_database(&conn).is_ok(),
+        Err(_) => false,
+    };
+    if ready {
+        probe_response(StatusCode::OK)
+    } else {
+        probe_response(StatusCode::SERVICE_UNAVAILABLE)
+    }
+}
+
+/// Router for a server that binds before it finishes starting up.
+#[derive(Clone, Default)]
+pub struct Startup {
+    app: Arc<OnceLock<Router>>,
+}
+
+impl Startup {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Route every request to `app` from now on.
+    pub fn finish(&self, app: Router) {
+        if self.app.set(app).is_err() {
+            tracing::warn!("Startup was already finished");
+        }
+    }
+
+    /// `/livez` answers at once; everything else, `/readyz` included, gets a
+    /// 503 until `finish` is called.
+    pub fn router(&self) -> Router {
+        let startup = self.clone();
+        Router::new()
+            .route("/livez", get(livez))
+            .fallback(move |request: Request| {
+                let startup = startup.clone();
+                async move {
+                    match startup.app.get() {
+                        Some(app) => app.clone().oneshot(request).await.into_response(),
+                        None if request.uri().path() == "/readyz" => {
+                            probe_response(StatusCode::SERVICE_UNAVAILABLE)
+                        }
+                        None => (
+                            StatusCode::SERVICE_UNAVAILABLE,
+                            Json(crate::ErrorResponse {
+                                error: "Server is starting".to_string(),
+                            }),
+                        )
+                            .into_response(),
+                    }
+                }
+            })
+    }
+}
+
+pub async fn health_check(State(state): State<AppState>) -> impl IntoResponse {
+    let (database, spatial) = match tokio::time::timeout(DB_LOCK_TIMEOUT, state.db.lock()).await {
+        Ok(conn) => (
+            check_database(&conn),
+            conn.query_row("SELECT ST_AsText(ST_Point(0, 0))", [], |row| {
+                row.get::<_, String>(0)
+            })
+            .map(|_| ())
+            .map_err(|e| format!("Spatial extension is not available: {e}")),
+        ),
+        Err(_) => {
+            let busy = "Database did not respond in time".to_string();
+            (Err(busy.clone()), Err(busy))
+        }
+    };
+    let upload_dir = check_upload_dir(&state.upload_dir);
+
+    let checks = BTreeMap::from([
+        ("database".to_string(), status_of(database)),
+        ("spatial".to_string(), status_of(spatial)),
+        ("uploadDir".to_string(), status_of(upload_dir)),
+    ]);
+    let healthy = checks.values().all(|check| check.error.is_none());
+    for (name, check) in checks.iter().filter(|(_, check)| check.error.is_some()) {
+        tracing::warn!(check = %name, error = ?check.error, "Health check failed");
+    }
+    let (code, status) = if healthy {
+        (StatusCode::OK, "ok")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "error")
+    };
+    (
+        code,
+        Json(HealthResponse {
+            status: status.to_string(),
+            checks,
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+
+    async fn status_of_path(router: &Router, path: &str) -> StatusCode {
+        let request = Request::builder().uri(path).body(Body::empty()).unwrap();
+        router.clone().oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn startup_serves_probes_until_finished() {
+        let startup = Startup::new();
+        let router = startup.router();
+        assert_eq!(status_of_path(&router, "/livez").await, StatusCode::OK);
+        assert_eq!(
+            status_of_path(&router, "/readyz").await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(
+            status_of_path(&router, "/api/files").await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+
+        startup.finish(Router::new().route("/readyz", get(|| async { "ready" })));
+        assert_eq!(status_of_path(&router, "/livez").await, StatusCode::OK);
+        assert_eq!(status_of_path(&router, "/readyz").await, StatusCode::OK);
+        assert_eq!(
+            status_of_path(&router, "/api/files").await,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[test]
+    fn upload_dir_probe_leaves_nothing_behind() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(check_upload_dir(dir.path()).is_ok());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+
+        let missing = dir.path().join("missing");
+        let error = check_upload_dir(&missing).unwrap_err();
+        assert!(error.starts_with("Upload directory is not writable"));
+    }
+}
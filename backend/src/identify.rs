@@ -0,0 +1,130 @@
+//! Point identify queries
+//!
+//! Finds the features under a clicked map position: geometries that contain the
+//! point or lie within a few screen pixels of it at the current zoom. Distances
+//! are measured in Web Mercator, the same projection the tiles are cut in.
+
+use serde::Deserialize;
+
+use crate::columns::{quote_identifier, quote_literal, DatasetColumn};
+use crate::tiles::{mercator_pixel_size, web_mercator_geom_sql};
+
+/// Click tolerance in screen pixels (256px tiles).
+pub const IDENTIFY_TOLERANCE_PIXELS: f64 = 5.0;
+pub const DEFAULT_IDENTIFY_LIMIT: u32 = 10;
+pub const MAX_IDENTIFY_LIMIT: u32 = 100;
+pub const MAX_IDENTIFY_ZOOM: f64 = 24.0;
+
+/// Web Mercator is undefined at the poles; clicks are clamped to its latitude range.
+const MAX_MERCATOR_LATITUDE: f64 = 85.051_128_779_806_59;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct IdentifyQuery {
+    pub lng: Option<f64>,
+    pub lat: Option<f64>,
+    pub zoom: Option<f64>,
+    pub limit: Option<u32>,
+}
+
+/// Validated identify request.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IdentifyPoint {
+    pub lng: f64,
+    pub lat: f64,
+    /// Search radius in Web Mercator meters.
+    pub tolerance: f64,
+    pub limit: u32,
+}
+
+impl IdentifyQuery {
+    pub fn point(&self) -> Result<IdentifyPoint, String> {
+        let (Some(lng), Some(lat), Some(zoom)) = (self.lng, self.lat, self.zoom) else {
+            return Err("lng, lat and zoom are required".to_string());
+        };
+        if !(-180.0..=180.0).contains(&lng) || !(-90.0..=90.0).contains(&lat) {
+            return Err("lng/lat must be WGS84 degrees".to_string());
+        }
+        if !(0.0..=MAX_IDENTIFY_ZOOM).contains(&zoom) {
+            return Err(format!("zoom must be between 0 and {MAX_IDENTIFY_ZOOM}"));
+        }
+        let limit = match self.limit {
+            None => DEFAULT_IDENTIFY_LIMIT,
+            Some(limit) if (1..=MAX_IDENTIFY_LIMIT).contains(&limit) => limit,
+            Some(_) => return Err(format!("limit must be between 1 and {MAX_IDENTIFY_LIMIT}")),
+        };
+
+        Ok(IdentifyPoint {
+            lng,
+            lat: lat.clamp(-MAX_MERCATOR_LATITUDE, MAX_MERCATOR_LATITUDE),
+            tolerance: IDENTIFY_TOLERANCE_PIXELS * mercator_pixel_size(256, zoom),
+            limit,
+        })
+    }
+}
+
+/// Select `fid`, the property columns, the WGS84 GeoJSON geometry and the
+/// distance to the point, nearest first. Parameters: lng, lat, tolerance.
+pub fn build_identify_sql(
+    table_name: &str,
+    source_crs: &str,
+    columns: &[DatasetColumn],
+    limit: u32,
+) -> String {
+    let mut select_exprs = vec!["fid".to_string()];
+    select_exprs.extend(columns.iter().map(|c| quote_identifier(&c.normalized)));
+    select_exprs.push(format!(
+        "ST_AsGeoJSON(ST_Transform(geom, {}, 'EPSG:4326', always_xy := true))",
+        quote_literal(source_crs)
+    ));
+
+    format!(
+        "WITH click AS (\n    SELECT ST_Transform(ST_Point(?, ?), 'EPSG:4326', 'EPSG:3857', always_xy := true) AS point\n),\ncandidates AS (\n    SELECT *, {} AS geom_3857 FROM {}\n)\nSELECT {}, ST_Distance(geom_3857, click.point) AS distance\nFROM candidates, click\nWHERE ST_DWithin(geom_3857, click.point, ?)\nORDER BY distance, fid\nLIMIT {limit}",
+        web_mercator_geom_sql(source_crs),
+        quote_identifier(table_name),
+        select_exprs.join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(lng: f64, lat: f64, zoom: f64) -> IdentifyQuery {
+        IdentifyQuery {
+            lng: Some(lng),
+            lat: Some(lat),
+            zoom: Some(zoom),
+            limit: None,
+        }
+    }
+
+    #[test]
+    fn tolerance_halves_with_each_zoom_level() {
+        let z0 = query(0.0, 0.0, 0.0).point().unwrap();
+        let z1 = query(0.0, 0.0, 1.0).point().unwrap();
+        assert_eq!(z0.limit, DEFAULT_IDENTIFY_LIMIT);
+        assert!((z0.tolerance / z1.tolerance - 2.0).abs() < 1e-9);
+        // 5px at zoom 0 on a 256px world.
+        assert!((z0.tolerance - 5.0 * 40_075_016.685_578_49 / 256.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn latitude_is_clamped_to_mercator_range() {
+        assert_eq!(
+            query(10.0, 90.0, 3.0).point().unwrap().lat,
+            MAX_MERCATOR_LATITUDE
+        );
+    }
+
+    #[test]
+    fn rejects_missing_or_out_of_range_parameters() {
+        assert!(IdentifyQuery::default().point().is_err());
+        assert!(query(181.0, 0.0, 1.0).point().is_err());
+        assert!(query(0.0, -91.0, 1.0).point().is_err());
+        assert!(query(0.0, 0.0, -1.0).point().is_err());
+        assert!(query(0.0, 0.0, f64::NAN).point().is_err());
+        let mut too_many = query(0.0, 0.0, 1.0);
+        too_many.limit = Some(MAX_IDENTIFY_LIMIT + 1);
+        assert!(too_many.point().is_err());
+    }
+}
@@ -4,40 +4,47 @@ use std::sync::Arc;
 
 use tokio::sync::Mutex;
 
+use crate::antimeridian::split_new_features;
+use crate::spatial_index::create_spatial_index;
+use crate::versions::retain_current_version;
+use crate::zm::{add_elevation_columns, flatten_new_features};
+
 pub async fn import_spatial_data(
     db: &Arc<Mutex<duckdb::Connection>>,
     source_id: &str,
     file_path: &Path,
 ) -> Result<(), String> {
-    let abs_path = std::fs::canonicalize(file_path)
-        .map_err(|e| format!("Cannot resolve file path {:?}: {}", file_path, e))?
-        .to_string_lossy()
-        .to_string();
-
-    let abs_path = if file_path.extension().and_then(|e| e.to_str()) == Some("zip") {
-        // Use /vsizip/ prefix for GDAL to read directly from zip
-        format!("/vsizip/{}", abs_path)
-    } else {
-        abs_path
-    };
+    let abs_path = gdal_source_path(file_path)?;
 
     let conn = db.lock().await;
 
     // 1. Detect CRS using ST_Read_Meta
-    // layers[1].geometry_fields[1].crs.auth_name / auth_code
-    // Note: ST_Read_Meta return structure depends on the file.
-    // We try to get the first layer's CRS.
-    // List indexing in DuckDB is 1-based.
-    let crs_query = format!(
-        "SELECT 
-            layers[1].geometry_fields[1].crs.auth_name || ':' || layers[1].geometry_fields[1].crs.auth_code 
-         FROM ST_Read_Meta('{abs_path}')"
-    );
+    let detected_crs = detect_crs(&conn, &abs_path);
+
+    load_dataset_table(
+        &conn,
+        source_id,
+        detected_crs.as_deref(),
+        &format!(
+            "SELECT row_number() OVER ()::BIGINT AS fid, *\n         FROM ST_Read('{abs_path}')"
+        ),
+    )
+}
 
-    let detected_crs: Option<String> = conn.query_row(&crs_query, [], |row| row.get(0)).ok();
+/// Create a dataset's table from `select_sql`, which must yield a `fid` and a
+/// geometry column, then normalize its columns and record them and its stats.
+pub fn load_dataset_table(
+    conn: &duckdb::Connection,
+    source_id: &str,
+    detected_crs: Option<&str>,
+    select_sql: &str,
+) -> Result<(), String> {
+    // Keep the data being replaced, before its CRS is overwritten.
+    retain_current_version(conn, source_id)
+        .map_err(|e| format!("Failed to keep the previous version: {e}"))?;
 
     // Update files table with detected CRS
-    if let Some(crs) = &detected_crs {
+    if let Some(crs) = detected_crs {
         let _ = conn.execute(
             "UPDATE files SET crs = ? WHERE id = ?",
             duckdb::params![crs, source_id],
@@ -50,12 +57,11 @@ pub async fn import_spatial_data(
     let safe_table_name =
         normalize_column_name(&table_name).unwrap_or_else(|| format!("layer_{}", source_id));
 
-    // Drop if exists (id collision should be impossible, but keep idempotent).
+    // Drop if exists: a new import of the dataset, whose previous table was
+    // kept above, or an id collision, which should be impossible.
     let _ = conn.execute(&format!("DROP TABLE IF EXISTS \"{safe_table_name}\""), []);
 
-    let create_sql = format!(
-        "CREATE TABLE \"{safe_table_name}\" AS\n         SELECT row_number() OVER ()::BIGINT AS fid, *\n         FROM ST_Read('{abs_path}')"
-    );
+    let create_sql = format!("CREATE TABLE \"{safe_table_name}\" AS\n         {select_sql}");
 
     conn.execute(&create_sql, [])
         .map_err(|e| format!("Spatial import failed: {}", e))?;
@@ -114,7 +120,10 @@ pub async fn import_spatial_data(
         }
     }
 
-    // Refresh columns after potential geom rename.
+    add_elevation_columns(conn, source_id, &safe_table_name)?;
+    flatten_new_features(conn, source_id, &safe_table_name, 0)?;
+
+    // Refresh columns after potential geom rename and added elevation columns.
     let mut refresh_stmt = conn
         .prepare(
             "SELECT column_name, data_type, ordinal_position\n             FROM information_schema.columns\n             WHERE table_schema = 'main' AND table_name = ?\n             ORDER BY ordinal_position",
@@ -233,9 +242,103 @@ pub async fn import_spatial_data(
         }
     }
 
+    split_new_features(conn, source_id, &safe_table_name, detected_crs, 0)?;
+
+    // Column renames and type changes are done, so the table can be indexed.
+    // A missing index only costs speed; the schema endpoint reports it.
+    if let Err(e) = create_spatial_index(conn, &safe_table_name) {
+        tracing::warn!(table = %safe_table_name, error = %e, "Failed to create spatial index");
+    }
+
+    update_dataset_stats(conn, source_id, &safe_table_name, detected_crs)
+        .map_err(|e| format!("Failed to measure dataset: {}", e))?;
+
+    Ok(())
+}
+
+/// Store the WGS84 extent, feature count and geometry type of a dataset on its
+/// `files` row, so previews don't scan the table. Run after every import and
+/// every change that adds features or moves geometries.
+pub fn update_dataset_stats(
+    conn: &duckdb::Connection,
+    source_id: &str,
+    table_name: &str,
+    crs: Option<&str>,
+) -> Result<(), duckdb::Error> {
+    let bbox = crate::dataset_bbox(conn, None, Some(table_name), crs)
+        .map(|bbox| serde_json::to_string(&bbox).expect("bbox serializes"));
+    let feature_count: i64 = conn.query_row(
+        &format!("SELECT count(*) FROM \"{table_name}\""),
+        [],
+        |row| row.get(0),
+    )?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT DISTINCT ST_GeometryType(geom)::VARCHAR FROM \"{table_name}\" WHERE geom IS NOT NULL"
+    ))?;
+    let types = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    conn.execute(
+        "UPDATE files SET bbox = ?, feature_count = ?, geometry_type = ? WHERE id = ?",
+        duckdb::params![bbox, feature_count, geometry_family(&types), source_id],
+    )?;
     Ok(())
 }
 
+/// `Point`, `LineString` or `Polygon` when every geometry (single or multi) is
+/// of that kind, `Geometry` for a mix or collections, `None` without geometries.
+fn geometry_family(types: &[String]) -> Option<&'static str> {
+    let family = |geometry_type: &String| match geometry_type
+        .to_ascii_uppercase()
+        .trim_start_matches("MULTI")
+    {
+        "POINT" => "Point",
+        "LINESTRING" => "LineString",
+        "POLYGON" => "Polygon",
+        _ => "Geometry",
+    };
+    let first = family(types.first()?);
+    if types
+        .iter()
+        .all(|geometry_type| family(geometry_type) == first)
+    {
+        Some(first)
+    } else {
+        Some("Geometry")
+    }
+}
+
+/// Absolute path of an uploaded file as GDAL should open it.
+pub fn gdal_source_path(file_path: &Path) -> Result<String, String> {
+    let abs_path = std::fs::canonicalize(file_path)
+        .map_err(|e| format!("Cannot resolve file path {:?}: {}", file_path, e))?
+        .to_string_lossy()
+        .to_string();
+
+    if file_path.extension().and_then(|e| e.to_str()) == Some("zip") {
+        // Use /vsizip/ prefix for GDAL to read directly from zip
+        Ok(format!("/vsizip/{}", abs_path))
+    } else {
+        Ok(abs_path)
+    }
+}
+
+/// CRS of the first layer as `AUTH:CODE`, if GDAL reports one.
+pub fn detect_crs(conn: &duckdb::Connection, abs_path: &str) -> Option<String> {
+    // layers[1].geometry_fields[1].crs.auth_name / auth_code
+    // Note: ST_Read_Meta return structure depends on the file.
+    // We try to get the first layer's CRS.
+    // List indexing in DuckDB is 1-based.
+    let crs_query = format!(
+        "SELECT 
+            layers[1].geometry_fields[1].crs.auth_name || ':' || layers[1].geometry_fields[1].crs.auth_code 
+         FROM ST_Read_Meta('{abs_path}')"
+    );
+
+    conn.query_row(&crs_query, [], |row| row.get(0)).ok()
+}
+
 fn normalize_column_name(name: &str) -> Option<String> {
     let trimmed = name.trim();
     if trimmed.is_empty() {
@@ -278,3 +381,28 @@ fn normalize_column_name(name: &str) -> Option<String> {
 
     Some(out)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn geometry_types_collapse_to_a_family() {
+        let types =
+            |names: &[&str]| -> Vec<String> { names.iter().map(|name| name.to_string()).collect() };
+        assert_eq!(geometry_family(&[]), None);
+        assert_eq!(geometry_family(&types(&["POINT"])), Some("Point"));
+        assert_eq!(
+            geometry_family(&types(&["POLYGON", "MULTIPOLYGON"])),
+            Some("Polygon")
+        );
+        assert_eq!(
+            geometry_family(&types(&["LINESTRING", "POINT"])),
+            Some("Geometry")
+        );
+        assert_eq!(
+            geometry_family(&types(&["GEOMETRYCOLLECTION"])),
+            Some("Geometry")
+        );
+    }
+}
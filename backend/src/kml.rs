@@ -0,0 +1,30 @@
+//! KML imports
+//!
+//! GDAL reads each folder of a KML document as a layer, and reading the
+//! document as one dataset keeps only the first. Each folder with placemarks
+//! is imported as a dataset of its own instead, named after the folder; see
+//! `layers.rs`. A document without folders stays a single dataset.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+
+use crate::layers::{gdal_layers, import_gdal_layers, non_empty_layers};
+
+const KML_CRS: &str = "EPSG:4326";
+
+pub async fn import_kml(
+    db: &Arc<Mutex<duckdb::Connection>>,
+    file_id: &str,
+    file_path: &Path,
+) -> Result<(), String> {
+    import_gdal_layers(db, file_id, file_path, KML_CRS, |conn, abs_path| {
+        let layers = non_empty_layers(conn, abs_path, gdal_layers(conn, abs_path)?);
+        if layers.is_empty() {
+            return Err("KML document has no placemarks".to_string());
+        }
+        Ok(layers)
+    })
+    .await
+}
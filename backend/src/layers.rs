@@ -0,0 +1,194 @@
+//! Multi-layer imports
+//!
+//! GPX files, KML documents with folders and zips of several shapefiles hold
+//! more than one layer, but a dataset is a single table. Each layer is
+//! imported as a dataset of its own: the first into the uploaded file and the
+//! rest into copies of the upload, added as new files named
+//! `<name> (<layer>)` and owned and filed like it. Every file records the
+//! layer it reads in `files.layer`, so importing it again reads the same one
+//! instead of fanning out again.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+
+use crate::columns::quote_literal;
+use crate::import::{gdal_source_path, load_dataset_table};
+use crate::{create_id, storage_path_string, track_import};
+
+/// Dataset name of one layer of an upload that has several.
+pub fn layer_name(name: &str, layer: &str) -> String {
+    format!("{name} ({layer})")
+}
+
+/// Copy an upload with several layers into a new upload, to import another of
+/// its layers, and add its `files` row, owned, filed and imported like
+/// `file_id` and reading `layer`. Returns the new file's id and path.
+pub fn create_layer_file(
+    conn: &duckdb::Connection,
+    file_id: &str,
+    file_path: &Path,
+    name: &str,
+    layer: &str,
+) -> Result<(String, PathBuf), String> {
+    let layer_id = create_id();
+    let file_name = file_path.file_name().ok_or("Upload has no file name")?;
+    let upload_dir = file_path
+        .parent()
+        .and_then(Path::parent)
+        .ok_or("Upload is not in an upload directory")?;
+    let dir = upload_dir.join(&layer_id);
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let layer_path = dir.join(file_name);
+    std::fs::copy(file_path, &layer_path).map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "INSERT INTO files (id, name, type, size, uploaded_at, status, path, is_public, owner_id, org_id, folder, encoding, layer, split_antimeridian, z_values)
+         SELECT ?, ?, type, size, uploaded_at, 'uploaded', ?, FALSE, owner_id, org_id, folder, encoding, ?, split_antimeridian, z_values
+         FROM files WHERE id = ?",
+        duckdb::params![
+            &layer_id,
+            name,
+            storage_path_string(&layer_path),
+            layer,
+            file_id
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok((layer_id, layer_path))
+}
+
+/// A file's name and the layer it reads, if it has been assigned one.
+pub fn file_layer(
+    conn: &duckdb::Connection,
+    file_id: &str,
+) -> Result<(String, Option<String>), String> {
+    conn.query_row(
+        "SELECT name, layer FROM files WHERE id = ?",
+        duckdb::params![file_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .map_err(|e| format!("File lookup failed: {e}"))
+}
+
+/// Names of the layers GDAL reads from `abs_path`, in order.
+pub fn gdal_layers(conn: &duckdb::Connection, abs_path: &str) -> Result<Vec<String>, String> {
+    let failed = |e: duckdb::Error| format!("Failed to list layers: {e}");
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT layer.name FROM (SELECT unnest(layers) AS layer FROM ST_Read_Meta({}))",
+            quote_literal(abs_path)
+        ))
+        .map_err(failed)?;
+    let rows = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(failed)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(failed)
+}
+
+/// The `layers` of `abs_path` with at least one feature.
+pub fn non_empty_layers(
+    conn: &duckdb::Connection,
+    abs_path: &str,
+    layers: impl IntoIterator<Item = String>,
+) -> Vec<String> {
+    layers
+        .into_iter()
+        .filter(|layer| {
+            let count: Result<i64, _> = conn.query_row(
+                &format!(
+                    "SELECT count(*) FROM ST_Read({}, layer = {})",
+                    quote_literal(abs_path),
+                    quote_literal(layer)
+                ),
+                [],
+                |row| row.get(0),
+            );
+            count.is_ok_and(|count| count > 0)
+        })
+        .collect()
+}
+
+fn layer_select(abs_path: &str, layer: &str) -> String {
+    format!(
+        "SELECT row_number() OVER ()::BIGINT AS fid, *\n         FROM ST_Read({}, layer = {})",
+        quote_literal(abs_path),
+        quote_literal(layer)
+    )
+}
+
+/// Import the first of the GDAL layers `list_layers` picks into `file_id`,
+/// or the layer it already reads, and start importing each further one into
+/// a new file. Every layer is read as being in `crs`.
+pub async fn import_gdal_layers(
+    db: &Arc<Mutex<duckdb::Connection>>,
+    file_id: &str,
+    file_path: &Path,
+    crs: &'static str,
+    list_layers: impl FnOnce(&duckdb::Connection, &str) -> Result<Vec<String>, String>,
+) -> Result<(), String> {
+    let abs_path = gdal_source_path(file_path)?;
+    let conn = db.lock().await;
+    let (name, layer) = file_layer(&conn, file_id)?;
+    let layers = match layer {
+        Some(layer) => vec![layer],
+        None => list_layers(&conn, &abs_path)?,
+    };
+    let Some((first, rest)) = layers.split_first() else {
+        return Err("File has no layers".to_string());
+    };
+    load_dataset_table(&conn, file_id, Some(crs), &layer_select(&abs_path, first))?;
+
+    let dataset_name = if rest.is_empty() {
+        name.clone()
+    } else {
+        layer_name(&name, first)
+    };
+    conn.execute(
+        "UPDATE files SET name = ?, layer = ? WHERE id = ?",
+        duckdb::params![dataset_name, first, file_id],
+    )
+    .map_err(|e| format!("Failed to record layer: {e}"))?;
+
+    let mut layer_files = Vec::with_capacity(rest.len());
+    for layer in rest {
+        match create_layer_file(&conn, file_id, file_path, &layer_name(&name, layer), layer) {
+            Ok((layer_id, layer_path)) => layer_files.push((layer_id, layer_path, layer.clone())),
+            Err(e) => tracing::error!(file_id, layer, error = %e, "Failed to add layer"),
+        }
+    }
+    drop(conn);
+
+    for (layer_id, layer_path, layer) in layer_files {
+        let db = db.clone();
+        tokio::spawn(async move {
+            let import = async {
+                let abs_path = gdal_source_path(&layer_path)?;
+                let conn = db.lock().await;
+                load_dataset_table(
+                    &conn,
+                    &layer_id,
+                    Some(crs),
+                    &layer_select(&abs_path, &layer),
+                )
+            };
+            let _ = track_import(&db, &layer_id, &layer_path, import).await;
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layers_are_read_by_name() {
+        assert_eq!(
+            layer_select("/data/o'hare.gpx", "tracks"),
+            "SELECT row_number() OVER ()::BIGINT AS fid, *\n         FROM ST_Read('/data/o''hare.gpx', layer = 'tracks')"
+        );
+        assert_eq!(layer_name("ride", "tracks"), "ride (tracks)");
+    }
+}
@@ -0,0 +1,176 @@
+//! LDAP / Active Directory sign-in
+//!
+//! With `AUTH_BACKEND=ldap`, `AuthBackend` checks passwords against a
+//! directory instead of the local bcrypt hashes. The user is looked up with an
+//! optional service account, then bound as with their own password. On
+//! success the user is created or updated locally, taking their role from the
+//! configured admin/editor groups, so sessions, ownership and shares keep
+//! working on local user ids. Usernames the directory does not know fall back
+//! to local accounts, which keeps the `/api/auth/init` admin usable, and so
+//! does an unreachable directory. A directory user whose name belongs to a
+//! local account is refused rather than signed in as that account.
+
+use ldap3::{ldap_escape, LdapConnAsync, Scope, SearchEntry};
+
+use crate::auth::Role;
+
+/// Marks users whose password lives in the directory; never a valid bcrypt hash.
+pub const LDAP_PASSWORD_HASH: &str = "!ldap";
+
+#[derive(Clone, Debug)]
+pub struct LdapConfig {
+    /// `ldap://` or `ldaps://` server URL (`LDAP_URL`).
+    pub url: String,
+    /// Service account used for the user search; anonymous when unset.
+    pub bind_dn: Option<String>,
+    pub bind_password: Option<String>,
+    /// Subtree searched for users (`LDAP_USER_BASE_DN`).
+    pub user_base_dn: String,
+    /// Search filter with a `{username}` placeholder (`LDAP_USER_FILTER`).
+    pub user_filter: String,
+    /// Attribute listing the user's group DNs (`LDAP_GROUP_ATTRIBUTE`).
+    pub group_attribute: String,
+    pub admin_group: Option<String>,
+    pub editor_group: Option<String>,
+    /// Role for users in neither group (`LDAP_DEFAULT_ROLE`).
+    pub default_role: Role,
+}
+
+pub const DEFAULT_USER_FILTER: &str = "(uid={username})";
+pub const DEFAULT_GROUP_ATTRIBUTE: &str = "memberOf";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum LdapOutcome {
+    /// The password was accepted; the user should have this role.
+    Authenticated(Role),
+    /// The directory knows the user but rejected the password.
+    InvalidCredentials,
+    /// No directory entry matched the username.
+    UnknownUser,
+}
+
+impl LdapConfig {
+    fn user_filter_for(&self, username: &str) -> String {
+        self.user_filter
+            .replace("{username}", &ldap_escape(username))
+    }
+
+    /// The most privileged role any of `groups` maps to. Group DNs compare
+    /// case-insensitively, as directories do.
+    pub fn role_for_groups(&self, groups: &[String]) -> Role {
+        let member_of = |group: &Option<String>| {
+            group.as_ref().is_some_and(|group| {
+                groups
+                    .iter()
+                    .any(|candidate| candidate.eq_ignore_ascii_case(group))
+            })
+        };
+        if member_of(&self.admin_group) {
+            Role::Admin
+        } else if member_of(&self.editor_group) {
+            Role::Editor
+        } else {
+            self.default_role
+        }
+    }
+}
+
+/// Check `username`/`password` against the directory.
+pub async fn authenticate(
+    config: &LdapConfig,
+    username: &str,
+    password: &str,
+) -> Result<LdapOutcome, ldap3::LdapError> {
+    // An empty password would be an unauthenticated bind, which servers accept.
+    if password.is_empty() {
+        return Ok(LdapOutcome::InvalidCredentials);
+    }
+
+    let (conn, mut ldap) = LdapConnAsync::new(&config.url).await?;
+    ldap3::drive!(conn);
+
+    if let (Some(bind_dn), Some(bind_password)) = (&config.bind_dn, &config.bind_password) {
+        ldap.simple_bind(bind_dn, bind_password).await?.success()?;
+    }
+    let (entries, _) = ldap
+        .search(
+            &config.user_base_dn,
+            Scope::Subtree,
+            &config.user_filter_for(username),
+            vec![config.group_attribute.as_str()],
+        )
+        .await?
+        .success()?;
+    // Ambiguous filters must not let the first match sign in.
+    let Ok([entry]) = <[_; 1]>::try_from(entries) else {
+        let _ = ldap.unbind().await;
+        return Ok(LdapOutcome::UnknownUser);
+    };
+    let entry = SearchEntry::construct(entry);
+
+    let outcome = match ldap.simple_bind(&entry.dn, password).await?.success() {
+        Ok(_) => {
+            let groups = entry
+                .attrs
+                .get(&config.group_attribute)
+                .cloned()
+                .unwrap_or_default();
+            LdapOutcome::Authenticated(config.role_for_groups(&groups))
+        }
+        Err(_) => LdapOutcome::InvalidCredentials,
+    };
+    let _ = ldap.unbind().await;
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> LdapConfig {
+        LdapConfig {
+            url: "ldap://localhost".to_string(),
+            bind_dn: None,
+            bind_password: None,
+            user_base_dn: "ou=people,dc=example,dc=org".to_string(),
+            user_filter: DEFAULT_USER_FILTER.to_string(),
+            group_attribute: DEFAULT_GROUP_ATTRIBUTE.to_string(),
+            admin_group: Some("cn=gis-admins,ou=groups,dc=example,dc=org".to_string()),
+            editor_group: Some("cn=gis-editors,ou=groups,dc=example,dc=org".to_string()),
+            default_role: Role::Viewer,
+        }
+    }
+
+    #[test]
+    fn groups_map_to_the_highest_role() {
+        let config = config();
+        let groups = |names: &[&str]| -> Vec<String> {
+            names
+                .iter()
+                .map(|name| format!("cn={name},ou=groups,dc=example,dc=org"))
+                .collect()
+        };
+        assert_eq!(config.role_for_groups(&[]), Role::Viewer);
+        assert_eq!(
+            config.role_for_groups(&groups(&["gis-editors"])),
+            Role::Editor
+        );
+        assert_eq!(
+            config.role_for_groups(&groups(&["gis-editors", "gis-admins"])),
+            Role::Admin
+        );
+        assert_eq!(
+            config.role_for_groups(&["CN=GIS-Admins,OU=Groups,DC=example,DC=org".to_string()]),
+            Role::Admin
+        );
+    }
+
+    #[test]
+    fn usernames_are_escaped_in_the_filter() {
+        assert_eq!(config().user_filter_for("alice"), "(uid=alice)");
+        assert_eq!(
+            config().user_filter_for("*)(uid=admin"),
+            "(uid=\\2a\\29\\28uid=admin)"
+        );
+    }
+}
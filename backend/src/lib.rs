@@ -1,34 +1,107 @@
 use axum::{
-    extract::{DefaultBodyLimit, Multipart, Path as AxumPath, State},
+    extract::{DefaultBodyLimit, Multipart, Path as AxumPath, Query, State},
     http::{header, StatusCode},
     response::IntoResponse,
-    routing::{get, post},
+    routing::{delete, get, patch, post, put},
     Json, Router,
 };
-use axum_login::AuthManagerLayerBuilder;
-use chrono::Utc;
+use axum_login::{AuthManagerLayerBuilder, AuthSession};
+use chrono::{DateTime, Utc};
+use duckdb::OptionalExt;
 use rand::RngCore;
+use sha2::{Digest, Sha256};
 use std::path::{Path, PathBuf};
 use tokio::{
     fs,
     io::{AsyncWriteExt, BufWriter},
 };
-use tower_http::cors::CorsLayer;
+use tower_http::{cors::CorsLayer, trace::TraceLayer};
 use tower_sessions::SessionManagerLayer;
 
+mod antimeridian;
+mod api_tokens;
+mod append;
+mod attributes;
 mod auth;
 mod auth_routes;
+mod authz;
+mod backup;
+pub mod cli;
+mod columns;
+mod compressed;
 mod config;
 mod db;
+mod export;
+mod feature_edit;
+mod features;
+mod field_stats;
+mod file_events;
+mod filter;
+mod geoprocessing;
+mod geotiff;
+mod gpx;
+mod health;
 mod http_errors;
+mod identify;
 mod import;
+mod kml;
+mod layers;
+mod ldap;
+mod logging;
 mod mbtiles;
+mod measure;
+mod metadata;
+mod migrations;
 mod models;
+mod ogc;
+mod ogc_tiles;
+mod openapi;
+mod orgs;
+mod orphans;
+mod overzoom;
 mod password;
+mod password_reset;
+mod pbf;
+mod postgis;
+mod public_cors;
+mod raster;
+mod read_pool;
+mod ready_file;
+mod remote;
+mod request_id;
+mod retention;
+mod scan;
+mod seed;
 mod session_store;
+mod settings;
+mod shapefile;
+mod shares;
+mod signing;
+mod slug_history;
+mod spatial_index;
+mod sql_query;
+mod storage;
+mod style;
+mod tags;
 mod test_routes;
+mod thumbnail;
+mod tile_cache;
+mod tile_debug;
+mod tile_geojson;
+mod tile_options;
+mod tile_routes;
+mod tile_seed;
+mod tilejson;
 mod tiles;
+mod tilesets;
+mod users;
 mod validation;
+mod versions;
+mod viewer;
+mod webhooks;
+mod wms;
+mod wmts;
+mod zm;
 
 /// Type alias for file metadata from the database
 type FileMetadata = (
@@ -40,48 +113,178 @@ type FileMetadata = (
     Option<String>,
     Option<i32>,
     Option<i32>,
+    Option<i64>,
+    Option<String>,
+    Option<i64>,
+    Option<String>,
 );
 
-pub use auth::{AuthBackend, User};
+/// Type alias for (file_id, tile_options, signing_secret, expires_at, version)
+/// of a public slug
+type PublishedSlugRow = (
+    String,
+    Option<String>,
+    Option<String>,
+    Option<chrono::NaiveDateTime>,
+    Option<i32>,
+);
+
+/// Type alias for (status, table_name, tile_format, crs) of a feature source
+type FeatureSourceRow = (String, Option<String>, Option<String>, Option<String>);
+
+use api_tokens::{bearer_auth, build_tokens_router};
+use append::{append_spatial_data, UploadQuery};
+use attributes::{apply_attribute_update, attribute_source_reader, AttributeUpdateError};
+pub use auth::{AuthBackend, Role, User};
 pub use auth_routes::build_auth_router;
-pub use config::{format_bytes, read_cookie_secure, read_max_size_config};
+pub use authz::FileAccess;
+use authz::{access_denied, can_change, file_access, not_owner, require_file_access, require_role};
+use backup::build_backup_router;
+pub use backup::RestoreReport;
+use columns::{
+    load_dataset_columns, quote_identifier, quote_literal, resolve_column, DatasetColumn,
+};
+use compressed::{unpack_upload, Compression, UnpackError};
+pub use config::{format_bytes, read_ldap_config, Config, CONFIG_PATH_ENV, DEFAULT_CONFIG_PATH};
+use db::bump_data_version;
 pub use db::{
-    init_database, is_initialized, reconcile_processing_files, set_initialized, DEFAULT_DB_PATH,
-    PROCESSING_RECONCILIATION_ERROR,
+    expire_published_files, init_database, is_initialized, reconcile_export_jobs,
+    reconcile_processing_files, reconcile_seed_jobs, set_initialized, shutdown_database,
+    InterruptedJobs, DEFAULT_DB_PATH, PROCESSING_RECONCILIATION_ERROR,
+    PUBLISH_EXPIRY_SWEEP_INTERVAL,
 };
-use duckdb::types::ValueRef;
+use export::{export_dataset, load_export_job, ExportFormat};
+use feature_edit::{
+    geojson_geometry_text, insert_feature, parse_property_updates, replace_feature_geometry,
+    update_feature_properties,
+};
+use features::{
+    bbox_in_crs, order_by_clause, round_coordinates, value_ref_to_json, FeatureFormat,
+    FeatureListQuery, SampleQuery,
+};
+use field_stats::{field_stats, value_counts, FieldValuesQuery};
+use file_events::file_events;
+pub use file_events::FileEvents;
+use filter::compile_filter;
+use geoprocessing::{build_processing_router, spatial_join};
+use geotiff::import_geotiff;
+use gpx::import_gpx;
+pub use health::Startup;
+use health::{health_check, livez, readyz};
 use http_errors::{bad_request, internal_error, payload_too_large};
-use import::import_spatial_data;
+use identify::{build_identify_sql, IdentifyQuery};
+use import::{import_spatial_data, update_dataset_stats};
+use kml::import_kml;
+pub use ldap::LdapConfig;
+pub use logging::{init_logging, LogFormat};
+use logging::{record_user, request_span};
 use mbtiles::import_mbtiles;
+use measure::get_feature_measurements;
+use metadata::{get_file_metadata, load_dataset_metadata, set_file_metadata, tileset_attribution};
+pub use migrations::{latest_version, migrate, schema_version, MigrationError, MigrationReport};
+pub use models::{
+    ApiTokenItem, AppState, AppendResponse, AttributeUpdateResponse, BackupInfo, DatasetMetadata,
+    DatasetQueryResponse, DatasetStorage, DatasetVersion, ErrorResponse, ExportJob, ExportRequest,
+    FeatureCreateRequest, FeatureEditRequest, FeatureLimitStrategy, FeatureMeasurements,
+    FieldStatsResponse, FileFolder, FileItem, FileRetention, FileSchemaResponse, FileShare,
+    FileTags, GeoJsonFeature, HealthResponse, Measurement, OgcBoundingBox, OgcCollection,
+    OgcCollections, OgcExtent, OgcFeatureCollection, OgcLink, OgcSpatialExtent, OgcTileLayer,
+    OgcTileMatrixLimits, OgcTileSet, OgcTileSets, OrgItem, OrgMember, PasswordResetResponse,
+    PreviewMeta, PublicTileMeta, PublicTileUrl, PublishAccess, PublishRequest, PublishResponse,
+    RemoteRefreshResponse, RemoteRefreshStatus, Settings, SignedUrlRequest, SignedUrlResponse,
+    StorageStats, TileInspection, TileJson, TileLayerInspection, TileOptions, TileSeedJob,
+    TileSeedRequest, TilesetRequest, TilesetResponse, UserItem, VectorLayer, WebhookItem,
+};
 pub use models::{
-    AppState, ErrorResponse, FileItem, FileSchemaResponse, PreviewMeta, PublicTileUrl,
-    PublishRequest, PublishResponse,
+    CheckStatus, FeatureListResponse, FeaturePropertiesResponse, FeatureProperty, FeatureRow,
+    FieldInfo, GeoJsonFeatureCollection, LayerInfo,
 };
-use models::{FeaturePropertiesResponse, FeatureProperty};
+use models::{FileListQuery, IdentifyResponse};
+use ogc::{build_ogc_collection_router, build_ogc_router};
+use ogc_tiles::build_ogc_tiles_router;
+use openapi::{api_docs_asset, api_docs_page, build_openapi_spec, API_DOCS_CSP};
+use orgs::{build_orgs_router, list_orgs, set_file_org};
+pub use orphans::{clean_orphans, OrphanReport, ORPHAN_SWEEP_INTERVAL};
 pub use password::{hash_password, validate_password_complexity, verify_password, PasswordError};
-pub use session_store::DuckDBStore;
+use password_reset::build_password_reset_router;
+pub use password_reset::ResetDelivery;
+pub use postgis::load_postgres_extension;
+use postgis::{export_to_postgis, import_from_postgis};
+use public_cors::{public_cors, validate_allowed_origins};
+pub use read_pool::{ReadConnection, ReadPool, DEFAULT_READ_POOL_SIZE};
+use ready_file::{load_ready_file, ReadyFile};
+use remote::{get_refresh_status, import_from_url, refresh_remote_file};
+use request_id::assign_request_id;
+pub use request_id::REQUEST_ID_HEADER;
+use retention::{get_file_retention, set_file_retention};
+pub use retention::{purge_failed_uploads, RETENTION_SWEEP_INTERVAL};
+pub use scan::UploadScanner;
+use scan::{quarantine_upload, ScanError};
+pub use seed::{seed_demo_data, DEMO_SLUG};
+pub use session_store::{DuckDBStore, DEFAULT_SESSION_WRITE_INTERVAL, SESSION_CLEANUP_INTERVAL};
+use settings::{
+    build_settings_router, load_settings, public_cache_control, public_url, upload_max_size,
+};
+use shapefile::{import_shapefile, normalize_encoding, select_layers};
+use shares::build_shares_router;
+use signing::{
+    generate_signing_secret, signed_query_string, verify_token, SignedQuery,
+    DEFAULT_SIGNED_URL_TTL_SECS, MAX_SIGNED_URL_TTL_SECS,
+};
+use slug_history::{redirect_renamed_slugs, update_slug};
+use spatial_index::has_spatial_index;
+use sql_query::{
+    build_query_sql, check_query_dataset_rows, open_query_sandbox, validate_query,
+    DatasetQueryRequest,
+};
+use storage::build_storage_router;
+pub use style::build_style;
+use tags::{load_all_file_tags, set_file_folder, set_file_tags};
 use test_routes::add_test_routes;
-use tiles::build_mvt_select_sql;
+use thumbnail::{get_file_thumbnail, write_thumbnail};
+use tile_cache::{discard_cached_tiles, purge_cached_tiles, tile_cache_root};
+use tile_debug::inspect_tile;
+use tile_options::{
+    apply_publish_overrides, load_render_options, load_tile_options, merge_tile_options,
+    parse_stored_tile_options, save_tile_options, validate_publish_overrides,
+    validate_publish_zoom, validate_tile_options, MAX_TILE_ZOOM,
+};
+use tile_routes::{get_public_tile, get_tile};
+use tile_seed::{get_seed_job, seed_file_tiles};
+use tilejson::{
+    dataset_vector_layer, mbtiles_vector_layers, restrict_zoom_range, union_bounds,
+    TILEJSON_VERSION,
+};
+use tilesets::{
+    check_layer_names, load_tileset_files, load_tileset_sources, slug_in_use,
+    validate_tileset_files, TilesetSource,
+};
+use users::{build_registration_router, build_users_router};
 pub use validation::{validate_geojson, validate_shapefile_zip};
-
-pub fn build_api_router(state: AppState) -> Router {
-    build_api_router_with_auth(state, true)
+use versions::{current_version, list_dataset_versions, retained_version, RetainedVersion};
+use viewer::{public_slug_name, render_viewer_page, viewer_csp, VIEWER_SCRIPT};
+use webhooks::{build_webhooks_router, notify, WebhookEvent};
+use wms::build_wms_router;
+use wmts::build_wmts_router;
+use zm::ZValues;
+
+pub fn build_api_router(state: AppState, config: &Config) -> Router {
+    build_api_router_with_auth(state, config, true)
 }
 
 pub fn build_test_router(state: AppState) -> Router {
-    build_api_router_with_auth(state, false)
+    build_api_router_with_auth(state, &Config::default(), false)
 }
 
-fn build_api_router_with_auth(state: AppState, with_auth: bool) -> Router {
-    // Read allowed origins from environment or use defaults
-    let allowed_origins = config::read_cors_origins();
-
+fn build_api_router_with_auth(state: AppState, config: &Config, with_auth: bool) -> Router {
     // Build CORS layer with specific origins
     // Note: When using credentials, we cannot use wildcards for headers
     let mut cors = CorsLayer::new()
         .allow_methods([
             axum::http::Method::GET,
             axum::http::Method::POST,
+            axum::http::Method::PUT,
+            axum::http::Method::PATCH,
             axum::http::Method::DELETE,
         ])
         .allow_headers([
@@ -89,47 +292,196 @@ fn build_api_router_with_auth(state: AppState, with_auth: bool) -> Router {
             axum::http::header::ACCEPT,
             axum::http::header::AUTHORIZATION,
         ])
+        .expose_headers([
+            axum::http::HeaderName::from_static("x-feature-limit"),
+            axum::http::HeaderName::from_static("x-feature-limit-strategy"),
+            REQUEST_ID_HEADER,
+        ])
         .allow_credentials(true);
 
     // Add each allowed origin
-    for origin in allowed_origins {
+    for origin in &config.cors_allowed_origins {
         if let Ok(parsed) = origin.parse::<axum::http::HeaderValue>() {
             cors = cors.allow_origin(parsed);
         } else {
-            eprintln!("Warning: Failed to parse CORS origin '{}', skipping. Check CORS_ALLOWED_ORIGINS environment variable.", origin);
+            tracing::warn!(%origin, "Skipping unparseable CORS origin; check cors_allowed_origins / CORS_ALLOWED_ORIGINS");
         }
     }
 
     let session_layer = SessionManagerLayer::new(state.session_store.clone())
-        .with_secure(config::read_cookie_secure())
+        .with_secure(config.cookie_secure)
         .with_same_site(tower_cookies::cookie::SameSite::Lax);
 
     let auth_layer =
         AuthManagerLayerBuilder::new(state.auth_backend.clone(), session_layer).build();
 
-    let auth_router = build_auth_router();
+    let auth_router = build_auth_router()
+        .merge(build_password_reset_router())
+        .merge(build_registration_router());
     let public_router = Router::new()
         .route("/health", get(health_check))
-        .route("/api/test/is-initialized", get(check_is_initialized))
-        .route("/tiles/{slug}/{z}/{x}/{y}", get(get_public_tile));
-
-    let mut api_router = Router::new()
-        .route("/api/files", get(list_files))
-        .route("/api/uploads", post(upload_file))
+        .route("/livez", get(livez))
+        .route("/readyz", get(readyz))
+        .route("/api/openapi.json", get(get_openapi_spec))
+        .route("/api/docs", get(get_api_docs))
+        .route("/api/docs/{asset}", get(get_api_docs_asset))
+        .route("/api/test/is-initialized", get(check_is_initialized));
+
+    // Anonymous tile traffic never needs a session; keeping it outside the auth
+    // layer avoids a session lookup (and DuckDB lock) per tile.
+    let redirects = || axum::middleware::from_fn_with_state(state.clone(), redirect_renamed_slugs);
+    // Tiles carry their publish's CORS policy instead of the app's.
+    let public_tiles_router = Router::new()
+        .route("/tiles/{slug}/tilejson.json", get(get_public_tilejson))
+        .route("/tiles/{slug}/style.json", get(get_public_style))
+        .route("/tiles/{slug}/meta", get(get_public_meta))
+        .route("/tiles/{slug}/{z}/{x}/{y}", get(get_public_tile))
+        .merge(build_ogc_tiles_router())
+        .route_layer(redirects())
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            public_cors,
+        ))
+        .with_state(state.clone());
+    let public_maps_router = Router::new()
+        .route("/view/viewer.js", get(get_viewer_script))
+        .route("/view/{slug}", get(get_public_viewer))
+        .merge(build_wms_router())
+        .merge(build_wmts_router())
+        .route_layer(redirects())
+        .layer(cors.clone())
+        .with_state(state.clone());
+
+    // Reading data (including exports and read-only SQL) needs any role, and
+    // reading a file needs at least a read share.
+    let mut file_viewer_router = Router::new()
         .route("/api/files/{id}/preview", get(get_preview_meta))
         .route("/api/files/{id}/tiles/{z}/{x}/{y}", get(get_tile))
+        .route(
+            "/api/files/{id}/tiles/{z}/{x}/{y}/inspect",
+            get(inspect_tile),
+        )
+        .route("/api/files/{id}/tilejson", get(get_tilejson))
+        .route("/api/files/{id}/features", get(list_features))
+        .route("/api/files/{id}/sample", get(sample_features))
+        .route("/api/files/{id}/identify", get(identify_features))
         .route(
             "/api/files/{id}/features/{fid}",
             get(get_feature_properties),
         )
+        .route(
+            "/api/files/{id}/features/{fid}/measure",
+            get(get_feature_measurements),
+        )
         .route("/api/files/{id}/schema", get(get_file_schema))
+        .route("/api/files/{id}/thumbnail", get(get_file_thumbnail))
+        .route("/api/files/{id}/fields/{name}/stats", get(get_field_stats))
+        .route(
+            "/api/files/{id}/fields/{name}/values",
+            get(get_field_values),
+        )
+        .route("/api/files/{id}/query", post(query_dataset))
+        .route("/api/files/{id}/tile-options", get(get_tile_options))
+        .route("/api/files/{id}/public-url", get(get_public_url))
+        .route("/api/files/{id}/retention", get(get_file_retention))
+        .route("/api/files/{id}/metadata", get(get_file_metadata))
+        .route("/api/files/{id}/versions", get(list_dataset_versions))
+        .route("/api/files/{id}/exports", post(create_export))
+        .merge(build_ogc_collection_router());
+    let viewer_router = Router::new()
+        .route("/api/files", get(list_files))
+        .route("/api/files/events", get(file_events))
+        .route("/api/orgs", get(list_orgs))
+        .merge(build_tokens_router())
+        .route("/api/exports/{job_id}", get(get_export))
+        .route("/api/exports/{job_id}/download", get(download_export))
+        .route("/api/seed-jobs/{job_id}", get(get_seed_job))
+        .route("/api/tilesets", get(list_tilesets))
+        .merge(build_ogc_router());
+
+    // Changing or publishing data needs the editor role. Changing a file needs
+    // an edit share; publishing or sharing it needs its ownership.
+    let mut file_editor_router = Router::new()
+        .route("/api/files/{id}/features", post(create_feature))
+        .route("/api/files/{id}/features/{fid}", patch(update_feature))
+        .route(
+            "/api/files/{id}/features/{fid}/geometry",
+            put(update_feature_geometry),
+        )
+        .route("/api/files/{id}/attributes", post(update_attributes))
+        .route("/api/files/{id}/tile-options", patch(update_tile_options))
+        .route("/api/files/{id}/seed", post(seed_file_tiles))
+        .route("/api/files/{id}/retention", put(set_file_retention))
+        .route("/api/files/{id}/tags", put(set_file_tags))
+        .route("/api/files/{id}/folder", put(set_file_folder))
+        .route("/api/files/{id}/metadata", put(set_file_metadata))
+        .route(
+            "/api/files/{id}/refresh",
+            get(get_refresh_status).post(refresh_remote_file),
+        )
+        .route("/api/files/{id}/export/postgis", post(export_to_postgis));
+    let mut file_owner_router = Router::new()
         .route("/api/files/{id}/publish", post(publish_file))
         .route("/api/files/{id}/unpublish", post(unpublish_file))
-        .route("/api/files/{id}/public-url", get(get_public_url));
-
-    // Add authentication middleware if required
+        .route("/api/files/{id}/slug", put(update_slug))
+        .route("/api/files/{id}/cache/purge", post(purge_tile_cache))
+        .route("/api/files/{id}/signed-url", post(create_signed_url))
+        .route("/api/files/{id}/org", put(set_file_org))
+        .merge(build_shares_router());
+    // Deriving a dataset adds a file, so it needs the editor role, but only a
+    // read share on its source.
+    let mut file_processing_router = build_processing_router();
+    let mut editor_router = Router::new()
+        .route("/api/uploads", post(upload_file))
+        .route("/api/uploads/url", post(import_from_url))
+        .route("/api/uploads/postgis", post(import_from_postgis))
+        .route("/api/process/spatial-join", post(spatial_join))
+        .route("/api/tilesets", post(create_tileset))
+        .route("/api/tilesets/{id}", delete(delete_tileset));
+
+    let mut admin_router = build_users_router()
+        .merge(build_orgs_router())
+        .merge(build_settings_router())
+        .merge(build_backup_router())
+        .merge(build_storage_router())
+        .merge(build_webhooks_router());
+
+    // Add authentication and role middleware if required
     if with_auth {
-        api_router = api_router.route_layer(axum_login::login_required!(crate::AuthBackend));
+        let file_access_layer = |access: FileAccess| {
+            axum::middleware::from_fn_with_state((state.clone(), access), require_file_access)
+        };
+        file_viewer_router = file_viewer_router.route_layer(file_access_layer(FileAccess::Read));
+        file_editor_router = file_editor_router.route_layer(file_access_layer(FileAccess::Edit));
+        file_owner_router = file_owner_router.route_layer(file_access_layer(FileAccess::Own));
+        file_processing_router =
+            file_processing_router.route_layer(file_access_layer(FileAccess::Read));
+    }
+    let viewer_router = viewer_router.merge(file_viewer_router);
+    editor_router = editor_router
+        .merge(file_editor_router)
+        .merge(file_owner_router)
+        .merge(file_processing_router);
+    if with_auth {
+        editor_router = editor_router.route_layer(axum::middleware::from_fn_with_state(
+            Role::Editor,
+            require_role,
+        ));
+        admin_router = admin_router.route_layer(axum::middleware::from_fn_with_state(
+            Role::Admin,
+            require_role,
+        ));
+    }
+    let mut api_router = viewer_router.merge(editor_router).merge(admin_router);
+    if with_auth {
+        // Bearer keys sign the request in before the login check sees it.
+        api_router = api_router
+            .route_layer(axum::middleware::from_fn(record_user))
+            .route_layer(axum_login::login_required!(crate::AuthBackend))
+            .route_layer(axum::middleware::from_fn_with_state(
+                state.clone(),
+                bearer_auth,
+            ));
     }
 
     // Combine all routes
@@ -143,60 +495,134 @@ fn build_api_router_with_auth(state: AppState, with_auth: bool) -> Router {
         .with_state(state)
         .layer(auth_layer)
         .layer(cors)
+        .merge(public_tiles_router)
+        .merge(public_maps_router)
+        .layer(TraceLayer::new_for_http().make_span_with(request_span))
+        .layer(axum::middleware::from_fn(assign_request_id))
 }
 
-async fn list_files(State(state): State<AppState>) -> impl IntoResponse {
-    let conn = state.db.lock().await;
-    let mut stmt = conn
-        .prepare(
-            "SELECT f.id, f.name, f.type, f.size, f.uploaded_at, f.status, f.crs, f.path, f.table_name, f.error, f.is_public, pf.slug
+async fn list_files(
+    State(state): State<AppState>,
+    auth_session: AuthSession<AuthBackend>,
+    Query(query): Query<FileListQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let owner_id = visible_files_owner(&auth_session);
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    let items = load_file_items(&conn, owner_id.as_deref(), &query).map_err(internal_error)?;
+    drop(conn);
+    Ok(Json(items))
+}
+
+/// User whose files the caller may list, or `None` for all of them. Admins
+/// (and unauthenticated test routers) see every file, others their own, those
+/// shared with them and those of their organizations.
+fn visible_files_owner(auth_session: &AuthSession<AuthBackend>) -> Option<String> {
+    auth_session
+        .user
+        .as_ref()
+        .filter(|user| user.role() != Role::Admin)
+        .map(|user| user.id.clone())
+}
+
+/// Condition on `files f` for the files a user may see, binding their id
+/// `VISIBLE_FILES_PARAMS` times.
+const VISIBLE_FILES_FILTER: &str = "(f.owner_id = ?
+    OR f.id IN (SELECT file_id FROM file_shares WHERE user_id = ?)
+    OR f.org_id IN (SELECT org_id FROM org_members WHERE user_id = ?))";
+const VISIBLE_FILES_PARAMS: usize = 3;
+
+/// The files list, newest first, restricted to what `owner_id` may see and
+/// to `query`'s tag and folder.
+fn load_file_items(
+    conn: &duckdb::Connection,
+    owner_id: Option<&str>,
+    query: &FileListQuery,
+) -> Result<Vec<FileItem>, duckdb::Error> {
+    let mut conditions = Vec::new();
+    let mut params: Vec<String> = Vec::new();
+    if let Some(owner_id) = owner_id {
+        conditions.push(VISIBLE_FILES_FILTER.to_string());
+        params.extend(std::iter::repeat_n(
+            owner_id.to_string(),
+            VISIBLE_FILES_PARAMS,
+        ));
+    }
+    if let Some(tag) = query
+        .tag
+        .as_deref()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+    {
+        conditions.push(
+            "f.id IN (SELECT file_id FROM file_tags WHERE lower(tag) = lower(?))".to_string(),
+        );
+        params.push(tag.to_string());
+    }
+    if let Some(folder) = query.folder.as_deref() {
+        let folder = folder.trim().trim_matches('/');
+        if !folder.is_empty() {
+            conditions.push("(f.folder = ? OR starts_with(f.folder, ?))".to_string());
+            params.push(folder.to_string());
+            params.push(format!("{folder}/"));
+        }
+    }
+    let where_clause = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    };
+
+    let tags = load_all_file_tags(conn)?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT f.id, f.name, f.type, f.size, f.uploaded_at, f.status, f.crs, f.path, f.table_name, f.error, f.is_public, pf.slug, f.org_id,
+                 f.geometry_type, f.feature_count, f.folder
           FROM files f
           LEFT JOIN published_files pf ON f.id = pf.file_id
-          ORDER BY f.uploaded_at DESC",
-        )
-        .unwrap();
-
-    let items: Vec<FileItem> = stmt
-        .query_map([], |row| {
-            let table_name: Option<String> = row.get(8)?;
-            let error: Option<String> = row.get(9)?;
-            let is_public: bool = row.get(10).unwrap_or(false);
-            let public_slug: Option<String> = row.get(11).ok();
-            Ok(FileItem {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                file_type: row.get(2)?,
-                size: row.get(3)?,
-                uploaded_at: {
-                    let ts: chrono::NaiveDateTime = row.get(4)?;
-                    ts.and_utc().to_rfc3339()
-                },
-                status: row.get(5)?,
-                crs: row.get(6)?,
-                path: row.get(7)?,
-                table_name,
-                error,
-                is_public: Some(is_public),
-                public_slug,
-            })
+          {where_clause}
+          ORDER BY f.uploaded_at DESC"
+    ))?;
+
+    let items = stmt.query_map(duckdb::params_from_iter(params.iter()), |row| {
+        let id: String = row.get(0)?;
+        let table_name: Option<String> = row.get(8)?;
+        let error: Option<String> = row.get(9)?;
+        let is_public: bool = row.get(10).unwrap_or(false);
+        let public_slug: Option<String> = row.get(11).ok();
+        Ok(FileItem {
+            tags: tags.get(&id).cloned().unwrap_or_default(),
+            id,
+            name: row.get(1)?,
+            file_type: row.get(2)?,
+            size: row.get(3)?,
+            uploaded_at: {
+                let ts: chrono::NaiveDateTime = row.get(4)?;
+                ts.and_utc().to_rfc3339()
+            },
+            status: row.get(5)?,
+            crs: row.get(6)?,
+            path: row.get(7)?,
+            table_name,
+            error,
+            is_public: Some(is_public),
+            public_slug,
+            org_id: row.get(12)?,
+            geometry_type: row.get(13)?,
+            feature_count: row.get(14)?,
+            folder: row.get(15)?,
         })
-        .unwrap()
-        .collect::<Result<Vec<_>, _>>()
-        .unwrap();
-
-    drop(conn);
-    Json(items)
+    })?;
+    items.collect()
 }
 
 async fn get_preview_meta(
     State(state): State<AppState>,
     AxumPath(id): AxumPath<String>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
-    let conn = state.db.lock().await;
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
 
     // Check if file exists and get meta
     let mut stmt = conn
-        .prepare("SELECT name, crs, status, table_name, tile_format, tile_bounds, minzoom, maxzoom FROM files WHERE id = ?")
+        .prepare("SELECT name, crs, status, table_name, tile_format, COALESCE(tile_bounds, bbox), minzoom, maxzoom, data_version, geometry_type, feature_count, z_values FROM files WHERE id = ?")
         .map_err(internal_error)?;
 
     let meta: Option<FileMetadata> = stmt
@@ -210,11 +636,28 @@ async fn get_preview_meta(
                 row.get(5)?,
                 row.get(6)?,
                 row.get(7)?,
+                row.get(8)?,
+                row.get(9)?,
+                row.get(10)?,
+                row.get(11)?,
             ))
         })
         .ok();
 
-    let (name, crs, status, table_name, tile_format, tile_bounds, minzoom, maxzoom) = match meta {
+    let (
+        name,
+        crs,
+        status,
+        table_name,
+        tile_format,
+        stored_bounds,
+        minzoom,
+        maxzoom,
+        data_version,
+        geometry_type,
+        feature_count,
+        z_values,
+    ) = match meta {
         Some(m) => m,
         None => {
             return Err((
@@ -235,21 +678,62 @@ async fn get_preview_meta(
         ));
     }
 
-    // Calculate BBOX in WGS84
-    let bbox_values = if let Some(bounds_json) = tile_bounds {
-        // MBTiles: use pre-calculated bounds
-        serde_json::from_str::<[f64; 4]>(&bounds_json).ok()
+    let bbox_values = dataset_bbox(
+        &conn,
+        stored_bounds.as_deref(),
+        table_name.as_deref(),
+        crs.as_deref(),
+    );
+
+    // Dynamic tables report the zoom range and layer name from their tile options.
+    let (minzoom, maxzoom, layer_name) = if tile_format.is_none() {
+        let options = load_render_options(&conn, &id)
+            .map_err(internal_error)?
+            .unwrap_or_default();
+        (
+            options.min_zoom.map(i32::from),
+            options.max_zoom.map(i32::from),
+            Some(options.layer_name().to_string()),
+        )
+    } else {
+        (minzoom, maxzoom, None)
+    };
+
+    Ok(Json(PreviewMeta {
+        id,
+        name,
+        crs,
+        bbox: bbox_values,
+        tile_format,
+        minzoom,
+        maxzoom,
+        data_version: data_version.unwrap_or(0),
+        layer_name,
+        geometry_type,
+        feature_count,
+        z_values,
+    }))
+}
+
+/// Dataset extent in WGS84. `stored_bounds` are the bounds of an MBTiles file
+/// or the extent saved at import (`COALESCE(tile_bounds, bbox)`); without them
+/// the table is measured. `None` when the extent is empty or unknown.
+fn dataset_bbox(
+    conn: &duckdb::Connection,
+    stored_bounds: Option<&str>,
+    table_name: Option<&str>,
+    crs: Option<&str>,
+) -> Option<[f64; 4]> {
+    if let Some(bounds_json) = stored_bounds {
+        serde_json::from_str::<[f64; 4]>(bounds_json).ok()
     } else if let Some(tbl) = table_name {
-        // Dynamic: calculate ST_Extent (existing logic)
         // Note: If CRS is missing/null, we assume EPSG:4326 and always_xy=true (lon/lat) for simplicity
-
-        // Query for bbox components directly
         let bbox_components_query = format!(
             "SELECT ST_XMin(b), ST_YMin(b), ST_XMax(b), ST_YMax(b) FROM (
                 SELECT ST_Extent(ST_Transform(geom, '{}', 'EPSG:4326', always_xy := true)) as b
                 FROM \"{tbl}\"
             )",
-            crs.as_deref().unwrap_or("EPSG:4326")
+            crs.unwrap_or("EPSG:4326")
         );
 
         conn.query_row(&bbox_components_query, [], |row| {
@@ -268,41 +752,296 @@ async fn get_preview_meta(
         .filter(|b| b != &[0.0, 0.0, 0.0, 0.0])
     } else {
         None
+    }
+}
+
+async fn get_tilejson(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    let tiles_url = format!("/api/files/{id}/tiles/{{z}}/{{x}}/{{y}}");
+    Ok(Json(build_tilejson(
+        &conn,
+        &id,
+        &tiles_url,
+        &TileOptions::default(),
+        None,
+    )?))
+}
+
+async fn get_public_tilejson(
+    State(state): State<AppState>,
+    AxumPath(slug): AxumPath<String>,
+    Query(signed): Query<SignedQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    let settings = load_settings(&conn, &state).map_err(internal_error)?;
+    // Relative unless a public base URL is configured.
+    let tiles_url = public_url(&settings, &format!("/tiles/{slug}/{{z}}/{{x}}/{{y}}"));
+    Ok((
+        [(header::CACHE_CONTROL, public_cache_control(&settings))],
+        Json(public_tilejson(&conn, &slug, &tiles_url, &signed)?),
+    ))
+}
+
+async fn get_public_style(
+    State(state): State<AppState>,
+    AxumPath(slug): AxumPath<String>,
+    Query(signed): Query<SignedQuery>,
+    headers: axum::http::HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    let settings = load_settings(&conn, &state).map_err(internal_error)?;
+    // Styles are meant to be copied elsewhere, so their tile URLs are absolute
+    // whenever the origin is known.
+    let base_url = public_origin(&state, &settings, &headers);
+    let tiles_url = format!("{base_url}/tiles/{slug}/{{z}}/{{x}}/{{y}}");
+    let tilejson = public_tilejson(&conn, &slug, &tiles_url, &signed)?;
+    Ok((
+        [(header::CACHE_CONTROL, public_cache_control(&settings))],
+        Json(build_style(&slug, &tilejson)),
+    ))
+}
+
+async fn get_public_meta(
+    State(state): State<AppState>,
+    AxumPath(slug): AxumPath<String>,
+    Query(signed): Query<SignedQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    let settings = load_settings(&conn, &state).map_err(internal_error)?;
+    let tiles_url = public_url(&settings, &format!("/tiles/{slug}/{{z}}/{{x}}/{{y}}"));
+    let tilejson = public_tilejson(&conn, &slug, &tiles_url, &signed)?;
+    drop(conn);
+
+    // The viewer passes its query on to the style, so it takes the signature too.
+    let mut viewer_url = public_url(&settings, &format!("/view/{slug}"));
+    if let (Some(expires), Some(token)) = (signed.expires, signed.token.as_deref()) {
+        viewer_url.push_str(&format!("?expires={expires}&token={token}"));
+    }
+    let tile_source = match tilejson.vector_layers {
+        Some(_) => "vector",
+        None => "raster",
+    };
+    let vector_layers = tilejson.vector_layers.unwrap_or_default();
+    let layer_name = match vector_layers.as_slice() {
+        [layer] => Some(layer.id.clone()),
+        _ => None,
+    };
+    let meta = PublicTileMeta {
+        slug,
+        name: tilejson.name,
+        tile_source: tile_source.to_string(),
+        tile_url: tilejson.tiles.into_iter().next().unwrap_or_default(),
+        viewer_url,
+        minzoom: tilejson.minzoom,
+        maxzoom: tilejson.maxzoom,
+        bounds: tilejson.bounds,
+        attribution: tilejson.attribution,
+        layer_name,
+        vector_layers,
     };
+    Ok((
+        [(header::CACHE_CONTROL, public_cache_control(&settings))],
+        Json(meta),
+    ))
+}
 
-    Ok(Json(PreviewMeta {
-        id,
-        name,
-        crs,
-        bbox: bbox_values,
-        tile_format,
-        minzoom,
-        maxzoom,
-    }))
+async fn get_public_viewer(
+    State(state): State<AppState>,
+    AxumPath(slug): AxumPath<String>,
+    Query(signed): Query<SignedQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    let settings = load_settings(&conn, &state).map_err(internal_error)?;
+    let public = public_slug_name(&conn, &slug)
+        .map_err(internal_error)?
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    error: "Public tile not found".to_string(),
+                }),
+            )
+        })?;
+    drop(conn);
+    check_not_expired(public.expires_at)?;
+    check_signed_access(public.signing_secret.as_deref(), &slug, &signed)?;
+
+    let base_url = settings.public_base_url.as_deref().unwrap_or_default();
+    Ok((
+        [
+            (header::CACHE_CONTROL, public_cache_control(&settings)),
+            (header::CONTENT_SECURITY_POLICY, viewer_csp(base_url)),
+        ],
+        axum::response::Html(render_viewer_page(&public.name, &slug, base_url)),
+    ))
 }
 
-async fn get_tile(
+/// Script of the viewer page.
+async fn get_viewer_script(
     State(state): State<AppState>,
-    AxumPath((id, z, x, y)): AxumPath<(String, i32, i32, i32)>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
-    validate_tile_coords(z, x, y)?;
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    let settings = load_settings(&conn, &state).map_err(internal_error)?;
+    Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                "text/javascript; charset=utf-8".to_string(),
+            ),
+            (header::CACHE_CONTROL, public_cache_control(&settings)),
+        ],
+        VIEWER_SCRIPT,
+    ))
+}
 
-    println!(
-        "Received tile request: id={}, z={}, x={}, y={}",
-        id, z, x, y
-    );
-    let conn = state.db.lock().await;
+/// TileJSON behind a public slug: a tileset, or else a published dataset. For
+/// a signed publish the request's signature is checked and carried over to the
+/// tile URLs.
+fn public_tilejson(
+    conn: &duckdb::Connection,
+    slug: &str,
+    tiles_url: &str,
+    signed: &SignedQuery,
+) -> Result<TileJson, (StatusCode, Json<ErrorResponse>)> {
+    if let Some(tileset_id) = find_tileset_by_slug(conn, slug)? {
+        return build_tileset_tilejson(conn, &tileset_id, tiles_url);
+    }
 
-    // Get file metadata including tile_format
-    let (crs, status, table_name, tile_format, file_path): (
-        Option<String>,
-        String,
-        Option<String>,
-        Option<String>,
-        String,
+    let not_found = || {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Public tile not found".to_string(),
+            }),
+        )
+    };
+    let ((file_id, overrides, signing_secret, expires_at, version), is_public): (
+        PublishedSlugRow,
+        bool,
     ) = conn
         .query_row(
-            "SELECT crs, status, table_name, tile_format, path FROM files WHERE id = ?",
+            "SELECT p.file_id, p.tile_options, p.signing_secret, p.expires_at, p.version,
+                    f.is_public
+             FROM published_files p JOIN files f ON f.id = p.file_id
+             WHERE p.slug = ?",
+            duckdb::params![slug],
+            |row| {
+                Ok((
+                    (
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                    ),
+                    row.get(5)?,
+                ))
+            },
+        )
+        .map_err(|_| not_found())?;
+    check_not_expired(expires_at)?;
+    if !is_public {
+        return Err(not_found());
+    }
+    check_signed_access(signing_secret.as_deref(), slug, signed)?;
+    let pinned = retained_version(conn, &file_id, version).map_err(internal_error)?;
+
+    let overrides = parse_stored_tile_options(overrides.as_deref());
+    let mut tilejson = build_tilejson(conn, &file_id, tiles_url, &overrides, pinned.as_ref())?;
+    restrict_zoom_range(&mut tilejson, overrides.min_zoom, overrides.max_zoom);
+    if let (Some(expires), Some(token)) = (signed.expires, signed.token.as_deref()) {
+        if signing_secret.is_some() {
+            for url in &mut tilejson.tiles {
+                url.push_str(&format!("&expires={expires}&token={token}"));
+            }
+        }
+    }
+    Ok(tilejson)
+}
+
+/// Links published with an expiry answer 410 once it has passed, whether or not
+/// the background sweep has unpublished them yet.
+fn check_not_expired(
+    expires_at: Option<chrono::NaiveDateTime>,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    match expires_at {
+        Some(expires_at) if expires_at <= Utc::now().naive_utc() => Err((
+            StatusCode::GONE,
+            Json(ErrorResponse {
+                error: "Public link has expired".to_string(),
+            }),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Reject requests to a signed publish that lack a valid, unexpired token.
+fn check_signed_access(
+    signing_secret: Option<&str>,
+    slug: &str,
+    signed: &SignedQuery,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    let Some(secret) = signing_secret else {
+        return Ok(());
+    };
+    verify_token(secret, slug, signed, Utc::now().timestamp()).map_err(|e| {
+        (
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse {
+                error: e.message().to_string(),
+            }),
+        )
+    })
+}
+
+/// Origin of absolute links in publicly cached responses: the configured base
+/// URL, else the request's own when `trust_forwarded_headers` is set, else
+/// empty so the links are relative. Without a trusted proxy, `Host` and
+/// `X-Forwarded-*` are whatever the client sent.
+fn public_origin(state: &AppState, settings: &Settings, headers: &axum::http::HeaderMap) -> String {
+    match &settings.public_base_url {
+        Some(url) => url.clone(),
+        None if state.trust_forwarded_headers => request_base_url(headers),
+        None => String::new(),
+    }
+}
+
+/// `scheme://host` of the request as the proxy in front saw it, from
+/// `X-Forwarded-*` or `Host`; empty when there is no Host header.
+fn request_base_url(headers: &axum::http::HeaderMap) -> String {
+    let header_value = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(',').next())
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+    };
+    match header_value("x-forwarded-host").or_else(|| header_value("host")) {
+        Some(host) => format!(
+            "{}://{host}",
+            header_value("x-forwarded-proto").unwrap_or("http")
+        ),
+        None => String::new(),
+    }
+}
+
+/// TileJSON for a ready file whose tiles are served from `tiles_url`; the
+/// dataset's `dataVersion` is appended so clients refetch after edits. A
+/// published file's `overrides` narrow and rename its layer's fields.
+fn build_tilejson(
+    conn: &duckdb::Connection,
+    id: &str,
+    tiles_url: &str,
+    overrides: &TileOptions,
+    version: Option<&RetainedVersion>,
+) -> Result<TileJson, (StatusCode, Json<ErrorResponse>)> {
+    let meta: FileMetadata = conn
+        .query_row(
+            "SELECT name, crs, status, table_name, tile_format, COALESCE(tile_bounds, bbox), minzoom, maxzoom, data_version, geometry_type, feature_count, z_values FROM files WHERE id = ?",
             duckdb::params![id],
             |row| {
                 Ok((
@@ -311,6 +1050,13 @@ async fn get_tile(
                     row.get(2)?,
                     row.get(3)?,
                     row.get(4)?,
+                    row.get(5)?,
+                    row.get(6)?,
+                    row.get(7)?,
+                    row.get(8)?,
+                    row.get(9)?,
+                    row.get(10)?,
+                    row.get(11)?,
                 ))
             },
         )
@@ -322,128 +1068,592 @@ async fn get_tile(
                 }),
             )
         })?;
+    let (
+        name,
+        crs,
+        status,
+        table_name,
+        tile_format,
+        stored_bounds,
+        minzoom,
+        maxzoom,
+        data_version,
+        ..,
+    ) = meta;
 
     if status != "ready" {
         return Err((
             StatusCode::CONFLICT,
             Json(ErrorResponse {
-                error: "File is not ready for preview".to_string(),
+                error: "File is not ready".to_string(),
             }),
         ));
     }
 
-    // MBTiles branch
-    if let Some(format) = tile_format {
-        let full_path = mbtiles::resolve_mbtiles_path(&file_path);
-        drop(conn); // Release lock before async operation
-        match mbtiles::get_tile_from_mbtiles(&full_path, z, x, y).await {
-            Ok(Some(data)) => {
-                let ct = match format.as_str() {
-                    "mvt" => "application/vnd.mapbox-vector-tile",
-                    "png" => "image/png",
-                    _ => "application/octet-stream",
-                };
+    let bounds = match version {
+        Some(version) => dataset_bbox(
+            conn,
+            None,
+            Some(&version.table_name),
+            version.crs.as_deref(),
+        ),
+        None => dataset_bbox(
+            conn,
+            stored_bounds.as_deref(),
+            table_name.as_deref(),
+            crs.as_deref(),
+        ),
+    };
 
-                // Check if data is gzip-compressed (MBTiles tiles are often gzipped)
-                // Gzip magic bytes: 0x1f 0x8b
-                let is_gzipped = data.starts_with(&[0x1f, 0x8b]);
-
-                if is_gzipped {
-                    return Ok((
-                        [
-                            (header::CONTENT_TYPE, ct),
-                            (header::CONTENT_ENCODING, "gzip"),
-                        ],
-                        data,
-                    )
-                        .into_response());
-                } else {
-                    return Ok(([(header::CONTENT_TYPE, ct)], data).into_response());
+    let (minzoom, maxzoom, vector_layers) = match tile_format.as_deref() {
+        Some("mvt") => {
+            let path: String = conn
+                .query_row(
+                    "SELECT path FROM files WHERE id = ?",
+                    duckdb::params![id],
+                    |row| row.get(0),
+                )
+                .map_err(internal_error)?;
+            let layers = mbtiles::extract_mbtiles_layers(&mbtiles::resolve_mbtiles_path(&path))
+                .unwrap_or_else(|e| {
+                    tracing::warn!(file_id = %id, error = %e, "Failed to extract MBTiles layers");
+                    Vec::new()
+                });
+            (minzoom, maxzoom, Some(mbtiles_vector_layers(layers)))
+        }
+        Some(_) => (minzoom, maxzoom, None),
+        None => {
+            let options = apply_publish_overrides(
+                &load_render_options(conn, id)
+                    .map_err(internal_error)?
+                    .unwrap_or_default(),
+                overrides,
+            );
+            let columns_source = version.map_or(id, |version| version.table_name.as_str());
+            let columns = load_dataset_columns(conn, columns_source).map_err(internal_error)?;
+            (
+                options.min_zoom.map(i32::from),
+                options.max_zoom.map(i32::from),
+                Some(vec![dataset_vector_layer(&options, &columns)]),
+            )
+        }
+    };
+    let zoom =
+        |z: Option<i32>, default: u8| z.and_then(|z| u8::try_from(z).ok()).unwrap_or(default);
+    let metadata = load_dataset_metadata(conn, id)
+        .map_err(internal_error)?
+        .unwrap_or_default();
+
+    Ok(TileJson {
+        tilejson: TILEJSON_VERSION.to_string(),
+        name,
+        tiles: vec![format!("{tiles_url}?v={}", data_version.unwrap_or(0))],
+        minzoom: zoom(minzoom, 0),
+        maxzoom: zoom(maxzoom, MAX_TILE_ZOOM),
+        bounds,
+        description: metadata.description,
+        attribution: metadata.attribution,
+        license: metadata.license,
+        vector_layers,
+    })
+}
+
+async fn list_features(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+    Query(query): Query<FeatureListQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let limit = query.limit().map_err(|e| bad_request(&e))?;
+    let offset = query.offset();
+    let format = query.format().map_err(|e| bad_request(&e))?;
+    let bbox = query.bbox().map_err(|e| bad_request(&e))?;
+
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+
+    let ReadyFile {
+        table_name,
+        crs: source_crs,
+        ..
+    } = load_ready_file(
+        &conn,
+        &id,
+        "Attribute table not available for MBTiles files",
+    )?;
+    let wgs84_geom = format!(
+        "ST_Transform(geom, '{}', 'EPSG:4326', always_xy := true)",
+        source_crs.replace('\'', "''")
+    );
+
+    let columns = load_dataset_columns(&conn, &id).map_err(internal_error)?;
+    let precision = load_tile_options(&conn, &id)
+        .map_err(internal_error)?
+        .and_then(|options| options.precision);
+    let order_by = order_by_clause(&columns, query.sort.as_deref()).map_err(|e| bad_request(&e))?;
+    let filter = query
+        .filter
+        .as_deref()
+        .filter(|f| !f.trim().is_empty())
+        .map(|f| compile_filter(f, &columns))
+        .transpose()
+        .map_err(|e| bad_request(&e))?;
+
+    let mut conditions = Vec::new();
+    let mut params: Vec<duckdb::types::Value> = Vec::new();
+    if let Some(bbox) = bbox {
+        let envelope = bbox_in_crs(&conn, bbox, &source_crs).map_err(internal_error)?;
+        conditions.push("ST_Intersects(geom, ST_MakeEnvelope(?, ?, ?, ?))".to_string());
+        params.extend(envelope.map(duckdb::types::Value::Double));
+    }
+    if let Some(filter) = filter {
+        conditions.push(filter.sql);
+        params.extend(filter.params);
+    }
+    let where_clause = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!(" WHERE {}", conditions.join(" AND "))
+    };
+    let table = quote_identifier(&table_name);
+
+    let total: i64 = conn
+        .query_row(
+            &format!("SELECT COUNT(*) FROM {table}{where_clause}"),
+            duckdb::params_from_iter(params.iter()),
+            |row| row.get(0),
+        )
+        .map_err(internal_error)?;
+    let total = total.max(0) as u64;
+
+    let mut select_exprs = vec!["fid".to_string()];
+    select_exprs.extend(columns.iter().map(|c| quote_identifier(&c.normalized)));
+    if format == FeatureFormat::GeoJson {
+        select_exprs.push(format!("ST_AsGeoJSON({wgs84_geom})"));
+    }
+    let sql = format!(
+        "SELECT {} FROM {table}{where_clause} ORDER BY {order_by} LIMIT {limit} OFFSET {offset}",
+        select_exprs.join(", ")
+    );
+
+    let mut stmt = conn.prepare(&sql).map_err(internal_error)?;
+    let mut result = stmt
+        .query(duckdb::params_from_iter(params.iter()))
+        .map_err(internal_error)?;
+
+    let mut rows = Vec::new();
+    let mut features = Vec::new();
+    while let Some(row) = result.next().map_err(internal_error)? {
+        let fid: i64 = row.get(0).map_err(internal_error)?;
+        let mut properties = serde_json::Map::with_capacity(columns.len());
+        for (index, column) in columns.iter().enumerate() {
+            let value = value_ref_to_json(row.get_ref(index + 1).map_err(internal_error)?);
+            properties.insert(column.original.clone(), value);
+        }
+
+        match format {
+            FeatureFormat::Json => rows.push(FeatureRow { fid, properties }),
+            FeatureFormat::GeoJson => {
+                let geometry: Option<String> =
+                    row.get(columns.len() + 1).map_err(internal_error)?;
+                let mut geometry = match geometry {
+                    Some(text) => serde_json::from_str(&text).map_err(internal_error)?,
+                    None => serde_json::Value::Null,
+                };
+                if let Some(places) = precision {
+                    round_coordinates(&mut geometry, places);
                 }
-            }
-            Ok(None) => {
-                // Tile doesn't exist but coordinates are valid → 204 No Content
-                return Ok(StatusCode::NO_CONTENT.into_response());
-            }
-            Err(e) => {
-                return Err(internal_error(format!("Failed to read MBTiles: {}", e)));
+                features.push(GeoJsonFeature {
+                    kind: "Feature".to_string(),
+                    id: fid,
+                    geometry,
+                    properties,
+                });
             }
         }
     }
 
-    // Dynamic generation branch (existing logic)
-    let table_name = table_name.ok_or_else(|| {
+    if format == FeatureFormat::GeoJson {
+        return Ok((
+            [(header::CONTENT_TYPE, "application/geo+json")],
+            Json(GeoJsonFeatureCollection {
+                kind: "FeatureCollection".to_string(),
+                total,
+                limit,
+                offset,
+                features,
+            }),
+        )
+            .into_response());
+    }
+
+    let fields = columns
+        .into_iter()
+        .map(|c| models::FieldInfo {
+            name: c.original,
+            r#type: c.mvt_type,
+        })
+        .collect();
+
+    Ok(Json(FeatureListResponse {
+        total,
+        limit,
+        offset,
+        fields,
+        rows,
+    })
+    .into_response())
+}
+
+/// First features of a dataset as GeoJSON in WGS84, for a preview that does
+/// not wait on tiles: the attribute table in `format=geojson`, sorted by `fid`.
+async fn sample_features(
+    state: State<AppState>,
+    id: AxumPath<String>,
+    Query(query): Query<SampleQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    list_features(state, id, Query(query.into())).await
+}
+
+async fn get_feature_properties(
+    State(state): State<AppState>,
+    AxumPath((id, fid)): AxumPath<(String, i64)>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+
+    let table_name = load_ready_file(
+        &conn,
+        &id,
+        "Feature properties not available for MBTiles files",
+    )?
+    .table_name;
+
+    let feature = load_feature_properties(&conn, &id, &table_name, fid).map_err(internal_error)?;
+    feature.map(Json).ok_or_else(|| {
         (
-            StatusCode::CONFLICT,
+            StatusCode::NOT_FOUND,
             Json(ErrorResponse {
-                error: "File is not ready for preview".to_string(),
+                error: "Feature not found".to_string(),
             }),
         )
-    })?;
+    })
+}
 
-    let source_crs = crs.as_deref().unwrap_or("EPSG:4326");
-
-    // 2. Generate MVT
-    // logic:
-    //  - filter by source_id
-    //  - ST_Transform(geom, source_crs, 'EPSG:3857', always_xy := true)
-    //  - ST_TileEnvelope(z, x, y) to get tile bounds in 3857
-    //  - ST_AsMVTGeom(geom_3857, tile_env) to clip/transform to tile coords
-    //  - ST_AsMVT(...) to encode
-
-    // 2a. Build property struct keys based on captured column metadata.
-    // We keep property keys as original names for UX.
-    // Note: We exclude fid + geom.
-    let select_sql =
-        build_mvt_select_sql(&conn, &id, &table_name, source_crs).map_err(internal_error)?;
-
-    println!("Executing SQL for tile z={z} x={x} y={y} id={id}");
-
-    // Params: z, x, y (for AsMVTGeom bounds), z, x, y (for intersects)
-    let mvt_blob: Option<Vec<u8>> =
-        match conn.query_row(&select_sql, duckdb::params![z, x, y, z, x, y], |row| {
-            row.get(0)
-        }) {
-            Ok(blob) => Some(blob),
-            Err(e) => {
-                eprintln!("Tile Error (z={z}, x={x}, y={y}): {:?}", e);
-                eprintln!("SQL that failed: {}", select_sql);
-                return Err(internal_error(format!("Tile generation failed: {}", e)));
-            }
-        };
+fn load_feature_properties(
+    conn: &duckdb::Connection,
+    id: &str,
+    table_name: &str,
+    fid: i64,
+) -> Result<Option<FeaturePropertiesResponse>, duckdb::Error> {
+    let columns = load_dataset_columns(conn, id)?;
 
-    println!(
-        "Tile Request: z={z}, x={x}, y={y}, Blob Size: {:?}",
-        mvt_blob.as_ref().map(|v| v.len())
+    // Build a projection that preserves ordering and uses safe identifiers.
+    let select_exprs: Vec<String> = columns
+        .iter()
+        .map(|c| quote_identifier(&c.normalized))
+        .collect();
+
+    let sql = format!(
+        "SELECT {} FROM {} WHERE fid = ?",
+        select_exprs.join(", "),
+        quote_identifier(table_name)
     );
 
-    match mvt_blob {
-        Some(blob) if !blob.is_empty() => Ok((
-            [(header::CONTENT_TYPE, "application/vnd.mapbox-vector-tile")],
-            blob,
-        )
-            .into_response()),
-        _ => {
-            // Return empty response or 204? Mapbox clients usually expect 200 with empty body or valid PBF.
-            // An empty blob is a valid MVT (empty).
-            Ok((
-                [(header::CONTENT_TYPE, "application/vnd.mapbox-vector-tile")],
-                Vec::new(),
-            )
-                .into_response())
-        }
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query(duckdb::params![fid])?;
+
+    let Some(row) = rows.next()? else {
+        return Ok(None);
+    };
+
+    let mut properties: Vec<FeatureProperty> = Vec::with_capacity(columns.len());
+    for (index, column) in columns.iter().enumerate() {
+        properties.push(FeatureProperty {
+            key: column.original.clone(),
+            value: value_ref_to_json(row.get_ref(index)?),
+        });
     }
+
+    Ok(Some(FeaturePropertiesResponse { fid, properties }))
 }
 
-async fn get_feature_properties(
+/// Resolve a ready dynamic-table dataset for editing; returns its table and CRS.
+fn load_editable_table(
+    conn: &duckdb::Connection,
+    id: &str,
+) -> Result<(String, String), (StatusCode, Json<ErrorResponse>)> {
+    let file = load_ready_file(conn, id, "Features of MBTiles files cannot be edited")?;
+    Ok((file.table_name, file.crs))
+}
+
+fn feature_not_found() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse {
+            error: "Feature not found".to_string(),
+        }),
+    )
+}
+
+async fn update_feature(
     State(state): State<AppState>,
     AxumPath((id, fid)): AxumPath<(String, i64)>,
+    Json(request): Json<FeatureEditRequest>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     let conn = state.db.lock().await;
+    let (table_name, _) = load_editable_table(&conn, &id)?;
+
+    let columns = load_dataset_columns(&conn, &id).map_err(internal_error)?;
+    let updates =
+        parse_property_updates(&columns, &request.properties).map_err(|e| bad_request(&e))?;
+
+    let updated = update_feature_properties(&conn, &table_name, fid, &updates)
+        .map_err(|e| bad_request(&format!("Update failed: {e}")))?;
+    if updated == 0 {
+        return Err(feature_not_found());
+    }
+    bump_data_version(&conn, &id).map_err(internal_error)?;
+    discard_cached_tiles(&state.upload_dir, &id);
+
+    let feature = load_feature_properties(&conn, &id, &table_name, fid)
+        .map_err(internal_error)?
+        .ok_or_else(|| internal_error("Updated feature disappeared"))?;
+    Ok(Json(feature))
+}
+
+async fn update_feature_geometry(
+    State(state): State<AppState>,
+    AxumPath((id, fid)): AxumPath<(String, i64)>,
+    Json(geometry): Json<serde_json::Value>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let geometry = geojson_geometry_text(&geometry).map_err(|e| bad_request(&e))?;
 
-    let (status, table_name, tile_format): (String, Option<String>, Option<String>) = conn
+    let conn = state.db.lock().await;
+    let (table_name, source_crs) = load_editable_table(&conn, &id)?;
+
+    let updated = replace_feature_geometry(&conn, &table_name, &source_crs, fid, &geometry)
+        .map_err(|e| bad_request(&format!("Invalid geometry: {e}")))?;
+    if updated == 0 {
+        return Err(feature_not_found());
+    }
+    bump_data_version(&conn, &id).map_err(internal_error)?;
+    discard_cached_tiles(&state.upload_dir, &id);
+    update_dataset_stats(&conn, &id, &table_name, Some(&source_crs)).map_err(internal_error)?;
+
+    let feature = load_geojson_feature(&conn, &id, &table_name, &source_crs, fid)
+        .map_err(internal_error)?
+        .ok_or_else(|| internal_error("Updated feature disappeared"))?;
+    Ok((
+        [(header::CONTENT_TYPE, "application/geo+json")],
+        Json(feature),
+    ))
+}
+
+async fn create_feature(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+    Json(request): Json<FeatureCreateRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let geometry = geojson_geometry_text(&request.geometry).map_err(|e| bad_request(&e))?;
+
+    let conn = state.db.lock().await;
+    let (table_name, source_crs) = load_editable_table(&conn, &id)?;
+
+    let columns = load_dataset_columns(&conn, &id).map_err(internal_error)?;
+    // Unlike an edit, a new feature may leave every property NULL.
+    let values = if request.properties.is_empty() {
+        Vec::new()
+    } else {
+        parse_property_updates(&columns, &request.properties).map_err(|e| bad_request(&e))?
+    };
+
+    let fid = insert_feature(&conn, &table_name, &source_crs, &geometry, &values)
+        .map_err(|e| bad_request(&format!("Invalid feature: {e}")))?;
+    bump_data_version(&conn, &id).map_err(internal_error)?;
+    discard_cached_tiles(&state.upload_dir, &id);
+    update_dataset_stats(&conn, &id, &table_name, Some(&source_crs)).map_err(internal_error)?;
+
+    let feature = load_geojson_feature(&conn, &id, &table_name, &source_crs, fid)
+        .map_err(internal_error)?
+        .ok_or_else(|| internal_error("Created feature disappeared"))?;
+    Ok((
+        StatusCode::CREATED,
+        [(header::CONTENT_TYPE, "application/geo+json")],
+        Json(feature),
+    ))
+}
+
+/// One feature as GeoJSON with a WGS84 geometry.
+fn load_geojson_feature(
+    conn: &duckdb::Connection,
+    id: &str,
+    table_name: &str,
+    source_crs: &str,
+    fid: i64,
+) -> Result<Option<GeoJsonFeature>, duckdb::Error> {
+    let columns = load_dataset_columns(conn, id)?;
+
+    let mut select_exprs: Vec<String> = columns
+        .iter()
+        .map(|c| quote_identifier(&c.normalized))
+        .collect();
+    select_exprs.push(format!(
+        "ST_AsGeoJSON(ST_Transform(geom, {}, 'EPSG:4326', always_xy := true))",
+        quote_literal(source_crs)
+    ));
+
+    let sql = format!(
+        "SELECT {} FROM {} WHERE fid = ?",
+        select_exprs.join(", "),
+        quote_identifier(table_name)
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query(duckdb::params![fid])?;
+    let Some(row) = rows.next()? else {
+        return Ok(None);
+    };
+
+    let mut properties = serde_json::Map::with_capacity(columns.len());
+    for (index, column) in columns.iter().enumerate() {
+        properties.insert(
+            column.original.clone(),
+            value_ref_to_json(row.get_ref(index)?),
+        );
+    }
+    let geometry: Option<String> = row.get(columns.len())?;
+    let geometry = geometry
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or(serde_json::Value::Null);
+
+    Ok(Some(GeoJsonFeature {
+        kind: "Feature".to_string(),
+        id: fid,
+        geometry,
+        properties,
+    }))
+}
+
+async fn update_attributes(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.db.lock().await;
+    let table_name = load_ready_file(
+        &conn,
+        &id,
+        "Attribute updates are not available for MBTiles files",
+    )?
+    .table_name;
+    drop(conn);
+
+    let updates_dir = state.upload_dir.join(&id).join("updates");
+    fs::create_dir_all(&updates_dir)
+        .await
+        .map_err(internal_error)?;
+
+    let mut key: Option<String> = None;
+    let mut source_path: Option<PathBuf> = None;
+    let read_result: Result<(), (StatusCode, Json<ErrorResponse>)> = async {
+        while let Some(mut field) = multipart.next_field().await.map_err(|e| {
+            let message = format!("Invalid multipart form: {e}");
+            bad_request(&message)
+        })? {
+            match field.name() {
+                Some("key") => {
+                    key = Some(field.text().await.map_err(|e| {
+                        let message = format!("Invalid multipart form: {e}");
+                        bad_request(&message)
+                    })?);
+                }
+                Some("file") => {
+                    let file_name = field.file_name().unwrap_or_default().to_string();
+                    if attribute_source_reader(Path::new(&file_name)).is_none() {
+                        return Err(bad_request(
+                            "Unsupported attribute file type. Use .csv or .geojson",
+                        ));
+                    }
+                    let ext = Path::new(&file_name)
+                        .extension()
+                        .and_then(|ext| ext.to_str())
+                        .unwrap_or_default()
+                        .to_lowercase();
+                    let path = updates_dir.join(format!("{}.{ext}", create_id()));
+                    source_path = Some(path.clone());
+
+                    let mut file =
+                        BufWriter::new(fs::File::create(&path).await.map_err(internal_error)?);
+                    let (max_size, max_size_label) = upload_max_size(&state).await?;
+                    let mut size: u64 = 0;
+                    while let Some(chunk) = field.chunk().await.map_err(internal_error)? {
+                        size = size.saturating_add(chunk.len() as u64);
+                        if size > max_size {
+                            let message = format!("File too large (max {max_size_label})");
+                            return Err(payload_too_large(&message));
+                        }
+                        file.write_all(&chunk).await.map_err(internal_error)?;
+                    }
+                    file.flush().await.map_err(internal_error)?;
+                }
+                _ => continue,
+            }
+        }
+        Ok(())
+    }
+    .await;
+
+    let result = match (read_result, key, &source_path) {
+        (Err(e), _, _) => Err(e),
+        (Ok(()), None, _) => Err(bad_request("Missing key field")),
+        (Ok(()), _, None) => Err(bad_request("No file uploaded")),
+        (Ok(()), Some(key), Some(path)) => {
+            let conn = state.db.lock().await;
+            apply_attribute_update(&conn, &id, &table_name, path, key.trim())
+                .map_err(|e| match e {
+                    AttributeUpdateError::Invalid(message) => bad_request(&message),
+                    AttributeUpdateError::Internal(message) => internal_error(message),
+                })
+                .and_then(|response| {
+                    bump_data_version(&conn, &id).map_err(internal_error)?;
+                    discard_cached_tiles(&state.upload_dir, &id);
+                    Ok(response)
+                })
+        }
+    };
+
+    if let Some(path) = &source_path {
+        let _ = fs::remove_file(path).await;
+    }
+
+    result.map(Json)
+}
+
+async fn get_tile_options(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    let options = load_tile_options(&conn, &id).map_err(internal_error)?;
+    drop(conn);
+
+    options.map(Json).ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "File not found".to_string(),
+            }),
+        )
+    })
+}
+
+async fn update_tile_options(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+    Json(patch): Json<serde_json::Value>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.db.lock().await;
+
+    let (status, tile_format, geometry_type): (String, Option<String>, Option<String>) = conn
         .query_row(
-            "SELECT status, table_name, tile_format FROM files WHERE id = ?",
+            "SELECT status, tile_format, geometry_type FROM files WHERE id = ?",
             duckdb::params![id],
             |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
         )
@@ -456,113 +1666,253 @@ async fn get_feature_properties(
             )
         })?;
 
-    // MBTiles files don't support feature properties
     if tile_format.is_some() {
+        return Err(bad_request(
+            "Tile options are not available for MBTiles files",
+        ));
+    }
+    if status != "ready" {
         return Err((
-            StatusCode::BAD_REQUEST,
+            StatusCode::CONFLICT,
             Json(ErrorResponse {
-                error: "Feature properties not available for MBTiles files".to_string(),
+                error: "File is not ready".to_string(),
             }),
         ));
     }
 
-    let table_name = table_name.filter(|_| status == "ready").ok_or_else(|| {
-        (
-            StatusCode::CONFLICT,
+    let current = load_tile_options(&conn, &id)
+        .map_err(internal_error)?
+        .unwrap_or_default();
+    let options = merge_tile_options(&current, &patch).map_err(|e| bad_request(&e))?;
+    let columns = load_dataset_columns(&conn, &id).map_err(internal_error)?;
+    validate_tile_options(&options, &columns).map_err(|e| bad_request(&e))?;
+    if options.cluster_max_zoom.is_some() && geometry_type.as_deref() != Some("Point") {
+        return Err(bad_request(
+            "Clustering is only available for point datasets",
+        ));
+    }
+    save_tile_options(&conn, &id, &options).map_err(internal_error)?;
+    bump_data_version(&conn, &id).map_err(internal_error)?;
+    discard_cached_tiles(&state.upload_dir, &id);
+
+    Ok(Json(options))
+}
+
+/// Delete a dataset's cached tiles, so they are rendered afresh.
+async fn purge_tile_cache(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    let exists: bool = conn
+        .query_row(
+            "SELECT count(*) > 0 FROM files WHERE id = ?",
+            duckdb::params![&id],
+            |row| row.get(0),
+        )
+        .map_err(internal_error)?;
+    drop(conn);
+    if !exists {
+        return Err((
+            StatusCode::NOT_FOUND,
             Json(ErrorResponse {
-                error: "File is not ready for preview".to_string(),
+                error: "File not found".to_string(),
             }),
-        )
-    })?;
+        ));
+    }
+    purge_cached_tiles(&tile_cache_root(&state.upload_dir), &id)
+        .await
+        .map_err(internal_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn query_dataset(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+    Json(request): Json<DatasetQueryRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let limit = request.limit().map_err(|e| bad_request(&e))?;
+    let statement = validate_query(&request.sql).map_err(|e| bad_request(&e))?;
 
-    let mut cols_stmt = conn
-        .prepare(
-            "SELECT normalized_name, original_name\n         FROM dataset_columns\n         WHERE source_id = ?\n         ORDER BY ordinal",
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+
+    let table_name = load_ready_file(
+        &conn,
+        &id,
+        "SQL queries are not available for MBTiles files",
+    )?
+    .table_name;
+
+    let rows: i64 = conn
+        .query_row(
+            &format!("SELECT COUNT(*) FROM {}", quote_identifier(&table_name)),
+            [],
+            |row| row.get(0),
         )
         .map_err(internal_error)?;
+    check_query_dataset_rows(rows).map_err(|e| bad_request(&e))?;
 
-    let cols_iter = cols_stmt
-        .query_map(duckdb::params![&id], |row| {
-            let normalized: String = row.get(0)?;
-            let original: String = row.get(1)?;
-            Ok((normalized, original))
-        })
+    let columns = load_dataset_columns(&conn, &id).map_err(internal_error)?;
+    let sandbox = open_query_sandbox(&conn, &table_name, &columns, &state.upload_dir.join(&id))
         .map_err(internal_error)?;
-
-    let mut columns: Vec<(String, String)> = Vec::new();
-    for c in cols_iter {
-        columns.push(c.map_err(internal_error)?);
+    drop(conn);
+    let sql = build_query_sql(statement, limit);
+
+    // Errors from here on come from the user's SQL (syntax, unknown columns, casts).
+    let query_error = |e: duckdb::Error| bad_request(&format!("Query failed: {e}"));
+    let mut stmt = sandbox.prepare(&sql).map_err(query_error)?;
+    let mut result = stmt.query([]).map_err(query_error)?;
+    let column_names = result
+        .as_ref()
+        .map(|stmt| stmt.column_names())
+        .unwrap_or_default();
+
+    let mut rows = Vec::new();
+    let mut truncated = false;
+    while let Some(row) = result.next().map_err(query_error)? {
+        if rows.len() == limit as usize {
+            truncated = true;
+            break;
+        }
+        let mut values = serde_json::Map::with_capacity(column_names.len());
+        for (index, name) in column_names.iter().enumerate() {
+            let value = value_ref_to_json(row.get_ref(index).map_err(internal_error)?);
+            values.insert(name.clone(), value);
+        }
+        rows.push(values);
     }
 
-    // Build a projection that preserves ordering and uses safe identifiers.
-    let mut select_exprs: Vec<String> = Vec::with_capacity(columns.len());
-    for (normalized, _original) in &columns {
-        select_exprs.push(format!("\"{normalized}\""));
-    }
+    Ok(Json(DatasetQueryResponse {
+        columns: column_names,
+        rows,
+        truncated,
+    }))
+}
 
-    let sql = format!(
-        "SELECT {} FROM \"{}\" WHERE fid = ?",
-        select_exprs.join(", "),
-        table_name
-    );
+async fn identify_features(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+    Query(query): Query<IdentifyQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let point = query.point().map_err(|e| bad_request(&e))?;
 
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+
+    let ReadyFile {
+        table_name,
+        crs: source_crs,
+        ..
+    } = load_ready_file(&conn, &id, "Identify is not available for MBTiles files")?;
+
+    let columns = load_dataset_columns(&conn, &id).map_err(internal_error)?;
+    let sql = build_identify_sql(&table_name, &source_crs, &columns, point.limit);
     let mut stmt = conn.prepare(&sql).map_err(internal_error)?;
-    let mut rows = stmt.query(duckdb::params![fid]).map_err(internal_error)?;
+    let mut rows = stmt
+        .query(duckdb::params![point.lng, point.lat, point.tolerance])
+        .map_err(internal_error)?;
 
-    let Some(row) = rows.next().map_err(internal_error)? else {
-        return Err((
+    let mut features = Vec::new();
+    while let Some(row) = rows.next().map_err(internal_error)? {
+        let fid: i64 = row.get(0).map_err(internal_error)?;
+        let mut properties = serde_json::Map::with_capacity(columns.len());
+        for (index, column) in columns.iter().enumerate() {
+            let value = value_ref_to_json(row.get_ref(index + 1).map_err(internal_error)?);
+            properties.insert(column.original.clone(), value);
+        }
+        let geometry: Option<String> = row.get(columns.len() + 1).map_err(internal_error)?;
+        let geometry = match geometry {
+            Some(text) => serde_json::from_str(&text).map_err(internal_error)?,
+            None => serde_json::Value::Null,
+        };
+        features.push(GeoJsonFeature {
+            kind: "Feature".to_string(),
+            id: fid,
+            geometry,
+            properties,
+        });
+    }
+
+    Ok((
+        [(header::CONTENT_TYPE, "application/geo+json")],
+        Json(IdentifyResponse {
+            kind: "FeatureCollection".to_string(),
+            features,
+        }),
+    ))
+}
+
+/// Resolve `name` to a property column of a ready dynamic-table dataset.
+fn load_dataset_field(
+    conn: &duckdb::Connection,
+    id: &str,
+    name: &str,
+) -> Result<(String, DatasetColumn), (StatusCode, Json<ErrorResponse>)> {
+    let table_name =
+        load_ready_file(conn, id, "Fields are not available for MBTiles files")?.table_name;
+
+    let columns = load_dataset_columns(conn, id).map_err(internal_error)?;
+    let column = resolve_column(&columns, name).cloned().ok_or_else(|| {
+        (
             StatusCode::NOT_FOUND,
             Json(ErrorResponse {
-                error: "Feature not found".to_string(),
+                error: "Field not found".to_string(),
             }),
-        ));
-    };
+        )
+    })?;
 
-    let mut properties: Vec<FeatureProperty> = Vec::with_capacity(columns.len());
-    for (index, (_normalized, original)) in columns.iter().enumerate() {
-        let raw = match row.get_ref(index).map_err(internal_error)? {
-            ValueRef::Null => serde_json::Value::Null,
-            ValueRef::Boolean(v) => serde_json::Value::Bool(v),
-            ValueRef::TinyInt(v) => serde_json::Value::Number(v.into()),
-            ValueRef::SmallInt(v) => serde_json::Value::Number(v.into()),
-            ValueRef::Int(v) => serde_json::Value::Number(v.into()),
-            ValueRef::BigInt(v) => serde_json::Value::Number(v.into()),
-            ValueRef::UTinyInt(v) => serde_json::Value::Number(v.into()),
-            ValueRef::USmallInt(v) => serde_json::Value::Number(v.into()),
-            ValueRef::UInt(v) => serde_json::Value::Number(v.into()),
-            ValueRef::UBigInt(v) => serde_json::Value::Number(v.into()),
-            ValueRef::Float(v) => serde_json::Number::from_f64(v as f64)
-                .map(serde_json::Value::Number)
-                .unwrap_or(serde_json::Value::Null),
-            ValueRef::Double(v) => serde_json::Number::from_f64(v)
-                .map(serde_json::Value::Number)
-                .unwrap_or(serde_json::Value::Null),
-            ValueRef::Text(bytes) => {
-                serde_json::Value::String(String::from_utf8_lossy(bytes).to_string())
-            }
-            ValueRef::Blob(bytes) => serde_json::Value::String(format!("0x{}", hex::encode(bytes))),
-            other => serde_json::Value::String(format!("{other:?}")),
-        };
-        properties.push(FeatureProperty {
-            key: original.clone(),
-            value: raw,
-        });
-    }
+    Ok((table_name, column))
+}
 
-    Ok(Json(FeaturePropertiesResponse { fid, properties }))
+async fn get_field_stats(
+    State(state): State<AppState>,
+    AxumPath((id, name)): AxumPath<(String, String)>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    let (table_name, column) = load_dataset_field(&conn, &id, &name)?;
+
+    let stats = field_stats(&conn, &table_name, &column).map_err(internal_error)?;
+    Ok(Json(stats))
+}
+
+async fn get_field_values(
+    State(state): State<AppState>,
+    AxumPath((id, name)): AxumPath<(String, String)>,
+    Query(query): Query<FieldValuesQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let limit = query.limit().map_err(|e| bad_request(&e))?;
+
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    let (table_name, column) = load_dataset_field(&conn, &id, &name)?;
+
+    let mut values =
+        value_counts(&conn, &table_name, &column, limit + 1).map_err(internal_error)?;
+    let truncated = values.len() > limit as usize;
+    values.truncate(limit as usize);
+
+    Ok(Json(FieldValuesResponse {
+        name: column.original,
+        field_type: column.mvt_type,
+        values,
+        truncated,
+    }))
 }
 
 async fn get_file_schema(
     State(state): State<AppState>,
     AxumPath(id): AxumPath<String>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
-    let conn = state.db.lock().await;
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
 
-    let (status, tile_format, file_path): (String, Option<String>, String) = conn
+    let (status, tile_format, file_path, table_name): (
+        String,
+        Option<String>,
+        String,
+        Option<String>,
+    ) = conn
         .query_row(
-            "SELECT status, tile_format, path FROM files WHERE id = ?",
+            "SELECT status, tile_format, path, table_name FROM files WHERE id = ?",
             duckdb::params![id],
-            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
         )
         .map_err(|_| {
             (
@@ -590,17 +1940,30 @@ async fn get_file_schema(
             let full_path = mbtiles::resolve_mbtiles_path(&file_path);
             match mbtiles::extract_mbtiles_layers(&full_path) {
                 Ok(layers) => {
-                    return Ok(Json(models::FileSchemaResponse { layers }));
+                    return Ok(Json(models::FileSchemaResponse {
+                        layers,
+                        spatial_index: None,
+                    }));
                 }
                 Err(e) => {
-                    eprintln!("Failed to extract MBTiles layers for {}: {}", id, e);
-                    eprintln!("  File path: {}", full_path.display());
-                    eprintln!("  Tile format: {}", format);
-                    return Ok(Json(models::FileSchemaResponse { layers: vec![] }));
+                    tracing::warn!(
+                        file_id = %id,
+                        path = %full_path.display(),
+                        tile_format = %format,
+                        error = %e,
+                        "Failed to extract MBTiles layers"
+                    );
+                    return Ok(Json(models::FileSchemaResponse {
+                        layers: vec![],
+                        spatial_index: None,
+                    }));
                 }
             }
         } else {
-            return Ok(Json(models::FileSchemaResponse { layers: vec![] }));
+            return Ok(Json(models::FileSchemaResponse {
+                layers: vec![],
+                spatial_index: None,
+            }));
         }
     }
 
@@ -625,6 +1988,11 @@ async fn get_file_schema(
         fields.push(models::FieldInfo { name, r#type });
     }
 
+    let spatial_index = match &table_name {
+        Some(table_name) => has_spatial_index(&conn, table_name).map_err(internal_error)?,
+        None => false,
+    };
+
     drop(conn);
 
     let default_layer = models::LayerInfo {
@@ -634,37 +2002,41 @@ async fn get_file_schema(
     };
     Ok(Json(models::FileSchemaResponse {
         layers: vec![default_layer],
+        spatial_index: Some(spatial_index),
     }))
 }
 
-fn validate_tile_coords(z: i32, x: i32, y: i32) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
-    // Practical cap. This is plenty for web maps and keeps bounds math simple.
-    const MAX_Z: i32 = 22;
-
-    if z < 0 || x < 0 || y < 0 || z > MAX_Z {
-        return Err(bad_request("Invalid tile coordinates"));
-    }
-
-    let max_xy: i32 = 1_i32 << z;
-    if x >= max_xy || y >= max_xy {
-        return Err(bad_request("Invalid tile coordinates"));
-    }
-
-    Ok(())
-}
-
 async fn upload_file(
     State(state): State<AppState>,
+    auth_session: AuthSession<AuthBackend>,
+    Query(query): Query<UploadQuery>,
     mut multipart: Multipart,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let owner_id = auth_session.user.as_ref().map(|user| user.id.clone());
+    let append_target = query
+        .append_target()
+        .map_err(|e| bad_request(&e))?
+        .map(str::to_string);
+    if let Some(target) = &append_target {
+        // Fail before streaming the body when the target can't take features.
+        let conn = state.db.lock().await;
+        if let Some(user) = &auth_session.user {
+            if let Some(access) = file_access(&conn, user, target).map_err(internal_error)? {
+                if access < Some(FileAccess::Edit) {
+                    return Err(access_denied(FileAccess::Edit));
+                }
+            }
+        }
+        load_editable_table(&conn, target)?;
+    }
+
+    // The other fields may come before or after the file.
+    let mut fields = UploadFields::default();
     let mut field = loop {
-        let next = multipart.next_field().await.map_err(|e| {
-            let message = format!("Invalid multipart form: {e}");
-            bad_request(&message)
-        })?;
+        let next = multipart.next_field().await.map_err(invalid_multipart)?;
         match next {
             Some(field) if field.name() == Some("file") => break field,
-            Some(_) => continue,
+            Some(field) => fields.read(field).await?,
             None => return Err(bad_request("No file uploaded")),
         }
     };
@@ -679,24 +2051,18 @@ async fn upload_file(
         .ok_or_else(|| bad_request("Invalid file name"))?
         .to_string();
 
-    let ext = Path::new(&safe_name)
-        .extension()
-        .and_then(|ext| ext.to_str())
-        .map(|ext| format!(".{}", ext.to_lowercase()))
-        .ok_or_else(|| bad_request("Unsupported file type. Use .zip, .geojson, .json, .geojsonl, .kml, .gpx, .topojson, or .mbtiles"))?;
-
-    let file_type = match ext.as_str() {
-        ".zip" => "shapefile",
-        ".geojson" | ".json" => "geojson",
-        ".geojsonl" | ".geojsons" => "geojsonl",
-        ".kml" => "kml",
-        ".gpx" => "gpx",
-        ".topojson" => "topojson",
-        ".mbtiles" => "mbtiles",
-        _ => return Err(bad_request(
-            "Unsupported file type. Use .zip, .geojson, .json, .geojsonl, .kml, .gpx, .topojson, or .mbtiles",
-        )),
+    // A gzip has its inner file's type; a tarball's is known once unpacked.
+    let compression = Compression::of(&safe_name);
+    let named_type = match &compression {
+        Some((Compression::TarGzip, _)) => None,
+        Some((Compression::Gzip, inner)) => Some(inner.as_str()),
+        None => Some(safe_name.as_str()),
     };
+    if let Some(name) = named_type {
+        let file_type =
+            upload_file_type(name).ok_or_else(|| bad_request(UNSUPPORTED_UPLOAD_TYPE))?;
+        check_append_type(file_type, append_target.is_some())?;
+    }
 
     let upload_id = create_id();
     let dir = state.upload_dir.join(&upload_id);
@@ -705,19 +2071,77 @@ async fn upload_file(
     let file_path = dir.join(&safe_name);
     let mut file = BufWriter::new(fs::File::create(&file_path).await.map_err(internal_error)?);
 
+    let (max_size, max_size_label) = upload_max_size(&state).await?;
     let mut size: u64 = 0;
+    let mut hasher = Sha256::new();
     while let Some(chunk) = field.chunk().await.map_err(internal_error)? {
         size = size.saturating_add(chunk.len() as u64);
-        if size > state.max_size {
+        if size > max_size {
             drop(file);
             let _ = fs::remove_file(&file_path).await;
-            let message = format!("File too large (max {})", state.max_size_label);
+            let message = format!("File too large (max {max_size_label})");
             return Err(payload_too_large(&message));
         }
+        hasher.update(&chunk);
         file.write_all(&chunk).await.map_err(internal_error)?;
     }
     file.flush().await.map_err(internal_error)?;
     drop(file); // Explicitly close file to release lock
+    drop(field);
+
+    let actual_sha256 = hex::encode(hasher.finalize());
+
+    let unpacked = async {
+        fields.read_trailing(&mut multipart).await?;
+        let (file_path, safe_name) = match compression {
+            Some((compression, inner_name)) => {
+                // Unpacking a truncated archive would only obscure why it failed.
+                if let Some(expected) = fields.sha256.as_ref().filter(|e| **e != actual_sha256) {
+                    return Err(bad_request(&format!(
+                        "Checksum mismatch: expected sha256 {expected} but received {actual_sha256}; the upload may be truncated"
+                    )));
+                }
+                let archive = file_path.clone();
+                let unpacked = tokio::task::spawn_blocking(move || {
+                    unpack_upload(&archive, compression, &inner_name, max_size)
+                })
+                .await
+                .map_err(internal_error)?;
+                let file_path = match unpacked {
+                    Ok(file_path) => file_path,
+                    Err(UnpackError::TooLarge) => {
+                        let message = format!("Decompressed file too large (max {max_size_label})");
+                        return Err(payload_too_large(&message));
+                    }
+                    Err(UnpackError::Invalid(message)) => return Err(bad_request(&message)),
+                };
+                let safe_name = file_path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .unwrap_or_default()
+                    .to_string();
+                (file_path, safe_name)
+            }
+            None => (file_path, safe_name),
+        };
+        let file_type =
+            upload_file_type(&safe_name).ok_or_else(|| bad_request(UNSUPPORTED_UPLOAD_TYPE))?;
+        check_append_type(file_type, append_target.is_some())?;
+        fields.check(file_type, append_target.is_some())?;
+        Ok::<_, (StatusCode, Json<ErrorResponse>)>((file_path, safe_name, file_type))
+    }
+    .await;
+    let (file_path, safe_name, file_type) = match unpacked {
+        Ok(unpacked) => unpacked,
+        Err(e) => {
+            let _ = fs::remove_dir_all(&dir).await;
+            return Err(e);
+        }
+    };
+    // The stored file's size, which differs from the upload's when unpacked.
+    let size = fs::metadata(&file_path)
+        .await
+        .map_or(size, |metadata| metadata.len());
 
     let base_name = Path::new(&safe_name)
         .file_stem()
@@ -725,33 +2149,63 @@ async fn upload_file(
         .unwrap_or(&safe_name)
         .to_string();
 
-    let validation = match file_type {
-        "shapefile" => validate_shapefile_zip(&file_path).await,
-        "geojson" => validate_geojson(&file_path).await,
-        "mbtiles" => mbtiles::validate_mbtiles_structure(&file_path),
-        "geojsonl" | "kml" | "gpx" | "topojson" => Ok(()), // Trust GDAL to validate
-        _ => Ok(()), // Unreachable due to earlier validation, but required for type safety
+    let validation = match &fields.sha256 {
+        Some(expected) if *expected != actual_sha256 => Err(format!(
+            "Checksum mismatch: expected sha256 {expected} but received {actual_sha256}; the upload may be truncated"
+        )),
+        _ => match validate_upload(file_type, &file_path).await {
+            Ok(()) if file_type == "shapefile" => {
+                fields.check_shapefiles(&file_path, append_target.is_some())
+            }
+            validation => validation,
+        },
     };
 
+    if let Some(target) = append_target {
+        // Scanned here since the features are appended before responding.
+        let validation = match (validation, &state.upload_scanner) {
+            (Ok(()), Some(scanner)) => match scanner.scan(&file_path).await {
+                Ok(()) => Ok(()),
+                Err(e) => {
+                    tracing::warn!(upload_id = %upload_id, error = %e, "Quarantined appended upload");
+                    if let Err(e) =
+                        quarantine_upload(&state.upload_dir, &upload_id, &file_path).await
+                    {
+                        tracing::error!(upload_id = %upload_id, error = %e, "Failed to quarantine upload");
+                    }
+                    Err(e.to_string())
+                }
+            },
+            (validation, _) => validation,
+        };
+        let result = append_upload(
+            &state,
+            &target,
+            &upload_id,
+            &file_path,
+            fields.encoding.as_deref(),
+            validation,
+        )
+        .await;
+        // Appended uploads don't become datasets, so their files aren't kept.
+        let _ = fs::remove_dir_all(&dir).await;
+        return result.map(|response| Json(response).into_response());
+    }
+
     let uploaded_at = Utc::now().to_rfc3339();
 
-    // Calculate relative path for storage
-    let relative = file_path
-        .strip_prefix(std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")))
-        .unwrap_or(&file_path)
-        .to_path_buf();
-    let mut rel_string = relative.to_string_lossy().replace('\\', "/");
-    if !rel_string.starts_with('.') {
-        rel_string = format!("./{rel_string}");
-    }
+    let rel_string = storage_path_string(&file_path);
+    // Rasters have no Z values to keep or drop.
+    let stored_z_values = (!matches!(file_type, "mbtiles" | "geotiff"))
+        .then(|| fields.z_values.unwrap_or_default().as_str());
 
     let conn = state.db.lock().await;
 
     if let Err(message) = validation {
         let size_i64 = size as i64;
         conn.execute(
-            "INSERT INTO files (id, name, type, size, uploaded_at, status, crs, path, table_name, error, is_public)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            "INSERT INTO files (id, name, type, size, uploaded_at, status, crs, path, table_name, error, is_public, owner_id, encoding, layer, split_antimeridian, z_values)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
             duckdb::params![
                 &upload_id,
                 &base_name,
@@ -764,18 +2218,24 @@ async fn upload_file(
                 &None::<String>,
                 &Some(message.clone()),
                 false,
+                &owner_id,
+                &fields.encoding,
+                &fields.layers,
+                fields.split_antimeridian,
+                stored_z_values,
             ],
         )
         .map_err(internal_error)?;
 
         drop(conn);
+        notify(&state.db, WebhookEvent::FileFailed, &upload_id, None);
         return Err(bad_request(&message));
     }
 
     let size_i64 = size as i64;
     conn.execute(
-        "INSERT INTO files (id, name, type, size, uploaded_at, status, crs, path, table_name, error, is_public)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        "INSERT INTO files (id, name, type, size, uploaded_at, status, crs, path, table_name, error, is_public, owner_id, encoding, layer, split_antimeridian, z_values)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
         duckdb::params![
             &upload_id,
             &base_name,
@@ -788,6 +2248,11 @@ async fn upload_file(
             &None::<String>,
             &None::<String>,
             false,
+            &owner_id,
+            &fields.encoding,
+            &fields.layers,
+            fields.split_antimeridian,
+            stored_z_values,
         ],
     )
     .map_err(internal_error)?;
@@ -797,44 +2262,17 @@ async fn upload_file(
     let db = state.db.clone();
     let upload_id_clone = upload_id.clone();
     let file_path_clone = file_path.clone();
-    let file_type_clone = file_type.to_string();
+    let upload_dir = state.upload_dir.clone();
+    let scanner = state.upload_scanner.clone();
     tokio::spawn(async move {
-        // Set status to processing
-        {
-            let conn = db.lock().await;
-            let _ = conn.execute(
-                "UPDATE files SET status = 'processing' WHERE id = ?",
-                duckdb::params![upload_id_clone],
-            );
-        }
-
-        let result = match file_type_clone.as_str() {
-            "mbtiles" => import_mbtiles(&db, &upload_id_clone, &file_path_clone).await,
-            _ => import_spatial_data(&db, &upload_id_clone, &file_path_clone).await,
-        };
-
-        match result {
-            Ok(_) => {
-                println!("Successfully imported spatial data for {}", upload_id_clone);
-                let conn = db.lock().await;
-                let _ = conn.execute(
-                    "UPDATE files SET status = 'ready' WHERE id = ?",
-                    duckdb::params![upload_id_clone],
-                );
-            }
-            Err(e) => {
-                eprintln!(
-                    "Failed to import spatial data for {}: {}",
-                    upload_id_clone, e
-                );
-                // Update status to failed
-                let conn = db.lock().await;
-                let _ = conn.execute(
-                    "UPDATE files SET status = 'failed', error = ? WHERE id = ?",
-                    duckdb::params![e, upload_id_clone],
-                );
+        if let Some(scanner) = scanner {
+            if let Err(e) = scanner.scan(&file_path_clone).await {
+                quarantine_failed_upload(&db, &upload_dir, &upload_id_clone, &file_path_clone, &e)
+                    .await;
+                return;
             }
         }
+        let _ = run_import(&db, &upload_id_clone, &file_path_clone, file_type).await;
     });
 
     let meta = FileItem {
@@ -850,13 +2288,389 @@ async fn upload_file(
         error: None,
         is_public: Some(false),
         public_slug: None,
+        org_id: None,
+        geometry_type: None,
+        feature_count: None,
+        folder: None,
+        tags: Vec::new(),
+    };
+
+    Ok((StatusCode::CREATED, Json(meta)).into_response())
+}
+
+fn invalid_multipart(
+    e: axum::extract::multipart::MultipartError,
+) -> (StatusCode, Json<ErrorResponse>) {
+    bad_request(&format!("Invalid multipart form: {e}"))
+}
+
+/// The lowercase hex digest of an upload's `sha256` field.
+async fn read_sha256_field(
+    field: axum::extract::multipart::Field<'_>,
+) -> Result<String, (StatusCode, Json<ErrorResponse>)> {
+    let value = field.text().await.map_err(invalid_multipart)?;
+    let value = value.trim().to_ascii_lowercase();
+    if value.len() != 64 || !value.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(bad_request("sha256 must be 64 hexadecimal characters"));
+    }
+    Ok(value)
+}
+
+/// Reject upload types that can't be appended to a dataset.
+fn check_append_type(
+    file_type: &str,
+    append: bool,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    match file_type {
+        "mbtiles" if append => Err(bad_request("MBTiles files cannot be appended to a dataset")),
+        "geotiff" if append => Err(bad_request("GeoTIFF files cannot be appended to a dataset")),
+        _ => Ok(()),
+    }
+}
+
+/// Form fields sent with an upload's `file`, before or after it.
+#[derive(Default)]
+struct UploadFields {
+    /// Lowercase hex digest to verify the upload against.
+    sha256: Option<String>,
+    /// GDAL name of a shapefile's code page; see `shapefile.rs`.
+    encoding: Option<String>,
+    /// Comma-separated shapefiles of a zip to import; see `shapefile.rs`.
+    layers: Option<String>,
+    /// Split geometries crossing ±180°; see `antimeridian.rs`.
+    split_antimeridian: bool,
+    /// Keep elevation from Z values or drop them; see `zm.rs`.
+    z_values: Option<ZValues>,
+}
+
+impl UploadFields {
+    /// Take a known field, keeping the first of each.
+    async fn read(
+        &mut self,
+        field: axum::extract::multipart::Field<'_>,
+    ) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+        match field.name() {
+            Some("sha256") if self.sha256.is_none() => {
+                self.sha256 = Some(read_sha256_field(field).await?);
+            }
+            Some("encoding") if self.encoding.is_none() => {
+                let value = field.text().await.map_err(invalid_multipart)?;
+                self.encoding = Some(normalize_encoding(&value).map_err(|e| bad_request(&e))?);
+            }
+            Some("layers") if self.layers.is_none() => {
+                let value = field.text().await.map_err(invalid_multipart)?;
+                self.layers = Some(value.trim().to_string()).filter(|value| !value.is_empty());
+            }
+            Some("splitAntimeridian") => {
+                let value = field.text().await.map_err(invalid_multipart)?;
+                self.split_antimeridian = match value.trim() {
+                    "true" => true,
+                    "false" => false,
+                    _ => return Err(bad_request("splitAntimeridian must be true or false")),
+                };
+            }
+            Some("zValues") if self.z_values.is_none() => {
+                let value = field.text().await.map_err(invalid_multipart)?;
+                self.z_values = Some(
+                    ZValues::parse(&value)
+                        .ok_or_else(|| bad_request("zValues must be drop or elevation"))?,
+                );
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Fields sent after the file.
+    async fn read_trailing(
+        &mut self,
+        multipart: &mut Multipart,
+    ) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+        while let Some(field) = multipart.next_field().await.map_err(invalid_multipart)? {
+            self.read(field).await?;
+        }
+        Ok(())
+    }
+
+    /// Reject fields that don't apply to the upload.
+    fn check(
+        &self,
+        file_type: &str,
+        append: bool,
+    ) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+        if file_type != "shapefile" {
+            if self.encoding.is_some() {
+                return Err(bad_request(
+                    "encoding only applies to shapefile (.zip) uploads",
+                ));
+            }
+            if self.layers.is_some() {
+                return Err(bad_request(
+                    "layers only applies to shapefile (.zip) uploads",
+                ));
+            }
+        }
+        if append && self.layers.is_some() {
+            return Err(bad_request("layers cannot be used when appending"));
+        }
+        if self.z_values.is_some() {
+            if matches!(file_type, "mbtiles" | "geotiff") {
+                return Err(bad_request("zValues only applies to vector uploads"));
+            }
+            if append {
+                return Err(bad_request(
+                    "zValues cannot be used when appending; appends follow the target dataset",
+                ));
+            }
+        }
+        if self.split_antimeridian {
+            if matches!(file_type, "mbtiles" | "geotiff") {
+                return Err(bad_request(
+                    "splitAntimeridian only applies to vector uploads",
+                ));
+            }
+            if append {
+                return Err(bad_request(
+                    "splitAntimeridian cannot be used when appending; appends follow the target dataset",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that a shapefile zip holds the selected shapefiles, and only one
+    /// when appending.
+    fn check_shapefiles(&self, file_path: &Path, append: bool) -> Result<(), String> {
+        let layers = select_layers(file_path, self.layers.as_deref())?;
+        if append && layers.len() > 1 {
+            return Err(
+                "Zips with several shapefiles cannot be appended; append them one at a time"
+                    .to_string(),
+            );
+        }
+        Ok(())
+    }
+}
+
+const UNSUPPORTED_UPLOAD_TYPE: &str =
+    "Unsupported file type. Use .zip, .geojson, .json, .geojsonl, .kml, .gpx, .topojson, .mbtiles, or .tif, optionally as .gz or in a .tar.gz";
+
+/// The `files.type` an upload is stored as, from its extension.
+fn upload_file_type(file_name: &str) -> Option<&'static str> {
+    let ext = Path::new(file_name)
+        .extension()
+        .and_then(|ext| ext.to_str())?
+        .to_lowercase();
+    match ext.as_str() {
+        "zip" => Some("shapefile"),
+        "geojson" | "json" => Some("geojson"),
+        "geojsonl" | "geojsons" => Some("geojsonl"),
+        "kml" => Some("kml"),
+        "gpx" => Some("gpx"),
+        "topojson" => Some("topojson"),
+        "mbtiles" => Some("mbtiles"),
+        "tif" | "tiff" => Some("geotiff"),
+        _ => None,
+    }
+}
+
+async fn validate_upload(file_type: &str, file_path: &Path) -> Result<(), String> {
+    match file_type {
+        "shapefile" => validate_shapefile_zip(file_path).await,
+        "geojson" => validate_geojson(file_path).await,
+        "mbtiles" => mbtiles::validate_mbtiles_structure(file_path),
+        "geotiff" => geotiff::validate_geotiff(file_path),
+        // Trust GDAL to validate the rest
+        _ => Ok(()),
+    }
+}
+
+/// Move an upload that failed its malware scan to the quarantine and mark its
+/// file failed with the scan error.
+async fn quarantine_failed_upload(
+    db: &std::sync::Arc<tokio::sync::Mutex<duckdb::Connection>>,
+    upload_dir: &Path,
+    file_id: &str,
+    file_path: &Path,
+    error: &ScanError,
+) {
+    tracing::warn!(file_id, error = %error, "Quarantined upload");
+    let path = match quarantine_upload(upload_dir, file_id, file_path).await {
+        Ok(path) => Some(storage_path_string(&path)),
+        Err(e) => {
+            tracing::error!(file_id, error = %e, "Failed to quarantine upload");
+            None
+        }
     };
 
-    Ok((StatusCode::CREATED, Json(meta)))
+    let conn = db.lock().await;
+    let _ = conn.execute(
+        "UPDATE files SET status = 'failed', error = ?, failed_at = ?, path = COALESCE(?, path)
+         WHERE id = ?",
+        duckdb::params![error.to_string(), Utc::now().naive_utc(), path, file_id],
+    );
+    drop(conn);
+    notify(db, WebhookEvent::FileFailed, file_id, None);
+}
+
+/// Import an uploaded file into its `files` row, moving it through
+/// `processing` to `ready` or `failed`.
+async fn run_import(
+    db: &std::sync::Arc<tokio::sync::Mutex<duckdb::Connection>>,
+    file_id: &str,
+    file_path: &Path,
+    file_type: &str,
+) -> Result<(), String> {
+    track_import(
+        db,
+        file_id,
+        file_path,
+        import_file(db, file_id, file_path, file_type),
+    )
+    .await
+}
+
+/// Import a file of `file_type` into its `files` row, leaving its status to
+/// the caller.
+async fn import_file(
+    db: &std::sync::Arc<tokio::sync::Mutex<duckdb::Connection>>,
+    file_id: &str,
+    file_path: &Path,
+    file_type: &str,
+) -> Result<(), String> {
+    match file_type {
+        "mbtiles" => import_mbtiles(db, file_id, file_path).await,
+        "geotiff" => import_geotiff(db, file_id, file_path).await,
+        "gpx" => import_gpx(db, file_id, file_path).await,
+        "kml" => import_kml(db, file_id, file_path).await,
+        "shapefile" => import_shapefile(db, file_id, file_path).await,
+        _ => import_spatial_data(db, file_id, file_path).await,
+    }
+}
+
+/// Run `import` for a file, moving it through `processing` to `ready` (with
+/// a thumbnail beside `file_path`) or `failed`.
+async fn track_import(
+    db: &std::sync::Arc<tokio::sync::Mutex<duckdb::Connection>>,
+    file_id: &str,
+    file_path: &Path,
+    import: impl std::future::Future<Output = Result<(), String>>,
+) -> Result<(), String> {
+    {
+        let conn = db.lock().await;
+        let _ = conn.execute(
+            "UPDATE files SET status = 'processing' WHERE id = ?",
+            duckdb::params![file_id],
+        );
+    }
+
+    let result = import.await;
+
+    let conn = db.lock().await;
+    match &result {
+        Ok(_) => mark_imported(db, &conn, file_id, file_path),
+        Err(e) => {
+            tracing::error!(file_id, error = %e, "Failed to import spatial data");
+            let _ = conn.execute(
+                "UPDATE files SET status = 'failed', error = ?, failed_at = ? WHERE id = ?",
+                duckdb::params![e, Utc::now().naive_utc(), file_id],
+            );
+            notify(db, WebhookEvent::FileFailed, file_id, None);
+        }
+    }
+    result
+}
+
+/// Draw the thumbnail of a file that finished importing and mark it `ready`.
+fn mark_imported(
+    db: &std::sync::Arc<tokio::sync::Mutex<duckdb::Connection>>,
+    conn: &duckdb::Connection,
+    file_id: &str,
+    file_path: &Path,
+) {
+    tracing::info!(file_id, "Imported spatial data");
+    if let Err(e) = write_thumbnail(conn, file_id, file_path) {
+        tracing::warn!(file_id, error = %e, "Failed to draw dataset thumbnail");
+    }
+    let _ = conn.execute(
+        "UPDATE files SET status = 'ready' WHERE id = ?",
+        duckdb::params![file_id],
+    );
+    notify(db, WebhookEvent::FileReady, file_id, None);
+}
+
+async fn append_upload(
+    state: &AppState,
+    target: &str,
+    upload_id: &str,
+    file_path: &Path,
+    encoding: Option<&str>,
+    validation: Result<(), String>,
+) -> Result<AppendResponse, (StatusCode, Json<ErrorResponse>)> {
+    validation.map_err(|message| bad_request(&message))?;
+
+    let conn = state.db.lock().await;
+    let (table_name, target_crs) = load_editable_table(&conn, target)?;
+    let appended = append_spatial_data(
+        &conn,
+        target,
+        &table_name,
+        &target_crs,
+        upload_id,
+        file_path,
+        encoding,
+    )
+    .map_err(|e| bad_request(&e))?;
+    bump_data_version(&conn, target).map_err(internal_error)?;
+    discard_cached_tiles(&state.upload_dir, target);
+    update_dataset_stats(&conn, target, &table_name, Some(&target_crs)).map_err(internal_error)?;
+    let data_version: i64 = conn
+        .query_row(
+            "SELECT COALESCE(data_version, 0) FROM files WHERE id = ?",
+            duckdb::params![target],
+            |row| row.get(0),
+        )
+        .map_err(internal_error)?;
+
+    Ok(AppendResponse {
+        id: target.to_string(),
+        appended,
+        data_version,
+    })
+}
+
+async fn get_openapi_spec() -> impl IntoResponse {
+    Json(build_openapi_spec())
+}
+
+async fn get_api_docs() -> impl IntoResponse {
+    (
+        [(header::CONTENT_SECURITY_POLICY, API_DOCS_CSP)],
+        axum::response::Html(api_docs_page()),
+    )
 }
 
-async fn health_check() -> impl IntoResponse {
-    (StatusCode::OK, Json(serde_json::json!({ "status": "ok" })))
+async fn get_api_docs_asset(
+    State(state): State<AppState>,
+    AxumPath(asset): AxumPath<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let (content_type, body) = api_docs_asset(&asset).ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Not found".to_string(),
+            }),
+        )
+    })?;
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    let settings = load_settings(&conn, &state).map_err(internal_error)?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::CACHE_CONTROL, public_cache_control(&settings)),
+        ],
+        body,
+    ))
 }
 
 async fn check_is_initialized(State(state): State<AppState>) -> impl IntoResponse {
@@ -886,6 +2700,29 @@ async fn publish_file(
         Some(s) => validate_slug(&s).map_err(|e| bad_request(&e))?,
         None => validate_slug(&id).map_err(|e| bad_request(&e))?,
     };
+    if let Some(overrides) = &req.tile_options {
+        validate_publish_overrides(overrides).map_err(|e| bad_request(&e))?;
+    }
+    let overrides = Some(TileOptions {
+        fields: req.include_fields,
+        field_aliases: req.field_aliases,
+        min_zoom: req.minzoom,
+        max_zoom: req.maxzoom,
+        ..req.tile_options.unwrap_or_default()
+    })
+    .filter(|options| !options.is_empty());
+    validate_publish_zoom(req.minzoom, req.maxzoom).map_err(|e| bad_request(&e))?;
+    let allowed_origins = req
+        .allowed_origins
+        .map(validate_allowed_origins)
+        .transpose()
+        .map_err(|e| bad_request(&e))?;
+    let expires_at = req
+        .expires_at
+        .as_deref()
+        .map(|raw| parse_expires_at(raw, Utc::now()))
+        .transpose()
+        .map_err(|e| bad_request(&e))?;
 
     // Use transaction to ensure atomicity: insert into published_files first (enforces uniqueness),
     // then update files table. This eliminates race conditions for concurrent publish requests.
@@ -893,11 +2730,11 @@ async fn publish_file(
         .map_err(internal_error)?;
 
     // Check file status within transaction to provide better error messages
-    let (status, _name): (String, String) = conn
+    let (status, tile_format, stored_options): (String, Option<String>, Option<String>) = conn
         .query_row(
-            "SELECT status, name FROM files WHERE id = ?",
+            "SELECT status, tile_format, tile_options FROM files WHERE id = ?",
             duckdb::params![&id],
-            |row| Ok((row.get(0)?, row.get(1)?)),
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
         )
         .map_err(|_| {
             (
@@ -910,18 +2747,89 @@ async fn publish_file(
 
     if status != "ready" {
         conn.execute_batch("ROLLBACK").map_err(internal_error)?;
-        drop(conn);
-        return Err((
-            StatusCode::CONFLICT,
-            Json(ErrorResponse {
-                error: format!("File is not ready for publishing (status: {})", status),
-            }),
-        ));
+        drop(conn);
+        return Err((
+            StatusCode::CONFLICT,
+            Json(ErrorResponse {
+                error: format!("File is not ready for publishing (status: {})", status),
+            }),
+        ));
+    }
+
+    let pinned = match req.version {
+        Some(version) => {
+            let current = current_version(&conn, &id).map_err(internal_error)?;
+            if !(1..=current).contains(&version) {
+                conn.execute_batch("ROLLBACK").map_err(internal_error)?;
+                return Err(bad_request(&format!(
+                    "Unknown version {version}; versions run from 1 to {current}"
+                )));
+            }
+            retained_version(&conn, &id, Some(version)).map_err(internal_error)?
+        }
+        None => None,
+    };
+
+    if let Some(overrides) = &overrides {
+        let checked = if tile_format.is_some() {
+            Err("Tile options cannot be set for MBTiles files".to_string())
+        } else {
+            let options = apply_publish_overrides(
+                &parse_stored_tile_options(stored_options.as_deref()),
+                overrides,
+            );
+            let columns_source = pinned
+                .as_ref()
+                .map_or(id.as_str(), |version| version.table_name.as_str());
+            load_dataset_columns(&conn, columns_source)
+                .map_err(|e| e.to_string())
+                .and_then(|columns| validate_tile_options(&options, &columns))
+        };
+        if let Err(message) = checked {
+            conn.execute_batch("ROLLBACK").map_err(internal_error)?;
+            return Err(bad_request(&message));
+        }
+    }
+    // Tilesets share the public slug namespace.
+    let tileset_slug: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM tilesets WHERE slug = ?",
+            duckdb::params![&slug],
+            |row| row.get(0),
+        )
+        .map_err(internal_error)?;
+    if tileset_slug > 0 {
+        conn.execute_batch("ROLLBACK").map_err(internal_error)?;
+        return Err(bad_request("Slug already in use"));
     }
 
+    let overrides_json = overrides
+        .as_ref()
+        .map(|options| serde_json::to_string(options).expect("tile options serialize"));
+    let signing_secret = (req.access == PublishAccess::Signed).then(generate_signing_secret);
+    let allowed_origins_json = allowed_origins
+        .as_ref()
+        .map(|origins| serde_json::to_string(origins).expect("origins serialize"));
+
+    // An expired link keeps its slug until the file is published again.
+    conn.execute(
+        "DELETE FROM published_files WHERE file_id = ? AND expires_at <= ?",
+        duckdb::params![&id, Utc::now().naive_utc()],
+    )
+    .map_err(internal_error)?;
+
     let insert_result = conn.execute(
-        "INSERT INTO published_files (file_id, slug) VALUES (?, ?)",
-        duckdb::params![&id, &slug],
+        "INSERT INTO published_files (file_id, slug, tile_options, signing_secret, expires_at, allowed_origins, version)
+         VALUES (?, ?, ?, ?, ?, ?, ?)",
+        duckdb::params![
+            &id,
+            &slug,
+            overrides_json,
+            &signing_secret,
+            expires_at.map(|at| at.naive_utc()),
+            allowed_origins_json,
+            req.version
+        ],
     );
 
     let publish_result: Result<(), String> = match insert_result {
@@ -990,11 +2898,21 @@ async fn publish_file(
     match publish_result {
         Ok(()) => {
             conn.execute_batch("COMMIT").map_err(internal_error)?;
+            let settings = load_settings(&conn, &state).map_err(internal_error)?;
             drop(conn);
+            notify(&state.db, WebhookEvent::FilePublished, &id, Some(&slug));
             Ok(Json(PublishResponse {
-                url: format!("/tiles/{slug}/{{z}}/{{x}}/{{y}}"),
+                url: public_url(&settings, &format!("/tiles/{slug}/{{z}}/{{x}}/{{y}}")),
                 slug,
                 is_public: true,
+                access: req.access,
+                signing_secret,
+                expires_at: expires_at.map(|at| at.to_rfc3339()),
+                minzoom: req.minzoom,
+                maxzoom: req.maxzoom,
+                tile_options: overrides,
+                allowed_origins,
+                version: req.version,
             }))
         }
         Err(err_msg) => {
@@ -1016,12 +2934,14 @@ async fn unpublish_file(
         .map_err(internal_error)?;
 
     // Delete from published_files and verify file is actually published (is_public=TRUE)
-    // This ensures we don't leave orphaned published_files entries if files.is_public is FALSE
+    // This ensures we don't leave orphaned published_files entries if files.is_public is FALSE.
+    // Expired links were already made private by the sweep but still hold their slug.
     let rows_affected = conn
         .execute(
             "DELETE FROM published_files 
-            WHERE file_id = ? AND file_id IN (SELECT id FROM files WHERE is_public = TRUE)",
-            duckdb::params![&id],
+            WHERE file_id = ?
+              AND (file_id IN (SELECT id FROM files WHERE is_public = TRUE) OR expires_at <= ?)",
+            duckdb::params![&id, Utc::now().naive_utc()],
         )
         .map_err(internal_error)?;
 
@@ -1047,6 +2967,8 @@ async fn unpublish_file(
         Ok(_) => {
             conn.execute_batch("COMMIT").map_err(internal_error)?;
             drop(conn);
+            discard_cached_tiles(&state.upload_dir, &id);
+            notify(&state.db, WebhookEvent::FileUnpublished, &id, None);
             Ok(Json(serde_json::json!({ "message": "File unpublished" })))
         }
         Err(err_msg) => {
@@ -1070,13 +2992,14 @@ async fn get_public_url(
             |row| Ok((row.get(0)?, row.get(1)?)),
         )
         .ok();
+    let settings = load_settings(&conn, &state).map_err(internal_error)?;
 
     drop(conn);
 
     match result {
         Some((slug, _published_at)) => Ok(Json(PublicTileUrl {
-            slug: slug.clone(),
-            url: format!("/tiles/{slug}/{{z}}/{{x}}/{{y}}"),
+            url: public_url(&settings, &format!("/tiles/{slug}/{{z}}/{{x}}/{{y}}")),
+            slug,
         })),
         None => Err((
             StatusCode::NOT_FOUND,
@@ -1087,134 +3010,531 @@ async fn get_public_url(
     }
 }
 
-async fn get_public_tile(
+async fn create_signed_url(
     State(state): State<AppState>,
-    AxumPath((slug, z, x, y)): AxumPath<(String, i32, i32, i32)>,
+    AxumPath(id): AxumPath<String>,
+    Json(req): Json<SignedUrlRequest>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
-    validate_tile_coords(z, x, y)?;
+    let expires_in = req.expires_in.unwrap_or(DEFAULT_SIGNED_URL_TTL_SECS);
+    if expires_in == 0 || expires_in > MAX_SIGNED_URL_TTL_SECS {
+        return Err(bad_request(&format!(
+            "expiresIn must be between 1 and {MAX_SIGNED_URL_TTL_SECS} seconds"
+        )));
+    }
 
     let conn = state.db.lock().await;
-
-    // Step 1: Get file_id from published_files using slug (enforces uniqueness)
-    let file_id: String = conn
+    let (slug, signing_secret): (String, Option<String>) = conn
         .query_row(
-            "SELECT file_id FROM published_files WHERE slug = ?",
-            duckdb::params![&slug],
-            |row| row.get(0),
+            "SELECT pf.slug, pf.signing_secret FROM published_files pf JOIN files f ON pf.file_id = f.id WHERE f.id = ? AND f.is_public = TRUE",
+            duckdb::params![&id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
         )
         .map_err(|_| {
             (
                 StatusCode::NOT_FOUND,
                 Json(ErrorResponse {
-                    error: "Public tile not found".to_string(),
+                    error: "File not published".to_string(),
                 }),
             )
         })?;
+    let settings = load_settings(&conn, &state).map_err(internal_error)?;
+    drop(conn);
 
-    // Step 2: Get file metadata from files table, verifying is_public flag
-    let (crs, status, table_name, tile_format, file_path): (
-        Option<String>,
-        String,
-        Option<String>,
-        Option<String>,
-        String,
-    ) = conn
-        .query_row(
-            "SELECT crs, status, table_name, tile_format, path FROM files WHERE id = ? AND is_public = TRUE",
-            duckdb::params![&file_id],
-            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
+    let Some(secret) = signing_secret else {
+        return Err(bad_request("File is published without signed access"));
+    };
+    let expires_at = Utc::now() + chrono::Duration::seconds(expires_in as i64);
+    let query = signed_query_string(&secret, &slug, expires_at.timestamp());
+
+    Ok(Json(SignedUrlResponse {
+        url: public_url(
+            &settings,
+            &format!("/tiles/{slug}/{{z}}/{{x}}/{{y}}?{query}"),
+        ),
+        expires_at: expires_at.to_rfc3339(),
+    }))
+}
+
+async fn create_tileset(
+    State(state): State<AppState>,
+    auth_session: AuthSession<AuthBackend>,
+    Json(req): Json<TilesetRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let slug = validate_slug(&req.slug).map_err(|e| bad_request(&e))?;
+    validate_tileset_files(&req.files).map_err(|e| bad_request(&e))?;
+    let name = match req.name.as_deref().map(str::trim) {
+        Some("") => return Err(bad_request("Tileset name cannot be empty")),
+        Some(name) => name.to_string(),
+        None => slug.clone(),
+    };
+
+    let conn = state.db.lock().await;
+
+    let mut sources = Vec::with_capacity(req.files.len());
+    for file_id in &req.files {
+        let ((status, table_name, tile_format, crs), owner_id): (FeatureSourceRow, Option<String>) =
+            conn.query_row(
+                "SELECT status, table_name, tile_format, crs, owner_id FROM files WHERE id = ?",
+                duckdb::params![file_id],
+                |row| {
+                    Ok((
+                        (row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?),
+                        row.get(4)?,
+                    ))
+                },
+            )
+            .map_err(|_| {
+                (
+                    StatusCode::NOT_FOUND,
+                    Json(ErrorResponse {
+                        error: format!("File '{file_id}' not found"),
+                    }),
+                )
+            })?;
+        if let Some(user) = &auth_session.user {
+            if !can_change(user, owner_id.as_deref()) {
+                return Err(not_owner("file"));
+            }
+        }
+        if tile_format.is_some() {
+            return Err(bad_request(
+                "MBTiles files cannot be combined into a tileset",
+            ));
+        }
+        let table_name = match table_name {
+            Some(table_name) if status == "ready" => table_name,
+            _ => {
+                return Err((
+                    StatusCode::CONFLICT,
+                    Json(ErrorResponse {
+                        error: format!("File '{file_id}' is not ready"),
+                    }),
+                ))
+            }
+        };
+        sources.push(TilesetSource {
+            file_id: file_id.clone(),
+            table_name,
+            crs: crs.unwrap_or_else(|| "EPSG:4326".to_string()),
+            options: load_render_options(&conn, file_id)
+                .map_err(internal_error)?
+                .unwrap_or_default(),
+        });
+    }
+    check_layer_names(&sources).map_err(|e| bad_request(&e))?;
+
+    if slug_in_use(&conn, &slug).map_err(internal_error)? {
+        return Err(bad_request("Slug already in use"));
+    }
+
+    let id = create_id();
+    let created_at = Utc::now().to_rfc3339();
+    conn.execute_batch("BEGIN TRANSACTION")
+        .map_err(internal_error)?;
+    let inserted = conn
+        .execute(
+            "INSERT INTO tilesets (id, slug, name, created_at, owner_id) VALUES (?, ?, ?, ?, ?)",
+            duckdb::params![
+                &id,
+                &slug,
+                &name,
+                &created_at,
+                auth_session.user.as_ref().map(|user| &user.id)
+            ],
         )
-        .map_err(|_| {
-            (
+        .and_then(|_| {
+            for (ordinal, file_id) in req.files.iter().enumerate() {
+                conn.execute(
+                    "INSERT INTO tileset_sources (tileset_id, file_id, ordinal) VALUES (?, ?, ?)",
+                    duckdb::params![&id, file_id, ordinal as i32],
+                )?;
+            }
+            Ok(())
+        });
+    if let Err(e) = inserted {
+        conn.execute_batch("ROLLBACK").map_err(internal_error)?;
+        return Err(internal_error(e));
+    }
+    conn.execute_batch("COMMIT").map_err(internal_error)?;
+    let settings = load_settings(&conn, &state).map_err(internal_error)?;
+    drop(conn);
+
+    Ok((
+        StatusCode::CREATED,
+        Json(TilesetResponse {
+            id,
+            name,
+            url: public_url(&settings, &format!("/tiles/{slug}/{{z}}/{{x}}/{{y}}")),
+            slug,
+            files: req.files,
+            created_at,
+        }),
+    ))
+}
+
+async fn list_tilesets(
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    let settings = load_settings(&conn, &state).map_err(internal_error)?;
+    let mut stmt = conn
+        .prepare("SELECT id, name, slug, created_at FROM tilesets ORDER BY created_at DESC")
+        .map_err(internal_error)?;
+    let rows: Vec<(String, String, String, chrono::NaiveDateTime)> = stmt
+        .query_map([], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })
+        .map_err(internal_error)?
+        .collect::<Result<_, _>>()
+        .map_err(internal_error)?;
+
+    let mut tilesets = Vec::with_capacity(rows.len());
+    for (id, name, slug, created_at) in rows {
+        tilesets.push(TilesetResponse {
+            files: load_tileset_files(&conn, &id).map_err(internal_error)?,
+            id,
+            name,
+            url: public_url(&settings, &format!("/tiles/{slug}/{{z}}/{{x}}/{{y}}")),
+            slug,
+            created_at: created_at.and_utc().to_rfc3339(),
+        });
+    }
+    Ok(Json(tilesets))
+}
+
+async fn delete_tileset(
+    State(state): State<AppState>,
+    auth_session: AuthSession<AuthBackend>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.db.lock().await;
+
+    if let Some(user) = &auth_session.user {
+        let owner: Option<Option<String>> = conn
+            .query_row(
+                "SELECT owner_id FROM tilesets WHERE id = ?",
+                duckdb::params![&id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(internal_error)?;
+        if let Some(owner_id) = owner {
+            if !can_change(user, owner_id.as_deref()) {
+                return Err(not_owner("tileset"));
+            }
+        }
+    }
+
+    conn.execute_batch("BEGIN TRANSACTION")
+        .map_err(internal_error)?;
+    let deleted = conn
+        .execute(
+            "DELETE FROM tileset_sources WHERE tileset_id = ?",
+            duckdb::params![&id],
+        )
+        .and_then(|_| conn.execute("DELETE FROM tilesets WHERE id = ?", duckdb::params![&id]));
+    match deleted {
+        Ok(0) => {
+            conn.execute_batch("ROLLBACK").map_err(internal_error)?;
+            Err((
                 StatusCode::NOT_FOUND,
                 Json(ErrorResponse {
-                    error: "File not found".to_string(),
+                    error: "Tileset not found".to_string(),
                 }),
-            )
-        })?;
+            ))
+        }
+        Ok(_) => {
+            conn.execute_batch("COMMIT").map_err(internal_error)?;
+            Ok(StatusCode::NO_CONTENT)
+        }
+        Err(e) => {
+            conn.execute_batch("ROLLBACK").map_err(internal_error)?;
+            Err(internal_error(e))
+        }
+    }
+}
 
-    if status != "ready" {
-        return Err((
-            StatusCode::CONFLICT,
-            Json(ErrorResponse {
-                error: "File is not ready".to_string(),
-            }),
-        ));
+fn find_tileset_by_slug(
+    conn: &duckdb::Connection,
+    slug: &str,
+) -> Result<Option<String>, (StatusCode, Json<ErrorResponse>)> {
+    conn.query_row(
+        "SELECT id FROM tilesets WHERE slug = ?",
+        duckdb::params![slug],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(internal_error)
+}
+
+/// TileJSON for a tileset: one vector layer per source, with the zoom range and
+/// bounds covering all of them.
+fn build_tileset_tilejson(
+    conn: &duckdb::Connection,
+    tileset_id: &str,
+    tiles_url: &str,
+) -> Result<TileJson, (StatusCode, Json<ErrorResponse>)> {
+    let (name, data_version): (String, i64) = conn
+        .query_row(
+            "SELECT t.name, COALESCE(SUM(f.data_version), 0)::BIGINT
+             FROM tilesets t
+             LEFT JOIN tileset_sources s ON s.tileset_id = t.id
+             LEFT JOIN files f ON f.id = s.file_id
+             WHERE t.id = ?
+             GROUP BY t.name",
+            duckdb::params![tileset_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(internal_error)?;
+    let sources = load_tileset_sources(conn, tileset_id).map_err(internal_error)?;
+
+    let mut vector_layers = Vec::with_capacity(sources.len());
+    let mut bounds = None;
+    for source in &sources {
+        let columns = load_dataset_columns(conn, &source.file_id).map_err(internal_error)?;
+        vector_layers.push(dataset_vector_layer(&source.options, &columns));
+        if let Some(source_bounds) =
+            dataset_bbox(conn, None, Some(&source.table_name), Some(&source.crs))
+        {
+            bounds = Some(union_bounds(bounds, source_bounds));
+        }
     }
+    let minzoom = sources
+        .iter()
+        .map(|source| source.options.min_zoom.unwrap_or(0))
+        .min()
+        .unwrap_or(0);
+    let maxzoom = sources
+        .iter()
+        .map(|source| source.options.max_zoom.unwrap_or(MAX_TILE_ZOOM))
+        .max()
+        .unwrap_or(MAX_TILE_ZOOM);
+
+    Ok(TileJson {
+        tilejson: TILEJSON_VERSION.to_string(),
+        name,
+        tiles: vec![format!("{tiles_url}?v={data_version}")],
+        minzoom,
+        maxzoom,
+        bounds,
+        description: None,
+        attribution: tileset_attribution(conn, tileset_id).map_err(internal_error)?,
+        license: None,
+        vector_layers: Some(vector_layers),
+    })
+}
 
-    // MBTiles branch
-    if let Some(format) = tile_format {
-        let full_path = mbtiles::resolve_mbtiles_path(&file_path);
-        drop(conn); // Release lock before async operation
-        match mbtiles::get_tile_from_mbtiles(&full_path, z, x, y).await {
-            Ok(Some(data)) => {
-                let ct = match format.as_str() {
-                    "mvt" => "application/vnd.mapbox-vector-tile",
-                    "png" => "image/png",
-                    _ => "application/octet-stream",
-                };
-                return Ok((
-                    [
-                        (header::CONTENT_TYPE, ct),
-                        (header::CACHE_CONTROL, "public, max-age=300"),
+async fn create_export(
+    State(state): State<AppState>,
+    auth_session: AuthSession<AuthBackend>,
+    AxumPath(id): AxumPath<String>,
+    Json(req): Json<ExportRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let format = ExportFormat::parse(&req.format)
+        .ok_or_else(|| bad_request("Unsupported export format. Use gpkg"))?;
+
+    let created_by = auth_session.user.as_ref().map(|user| user.id.as_str());
+    let conn = state.db.lock().await;
+    let job = insert_export_job(&conn, &id, format.as_str(), created_by)?;
+    drop(conn);
+
+    let db = state.db.clone();
+    let file_id = id.clone();
+    let export_dir = state.upload_dir.join(&id).join("exports");
+    let out_path = export_dir.join(format!("{}.{}", job.id, format.extension()));
+    spawn_export_job(state.db.clone(), job.id.clone(), id, async move {
+        fs::create_dir_all(&export_dir)
+            .await
+            .map_err(|e| format!("Failed to create export directory: {}", e))?;
+        export_dataset(&db, &file_id, format, &out_path).await?;
+        Ok(Some(out_path))
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+/// Check a file has a feature table to export and record a pending export
+/// job for it, started by `created_by`.
+fn insert_export_job(
+    conn: &duckdb::Connection,
+    id: &str,
+    format: &str,
+    created_by: Option<&str>,
+) -> Result<ExportJob, (StatusCode, Json<ErrorResponse>)> {
+    // MBTiles files have no feature table to export
+    load_ready_file(conn, id, "Export not available for MBTiles files")?;
+
+    let job_id = create_id();
+    let created_at = Utc::now().to_rfc3339();
+    conn.execute(
+        "INSERT INTO export_jobs (id, file_id, format, status, created_at, created_by)
+         VALUES (?, ?, ?, 'pending', ?, ?)",
+        duckdb::params![&job_id, id, format, &created_at, created_by],
+    )
+    .map_err(internal_error)?;
+
+    Ok(ExportJob {
+        id: job_id,
+        file_id: id.to_string(),
+        format: format.to_string(),
+        status: "pending".to_string(),
+        created_at,
+        finished_at: None,
+        error: None,
+        download_url: None,
+    })
+}
+
+/// Run an export job in the background, moving it through `processing` to
+/// `ready` (with the path of the file `export` wrote, if any) or `failed`.
+fn spawn_export_job(
+    db: std::sync::Arc<tokio::sync::Mutex<duckdb::Connection>>,
+    job_id: String,
+    file_id: String,
+    export: impl std::future::Future<Output = Result<Option<PathBuf>, String>> + Send + 'static,
+) {
+    tokio::spawn(async move {
+        {
+            let conn = db.lock().await;
+            let _ = conn.execute(
+                "UPDATE export_jobs SET status = 'processing' WHERE id = ?",
+                duckdb::params![job_id],
+            );
+        }
+
+        let result = export.await;
+
+        let conn = db.lock().await;
+        match result {
+            Ok(out_path) => {
+                tracing::info!(file_id = %file_id, job_id = %job_id, "Exported dataset");
+                let _ = conn.execute(
+                    "UPDATE export_jobs SET status = 'ready', path = ?, finished_at = ? WHERE id = ?",
+                    duckdb::params![
+                        out_path.map(|path| path.to_string_lossy().to_string()),
+                        Utc::now().to_rfc3339(),
+                        job_id
                     ],
-                    data,
-                )
-                    .into_response());
-            }
-            Ok(None) => {
-                return Ok(StatusCode::NO_CONTENT.into_response());
+                );
             }
             Err(e) => {
-                return Err(internal_error(format!("Failed to read MBTiles: {}", e)));
+                tracing::error!(file_id = %file_id, error = %e, "Failed to export dataset");
+                let _ = conn.execute(
+                    "UPDATE export_jobs SET status = 'failed', error = ?, finished_at = ? WHERE id = ?",
+                    duckdb::params![e, Utc::now().to_rfc3339(), job_id],
+                );
             }
         }
+    });
+}
+
+fn export_not_found() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse {
+            error: "Export not found".to_string(),
+        }),
+    )
+}
+
+/// An export job is visible to the user who started it, while they can still
+/// read its file, and to admins. Jobs from before their creator was recorded
+/// are left to admins. Anyone else is told the job does not exist.
+fn check_export_access(
+    conn: &duckdb::Connection,
+    user: Option<&User>,
+    job_id: &str,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    let job: Option<(String, Option<String>)> = conn
+        .query_row(
+            "SELECT file_id, created_by FROM export_jobs WHERE id = ?",
+            duckdb::params![job_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(internal_error)?;
+    let (file_id, created_by) = job.ok_or_else(export_not_found)?;
+    let Some(user) = user else {
+        return Ok(());
+    };
+    let can_read = file_access(conn, user, &file_id)
+        .map_err(internal_error)?
+        .flatten()
+        .is_some();
+    if can_read && can_change(user, created_by.as_deref()) {
+        Ok(())
+    } else {
+        Err(export_not_found())
     }
+}
 
-    // Dynamic generation branch (existing logic)
-    let table_name = table_name.ok_or_else(|| {
-        (
+async fn get_export(
+    State(state): State<AppState>,
+    auth_session: AuthSession<AuthBackend>,
+    AxumPath(job_id): AxumPath<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    check_export_access(&conn, auth_session.user.as_ref(), &job_id)?;
+    let job = load_export_job(&conn, &job_id).map_err(internal_error)?;
+    drop(conn);
+
+    job.map(Json).ok_or_else(export_not_found)
+}
+
+async fn download_export(
+    State(state): State<AppState>,
+    auth_session: AuthSession<AuthBackend>,
+    AxumPath(job_id): AxumPath<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    check_export_access(&conn, auth_session.user.as_ref(), &job_id)?;
+
+    let (status, format, path, name): (String, String, Option<String>, String) = conn
+        .query_row(
+            "SELECT e.status, e.format, e.path, f.name FROM export_jobs e JOIN files f ON e.file_id = f.id WHERE e.id = ?",
+            duckdb::params![&job_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .map_err(|_| export_not_found())?;
+
+    drop(conn);
+
+    let (Some(path), Some(format)) = (
+        path.filter(|_| status == "ready"),
+        ExportFormat::parse(&format),
+    ) else {
+        return Err((
             StatusCode::CONFLICT,
             Json(ErrorResponse {
-                error: "File is not ready".to_string(),
+                error: format!("Export is not ready (status: {})", status),
             }),
-        )
-    })?;
-
-    let source_crs = crs.as_deref().unwrap_or("EPSG:4326");
+        ));
+    };
 
-    let select_sql =
-        build_mvt_select_sql(&conn, &file_id, &table_name, source_crs).map_err(internal_error)?;
+    let data = fs::read(&path).await.map_err(internal_error)?;
+    let file_name = format!("{}.{}", name.replace(['"', '\\'], "_"), format.extension());
 
-    let mvt_blob: Option<Vec<u8>> =
-        match conn.query_row(&select_sql, duckdb::params![z, x, y, z, x, y], |row| {
-            row.get(0)
-        }) {
-            Ok(blob) => Some(blob),
-            Err(e) => {
-                eprintln!("Tile Error (z={z}, x={x}, y={y}): {:?}", e);
-                return Err(internal_error(format!("Tile generation failed: {}", e)));
-            }
-        };
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{file_name}\""),
+            ),
+        ],
+        data,
+    ))
+}
 
-    match mvt_blob {
-        Some(blob) if !blob.is_empty() => Ok((
-            [
-                (header::CONTENT_TYPE, "application/vnd.mapbox-vector-tile"),
-                (header::CACHE_CONTROL, "public, max-age=300"),
-            ],
-            blob,
-        )
-            .into_response()),
-        _ => Ok((
-            [
-                (header::CONTENT_TYPE, "application/vnd.mapbox-vector-tile"),
-                (header::CACHE_CONTROL, "public, max-age=300"),
-            ],
-            Vec::new(),
-        )
-            .into_response()),
+/// Parse a publish expiry (RFC 3339), which must lie in the future.
+fn parse_expires_at(raw: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>, String> {
+    let expires_at = DateTime::parse_from_rfc3339(raw.trim())
+        .map_err(|_| "expiresAt must be an RFC 3339 timestamp".to_string())?
+        .with_timezone(&Utc);
+    if expires_at <= now {
+        return Err("expiresAt must be in the future".to_string());
     }
+    Ok(expires_at)
 }
 
 fn validate_slug(slug: &str) -> Result<String, String> {
@@ -1238,6 +3558,20 @@ fn validate_slug(slug: &str) -> Result<String, String> {
     Ok(slug)
 }
 
+/// Path stored in `files.path`: relative to the working directory when possible.
+fn storage_path_string(file_path: &Path) -> String {
+    let relative = file_path
+        .strip_prefix(std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")))
+        .unwrap_or(file_path)
+        .to_path_buf();
+    let rel_string = relative.to_string_lossy().replace('\\', "/");
+    if rel_string.starts_with('.') {
+        rel_string
+    } else {
+        format!("./{rel_string}")
+    }
+}
+
 fn create_id() -> String {
     let mut bytes = [0u8; 3];
     rand::thread_rng().fill_bytes(&mut bytes);
@@ -1251,13 +3585,10 @@ mod tests {
     use axum::http::Request;
     use http_body_util::BodyExt;
     use std::sync::Arc;
-    use std::sync::OnceLock;
     use tempfile::TempDir;
     use tokio::sync::Mutex;
     use tower::util::ServiceExt;
 
-    static ENV_LOCK: OnceLock<std::sync::Mutex<()>> = OnceLock::new();
-
     async fn setup_state(max_size: u64) -> (AppState, TempDir) {
         let temp_dir = TempDir::new().expect("temp dir");
         let upload_dir = temp_dir.path().join("uploads");
@@ -1312,7 +3643,16 @@ mod tests {
             max_size,
             max_size_label: format_bytes(max_size),
             auth_backend: AuthBackend::new(conn.clone()),
-            session_store: DuckDBStore::new(conn),
+            session_store: DuckDBStore::new(conn.clone()),
+            read_pool: ReadPool::new(conn),
+            upload_scanner: None,
+            postgis_export_connection: None,
+            postgis_export_schemas: vec!["public".to_string()],
+            public_base_url: None,
+            trust_forwarded_headers: false,
+            remote_allow_private_hosts: false,
+            password_reset_delivery: None,
+            file_events: FileEvents::default(),
         };
 
         (state, temp_dir)
@@ -1349,6 +3689,11 @@ mod tests {
             error: None,
             is_public: Some(false),
             public_slug: None,
+            org_id: None,
+            geometry_type: None,
+            feature_count: None,
+            folder: None,
+            tags: Vec::new(),
         };
 
         let conn = state.db.lock().await;
@@ -1373,7 +3718,7 @@ mod tests {
         .unwrap();
         drop(conn);
 
-        let app = build_api_router(state);
+        let app = build_api_router(state, &Config::default());
         let response = app
             .oneshot(
                 Request::builder()
@@ -1391,60 +3736,118 @@ mod tests {
         assert_eq!(items[0].status, "uploaded");
     }
 
-    #[test]
-    fn read_cookie_secure_from_env() {
-        let _guard = ENV_LOCK
-            .get_or_init(|| std::sync::Mutex::new(()))
-            .lock()
-            .expect("env lock");
+    fn config_with_env(vars: &[(&str, &str)]) -> Config {
+        let mut config = Config::default();
+        config.apply_env(|name| {
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        });
+        config
+    }
 
+    #[test]
+    fn cookie_secure_from_env() {
         // Default to false
-        std::env::remove_var("COOKIE_SECURE");
-        assert!(!read_cookie_secure());
+        assert!(!config_with_env(&[]).cookie_secure);
 
         // Explicitly set to false
-        std::env::set_var("COOKIE_SECURE", "false");
-        assert!(!read_cookie_secure());
+        assert!(!config_with_env(&[("COOKIE_SECURE", "false")]).cookie_secure);
 
-        std::env::set_var("COOKIE_SECURE", "true");
-        assert!(read_cookie_secure());
+        assert!(config_with_env(&[("COOKIE_SECURE", "true")]).cookie_secure);
 
         // Invalid value falls back to default false
-        std::env::set_var("COOKIE_SECURE", "invalid");
-        assert!(!read_cookie_secure());
-
-        std::env::remove_var("COOKIE_SECURE");
+        assert!(!config_with_env(&[("COOKIE_SECURE", "invalid")]).cookie_secure);
     }
 
     #[test]
-    fn read_max_size_config_default_and_custom() {
-        let _guard = ENV_LOCK
-            .get_or_init(|| std::sync::Mutex::new(()))
-            .lock()
-            .expect("env lock");
-
+    fn max_size_default_and_custom() {
         let default_mb: u64 = 200;
         let bytes_per_mb: u64 = 1024 * 1024;
 
-        std::env::remove_var("UPLOAD_MAX_SIZE_MB");
-        let (bytes, label) = read_max_size_config();
+        let (bytes, label) = config_with_env(&[]).max_size();
         assert_eq!(bytes, default_mb * bytes_per_mb);
         assert_eq!(label, "200MB");
 
-        std::env::set_var("UPLOAD_MAX_SIZE_MB", "12");
-        let (bytes, label) = read_max_size_config();
+        let (bytes, label) = config_with_env(&[("UPLOAD_MAX_SIZE_MB", "12")]).max_size();
         assert_eq!(bytes, 12 * bytes_per_mb);
         assert_eq!(label, "12MB");
 
-        std::env::set_var("UPLOAD_MAX_SIZE_MB", "0");
-        let (bytes, label) = read_max_size_config();
+        let (bytes, label) = config_with_env(&[("UPLOAD_MAX_SIZE_MB", "0")]).max_size();
         assert_eq!(bytes, default_mb * bytes_per_mb);
         assert_eq!(label, "200MB");
 
-        std::env::set_var("UPLOAD_MAX_SIZE_MB", "nope");
-        let (bytes, label) = read_max_size_config();
+        let (bytes, label) = config_with_env(&[("UPLOAD_MAX_SIZE_MB", "nope")]).max_size();
         assert_eq!(bytes, default_mb * bytes_per_mb);
         assert_eq!(label, "200MB");
-        std::env::remove_var("UPLOAD_MAX_SIZE_MB");
+    }
+
+    #[test]
+    fn env_overrides_config_file() {
+        let mut config = Config::from_toml(
+            r#"
+            port = 8080
+            upload_max_size_mb = 50
+            cors_allowed_origins = ["https://maps.example.com"]
+            "#,
+        )
+        .expect("valid config");
+        assert_eq!(config.upload_max_size_mb, 50);
+        // Keys missing from the file keep their defaults.
+        assert_eq!(config.upload_dir, Config::default().upload_dir);
+
+        config.apply_env(|name| (name == "PORT").then(|| "9090".to_string()));
+        assert_eq!(config.port, 9090);
+        assert_eq!(
+            config.cors_allowed_origins,
+            vec!["https://maps.example.com"]
+        );
+
+        let logging = config_with_env(&[("LOG_LEVEL", "backend=debug"), ("LOG_FORMAT", "JSON")]);
+        assert_eq!(logging.log_level, "backend=debug");
+        assert_eq!(logging.log_format, LogFormat::Json);
+        assert_eq!(
+            config_with_env(&[("LOG_FORMAT", "xml")]).log_format,
+            LogFormat::Text
+        );
+
+        assert!(Config::from_toml("upload_max_size_mb = 0").is_err());
+        assert!(Config::from_toml("uplaod_dir = \"/data\"").is_err());
+
+        assert_eq!(
+            config_with_env(&[("PUBLIC_BASE_URL", "https://maps.example.com/")]).public_base_url,
+            Some("https://maps.example.com".to_string())
+        );
+        assert_eq!(
+            config_with_env(&[("PUBLIC_BASE_URL", "maps.example.com")]).public_base_url,
+            None
+        );
+        assert!(Config::from_toml("public_base_url = \"maps.example.com\"").is_err());
+    }
+
+    #[test]
+    fn upload_scanner_from_config() {
+        assert_eq!(config_with_env(&[]).upload_scanner(), None);
+        assert_eq!(
+            config_with_env(&[("UPLOAD_SCAN_COMMAND", "clamdscan --no-summary {path}")])
+                .upload_scanner(),
+            Some(UploadScanner::Command(vec![
+                "clamdscan".to_string(),
+                "--no-summary".to_string(),
+                "{path}".to_string(),
+            ]))
+        );
+
+        let mut config =
+            Config::from_toml("upload_scan_command = \"clamscan\"").expect("valid config");
+        config.apply_env(|name| (name == "UPLOAD_SCAN_CLAMD").then(|| "clamav:3310".to_string()));
+        assert_eq!(
+            config.upload_scanner(),
+            Some(UploadScanner::Clamd("clamav:3310".to_string()))
+        );
+        assert!(Config::from_toml(
+            "upload_scan_command = \"clamscan\"\nupload_scan_clamd = \"clamav:3310\""
+        )
+        .is_err());
     }
 }
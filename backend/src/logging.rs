@@ -0,0 +1,72 @@
+//! Structured logging
+//!
+//! Logs go through `tracing`. `log_level` takes `EnvFilter` directives
+//! (`info`, `backend=debug,tower_http=info`, ...) and `log_format` picks
+//! human-readable text or one JSON object per line. Every HTTP request runs in
+//! a `request` span carrying its request id, method, path and, once
+//! authenticated, the username, so handler events such as tile errors can be
+//! traced back to the request.
+
+use axum::{extract::Request, http::Request as HttpRequest, middleware::Next, response::Response};
+use axum_login::AuthSession;
+use serde::Deserialize;
+use tracing::Span;
+use tracing_subscriber::EnvFilter;
+
+use crate::auth::AuthBackend;
+use crate::request_id::request_id_of;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl LogFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "text" => Some(Self::Text),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Install the global subscriber. Invalid filter directives fall back to `info`.
+pub fn init_logging(level: &str, format: LogFormat) {
+    let filter = EnvFilter::try_new(level).unwrap_or_else(|e| {
+        eprintln!("Invalid log_level '{level}' ({e}), using 'info'");
+        EnvFilter::new("info")
+    });
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    // Fails only when a subscriber is already installed, which is fine.
+    let _ = match format {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Json => builder.json().try_init(),
+    };
+}
+
+/// Span wrapping one HTTP request; `user` is filled in by `record_user`.
+pub fn request_span<B>(request: &HttpRequest<B>) -> Span {
+    tracing::info_span!(
+        "request",
+        request_id = request_id_of(request).unwrap_or_default(),
+        method = %request.method(),
+        path = %request.uri().path(),
+        user = tracing::field::Empty,
+    )
+}
+
+/// Attach the signed-in user to the request span.
+pub async fn record_user(
+    auth_session: AuthSession<AuthBackend>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Some(user) = &auth_session.user {
+        Span::current().record("user", user.username.as_str());
+    }
+    next.run(request).await
+}
@@ -1,55 +1,458 @@
-use std::{path::PathBuf, sync::Arc};
+use clap::{Parser, Subcommand};
+use std::future::IntoFuture;
+use std::io::BufRead;
+use std::path::PathBuf;
+use std::sync::Arc;
 use tokio::{fs, sync::Mutex};
 use tower_http::services::{ServeDir, ServeFile};
+use tower_sessions::session_store::ExpiredDeletion;
+
+/// MapFlow map data server. Without a subcommand it runs the server.
+///
+/// The admin subcommands open the database directly; DuckDB allows a single
+/// writer, so run them while the server is stopped.
+#[derive(Parser)]
+#[command(name = "mapflow", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the HTTP server
+    Serve,
+    /// Manage local user accounts
+    #[command(subcommand)]
+    User(UserCommand),
+    /// Import a spatial file as a new dataset and wait for it to finish
+    Import {
+        file: PathBuf,
+        /// Username that will own the dataset
+        #[arg(long)]
+        owner: Option<String>,
+    },
+    /// Export a dataset to a file
+    Export {
+        id: String,
+        #[arg(long, default_value = "gpkg")]
+        format: String,
+        /// Output path; defaults to `<id>.<format>` in the working directory
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Render a dataset's tiles into the tile cache and wait for it to finish
+    Seed {
+        id: String,
+        /// `minx,miny,maxx,maxy` in WGS84; defaults to the dataset's extent
+        #[arg(long)]
+        bbox: Option<String>,
+        #[arg(long, default_value_t = 0)]
+        min_zoom: u8,
+        #[arg(long)]
+        max_zoom: u8,
+    },
+    /// Write a backup archive of the database and uploads
+    Backup {
+        /// Output path; defaults to `mapflow-<timestamp>.zip` in the working directory
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Restore a backup archive into DB_PATH and UPLOAD_DIR
+    Restore {
+        archive: PathBuf,
+        /// Replace an existing database
+        #[arg(long)]
+        force: bool,
+    },
+    /// Database maintenance
+    #[command(subcommand)]
+    Db(DbCommand),
+}
+
+#[derive(Subcommand)]
+enum UserCommand {
+    /// Create an account; the password is read from stdin unless given
+    Create {
+        username: String,
+        /// admin, editor or viewer
+        #[arg(long, default_value = "viewer")]
+        role: String,
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Set a new password; it is read from stdin unless given
+    ResetPassword {
+        username: String,
+        #[arg(long)]
+        password: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum DbCommand {
+    /// Create or upgrade the database schema
+    Migrate,
+    /// Remove upload directories and dataset tables no dataset refers to
+    Cleanup {
+        /// Only list what would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
 
 #[tokio::main]
 async fn main() {
-    let db_path = std::env::var("DB_PATH").unwrap_or_else(|_| backend::DEFAULT_DB_PATH.to_string());
-    let db_path = PathBuf::from(db_path);
-    let conn = backend::init_database(&db_path);
+    let cli = Cli::parse();
+    let config = backend::Config::load().unwrap_or_else(|e| panic!("Invalid configuration: {e}"));
+    backend::init_logging(&config.log_level, config.log_format);
+
+    let result = match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => {
+            serve(config).await;
+            Ok(())
+        }
+        Command::User(command) => run_user_command(&config, command).await,
+        Command::Import { file, owner } => {
+            let state = build_state(&config).await;
+            backend::cli::import_file(&state, &file, owner.as_deref())
+                .await
+                .map(|id| println!("Imported {} as {id}", file.display()))
+        }
+        Command::Export { id, format, output } => {
+            let state = build_state(&config).await;
+            backend::cli::export_file(&state, &id, &format, output)
+                .await
+                .map(|path| println!("Exported {id} to {}", path.display()))
+        }
+        Command::Seed {
+            id,
+            bbox,
+            min_zoom,
+            max_zoom,
+        } => {
+            let state = build_state(&config).await;
+            backend::cli::seed_tiles(
+                &state,
+                &id,
+                bbox.as_deref(),
+                min_zoom,
+                max_zoom,
+                |rendered, total| eprint!("\rSeeded {rendered}/{total} tiles"),
+            )
+            .await
+            .map(|rendered| {
+                eprintln!();
+                println!("Seeded {rendered} tiles for {id}");
+            })
+        }
+        Command::Backup { output } => {
+            let state = build_state(&config).await;
+            backend::cli::backup(&state, output)
+                .await
+                .map(|(path, size)| {
+                    println!(
+                        "Wrote backup {} ({})",
+                        path.display(),
+                        backend::format_bytes(size)
+                    )
+                })
+        }
+        Command::Restore { archive, force } => {
+            backend::cli::restore(&archive, &config.db_path, &config.upload_dir, force)
+                .await
+                .map(|report| {
+                    println!(
+                        "Restored backup from {} ({} uploaded files, schema version {}) to {}",
+                        report.created_at,
+                        report.uploads,
+                        report.schema_version,
+                        config.db_path.display()
+                    )
+                })
+        }
+        Command::Db(DbCommand::Migrate) => {
+            let conn = backend::init_database(&config.db_path);
+            backend::schema_version(&conn)
+                .map(|version| {
+                    println!(
+                        "Database schema is up to date (version {version}): {}",
+                        config.db_path.display()
+                    )
+                })
+                .map_err(|e| e.to_string())
+        }
+        Command::Db(DbCommand::Cleanup { dry_run }) => {
+            let state = build_state(&config).await;
+            backend::clean_orphans(&state, dry_run).await.map(|report| {
+                let verb = if dry_run { "Would remove" } else { "Removed" };
+                for dir in &report.directories {
+                    println!("{verb} directory {}", dir.display());
+                }
+                for table in &report.tables {
+                    println!("{verb} table {table}");
+                }
+                if report.is_empty() {
+                    println!("No orphaned uploads or tables");
+                }
+            })
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    }
+}
 
-    let upload_dir = std::env::var("UPLOAD_DIR").unwrap_or_else(|_| "./uploads".to_string());
-    let upload_dir = PathBuf::from(upload_dir);
+async fn run_user_command(config: &backend::Config, command: UserCommand) -> Result<(), String> {
+    let state = build_state(config).await;
+    match command {
+        UserCommand::Create {
+            username,
+            role,
+            password,
+        } => {
+            let role =
+                backend::Role::parse(&role).ok_or_else(|| format!("Unknown role '{role}'"))?;
+            let password = password_or_stdin(password)?;
+            let user = backend::cli::create_user(&state, &username, &password, role).await?;
+            println!("Created {} user {}", user.role.as_str(), user.username);
+        }
+        UserCommand::ResetPassword { username, password } => {
+            let password = password_or_stdin(password)?;
+            backend::cli::reset_password(&state, &username, &password).await?;
+            println!("Password reset for {username}");
+        }
+    }
+    Ok(())
+}
+
+/// Passwords given as flags end up in shell history, so stdin is the default.
+fn password_or_stdin(password: Option<String>) -> Result<String, String> {
+    if let Some(password) = password {
+        return Ok(password);
+    }
+    eprintln!("Password:");
+    let mut line = String::new();
+    std::io::stdin()
+        .lock()
+        .read_line(&mut line)
+        .map_err(|e| format!("Failed to read password: {e}"))?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+async fn build_state(config: &backend::Config) -> backend::AppState {
+    // Installing the spatial extension can take a while; keep it off the
+    // runtime threads that answer probes in the meantime.
+    let db_path = config.db_path.clone();
+    let postgis_export = config.postgis_export_connection.is_some();
+    let conn = tokio::task::spawn_blocking(move || {
+        let conn = backend::init_database(&db_path);
+        // Loaded here so requests never install it; exports need it.
+        match backend::load_postgres_extension(&conn) {
+            Ok(()) => {}
+            Err(e) if postgis_export => panic!("postgis_export_connection is set, but {e}"),
+            Err(e) => tracing::warn!(error = %e, "PostGIS import and export are unavailable"),
+        }
+        conn
+    })
+    .await
+    .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()));
+
+    let upload_dir = config.upload_dir.clone();
     let _ = fs::create_dir_all(&upload_dir).await;
 
-    let (max_size, max_size_label) = backend::read_max_size_config();
+    let (max_size, max_size_label) = config.max_size();
 
     let db = Arc::new(Mutex::new(conn));
 
     // 创建认证 backend 和 session store
-    let auth_backend = backend::AuthBackend::new(db.clone());
-    let session_store = backend::DuckDBStore::new(db.clone());
+    let mut auth_backend = backend::AuthBackend::new(db.clone());
+    match backend::read_ldap_config() {
+        Ok(Some(ldap)) => {
+            tracing::info!(url = %ldap.url, "Authenticating users against LDAP");
+            auth_backend = auth_backend.with_ldap(ldap);
+        }
+        Ok(None) => {}
+        Err(e) => panic!("Invalid LDAP configuration: {e}"),
+    }
+    let session_store =
+        backend::DuckDBStore::new(db.clone()).with_write_interval(config.session_write_interval());
+    let read_pool = backend::ReadPool::new(db.clone()).with_size(config.read_pool_size);
 
-    let state = backend::AppState {
+    backend::AppState {
         upload_dir,
-        db: db.clone(),
+        db,
         max_size,
         max_size_label,
         auth_backend,
         session_store,
-    };
+        read_pool,
+        upload_scanner: config.upload_scanner(),
+        postgis_export_connection: config.postgis_export_connection.clone(),
+        postgis_export_schemas: config.postgis_export_schemas.clone(),
+        public_base_url: config.public_base_url.clone(),
+        trust_forwarded_headers: config.trust_forwarded_headers,
+        remote_allow_private_hosts: config.remote_import_allow_private_hosts,
+        password_reset_delivery: config.password_reset_delivery(),
+        file_events: backend::FileEvents::default(),
+    }
+}
+
+async fn serve(config: backend::Config) {
+    // Bind first so /livez answers while the database is opened; /readyz and
+    // the API return 503 until startup finishes.
+    let addr = format!("0.0.0.0:{}", config.port);
+    let listener = tokio::net::TcpListener::bind(&addr)
+        .await
+        .expect("failed to bind");
+    let startup = backend::Startup::new();
+    let (stop_accepting, stopped) = tokio::sync::oneshot::channel::<()>();
+    let mut server = tokio::spawn(
+        axum::serve(listener, startup.router())
+            .with_graceful_shutdown(async move {
+                let _ = stopped.await;
+            })
+            .into_future(),
+    );
+    tracing::info!("MapFlow server starting at http://{addr}");
+
+    let state = build_state(&config).await;
 
     // Reconciliation: Mark any 'processing' files as 'failed' on startup
     let _ = backend::reconcile_processing_files(&state.db).await;
+    let _ = backend::reconcile_export_jobs(&state.db).await;
+    let _ = backend::reconcile_seed_jobs(&state.db).await;
+
+    // Expired public links are made private in the background
+    let sweep_db = state.db.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(backend::PUBLISH_EXPIRY_SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = backend::expire_published_files(&sweep_db).await {
+                tracing::warn!(error = %e, "Failed to expire public links");
+            }
+        }
+    });
+
+    // Sessions that are never loaded again are only removed here.
+    let session_store = state.session_store.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(backend::SESSION_CLEANUP_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = session_store.delete_expired().await {
+                tracing::warn!(error = %e, "Failed to delete expired sessions");
+            }
+        }
+    });
 
-    let mut app = backend::build_api_router(state.clone());
+    let retention_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(backend::RETENTION_SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            match backend::purge_failed_uploads(&retention_state).await {
+                Ok(purged) if purged.is_empty() => {}
+                Ok(purged) => tracing::info!(files = ?purged, "Purged expired failed uploads"),
+                Err(e) => tracing::warn!(error = %e, "Failed to purge failed uploads"),
+            }
+        }
+    });
 
-    let web_dist = std::env::var("WEB_DIST").unwrap_or_else(|_| "frontend/dist".to_string());
-    let web_dist_path = PathBuf::from(web_dist);
+    // Runs once now, after the reconciliation above, then periodically.
+    let sweep_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(backend::ORPHAN_SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            match backend::clean_orphans(&sweep_state, false).await {
+                Ok(report) if report.is_empty() => {}
+                Ok(report) => tracing::info!(
+                    directories = ?report.directories,
+                    tables = ?report.tables,
+                    "Removed orphaned uploads and tables"
+                ),
+                Err(e) => tracing::warn!(error = %e, "Failed to clean up orphaned uploads"),
+            }
+        }
+    });
+
+    if config.seed_demo {
+        match backend::seed_demo_data(&state).await {
+            Ok(Some(published)) => {
+                tracing::info!(url = %published.url, "Seeded demo dataset")
+            }
+            Ok(None) => {}
+            Err(e) => tracing::error!(error = %e, "Failed to seed demo dataset"),
+        }
+    }
+
+    let mut app = backend::build_api_router(state.clone(), &config);
+
+    let web_dist_path = &config.web_dist;
     if web_dist_path.exists() {
         let index_path = web_dist_path.join("index.html");
         app = app.fallback_service(
-            ServeDir::new(&web_dist_path).not_found_service(ServeFile::new(index_path)),
+            ServeDir::new(web_dist_path).not_found_service(ServeFile::new(index_path)),
         );
     }
 
-    let port = std::env::var("PORT").unwrap_or_else(|_| "3000".to_string());
-    let addr = format!("0.0.0.0:{port}");
-    println!("MapFlow server running at http://{addr}");
+    startup.finish(app);
+    tracing::info!("MapFlow server running at http://{addr}");
 
-    let listener = tokio::net::TcpListener::bind(&addr)
-        .await
-        .expect("failed to bind");
+    tokio::select! {
+        result = &mut server => {
+            result.expect("server task failed").expect("server failed");
+            return;
+        }
+        () = shutdown_signal() => {}
+    }
+
+    // Stop accepting connections and give in-flight requests a bounded window
+    // to finish; background imports are failed below, as on restart.
+    let timeout = config.shutdown_timeout();
+    tracing::info!(
+        timeout_secs = timeout.as_secs(),
+        "Shutting down, draining requests"
+    );
+    let _ = stop_accepting.send(());
+    match tokio::time::timeout(timeout, &mut server).await {
+        Ok(result) => result.expect("server task failed").expect("server failed"),
+        Err(_) => tracing::warn!("Requests still running after the drain window; dropping them"),
+    }
 
-    axum::serve(listener, app).await.expect("server failed");
+    match backend::shutdown_database(&state.db).await {
+        Ok(interrupted) => tracing::info!(
+            interrupted_imports = interrupted.files,
+            interrupted_exports = interrupted.exports,
+            interrupted_seeds = interrupted.seeds,
+            "Database checkpointed"
+        ),
+        Err(e) => tracing::error!(error = %e, "Failed to checkpoint database on shutdown"),
+    }
+}
+
+/// Resolves on Ctrl+C, or SIGTERM where there are signals.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to listen for Ctrl+C");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = ctrl_c => {}
+        () = terminate => {}
+    }
 }
@@ -0,0 +1,208 @@
+//! Feature measurements
+//!
+//! `GET /api/files/{id}/features/{fid}/measure` reports a line's length, or a
+//! polygon's area and perimeter, measured on the WGS84 ellipsoid whatever the
+//! dataset's CRS, so clients can show them without fetching the geometry and
+//! measuring it themselves. Each value comes in a unit suited to its size, in
+//! the metric or imperial system asked for with `units`, next to its value in
+//! meters or square meters.
+
+use axum::{
+    extract::{Path as AxumPath, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use duckdb::OptionalExt;
+use serde::Deserialize;
+
+use crate::columns::{quote_identifier, quote_literal};
+use crate::http_errors::{bad_request, internal_error};
+use crate::models::{FeatureMeasurements, Measurement};
+use crate::ready_file::{load_ready_file, ReadyFile};
+use crate::{AppState, ErrorResponse};
+
+const METERS_PER_FOOT: f64 = 0.3048;
+const FEET_PER_MILE: f64 = 5280.0;
+const SQUARE_FEET_PER_ACRE: f64 = 43_560.0;
+const ACRES_PER_SQUARE_MILE: f64 = 640.0;
+
+/// Geometry type, dimension, then length, area and perimeter in meters.
+type MeasureRow = (
+    Option<String>,
+    Option<i32>,
+    Option<f64>,
+    Option<f64>,
+    Option<f64>,
+);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnitSystem {
+    #[default]
+    Metric,
+    Imperial,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct MeasureQuery {
+    pub units: Option<String>,
+}
+
+impl MeasureQuery {
+    pub fn units(&self) -> Result<UnitSystem, String> {
+        match self.units.as_deref().map(str::trim) {
+            None | Some("metric") => Ok(UnitSystem::Metric),
+            Some("imperial") => Ok(UnitSystem::Imperial),
+            Some(_) => Err("units must be metric or imperial".to_string()),
+        }
+    }
+}
+
+/// A length of `meters`, in m or km, or ft or mi.
+pub fn length(meters: f64, units: UnitSystem) -> Measurement {
+    let (value, unit) = match units {
+        UnitSystem::Metric if meters < 1000.0 => (meters, "m"),
+        UnitSystem::Metric => (meters / 1000.0, "km"),
+        UnitSystem::Imperial => {
+            let feet = meters / METERS_PER_FOOT;
+            if feet < FEET_PER_MILE {
+                (feet, "ft")
+            } else {
+                (feet / FEET_PER_MILE, "mi")
+            }
+        }
+    };
+    Measurement {
+        value,
+        unit,
+        base_value: meters,
+        base_unit: "m",
+    }
+}
+
+/// An area of `square_meters`, in m², ha or km², or ft², ac or mi².
+pub fn area(square_meters: f64, units: UnitSystem) -> Measurement {
+    let (value, unit) = match units {
+        UnitSystem::Metric if square_meters < 10_000.0 => (square_meters, "m²"),
+        UnitSystem::Metric if square_meters < 1_000_000.0 => (square_meters / 10_000.0, "ha"),
+        UnitSystem::Metric => (square_meters / 1_000_000.0, "km²"),
+        UnitSystem::Imperial => {
+            let square_feet = square_meters / (METERS_PER_FOOT * METERS_PER_FOOT);
+            let acres = square_feet / SQUARE_FEET_PER_ACRE;
+            if square_feet < SQUARE_FEET_PER_ACRE {
+                (square_feet, "ft²")
+            } else if acres < ACRES_PER_SQUARE_MILE {
+                (acres, "ac")
+            } else {
+                (acres / ACRES_PER_SQUARE_MILE, "mi²")
+            }
+        }
+    };
+    Measurement {
+        value,
+        unit,
+        base_value: square_meters,
+        base_unit: "m²",
+    }
+}
+
+/// Select a feature's geometry type and dimension, then its geodesic length,
+/// area and perimeter in meters. The spheroid functions take latitude first.
+fn measure_sql(table_name: &str, source_crs: &str) -> String {
+    format!(
+        "SELECT ST_GeometryType(geom)::VARCHAR, ST_Dimension(geom),
+                ST_Length_Spheroid(g), ST_Area_Spheroid(g), ST_Perimeter_Spheroid(g)
+         FROM (
+             SELECT geom,
+                 ST_FlipCoordinates(ST_Transform(geom, {}, 'EPSG:4326', always_xy := true)) AS g
+             FROM {} WHERE fid = ?
+         )",
+        quote_literal(source_crs),
+        quote_identifier(table_name)
+    )
+}
+
+pub async fn get_feature_measurements(
+    State(state): State<AppState>,
+    AxumPath((id, fid)): AxumPath<(String, i64)>,
+    Query(query): Query<MeasureQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let units = query.units().map_err(|e| bad_request(&e))?;
+    let not_found = |error: &str| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: error.to_string(),
+            }),
+        )
+    };
+
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    let ReadyFile {
+        table_name,
+        crs: source_crs,
+        ..
+    } = load_ready_file(
+        &conn,
+        &id,
+        "Measurements are not available for MBTiles or GeoTIFF files",
+    )?;
+
+    let (geometry_type, dimension, length_m, area_m2, perimeter_m): MeasureRow = conn
+        .query_row(
+            &measure_sql(&table_name, &source_crs),
+            duckdb::params![fid],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(internal_error)?
+        .ok_or_else(|| not_found("Feature not found"))?;
+
+    // Points measure nothing; lines have a length; polygons an area and perimeter.
+    let lines = dimension == Some(1);
+    let polygons = dimension == Some(2);
+    Ok(Json(FeatureMeasurements {
+        fid,
+        geometry_type,
+        length: length_m.filter(|_| lines).map(|m| length(m, units)),
+        area: area_m2.filter(|_| polygons).map(|m2| area(m2, units)),
+        perimeter: perimeter_m.filter(|_| polygons).map(|m| length(m, units)),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measurements_pick_a_unit_for_their_size() {
+        let metric = |m: Measurement| (m.value, m.unit);
+        assert_eq!(metric(length(250.0, UnitSystem::Metric)), (250.0, "m"));
+        assert_eq!(metric(length(1500.0, UnitSystem::Metric)), (1.5, "km"));
+        assert_eq!(metric(area(500.0, UnitSystem::Metric)), (500.0, "m²"));
+        assert_eq!(metric(area(25_000.0, UnitSystem::Metric)), (2.5, "ha"));
+        assert_eq!(metric(area(3_000_000.0, UnitSystem::Metric)), (3.0, "km²"));
+
+        let miles = length(16_093.44, UnitSystem::Imperial);
+        assert_eq!(miles.unit, "mi");
+        assert!((miles.value - 10.0).abs() < 1e-9);
+        assert_eq!(miles.base_value, 16_093.44);
+        assert_eq!(length(3.048, UnitSystem::Imperial).unit, "ft");
+        assert_eq!(area(4046.856_422_4 * 2.0, UnitSystem::Imperial).unit, "ac");
+
+        assert_eq!(MeasureQuery::default().units(), Ok(UnitSystem::Metric));
+        assert!(MeasureQuery {
+            units: Some("nautical".to_string())
+        }
+        .units()
+        .is_err());
+    }
+}
@@ -0,0 +1,154 @@
+//! Dataset metadata
+//!
+//! A file's description, attribution and license are set with
+//! `PUT /api/files/{id}/metadata` and carried into its TileJSON (private and
+//! published), its OGC API - Features collection and its published OGC API -
+//! Tiles tileset, so maps built on it can credit the upstream data. Tilesets
+//! list the distinct attributions of their sources.
+
+use axum::{
+    extract::{Path as AxumPath, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use duckdb::OptionalExt;
+
+use crate::http_errors::{bad_request, internal_error};
+use crate::models::{AppState, DatasetMetadata};
+use crate::ErrorResponse;
+
+const MAX_DESCRIPTION_LENGTH: usize = 4000;
+const MAX_ATTRIBUTION_LENGTH: usize = 1000;
+const MAX_LICENSE_LENGTH: usize = 200;
+
+/// Trim each field, dropping empty ones, and check their lengths.
+pub fn validate_metadata(metadata: DatasetMetadata) -> Result<DatasetMetadata, String> {
+    let field = |value: Option<String>, name: &str, max: usize| {
+        let value = value
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty());
+        match value {
+            Some(value) if value.chars().count() > max => {
+                Err(format!("{name} must be at most {max} characters"))
+            }
+            value => Ok(value),
+        }
+    };
+    Ok(DatasetMetadata {
+        description: field(metadata.description, "description", MAX_DESCRIPTION_LENGTH)?,
+        attribution: field(metadata.attribution, "attribution", MAX_ATTRIBUTION_LENGTH)?,
+        license: field(metadata.license, "license", MAX_LICENSE_LENGTH)?,
+    })
+}
+
+/// A file's metadata, or `None` when there is no such file.
+pub fn load_dataset_metadata(
+    conn: &duckdb::Connection,
+    id: &str,
+) -> Result<Option<DatasetMetadata>, duckdb::Error> {
+    conn.query_row(
+        "SELECT description, attribution, license FROM files WHERE id = ?",
+        duckdb::params![id],
+        |row| {
+            Ok(DatasetMetadata {
+                description: row.get(0)?,
+                attribution: row.get(1)?,
+                license: row.get(2)?,
+            })
+        },
+    )
+    .optional()
+}
+
+/// Distinct attributions of a tileset's sources, joined for its TileJSON.
+pub fn tileset_attribution(
+    conn: &duckdb::Connection,
+    tileset_id: &str,
+) -> Result<Option<String>, duckdb::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT f.attribution
+         FROM tileset_sources s JOIN files f ON f.id = s.file_id
+         WHERE s.tileset_id = ? AND f.attribution IS NOT NULL
+         GROUP BY f.attribution
+         ORDER BY MIN(s.ordinal)",
+    )?;
+    let attributions = stmt
+        .query_map(duckdb::params![tileset_id], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Some(attributions.join("; ")).filter(|joined| !joined.is_empty()))
+}
+
+fn file_not_found() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse {
+            error: "File not found".to_string(),
+        }),
+    )
+}
+
+pub async fn get_file_metadata(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    load_dataset_metadata(&conn, &id)
+        .map_err(internal_error)?
+        .map(Json)
+        .ok_or_else(file_not_found)
+}
+
+/// Replace a file's metadata; omitted or `null` fields are cleared.
+pub async fn set_file_metadata(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+    Json(req): Json<DatasetMetadata>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let metadata = validate_metadata(req).map_err(|e| bad_request(&e))?;
+
+    let conn = state.db.lock().await;
+    let updated = conn
+        .execute(
+            "UPDATE files SET description = ?, attribution = ?, license = ? WHERE id = ?",
+            duckdb::params![
+                &metadata.description,
+                &metadata.attribution,
+                &metadata.license,
+                &id
+            ],
+        )
+        .map_err(internal_error)?;
+    if updated == 0 {
+        return Err(file_not_found());
+    }
+    Ok(Json(metadata))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metadata_is_trimmed_and_bounded() {
+        let metadata = validate_metadata(DatasetMetadata {
+            description: Some("  Roads of the city ".to_string()),
+            attribution: Some(" ".to_string()),
+            license: Some("CC-BY-4.0".to_string()),
+        })
+        .unwrap();
+        assert_eq!(
+            metadata,
+            DatasetMetadata {
+                description: Some("Roads of the city".to_string()),
+                attribution: None,
+                license: Some("CC-BY-4.0".to_string()),
+            }
+        );
+        assert!(validate_metadata(DatasetMetadata {
+            license: Some("x".repeat(MAX_LICENSE_LENGTH + 1)),
+            ..Default::default()
+        })
+        .is_err());
+    }
+}
@@ -0,0 +1,809 @@
+//! Schema migrations
+//!
+//! The catalog schema is versioned in `schema_version`, one row per applied
+//! migration. `migrate` runs the migrations newer than the database, in order
+//! and each in its own transaction, and refuses a database written by a newer
+//! build. To change the schema, append a migration to `MIGRATIONS`; never edit
+//! one that has shipped.
+//!
+//! Version 1 is the schema as it stood when versioning was introduced. Older
+//! databases were upgraded piecemeal at startup and may lack any of its later
+//! columns, so it only creates what is missing.
+
+use std::fmt;
+
+use chrono::Utc;
+use duckdb::Connection;
+
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    up: fn(&Connection) -> Result<(), duckdb::Error>,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "baseline",
+        up: baseline,
+    },
+    Migration {
+        version: 2,
+        name: "failed upload retention",
+        up: file_retention,
+    },
+    Migration {
+        version: 3,
+        name: "webhooks",
+        up: webhooks,
+    },
+    Migration {
+        version: 4,
+        name: "raster value range",
+        up: raster_range,
+    },
+    Migration {
+        version: 5,
+        name: "file tags and folders",
+        up: file_tags,
+    },
+    Migration {
+        version: 6,
+        name: "dataset metadata",
+        up: dataset_metadata,
+    },
+    Migration {
+        version: 7,
+        name: "remote sources",
+        up: remote_sources,
+    },
+    Migration {
+        version: 8,
+        name: "shapefile encodings",
+        up: shapefile_encodings,
+    },
+    Migration {
+        version: 9,
+        name: "shapefile layers",
+        up: shapefile_layers,
+    },
+    Migration {
+        version: 10,
+        name: "antimeridian splitting",
+        up: antimeridian_splitting,
+    },
+    Migration {
+        version: 11,
+        name: "z values",
+        up: z_values,
+    },
+    Migration {
+        version: 12,
+        name: "slug history",
+        up: slug_history,
+    },
+    Migration {
+        version: 13,
+        name: "publish allowed origins",
+        up: publish_allowed_origins,
+    },
+    Migration {
+        version: 14,
+        name: "dataset versions",
+        up: dataset_versions,
+    },
+    Migration {
+        version: 15,
+        name: "export job owners",
+        up: export_job_owners,
+    },
+    Migration {
+        version: 16,
+        name: "publish zoom in tile options",
+        up: publish_zoom_in_tile_options,
+    },
+    Migration {
+        version: 17,
+        name: "failure times",
+        up: failure_times,
+    },
+    Migration {
+        version: 18,
+        name: "postgis exports",
+        up: postgis_exports,
+    },
+];
+
+/// Version of the newest migration this build knows.
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map_or(0, |migration| migration.version)
+}
+
+#[derive(Debug)]
+pub enum MigrationError {
+    /// The database was migrated by a newer build.
+    TooNew {
+        current: u32,
+        latest: u32,
+    },
+    Failed {
+        version: u32,
+        name: &'static str,
+        source: duckdb::Error,
+    },
+    Database(duckdb::Error),
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::TooNew { current, latest } => write!(
+                f,
+                "database schema version {current} is newer than this build supports ({latest}); upgrade MapFlow"
+            ),
+            MigrationError::Failed {
+                version,
+                name,
+                source,
+            } => write!(f, "migration {version} ({name}) failed: {source}"),
+            MigrationError::Database(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+impl From<duckdb::Error> for MigrationError {
+    fn from(e: duckdb::Error) -> Self {
+        MigrationError::Database(e)
+    }
+}
+
+/// Schema versions before and after `migrate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MigrationReport {
+    pub from: u32,
+    pub to: u32,
+}
+
+/// Highest applied migration; 0 for a new or unversioned database.
+pub fn schema_version(conn: &Connection) -> Result<u32, duckdb::Error> {
+    conn.execute_batch(
+        r"
+        CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            name VARCHAR NOT NULL,
+            applied_at TIMESTAMP NOT NULL
+        );
+        ",
+    )?;
+    conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM schema_version",
+        [],
+        |row| row.get(0),
+    )
+}
+
+/// Bring the schema up to `latest_version`.
+pub fn migrate(conn: &Connection) -> Result<MigrationReport, MigrationError> {
+    run_migrations(conn, MIGRATIONS)
+}
+
+fn run_migrations(
+    conn: &Connection,
+    migrations: &[Migration],
+) -> Result<MigrationReport, MigrationError> {
+    let from = schema_version(conn)?;
+    let latest = migrations.last().map_or(0, |migration| migration.version);
+    if from > latest {
+        return Err(MigrationError::TooNew {
+            current: from,
+            latest,
+        });
+    }
+
+    for migration in migrations
+        .iter()
+        .filter(|migration| migration.version > from)
+    {
+        conn.execute_batch("BEGIN TRANSACTION")?;
+        let applied = (migration.up)(conn).and_then(|()| {
+            conn.execute(
+                "INSERT INTO schema_version (version, name, applied_at) VALUES (?, ?, ?)",
+                duckdb::params![migration.version, migration.name, Utc::now().naive_utc()],
+            )
+        });
+        if let Err(source) = applied {
+            let _ = conn.execute_batch("ROLLBACK");
+            return Err(MigrationError::Failed {
+                version: migration.version,
+                name: migration.name,
+                source,
+            });
+        }
+        conn.execute_batch("COMMIT")?;
+        tracing::info!(
+            version = migration.version,
+            name = migration.name,
+            "Applied schema migration"
+        );
+    }
+    Ok(MigrationReport { from, to: latest })
+}
+
+fn column_exists(conn: &Connection, table: &str, column: &str) -> Result<bool, duckdb::Error> {
+    conn.query_row(
+        "SELECT COUNT(*) > 0 FROM information_schema.columns
+         WHERE table_schema = 'main' AND table_name = ? AND column_name = ?",
+        duckdb::params![table, column],
+        |row| row.get(0),
+    )
+}
+
+fn add_column(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<(), duckdb::Error> {
+    if !column_exists(conn, table, column)? {
+        conn.execute_batch(&format!(
+            "ALTER TABLE {table} ADD COLUMN {column} {definition}"
+        ))?;
+    }
+    Ok(())
+}
+
+fn baseline(conn: &Connection) -> Result<(), duckdb::Error> {
+    conn.execute_batch(
+        r"
+        CREATE TABLE IF NOT EXISTS files (
+            id VARCHAR PRIMARY KEY,
+            name VARCHAR NOT NULL,
+            type VARCHAR NOT NULL,
+            size BIGINT NOT NULL,
+            uploaded_at TIMESTAMP NOT NULL,
+            status VARCHAR NOT NULL,
+            crs VARCHAR,
+            path VARCHAR NOT NULL,
+            table_name VARCHAR,
+            error VARCHAR,
+            is_public BOOLEAN DEFAULT FALSE,
+            tile_format VARCHAR,
+            minzoom INTEGER,
+            maxzoom INTEGER,
+            tile_bounds VARCHAR,
+            tile_options VARCHAR,
+            data_version BIGINT DEFAULT 0,
+            owner_id VARCHAR,
+            org_id VARCHAR,
+            bbox VARCHAR,
+            feature_count BIGINT,
+            geometry_type VARCHAR
+        );
+
+        CREATE TABLE IF NOT EXISTS published_files (
+            file_id VARCHAR PRIMARY KEY,
+            slug VARCHAR UNIQUE NOT NULL,
+            published_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            tile_options VARCHAR,
+            signing_secret VARCHAR,
+            expires_at TIMESTAMP,
+            minzoom INTEGER,
+            maxzoom INTEGER,
+            FOREIGN KEY (file_id) REFERENCES files(id)
+        );
+        ",
+    )?;
+
+    // Columns added to existing tables before migrations were versioned.
+    add_column(conn, "files", "tile_format", "VARCHAR")?;
+    add_column(conn, "files", "minzoom", "INTEGER")?;
+    add_column(conn, "files", "maxzoom", "INTEGER")?;
+    add_column(conn, "files", "tile_bounds", "VARCHAR")?;
+    add_column(conn, "files", "tile_options", "VARCHAR")?;
+    add_column(conn, "files", "data_version", "BIGINT DEFAULT 0")?;
+    add_column(conn, "files", "owner_id", "VARCHAR")?;
+    add_column(conn, "files", "org_id", "VARCHAR")?;
+    // Extent, feature count and geometry type measured at import.
+    add_column(conn, "files", "bbox", "VARCHAR")?;
+    add_column(conn, "files", "feature_count", "BIGINT")?;
+    add_column(conn, "files", "geometry_type", "VARCHAR")?;
+    add_column(conn, "published_files", "tile_options", "VARCHAR")?;
+    add_column(conn, "published_files", "signing_secret", "VARCHAR")?;
+    add_column(conn, "published_files", "expires_at", "TIMESTAMP")?;
+    add_column(conn, "published_files", "minzoom", "INTEGER")?;
+    add_column(conn, "published_files", "maxzoom", "INTEGER")?;
+
+    conn.execute_batch(
+        r"
+        CREATE TABLE IF NOT EXISTS dataset_columns (
+            source_id VARCHAR NOT NULL,
+            normalized_name VARCHAR NOT NULL,
+            original_name VARCHAR NOT NULL,
+            ordinal BIGINT NOT NULL,
+            mvt_type VARCHAR NOT NULL,
+            PRIMARY KEY (source_id, normalized_name)
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_dataset_columns_source
+            ON dataset_columns(source_id);
+        ",
+    )?;
+
+    conn.execute_batch(
+        r"
+        CREATE TABLE IF NOT EXISTS export_jobs (
+            id VARCHAR PRIMARY KEY,
+            file_id VARCHAR NOT NULL,
+            format VARCHAR NOT NULL,
+            status VARCHAR NOT NULL,
+            path VARCHAR,
+            error VARCHAR,
+            created_at TIMESTAMP NOT NULL,
+            finished_at TIMESTAMP,
+            FOREIGN KEY (file_id) REFERENCES files(id)
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_export_jobs_file
+            ON export_jobs(file_id);
+        ",
+    )?;
+
+    conn.execute_batch(
+        r"
+        CREATE TABLE IF NOT EXISTS tile_seed_jobs (
+            id VARCHAR PRIMARY KEY,
+            file_id VARCHAR NOT NULL,
+            status VARCHAR NOT NULL,
+            bbox VARCHAR NOT NULL,
+            min_zoom INTEGER NOT NULL,
+            max_zoom INTEGER NOT NULL,
+            total_tiles BIGINT NOT NULL,
+            rendered_tiles BIGINT NOT NULL DEFAULT 0,
+            error VARCHAR,
+            created_at TIMESTAMP NOT NULL,
+            finished_at TIMESTAMP,
+            FOREIGN KEY (file_id) REFERENCES files(id)
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_tile_seed_jobs_file
+            ON tile_seed_jobs(file_id);
+        ",
+    )?;
+
+    conn.execute_batch(
+        r"
+        CREATE TABLE IF NOT EXISTS tilesets (
+            id VARCHAR PRIMARY KEY,
+            slug VARCHAR UNIQUE NOT NULL,
+            name VARCHAR NOT NULL,
+            created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            owner_id VARCHAR
+        );
+
+        -- No foreign key to tilesets: DuckDB refuses to delete a referenced row
+        -- in the same transaction that deletes its references.
+        CREATE TABLE IF NOT EXISTS tileset_sources (
+            tileset_id VARCHAR NOT NULL,
+            file_id VARCHAR NOT NULL,
+            ordinal INTEGER NOT NULL,
+            PRIMARY KEY (tileset_id, file_id),
+            FOREIGN KEY (file_id) REFERENCES files(id)
+        );
+        ",
+    )?;
+    add_column(conn, "tilesets", "owner_id", "VARCHAR")?;
+
+    conn.execute_batch(
+        r"
+        CREATE TABLE IF NOT EXISTS users (
+            id VARCHAR PRIMARY KEY,
+            username VARCHAR UNIQUE NOT NULL,
+            password_hash VARCHAR NOT NULL,
+            role VARCHAR NOT NULL,
+            created_at TIMESTAMP NOT NULL
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_users_username
+            ON users(username);
+        ",
+    )?;
+
+    conn.execute_batch(
+        r"
+        CREATE TABLE IF NOT EXISTS file_shares (
+            file_id VARCHAR NOT NULL,
+            user_id VARCHAR NOT NULL,
+            access VARCHAR NOT NULL,
+            created_at TIMESTAMP NOT NULL,
+            PRIMARY KEY (file_id, user_id),
+            FOREIGN KEY (file_id) REFERENCES files(id)
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_file_shares_user_id
+            ON file_shares(user_id);
+        ",
+    )?;
+
+    conn.execute_batch(
+        r"
+        CREATE TABLE IF NOT EXISTS orgs (
+            id VARCHAR PRIMARY KEY,
+            name VARCHAR UNIQUE NOT NULL,
+            created_at TIMESTAMP NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS org_members (
+            org_id VARCHAR NOT NULL,
+            user_id VARCHAR NOT NULL,
+            joined_at TIMESTAMP NOT NULL,
+            PRIMARY KEY (org_id, user_id)
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_org_members_user_id
+            ON org_members(user_id);
+        ",
+    )?;
+
+    conn.execute_batch(
+        r"
+        CREATE TABLE IF NOT EXISTS api_tokens (
+            id VARCHAR PRIMARY KEY,
+            user_id VARCHAR NOT NULL,
+            name VARCHAR NOT NULL,
+            token_hash VARCHAR UNIQUE NOT NULL,
+            prefix VARCHAR NOT NULL,
+            created_at TIMESTAMP NOT NULL,
+            last_used_at TIMESTAMP
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_api_tokens_user_id
+            ON api_tokens(user_id);
+        ",
+    )?;
+
+    conn.execute_batch(
+        r"
+        CREATE TABLE IF NOT EXISTS password_reset_tokens (
+            token_hash VARCHAR PRIMARY KEY,
+            user_id VARCHAR NOT NULL,
+            created_at TIMESTAMP NOT NULL,
+            expires_at TIMESTAMP NOT NULL,
+            used_at TIMESTAMP
+        );
+        ",
+    )?;
+
+    conn.execute_batch(
+        r"
+        CREATE TABLE IF NOT EXISTS sessions (
+            id VARCHAR PRIMARY KEY,
+            data VARCHAR NOT NULL,
+            expiry_date TIMESTAMP NOT NULL,
+            created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_sessions_expiry_date
+            ON sessions(expiry_date);
+        ",
+    )?;
+
+    conn.execute_batch(
+        r"
+        CREATE TABLE IF NOT EXISTS system_settings (
+            key VARCHAR PRIMARY KEY,
+            value VARCHAR NOT NULL
+        );
+        ",
+    )?;
+
+    Ok(())
+}
+
+/// Per-file overrides of the failed upload retention; see `retention.rs`.
+fn file_retention(conn: &Connection) -> Result<(), duckdb::Error> {
+    conn.execute_batch(
+        r"
+        CREATE TABLE file_retention (
+            file_id VARCHAR PRIMARY KEY,
+            retention_days BIGINT NOT NULL
+        );
+        ",
+    )
+}
+
+fn webhooks(conn: &Connection) -> Result<(), duckdb::Error> {
+    conn.execute_batch(
+        r"
+        CREATE TABLE webhooks (
+            id VARCHAR PRIMARY KEY,
+            url VARCHAR NOT NULL,
+            secret VARCHAR NOT NULL,
+            events VARCHAR NOT NULL,
+            created_at TIMESTAMP NOT NULL
+        );
+        ",
+    )
+}
+
+/// Value range single-band GeoTIFFs are drawn over; see `geotiff.rs`.
+fn raster_range(conn: &Connection) -> Result<(), duckdb::Error> {
+    add_column(conn, "files", "raster_range", "VARCHAR")
+}
+
+/// Tags and folders to organize files by; see `tags.rs`.
+fn file_tags(conn: &Connection) -> Result<(), duckdb::Error> {
+    add_column(conn, "files", "folder", "VARCHAR")?;
+    conn.execute_batch(
+        r"
+        CREATE TABLE file_tags (
+            file_id VARCHAR NOT NULL,
+            tag VARCHAR NOT NULL,
+            PRIMARY KEY (file_id, tag)
+        );
+        ",
+    )
+}
+
+/// Description, attribution and license; see `metadata.rs`.
+fn dataset_metadata(conn: &Connection) -> Result<(), duckdb::Error> {
+    add_column(conn, "files", "description", "VARCHAR")?;
+    add_column(conn, "files", "attribution", "VARCHAR")?;
+    add_column(conn, "files", "license", "VARCHAR")
+}
+
+/// URLs files were imported from, with the headers and validators to fetch
+/// them again; see `remote.rs`.
+fn remote_sources(conn: &Connection) -> Result<(), duckdb::Error> {
+    conn.execute_batch(
+        r"
+        CREATE TABLE remote_sources (
+            file_id VARCHAR PRIMARY KEY,
+            url VARCHAR NOT NULL,
+            headers VARCHAR NOT NULL,
+            file_name VARCHAR NOT NULL,
+            etag VARCHAR,
+            last_modified VARCHAR,
+            fetched_at TIMESTAMP
+        );
+        ",
+    )
+}
+
+/// Code pages shapefile uploads are read with; see `shapefile.rs`.
+fn shapefile_encodings(conn: &Connection) -> Result<(), duckdb::Error> {
+    add_column(conn, "files", "encoding", "VARCHAR")
+}
+
+/// The layer of a multi-layer upload a file imports; see `layers.rs`.
+fn shapefile_layers(conn: &Connection) -> Result<(), duckdb::Error> {
+    add_column(conn, "files", "layer", "VARCHAR")
+}
+
+/// Whether imports split geometries at the antimeridian; see `antimeridian.rs`.
+fn antimeridian_splitting(conn: &Connection) -> Result<(), duckdb::Error> {
+    add_column(conn, "files", "split_antimeridian", "BOOLEAN")
+}
+
+/// Whether imports keep elevation from Z values; see `zm.rs`.
+fn z_values(conn: &Connection) -> Result<(), duckdb::Error> {
+    add_column(conn, "files", "z_values", "VARCHAR")
+}
+
+/// Slugs published datasets gave up, to redirect from; see `slug_history.rs`.
+fn slug_history(conn: &Connection) -> Result<(), duckdb::Error> {
+    conn.execute_batch(
+        r"
+        CREATE TABLE slug_history (
+            slug VARCHAR PRIMARY KEY,
+            file_id VARCHAR NOT NULL,
+            replaced_at TIMESTAMP NOT NULL
+        );
+        ",
+    )
+}
+
+/// Origins a publish's tiles are limited to; see `public_cors.rs`.
+fn publish_allowed_origins(conn: &Connection) -> Result<(), duckdb::Error> {
+    add_column(conn, "published_files", "allowed_origins", "VARCHAR")
+}
+
+/// Tables kept when a dataset is imported again, and the version a publish
+/// pins; see `versions.rs`.
+fn dataset_versions(conn: &Connection) -> Result<(), duckdb::Error> {
+    conn.execute_batch(
+        r"
+        CREATE TABLE dataset_versions (
+            file_id VARCHAR NOT NULL,
+            version INTEGER NOT NULL,
+            table_name VARCHAR NOT NULL,
+            crs VARCHAR,
+            feature_count BIGINT,
+            created_at TIMESTAMP NOT NULL,
+            replaced_at TIMESTAMP NOT NULL,
+            PRIMARY KEY (file_id, version)
+        );
+        ",
+    )?;
+    add_column(conn, "published_files", "version", "INTEGER")
+}
+
+fn export_job_owners(conn: &Connection) -> Result<(), duckdb::Error> {
+    add_column(conn, "export_jobs", "created_by", "VARCHAR")
+}
+
+/// A publish's zoom range moves into its `tile_options` overrides. DuckDB
+/// cannot drop columns from a table with constraints, so the table is rebuilt.
+fn publish_zoom_in_tile_options(conn: &Connection) -> Result<(), duckdb::Error> {
+    conn.execute_batch(
+        r"
+        UPDATE published_files
+        SET tile_options = CAST(json_merge_patch(
+            COALESCE(tile_options, '{}'),
+            json_object('minZoom', minzoom, 'maxZoom', maxzoom)
+        ) AS VARCHAR)
+        WHERE minzoom IS NOT NULL OR maxzoom IS NOT NULL;
+
+        CREATE TEMP TABLE published_files_old AS
+        SELECT file_id, slug, published_at, tile_options, signing_secret, expires_at,
+               allowed_origins, version
+        FROM published_files;
+        DROP TABLE published_files;
+
+        CREATE TABLE published_files (
+            file_id VARCHAR PRIMARY KEY,
+            slug VARCHAR UNIQUE NOT NULL,
+            published_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            tile_options VARCHAR,
+            signing_secret VARCHAR,
+            expires_at TIMESTAMP,
+            allowed_origins VARCHAR,
+            version INTEGER,
+            FOREIGN KEY (file_id) REFERENCES files(id)
+        );
+        INSERT INTO published_files
+        SELECT file_id, slug, published_at, tile_options, signing_secret, expires_at,
+               allowed_origins, version
+        FROM published_files_old;
+        DROP TABLE published_files_old;
+        ",
+    )
+}
+
+/// When a file failed, which failed-upload retention counts from, and the
+/// last failed refresh of a remote file, which leaves the file as it was.
+fn failure_times(conn: &Connection) -> Result<(), duckdb::Error> {
+    add_column(conn, "files", "failed_at", "TIMESTAMP")?;
+    add_column(conn, "remote_sources", "last_refresh_error", "VARCHAR")?;
+    add_column(
+        conn,
+        "remote_sources",
+        "last_refresh_failed_at",
+        "TIMESTAMP",
+    )
+}
+
+/// PostGIS tables exports created, the only ones an export may overwrite; see
+/// `postgis.rs`.
+fn postgis_exports(conn: &Connection) -> Result<(), duckdb::Error> {
+    conn.execute_batch(
+        r"
+        CREATE TABLE postgis_exports (
+            schema_name VARCHAR NOT NULL,
+            table_name VARCHAR NOT NULL,
+            file_id VARCHAR NOT NULL,
+            exported_at TIMESTAMP NOT NULL,
+            PRIMARY KEY (schema_name, table_name)
+        );
+        ",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table_exists(conn: &Connection, table: &str) -> bool {
+        conn.query_row(
+            "SELECT COUNT(*) > 0 FROM information_schema.tables WHERE table_name = ?",
+            duckdb::params![table],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn versions_increase_from_one() {
+        let versions: Vec<u32> = MIGRATIONS.iter().map(|m| m.version).collect();
+        let expected: Vec<u32> = (1..=latest_version()).collect();
+        assert_eq!(versions, expected);
+    }
+
+    #[test]
+    fn pending_migrations_run_once() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert_eq!(
+            migrate(&conn).unwrap(),
+            MigrationReport {
+                from: 0,
+                to: latest_version()
+            }
+        );
+        assert!(table_exists(&conn, "files"));
+        assert_eq!(
+            migrate(&conn).unwrap(),
+            MigrationReport {
+                from: latest_version(),
+                to: latest_version()
+            }
+        );
+    }
+
+    #[test]
+    fn failed_migration_rolls_back() {
+        const STEPS: &[Migration] = &[
+            Migration {
+                version: 1,
+                name: "create a",
+                up: |conn| conn.execute_batch("CREATE TABLE a (x INTEGER)"),
+            },
+            Migration {
+                version: 2,
+                name: "broken",
+                up: |conn| {
+                    conn.execute_batch("CREATE TABLE b (x INTEGER)")?;
+                    conn.execute_batch("SELECT * FROM missing")
+                },
+            },
+        ];
+        let conn = Connection::open_in_memory().unwrap();
+        let error = run_migrations(&conn, STEPS).unwrap_err();
+        assert!(matches!(error, MigrationError::Failed { version: 2, .. }));
+        assert_eq!(schema_version(&conn).unwrap(), 1);
+        assert!(table_exists(&conn, "a"));
+        assert!(!table_exists(&conn, "b"));
+    }
+
+    #[test]
+    fn publish_zoom_moves_into_tile_options() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn, &MIGRATIONS[..15]).unwrap();
+        conn.execute_batch(
+            r#"
+            INSERT INTO files (id, name, type, size, uploaded_at, status, path)
+            VALUES ('a', 'a.geojson', 'geojson', 1, NOW(), 'ready', 'a'),
+                   ('b', 'b.geojson', 'geojson', 1, NOW(), 'ready', 'b');
+            INSERT INTO published_files (file_id, slug, tile_options, minzoom, maxzoom)
+            VALUES ('a', 'a', '{"extent":512}', 2, NULL), ('b', 'b', NULL, NULL, NULL);
+            "#,
+        )
+        .unwrap();
+
+        migrate(&conn).unwrap();
+        let overrides: Vec<Option<String>> = conn
+            .prepare("SELECT tile_options FROM published_files ORDER BY file_id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            overrides,
+            [Some(r#"{"extent":512,"minZoom":2}"#.to_string()), None]
+        );
+        assert!(!column_exists(&conn, "published_files", "minzoom").unwrap());
+    }
+
+    #[test]
+    fn newer_databases_are_refused() {
+        let conn = Connection::open_in_memory().unwrap();
+        migrate(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO schema_version (version, name, applied_at) VALUES (?, 'future', NOW())",
+            duckdb::params![latest_version() + 1],
+        )
+        .unwrap();
+        assert!(matches!(migrate(&conn), Err(MigrationError::TooNew { .. })));
+    }
+}
@@ -3,7 +3,12 @@ use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
-use crate::{AuthBackend, DuckDBStore};
+use crate::auth::Role;
+use crate::authz::FileAccess;
+use crate::config::format_bytes;
+use crate::password_reset::ResetDelivery;
+use crate::scan::UploadScanner;
+use crate::{AuthBackend, DuckDBStore, FileEvents, ReadPool};
 
 #[derive(Clone)]
 pub struct AppState {
@@ -13,9 +18,52 @@ pub struct AppState {
     pub max_size_label: String,
     pub auth_backend: AuthBackend,
     pub session_store: DuckDBStore,
+    /// Extra connections for handlers that only read; see `read_pool.rs`.
+    pub read_pool: ReadPool,
+    /// Checks uploads before they are imported; see `scan.rs`.
+    pub upload_scanner: Option<UploadScanner>,
+    /// PostGIS database datasets are exported to; see `postgis.rs`.
+    pub postgis_export_connection: Option<String>,
+    /// Schemas PostGIS exports may write to.
+    pub postgis_export_schemas: Vec<String>,
+    /// Default base URL of generated public links; see `settings.rs`.
+    pub public_base_url: Option<String>,
+    /// Whether `Host` and `X-Forwarded-*` may set public links; see `lib.rs`.
+    pub trust_forwarded_headers: bool,
+    /// Whether remote and PostGIS imports may reach private addresses; see
+    /// `remote.rs`.
+    pub remote_allow_private_hosts: bool,
+    /// Delivers self-service password reset tokens; see `password_reset.rs`.
+    pub password_reset_delivery: Option<ResetDelivery>,
+    /// Wakes `/api/files/events` streams; see `file_events.rs`.
+    pub file_events: FileEvents,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+impl AppState {
+    /// State over `db` with an upload limit of `max_size` and none of the
+    /// optional services; override fields with struct update syntax.
+    pub fn new(upload_dir: PathBuf, db: Arc<Mutex<duckdb::Connection>>, max_size: u64) -> Self {
+        AppState {
+            upload_dir,
+            db: db.clone(),
+            max_size,
+            max_size_label: format_bytes(max_size),
+            auth_backend: AuthBackend::new(db.clone()),
+            session_store: DuckDBStore::new(db.clone()),
+            read_pool: ReadPool::new(db),
+            upload_scanner: None,
+            postgis_export_connection: None,
+            postgis_export_schemas: vec!["public".to_string()],
+            public_base_url: None,
+            trust_forwarded_headers: false,
+            remote_allow_private_hosts: false,
+            password_reset_delivery: None,
+            file_events: FileEvents::default(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct FileItem {
     pub id: String,
     pub name: String,
@@ -37,6 +85,21 @@ pub struct FileItem {
     #[serde(rename = "publicSlug")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub public_slug: Option<String>,
+    #[serde(rename = "orgId")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub org_id: Option<String>,
+    /// `Point`, `LineString`, `Polygon` or `Geometry` (mixed), counted at import.
+    #[serde(rename = "geometryType")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub geometry_type: Option<String>,
+    #[serde(rename = "featureCount")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feature_count: Option<i64>,
+    /// Slash-separated folder path; see `tags.rs`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub folder: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -56,6 +119,21 @@ pub struct PreviewMeta {
     pub minzoom: Option<i32>, // MBTiles: valid zoom range (min), null for dynamic tables
     #[serde(rename = "maxZoom", skip_serializing_if = "Option::is_none")]
     pub maxzoom: Option<i32>, // MBTiles: valid zoom range (max), null for dynamic tables
+    /// Bumped whenever the dataset's tiles change; append as `?v=` to tile URLs.
+    #[serde(rename = "dataVersion")]
+    pub data_version: i64,
+    /// MVT layer the dataset's tiles are encoded in; absent for MBTiles.
+    #[serde(rename = "layerName", skip_serializing_if = "Option::is_none")]
+    pub layer_name: Option<String>,
+    /// As in `FileItem`; absent for MBTiles and GeoTIFF files.
+    #[serde(rename = "geometryType", skip_serializing_if = "Option::is_none")]
+    pub geometry_type: Option<String>,
+    #[serde(rename = "featureCount", skip_serializing_if = "Option::is_none")]
+    pub feature_count: Option<i64>,
+    /// Whether the import dropped Z values or kept them as elevation
+    /// properties: `drop` or `elevation`; see `zm.rs`.
+    #[serde(rename = "zValues", skip_serializing_if = "Option::is_none")]
+    pub z_values: Option<String>,
 }
 
 #[allow(dead_code)]
@@ -72,12 +150,283 @@ pub struct FeaturePropertiesResponse {
     pub properties: Vec<FeatureProperty>,
 }
 
+/// `GET /api/files/:id/features/:fid/measure`; see `measure.rs`.
+#[derive(Debug, Serialize)]
+pub struct FeatureMeasurements {
+    pub fid: i64,
+    #[serde(rename = "geometryType")]
+    pub geometry_type: Option<String>,
+    /// Lines only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub length: Option<Measurement>,
+    /// Polygons only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub area: Option<Measurement>,
+    /// Polygons only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub perimeter: Option<Measurement>,
+}
+
+/// `GET /api/files/:id/tiles/:z/:x/:y/inspect`; see `tile_debug.rs`.
+#[derive(Debug, Serialize)]
+pub struct TileInspection {
+    pub z: i32,
+    pub x: i32,
+    pub y: i32,
+    /// Size of the decoded tile, before any gzip.
+    pub bytes: u64,
+    pub layers: Vec<TileLayerInspection>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TileLayerInspection {
+    pub name: String,
+    pub extent: u32,
+    #[serde(rename = "featureCount")]
+    pub feature_count: u64,
+    #[serde(rename = "propertyKeys")]
+    pub property_keys: Vec<String>,
+    /// Number of features of each MVT geometry type: `Point`, `LineString`,
+    /// `Polygon` or `Unknown`.
+    #[serde(rename = "geometryTypes")]
+    pub geometry_types: std::collections::BTreeMap<String, u64>,
+}
+
+/// A geodesic measurement in a unit suited to its size, and in meters or
+/// square meters.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Measurement {
+    pub value: f64,
+    pub unit: &'static str,
+    #[serde(rename = "baseValue")]
+    pub base_value: f64,
+    #[serde(rename = "baseUnit")]
+    pub base_unit: &'static str,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FeatureRow {
+    pub fid: i64,
+    pub properties: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FeatureListResponse {
+    pub total: u64,
+    pub limit: u32,
+    pub offset: u64,
+    pub fields: Vec<FieldInfo>,
+    pub rows: Vec<FeatureRow>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GeoJsonFeature {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub id: i64,
+    pub geometry: serde_json::Value,
+    pub properties: serde_json::Map<String, serde_json::Value>,
+}
+
+/// GeoJSON variant of `FeatureListResponse`; paging fields are foreign members.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GeoJsonFeatureCollection {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub total: u64,
+    pub limit: u32,
+    pub offset: u64,
+    pub features: Vec<GeoJsonFeature>,
+}
+
+/// Features under a map click (`GET /api/files/:id/identify`), nearest first.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IdentifyResponse {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub features: Vec<GeoJsonFeature>,
+}
+
+/// Link of the OGC API responses; see `ogc.rs` and `ogc_tiles.rs`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OgcLink {
+    pub href: String,
+    pub rel: String,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// `href` is a URL template, such as a tile's.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub templated: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OgcCollections {
+    pub links: Vec<OgcLink>,
+    pub collections: Vec<OgcCollection>,
+}
+
+/// A dataset as an OGC API - Features collection.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OgcCollection {
+    pub id: String,
+    pub title: String,
+    #[serde(rename = "itemType")]
+    pub item_type: String,
+    pub crs: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attribution: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extent: Option<OgcExtent>,
+    pub links: Vec<OgcLink>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OgcExtent {
+    pub spatial: OgcSpatialExtent,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OgcSpatialExtent {
+    /// One `[minx, miny, maxx, maxy]` in CRS84.
+    pub bbox: Vec<[f64; 4]>,
+    pub crs: String,
+}
+
+/// A page of `/ogc/collections/{id}/items`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OgcFeatureCollection {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub features: Vec<GeoJsonFeature>,
+    #[serde(rename = "numberMatched")]
+    pub number_matched: u64,
+    #[serde(rename = "numberReturned")]
+    pub number_returned: u64,
+    #[serde(rename = "timeStamp")]
+    pub time_stamp: String,
+    pub links: Vec<OgcLink>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OgcFeature {
+    #[serde(flatten)]
+    pub feature: GeoJsonFeature,
+    pub links: Vec<OgcLink>,
+}
+
+/// Tilesets of a published slug (`GET /tiles/:slug/ogc/tiles`).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OgcTileSets {
+    pub links: Vec<OgcLink>,
+    pub tilesets: Vec<OgcTileSet>,
+}
+
+/// OGC API - Tiles tileset metadata. The tileset list carries the same
+/// document without `layers`, `boundingBox` and `tileMatrixSetLimits`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OgcTileSet {
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attribution: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    /// `vector` or `map`.
+    pub data_type: String,
+    pub crs: String,
+    #[serde(rename = "tileMatrixSetURI")]
+    pub tile_matrix_set_uri: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tile_matrix_set_limits: Option<Vec<OgcTileMatrixLimits>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bounding_box: Option<OgcBoundingBox>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layers: Option<Vec<OgcTileLayer>>,
+    pub links: Vec<OgcLink>,
+}
+
+/// Tiles that hold data at one zoom level.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OgcTileMatrixLimits {
+    pub tile_matrix: String,
+    pub min_tile_row: u32,
+    pub max_tile_row: u32,
+    pub min_tile_col: u32,
+    pub max_tile_col: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OgcBoundingBox {
+    pub lower_left: [f64; 2],
+    pub upper_right: [f64; 2],
+    pub crs: String,
+}
+
+/// A vector layer of a tileset; `propertiesSchema` is a JSON Schema of its
+/// feature properties.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OgcTileLayer {
+    pub id: String,
+    pub data_type: String,
+    pub properties_schema: serde_json::Value,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FieldInfo {
     pub name: String,
     pub r#type: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ValueCount {
+    pub value: serde_json::Value,
+    pub count: u64,
+}
+
+/// Column statistics (`GET /api/files/:id/fields/:name/stats`). Numeric columns
+/// carry min/max/mean, text and boolean columns distinctCount/topValues.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldStatsResponse {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub field_type: String,
+    pub count: u64,
+    pub null_count: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mean: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distinct_count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_values: Option<Vec<ValueCount>>,
+}
+
+/// Distinct values of a column (`GET /api/files/:id/fields/:name/values`).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FieldValuesResponse {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub field_type: String,
+    pub values: Vec<ValueCount>,
+    /// More distinct values exist than `limit`.
+    pub truncated: bool,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LayerInfo {
     pub id: String,
@@ -86,14 +435,385 @@ pub struct LayerInfo {
     pub fields: Vec<FieldInfo>,
 }
 
+/// TileJSON 3.0.0 document for a dataset's tiles.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TileJson {
+    pub tilejson: String,
+    pub name: String,
+    pub tiles: Vec<String>,
+    pub minzoom: u8,
+    pub maxzoom: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bounds: Option<[f64; 4]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// May hold HTML links, as in TileJSON.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attribution: Option<String>,
+    /// Not part of TileJSON 3.0.0; carried like other extension keys.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    /// Absent for raster tiles.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vector_layers: Option<Vec<VectorLayer>>,
+}
+
+/// What a frontend needs to show a public slug (`GET /tiles/:slug/meta`).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PublicTileMeta {
+    pub slug: String,
+    pub name: String,
+    /// `vector` or `raster`: the kind of map source `tile_url` feeds.
+    pub tile_source: String,
+    pub tile_url: String,
+    pub viewer_url: String,
+    pub minzoom: u8,
+    pub maxzoom: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bounds: Option<[f64; 4]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attribution: Option<String>,
+    /// The `source-layer` of a single-layer slug.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layer_name: Option<String>,
+    /// Layer names and field schemas, as in TileJSON; empty for raster tiles.
+    pub vector_layers: Vec<VectorLayer>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VectorLayer {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Property name to `Number`, `Boolean` or `String`.
+    pub fields: std::collections::BTreeMap<String, String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FileSchemaResponse {
     pub layers: Vec<LayerInfo>,
+    /// Whether the dataset table has an R-tree on `geom`; absent for MBTiles.
+    #[serde(rename = "spatialIndex")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spatial_index: Option<bool>,
+}
+
+/// Who can read a published dataset's tiles.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PublishAccess {
+    /// Anyone with the URL.
+    #[default]
+    Public,
+    /// Only URLs signed with the publish's signing secret.
+    Signed,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct PublishRequest {
     pub slug: Option<String>,
+    #[serde(default)]
+    pub access: PublishAccess,
+    /// RFC 3339 time after which the public link stops serving.
+    #[serde(default, rename = "expiresAt")]
+    pub expires_at: Option<String>,
+    /// Zoom range served under the slug, within the dataset's own range.
+    #[serde(default)]
+    pub minzoom: Option<u8>,
+    #[serde(default)]
+    pub maxzoom: Option<u8>,
+    /// Encoding overrides (extent, buffer, clip) for this publish's tiles.
+    #[serde(default, rename = "tileOptions")]
+    pub tile_options: Option<TileOptions>,
+    /// Properties the public tiles carry, by original name; stored as the
+    /// `fields` of the publish's tile options.
+    #[serde(default, rename = "includeFields")]
+    pub include_fields: Option<Vec<String>>,
+    /// Public property keys by column, e.g. `{"spd_lmt": "speed_limit"}`;
+    /// stored as the `fieldAliases` of the publish's tile options.
+    #[serde(default, rename = "fieldAliases")]
+    pub field_aliases: Option<std::collections::BTreeMap<String, String>>,
+    /// Decimal places of longitude and latitude kept in tiles and GeoJSON;
+    /// full precision when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub precision: Option<u8>,
+    /// Origins allowed to fetch the public tiles; any origin when unset.
+    #[serde(default, rename = "allowedOrigins")]
+    pub allowed_origins: Option<Vec<String>>,
+    /// Dataset version the public tiles serve; the current data when unset.
+    #[serde(default)]
+    pub version: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TilesetRequest {
+    pub slug: String,
+    /// Defaults to the slug.
+    pub name: Option<String>,
+    /// Dataset ids, in layer order.
+    pub files: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TilesetResponse {
+    pub id: String,
+    pub name: String,
+    pub slug: String,
+    pub url: String,
+    pub files: Vec<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserItem {
+    pub id: String,
+    pub username: String,
+    pub role: Role,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateUserRequest {
+    pub username: String,
+    pub password: String,
+    pub role: Role,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateUserRequest {
+    pub role: Role,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileShare {
+    pub username: String,
+    pub access: FileAccess,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+}
+
+/// One version of a dataset's data; see `versions.rs`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DatasetVersion {
+    pub version: i32,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    /// When a later import replaced it; unset for the current version.
+    #[serde(rename = "replacedAt", skip_serializing_if = "Option::is_none")]
+    pub replaced_at: Option<String>,
+    #[serde(rename = "featureCount")]
+    pub feature_count: Option<i64>,
+    pub current: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ShareRequest {
+    pub username: String,
+    /// `read` or `edit`; ownership cannot be shared.
+    pub access: FileAccess,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OrgItem {
+    pub id: String,
+    pub name: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    #[serde(rename = "memberCount")]
+    pub member_count: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateOrgRequest {
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OrgMember {
+    pub username: String,
+    #[serde(rename = "joinedAt")]
+    pub joined_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OrgMemberRequest {
+    pub username: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PasswordResetRequest {
+    pub username: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasswordResetResponse {
+    pub message: String,
+    /// Only returned when an admin requests the reset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    #[serde(rename = "expiresAt", skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RegisterRequest {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiTokenItem {
+    pub id: String,
+    pub name: String,
+    /// Leading characters of the key, to tell keys apart.
+    pub prefix: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    #[serde(rename = "lastUsedAt")]
+    pub last_used_at: Option<String>,
+    /// The key itself, returned once when it is created.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiTokenRequest {
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebhookItem {
+    pub id: String,
+    pub url: String,
+    /// Event names the hook is called for.
+    pub events: Vec<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    /// Signing secret, returned once when the hook is created.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    /// Defaults to every event.
+    pub events: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FileOrgRequest {
+    #[serde(rename = "orgId")]
+    pub org_id: Option<String>,
+}
+
+/// `GET /api/files` filters.
+#[derive(Debug, Default, Deserialize)]
+pub struct FileListQuery {
+    pub tag: Option<String>,
+    /// Also matches the folder's subfolders.
+    pub folder: Option<String>,
+}
+
+/// `PUT /api/files/{id}/tags` body and response.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileTags {
+    pub tags: Vec<String>,
+}
+
+/// `PUT /api/files/{id}/folder` body and response; `null` clears it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileFolder {
+    pub folder: Option<String>,
+}
+
+/// `GET /health`; see `health.rs`.
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthResponse {
+    /// `ok` when every check passed, otherwise `error`.
+    pub status: String,
+    pub checks: std::collections::BTreeMap<String, CheckStatus>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckStatus {
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Runtime-adjustable settings; see `settings.rs`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Settings {
+    /// `max-age` of public tiles, TileJSON, styles and viewers.
+    #[serde(rename = "cacheTtlSecs")]
+    pub cache_ttl_secs: u64,
+    #[serde(rename = "uploadMaxSizeBytes")]
+    pub upload_max_size_bytes: u64,
+    /// Origin used in public URLs instead of the request's Host header.
+    #[serde(rename = "publicBaseUrl", default)]
+    pub public_base_url: Option<String>,
+    /// Whether `POST /api/auth/register` creates viewer accounts.
+    #[serde(rename = "registrationEnabled")]
+    pub registration_enabled: bool,
+    /// Days before failed uploads are purged; 0 keeps them.
+    #[serde(
+        rename = "failedUploadRetentionDays",
+        default = "default_failed_upload_retention_days"
+    )]
+    pub failed_upload_retention_days: u64,
+    /// Retained versions kept per dataset, besides pinned ones; 0 keeps all.
+    #[serde(
+        rename = "datasetVersionRetention",
+        default = "default_dataset_version_retention"
+    )]
+    pub dataset_version_retention: u64,
+}
+
+fn default_failed_upload_retention_days() -> u64 {
+    crate::retention::DEFAULT_FAILED_UPLOAD_RETENTION_DAYS
+}
+
+fn default_dataset_version_retention() -> u64 {
+    crate::versions::DEFAULT_DATASET_VERSION_RETENTION
+}
+
+/// How long a failed file is kept; see `retention.rs`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileRetention {
+    /// This file's override, if any.
+    pub retention_days: Option<u64>,
+    pub effective_retention_days: u64,
+    /// When the file will be purged; only set for failed files.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub purge_at: Option<String>,
+}
+
+/// `GET`/`PUT /api/files/{id}/metadata`; see `metadata.rs`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DatasetMetadata {
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub attribution: Option<String>,
+    #[serde(default)]
+    pub license: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileRetentionRequest {
+    /// `null` falls back to the server setting.
+    pub retention_days: Option<u64>,
 }
 
 #[derive(Debug, Serialize)]
@@ -101,6 +821,36 @@ pub struct PublishResponse {
     pub url: String,
     pub slug: String,
     pub is_public: bool,
+    pub access: PublishAccess,
+    /// Returned once, when a signed publish is created.
+    #[serde(rename = "signingSecret", skip_serializing_if = "Option::is_none")]
+    pub signing_secret: Option<String>,
+    #[serde(rename = "expiresAt", skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub minzoom: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub maxzoom: Option<u8>,
+    #[serde(rename = "tileOptions", skip_serializing_if = "Option::is_none")]
+    pub tile_options: Option<TileOptions>,
+    #[serde(rename = "allowedOrigins", skip_serializing_if = "Option::is_none")]
+    pub allowed_origins: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<i32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SignedUrlRequest {
+    /// Seconds until the URL expires.
+    #[serde(rename = "expiresIn")]
+    pub expires_in: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignedUrlResponse {
+    pub url: String,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: String,
 }
 
 #[derive(Debug, Serialize)]
@@ -108,3 +858,333 @@ pub struct PublicTileUrl {
     pub slug: String,
     pub url: String,
 }
+
+/// `PUT /api/files/:id/slug`; see `slug_history.rs`.
+#[derive(Debug, Deserialize)]
+pub struct SlugRequest {
+    pub slug: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExportJob {
+    pub id: String,
+    #[serde(rename = "fileId")]
+    pub file_id: String,
+    pub format: String,
+    pub status: String, // pending -> processing -> ready/failed
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    #[serde(rename = "finishedAt", skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(rename = "downloadUrl", skip_serializing_if = "Option::is_none")]
+    pub download_url: Option<String>,
+}
+
+/// `POST /api/files/:id/seed`; see `tile_seed.rs`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct TileSeedRequest {
+    /// WGS84 `[west, south, east, north]`; the dataset's extent when unset.
+    #[serde(default)]
+    pub bbox: Option<[f64; 4]>,
+    #[serde(default)]
+    pub min_zoom: u8,
+    pub max_zoom: u8,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TileSeedJob {
+    pub id: String,
+    pub file_id: String,
+    pub status: String, // pending -> processing -> ready/failed
+    pub bbox: [f64; 4],
+    pub min_zoom: u8,
+    pub max_zoom: u8,
+    pub total_tiles: u64,
+    pub rendered_tiles: u64,
+    pub created_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A backup archive written by `POST /api/admin/backup`; see `backup.rs`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupInfo {
+    pub name: String,
+    pub size: u64,
+    pub created_at: String,
+    pub download_url: String,
+}
+
+/// Disk usage reported by `GET /api/admin/storage`; see `storage.rs`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageStats {
+    pub database_bytes: u64,
+    pub wal_bytes: u64,
+    /// The upload directory without the tile cache and backups.
+    pub uploads_bytes: u64,
+    pub tile_cache_bytes: u64,
+    pub backups_bytes: u64,
+    /// Largest first.
+    pub datasets: Vec<DatasetStorage>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DatasetStorage {
+    pub id: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub table_name: Option<String>,
+    pub row_count: u64,
+    /// Estimated from the table's storage blocks.
+    pub table_bytes: u64,
+    pub upload_bytes: u64,
+    pub tile_cache_bytes: u64,
+}
+
+/// Per-dataset tile generation settings, stored as JSON in `files.tile_options`.
+/// Unset fields fall back to the server defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct TileOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layer_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extent: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub buffer: Option<u32>,
+    /// Clip geometries to the tile (plus buffer); on by default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clip: Option<bool>,
+    /// Simplification tolerance in tile pixels.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub simplify: Option<f64>,
+    /// Original names of the properties to include; all when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fields: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_zoom: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_zoom: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feature_limit: Option<u32>,
+    /// Which features a tile keeps when `feature_limit` is exceeded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feature_limit_strategy: Option<FeatureLimitStrategy>,
+    /// `<field>` or `-<field>` for the `sort` strategy.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feature_limit_sort: Option<String>,
+    /// Point datasets only: up to this zoom, tiles hold one cluster per grid
+    /// cell instead of the points; unset disables clustering.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cluster_max_zoom: Option<u8>,
+    /// Side of a cluster cell in tile pixels.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cluster_radius: Option<u32>,
+    /// Property keys used in tiles instead of the original names, by column.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field_aliases: Option<std::collections::BTreeMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeatureLimitStrategy {
+    /// Lowest fids first.
+    #[default]
+    Fid,
+    /// A pseudo-random sample that is stable across requests.
+    Random,
+    /// Highest-ranked by `feature_limit_sort`.
+    Sort,
+    /// At most one feature per grid cell, spreading features across the tile.
+    Grid,
+}
+
+impl FeatureLimitStrategy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FeatureLimitStrategy::Fid => "fid",
+            FeatureLimitStrategy::Random => "random",
+            FeatureLimitStrategy::Sort => "sort",
+            FeatureLimitStrategy::Grid => "grid",
+        }
+    }
+}
+
+/// Result of an attribute-only update (`POST /api/files/:id/attributes`).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AttributeUpdateResponse {
+    pub updated: u64,
+    pub unmatched: u64,
+    pub columns: Vec<String>,
+}
+
+/// Result of a read-only SQL query (`POST /api/files/:id/query`).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DatasetQueryResponse {
+    pub columns: Vec<String>,
+    pub rows: Vec<serde_json::Map<String, serde_json::Value>>,
+    /// More rows matched than `limit`.
+    pub truncated: bool,
+}
+
+/// Body of `PATCH /api/files/:id/features/:fid`: property values keyed by
+/// original column name; `null` clears a value.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FeatureEditRequest {
+    pub properties: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Result of `POST /api/uploads?mode=append&target=:id`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AppendResponse {
+    pub id: String,
+    pub appended: u64,
+    #[serde(rename = "dataVersion")]
+    pub data_version: i64,
+}
+
+/// Body of `POST /api/uploads/url`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct RemoteImportRequest {
+    pub url: String,
+    /// Name the download is stored and typed by; defaults to the last segment
+    /// of the URL's path.
+    #[serde(default)]
+    pub file_name: Option<String>,
+    /// Sent with every fetch of `url`, e.g. `Authorization`.
+    #[serde(default)]
+    pub headers: std::collections::BTreeMap<String, String>,
+}
+
+/// Body of `POST /api/uploads/postgis`; give either `table` or `query`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct PostgisImportRequest {
+    /// libpq connection string or `postgresql://` URI.
+    pub connection: String,
+    /// `table` or `schema.table`.
+    #[serde(default)]
+    pub table: Option<String>,
+    /// A `SELECT` whose rows are copied.
+    #[serde(default)]
+    pub query: Option<String>,
+    /// Defaults to `geom`.
+    #[serde(default)]
+    pub geometry_column: Option<String>,
+    /// Defaults to the table's name.
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// Body of `POST /api/files/:id/export/postgis`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct PostgisExportRequest {
+    pub table: String,
+    /// Defaults to `public`.
+    #[serde(default)]
+    pub schema: Option<String>,
+    /// Replace an existing table instead of failing.
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+/// Body of `POST /api/files/:id/process/buffer`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct BufferRequest {
+    /// Meters; negative shrinks polygons.
+    pub distance: f64,
+    /// Defaults to `<source name> (buffer <distance> m)`.
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// Body of `POST /api/files/:id/process/dissolve`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct DissolveRequest {
+    /// Property whose values group the features.
+    pub field: String,
+    /// Defaults to `<source name> (dissolved by <field>)`.
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// Body of `POST /api/files/:id/process/centroids`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct CentroidsRequest {
+    /// `centroid` (default) or `pointOnSurface`.
+    #[serde(default)]
+    pub method: Option<String>,
+    /// Defaults to `<source name> (points)`.
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// Body of `POST /api/process/spatial-join`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct SpatialJoinRequest {
+    /// Dataset whose features are kept.
+    pub target: String,
+    /// Dataset whose properties are joined on.
+    pub join: String,
+    /// `intersects` (default) or `within`.
+    #[serde(default)]
+    pub predicate: Option<String>,
+    /// Defaults to `<target name> (joined with <join name>)`.
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// Result of `POST /api/files/:id/refresh`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RemoteRefreshResponse {
+    pub id: String,
+    /// The upstream copy changed and is being re-imported.
+    pub changed: bool,
+}
+
+/// Result of `GET /api/files/:id/refresh`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RemoteRefreshStatus {
+    pub id: String,
+    /// When the URL was last fetched, changed or not.
+    #[serde(rename = "fetchedAt", skip_serializing_if = "Option::is_none")]
+    pub fetched_at: Option<String>,
+    /// Why the last refresh failed, leaving the file as it was; cleared by
+    /// the next refresh that succeeds.
+    #[serde(rename = "lastRefreshError", skip_serializing_if = "Option::is_none")]
+    pub last_refresh_error: Option<String>,
+    #[serde(
+        rename = "lastRefreshFailedAt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub last_refresh_failed_at: Option<String>,
+}
+
+/// Body of `POST /api/files/:id/features`; a GeoJSON Feature is accepted as-is.
+#[derive(Debug, Deserialize)]
+pub struct FeatureCreateRequest {
+    pub geometry: serde_json::Value,
+    #[serde(default)]
+    pub properties: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportRequest {
+    pub format: String,
+}
@@ -0,0 +1,522 @@
+//! OGC API - Features
+//!
+//! `/ogc` serves every ready vector dataset the caller can see as a collection
+//! of OGC API - Features (Part 1: Core, GeoJSON), so QGIS, GDAL and other OGC
+//! clients can read MapFlow data without exporting it. Items come from the
+//! dataset's layer table with properties named as in `dataset_columns`,
+//! reprojected to CRS84, and are paged with `limit` and `offset`; `bbox`
+//! restricts them to a CRS84 box. The routes sit behind the same login or API
+//! key as `/api`, and a collection behind read access to its file.
+//!
+//! Links are absolute, built from the public base URL setting or else the
+//! request's host.
+
+use axum::{
+    extract::{Path as AxumPath, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use axum_login::AuthSession;
+use chrono::{SecondsFormat, Utc};
+use duckdb::OptionalExt;
+use serde::Deserialize;
+
+use crate::auth::AuthBackend;
+use crate::columns::{load_dataset_columns, quote_identifier, DatasetColumn};
+use crate::features::{
+    bbox_in_crs, parse_bbox, round_coordinates, value_ref_to_json, DEFAULT_FEATURE_LIMIT,
+    MAX_FEATURE_LIMIT,
+};
+use crate::http_errors::{bad_request, internal_error};
+use crate::models::{
+    DatasetMetadata, GeoJsonFeature, OgcCollection, OgcCollections, OgcExtent, OgcFeature,
+    OgcFeatureCollection, OgcLink, OgcSpatialExtent,
+};
+use crate::settings::load_settings;
+use crate::tile_options::load_tile_options;
+use crate::{
+    public_origin, visible_files_owner, AppState, ErrorResponse, VISIBLE_FILES_FILTER,
+    VISIBLE_FILES_PARAMS,
+};
+
+pub(crate) const CRS84: &str = "http://www.opengis.net/def/crs/OGC/1.3/CRS84";
+const CONFORMANCE_CLASSES: &[&str] = &[
+    "http://www.opengis.net/spec/ogcapi-features-1/1.0/conf/core",
+    "http://www.opengis.net/spec/ogcapi-features-1/1.0/conf/geojson",
+];
+pub(crate) const JSON: &str = "application/json";
+const GEOJSON: &str = "application/geo+json";
+
+/// Landing page, conformance and the collection list.
+pub fn build_ogc_router() -> Router<AppState> {
+    Router::new()
+        .route("/ogc", get(landing_page))
+        .route("/ogc/conformance", get(conformance))
+        .route("/ogc/collections", get(list_collections))
+}
+
+/// Routes of one collection, which need read access to its file.
+pub fn build_ogc_collection_router() -> Router<AppState> {
+    Router::new()
+        .route("/ogc/collections/{id}", get(get_collection))
+        .route("/ogc/collections/{id}/items", get(list_items))
+        .route("/ogc/collections/{id}/items/{fid}", get(get_item))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ItemsQuery {
+    pub limit: Option<u32>,
+    pub offset: Option<u64>,
+    pub bbox: Option<String>,
+}
+
+impl ItemsQuery {
+    /// Larger limits are capped rather than refused, as the standard asks.
+    fn limit(&self) -> Result<u32, String> {
+        match self.limit {
+            None => Ok(DEFAULT_FEATURE_LIMIT),
+            Some(0) => Err("limit must be at least 1".to_string()),
+            Some(limit) => Ok(limit.min(MAX_FEATURE_LIMIT)),
+        }
+    }
+
+    fn bbox(&self) -> Result<Option<[f64; 4]>, String> {
+        self.bbox
+            .as_deref()
+            .filter(|b| !b.trim().is_empty())
+            .map(parse_bbox)
+            .transpose()
+    }
+}
+
+pub(crate) fn link(href: String, rel: &str, media_type: &str, title: Option<&str>) -> OgcLink {
+    OgcLink {
+        href,
+        rel: rel.to_string(),
+        media_type: Some(media_type.to_string()),
+        title: title.map(str::to_string),
+        templated: None,
+    }
+}
+
+/// URL clients reach the server at, without a trailing slash.
+async fn root_url(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Result<String, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    let settings = load_settings(&conn, state).map_err(internal_error)?;
+    Ok(public_origin(state, &settings, headers))
+}
+
+async fn base_url(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Result<String, (StatusCode, Json<ErrorResponse>)> {
+    Ok(format!("{}/ogc", root_url(state, headers).await?))
+}
+
+fn collection_not_found() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse {
+            error: "Collection not found".to_string(),
+        }),
+    )
+}
+
+async fn landing_page(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let root = root_url(&state, &headers).await?;
+    let base = format!("{root}/ogc");
+    Ok(Json(serde_json::json!({
+        "title": "MapFlow",
+        "description": "Datasets served as OGC API - Features collections",
+        "links": [
+            link(base.clone(), "self", JSON, Some("This document")),
+            link(
+                format!("{root}/api/openapi.json"),
+                "service-desc",
+                "application/vnd.oai.openapi+json;version=3.1",
+                Some("API definition"),
+            ),
+            link(
+                format!("{base}/conformance"),
+                "conformance",
+                JSON,
+                Some("Conformance classes"),
+            ),
+            link(format!("{base}/collections"), "data", JSON, Some("Collections")),
+        ],
+    })))
+}
+
+async fn conformance() -> impl IntoResponse {
+    Json(serde_json::json!({ "conformsTo": CONFORMANCE_CLASSES }))
+}
+
+/// Ready vector datasets, which have a layer table and no tile format.
+const COLLECTION_CONDITION: &str =
+    "f.status = 'ready' AND f.table_name IS NOT NULL AND f.tile_format IS NULL";
+
+fn collection(
+    base: &str,
+    id: String,
+    title: String,
+    bbox: Option<String>,
+    metadata: DatasetMetadata,
+) -> OgcCollection {
+    let extent = bbox
+        .and_then(|bbox| serde_json::from_str::<[f64; 4]>(&bbox).ok())
+        .map(|bbox| OgcExtent {
+            spatial: OgcSpatialExtent {
+                bbox: vec![bbox],
+                crs: CRS84.to_string(),
+            },
+        });
+    let href = format!("{base}/collections/{id}");
+    OgcCollection {
+        links: vec![
+            link(href.clone(), "self", JSON, None),
+            link(format!("{href}/items"), "items", GEOJSON, Some("Features")),
+        ],
+        id,
+        title,
+        item_type: "feature".to_string(),
+        crs: vec![CRS84.to_string()],
+        description: metadata.description,
+        attribution: metadata.attribution,
+        license: metadata.license,
+        extent,
+    }
+}
+
+/// The collection of a row of id, name, bbox, description, attribution and
+/// license.
+fn collection_from_row(base: &str, row: &duckdb::Row) -> duckdb::Result<OgcCollection> {
+    let metadata = DatasetMetadata {
+        description: row.get(3)?,
+        attribution: row.get(4)?,
+        license: row.get(5)?,
+    };
+    Ok(collection(
+        base,
+        row.get(0)?,
+        row.get(1)?,
+        row.get(2)?,
+        metadata,
+    ))
+}
+
+async fn list_collections(
+    State(state): State<AppState>,
+    auth_session: AuthSession<AuthBackend>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let base = base_url(&state, &headers).await?;
+    let owner_id = visible_files_owner(&auth_session);
+    let owner_filter = if owner_id.is_some() {
+        format!("AND {VISIBLE_FILES_FILTER}")
+    } else {
+        String::new()
+    };
+
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT f.id, f.name, f.bbox, f.description, f.attribution, f.license FROM files f
+             WHERE {COLLECTION_CONDITION} {owner_filter}
+             ORDER BY f.name, f.id"
+        ))
+        .map_err(internal_error)?;
+    let collections = stmt
+        .query_map(
+            duckdb::params_from_iter(
+                std::iter::repeat_n(owner_id.iter(), VISIBLE_FILES_PARAMS).flatten(),
+            ),
+            |row| collection_from_row(&base, row),
+        )
+        .map_err(internal_error)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(internal_error)?;
+
+    Ok(Json(OgcCollections {
+        links: vec![link(format!("{base}/collections"), "self", JSON, None)],
+        collections,
+    }))
+}
+
+async fn get_collection(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let base = base_url(&state, &headers).await?;
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    conn.query_row(
+        &format!(
+            "SELECT f.id, f.name, f.bbox, f.description, f.attribution, f.license FROM files f WHERE f.id = ? AND {COLLECTION_CONDITION}"
+        ),
+        duckdb::params![&id],
+        |row| collection_from_row(&base, row),
+    )
+    .optional()
+    .map_err(internal_error)?
+    .map(Json)
+    .ok_or_else(collection_not_found)
+}
+
+/// Layer table, source CRS and columns of a collection.
+fn load_source(
+    conn: &duckdb::Connection,
+    id: &str,
+) -> Result<(String, String, Vec<DatasetColumn>), (StatusCode, Json<ErrorResponse>)> {
+    let (table_name, crs): (String, Option<String>) = conn
+        .query_row(
+            &format!(
+                "SELECT f.table_name, f.crs FROM files f WHERE f.id = ? AND {COLLECTION_CONDITION}"
+            ),
+            duckdb::params![id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(internal_error)?
+        .ok_or_else(collection_not_found)?;
+    let columns = load_dataset_columns(conn, id).map_err(internal_error)?;
+    Ok((
+        table_name,
+        crs.unwrap_or_else(|| "EPSG:4326".to_string()),
+        columns,
+    ))
+}
+
+fn wgs84_geometry(crs: &str) -> String {
+    format!(
+        "ST_Transform(geom, '{}', 'EPSG:4326', always_xy := true)",
+        crs.replace('\'', "''")
+    )
+}
+
+/// The dataset's `precision` tile option, which GeoJSON coordinates keep.
+fn load_precision(
+    conn: &duckdb::Connection,
+    id: &str,
+) -> Result<Option<u8>, (StatusCode, Json<ErrorResponse>)> {
+    Ok(load_tile_options(conn, id)
+        .map_err(internal_error)?
+        .and_then(|options| options.precision))
+}
+
+/// Read features from `rows` selected as `fid`, the columns, then GeoJSON,
+/// with coordinates rounded to `precision` decimals when given.
+fn read_features(
+    rows: &mut duckdb::Rows<'_>,
+    columns: &[DatasetColumn],
+    precision: Option<u8>,
+) -> Result<Vec<GeoJsonFeature>, (StatusCode, Json<ErrorResponse>)> {
+    let mut features = Vec::new();
+    while let Some(row) = rows.next().map_err(internal_error)? {
+        let mut properties = serde_json::Map::with_capacity(columns.len());
+        for (index, column) in columns.iter().enumerate() {
+            let value = value_ref_to_json(row.get_ref(index + 1).map_err(internal_error)?);
+            properties.insert(column.original.clone(), value);
+        }
+        let geometry: Option<String> = row.get(columns.len() + 1).map_err(internal_error)?;
+        let mut geometry = match geometry {
+            Some(text) => serde_json::from_str(&text).map_err(internal_error)?,
+            None => serde_json::Value::Null,
+        };
+        if let Some(places) = precision {
+            round_coordinates(&mut geometry, places);
+        }
+        features.push(GeoJsonFeature {
+            kind: "Feature".to_string(),
+            id: row.get(0).map_err(internal_error)?,
+            geometry,
+            properties,
+        });
+    }
+    Ok(features)
+}
+
+fn select_list(columns: &[DatasetColumn], crs: &str) -> String {
+    let mut exprs = vec!["fid".to_string()];
+    exprs.extend(columns.iter().map(|c| quote_identifier(&c.normalized)));
+    exprs.push(format!("ST_AsGeoJSON({})", wgs84_geometry(crs)));
+    exprs.join(", ")
+}
+
+fn items_href(base: &str, id: &str, limit: u32, offset: u64, bbox: Option<[f64; 4]>) -> String {
+    let mut href = format!("{base}/collections/{id}/items?limit={limit}&offset={offset}");
+    if let Some([minx, miny, maxx, maxy]) = bbox {
+        href.push_str(&format!("&bbox={minx},{miny},{maxx},{maxy}"));
+    }
+    href
+}
+
+async fn list_items(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+    Query(query): Query<ItemsQuery>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let limit = query.limit().map_err(|e| bad_request(&e))?;
+    let offset = query.offset.unwrap_or(0);
+    let bbox = query.bbox().map_err(|e| bad_request(&e))?;
+    let base = base_url(&state, &headers).await?;
+
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    let (table_name, crs, columns) = load_source(&conn, &id)?;
+    let precision = load_precision(&conn, &id)?;
+    let table = quote_identifier(&table_name);
+    let (where_clause, params) = match bbox {
+        Some(bbox) => (
+            " WHERE ST_Intersects(geom, ST_MakeEnvelope(?, ?, ?, ?))".to_string(),
+            bbox_in_crs(&conn, bbox, &crs)
+                .map_err(internal_error)?
+                .to_vec(),
+        ),
+        None => (String::new(), Vec::new()),
+    };
+
+    let matched: i64 = conn
+        .query_row(
+            &format!("SELECT COUNT(*) FROM {table}{where_clause}"),
+            duckdb::params_from_iter(params.iter()),
+            |row| row.get(0),
+        )
+        .map_err(internal_error)?;
+    let matched = matched.max(0) as u64;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM {table}{where_clause} ORDER BY fid LIMIT {limit} OFFSET {offset}",
+            select_list(&columns, &crs)
+        ))
+        .map_err(internal_error)?;
+    let mut rows = stmt
+        .query(duckdb::params_from_iter(params.iter()))
+        .map_err(internal_error)?;
+    let features = read_features(&mut rows, &columns, precision)?;
+    let returned = features.len() as u64;
+
+    let mut links = vec![
+        link(
+            items_href(&base, &id, limit, offset, bbox),
+            "self",
+            GEOJSON,
+            None,
+        ),
+        link(format!("{base}/collections/{id}"), "collection", JSON, None),
+    ];
+    if offset + returned < matched {
+        links.push(link(
+            items_href(&base, &id, limit, offset + returned, bbox),
+            "next",
+            GEOJSON,
+            Some("Next page"),
+        ));
+    }
+    if offset > 0 {
+        links.push(link(
+            items_href(&base, &id, limit, offset.saturating_sub(limit.into()), bbox),
+            "prev",
+            GEOJSON,
+            Some("Previous page"),
+        ));
+    }
+
+    Ok((
+        [(header::CONTENT_TYPE, GEOJSON)],
+        Json(OgcFeatureCollection {
+            kind: "FeatureCollection".to_string(),
+            features,
+            number_matched: matched,
+            number_returned: returned,
+            time_stamp: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            links,
+        }),
+    ))
+}
+
+async fn get_item(
+    State(state): State<AppState>,
+    AxumPath((id, fid)): AxumPath<(String, String)>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let not_found = || {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Feature not found".to_string(),
+            }),
+        )
+    };
+    let fid: i64 = fid.parse().map_err(|_| not_found())?;
+    let base = base_url(&state, &headers).await?;
+
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    let (table_name, crs, columns) = load_source(&conn, &id)?;
+    let precision = load_precision(&conn, &id)?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM {} WHERE fid = ?",
+            select_list(&columns, &crs),
+            quote_identifier(&table_name)
+        ))
+        .map_err(internal_error)?;
+    let mut rows = stmt.query(duckdb::params![fid]).map_err(internal_error)?;
+    let feature = read_features(&mut rows, &columns, precision)?
+        .pop()
+        .ok_or_else(not_found)?;
+
+    let collection_href = format!("{base}/collections/{id}");
+    Ok((
+        [(header::CONTENT_TYPE, GEOJSON)],
+        Json(OgcFeature {
+            feature,
+            links: vec![
+                link(
+                    format!("{collection_href}/items/{fid}"),
+                    "self",
+                    GEOJSON,
+                    None,
+                ),
+                link(collection_href, "collection", JSON, None),
+            ],
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_are_capped_and_bboxes_checked() {
+        let query = |limit, bbox: Option<&str>| ItemsQuery {
+            limit,
+            offset: None,
+            bbox: bbox.map(str::to_string),
+        };
+        assert_eq!(query(None, None).limit(), Ok(DEFAULT_FEATURE_LIMIT));
+        assert_eq!(query(Some(50_000), None).limit(), Ok(MAX_FEATURE_LIMIT));
+        assert!(query(Some(0), None).limit().is_err());
+        assert_eq!(
+            query(None, Some("-1,-2,3,4")).bbox(),
+            Ok(Some([-1.0, -2.0, 3.0, 4.0]))
+        );
+        assert!(query(None, Some("0,0,1,1,0,1")).bbox().is_err());
+    }
+
+    #[test]
+    fn page_links_keep_the_query() {
+        assert_eq!(
+            items_href("http://h/ogc", "a1", 10, 20, Some([0.0, 1.0, 2.5, 3.0])),
+            "http://h/ogc/collections/a1/items?limit=10&offset=20&bbox=0,1,2.5,3"
+        );
+    }
+}
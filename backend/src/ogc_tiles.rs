@@ -0,0 +1,366 @@
+//! OGC API - Tiles
+//!
+//! Every public slug (a published dataset or a tileset) is also an OGC API -
+//! Tiles endpoint at `/tiles/{slug}/ogc`: a landing page, conformance, the
+//! slug's tileset list and the metadata of its one `WebMercatorQuad` tileset,
+//! whose `item` link is the tile URL template. Tiles under
+//! `/tiles/{slug}/ogc/tiles/WebMercatorQuad/{tileMatrix}/{tileRow}/{tileCol}`
+//! are the ones `/tiles/{slug}/{z}/{x}/{y}` serves.
+//!
+//! Like the other `/tiles` routes these are anonymous, and a signed publish
+//! needs its `expires` and `token`, which are carried over to every link.
+
+use axum::{
+    extract::{Path as AxumPath, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+
+use crate::http_errors::internal_error;
+use crate::models::{
+    OgcBoundingBox, OgcLink, OgcTileLayer, OgcTileMatrixLimits, OgcTileSet, OgcTileSets, TileJson,
+};
+use crate::ogc::{link, CRS84, JSON};
+use crate::settings::{load_settings, public_cache_control};
+use crate::signing::SignedQuery;
+use crate::tile_routes::serve_public_tile;
+use crate::tiles::{TileEncoding, TileQuery};
+use crate::{public_origin, public_tilejson, AppState, ErrorResponse};
+
+const TILE_MATRIX_SET: &str = "WebMercatorQuad";
+const TILE_MATRIX_SET_URI: &str =
+    "http://www.opengis.net/def/tilematrixset/OGC/1.0/WebMercatorQuad";
+const WEB_MERCATOR: &str = "http://www.opengis.net/def/crs/EPSG/0/3857";
+const MVT: &str = "application/vnd.mapbox-vector-tile";
+/// Web Mercator stops short of the poles.
+const MAX_LATITUDE: f64 = 85.051_128_779_806_6;
+const CONFORMANCE_CLASSES: &[&str] = &[
+    "http://www.opengis.net/spec/ogcapi-common-1/1.0/conf/core",
+    "http://www.opengis.net/spec/ogcapi-tiles-1/1.0/conf/core",
+    "http://www.opengis.net/spec/ogcapi-tiles-1/1.0/conf/tileset",
+    "http://www.opengis.net/spec/ogcapi-tiles-1/1.0/conf/tilesets-list",
+    "http://www.opengis.net/spec/ogcapi-tiles-1/1.0/conf/dataset-tilesets",
+];
+const MVT_CONFORMANCE: &str = "http://www.opengis.net/spec/ogcapi-tiles-1/1.0/conf/mvt";
+
+pub fn build_ogc_tiles_router() -> Router<AppState> {
+    Router::new()
+        .route("/tiles/{slug}/ogc", get(landing_page))
+        .route("/tiles/{slug}/ogc/conformance", get(conformance))
+        .route("/tiles/{slug}/ogc/tiles", get(list_tilesets))
+        .route("/tiles/{slug}/ogc/tiles/WebMercatorQuad", get(get_tileset))
+        .route(
+            "/tiles/{slug}/ogc/tiles/WebMercatorQuad/{tile_matrix}/{tile_row}/{tile_col}",
+            get(get_tile),
+        )
+}
+
+/// What every document of a slug is built from.
+struct Slug {
+    tilejson: TileJson,
+    /// `/tiles/{slug}/ogc` as clients reach it.
+    base: String,
+    /// `?expires=..&token=..` of a signed request, else empty.
+    query: String,
+    cache_control: String,
+}
+
+impl Slug {
+    async fn load(
+        state: &AppState,
+        slug: &str,
+        signed: &SignedQuery,
+        headers: &HeaderMap,
+    ) -> Result<Self, (StatusCode, Json<ErrorResponse>)> {
+        let conn = state.read_pool.get().await.map_err(internal_error)?;
+        let settings = load_settings(&conn, state).map_err(internal_error)?;
+        // Checks expiry and signature; the tile URLs are built below.
+        let tilejson = public_tilejson(&conn, slug, "", signed)?;
+        let root = public_origin(state, &settings, headers);
+        let query = match (signed.expires, signed.token.as_deref()) {
+            (Some(expires), Some(token)) => format!("?expires={expires}&token={token}"),
+            _ => String::new(),
+        };
+        Ok(Self {
+            tilejson,
+            base: format!("{root}/tiles/{slug}/ogc"),
+            query,
+            cache_control: public_cache_control(&settings),
+        })
+    }
+
+    fn is_vector(&self) -> bool {
+        self.tilejson.vector_layers.is_some()
+    }
+
+    fn link(&self, path: &str, rel: &str, media_type: &str, title: Option<&str>) -> OgcLink {
+        link(
+            format!("{}{path}{}", self.base, self.query),
+            rel,
+            media_type,
+            title,
+        )
+    }
+
+    /// The tileset without its per-layer and per-zoom detail.
+    fn tileset_summary(&self) -> OgcTileSet {
+        let path = format!("/tiles/{TILE_MATRIX_SET}");
+        let mut item = self.link(
+            &format!("{path}/{{tileMatrix}}/{{tileRow}}/{{tileCol}}"),
+            "item",
+            MVT,
+            Some("Tiles"),
+        );
+        item.templated = Some(true);
+        if !self.is_vector() {
+            item.media_type = None;
+        }
+        OgcTileSet {
+            title: self.tilejson.name.clone(),
+            description: self.tilejson.description.clone(),
+            attribution: self.tilejson.attribution.clone(),
+            license: self.tilejson.license.clone(),
+            data_type: if self.is_vector() { "vector" } else { "map" }.to_string(),
+            crs: WEB_MERCATOR.to_string(),
+            tile_matrix_set_uri: TILE_MATRIX_SET_URI.to_string(),
+            tile_matrix_set_limits: None,
+            bounding_box: None,
+            layers: None,
+            links: vec![
+                self.link(&path, "self", JSON, Some("Tileset metadata")),
+                link(
+                    TILE_MATRIX_SET_URI.to_string(),
+                    "http://www.opengis.net/def/rel/ogc/1.0/tiling-scheme",
+                    JSON,
+                    Some(TILE_MATRIX_SET),
+                ),
+                item,
+            ],
+        }
+    }
+
+    fn tilesets_rel(&self) -> &'static str {
+        if self.is_vector() {
+            "http://www.opengis.net/def/rel/ogc/1.0/tilesets-vector"
+        } else {
+            "http://www.opengis.net/def/rel/ogc/1.0/tilesets-map"
+        }
+    }
+}
+
+/// Column and row of the tile holding `(lon, lat)` at zoom `z`.
+fn tile_index(lon: f64, lat: f64, z: u8) -> (u32, u32) {
+    let n = f64::from(1u32 << z);
+    let lat = lat.clamp(-MAX_LATITUDE, MAX_LATITUDE).to_radians();
+    let col = ((lon + 180.0) / 360.0 * n).floor();
+    let row = ((1.0 - (lat.tan() + 1.0 / lat.cos()).ln() / std::f64::consts::PI) / 2.0 * n).floor();
+    let max = n - 1.0;
+    (col.clamp(0.0, max) as u32, row.clamp(0.0, max) as u32)
+}
+
+/// Tiles covering `bounds` at each zoom from `minzoom` to `maxzoom`.
+pub(crate) fn tile_matrix_limits(
+    bounds: [f64; 4],
+    minzoom: u8,
+    maxzoom: u8,
+) -> Vec<OgcTileMatrixLimits> {
+    let [minx, miny, maxx, maxy] = bounds;
+    (minzoom..=maxzoom)
+        .map(|z| {
+            let (min_tile_col, min_tile_row) = tile_index(minx, maxy, z);
+            let (max_tile_col, max_tile_row) = tile_index(maxx, miny, z);
+            OgcTileMatrixLimits {
+                tile_matrix: z.to_string(),
+                min_tile_row,
+                max_tile_row,
+                min_tile_col,
+                max_tile_col,
+            }
+        })
+        .collect()
+}
+
+/// TileJSON field types as a JSON Schema of the layer's properties.
+fn properties_schema(fields: &std::collections::BTreeMap<String, String>) -> serde_json::Value {
+    let properties: serde_json::Map<String, serde_json::Value> = fields
+        .iter()
+        .map(|(name, kind)| {
+            let kind = match kind.as_str() {
+                "Number" => "number",
+                "Boolean" => "boolean",
+                _ => "string",
+            };
+            (name.clone(), serde_json::json!({ "type": kind }))
+        })
+        .collect();
+    serde_json::json!({ "type": "object", "properties": properties })
+}
+
+async fn landing_page(
+    State(state): State<AppState>,
+    AxumPath(slug): AxumPath<String>,
+    Query(signed): Query<SignedQuery>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let slug = Slug::load(&state, &slug, &signed, &headers).await?;
+    let body = serde_json::json!({
+        "title": slug.tilejson.name,
+        "description": "Published tiles served as OGC API - Tiles",
+        "links": [
+            slug.link("", "self", JSON, Some("This document")),
+            slug.link(
+                "/conformance",
+                "conformance",
+                JSON,
+                Some("Conformance classes"),
+            ),
+            slug.link("/tiles", slug.tilesets_rel(), JSON, Some("Tilesets")),
+        ],
+    });
+    Ok(([(header::CACHE_CONTROL, slug.cache_control)], Json(body)))
+}
+
+async fn conformance(
+    State(state): State<AppState>,
+    AxumPath(slug): AxumPath<String>,
+    Query(signed): Query<SignedQuery>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let slug = Slug::load(&state, &slug, &signed, &headers).await?;
+    let mut classes = CONFORMANCE_CLASSES.to_vec();
+    if slug.is_vector() {
+        classes.push(MVT_CONFORMANCE);
+    }
+    Ok((
+        [(header::CACHE_CONTROL, slug.cache_control)],
+        Json(serde_json::json!({ "conformsTo": classes })),
+    ))
+}
+
+async fn list_tilesets(
+    State(state): State<AppState>,
+    AxumPath(slug): AxumPath<String>,
+    Query(signed): Query<SignedQuery>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let slug = Slug::load(&state, &slug, &signed, &headers).await?;
+    let tilesets = OgcTileSets {
+        links: vec![slug.link("/tiles", "self", JSON, None)],
+        tilesets: vec![slug.tileset_summary()],
+    };
+    Ok((
+        [(header::CACHE_CONTROL, slug.cache_control)],
+        Json(tilesets),
+    ))
+}
+
+async fn get_tileset(
+    State(state): State<AppState>,
+    AxumPath(slug): AxumPath<String>,
+    Query(signed): Query<SignedQuery>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let slug = Slug::load(&state, &slug, &signed, &headers).await?;
+    let tilejson = &slug.tilejson;
+    let mut tileset = slug.tileset_summary();
+    if let Some(bounds) = tilejson.bounds {
+        tileset.tile_matrix_set_limits = Some(tile_matrix_limits(
+            bounds,
+            tilejson.minzoom,
+            tilejson.maxzoom,
+        ));
+        tileset.bounding_box = Some(OgcBoundingBox {
+            lower_left: [bounds[0], bounds[1]],
+            upper_right: [bounds[2], bounds[3]],
+            crs: CRS84.to_string(),
+        });
+    }
+    tileset.layers = tilejson.vector_layers.as_ref().map(|layers| {
+        layers
+            .iter()
+            .map(|layer| OgcTileLayer {
+                id: layer.id.clone(),
+                data_type: "vector".to_string(),
+                properties_schema: properties_schema(&layer.fields),
+            })
+            .collect()
+    });
+    Ok(([(header::CACHE_CONTROL, slug.cache_control)], Json(tileset)))
+}
+
+/// The tile `/tiles/{slug}/{z}/{x}/{y}` serves; OGC names the row before the
+/// column.
+async fn get_tile(
+    State(state): State<AppState>,
+    AxumPath((slug, z, row, col)): AxumPath<(String, i32, i32, i32)>,
+    Query(query): Query<TileQuery>,
+    Query(signed): Query<SignedQuery>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    serve_public_tile(
+        &state,
+        &slug,
+        (z, col, row),
+        query,
+        signed,
+        TileEncoding::Mvt,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tile_index_matches_xyz_tiles() {
+        assert_eq!(tile_index(-180.0, MAX_LATITUDE, 0), (0, 0));
+        assert_eq!(tile_index(180.0, -90.0, 0), (0, 0));
+        assert_eq!(tile_index(0.5, 0.5, 1), (1, 0));
+        assert_eq!(tile_index(-0.5, -0.5, 1), (0, 1));
+        // Central London at zoom 10.
+        assert_eq!(tile_index(-0.1276, 51.5072, 10), (511, 340));
+    }
+
+    #[test]
+    fn limits_cover_the_bounds_at_each_zoom() {
+        let limits = tile_matrix_limits([-1.0, -1.0, 1.0, 1.0], 0, 2);
+        let ranges: Vec<_> = limits
+            .iter()
+            .map(|l| {
+                (
+                    l.tile_matrix.as_str(),
+                    l.min_tile_col,
+                    l.max_tile_col,
+                    l.min_tile_row,
+                    l.max_tile_row,
+                )
+            })
+            .collect();
+        assert_eq!(
+            ranges,
+            vec![("0", 0, 0, 0, 0), ("1", 0, 1, 0, 1), ("2", 1, 2, 1, 2)]
+        );
+    }
+
+    #[test]
+    fn properties_schema_maps_tilejson_types() {
+        let fields = std::collections::BTreeMap::from([
+            ("name".to_string(), "String".to_string()),
+            ("lanes".to_string(), "Number".to_string()),
+            ("lit".to_string(), "Boolean".to_string()),
+        ]);
+        assert_eq!(
+            properties_schema(&fields),
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "lanes": { "type": "number" },
+                    "lit": { "type": "boolean" },
+                    "name": { "type": "string" },
+                },
+            })
+        );
+    }
+}
@@ -0,0 +1,366 @@
+//! OpenAPI description of the HTTP API
+//!
+//! `/api/openapi.json` is generated from the JSON Schema contracts in
+//! `docs/dev/contracts`, the same files the contract tests check responses
+//! against, so the published spec cannot drift from what the handlers return.
+//! Every entry of the contract index becomes an operation whose success
+//! response uses that schema; `/api/docs` renders the spec with Swagger UI,
+//! which is vendored in `assets/vendor/swagger-ui` and served from
+//! `/api/docs/*` so the page needs nothing from another origin.
+
+use serde_json::{json, Map, Value};
+
+const CONTRACT_INDEX: &str = include_str!("../../docs/dev/contracts/index.json");
+const API_DOCS_PAGE: &str = include_str!("../assets/api-docs.html");
+const API_DOCS_ASSETS: &[(&str, &str, &[u8])] = &[
+    (
+        "api-docs.js",
+        "text/javascript; charset=utf-8",
+        include_bytes!("../assets/api-docs.js"),
+    ),
+    (
+        "swagger-ui-bundle.js",
+        "text/javascript; charset=utf-8",
+        include_bytes!("../assets/vendor/swagger-ui/swagger-ui-bundle.js"),
+    ),
+    (
+        "swagger-ui.css",
+        "text/css; charset=utf-8",
+        include_bytes!("../assets/vendor/swagger-ui/swagger-ui.css"),
+    ),
+];
+
+/// Swagger UI sets inline styles and shows data: images; everything else
+/// comes from this origin.
+pub const API_DOCS_CSP: &str = "default-src 'none'; script-src 'self'; \
+     style-src 'self' 'unsafe-inline'; img-src 'self' data:; connect-src 'self'; \
+     base-uri 'none'; form-action 'none'; frame-ancestors 'none'";
+
+macro_rules! contract {
+    ($name:literal) => {
+        (
+            $name,
+            include_str!(concat!("../../docs/dev/contracts/", $name)),
+        )
+    };
+}
+
+/// Every schema under `docs/dev/contracts`, by file name.
+const CONTRACTS: &[(&str, &str)] = &[
+    contract!("api-token-list.schema.json"),
+    contract!("api-token.schema.json"),
+    contract!("backup.schema.json"),
+    contract!("dataset-metadata.schema.json"),
+    contract!("dataset-query.schema.json"),
+    contract!("dataset-version-list.schema.json"),
+    contract!("dataset-version.schema.json"),
+    contract!("error.schema.json"),
+    contract!("export-job.schema.json"),
+    contract!("feature-collection.schema.json"),
+    contract!("feature-list.schema.json"),
+    contract!("feature-measurements.schema.json"),
+    contract!("feature-properties.schema.json"),
+    contract!("file-folder.schema.json"),
+    contract!("file-item.schema.json"),
+    contract!("file-list.schema.json"),
+    contract!("file-retention.schema.json"),
+    contract!("file-schema.schema.json"),
+    contract!("file-share-list.schema.json"),
+    contract!("file-share.schema.json"),
+    contract!("file-tags.schema.json"),
+    contract!("health-check.schema.json"),
+    contract!("health.schema.json"),
+    contract!("map-style.schema.json"),
+    contract!("measurement.schema.json"),
+    contract!("ogc-collection.schema.json"),
+    contract!("ogc-collections.schema.json"),
+    contract!("ogc-feature-collection.schema.json"),
+    contract!("ogc-link.schema.json"),
+    contract!("ogc-tileset-list.schema.json"),
+    contract!("ogc-tileset.schema.json"),
+    contract!("org-list.schema.json"),
+    contract!("org-member-list.schema.json"),
+    contract!("org-member.schema.json"),
+    contract!("org.schema.json"),
+    contract!("password-reset.schema.json"),
+    contract!("preview-meta.schema.json"),
+    contract!("public-tile-url.schema.json"),
+    contract!("public-tile-meta.schema.json"),
+    contract!("publish-response.schema.json"),
+    contract!("remote-refresh.schema.json"),
+    contract!("settings.schema.json"),
+    contract!("signed-url.schema.json"),
+    contract!("storage.schema.json"),
+    contract!("tile-inspection.schema.json"),
+    contract!("tile-options.schema.json"),
+    contract!("tile-seed-job.schema.json"),
+    contract!("tilejson.schema.json"),
+    contract!("tileset-list.schema.json"),
+    contract!("tileset.schema.json"),
+    contract!("user-list.schema.json"),
+    contract!("user.schema.json"),
+    contract!("webhook-list.schema.json"),
+    contract!("webhook.schema.json"),
+];
+
+/// Index key of the error body shared by all failures.
+const ERROR_CONTRACT_KEY: &str = "error";
+
+/// Component name of a schema file: `file-item.schema.json` -> `file-item`.
+fn component_name(file_name: &str) -> &str {
+    file_name.trim_end_matches(".schema.json")
+}
+
+/// Drop the standalone-document keywords and point file references at
+/// `#/components/schemas`.
+fn to_component(mut schema: Value) -> Value {
+    fn rewrite_refs(value: &mut Value) {
+        match value {
+            Value::Object(object) => {
+                if let Some(Value::String(reference)) = object.get_mut("$ref") {
+                    if !reference.starts_with('#') {
+                        *reference = format!("#/components/schemas/{}", component_name(reference));
+                    }
+                }
+                object.values_mut().for_each(rewrite_refs);
+            }
+            Value::Array(items) => items.iter_mut().for_each(rewrite_refs),
+            _ => {}
+        }
+    }
+
+    if let Some(object) = schema.as_object_mut() {
+        object.remove("$schema");
+        object.remove("$id");
+    }
+    rewrite_refs(&mut schema);
+    schema
+}
+
+/// `:id` segments become `{id}` templates, each a required path parameter.
+fn openapi_path(path: &str) -> (String, Vec<Value>) {
+    let mut parameters = Vec::new();
+    let segments: Vec<String> = path
+        .split('/')
+        .map(|segment| match segment.strip_prefix(':') {
+            Some(name) => {
+                parameters.push(json!({
+                    "name": name,
+                    "in": "path",
+                    "required": true,
+                    "schema": { "type": "string" }
+                }));
+                format!("{{{name}}}")
+            }
+            None => segment.to_string(),
+        })
+        .collect();
+    (segments.join("/"), parameters)
+}
+
+fn schema_ref(file_name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", component_name(file_name)) })
+}
+
+pub fn build_openapi_spec() -> Value {
+    let index: Map<String, Value> =
+        serde_json::from_str(CONTRACT_INDEX).expect("contract index is valid JSON");
+
+    let schemas: Map<String, Value> = CONTRACTS
+        .iter()
+        .map(|(file_name, text)| {
+            let schema = serde_json::from_str(text).expect("contract schema is valid JSON");
+            (component_name(file_name).to_string(), to_component(schema))
+        })
+        .collect();
+
+    let error_schema = schema_ref(
+        index
+            .get(ERROR_CONTRACT_KEY)
+            .and_then(Value::as_str)
+            .expect("contract index names the error schema"),
+    );
+
+    let mut paths = Map::new();
+    for (key, schema_file) in &index {
+        let Some((method, target)) = key.split_once(' ') else {
+            continue;
+        };
+        let Some(schema_file) = schema_file.as_str() else {
+            continue;
+        };
+        let (path, query) = match target.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (target, None),
+        };
+        let (path, mut parameters) = openapi_path(path);
+        let method = method.to_ascii_lowercase();
+
+        let operations = paths
+            .entry(path.clone())
+            .or_insert_with(|| json!({}))
+            .as_object_mut()
+            .expect("path item is an object");
+        // A query variant (`?format=geojson`) answers with another body on the
+        // same operation.
+        if let Some(operation) = operations.get_mut(&method) {
+            let schema =
+                &mut operation["responses"]["2XX"]["content"]["application/json"]["schema"];
+            if schema.get("oneOf").is_none() {
+                *schema = json!({ "oneOf": [schema.take()] });
+            }
+            schema["oneOf"]
+                .as_array_mut()
+                .expect("oneOf is an array")
+                .push(schema_ref(schema_file));
+        } else {
+            operations.insert(
+                method.clone(),
+                json!({
+                    "operationId": format!("{method} {path}"),
+                    "parameters": [],
+                    "responses": {
+                        "2XX": {
+                            "description": "Success",
+                            "content": { "application/json": { "schema": schema_ref(schema_file) } }
+                        },
+                        "default": {
+                            "description": "Error",
+                            "content": {
+                                "application/json": { "schema": error_schema.clone() }
+                            }
+                        }
+                    }
+                }),
+            );
+        }
+
+        for pair in query.into_iter().flat_map(|query| query.split('&')) {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            parameters.push(json!({
+                "name": name,
+                "in": "query",
+                "required": false,
+                "schema": { "type": "string", "examples": [value] }
+            }));
+        }
+        let operation = &mut operations[&method];
+        let existing = operation["parameters"]
+            .as_array_mut()
+            .expect("parameters is an array");
+        for parameter in parameters {
+            if !existing.contains(&parameter) {
+                existing.push(parameter);
+            }
+        }
+        // Public tile endpoints and the health check are anonymous; everything
+        // else needs a session cookie or an API key.
+        if path.starts_with("/tiles/") || path == "/health" {
+            operation["security"] = json!([]);
+        }
+    }
+
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "MapFlow API",
+            "version": env!("CARGO_PKG_VERSION")
+        },
+        "security": [{ "sessionCookie": [] }, { "apiKey": [] }],
+        "paths": paths,
+        "components": {
+            "schemas": schemas,
+            "securitySchemes": {
+                "sessionCookie": { "type": "apiKey", "in": "cookie", "name": "id" },
+                "apiKey": { "type": "http", "scheme": "bearer" }
+            }
+        }
+    })
+}
+
+pub fn api_docs_page() -> &'static str {
+    API_DOCS_PAGE
+}
+
+/// Content type and body of a file under `/api/docs/`.
+pub fn api_docs_asset(name: &str) -> Option<(&'static str, &'static [u8])> {
+    API_DOCS_ASSETS
+        .iter()
+        .find(|(asset, _, _)| *asset == name)
+        .map(|(_, content_type, body)| (*content_type, *body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Collect every `$ref` in `value`.
+    fn refs<'a>(value: &'a Value, found: &mut Vec<&'a str>) {
+        match value {
+            Value::Object(object) => {
+                if let Some(Value::String(reference)) = object.get("$ref") {
+                    found.push(reference);
+                }
+                object.values().for_each(|value| refs(value, found));
+            }
+            Value::Array(items) => items.iter().for_each(|value| refs(value, found)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn docs_page_loads_only_served_assets() {
+        for src in API_DOCS_PAGE
+            .split(['"', '\''])
+            .filter(|part| part.starts_with("/api/docs/"))
+        {
+            let name = src.trim_start_matches("/api/docs/");
+            assert!(api_docs_asset(name).is_some(), "{src} is not served");
+        }
+        assert!(!API_DOCS_PAGE.contains("https://"));
+        assert!(!API_DOCS_PAGE.contains("<script>"));
+    }
+
+    #[test]
+    fn every_contract_is_embedded() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../docs/dev/contracts");
+        let mut on_disk: Vec<String> = std::fs::read_dir(dir)
+            .expect("contracts dir")
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter(|name| name.ends_with(".schema.json"))
+            .collect();
+        on_disk.sort();
+        let embedded: Vec<&str> = CONTRACTS.iter().map(|(name, _)| *name).collect();
+        assert_eq!(on_disk, embedded, "add new schemas to CONTRACTS");
+    }
+
+    #[test]
+    fn spec_references_resolve() {
+        let spec = build_openapi_spec();
+        let mut found = Vec::new();
+        refs(&spec, &mut found);
+        assert!(!found.is_empty());
+        for reference in found {
+            let name = reference
+                .strip_prefix("#/components/schemas/")
+                .unwrap_or_else(|| panic!("unexpected $ref {reference}"));
+            assert!(
+                spec["components"]["schemas"].get(name).is_some(),
+                "missing component {name}"
+            );
+        }
+
+        let features = &spec["paths"]["/api/files/{id}/features"]["get"];
+        assert_eq!(
+            features["responses"]["2XX"]["content"]["application/json"]["schema"]["oneOf"]
+                .as_array()
+                .map(Vec::len),
+            Some(2)
+        );
+        assert_eq!(features["parameters"][1]["name"], "format");
+        assert_eq!(
+            spec["paths"]["/tiles/{slug}/tilejson.json"]["get"]["security"],
+            json!([])
+        );
+    }
+}
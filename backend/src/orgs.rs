@@ -0,0 +1,369 @@
+//! Organizations
+//!
+//! Admins create organizations and manage their members. A file's owner can
+//! move it into one of their organizations, after which every member sees it in
+//! their file list and may edit it (publishing and sharing stay with the
+//! owner). Deleting an organization leaves its files with their owners.
+
+use axum::{
+    extract::{Path as AxumPath, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{delete, get, post},
+    Json, Router,
+};
+use axum_login::AuthSession;
+use chrono::Utc;
+use duckdb::OptionalExt;
+
+use crate::auth::{AuthBackend, Role};
+use crate::http_errors::{bad_request, internal_error};
+use crate::models::{CreateOrgRequest, FileOrgRequest, OrgItem, OrgMember, OrgMemberRequest};
+use crate::{AppState, ErrorResponse};
+
+const MAX_ORG_NAME_LENGTH: usize = 64;
+
+/// Admin-only routes; listing and file assignment are routed with the roles
+/// they need.
+pub fn build_orgs_router() -> Router<AppState> {
+    Router::new()
+        .route("/api/orgs", post(create_org))
+        .route("/api/orgs/{id}", delete(delete_org))
+        .route("/api/orgs/{id}/members", get(list_members).post(add_member))
+        .route("/api/orgs/{id}/members/{username}", delete(remove_member))
+}
+
+fn validate_org_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Organization name cannot be empty".to_string());
+    }
+    if name.chars().count() > MAX_ORG_NAME_LENGTH {
+        return Err(format!(
+            "Organization name must be {MAX_ORG_NAME_LENGTH} characters or less"
+        ));
+    }
+    Ok(name.to_string())
+}
+
+fn not_found(error: String) -> (StatusCode, Json<ErrorResponse>) {
+    (StatusCode::NOT_FOUND, Json(ErrorResponse { error }))
+}
+
+fn org_not_found() -> (StatusCode, Json<ErrorResponse>) {
+    not_found("Organization not found".to_string())
+}
+
+fn org_exists(
+    conn: &duckdb::Connection,
+    id: &str,
+) -> Result<bool, (StatusCode, Json<ErrorResponse>)> {
+    let count: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM orgs WHERE id = ?",
+            duckdb::params![id],
+            |row| row.get(0),
+        )
+        .map_err(internal_error)?;
+    Ok(count > 0)
+}
+
+fn is_member(
+    conn: &duckdb::Connection,
+    org_id: &str,
+    user_id: &str,
+) -> Result<bool, (StatusCode, Json<ErrorResponse>)> {
+    let count: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM org_members WHERE org_id = ? AND user_id = ?",
+            duckdb::params![org_id, user_id],
+            |row| row.get(0),
+        )
+        .map_err(internal_error)?;
+    Ok(count > 0)
+}
+
+/// Organizations with their member counts, optionally only those `member_id`
+/// belongs to.
+fn load_orgs(
+    conn: &duckdb::Connection,
+    org_id: Option<&str>,
+    member_id: Option<&str>,
+) -> Result<Vec<OrgItem>, duckdb::Error> {
+    let mut filters = Vec::new();
+    let mut params = Vec::new();
+    if let Some(org_id) = org_id {
+        filters.push("o.id = ?");
+        params.push(org_id);
+    }
+    if let Some(member_id) = member_id {
+        filters.push("o.id IN (SELECT org_id FROM org_members WHERE user_id = ?)");
+        params.push(member_id);
+    }
+    let where_clause = if filters.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", filters.join(" AND "))
+    };
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT o.id, o.name, o.created_at, COUNT(m.user_id)
+         FROM orgs o
+         LEFT JOIN org_members m ON m.org_id = o.id
+         {where_clause}
+         GROUP BY o.id, o.name, o.created_at
+         ORDER BY o.name"
+    ))?;
+    let orgs = stmt
+        .query_map(duckdb::params_from_iter(params), |row| {
+            let created_at: chrono::NaiveDateTime = row.get(2)?;
+            Ok(OrgItem {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                created_at: created_at.and_utc().to_rfc3339(),
+                member_count: row.get(3)?,
+            })
+        })?
+        .collect::<Result<_, _>>()?;
+    Ok(orgs)
+}
+
+/// Admins see every organization, other users the ones they belong to.
+pub async fn list_orgs(
+    State(state): State<AppState>,
+    auth_session: AuthSession<AuthBackend>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let member_id = auth_session
+        .user
+        .filter(|user| user.role() != Role::Admin)
+        .map(|user| user.id);
+    let conn = state.db.lock().await;
+    let orgs = load_orgs(&conn, None, member_id.as_deref()).map_err(internal_error)?;
+    Ok(Json(orgs))
+}
+
+async fn create_org(
+    State(state): State<AppState>,
+    Json(req): Json<CreateOrgRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let name = validate_org_name(&req.name).map_err(|e| bad_request(&e))?;
+
+    let conn = state.db.lock().await;
+    let exists: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM orgs WHERE name = ?",
+            duckdb::params![&name],
+            |row| row.get(0),
+        )
+        .map_err(internal_error)?;
+    if exists > 0 {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(ErrorResponse {
+                error: format!("Organization '{name}' already exists"),
+            }),
+        ));
+    }
+
+    let id = uuid::Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO orgs (id, name, created_at) VALUES (?, ?, ?)",
+        duckdb::params![&id, &name, Utc::now().naive_utc()],
+    )
+    .map_err(internal_error)?;
+    let org = load_orgs(&conn, Some(&id), None)
+        .map_err(internal_error)?
+        .pop()
+        .ok_or_else(org_not_found)?;
+
+    Ok((StatusCode::CREATED, Json(org)))
+}
+
+async fn delete_org(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.db.lock().await;
+    if !org_exists(&conn, &id)? {
+        return Err(org_not_found());
+    }
+    conn.execute_batch("BEGIN TRANSACTION")
+        .map_err(internal_error)?;
+    let result = conn
+        .execute(
+            "UPDATE files SET org_id = NULL WHERE org_id = ?",
+            duckdb::params![&id],
+        )
+        .and_then(|_| {
+            conn.execute(
+                "DELETE FROM org_members WHERE org_id = ?",
+                duckdb::params![&id],
+            )
+        })
+        .and_then(|_| conn.execute("DELETE FROM orgs WHERE id = ?", duckdb::params![&id]));
+    match result {
+        Ok(_) => conn.execute_batch("COMMIT").map_err(internal_error)?,
+        Err(e) => {
+            let _ = conn.execute_batch("ROLLBACK");
+            return Err(internal_error(e));
+        }
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn load_members(conn: &duckdb::Connection, org_id: &str) -> Result<Vec<OrgMember>, duckdb::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT u.username, m.joined_at
+         FROM org_members m
+         JOIN users u ON u.id = m.user_id
+         WHERE m.org_id = ?
+         ORDER BY u.username",
+    )?;
+    let members = stmt
+        .query_map(duckdb::params![org_id], |row| {
+            let joined_at: chrono::NaiveDateTime = row.get(1)?;
+            Ok(OrgMember {
+                username: row.get(0)?,
+                joined_at: joined_at.and_utc().to_rfc3339(),
+            })
+        })?
+        .collect::<Result<_, _>>()?;
+    Ok(members)
+}
+
+async fn list_members(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.db.lock().await;
+    if !org_exists(&conn, &id)? {
+        return Err(org_not_found());
+    }
+    let members = load_members(&conn, &id).map_err(internal_error)?;
+    Ok(Json(members))
+}
+
+async fn add_member(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+    Json(req): Json<OrgMemberRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let username = req.username.trim();
+
+    let conn = state.db.lock().await;
+    if !org_exists(&conn, &id)? {
+        return Err(org_not_found());
+    }
+    let user_id: String = conn
+        .query_row(
+            "SELECT id FROM users WHERE username = ?",
+            duckdb::params![username],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(internal_error)?
+        .ok_or_else(|| not_found(format!("User '{username}' not found")))?;
+    if is_member(&conn, &id, &user_id)? {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(ErrorResponse {
+                error: format!("User '{username}' is already a member"),
+            }),
+        ));
+    }
+
+    conn.execute(
+        "INSERT INTO org_members (org_id, user_id, joined_at) VALUES (?, ?, ?)",
+        duckdb::params![&id, &user_id, Utc::now().naive_utc()],
+    )
+    .map_err(internal_error)?;
+    let member = load_members(&conn, &id)
+        .map_err(internal_error)?
+        .into_iter()
+        .find(|member| member.username == username)
+        .ok_or_else(|| internal_error("Member was not saved"))?;
+
+    Ok((StatusCode::CREATED, Json(member)))
+}
+
+async fn remove_member(
+    State(state): State<AppState>,
+    AxumPath((id, username)): AxumPath<(String, String)>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.db.lock().await;
+    let deleted = conn
+        .execute(
+            "DELETE FROM org_members
+             WHERE org_id = ? AND user_id IN (SELECT id FROM users WHERE username = ?)",
+            duckdb::params![&id, &username],
+        )
+        .map_err(internal_error)?;
+    if deleted == 0 {
+        return Err(not_found(format!(
+            "User '{username}' is not a member of this organization"
+        )));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Move a file into one of the caller's organizations, or out of any with
+/// `orgId: null`.
+pub async fn set_file_org(
+    State(state): State<AppState>,
+    auth_session: AuthSession<AuthBackend>,
+    AxumPath(id): AxumPath<String>,
+    Json(req): Json<FileOrgRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.db.lock().await;
+    let file_exists: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM files WHERE id = ?",
+            duckdb::params![&id],
+            |row| row.get(0),
+        )
+        .map_err(internal_error)?;
+    if file_exists == 0 {
+        return Err(not_found("File not found".to_string()));
+    }
+    if let Some(org_id) = &req.org_id {
+        if !org_exists(&conn, org_id)? {
+            return Err(org_not_found());
+        }
+        if let Some(user) = auth_session
+            .user
+            .as_ref()
+            .filter(|user| user.role() != Role::Admin)
+        {
+            if !is_member(&conn, org_id, &user.id)? {
+                return Err((
+                    StatusCode::FORBIDDEN,
+                    Json(ErrorResponse {
+                        error: "You are not a member of this organization".to_string(),
+                    }),
+                ));
+            }
+        }
+    }
+
+    conn.execute(
+        "UPDATE files SET org_id = ? WHERE id = ?",
+        duckdb::params![&req.org_id, &id],
+    )
+    .map_err(internal_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn org_names_are_trimmed_and_bounded() {
+        assert_eq!(validate_org_name(" GIS team "), Ok("GIS team".to_string()));
+        assert!(validate_org_name("").is_err());
+        assert!(validate_org_name(&"a".repeat(MAX_ORG_NAME_LENGTH + 1)).is_err());
+    }
+}
@@ -0,0 +1,209 @@
+//! Orphan cleanup
+//!
+//! Uploads that never got a `files` row (rejected as too large, interrupted,
+//! appended), imports that failed after creating their table, and catalog rows
+//! removed by hand leave directories under the upload directory and `layer_*`
+//! tables behind. The sweep cross-checks them against `files` and removes what
+//! nothing refers to: at startup, then every `ORPHAN_SWEEP_INTERVAL`.
+//! `mapflow db cleanup --dry-run` only reports.
+//!
+//! Directories younger than `ORPHAN_MIN_AGE` are left alone, since an upload
+//! in progress has its directory before its row. Tables of datasets that are
+//! still importing are kept by name.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::backup::SKIPPED_UPLOAD_DIRS;
+use crate::columns::quote_identifier;
+use crate::models::AppState;
+use crate::tile_cache::tile_cache_root;
+
+pub const ORPHAN_SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const ORPHAN_MIN_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// What a sweep found, and removed unless it was a dry run.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct OrphanReport {
+    pub directories: Vec<PathBuf>,
+    pub tables: Vec<String>,
+}
+
+impl OrphanReport {
+    pub fn is_empty(&self) -> bool {
+        self.directories.is_empty() && self.tables.is_empty()
+    }
+}
+
+/// Find orphaned upload and tile cache directories and dataset tables, and
+/// remove them unless `dry_run`.
+pub async fn clean_orphans(state: &AppState, dry_run: bool) -> Result<OrphanReport, String> {
+    let (file_ids, tables) = {
+        let conn = state.db.lock().await;
+        let file_ids = load_file_ids(&conn).map_err(|e| e.to_string())?;
+        let tables = orphan_tables(&conn).map_err(|e| e.to_string())?;
+        if !dry_run {
+            // Under the same lock as the check, so no import can claim them.
+            for table in &tables {
+                conn.execute(
+                    &format!("DROP TABLE IF EXISTS {}", quote_identifier(table)),
+                    [],
+                )
+                .map_err(|e| format!("Failed to drop {table}: {e}"))?;
+            }
+        }
+        (file_ids, tables)
+    };
+
+    let upload_dir = state.upload_dir.clone();
+    let directories = tokio::task::spawn_blocking(move || {
+        let now = SystemTime::now();
+        let mut directories = orphan_dirs(&upload_dir, &file_ids, SKIPPED_UPLOAD_DIRS, now);
+        directories.extend(orphan_dirs(
+            &tile_cache_root(&upload_dir),
+            &file_ids,
+            &[],
+            now,
+        ));
+        if !dry_run {
+            for dir in &directories {
+                std::fs::remove_dir_all(dir)
+                    .map_err(|e| format!("Failed to remove {}: {e}", dir.display()))?;
+            }
+        }
+        Ok::<_, String>(directories)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    Ok(OrphanReport {
+        directories,
+        tables,
+    })
+}
+
+fn load_file_ids(conn: &duckdb::Connection) -> Result<HashSet<String>, duckdb::Error> {
+    let mut stmt = conn.prepare("SELECT id FROM files")?;
+    let ids = stmt.query_map([], |row| row.get(0))?;
+    ids.collect()
+}
+
+/// `layer_*` tables no dataset points at. A dataset still importing has not
+/// recorded its table yet, so its `layer_<id>` is kept as well, and so are
+/// the versions datasets retain.
+fn orphan_tables(conn: &duckdb::Connection) -> Result<Vec<String>, duckdb::Error> {
+    let mut stmt = conn.prepare(
+        r"
+        SELECT t.table_name
+        FROM duckdb_tables() t
+        WHERE t.database_name = current_database()
+          AND t.schema_name = 'main'
+          AND t.table_name LIKE 'layer\_%' ESCAPE '\'
+          AND NOT EXISTS (
+              SELECT 1 FROM files f
+              WHERE f.table_name = t.table_name
+                 OR (f.status IN ('uploaded', 'processing') AND 'layer_' || f.id = t.table_name)
+          )
+          AND NOT EXISTS (
+              SELECT 1 FROM dataset_versions v JOIN files f ON f.id = v.file_id
+              WHERE v.table_name = t.table_name
+          )
+        ORDER BY t.table_name
+        ",
+    )?;
+    let tables = stmt.query_map([], |row| row.get(0))?;
+    tables.collect()
+}
+
+/// Subdirectories of `root` not named after a file id or in `keep`, and last
+/// modified at least `ORPHAN_MIN_AGE` before `now`.
+fn orphan_dirs(
+    root: &Path,
+    file_ids: &HashSet<String>,
+    keep: &[&str],
+    now: SystemTime,
+) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(root) else {
+        return Vec::new();
+    };
+    let mut orphans: Vec<PathBuf> = entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            !name.starts_with('.') && !keep.contains(&&*name) && !file_ids.contains(&*name)
+        })
+        .filter(|entry| {
+            entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| {
+                    now.duration_since(modified)
+                        .is_ok_and(|age| age >= ORPHAN_MIN_AGE)
+                })
+        })
+        .map(|entry| entry.path())
+        .collect();
+    orphans.sort();
+    orphans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_old_unknown_directories_are_orphans() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a1b2c3", "d4e5f6", "tile-cache", ".staging"] {
+            std::fs::create_dir(dir.path().join(name)).unwrap();
+        }
+        std::fs::write(dir.path().join("loose.txt"), b"x").unwrap();
+        let file_ids = HashSet::from(["a1b2c3".to_string()]);
+
+        let later = SystemTime::now() + ORPHAN_MIN_AGE;
+        assert_eq!(
+            orphan_dirs(dir.path(), &file_ids, &["tile-cache"], later),
+            vec![dir.path().join("d4e5f6")]
+        );
+        // Fresh directories may be uploads still being written.
+        assert!(orphan_dirs(dir.path(), &file_ids, &["tile-cache"], SystemTime::now()).is_empty());
+    }
+
+    #[test]
+    fn tables_of_importing_or_recorded_datasets_are_kept() {
+        let conn = duckdb::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r"
+            CREATE TABLE files (id VARCHAR, status VARCHAR, table_name VARCHAR);
+            INSERT INTO files VALUES
+                ('aaaaaa', 'ready', 'layer_aaaaaa'),
+                ('bbbbbb', 'processing', NULL),
+                ('cccccc', 'failed', NULL);
+            CREATE TABLE dataset_versions (file_id VARCHAR, table_name VARCHAR);
+            INSERT INTO dataset_versions VALUES
+                ('aaaaaa', 'layer_aaaaaa_v1'),
+                ('eeeeee', 'layer_eeeeee_v1');
+            CREATE TABLE layer_aaaaaa (fid BIGINT);
+            CREATE TABLE layer_aaaaaa_v1 (fid BIGINT);
+            CREATE TABLE layer_eeeeee_v1 (fid BIGINT);
+            CREATE TABLE layer_bbbbbb (fid BIGINT);
+            CREATE TABLE layer_cccccc (fid BIGINT);
+            CREATE TABLE layer_dddddd (fid BIGINT);
+            CREATE TABLE layerx (fid BIGINT);
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(
+            orphan_tables(&conn).unwrap(),
+            vec![
+                "layer_cccccc".to_string(),
+                "layer_dddddd".to_string(),
+                "layer_eeeeee_v1".to_string()
+            ]
+        );
+    }
+}
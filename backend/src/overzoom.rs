@@ -0,0 +1,606 @@
+//! Overzooming
+//!
+//! Beyond a dataset's native maxzoom (an MBTiles file's `maxzoom`, or the
+//! `maxZoom` tile option) tiles are cut from their ancestor at maxzoom instead
+//! of coming back empty. Vector tiles are decoded, each geometry is scaled up
+//! by `2^dz` around the requested quadrant and clipped to the tile plus a
+//! buffer, then encoded again with the same layers, keys and values. PNG tiles
+//! are cropped and scaled up with bilinear filtering.
+
+use std::io::Read;
+use std::path::Path;
+
+use tiny_skia::{FilterQuality, Pixmap, PixmapPaint, Transform};
+
+use crate::mbtiles::get_tile_from_mbtiles;
+use crate::models::TileOptions;
+use crate::pbf::{
+    command, fields, packed, read_packed, unzigzag, write_bytes, write_varint_field, zigzag,
+    Payload, CLOSE_PATH, FEATURE_GEOMETRY, FEATURE_TYPE, GEOMETRY_LINESTRING, GEOMETRY_POINT,
+    GEOMETRY_POLYGON, LAYER_EXTENT, LAYER_FEATURES, LINE_TO, MOVE_TO, TILE_LAYERS,
+};
+use crate::tile_options::{DEFAULT_BUFFER, DEFAULT_EXTENT};
+
+type Point = (f64, f64);
+
+/// Where a tile beyond maxzoom lies within its ancestor at maxzoom.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Overzoom {
+    /// The ancestor tile `(z, x, y)` at maxzoom.
+    pub parent: (i32, i32, i32),
+    /// Zoom levels between the ancestor and the requested tile.
+    dz: u32,
+    /// Column and row of the requested tile among the `2^dz` x `2^dz` tiles
+    /// covering the ancestor.
+    col: i64,
+    row: i64,
+}
+
+impl Overzoom {
+    /// How tile `z/x/y` is cut from its ancestor at `maxzoom`; `None` when it
+    /// is not beyond `maxzoom`.
+    pub fn new((z, x, y): (i32, i32, i32), maxzoom: i32) -> Option<Self> {
+        if z <= maxzoom || maxzoom < 0 {
+            return None;
+        }
+        let dz = u32::try_from(z - maxzoom).ok()?;
+        let parent = (maxzoom, x >> dz, y >> dz);
+        Some(Overzoom {
+            parent,
+            dz,
+            col: i64::from(x - (parent.1 << dz)),
+            row: i64::from(y - (parent.2 << dz)),
+        })
+    }
+
+    /// How a dataset's tile `z/x/y` is cut from its ancestor at the `maxZoom`
+    /// tile option, if it lies beyond it.
+    pub fn beyond_max_zoom(options: &TileOptions, tile: (i32, i32, i32)) -> Option<Self> {
+        options
+            .max_zoom
+            .and_then(|maxzoom| Overzoom::new(tile, i32::from(maxzoom)))
+    }
+
+    fn scale(&self) -> f64 {
+        f64::from(1u32 << self.dz)
+    }
+
+    /// The requested tile cut from the vector tile `parent`, keeping
+    /// `buffer` (a fraction of the tile side) around it. `None` when `parent`
+    /// is not a valid vector tile.
+    pub fn cut_mvt(&self, parent: &[u8], buffer: f64) -> Option<Vec<u8>> {
+        let mut tile = Vec::new();
+        for (field, payload) in fields(parent)? {
+            if field != TILE_LAYERS {
+                continue;
+            }
+            let Payload::Bytes(layer) = payload else {
+                return None;
+            };
+            if let Some(layer) = self.cut_layer(layer, buffer)? {
+                write_bytes(&mut tile, TILE_LAYERS, &layer);
+            }
+        }
+        Some(tile)
+    }
+
+    /// A layer with its features cut down; `Some(None)` when none are left.
+    fn cut_layer(&self, layer: &[u8], buffer: f64) -> Option<Option<Vec<u8>>> {
+        let layer_fields = fields(layer)?;
+        let extent = layer_fields
+            .iter()
+            .find_map(|(field, payload)| match payload {
+                Payload::Varint(extent) if *field == LAYER_EXTENT => Some(*extent as f64),
+                _ => None,
+            })
+            .unwrap_or(f64::from(DEFAULT_EXTENT));
+        let bounds = (-buffer * extent, extent + buffer * extent);
+
+        let mut out = Vec::new();
+        let mut kept = 0;
+        for (field, payload) in layer_fields {
+            match (field, payload) {
+                (LAYER_FEATURES, Payload::Bytes(feature)) => {
+                    if let Some(feature) = self.cut_feature(feature, extent, bounds)? {
+                        write_bytes(&mut out, LAYER_FEATURES, &feature);
+                        kept += 1;
+                    }
+                }
+                (field, Payload::Varint(value)) => write_varint_field(&mut out, field, value),
+                (field, Payload::Bytes(bytes)) => write_bytes(&mut out, field, bytes),
+                // Neither layers nor features have fixed-width fields.
+                (_, Payload::Fixed) => {}
+            }
+        }
+        Some((kept > 0).then_some(out))
+    }
+
+    /// A feature with its geometry cut down; `Some(None)` when nothing of it
+    /// is left.
+    fn cut_feature(
+        &self,
+        feature: &[u8],
+        extent: f64,
+        bounds: (f64, f64),
+    ) -> Option<Option<Vec<u8>>> {
+        let feature_fields = fields(feature)?;
+        let geometry_type = feature_fields
+            .iter()
+            .find_map(|(field, payload)| match payload {
+                Payload::Varint(value) if *field == FEATURE_TYPE => Some(*value),
+                _ => None,
+            })
+            .unwrap_or(0);
+        let scale = self.scale();
+        let (ox, oy) = (
+            self.col as f64 * extent / scale,
+            self.row as f64 * extent / scale,
+        );
+
+        let mut out = Vec::new();
+        for (field, payload) in feature_fields {
+            match (field, payload) {
+                (FEATURE_GEOMETRY, Payload::Bytes(geometry)) => {
+                    let paths: Vec<Vec<Point>> = decode_geometry(&read_packed(geometry)?)?
+                        .into_iter()
+                        .map(|path| {
+                            path.into_iter()
+                                .map(|(x, y)| ((x - ox) * scale, (y - oy) * scale))
+                                .collect()
+                        })
+                        .collect();
+                    let commands = match geometry_type {
+                        GEOMETRY_POINT => encode_points(clip_points(paths, bounds)),
+                        GEOMETRY_LINESTRING => encode_lines(clip_lines(paths, bounds)),
+                        GEOMETRY_POLYGON => {
+                            encode_rings(paths.iter().map(|ring| clip_ring(ring, bounds)).collect())
+                        }
+                        _ => return Some(None),
+                    };
+                    if commands.is_empty() {
+                        return Some(None);
+                    }
+                    write_bytes(&mut out, FEATURE_GEOMETRY, &packed(&commands));
+                }
+                (field, Payload::Varint(value)) => write_varint_field(&mut out, field, value),
+                (field, Payload::Bytes(bytes)) => write_bytes(&mut out, field, bytes),
+                (_, Payload::Fixed) => {}
+            }
+        }
+        Some(Some(out))
+    }
+
+    /// The requested tile cropped from the PNG tile `parent` and scaled up to
+    /// the same size.
+    pub fn cut_png(&self, parent: &[u8]) -> Result<Vec<u8>, String> {
+        let source = Pixmap::decode_png(parent).map_err(|e| e.to_string())?;
+        let (width, height) = (source.width(), source.height());
+        let mut pixmap = Pixmap::new(width, height).ok_or("Invalid tile size")?;
+        let scale = self.scale() as f32;
+        let transform = Transform::from_row(
+            scale,
+            0.0,
+            0.0,
+            scale,
+            -(self.col as f32) * width as f32,
+            -(self.row as f32) * height as f32,
+        );
+        let paint = PixmapPaint {
+            quality: FilterQuality::Bilinear,
+            ..PixmapPaint::default()
+        };
+        pixmap.draw_pixmap(0, 0, source.as_ref(), &paint, transform, None);
+        pixmap.encode_png().map_err(|e| e.to_string())
+    }
+}
+
+/// A vector tile generated for `overzoom`'s ancestor cut down to the requested
+/// tile, keeping the dataset's buffer; `tile` as is when not overzoomed.
+pub fn overzoom_mvt(
+    overzoom: Option<&Overzoom>,
+    tile: Vec<u8>,
+    options: &TileOptions,
+) -> Result<Vec<u8>, String> {
+    let Some(overzoom) = overzoom else {
+        return Ok(tile);
+    };
+    let buffer = f64::from(options.buffer()) / f64::from(options.extent());
+    overzoom
+        .cut_mvt(&tile, buffer)
+        .ok_or_else(|| "Tile is not a valid vector tile".to_string())
+}
+
+/// A tile of an MBTiles file; beyond `maxzoom` it is cut from the ancestor at
+/// `maxzoom` and returned uncompressed.
+pub async fn mbtiles_tile(
+    path: &Path,
+    format: &str,
+    tile: (i32, i32, i32),
+    maxzoom: Option<i32>,
+) -> Result<Option<Vec<u8>>, String> {
+    let (z, x, y) = tile;
+    let Some(overzoom) = maxzoom.and_then(|maxzoom| Overzoom::new(tile, maxzoom)) else {
+        return get_tile_from_mbtiles(path, z, x, y).await;
+    };
+    let (pz, px, py) = overzoom.parent;
+    let Some(parent) = get_tile_from_mbtiles(path, pz, px, py).await? else {
+        return Ok(None);
+    };
+    match format {
+        "mvt" => {
+            let parent = if parent.starts_with(&[0x1f, 0x8b]) {
+                let mut raw = Vec::new();
+                flate2::read::GzDecoder::new(&parent[..])
+                    .read_to_end(&mut raw)
+                    .map_err(|e| format!("Cannot decompress tile: {}", e))?;
+                raw
+            } else {
+                parent
+            };
+            let buffer = f64::from(DEFAULT_BUFFER) / f64::from(DEFAULT_EXTENT);
+            let tile = overzoom
+                .cut_mvt(&parent, buffer)
+                .ok_or("Tile is not a valid vector tile")?;
+            Ok((!tile.is_empty()).then_some(tile))
+        }
+        "png" => overzoom.cut_png(&parent).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// The paths of an MVT geometry in tile pixels: one per point, line or ring.
+fn decode_geometry(commands: &[u64]) -> Option<Vec<Vec<Point>>> {
+    let mut paths: Vec<Vec<Point>> = Vec::new();
+    let (mut x, mut y) = (0i64, 0i64);
+    let mut pos = 0;
+    while pos < commands.len() {
+        let (id, count) = (commands[pos] & 0x7, commands[pos] >> 3);
+        pos += 1;
+        match id {
+            MOVE_TO | LINE_TO => {
+                for _ in 0..count {
+                    x += unzigzag(*commands.get(pos)?);
+                    y += unzigzag(*commands.get(pos + 1)?);
+                    pos += 2;
+                    if id == MOVE_TO {
+                        paths.push(Vec::new());
+                    }
+                    paths.last_mut()?.push((x as f64, y as f64));
+                }
+            }
+            CLOSE_PATH => {}
+            _ => return None,
+        }
+    }
+    Some(paths)
+}
+
+fn inside((x, y): Point, (min, max): (f64, f64)) -> bool {
+    let range = min..=max;
+    range.contains(&x) && range.contains(&y)
+}
+
+fn clip_points(paths: Vec<Vec<Point>>, bounds: (f64, f64)) -> Vec<Point> {
+    paths
+        .into_iter()
+        .flatten()
+        .filter(|point| inside(*point, bounds))
+        .collect()
+}
+
+/// The part of segment `a`-`b` inside `bounds` (Liang–Barsky).
+fn clip_segment(a: Point, b: Point, (min, max): (f64, f64)) -> Option<(Point, Point)> {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let (mut t0, mut t1) = (0.0f64, 1.0f64);
+    for (p, q) in [
+        (-dx, a.0 - min),
+        (dx, max - a.0),
+        (-dy, a.1 - min),
+        (dy, max - a.1),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let r = q / p;
+            if p < 0.0 {
+                if r > t1 {
+                    return None;
+                }
+                t0 = t0.max(r);
+            } else {
+                if r < t0 {
+                    return None;
+                }
+                t1 = t1.min(r);
+            }
+        }
+    }
+    let at = |t: f64| (a.0 + t * dx, a.1 + t * dy);
+    Some((at(t0), at(t1)))
+}
+
+/// Lines clipped to `bounds`; a line leaving and re-entering becomes two.
+fn clip_lines(paths: Vec<Vec<Point>>, bounds: (f64, f64)) -> Vec<Vec<Point>> {
+    let mut lines = Vec::new();
+    for path in paths {
+        let mut current: Vec<Point> = Vec::new();
+        for segment in path.windows(2) {
+            match clip_segment(segment[0], segment[1], bounds) {
+                Some((start, end)) => {
+                    if current.last() != Some(&start) {
+                        if current.len() > 1 {
+                            lines.push(std::mem::take(&mut current));
+                        }
+                        current = vec![start];
+                    }
+                    current.push(end);
+                }
+                None => {
+                    if current.len() > 1 {
+                        lines.push(current);
+                    }
+                    current = Vec::new();
+                }
+            }
+        }
+        if current.len() > 1 {
+            lines.push(current);
+        }
+    }
+    lines
+}
+
+/// A polygon ring clipped to `bounds` (Sutherland–Hodgman), keeping its
+/// winding order.
+fn clip_ring(ring: &[Point], (min, max): (f64, f64)) -> Vec<Point> {
+    let mut out = ring.to_vec();
+    for edge in 0..4 {
+        let input = std::mem::take(&mut out);
+        let Some(&last) = input.last() else {
+            break;
+        };
+        let keeps = |(x, y): Point| match edge {
+            0 => x >= min,
+            1 => x <= max,
+            2 => y >= min,
+            _ => y <= max,
+        };
+        let crossing = |a: Point, b: Point| {
+            if edge < 2 {
+                let x = if edge == 0 { min } else { max };
+                (x, a.1 + (x - a.0) / (b.0 - a.0) * (b.1 - a.1))
+            } else {
+                let y = if edge == 2 { min } else { max };
+                (a.0 + (y - a.1) / (b.1 - a.1) * (b.0 - a.0), y)
+            }
+        };
+        let mut previous = last;
+        for &point in &input {
+            if keeps(point) {
+                if !keeps(previous) {
+                    out.push(crossing(previous, point));
+                }
+                out.push(point);
+            } else if keeps(previous) {
+                out.push(crossing(previous, point));
+            }
+            previous = point;
+        }
+    }
+    out
+}
+
+/// Points rounded to whole pixels, without consecutive repeats.
+fn round_path(path: &[Point]) -> Vec<(i64, i64)> {
+    let mut rounded: Vec<(i64, i64)> = Vec::with_capacity(path.len());
+    for (x, y) in path {
+        let point = (x.round() as i64, y.round() as i64);
+        if rounded.last() != Some(&point) {
+            rounded.push(point);
+        }
+    }
+    rounded
+}
+
+/// Geometry commands with cursor-relative parameters.
+struct Encoder {
+    commands: Vec<u64>,
+    cursor: (i64, i64),
+}
+
+impl Encoder {
+    fn new() -> Self {
+        Encoder {
+            commands: Vec::new(),
+            cursor: (0, 0),
+        }
+    }
+
+    fn points(&mut self, id: u64, points: &[(i64, i64)]) {
+        if points.is_empty() {
+            return;
+        }
+        self.commands.push(command(id, points.len() as u64));
+        for &(x, y) in points {
+            self.commands.push(zigzag(x - self.cursor.0));
+            self.commands.push(zigzag(y - self.cursor.1));
+            self.cursor = (x, y);
+        }
+    }
+}
+
+fn encode_points(points: Vec<Point>) -> Vec<u64> {
+    let points: Vec<(i64, i64)> = points
+        .iter()
+        .map(|(x, y)| (x.round() as i64, y.round() as i64))
+        .collect();
+    let mut encoder = Encoder::new();
+    encoder.points(MOVE_TO, &points);
+    encoder.commands
+}
+
+fn encode_lines(lines: Vec<Vec<Point>>) -> Vec<u64> {
+    let mut encoder = Encoder::new();
+    for line in lines {
+        let line = round_path(&line);
+        if line.len() < 2 {
+            continue;
+        }
+        encoder.points(MOVE_TO, &line[..1]);
+        encoder.points(LINE_TO, &line[1..]);
+    }
+    encoder.commands
+}
+
+fn encode_rings(rings: Vec<Vec<Point>>) -> Vec<u64> {
+    let mut encoder = Encoder::new();
+    for ring in rings {
+        let mut ring = round_path(&ring);
+        if ring.len() > 1 && ring.first() == ring.last() {
+            ring.pop();
+        }
+        if ring.len() < 3 {
+            continue;
+        }
+        encoder.points(MOVE_TO, &ring[..1]);
+        encoder.points(LINE_TO, &ring[1..]);
+        encoder.commands.push(command(CLOSE_PATH, 1));
+    }
+    encoder.commands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feature(geometry_type: u64, commands: &[u64]) -> Vec<u8> {
+        let mut feature = Vec::new();
+        write_varint_field(&mut feature, FEATURE_TYPE, geometry_type);
+        write_bytes(&mut feature, FEATURE_GEOMETRY, &packed(commands));
+        feature
+    }
+
+    fn geometries(tile: &[u8]) -> Vec<Vec<Vec<Point>>> {
+        let mut geometries = Vec::new();
+        for (_, layer) in fields(tile).unwrap() {
+            let Payload::Bytes(layer) = layer else {
+                panic!()
+            };
+            for (field, feature) in fields(layer).unwrap() {
+                let (LAYER_FEATURES, Payload::Bytes(feature)) = (field, feature) else {
+                    continue;
+                };
+                for (field, geometry) in fields(feature).unwrap() {
+                    if let (FEATURE_GEOMETRY, Payload::Bytes(geometry)) = (field, geometry) {
+                        geometries.push(decode_geometry(&read_packed(geometry).unwrap()).unwrap());
+                    }
+                }
+            }
+        }
+        geometries
+    }
+
+    #[test]
+    fn tiles_beyond_maxzoom_are_cut_from_their_ancestor() {
+        assert_eq!(Overzoom::new((5, 3, 3), 5), None);
+        let overzoom = Overzoom::new((7, 13, 6), 5).unwrap();
+        assert_eq!(overzoom.parent, (5, 3, 1));
+        assert_eq!((overzoom.col, overzoom.row), (1, 2));
+
+        // The requested tile is the lower left quarter of a 2x2 split.
+        let overzoom = Overzoom::new((1, 0, 1), 0).unwrap();
+        let mut layer = Vec::new();
+        write_bytes(&mut layer, 1, b"roads");
+        write_varint_field(&mut layer, LAYER_EXTENT, 4096);
+        // A point in the quarter and one outside it.
+        let points = [
+            command(MOVE_TO, 2),
+            zigzag(1024),
+            zigzag(3072),
+            zigzag(2048),
+            zigzag(-2048),
+        ];
+        write_bytes(
+            &mut layer,
+            LAYER_FEATURES,
+            &feature(GEOMETRY_POINT, &points),
+        );
+        // A line across the whole parent tile.
+        let line = [
+            command(MOVE_TO, 1),
+            zigzag(0),
+            zigzag(4096),
+            command(LINE_TO, 1),
+            zigzag(4096),
+            zigzag(-4096),
+        ];
+        write_bytes(
+            &mut layer,
+            LAYER_FEATURES,
+            &feature(GEOMETRY_LINESTRING, &line),
+        );
+        // A square in the upper right quarter only.
+        let square = [
+            command(MOVE_TO, 1),
+            zigzag(2500),
+            zigzag(500),
+            command(LINE_TO, 3),
+            zigzag(1000),
+            zigzag(0),
+            zigzag(0),
+            zigzag(1000),
+            zigzag(-1000),
+            zigzag(0),
+            command(CLOSE_PATH, 1),
+        ];
+        write_bytes(
+            &mut layer,
+            LAYER_FEATURES,
+            &feature(GEOMETRY_POLYGON, &square),
+        );
+        let mut parent = Vec::new();
+        write_bytes(&mut parent, TILE_LAYERS, &layer);
+
+        let tile = overzoom.cut_mvt(&parent, 0.0).unwrap();
+        let geometries = geometries(&tile);
+        assert_eq!(geometries.len(), 2);
+        assert_eq!(geometries[0], vec![vec![(2048.0, 2048.0)]]);
+        assert_eq!(geometries[1], vec![vec![(0.0, 4096.0), (4096.0, 0.0)]]);
+
+        // Nothing is left of a parent without features in the quarter.
+        let mut empty_layer = Vec::new();
+        write_bytes(
+            &mut empty_layer,
+            LAYER_FEATURES,
+            &feature(GEOMETRY_POLYGON, &square),
+        );
+        let mut parent = Vec::new();
+        write_bytes(&mut parent, TILE_LAYERS, &empty_layer);
+        assert_eq!(overzoom.cut_mvt(&parent, 0.0), Some(Vec::new()));
+        assert_eq!(overzoom.cut_mvt(&[0xff], 0.0), None);
+    }
+
+    #[test]
+    fn rings_are_clipped_to_the_buffered_tile() {
+        let ring = [
+            (-100.0, -100.0),
+            (500.0, -100.0),
+            (500.0, 500.0),
+            (-100.0, 500.0),
+        ];
+        assert_eq!(
+            clip_ring(&ring, (0.0, 256.0)),
+            vec![(0.0, 256.0), (0.0, 0.0), (256.0, 0.0), (256.0, 256.0)]
+        );
+        assert!(clip_ring(&ring, (600.0, 700.0)).is_empty());
+        assert_eq!(
+            clip_lines(
+                vec![vec![(-10.0, 5.0), (6.0, 5.0), (6.0, 25.0), (-10.0, 25.0)]],
+                (0.0, 10.0)
+            ),
+            vec![vec![(0.0, 5.0), (6.0, 5.0), (6.0, 10.0)]]
+        );
+    }
+}
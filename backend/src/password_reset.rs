@@ -0,0 +1,333 @@
+//! Password reset
+//!
+//! `POST /api/auth/reset-request` issues a single-use token for a username and
+//! `POST /api/auth/reset` trades it for a new password. An admin gets the
+//! token in the response to hand over. Anyone else gets the same generic
+//! answer whether or not the account exists; when `password_reset_command` is
+//! configured the token is delivered through it, say by mail, and otherwise
+//! the request is only logged, without a token, for the operator to follow up
+//! and any outstanding token is left alone. Anonymous requests are limited to
+//! one per username a minute. Only the token's SHA-256 is stored. Changing the
+//! password changes the session hash, so the user's existing sessions stop
+//! working.
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, routing::post, Json, Router};
+use axum_login::AuthSession;
+use chrono::{Duration, NaiveDateTime, Utc};
+use duckdb::OptionalExt;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+use tokio::io::AsyncWriteExt;
+
+use crate::auth::{AuthBackend, Role};
+use crate::http_errors::{bad_request, internal_error};
+use crate::ldap::LDAP_PASSWORD_HASH;
+use crate::models::{PasswordResetRequest, PasswordResetResponse, ResetPasswordRequest};
+use crate::{AppState, ErrorResponse};
+
+/// How long a reset token stays valid.
+pub const PASSWORD_RESET_TTL_SECS: i64 = 60 * 60;
+
+/// Anonymous requests allowed per username in this window.
+const ANONYMOUS_REQUEST_INTERVAL_SECS: u64 = 60;
+/// Usernames tracked at once; beyond this, anonymous requests are refused
+/// until the window passes.
+const MAX_TRACKED_USERNAMES: usize = 10_000;
+
+/// How long `password_reset_command` gets to deliver a token.
+const DELIVERY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+const RESET_REQUESTED_MESSAGE: &str =
+    "If the account exists, an administrator has been asked to reset its password";
+const RESET_SENT_MESSAGE: &str = "If the account exists, a reset token has been sent to its owner";
+
+/// Program and arguments, split on whitespace, that deliver a self-service
+/// reset token. It is sent `{username, token, expiresAt}` as JSON on stdin,
+/// so the token never shows in a process listing, and must exit 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResetDelivery(Vec<String>);
+
+impl ResetDelivery {
+    pub fn command(command: &str) -> Option<Self> {
+        let args: Vec<String> = command.split_whitespace().map(str::to_string).collect();
+        (!args.is_empty()).then_some(ResetDelivery(args))
+    }
+
+    async fn deliver(&self, username: &str, token: &str, expires_at: &str) -> Result<(), String> {
+        let args = &self.0;
+        let mut child = tokio::process::Command::new(&args[0])
+            .args(&args[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("could not run {}: {e}", args[0]))?;
+        let body = serde_json::json!({
+            "username": username,
+            "token": token,
+            "expiresAt": expires_at,
+        })
+        .to_string();
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        stdin
+            .write_all(body.as_bytes())
+            .await
+            .map_err(|e| format!("could not write to {}: {e}", args[0]))?;
+        drop(stdin);
+
+        let output = tokio::time::timeout(DELIVERY_TIMEOUT, child.wait_with_output())
+            .await
+            .map_err(|_| format!("{} timed out", args[0]))?
+            .map_err(|e| format!("{} failed: {e}", args[0]))?;
+        if output.status.success() {
+            return Ok(());
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(format!(
+            "{} exited with {}: {}",
+            args[0],
+            output.status,
+            stderr
+                .lines()
+                .map(str::trim)
+                .find(|l| !l.is_empty())
+                .unwrap_or_default()
+        ))
+    }
+}
+
+pub fn build_password_reset_router() -> Router<AppState> {
+    Router::new()
+        .route("/api/auth/reset-request", post(request_reset))
+        .route("/api/auth/reset", post(reset_password))
+}
+
+fn generate_reset_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+fn hash_reset_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Record an anonymous request for `username`, or refuse it if one came in
+/// the last `ANONYMOUS_REQUEST_INTERVAL_SECS`.
+fn allow_anonymous_request(username: &str, now: Instant) -> bool {
+    static RECENT: OnceLock<Mutex<HashMap<String, Instant>>> = OnceLock::new();
+    let mut recent = RECENT
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let interval = std::time::Duration::from_secs(ANONYMOUS_REQUEST_INTERVAL_SECS);
+    recent.retain(|_, at| now.duration_since(*at) < interval);
+    let key = username.to_lowercase();
+    if recent.contains_key(&key) || recent.len() >= MAX_TRACKED_USERNAMES {
+        return false;
+    }
+    recent.insert(key, now);
+    true
+}
+
+/// Whether a token with this expiry and use time can still be redeemed.
+fn token_usable(
+    expires_at: NaiveDateTime,
+    used_at: Option<NaiveDateTime>,
+    now: NaiveDateTime,
+) -> bool {
+    used_at.is_none() && now < expires_at
+}
+
+async fn request_reset(
+    State(state): State<AppState>,
+    auth_session: AuthSession<AuthBackend>,
+    Json(req): Json<PasswordResetRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let username = req.username.trim();
+    let is_admin = auth_session
+        .user
+        .is_some_and(|user| user.role() == Role::Admin);
+
+    if !is_admin && !allow_anonymous_request(username, Instant::now()) {
+        return Err((
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(ErrorResponse {
+                error: "Too many reset requests; try again later".to_string(),
+            }),
+        ));
+    }
+
+    let conn = state.db.lock().await;
+    // Directory users change their password in the directory.
+    let user_id: Option<String> = conn
+        .query_row(
+            "SELECT id FROM users WHERE username = ? AND password_hash <> ?",
+            duckdb::params![username, LDAP_PASSWORD_HASH],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(internal_error)?;
+    // The same answer whether or not the account exists.
+    let delivery = state.password_reset_delivery.clone().filter(|_| !is_admin);
+    let mut response = PasswordResetResponse {
+        message: if delivery.is_some() {
+            RESET_SENT_MESSAGE
+        } else {
+            RESET_REQUESTED_MESSAGE
+        }
+        .to_string(),
+        token: None,
+        expires_at: None,
+    };
+    let Some(user_id) = user_id else {
+        return Ok((StatusCode::ACCEPTED, Json(response)));
+    };
+    if !is_admin && delivery.is_none() {
+        // Nothing could deliver a token, so none is issued; an admin can.
+        tracing::warn!(%username, "Password reset requested");
+        return Ok((StatusCode::ACCEPTED, Json(response)));
+    }
+
+    let token = generate_reset_token();
+    let now = Utc::now().naive_utc();
+    let expires_at = now + Duration::seconds(PASSWORD_RESET_TTL_SECS);
+    // Only the newest token for a user is valid.
+    conn.execute(
+        "DELETE FROM password_reset_tokens WHERE user_id = ?",
+        duckdb::params![&user_id],
+    )
+    .map_err(internal_error)?;
+    conn.execute(
+        "INSERT INTO password_reset_tokens (token_hash, user_id, created_at, expires_at) VALUES (?, ?, ?, ?)",
+        duckdb::params![hash_reset_token(&token), &user_id, now, expires_at],
+    )
+    .map_err(internal_error)?;
+    drop(conn);
+    let expires_at = expires_at.and_utc().to_rfc3339();
+
+    if let Some(delivery) = delivery {
+        // In the background, so the response takes as long whether or not the
+        // account exists.
+        let username = username.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = delivery.deliver(&username, &token, &expires_at).await {
+                tracing::warn!(%username, error = %e, "Failed to deliver password reset token");
+            }
+        });
+        return Ok((StatusCode::ACCEPTED, Json(response)));
+    }
+    response.token = Some(token);
+    response.expires_at = Some(expires_at);
+    Ok((StatusCode::ACCEPTED, Json(response)))
+}
+
+async fn reset_password(
+    State(state): State<AppState>,
+    Json(req): Json<ResetPasswordRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    crate::validate_password_complexity(&req.password)
+        .map_err(|e| bad_request(&format!("Invalid password: {e}")))?;
+    let token_hash = hash_reset_token(req.token.trim());
+    let invalid_token = || bad_request("Invalid or expired reset token");
+
+    // Check the token before hashing: bcrypt is deliberately slow, and only
+    // someone holding a token should be able to make the server run it.
+    let token: Option<(NaiveDateTime, Option<NaiveDateTime>)> = {
+        let conn = state.db.lock().await;
+        conn.query_row(
+            "SELECT expires_at, used_at FROM password_reset_tokens WHERE token_hash = ?",
+            duckdb::params![&token_hash],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(internal_error)?
+    };
+    match token {
+        Some((expires_at, used_at))
+            if token_usable(expires_at, used_at, Utc::now().naive_utc()) => {}
+        _ => return Err(invalid_token()),
+    }
+    // Outside the lock, which the hash would hold up.
+    let password_hash = crate::hash_password(&req.password).map_err(internal_error)?;
+
+    // Spend the token and change the password together. The token is checked
+    // again: it may have been used or replaced while the password was hashed.
+    let conn = state.db.lock().await;
+    conn.execute_batch("BEGIN TRANSACTION")
+        .map_err(internal_error)?;
+    let result = redeem_token(&conn, &token_hash, &password_hash, Utc::now().naive_utc());
+    match result {
+        Ok(true) => conn.execute_batch("COMMIT").map_err(internal_error)?,
+        Ok(false) => {
+            conn.execute_batch("ROLLBACK").map_err(internal_error)?;
+            return Err(invalid_token());
+        }
+        Err(e) => {
+            let _ = conn.execute_batch("ROLLBACK");
+            return Err(internal_error(e));
+        }
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Mark the token used and set its user's password; false when the token is
+/// no longer usable.
+fn redeem_token(
+    conn: &duckdb::Connection,
+    token_hash: &str,
+    password_hash: &str,
+    now: NaiveDateTime,
+) -> Result<bool, duckdb::Error> {
+    let user_id: Option<String> = conn
+        .query_row(
+            "UPDATE password_reset_tokens SET used_at = ?
+             WHERE token_hash = ? AND used_at IS NULL AND expires_at > ?
+             RETURNING user_id",
+            duckdb::params![now, token_hash, now],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let Some(user_id) = user_id else {
+        return Ok(false);
+    };
+    conn.execute(
+        "UPDATE users SET password_hash = ? WHERE id = ?",
+        duckdb::params![password_hash, &user_id],
+    )?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_are_single_use_and_expire() {
+        let now = Utc::now().naive_utc();
+        let expires_at = now + Duration::seconds(PASSWORD_RESET_TTL_SECS);
+        assert!(token_usable(expires_at, None, now));
+        assert!(!token_usable(expires_at, Some(now), now));
+        assert!(!token_usable(expires_at, None, expires_at));
+
+        let token = generate_reset_token();
+        assert_eq!(token.len(), 64);
+        assert_ne!(hash_reset_token(&token), token);
+    }
+
+    #[test]
+    fn anonymous_requests_are_limited_per_username() {
+        let now = Instant::now();
+        assert!(allow_anonymous_request("limit-test", now));
+        assert!(!allow_anonymous_request("Limit-Test", now));
+        assert!(allow_anonymous_request("limit-test-other", now));
+        let later = now + std::time::Duration::from_secs(ANONYMOUS_REQUEST_INTERVAL_SECS);
+        assert!(allow_anonymous_request("limit-test", later));
+    }
+}
@@ -0,0 +1,137 @@
+//! Vector tile protobuf
+//!
+//! Just enough of the protobuf wire format to read and write vector tiles by
+//! hand: varints, length-delimited fields, packed repeated varints and the
+//! field numbers of the MVT messages. Used to add the debug layer
+//! (`tile_debug.rs`) and to cut overzoomed tiles (`overzoom.rs`).
+
+// Field numbers of the vector tile protobuf.
+pub const TILE_LAYERS: u32 = 3;
+pub const LAYER_NAME: u32 = 1;
+pub const LAYER_FEATURES: u32 = 2;
+pub const LAYER_KEYS: u32 = 3;
+pub const LAYER_VALUES: u32 = 4;
+pub const LAYER_EXTENT: u32 = 5;
+pub const LAYER_VERSION: u32 = 15;
+pub const FEATURE_ID: u32 = 1;
+pub const FEATURE_TAGS: u32 = 2;
+pub const FEATURE_TYPE: u32 = 3;
+pub const FEATURE_GEOMETRY: u32 = 4;
+
+pub const GEOMETRY_POINT: u64 = 1;
+pub const GEOMETRY_LINESTRING: u64 = 2;
+pub const GEOMETRY_POLYGON: u64 = 3;
+
+// Geometry command ids.
+pub const MOVE_TO: u64 = 1;
+pub const LINE_TO: u64 = 2;
+pub const CLOSE_PATH: u64 = 7;
+
+pub const WIRE_VARINT: u64 = 0;
+pub const WIRE_FIXED64: u64 = 1;
+pub const WIRE_LEN: u64 = 2;
+pub const WIRE_FIXED32: u64 = 5;
+
+/// A geometry command integer: `id` repeated `count` times.
+pub fn command(id: u64, count: u64) -> u64 {
+    (id & 0x7) | (count << 3)
+}
+
+pub fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+pub fn unzigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+pub fn write_key(out: &mut Vec<u8>, field: u32, wire_type: u64) {
+    write_varint(out, (u64::from(field) << 3) | wire_type);
+}
+
+pub fn write_varint_field(out: &mut Vec<u8>, field: u32, value: u64) {
+    write_key(out, field, WIRE_VARINT);
+    write_varint(out, value);
+}
+
+pub fn write_bytes(out: &mut Vec<u8>, field: u32, bytes: &[u8]) {
+    write_key(out, field, WIRE_LEN);
+    write_varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+pub fn packed(values: &[u64]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for value in values {
+        write_varint(&mut out, *value);
+    }
+    out
+}
+
+/// The values of a packed repeated varint field, or `None` when malformed.
+pub fn read_packed(buf: &[u8]) -> Option<Vec<u64>> {
+    let mut values = Vec::new();
+    let mut pos = 0;
+    while pos < buf.len() {
+        values.push(read_varint(buf, &mut pos)?);
+    }
+    Some(values)
+}
+
+pub enum Payload<'a> {
+    Varint(u64),
+    Fixed,
+    Bytes(&'a [u8]),
+}
+
+fn read_varint(buf: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *buf.get(*pos)?;
+        *pos += 1;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// The fields of a protobuf message, or `None` when it is malformed.
+pub fn fields(buf: &[u8]) -> Option<Vec<(u32, Payload<'_>)>> {
+    let mut fields = Vec::new();
+    let mut pos = 0;
+    while pos < buf.len() {
+        let key = read_varint(buf, &mut pos)?;
+        let field = u32::try_from(key >> 3).ok()?;
+        let payload = match key & 0x7 {
+            WIRE_VARINT => Payload::Varint(read_varint(buf, &mut pos)?),
+            WIRE_FIXED64 => {
+                pos = pos.checked_add(8).filter(|end| *end <= buf.len())?;
+                Payload::Fixed
+            }
+            WIRE_LEN => {
+                let len = usize::try_from(read_varint(buf, &mut pos)?).ok()?;
+                let end = pos.checked_add(len).filter(|end| *end <= buf.len())?;
+                let bytes = &buf[pos..end];
+                pos = end;
+                Payload::Bytes(bytes)
+            }
+            WIRE_FIXED32 => {
+                pos = pos.checked_add(4).filter(|end| *end <= buf.len())?;
+                Payload::Fixed
+            }
+            _ => return None,
+        };
+        fields.push((field, payload));
+    }
+    Some(fields)
+}
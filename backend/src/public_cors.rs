@@ -0,0 +1,194 @@
+//! CORS for public tiles
+//!
+//! Maps fetching public tiles are embedded anywhere, so routes under
+//! `/tiles/{slug}` are served to any origin (`*`, never with credentials)
+//! instead of going through the app's `CORS_ALLOWED_ORIGINS`. A publish can
+//! list the only origins its slug answers (`allowedOrigins`): a listed origin
+//! is echoed back with `Vary: Origin`, any other gets no CORS headers and is
+//! blocked by the browser. Tilesets and unknown slugs allow any origin.
+//! Preflight requests are answered here without reaching the handlers.
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use duckdb::OptionalExt;
+
+use crate::AppState;
+
+/// Most origins a single publish may list.
+pub const MAX_ALLOWED_ORIGINS: usize = 50;
+const PREFLIGHT_MAX_AGE_SECS: u64 = 24 * 60 * 60;
+
+/// Check and normalise a publish's origin list: `scheme://host[:port]`, no
+/// path, lowercased as browsers send them, trailing slashes dropped,
+/// duplicates removed.
+pub fn validate_allowed_origins(origins: Vec<String>) -> Result<Vec<String>, String> {
+    if origins.is_empty() {
+        return Err("allowedOrigins must not be empty".to_string());
+    }
+    if origins.len() > MAX_ALLOWED_ORIGINS {
+        return Err(format!(
+            "allowedOrigins can list at most {MAX_ALLOWED_ORIGINS} origins"
+        ));
+    }
+    let mut normalized: Vec<String> = Vec::with_capacity(origins.len());
+    for origin in origins {
+        let origin = origin.trim().trim_end_matches('/').to_ascii_lowercase();
+        let origin = origin.as_str();
+        let host = origin
+            .strip_prefix("https://")
+            .or_else(|| origin.strip_prefix("http://"));
+        let valid = host.is_some_and(|host| {
+            !host.is_empty()
+                && !host.contains(['/', '?', '#', '*'])
+                && HeaderValue::from_str(origin).is_ok()
+        });
+        if !valid {
+            return Err(format!(
+                "Invalid origin '{origin}': use scheme://host[:port]"
+            ));
+        }
+        if !normalized.iter().any(|existing| existing == origin) {
+            normalized.push(origin.to_string());
+        }
+    }
+    Ok(normalized)
+}
+
+/// The origins the publish under `slug` is limited to; `None` when any origin
+/// may fetch it.
+fn allowed_origins(
+    conn: &duckdb::Connection,
+    slug: &str,
+) -> Result<Option<Vec<String>>, duckdb::Error> {
+    let stored: Option<String> = conn
+        .query_row(
+            "SELECT allowed_origins FROM published_files WHERE slug = ?",
+            duckdb::params![slug],
+            |row| row.get(0),
+        )
+        .optional()?
+        .flatten();
+    // An unreadable list allows nothing rather than everything.
+    Ok(stored.map(|json| serde_json::from_str(&json).unwrap_or_default()))
+}
+
+/// The `Access-Control-Allow-Origin` a request from `origin` gets, if any.
+fn allow_origin(allowed: Option<&[String]>, origin: Option<&str>) -> Option<String> {
+    match allowed {
+        None => Some("*".to_string()),
+        Some(allowed) => origin
+            .filter(|origin| allowed.iter().any(|allowed| allowed == origin))
+            .map(str::to_string),
+    }
+}
+
+pub async fn public_cors(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let Some(slug) = request
+        .uri()
+        .path()
+        .strip_prefix("/tiles/")
+        .and_then(|rest| rest.split('/').next())
+        .filter(|slug| !slug.is_empty())
+        .map(str::to_string)
+    else {
+        return next.run(request).await;
+    };
+    let origin = request
+        .headers()
+        .get(header::ORIGIN)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let allowed = match state.read_pool.get().await {
+        Ok(conn) => allowed_origins(&conn, &slug),
+        Err(e) => Err(e),
+    };
+    let allowed = match allowed {
+        Ok(allowed) => allowed,
+        Err(e) => {
+            tracing::warn!(%slug, error = %e, "Failed to look up allowed origins");
+            Some(Vec::new())
+        }
+    };
+    let allow = allow_origin(allowed.as_deref(), origin.as_deref())
+        .and_then(|value| HeaderValue::from_str(&value).ok());
+
+    let preflight = request.method() == Method::OPTIONS
+        && request
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+    let mut response = if preflight {
+        let mut response = StatusCode::NO_CONTENT.into_response();
+        if allow.is_some() {
+            let headers = response.headers_mut();
+            headers.insert(
+                header::ACCESS_CONTROL_ALLOW_METHODS,
+                HeaderValue::from_static("GET, OPTIONS"),
+            );
+            headers.insert(
+                header::ACCESS_CONTROL_ALLOW_HEADERS,
+                HeaderValue::from_static("*"),
+            );
+            headers.insert(
+                header::ACCESS_CONTROL_MAX_AGE,
+                HeaderValue::from(PREFLIGHT_MAX_AGE_SECS),
+            );
+        }
+        response
+    } else {
+        next.run(request).await
+    };
+
+    let headers = response.headers_mut();
+    if let Some(allow) = allow {
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allow);
+    }
+    if allowed.is_some() {
+        headers.append(header::VARY, HeaderValue::from_static("origin"));
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn origins_are_normalised_and_matched_exactly() {
+        assert_eq!(
+            validate_allowed_origins(vec![
+                " https://maps.example.com/ ".to_string(),
+                "http://localhost:8080".to_string(),
+                "HTTPS://Maps.Example.com".to_string(),
+            ]),
+            Ok(vec![
+                "https://maps.example.com".to_string(),
+                "http://localhost:8080".to_string(),
+            ])
+        );
+        for invalid in [
+            "maps.example.com",
+            "https://",
+            "https://maps.example.com/app",
+            "https://*.example.com",
+        ] {
+            assert!(validate_allowed_origins(vec![invalid.to_string()]).is_err());
+        }
+        assert!(validate_allowed_origins(Vec::new()).is_err());
+
+        let allowed = ["https://maps.example.com".to_string()];
+        assert_eq!(allow_origin(None, None).as_deref(), Some("*"));
+        assert_eq!(
+            allow_origin(Some(&allowed), Some("https://maps.example.com")).as_deref(),
+            Some("https://maps.example.com")
+        );
+        assert_eq!(
+            allow_origin(Some(&allowed), Some("https://maps.example.com.evil.test")),
+            None
+        );
+        assert_eq!(allow_origin(Some(&allowed), None), None);
+    }
+}
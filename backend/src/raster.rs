@@ -0,0 +1,236 @@
+//! Vector to PNG rendering
+//!
+//! Draws GeoJSON geometries onto a PNG for the WMS and the `.png` tiles, in the
+//! colours and symbols of the generated MapLibre style: polygons filled at
+//! 0.35 opacity and outlined, lines stroked 1.5 px wide and points as 4 px
+//! circles with a white edge. Layers take the palette colours in order.
+
+use serde_json::Value;
+use tiny_skia::{
+    Color, FillRule, LineCap, LineJoin, Paint, PathBuilder, Pixmap, Stroke, Transform,
+};
+
+use crate::style::PALETTE;
+
+/// Part of a coordinate space that fills the canvas.
+pub struct Frame {
+    /// `[minx, miny, maxx, maxy]`.
+    pub bbox: [f64; 4],
+    /// `y` grows downwards, as in tile pixels, rather than upwards.
+    pub y_down: bool,
+}
+
+pub struct Canvas {
+    pixmap: Pixmap,
+}
+
+/// `0xRRGGBB` as in WMS `BGCOLOR`, or `#rrggbb` as in the style palette.
+pub fn parse_color(value: &str) -> Option<(u8, u8, u8)> {
+    let hex = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+        .or_else(|| value.strip_prefix('#'))?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// Palette colour of the `index`th layer.
+pub fn layer_color(index: usize) -> (u8, u8, u8) {
+    parse_color(PALETTE[index % PALETTE.len()]).unwrap_or((0, 0, 0))
+}
+
+impl Canvas {
+    /// A `width` x `height` canvas, transparent unless `background` is given.
+    pub fn new(width: u32, height: u32, background: Option<(u8, u8, u8)>) -> Option<Self> {
+        let mut pixmap = Pixmap::new(width, height)?;
+        if let Some((r, g, b)) = background {
+            pixmap.fill(Color::from_rgba8(r, g, b, 255));
+        }
+        Some(Self { pixmap })
+    }
+
+    /// Draw one layer's geometries, which are in `frame`'s coordinates.
+    pub fn draw_layer(&mut self, frame: &Frame, geometries: &[Value], color: (u8, u8, u8)) {
+        for geometry in geometries {
+            self.draw_geometry(frame, geometry, color);
+        }
+    }
+
+    pub fn encode_png(&self) -> Result<Vec<u8>, String> {
+        self.pixmap.encode_png().map_err(|e| e.to_string())
+    }
+
+    /// Pixel position of `(x, y)`.
+    fn pixel(&self, frame: &Frame, x: f64, y: f64) -> (f32, f32) {
+        let [minx, miny, maxx, maxy] = frame.bbox;
+        let row = if frame.y_down { y - miny } else { maxy - y };
+        (
+            ((x - minx) / (maxx - minx) * f64::from(self.pixmap.width())) as f32,
+            (row / (maxy - miny) * f64::from(self.pixmap.height())) as f32,
+        )
+    }
+
+    fn draw_geometry(&mut self, frame: &Frame, geometry: &Value, color: (u8, u8, u8)) {
+        let coordinates = &geometry["coordinates"];
+        let as_list = |value: &Value| value.as_array().cloned().unwrap_or_default();
+        match geometry["type"].as_str() {
+            Some("Point") => {
+                self.draw_points(frame, &Value::Array(vec![coordinates.clone()]), color)
+            }
+            Some("MultiPoint") => self.draw_points(frame, coordinates, color),
+            Some("LineString") => self.draw_lines(frame, &[coordinates.clone()], color),
+            Some("MultiLineString") => self.draw_lines(frame, &as_list(coordinates), color),
+            Some("Polygon") => self.draw_polygons(frame, &[coordinates.clone()], color),
+            Some("MultiPolygon") => self.draw_polygons(frame, &as_list(coordinates), color),
+            Some("GeometryCollection") => {
+                for part in geometry["geometries"].as_array().into_iter().flatten() {
+                    self.draw_geometry(frame, part, color);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn draw_points(&mut self, frame: &Frame, points: &Value, color: (u8, u8, u8)) {
+        for (x, y) in positions(points) {
+            let (px, py) = self.pixel(frame, x, y);
+            if let Some(circle) = PathBuilder::from_circle(px, py, 4.0) {
+                let transform = Transform::identity();
+                self.pixmap.fill_path(
+                    &circle,
+                    &paint(color, 255),
+                    FillRule::Winding,
+                    transform,
+                    None,
+                );
+                self.pixmap.stroke_path(
+                    &circle,
+                    &paint((255, 255, 255), 255),
+                    &stroke(1.0),
+                    transform,
+                    None,
+                );
+            }
+        }
+    }
+
+    fn draw_lines(&mut self, frame: &Frame, lines: &[Value], color: (u8, u8, u8)) {
+        let mut builder = PathBuilder::new();
+        for line in lines {
+            self.push_line(&mut builder, frame, line, false);
+        }
+        if let Some(path) = builder.finish() {
+            self.pixmap.stroke_path(
+                &path,
+                &paint(color, 255),
+                &stroke(1.5),
+                Transform::identity(),
+                None,
+            );
+        }
+    }
+
+    fn draw_polygons(&mut self, frame: &Frame, polygons: &[Value], color: (u8, u8, u8)) {
+        let mut builder = PathBuilder::new();
+        for rings in polygons {
+            for ring in rings.as_array().into_iter().flatten() {
+                self.push_line(&mut builder, frame, ring, true);
+            }
+        }
+        if let Some(path) = builder.finish() {
+            let transform = Transform::identity();
+            self.pixmap
+                .fill_path(&path, &paint(color, 89), FillRule::EvenOdd, transform, None);
+            self.pixmap
+                .stroke_path(&path, &paint(color, 255), &stroke(1.0), transform, None);
+        }
+    }
+
+    /// Add one line string or ring to `builder`.
+    fn push_line(&self, builder: &mut PathBuilder, frame: &Frame, line: &Value, close: bool) {
+        for (i, (x, y)) in positions(line).enumerate() {
+            let (px, py) = self.pixel(frame, x, y);
+            if i == 0 {
+                builder.move_to(px, py);
+            } else {
+                builder.line_to(px, py);
+            }
+        }
+        if close {
+            builder.close();
+        }
+    }
+}
+
+fn paint((r, g, b): (u8, u8, u8), alpha: u8) -> Paint<'static> {
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(r, g, b, alpha);
+    paint.anti_alias = true;
+    paint
+}
+
+fn stroke(width: f32) -> Stroke {
+    Stroke {
+        width,
+        line_cap: LineCap::Round,
+        line_join: LineJoin::Round,
+        ..Stroke::default()
+    }
+}
+
+/// Positions of a GeoJSON coordinate array.
+fn positions(coordinates: &Value) -> impl Iterator<Item = (f64, f64)> + '_ {
+    coordinates
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|position| Some((position.get(0)?.as_f64()?, position.get(1)?.as_f64()?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(png: &[u8], x: u32, y: u32) -> (u8, u8, u8, u8) {
+        let pixmap = Pixmap::decode_png(png).unwrap();
+        let color = pixmap.pixel(x, y).unwrap().demultiply();
+        (color.red(), color.green(), color.blue(), color.alpha())
+    }
+
+    #[test]
+    fn parses_bgcolor_and_palette_colors() {
+        assert_eq!(parse_color("0xFF8000"), Some((255, 128, 0)));
+        assert_eq!(parse_color("#3b82f6"), Some((0x3b, 0x82, 0xf6)));
+        assert_eq!(parse_color("red"), None);
+        assert_eq!(parse_color("0xFFF"), None);
+        assert_eq!(layer_color(PALETTE.len() + 1), layer_color(1));
+    }
+
+    #[test]
+    fn draws_points_in_the_layer_color() {
+        let point = serde_json::json!({ "type": "Point", "coordinates": [2.5, 7.5] });
+        let frame = Frame {
+            bbox: [0.0, 0.0, 10.0, 10.0],
+            y_down: false,
+        };
+        let mut canvas = Canvas::new(100, 100, Some((255, 255, 255))).unwrap();
+        canvas.draw_layer(&frame, &[point.clone()], layer_color(0));
+        let png = canvas.encode_png().unwrap();
+        assert_eq!(pixel(&png, 25, 25), (0x3b, 0x82, 0xf6, 255));
+        assert_eq!(pixel(&png, 75, 75), (255, 255, 255, 255));
+
+        // In tile pixels the same point is near the bottom.
+        let frame = Frame {
+            y_down: true,
+            ..frame
+        };
+        let mut canvas = Canvas::new(100, 100, None).unwrap();
+        canvas.draw_layer(&frame, &[point], layer_color(0));
+        let png = canvas.encode_png().unwrap();
+        assert_eq!(pixel(&png, 25, 75), (0x3b, 0x82, 0xf6, 255));
+        assert_eq!(pixel(&png, 25, 25).3, 0);
+    }
+}
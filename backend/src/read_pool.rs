@@ -0,0 +1,175 @@
+//! Read connections
+//!
+//! `AppState::db` is a single connection behind a mutex, so one slow tile or
+//! query used to hold up every other request. DuckDB serves concurrent readers
+//! within a database, so `ReadPool` hands out extra connections to the same
+//! database for handlers that only read; imports and other writes keep using
+//! `db`. Connections are cloned from the writer on first use and go back to
+//! the pool when dropped, with at most `size` out at once.
+//!
+//! DuckDB has no read-only mode per connection: nothing stops a pooled
+//! connection from writing, callers keep to reads.
+
+use std::ops::Deref;
+use std::sync::{Arc, Mutex as StdMutex, PoisonError};
+
+use duckdb::Connection;
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
+
+pub const DEFAULT_READ_POOL_SIZE: usize = 4;
+
+#[derive(Clone)]
+pub struct ReadPool {
+    inner: Arc<PoolInner>,
+}
+
+struct PoolInner {
+    writer: Arc<Mutex<Connection>>,
+    idle: StdMutex<Vec<Connection>>,
+    permits: Arc<Semaphore>,
+}
+
+impl ReadPool {
+    pub fn new(writer: Arc<Mutex<Connection>>) -> Self {
+        Self::build(writer, DEFAULT_READ_POOL_SIZE)
+    }
+
+    /// At most `size` read connections (at least one) are open at a time.
+    pub fn with_size(self, size: usize) -> Self {
+        Self::build(self.inner.writer.clone(), size)
+    }
+
+    fn build(writer: Arc<Mutex<Connection>>, size: usize) -> Self {
+        ReadPool {
+            inner: Arc::new(PoolInner {
+                writer,
+                idle: StdMutex::new(Vec::new()),
+                permits: Arc::new(Semaphore::new(size.max(1))),
+            }),
+        }
+    }
+
+    /// Wait for a free read connection.
+    pub async fn get(&self) -> Result<ReadConnection, duckdb::Error> {
+        let permit = self
+            .inner
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("read pool semaphore is never closed");
+        let idle = self
+            .inner
+            .idle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop();
+        let conn = match idle {
+            Some(conn) => conn,
+            // Only cloning takes the writer lock; queries run without it.
+            None => self.inner.writer.lock().await.try_clone()?,
+        };
+        Ok(ReadConnection {
+            conn: Some(conn),
+            pool: self.inner.clone(),
+            _permit: permit,
+        })
+    }
+}
+
+/// A pooled connection; returned to the pool on drop.
+pub struct ReadConnection {
+    conn: Option<Connection>,
+    pool: Arc<PoolInner>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Deref for ReadConnection {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn
+            .as_ref()
+            .expect("connection is present until drop")
+    }
+}
+
+impl ReadConnection {
+    /// Run `f` on the blocking thread pool; the connection goes back to the
+    /// pool when it returns.
+    pub async fn run<T, F>(self, f: F) -> T
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection) -> T + Send + 'static,
+    {
+        tokio::task::spawn_blocking(move || f(&self))
+            .await
+            .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))
+    }
+}
+
+impl Drop for ReadConnection {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            self.pool
+                .idle
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(conn);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn writer() -> Arc<Mutex<Connection>> {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE t (n INTEGER); INSERT INTO t VALUES (1);")
+            .unwrap();
+        Arc::new(Mutex::new(conn))
+    }
+
+    fn count(conn: &Connection) -> i64 {
+        conn.query_row("SELECT COUNT(*) FROM t", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn readers_see_writes_without_the_writer_lock() {
+        let writer = writer();
+        let pool = ReadPool::new(writer.clone());
+        let reader = pool.get().await.unwrap();
+
+        let guard = writer.lock().await;
+        assert_eq!(count(&reader), 1);
+        guard.execute("INSERT INTO t VALUES (2)", []).unwrap();
+        assert_eq!(count(&reader), 2);
+    }
+
+    #[tokio::test]
+    async fn run_uses_the_pooled_connection_off_the_runtime() {
+        let pool = ReadPool::new(writer()).with_size(1);
+        let conn = pool.get().await.unwrap();
+        assert_eq!(conn.run(count).await, 1);
+        assert_eq!(pool.inner.idle.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn connections_are_bounded_and_reused() {
+        let pool = ReadPool::new(writer()).with_size(1);
+        let first = pool.get().await.unwrap();
+        assert!(tokio::time::timeout(Duration::from_millis(50), pool.get())
+            .await
+            .is_err());
+
+        drop(first);
+        let second = pool.get().await.unwrap();
+        assert_eq!(count(&second), 1);
+        assert!(pool.inner.idle.lock().unwrap().is_empty());
+        drop(second);
+        assert_eq!(pool.inner.idle.lock().unwrap().len(), 1);
+    }
+}
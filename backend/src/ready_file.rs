@@ -0,0 +1,75 @@
+//! Looking up a dataset to read its features
+//!
+//! Handlers that query a dataset's feature table share one lookup: the file
+//! must exist, hold a feature table, which MBTiles and GeoTIFF files do not,
+//! and have finished importing.
+
+use axum::{http::StatusCode, Json};
+use duckdb::OptionalExt;
+
+use crate::http_errors::{bad_request, internal_error};
+use crate::ErrorResponse;
+
+/// A ready dataset's feature table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadyFile {
+    pub name: String,
+    pub table_name: String,
+    /// CRS the geometries are stored in; `EPSG:4326` when none was recorded.
+    pub crs: String,
+}
+
+/// The feature table of file `id`: 404 when there is no such file, 400 with
+/// `unsupported` for MBTiles and GeoTIFF files and 409 until it is ready.
+pub fn load_ready_file(
+    conn: &duckdb::Connection,
+    id: &str,
+    unsupported: &str,
+) -> Result<ReadyFile, (StatusCode, Json<ErrorResponse>)> {
+    let file: Option<(
+        String,
+        String,
+        Option<String>,
+        Option<String>,
+        Option<String>,
+    )> = conn
+        .query_row(
+            "SELECT name, status, table_name, tile_format, crs FROM files WHERE id = ?",
+            duckdb::params![id],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(internal_error)?;
+    let (name, status, table_name, tile_format, crs) = file.ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "File not found".to_string(),
+            }),
+        )
+    })?;
+    if tile_format.is_some() {
+        return Err(bad_request(unsupported));
+    }
+    let table_name = table_name.filter(|_| status == "ready").ok_or_else(|| {
+        (
+            StatusCode::CONFLICT,
+            Json(ErrorResponse {
+                error: "File is not ready".to_string(),
+            }),
+        )
+    })?;
+    Ok(ReadyFile {
+        name,
+        table_name,
+        crs: crs.unwrap_or_else(|| "EPSG:4326".to_string()),
+    })
+}
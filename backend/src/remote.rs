@@ -0,0 +1,951 @@
+//! Remote imports
+//!
+//! `POST /api/uploads/url` imports a file fetched from an http(s) URL instead
+//! of an upload. Headers given with the URL, such as
+//! `Authorization: Bearer ...`, are sent with every fetch of it so protected
+//! upstream APIs can be ingested; they are stored with the source and never
+//! returned. Fetches that cannot connect, time out, or get a 429 or 5xx
+//! answer are retried with backoff.
+//!
+//! `POST /api/files/{id}/refresh` fetches the URL again with the
+//! `If-None-Match` / `If-Modified-Since` validators of the last fetch and
+//! re-imports the file only when the upstream copy changed. A refresh that
+//! fails, fetching or importing, leaves the file with its data and status as
+//! they were and is recorded as its last refresh error, which
+//! `GET /api/files/{id}/refresh` reports.
+//!
+//! Unless `remote_import_allow_private_hosts` is set, URLs may only point at
+//! public addresses: `localhost`, loopback, private, link-local (such as the
+//! cloud metadata service at 169.254.169.254) and other reserved addresses
+//! are refused when the request is made, when the host is resolved for each
+//! connection, and on every redirect.
+
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use axum::{
+    extract::{Path as AxumPath, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use axum_login::AuthSession;
+use chrono::{NaiveDateTime, Utc};
+use duckdb::OptionalExt;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use tokio::fs;
+use tokio::io::{AsyncWriteExt, BufWriter};
+
+use crate::db::bump_data_version;
+use crate::http_errors::{bad_request, internal_error};
+use crate::models::{
+    AppState, FileItem, RemoteImportRequest, RemoteRefreshResponse, RemoteRefreshStatus,
+};
+use crate::settings::upload_max_size;
+use crate::tile_cache::discard_cached_tiles;
+use crate::versions::{current_version, restore_retained_version};
+use crate::webhooks::{notify, WebhookEvent};
+use crate::{
+    create_id, import_file, mark_imported, quarantine_failed_upload, run_import,
+    storage_path_string, upload_file_type, validate_upload, AuthBackend, ErrorResponse,
+    UNSUPPORTED_UPLOAD_TYPE,
+};
+
+const MAX_URL_LENGTH: usize = 2048;
+const MAX_HEADERS: usize = 20;
+const MAX_REDIRECTS: usize = 10;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Longest wait for the next chunk of a download.
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
+const FETCH_ATTEMPTS: u32 = 3;
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// Where a remote file is fetched from, with the validators of its last fetch.
+struct RemoteSource {
+    url: String,
+    headers: BTreeMap<String, String>,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+enum Fetched {
+    NotModified,
+    Changed {
+        size: u64,
+        etag: Option<String>,
+        last_modified: Option<String>,
+    },
+}
+
+/// A failed fetch attempt; only transient failures are retried.
+enum FetchError {
+    Transient(String),
+    Permanent(String),
+}
+
+fn validate_source_url(url: &str) -> Result<reqwest::Url, String> {
+    let url = url.trim();
+    if url.len() > MAX_URL_LENGTH {
+        return Err(format!("url must be at most {MAX_URL_LENGTH} characters"));
+    }
+    let parsed = reqwest::Url::parse(url).map_err(|_| "url must be an absolute URL".to_string())?;
+    if !matches!(parsed.scheme(), "http" | "https") || parsed.host_str().is_none() {
+        return Err("url must be an http or https URL".to_string());
+    }
+    Ok(parsed)
+}
+
+/// Whether `ip` is reachable on the public internet.
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ipv4(ip),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || a == 0
+        // Shared address space (carrier-grade NAT), 100.64.0.0/10.
+        || (a == 100 && (64..128).contains(&b))
+        // IETF protocol assignments, 192.0.0.0/24.
+        || (a == 192 && b == 0 && c == 0)
+        // Benchmarking, 198.18.0.0/15.
+        || (a == 198 && (18..20).contains(&b))
+        // Reserved, 240.0.0.0/4.
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // Unique local, fc00::/7.
+        || (first & 0xfe00) == 0xfc00
+        // Link-local, fe80::/10.
+        || (first & 0xffc0) == 0xfe80
+        // Documentation, 2001:db8::/32.
+        || (first == 0x2001 && ip.segments()[1] == 0x0db8))
+}
+
+/// `host` as an address, when it is an IP literal, bracketed or not.
+fn literal_ip(host: &str) -> Option<IpAddr> {
+    host.trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .ok()
+}
+
+/// Refuse `localhost` and literal non-public addresses; host names are
+/// checked again once resolved.
+fn check_public_name(host: &str) -> Result<(), String> {
+    let refused = match literal_ip(host) {
+        Some(ip) => !is_public_ip(ip),
+        None => {
+            let name = host.trim_end_matches('.').to_ascii_lowercase();
+            name.is_empty() || name == "localhost" || name.ends_with(".localhost")
+        }
+    };
+    if refused {
+        return Err(format!("{host} is not a public address"));
+    }
+    Ok(())
+}
+
+fn check_public_host(url: &reqwest::Url) -> Result<(), String> {
+    check_public_name(url.host_str().unwrap_or_default())
+}
+
+/// Resolve `host` and refuse it unless every address is public. Returns the
+/// address to connect to, so a name cannot be changed to point elsewhere
+/// after it was checked.
+pub(crate) async fn resolve_public_name(host: &str, port: u16) -> Result<IpAddr, String> {
+    check_public_name(host)?;
+    if let Some(ip) = literal_ip(host) {
+        return Ok(ip);
+    }
+    public_addrs(host, port)
+        .await?
+        .first()
+        .map(SocketAddr::ip)
+        .ok_or_else(|| format!("Could not resolve {host}"))
+}
+
+/// Resolve the host of `url` and refuse it unless every address is public.
+async fn resolve_public_host(url: &reqwest::Url) -> Result<(), String> {
+    let host = url.host_str().unwrap_or_default();
+    resolve_public_name(host, url.port_or_known_default().unwrap_or(80))
+        .await
+        .map(|_| ())
+}
+
+/// The addresses of `host`, or an error naming it when any is not public.
+async fn public_addrs(host: &str, port: u16) -> Result<Vec<SocketAddr>, String> {
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| format!("Could not resolve {host}: {e}"))?
+        .collect();
+    if addrs.iter().any(|addr| !is_public_ip(addr.ip())) {
+        return Err(format!("{host} is not a public address"));
+    }
+    Ok(addrs)
+}
+
+/// Resolves host names for the guarded client, so a name that changes to a
+/// private address after it was checked still cannot be connected to.
+struct PublicResolver;
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        Box::pin(async move {
+            let addrs = public_addrs(name.as_str(), 0).await?;
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+/// Check each header can be sent, keyed by its lowercase name.
+fn validate_headers(
+    headers: &BTreeMap<String, String>,
+) -> Result<BTreeMap<String, String>, String> {
+    if headers.len() > MAX_HEADERS {
+        return Err(format!("At most {MAX_HEADERS} headers can be sent"));
+    }
+    let mut validated = BTreeMap::new();
+    for (name, value) in headers {
+        let name = HeaderName::from_bytes(name.trim().as_bytes())
+            .map_err(|_| format!("Invalid header name '{name}'"))?;
+        if matches!(
+            name.as_str(),
+            "host" | "content-length" | "transfer-encoding" | "connection"
+        ) {
+            return Err(format!("Header '{name}' cannot be set"));
+        }
+        HeaderValue::from_str(value.trim())
+            .map_err(|_| format!("Invalid value for header '{name}'"))?;
+        validated.insert(name.as_str().to_string(), value.trim().to_string());
+    }
+    Ok(validated)
+}
+
+/// `file_name`, or the last segment of the URL's path.
+fn source_file_name(url: &reqwest::Url, file_name: Option<&str>) -> Result<String, String> {
+    let name = match file_name {
+        Some(name) => name.trim().to_string(),
+        None => url
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .unwrap_or_default()
+            .to_string(),
+    };
+    Path::new(&name)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .ok_or_else(|| "fileName is required when the URL does not end in a file name".to_string())
+}
+
+/// The client fetching remote files; unless `allow_private_hosts`, it only
+/// connects to and follows redirects to public addresses.
+fn http_client(allow_private_hosts: bool) -> &'static reqwest::Client {
+    static OPEN: OnceLock<reqwest::Client> = OnceLock::new();
+    static GUARDED: OnceLock<reqwest::Client> = OnceLock::new();
+    if allow_private_hosts {
+        return OPEN.get_or_init(|| {
+            reqwest::Client::builder()
+                .connect_timeout(CONNECT_TIMEOUT)
+                .build()
+                .expect("remote import HTTP client")
+        });
+    }
+    GUARDED.get_or_init(|| {
+        let redirects = reqwest::redirect::Policy::custom(|attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                return attempt.error("too many redirects");
+            }
+            let url = attempt.url();
+            if !matches!(url.scheme(), "http" | "https") {
+                return attempt.error("redirected to a URL that is not http or https");
+            }
+            match check_public_host(url) {
+                Ok(()) => attempt.follow(),
+                Err(e) => attempt.error(format!("redirected to {e}")),
+            }
+        });
+        reqwest::Client::builder()
+            .connect_timeout(CONNECT_TIMEOUT)
+            .redirect(redirects)
+            .dns_resolver(Arc::new(PublicResolver))
+            // A proxy would resolve and connect on our behalf, unchecked.
+            .no_proxy()
+            .build()
+            .expect("remote import HTTP client")
+    })
+}
+
+fn retryable(status: reqwest::StatusCode) -> bool {
+    status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        || status == reqwest::StatusCode::REQUEST_TIMEOUT
+        || status.is_server_error()
+}
+
+/// Fetch `source` into `dest`, retrying transient failures. With
+/// `conditional`, a `304 Not Modified` leaves `dest` untouched.
+async fn fetch_source(
+    client: &reqwest::Client,
+    source: &RemoteSource,
+    dest: &Path,
+    max_size: u64,
+    max_size_label: &str,
+    conditional: bool,
+) -> Result<Fetched, String> {
+    let mut attempt = 1;
+    loop {
+        match fetch_once(client, source, dest, max_size, max_size_label, conditional).await {
+            Ok(fetched) => return Ok(fetched),
+            Err(FetchError::Permanent(e)) => return Err(e),
+            Err(FetchError::Transient(e)) if attempt == FETCH_ATTEMPTS => {
+                return Err(format!("{e} (gave up after {FETCH_ATTEMPTS} attempts)"))
+            }
+            Err(FetchError::Transient(e)) => {
+                tracing::debug!(url = %source.url, attempt, error = %e, "Retrying remote fetch");
+                tokio::time::sleep(RETRY_BASE_DELAY * 2u32.pow(attempt - 1)).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+async fn fetch_once(
+    client: &reqwest::Client,
+    source: &RemoteSource,
+    dest: &Path,
+    max_size: u64,
+    max_size_label: &str,
+    conditional: bool,
+) -> Result<Fetched, FetchError> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in &source.headers {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            headers.insert(name, value);
+        }
+    }
+    if conditional {
+        if let Some(Ok(etag)) = source.etag.as_deref().map(HeaderValue::from_str) {
+            headers.insert(reqwest::header::IF_NONE_MATCH, etag);
+        }
+        if let Some(Ok(modified)) = source.last_modified.as_deref().map(HeaderValue::from_str) {
+            headers.insert(reqwest::header::IF_MODIFIED_SINCE, modified);
+        }
+    }
+
+    let mut response = client
+        .get(&source.url)
+        .headers(headers)
+        .send()
+        .await
+        .map_err(|e| {
+            // Refused redirects are not retried.
+            if e.is_redirect() {
+                FetchError::Permanent(e.to_string())
+            } else {
+                FetchError::Transient(e.to_string())
+            }
+        })?;
+    let status = response.status();
+    if conditional && status == reqwest::StatusCode::NOT_MODIFIED {
+        return Ok(Fetched::NotModified);
+    }
+    if !status.is_success() {
+        let error = format!("{} answered HTTP {status}", source.url);
+        return Err(if retryable(status) {
+            FetchError::Transient(error)
+        } else {
+            FetchError::Permanent(error)
+        });
+    }
+    if response.content_length().is_some_and(|len| len > max_size) {
+        return Err(FetchError::Permanent(format!(
+            "File too large (max {max_size_label})"
+        )));
+    }
+    let validator = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    };
+    let etag = validator(reqwest::header::ETAG);
+    let last_modified = validator(reqwest::header::LAST_MODIFIED);
+
+    // Downloaded beside `dest` so a failed refresh keeps the current copy.
+    let partial = partial_path(dest);
+    let permanent = |e: std::io::Error| FetchError::Permanent(e.to_string());
+    let mut file = BufWriter::new(fs::File::create(&partial).await.map_err(permanent)?);
+    let mut size: u64 = 0;
+    let result = loop {
+        let chunk = match tokio::time::timeout(READ_TIMEOUT, response.chunk()).await {
+            Ok(Ok(Some(chunk))) => chunk,
+            Ok(Ok(None)) => break Ok(()),
+            Ok(Err(e)) => break Err(FetchError::Transient(e.to_string())),
+            Err(_) => break Err(FetchError::Transient("download timed out".to_string())),
+        };
+        size = size.saturating_add(chunk.len() as u64);
+        if size > max_size {
+            break Err(FetchError::Permanent(format!(
+                "File too large (max {max_size_label})"
+            )));
+        }
+        if let Err(e) = file.write_all(&chunk).await {
+            break Err(permanent(e));
+        }
+    };
+    let result = match result {
+        Ok(()) => file.flush().await.map_err(permanent),
+        Err(e) => Err(e),
+    };
+    drop(file);
+    if let Err(e) = result {
+        let _ = fs::remove_file(&partial).await;
+        return Err(e);
+    }
+    fs::rename(&partial, dest).await.map_err(permanent)?;
+    Ok(Fetched::Changed {
+        size,
+        etag,
+        last_modified,
+    })
+}
+
+fn partial_path(dest: &Path) -> PathBuf {
+    let name = dest
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    dest.with_file_name(format!(".{name}.part"))
+}
+
+fn load_remote_source(
+    conn: &duckdb::Connection,
+    id: &str,
+) -> Result<Option<(RemoteSource, String)>, duckdb::Error> {
+    conn.query_row(
+        "SELECT url, headers, etag, last_modified, file_name FROM remote_sources WHERE file_id = ?",
+        duckdb::params![id],
+        |row| {
+            let headers: String = row.get(1)?;
+            Ok((
+                RemoteSource {
+                    url: row.get(0)?,
+                    headers: serde_json::from_str(&headers).unwrap_or_default(),
+                    etag: row.get(2)?,
+                    last_modified: row.get(3)?,
+                },
+                row.get(4)?,
+            ))
+        },
+    )
+    .optional()
+}
+
+fn record_fetch(
+    conn: &duckdb::Connection,
+    id: &str,
+    fetched: &Fetched,
+) -> Result<(), duckdb::Error> {
+    let fetched_at = Utc::now().naive_utc();
+    match fetched {
+        Fetched::NotModified => conn.execute(
+            "UPDATE remote_sources
+             SET fetched_at = ?, last_refresh_error = NULL, last_refresh_failed_at = NULL
+             WHERE file_id = ?",
+            duckdb::params![fetched_at, id],
+        ),
+        Fetched::Changed {
+            size,
+            etag,
+            last_modified,
+        } => {
+            conn.execute(
+                "UPDATE files SET size = ? WHERE id = ?",
+                duckdb::params![*size as i64, id],
+            )?;
+            conn.execute(
+                "UPDATE remote_sources
+                 SET etag = ?, last_modified = ?, fetched_at = ?,
+                     last_refresh_error = NULL, last_refresh_failed_at = NULL
+                 WHERE file_id = ?",
+                duckdb::params![etag, last_modified, fetched_at, id],
+            )
+        }
+    }?;
+    Ok(())
+}
+
+async fn mark_failed(state: &AppState, id: &str, message: &str) {
+    tracing::error!(file_id = id, error = message, "Remote import failed");
+    let conn = state.db.lock().await;
+    let _ = conn.execute(
+        "UPDATE files SET status = 'failed', error = ?, failed_at = ? WHERE id = ?",
+        duckdb::params![message, Utc::now().naive_utc(), id],
+    );
+    drop(conn);
+    notify(&state.db, WebhookEvent::FileFailed, id, None);
+}
+
+fn record_refresh_failure(
+    conn: &duckdb::Connection,
+    id: &str,
+    message: &str,
+) -> Result<(), duckdb::Error> {
+    tracing::warn!(file_id = id, error = message, "Remote refresh failed");
+    conn.execute(
+        "UPDATE remote_sources SET last_refresh_error = ?, last_refresh_failed_at = ?
+         WHERE file_id = ?",
+        duckdb::params![message, Utc::now().naive_utc(), id],
+    )?;
+    Ok(())
+}
+
+/// Validate, scan and import a downloaded file, as an upload would be.
+async fn import_download(state: AppState, id: String, file_path: PathBuf, file_type: &'static str) {
+    if let Err(message) = validate_upload(file_type, &file_path).await {
+        mark_failed(&state, &id, &message).await;
+        return;
+    }
+    if let Some(scanner) = &state.upload_scanner {
+        if let Err(e) = scanner.scan(&file_path).await {
+            quarantine_failed_upload(&state.db, &state.upload_dir, &id, &file_path, &e).await;
+            return;
+        }
+    }
+    let _ = run_import(&state.db, &id, &file_path, file_type).await;
+}
+
+/// Where a refresh downloads to, so the current copy stays until the new one
+/// has been imported.
+fn refresh_dir(file_path: &Path) -> PathBuf {
+    file_path.with_file_name(".refresh")
+}
+
+/// Validate, scan and import a refreshed download in place of the current
+/// data. On failure the replaced data and `previous_status` are put back and
+/// the error is recorded as the last refresh error.
+async fn import_refresh(
+    state: AppState,
+    id: String,
+    download: PathBuf,
+    file_path: PathBuf,
+    file_type: &'static str,
+    previous_status: String,
+    fetched: Fetched,
+) {
+    let replaced = current_version(&*state.db.lock().await, &id).ok();
+    let mut result = validate_upload(file_type, &download).await;
+    if let (true, Some(scanner)) = (result.is_ok(), &state.upload_scanner) {
+        result = scanner.scan(&download).await.map_err(|e| e.to_string());
+    }
+    if result.is_ok() {
+        result = import_file(&state.db, &id, &download, file_type).await;
+    }
+    if result.is_ok() {
+        result = fs::rename(&download, &file_path)
+            .await
+            .map_err(|e| format!("Failed to keep the download: {e}"));
+    }
+
+    let conn = state.db.lock().await;
+    match result {
+        Ok(()) => {
+            let _ = record_fetch(&conn, &id, &fetched);
+            let _ = conn.execute(
+                "UPDATE files SET error = NULL WHERE id = ?",
+                duckdb::params![&id],
+            );
+            mark_imported(&state.db, &conn, &id, &file_path);
+            // Re-imported tiles must not come from the cache.
+            let _ = bump_data_version(&conn, &id);
+            discard_cached_tiles(&state.upload_dir, &id);
+        }
+        Err(message) => {
+            // The import kept the data it was replacing as a new version.
+            if let (Some(replaced), Ok(current)) = (replaced, current_version(&conn, &id)) {
+                if current > replaced {
+                    if let Err(e) = restore_retained_version(&conn, &id, replaced) {
+                        tracing::error!(file_id = %id, error = %e, "Failed to restore replaced data");
+                    }
+                }
+            }
+            let _ = record_refresh_failure(&conn, &id, &format!("Re-import failed: {message}"));
+            let _ = conn.execute(
+                "UPDATE files SET status = ? WHERE id = ?",
+                duckdb::params![&previous_status, &id],
+            );
+        }
+    }
+    drop(conn);
+    if let Some(dir) = download.parent() {
+        let _ = fs::remove_dir_all(dir).await;
+    }
+}
+
+fn file_not_found() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse {
+            error: "File not found".to_string(),
+        }),
+    )
+}
+
+/// Create a file from a URL; it is fetched and imported in the background.
+pub async fn import_from_url(
+    State(state): State<AppState>,
+    auth_session: AuthSession<AuthBackend>,
+    Json(req): Json<RemoteImportRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let owner_id = auth_session.user.as_ref().map(|user| user.id.clone());
+    let url = validate_source_url(&req.url).map_err(|e| bad_request(&e))?;
+    if !state.remote_allow_private_hosts {
+        resolve_public_host(&url)
+            .await
+            .map_err(|e| bad_request(&e))?;
+    }
+    let headers = validate_headers(&req.headers).map_err(|e| bad_request(&e))?;
+    let file_name =
+        source_file_name(&url, req.file_name.as_deref()).map_err(|e| bad_request(&e))?;
+    let file_type =
+        upload_file_type(&file_name).ok_or_else(|| bad_request(UNSUPPORTED_UPLOAD_TYPE))?;
+    let (max_size, max_size_label) = upload_max_size(&state).await?;
+
+    let id = create_id();
+    let dir = state.upload_dir.join(&id);
+    fs::create_dir_all(&dir).await.map_err(internal_error)?;
+    let file_path = dir.join(&file_name);
+    let rel_string = storage_path_string(&file_path);
+    let base_name = Path::new(&file_name)
+        .file_stem()
+        .and_then(|name| name.to_str())
+        .unwrap_or(&file_name)
+        .to_string();
+    let uploaded_at = Utc::now().to_rfc3339();
+    let headers_json = serde_json::to_string(&headers).map_err(internal_error)?;
+
+    let conn = state.db.lock().await;
+    conn.execute_batch("BEGIN TRANSACTION")
+        .map_err(internal_error)?;
+    let inserted = conn
+        .execute(
+            "INSERT INTO files (id, name, type, size, uploaded_at, status, path, is_public, owner_id)
+             VALUES (?, ?, ?, 0, ?, 'uploaded', ?, FALSE, ?)",
+            duckdb::params![&id, &base_name, file_type, &uploaded_at, &rel_string, &owner_id],
+        )
+        .and_then(|_| {
+            conn.execute(
+                "INSERT INTO remote_sources (file_id, url, headers, file_name) VALUES (?, ?, ?, ?)",
+                duckdb::params![&id, url.as_str(), &headers_json, &file_name],
+            )
+        });
+    if let Err(e) = inserted {
+        let _ = conn.execute_batch("ROLLBACK");
+        return Err(internal_error(e));
+    }
+    conn.execute_batch("COMMIT").map_err(internal_error)?;
+    drop(conn);
+
+    let source = RemoteSource {
+        url: url.to_string(),
+        headers,
+        etag: None,
+        last_modified: None,
+    };
+    let task_state = state.clone();
+    let task_id = id.clone();
+    let task_path = file_path.clone();
+    tokio::spawn(async move {
+        let client = http_client(task_state.remote_allow_private_hosts);
+        let fetched = fetch_source(
+            client,
+            &source,
+            &task_path,
+            max_size,
+            &max_size_label,
+            false,
+        )
+        .await;
+        let recorded = match fetched {
+            Ok(fetched) => {
+                let conn = task_state.db.lock().await;
+                record_fetch(&conn, &task_id, &fetched).map_err(|e| e.to_string())
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = recorded {
+            let message = format!("Remote import failed: {e}");
+            mark_failed(&task_state, &task_id, &message).await;
+            return;
+        }
+        import_download(task_state, task_id, task_path, file_type).await;
+    });
+
+    let meta = FileItem {
+        id,
+        name: base_name,
+        file_type: file_type.to_string(),
+        size: 0,
+        uploaded_at,
+        status: "uploaded".to_string(),
+        crs: None,
+        path: rel_string,
+        table_name: None,
+        error: None,
+        is_public: Some(false),
+        public_slug: None,
+        org_id: None,
+        geometry_type: None,
+        feature_count: None,
+        folder: None,
+        tags: Vec::new(),
+    };
+    Ok((StatusCode::CREATED, Json(meta)))
+}
+
+/// Fetch a remote file again and re-import it if the upstream copy changed.
+pub async fn refresh_remote_file(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let (source, file_name, status) = {
+        let conn = state.read_pool.get().await.map_err(internal_error)?;
+        let status: String = conn
+            .query_row(
+                "SELECT status FROM files WHERE id = ?",
+                duckdb::params![&id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(internal_error)?
+            .ok_or_else(file_not_found)?;
+        let (source, file_name) = load_remote_source(&conn, &id)
+            .map_err(internal_error)?
+            .ok_or_else(|| bad_request("File was not imported from a URL"))?;
+        (source, file_name, status)
+    };
+    if status == "uploaded" || status == "processing" {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(ErrorResponse {
+                error: format!("File is still importing (status: {status})"),
+            }),
+        ));
+    }
+    let file_type =
+        upload_file_type(&file_name).ok_or_else(|| bad_request(UNSUPPORTED_UPLOAD_TYPE))?;
+    if !state.remote_allow_private_hosts {
+        let url = validate_source_url(&source.url).map_err(|e| bad_request(&e))?;
+        resolve_public_host(&url)
+            .await
+            .map_err(|e| bad_request(&e))?;
+    }
+
+    let (max_size, max_size_label) = upload_max_size(&state).await?;
+    let file_path = state.upload_dir.join(&id).join(&file_name);
+    let download_dir = refresh_dir(&file_path);
+    fs::create_dir_all(&download_dir)
+        .await
+        .map_err(internal_error)?;
+    let download = download_dir.join(&file_name);
+    let client = http_client(state.remote_allow_private_hosts);
+    let fetched =
+        match fetch_source(client, &source, &download, max_size, &max_size_label, true).await {
+            Ok(fetched) => fetched,
+            Err(e) => {
+                let _ = fs::remove_dir_all(&download_dir).await;
+                let message = format!("Remote fetch failed: {e}");
+                let conn = state.db.lock().await;
+                record_refresh_failure(&conn, &id, &message).map_err(internal_error)?;
+                return Err((
+                    StatusCode::BAD_GATEWAY,
+                    Json(ErrorResponse { error: message }),
+                ));
+            }
+        };
+
+    let changed = matches!(fetched, Fetched::Changed { .. });
+    let conn = state.db.lock().await;
+    if changed {
+        // Its validators are recorded once the new copy is imported, so a
+        // failed re-import is fetched again by the next refresh.
+        conn.execute(
+            "UPDATE files SET status = 'processing' WHERE id = ?",
+            duckdb::params![&id],
+        )
+        .map_err(internal_error)?;
+    } else {
+        record_fetch(&conn, &id, &fetched).map_err(internal_error)?;
+    }
+    drop(conn);
+    if changed {
+        tokio::spawn(import_refresh(
+            state.clone(),
+            id.clone(),
+            download,
+            file_path,
+            file_type,
+            status,
+            fetched,
+        ));
+    } else {
+        let _ = fs::remove_dir_all(&download_dir).await;
+    }
+
+    Ok(Json(RemoteRefreshResponse { id, changed }))
+}
+
+/// When a remote file was last fetched and why its last refresh failed.
+pub async fn get_refresh_status(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    let exists: bool = conn
+        .query_row(
+            "SELECT COUNT(*) > 0 FROM files WHERE id = ?",
+            duckdb::params![&id],
+            |row| row.get(0),
+        )
+        .map_err(internal_error)?;
+    if !exists {
+        return Err(file_not_found());
+    }
+    let row: Option<(Option<NaiveDateTime>, Option<String>, Option<NaiveDateTime>)> = conn
+        .query_row(
+            "SELECT fetched_at, last_refresh_error, last_refresh_failed_at
+             FROM remote_sources WHERE file_id = ?",
+            duckdb::params![&id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()
+        .map_err(internal_error)?;
+    let (fetched_at, last_refresh_error, last_refresh_failed_at) =
+        row.ok_or_else(|| bad_request("File was not imported from a URL"))?;
+    let rfc3339 = |at: NaiveDateTime| at.and_utc().to_rfc3339();
+    Ok(Json(RemoteRefreshStatus {
+        id,
+        fetched_at: fetched_at.map(rfc3339),
+        last_refresh_error,
+        last_refresh_failed_at: last_refresh_failed_at.map(rfc3339),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headers_are_checked_and_lowercased() {
+        let headers = BTreeMap::from([
+            ("Authorization".to_string(), " Bearer abc ".to_string()),
+            ("X-Api-Key".to_string(), "k".to_string()),
+        ]);
+        assert_eq!(
+            validate_headers(&headers).unwrap(),
+            BTreeMap::from([
+                ("authorization".to_string(), "Bearer abc".to_string()),
+                ("x-api-key".to_string(), "k".to_string()),
+            ])
+        );
+        assert!(validate_headers(&BTreeMap::from([(
+            "Bad Header".to_string(),
+            "x".to_string()
+        )]))
+        .is_err());
+        assert!(validate_headers(&BTreeMap::from([(
+            "Host".to_string(),
+            "example.com".to_string()
+        )]))
+        .is_err());
+        assert!(validate_headers(&BTreeMap::from([(
+            "x-token".to_string(),
+            "a\nb".to_string()
+        )]))
+        .is_err());
+    }
+
+    #[test]
+    fn file_names_come_from_the_url_unless_given() {
+        let url = validate_source_url("https://example.com/data/roads.geojson?x=1").unwrap();
+        assert_eq!(source_file_name(&url, None).unwrap(), "roads.geojson");
+        assert_eq!(
+            source_file_name(&url, Some("../roads.kml")).unwrap(),
+            "roads.kml"
+        );
+        let bare = validate_source_url("https://example.com/api/").unwrap();
+        assert!(source_file_name(&bare, None).is_err());
+        assert!(validate_source_url("file:///etc/passwd").is_err());
+    }
+
+    #[test]
+    fn only_public_addresses_are_fetched() {
+        for url in [
+            "http://localhost/a.geojson",
+            "http://tiles.localhost./a.geojson",
+            "http://127.0.0.1:8080/a.geojson",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://10.0.0.5/a.geojson",
+            "http://172.16.0.1/a.geojson",
+            "http://192.168.1.1/a.geojson",
+            "http://100.64.0.1/a.geojson",
+            "http://0.0.0.0/a.geojson",
+            "http://[::1]/a.geojson",
+            "http://[fd00::1]/a.geojson",
+            "http://[fe80::1]/a.geojson",
+            "http://[::ffff:127.0.0.1]/a.geojson",
+        ] {
+            let parsed = validate_source_url(url).unwrap();
+            assert!(check_public_host(&parsed).is_err(), "{url}");
+        }
+        for url in [
+            "https://example.com/a.geojson",
+            "http://8.8.8.8/a.geojson",
+            "http://[2606:4700::1111]/a.geojson",
+        ] {
+            let parsed = validate_source_url(url).unwrap();
+            assert!(check_public_host(&parsed).is_ok(), "{url}");
+        }
+    }
+
+    #[tokio::test]
+    async fn resolved_addresses_must_be_public() {
+        let url = validate_source_url("http://127.0.0.1/a.geojson").unwrap();
+        assert!(resolve_public_host(&url).await.is_err());
+        assert!(public_addrs("127.0.0.1", 80).await.is_err());
+    }
+
+    #[test]
+    fn only_transient_statuses_are_retried() {
+        assert!(retryable(reqwest::StatusCode::SERVICE_UNAVAILABLE));
+        assert!(retryable(reqwest::StatusCode::TOO_MANY_REQUESTS));
+        assert!(!retryable(reqwest::StatusCode::UNAUTHORIZED));
+        assert!(!retryable(reqwest::StatusCode::NOT_FOUND));
+    }
+}
@@ -0,0 +1,98 @@
+//! Request IDs
+//!
+//! Every request gets an `X-Request-Id`: a well-formed one sent by the client
+//! or a proxy is kept, anything else is replaced by a new UUID. The id is a
+//! field of the request's log span, is echoed in the response header and is
+//! added to JSON error bodies as `requestId`, so an error a user reports can
+//! be found in the server logs.
+
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header, HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+/// Error bodies are small; anything larger is passed through untouched.
+const MAX_ERROR_BODY_BYTES: usize = 64 * 1024;
+
+/// Incoming ids end up in logs and headers, so only plain tokens are kept.
+fn valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LENGTH
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// The request's id, once `assign_request_id` has run.
+pub fn request_id_of<B>(request: &axum::http::Request<B>) -> Option<&str> {
+    request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+}
+
+pub async fn assign_request_id(mut request: Request, next: Next) -> Response {
+    let id = request_id_of(&request)
+        .filter(|id| valid_request_id(id))
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let header_value = HeaderValue::from_str(&id).expect("request ids are valid header values");
+    request
+        .headers_mut()
+        .insert(REQUEST_ID_HEADER, header_value.clone());
+
+    let mut response = with_request_id_in_error(next.run(request).await, &id).await;
+    response
+        .headers_mut()
+        .insert(REQUEST_ID_HEADER, header_value);
+    response
+}
+
+async fn with_request_id_in_error(response: Response, id: &str) -> Response {
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    let status = response.status();
+    if !is_json || !(status.is_client_error() || status.is_server_error()) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, MAX_ERROR_BODY_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(_) => return Response::from_parts(parts, Body::empty()),
+    };
+    let body = match serde_json::from_slice::<serde_json::Value>(&bytes) {
+        // Only `{error}` bodies; other JSON (e.g. a failing health report)
+        // keeps its own shape.
+        Ok(serde_json::Value::Object(mut object)) if object.contains_key("error") => {
+            object.insert("requestId".to_string(), id.into());
+            parts.headers.remove(header::CONTENT_LENGTH);
+            Body::from(serde_json::Value::Object(object).to_string())
+        }
+        _ => Body::from(bytes),
+    };
+    Response::from_parts(parts, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_plain_incoming_ids_are_kept() {
+        assert!(valid_request_id("3f2a9c1e-7b4d-4e8f-9a6b-1c2d3e4f5a6b"));
+        assert!(valid_request_id("lb.01_abc"));
+        assert!(!valid_request_id(""));
+        assert!(!valid_request_id("id with spaces"));
+        assert!(!valid_request_id("evil\"}"));
+        assert!(!valid_request_id(&"a".repeat(MAX_REQUEST_ID_LENGTH + 1)));
+    }
+}
@@ -0,0 +1,270 @@
+//! Failed upload retention
+//!
+//! Files whose validation or import failed are kept so their error can be read,
+//! then purged with their stored upload once the retention period has passed
+//! since they failed, or since they were uploaded for files that failed
+//! before failure times were recorded. The period is the `failedUploadRetentionDays`
+//! setting unless `PUT /api/files/{id}/retention` overrides it for the file;
+//! 0 keeps files forever. The sweep runs at startup and every
+//! `RETENTION_SWEEP_INTERVAL`.
+
+use std::time::Duration;
+
+use axum::{
+    extract::{Path as AxumPath, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{NaiveDateTime, Utc};
+use duckdb::OptionalExt;
+
+use crate::columns::quote_identifier;
+use crate::http_errors::{bad_request, internal_error};
+use crate::models::{AppState, FileRetention, FileRetentionRequest};
+use crate::settings::load_settings;
+use crate::tile_cache::tile_cache_root;
+use crate::versions::drop_retained_versions;
+use crate::ErrorResponse;
+
+pub const DEFAULT_FAILED_UPLOAD_RETENTION_DAYS: u64 = 30;
+/// Ten years; longer periods should use 0.
+pub const MAX_RETENTION_DAYS: u64 = 3650;
+pub const RETENTION_SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Tables holding rows of a file, emptied before its `files` row.
+const FILE_TABLES: &[(&str, &str)] = &[
+    ("file_shares", "file_id"),
+    ("export_jobs", "file_id"),
+    ("tile_seed_jobs", "file_id"),
+    ("tileset_sources", "file_id"),
+    ("published_files", "file_id"),
+    ("dataset_columns", "source_id"),
+    ("file_retention", "file_id"),
+    ("file_tags", "file_id"),
+    ("remote_sources", "file_id"),
+    ("slug_history", "file_id"),
+];
+
+pub fn validate_retention_days(days: u64, field: &str) -> Result<(), String> {
+    if days > MAX_RETENTION_DAYS {
+        return Err(format!(
+            "{field} must be at most {MAX_RETENTION_DAYS} (0 keeps files forever)"
+        ));
+    }
+    Ok(())
+}
+
+/// Purge the failed files whose retention has run out, with their uploads.
+/// Returns their ids.
+pub async fn purge_failed_uploads(state: &AppState) -> Result<Vec<String>, String> {
+    let expired = {
+        let conn = state.db.lock().await;
+        let default_days = load_settings(&conn, state)
+            .map_err(|e| e.to_string())?
+            .failed_upload_retention_days;
+        let expired = expired_failed_files(&conn, default_days, Utc::now().naive_utc())
+            .map_err(|e| e.to_string())?;
+        for (id, table_name) in &expired {
+            delete_file_rows(&conn, id, table_name.as_deref())
+                .map_err(|e| format!("Failed to purge {id}: {e}"))?;
+        }
+        expired
+    };
+
+    let cache_root = tile_cache_root(&state.upload_dir);
+    let mut purged = Vec::with_capacity(expired.len());
+    for (id, _) in expired {
+        for dir in [state.upload_dir.join(&id), cache_root.join(&id)] {
+            if let Err(e) = tokio::fs::remove_dir_all(&dir).await {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    // The orphan sweep retries directories without a row.
+                    tracing::warn!(file_id = %id, error = %e, "Failed to remove purged upload");
+                }
+            }
+        }
+        purged.push(id);
+    }
+    Ok(purged)
+}
+
+fn expired_failed_files(
+    conn: &duckdb::Connection,
+    default_days: u64,
+    now: NaiveDateTime,
+) -> Result<Vec<(String, Option<String>)>, duckdb::Error> {
+    let mut stmt = conn.prepare(
+        r"
+        SELECT f.id, f.table_name
+        FROM files f
+        LEFT JOIN file_retention r ON r.file_id = f.id
+        WHERE f.status = 'failed'
+          AND COALESCE(r.retention_days, ?) > 0
+          AND COALESCE(f.failed_at, f.uploaded_at)
+              + to_days(CAST(COALESCE(r.retention_days, ?) AS INTEGER)) <= ?
+        ORDER BY COALESCE(f.failed_at, f.uploaded_at)
+        ",
+    )?;
+    let default_days = default_days as i64;
+    let rows = stmt.query_map(duckdb::params![default_days, default_days, now], |row| {
+        Ok((row.get(0)?, row.get(1)?))
+    })?;
+    rows.collect()
+}
+
+/// Delete a file's rows and tables. Each statement commits on its own and the
+/// `files` row goes last, so an interrupted purge is finished by the next one.
+fn delete_file_rows(
+    conn: &duckdb::Connection,
+    id: &str,
+    table_name: Option<&str>,
+) -> Result<(), duckdb::Error> {
+    if let Some(table_name) = table_name {
+        conn.execute_batch(&format!(
+            "DROP TABLE IF EXISTS {}",
+            quote_identifier(table_name)
+        ))?;
+    }
+    drop_retained_versions(conn, id)?;
+    for (table, column) in FILE_TABLES {
+        conn.execute(
+            &format!("DELETE FROM {table} WHERE {column} = ?"),
+            duckdb::params![id],
+        )?;
+    }
+    conn.execute("DELETE FROM files WHERE id = ?", duckdb::params![id])?;
+    Ok(())
+}
+
+fn load_file_retention(
+    conn: &duckdb::Connection,
+    state: &AppState,
+    id: &str,
+) -> Result<Option<FileRetention>, duckdb::Error> {
+    let row: Option<(String, NaiveDateTime, Option<i64>)> = conn
+        .query_row(
+            "SELECT f.status, COALESCE(f.failed_at, f.uploaded_at), r.retention_days
+             FROM files f
+             LEFT JOIN file_retention r ON r.file_id = f.id
+             WHERE f.id = ?",
+            duckdb::params![id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?;
+    let Some((status, failed_at, retention_days)) = row else {
+        return Ok(None);
+    };
+    let retention_days = retention_days.map(|days| days as u64);
+    let effective_retention_days = match retention_days {
+        Some(days) => days,
+        None => load_settings(conn, state)?.failed_upload_retention_days,
+    };
+    let purge_at = (status == "failed" && effective_retention_days > 0).then(|| {
+        (failed_at + chrono::Duration::days(effective_retention_days as i64))
+            .and_utc()
+            .to_rfc3339()
+    });
+    Ok(Some(FileRetention {
+        retention_days,
+        effective_retention_days,
+        purge_at,
+    }))
+}
+
+fn file_not_found() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse {
+            error: "File not found".to_string(),
+        }),
+    )
+}
+
+pub async fn get_file_retention(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    load_file_retention(&conn, &state, &id)
+        .map_err(internal_error)?
+        .map(Json)
+        .ok_or_else(file_not_found)
+}
+
+pub async fn set_file_retention(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+    Json(req): Json<FileRetentionRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    if let Some(days) = req.retention_days {
+        validate_retention_days(days, "retentionDays").map_err(|e| bad_request(&e))?;
+    }
+
+    let conn = state.db.lock().await;
+    match req.retention_days {
+        Some(days) => conn.execute(
+            "INSERT OR REPLACE INTO file_retention (file_id, retention_days) VALUES (?, ?)",
+            duckdb::params![&id, days as i64],
+        ),
+        None => conn.execute(
+            "DELETE FROM file_retention WHERE file_id = ?",
+            duckdb::params![&id],
+        ),
+    }
+    .map_err(internal_error)?;
+    load_file_retention(&conn, &state, &id)
+        .map_err(internal_error)?
+        .map(Json)
+        .ok_or_else(file_not_found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retention_is_bounded() {
+        assert!(validate_retention_days(0, "retentionDays").is_ok());
+        assert!(validate_retention_days(MAX_RETENTION_DAYS, "retentionDays").is_ok());
+        assert!(validate_retention_days(MAX_RETENTION_DAYS + 1, "retentionDays").is_err());
+    }
+
+    #[test]
+    fn only_failed_files_past_their_retention_expire() {
+        let conn = duckdb::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r"
+            CREATE TABLE files (
+                id VARCHAR, status VARCHAR, table_name VARCHAR,
+                uploaded_at TIMESTAMP, failed_at TIMESTAMP
+            );
+            CREATE TABLE file_retention (file_id VARCHAR PRIMARY KEY, retention_days BIGINT NOT NULL);
+            INSERT INTO files VALUES
+                ('old', 'failed', NULL, '2026-01-01 00:00:00', NULL),
+                ('recent', 'failed', NULL, '2026-01-28 00:00:00', NULL),
+                ('kept', 'failed', NULL, '2026-01-01 00:00:00', NULL),
+                ('short', 'failed', 'layer_short', '2026-01-28 00:00:00', NULL),
+                ('ready', 'ready', 'layer_ready', '2026-01-01 00:00:00', NULL),
+                ('reimported', 'failed', NULL, '2025-06-01 00:00:00', '2026-01-20 00:00:00');
+            INSERT INTO file_retention VALUES ('kept', 0), ('short', 1);
+            ",
+        )
+        .unwrap();
+        let now =
+            NaiveDateTime::parse_from_str("2026-02-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+
+        // 'reimported' failed twelve days ago, long after it was uploaded.
+        assert_eq!(
+            expired_failed_files(&conn, 30, now).unwrap(),
+            vec![
+                ("old".to_string(), None),
+                ("short".to_string(), Some("layer_short".to_string())),
+            ]
+        );
+        // With no default only the override applies.
+        assert_eq!(
+            expired_failed_files(&conn, 0, now).unwrap(),
+            vec![("short".to_string(), Some("layer_short".to_string()))]
+        );
+    }
+}
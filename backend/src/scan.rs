@@ -0,0 +1,216 @@
+//! Upload malware scanning
+//!
+//! When `upload_scan_command` or `upload_scan_clamd` is configured, every
+//! upload is scanned after it is written and before it is imported or
+//! appended. An upload that is infected, or that the scanner could not check,
+//! is moved to `<upload dir>/quarantine/<id>` and its file marked failed with
+//! the scan error. The quarantine is left for admins to review; backups and
+//! the orphan sweep skip it.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// Subdirectory of the upload directory holding quarantined uploads.
+pub const QUARANTINE_DIR: &str = "quarantine";
+/// Replaced by the upload's path in `upload_scan_command`.
+const PATH_PLACEHOLDER: &str = "{path}";
+const SCAN_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+/// Below clamd's default `StreamMaxLength` chunking.
+const CLAMD_CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UploadScanner {
+    /// Program and arguments, split on whitespace; `{path}` is replaced by the
+    /// upload's path, which is appended when there is no placeholder. Exit
+    /// status 0 means clean and 1 infected, as with `clamscan` and
+    /// `clamdscan`; anything else is a scanner failure.
+    Command(Vec<String>),
+    /// `host:port` of a clamd daemon, sent the upload with `INSTREAM`.
+    Clamd(String),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ScanError {
+    /// The scanner's name for what it found.
+    Infected(String),
+    Failed(String),
+}
+
+impl std::fmt::Display for ScanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScanError::Infected(found) => write!(f, "Malware scan detected {found}"),
+            ScanError::Failed(reason) => write!(f, "Malware scan failed: {reason}"),
+        }
+    }
+}
+
+impl UploadScanner {
+    pub fn command(command: &str) -> Option<Self> {
+        let args: Vec<String> = command.split_whitespace().map(str::to_string).collect();
+        (!args.is_empty()).then_some(UploadScanner::Command(args))
+    }
+
+    pub async fn scan(&self, path: &Path) -> Result<(), ScanError> {
+        let scan = async {
+            match self {
+                UploadScanner::Command(args) => scan_with_command(args, path).await,
+                UploadScanner::Clamd(address) => scan_with_clamd(address, path).await,
+            }
+        };
+        tokio::time::timeout(SCAN_TIMEOUT, scan)
+            .await
+            .unwrap_or_else(|_| Err(ScanError::Failed("timed out".to_string())))
+    }
+}
+
+async fn scan_with_command(args: &[String], path: &Path) -> Result<(), ScanError> {
+    let path = path.to_string_lossy();
+    let has_placeholder = args.iter().any(|arg| arg.contains(PATH_PLACEHOLDER));
+    let mut args: Vec<String> = args
+        .iter()
+        .map(|arg| arg.replace(PATH_PLACEHOLDER, &path))
+        .collect();
+    if !has_placeholder {
+        args.push(path.to_string());
+    }
+
+    let output = tokio::process::Command::new(&args[0])
+        .args(&args[1..])
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| ScanError::Failed(format!("could not run {}: {e}", args[0])))?;
+    let report = |bytes: &[u8]| {
+        String::from_utf8_lossy(bytes)
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .unwrap_or_default()
+            .to_string()
+    };
+    match output.status.code() {
+        Some(0) => Ok(()),
+        Some(1) => Err(ScanError::Infected(
+            Some(report(&output.stdout))
+                .filter(|found| !found.is_empty())
+                .unwrap_or_else(|| "malware".to_string()),
+        )),
+        _ => Err(ScanError::Failed(format!(
+            "{} exited with {}: {}",
+            args[0],
+            output.status,
+            report(&output.stderr)
+        ))),
+    }
+}
+
+async fn scan_with_clamd(address: &str, path: &Path) -> Result<(), ScanError> {
+    let failed = |e: std::io::Error| ScanError::Failed(format!("clamd at {address}: {e}"));
+
+    let mut file = tokio::fs::File::open(path).await.map_err(failed)?;
+    let mut stream = tokio::net::TcpStream::connect(address)
+        .await
+        .map_err(failed)?;
+    stream.write_all(b"zINSTREAM\0").await.map_err(failed)?;
+    let mut chunk = vec![0u8; CLAMD_CHUNK_SIZE];
+    loop {
+        let read = file.read(&mut chunk).await.map_err(failed)?;
+        if read == 0 {
+            break;
+        }
+        stream
+            .write_all(&(read as u32).to_be_bytes())
+            .await
+            .map_err(failed)?;
+        stream.write_all(&chunk[..read]).await.map_err(failed)?;
+    }
+    stream
+        .write_all(&0u32.to_be_bytes())
+        .await
+        .map_err(failed)?;
+
+    let mut reply = Vec::new();
+    stream.read_to_end(&mut reply).await.map_err(failed)?;
+    parse_clamd_reply(&String::from_utf8_lossy(&reply))
+}
+
+/// `stream: OK`, `stream: <signature> FOUND` or an error such as
+/// `INSTREAM size limit exceeded. ERROR`.
+fn parse_clamd_reply(reply: &str) -> Result<(), ScanError> {
+    let reply = reply.trim_end_matches('\0').trim();
+    let result = reply.strip_prefix("stream:").map(str::trim);
+    match result {
+        Some("OK") => Ok(()),
+        Some(result) if result.ends_with(" FOUND") => Err(ScanError::Infected(
+            result.trim_end_matches(" FOUND").trim().to_string(),
+        )),
+        _ => Err(ScanError::Failed(format!("clamd replied '{reply}'"))),
+    }
+}
+
+/// Move an upload's directory into the quarantine. Returns the new path of
+/// `file_path`.
+pub async fn quarantine_upload(
+    upload_dir: &Path,
+    upload_id: &str,
+    file_path: &Path,
+) -> std::io::Result<PathBuf> {
+    let quarantine = upload_dir.join(QUARANTINE_DIR);
+    tokio::fs::create_dir_all(&quarantine).await?;
+    let target = quarantine.join(upload_id);
+    tokio::fs::rename(upload_dir.join(upload_id), &target).await?;
+    Ok(match file_path.file_name() {
+        Some(name) => target.join(name),
+        None => target,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clamd_replies_are_parsed() {
+        assert_eq!(parse_clamd_reply("stream: OK\0"), Ok(()));
+        assert_eq!(
+            parse_clamd_reply("stream: Win.Test.EICAR_HDB-1 FOUND\0"),
+            Err(ScanError::Infected("Win.Test.EICAR_HDB-1".to_string()))
+        );
+        assert!(matches!(
+            parse_clamd_reply("INSTREAM size limit exceeded. ERROR\0"),
+            Err(ScanError::Failed(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn command_exit_status_decides() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("roads.geojson");
+        std::fs::write(&path, "{}").unwrap();
+
+        assert_eq!(
+            UploadScanner::command("test -f {path}")
+                .unwrap()
+                .scan(&path)
+                .await,
+            Ok(())
+        );
+        assert_eq!(
+            UploadScanner::command("test ! -f {path}")
+                .unwrap()
+                .scan(&path)
+                .await,
+            Err(ScanError::Infected("malware".to_string()))
+        );
+        assert!(matches!(
+            UploadScanner::command("false-scanner-that-does-not-exist")
+                .unwrap()
+                .scan(&path)
+                .await,
+            Err(ScanError::Failed(_))
+        ));
+    }
+}
@@ -0,0 +1,124 @@
+//! Optional first-run demo data (`SEED_DEMO=true`)
+//!
+//! Imports a small bundled GeoJSON and publishes it, so a fresh instance shows
+//! a working map and public tile URL instead of an empty file list.
+
+use chrono::Utc;
+use tokio::fs;
+
+use crate::import::import_spatial_data;
+use crate::models::{AppState, PublishAccess, PublishResponse};
+use crate::thumbnail::write_thumbnail;
+
+const DEMO_GEOJSON: &str = include_str!("../assets/demo_cities.geojson");
+const DEMO_FILE_NAME: &str = "demo_cities.geojson";
+pub const DEMO_SLUG: &str = "demo";
+const DEMO_SEEDED_KEY: &str = "demo_seeded";
+
+/// Seed the demo dataset once. Returns `Ok(None)` when the instance was seeded
+/// before or already has files of its own.
+pub async fn seed_demo_data(state: &AppState) -> Result<Option<PublishResponse>, String> {
+    {
+        let conn = state.db.lock().await;
+        let (seeded, file_count): (i64, i64) = conn
+            .query_row(
+                "SELECT (SELECT COUNT(*) FROM system_settings WHERE key = ?), (SELECT COUNT(*) FROM files)",
+                duckdb::params![DEMO_SEEDED_KEY],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(|e| format!("Failed to check seed state: {}", e))?;
+        if seeded > 0 || file_count > 0 {
+            return Ok(None);
+        }
+    }
+
+    let file_id = crate::create_id();
+    let dir = state.upload_dir.join(&file_id);
+    fs::create_dir_all(&dir)
+        .await
+        .map_err(|e| format!("Failed to create demo upload dir: {}", e))?;
+    let file_path = dir.join(DEMO_FILE_NAME);
+    fs::write(&file_path, DEMO_GEOJSON)
+        .await
+        .map_err(|e| format!("Failed to write demo dataset: {}", e))?;
+
+    let name = DEMO_FILE_NAME.trim_end_matches(".geojson");
+    {
+        let conn = state.db.lock().await;
+        conn.execute(
+            "INSERT INTO files (id, name, type, size, uploaded_at, status, path, is_public)
+             VALUES (?, ?, 'geojson', ?, ?, 'processing', ?, FALSE)",
+            duckdb::params![
+                &file_id,
+                name,
+                DEMO_GEOJSON.len() as i64,
+                Utc::now().to_rfc3339(),
+                crate::storage_path_string(&file_path),
+            ],
+        )
+        .map_err(|e| format!("Failed to register demo dataset: {}", e))?;
+    }
+
+    if let Err(e) = import_spatial_data(&state.db, &file_id, &file_path).await {
+        let conn = state.db.lock().await;
+        let _ = conn.execute(
+            "UPDATE files SET status = 'failed', error = ?, failed_at = ? WHERE id = ?",
+            duckdb::params![&e, Utc::now().naive_utc(), &file_id],
+        );
+        return Err(e);
+    }
+
+    let conn = state.db.lock().await;
+    if let Err(e) = write_thumbnail(&conn, &file_id, &file_path) {
+        tracing::warn!(%file_id, error = %e, "Failed to draw demo dataset thumbnail");
+    }
+    conn.execute(
+        "UPDATE files SET status = 'ready' WHERE id = ?",
+        duckdb::params![&file_id],
+    )
+    .map_err(|e| format!("Failed to mark demo dataset ready: {}", e))?;
+
+    conn.execute_batch("BEGIN TRANSACTION")
+        .map_err(|e| format!("Failed to publish demo dataset: {}", e))?;
+    let published = conn
+        .execute(
+            "INSERT INTO published_files (file_id, slug) VALUES (?, ?)",
+            duckdb::params![&file_id, DEMO_SLUG],
+        )
+        .and_then(|_| {
+            conn.execute(
+                "UPDATE files SET is_public = TRUE WHERE id = ?",
+                duckdb::params![&file_id],
+            )
+        })
+        .and_then(|_| {
+            conn.execute(
+                "INSERT OR REPLACE INTO system_settings (key, value) VALUES (?, '1')",
+                duckdb::params![DEMO_SEEDED_KEY],
+            )
+        });
+
+    match published {
+        Ok(_) => {
+            conn.execute_batch("COMMIT")
+                .map_err(|e| format!("Failed to publish demo dataset: {}", e))?;
+            Ok(Some(PublishResponse {
+                url: format!("/tiles/{DEMO_SLUG}/{{z}}/{{x}}/{{y}}"),
+                slug: DEMO_SLUG.to_string(),
+                is_public: true,
+                access: PublishAccess::Public,
+                signing_secret: None,
+                expires_at: None,
+                minzoom: None,
+                maxzoom: None,
+                tile_options: None,
+                allowed_origins: None,
+                version: None,
+            }))
+        }
+        Err(e) => {
+            let _ = conn.execute_batch("ROLLBACK");
+            Err(format!("Failed to publish demo dataset: {}", e))
+        }
+    }
+}
@@ -1,21 +1,90 @@
 use async_trait::async_trait;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
 use tower_sessions::{
     session::{Id, Record},
-    session_store::Error,
+    session_store::{Error, ExpiredDeletion},
     SessionStore,
 };
 
+/// How long an unchanged session may go without its expiry being written back.
+pub const DEFAULT_SESSION_WRITE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often the server deletes expired sessions.
+pub const SESSION_CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Last known state of a session, kept so that requests do not hit DuckDB
+/// (and its global lock) when nothing changed.
+#[derive(Debug, Clone)]
+struct CachedSession {
+    record: Record,
+    persisted_expiry: time::OffsetDateTime,
+    persisted_at: Instant,
+}
+
+/// Session store backed by the `sessions` table with a write-behind cache.
+///
+/// Loads are served from memory once a session has been seen. Saves only reach
+/// the database when the session data changed, or when only the expiry moved
+/// and the last write is older than the write interval.
 #[derive(Debug, Clone)]
 pub struct DuckDBStore {
     conn: Arc<Mutex<duckdb::Connection>>,
+    cache: Arc<std::sync::Mutex<HashMap<Id, CachedSession>>>,
+    write_interval: Duration,
 }
 
 impl DuckDBStore {
     pub fn new(conn: Arc<Mutex<duckdb::Connection>>) -> Self {
-        Self { conn }
+        Self {
+            conn,
+            cache: Arc::default(),
+            write_interval: DEFAULT_SESSION_WRITE_INTERVAL,
+        }
+    }
+
+    pub fn with_write_interval(mut self, write_interval: Duration) -> Self {
+        self.write_interval = write_interval;
+        self
+    }
+
+    /// Forget cached sessions, e.g. after the `sessions` table was cleared directly.
+    pub fn clear_cache(&self) {
+        self.cache_lock().clear();
+    }
+
+    fn cache_lock(&self) -> std::sync::MutexGuard<'_, HashMap<Id, CachedSession>> {
+        // The cache only holds copies of persisted state; a poisoned lock is still usable.
+        self.cache.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn remember(&self, record: &Record) {
+        self.cache_lock().insert(
+            record.id,
+            CachedSession {
+                record: record.clone(),
+                persisted_expiry: record.expiry_date,
+                persisted_at: Instant::now(),
+            },
+        );
+    }
+
+    /// Update the cached copy without writing when only the expiry was extended
+    /// and the last write is recent enough.
+    fn try_defer_save(&self, record: &Record) -> bool {
+        let mut cache = self.cache_lock();
+        let Some(cached) = cache.get_mut(&record.id) else {
+            return false;
+        };
+        let deferrable = cached.record.data == record.data
+            && record.expiry_date >= cached.persisted_expiry
+            && cached.persisted_at.elapsed() < self.write_interval;
+        if deferrable {
+            cached.record = record.clone();
+        }
+        deferrable
     }
 
     fn record_to_json(record: &Record) -> Result<String, Error> {
@@ -50,6 +119,10 @@ impl DuckDBStore {
 #[async_trait]
 impl SessionStore for DuckDBStore {
     async fn save(&self, session_record: &Record) -> Result<(), Error> {
+        if self.try_defer_save(session_record) {
+            return Ok(());
+        }
+
         let conn = self.conn.lock().await;
         let id = session_record.id.to_string();
         let data = Self::record_to_json(session_record)?;
@@ -63,11 +136,27 @@ impl SessionStore for DuckDBStore {
             duckdb::params![id, data, expiry_date],
         )
         .map_err(|e| Error::Backend(format!("Failed to save session: {}", e)))?;
+        drop(conn);
 
+        self.remember(session_record);
         Ok(())
     }
 
     async fn load(&self, session_id: &Id) -> Result<Option<Record>, Error> {
+        let cached = self
+            .cache_lock()
+            .get(session_id)
+            .map(|cached| cached.record.clone());
+        if let Some(record) = cached {
+            if record.expiry_date < time::OffsetDateTime::now_utc() {
+                if let Err(e) = self.delete(session_id).await {
+                    tracing::warn!(%session_id, error = %e, "Failed to delete expired session");
+                }
+                return Ok(None);
+            }
+            return Ok(Some(record));
+        }
+
         let (id, data, expiry_date) = {
             let conn = self.conn.lock().await;
             let id = session_id.to_string();
@@ -102,16 +191,19 @@ impl SessionStore for DuckDBStore {
         let now = chrono::Utc::now();
         if expiry_date < now {
             if let Err(e) = self.delete(session_id).await {
-                eprintln!("Failed to delete expired session {}: {}", session_id, e);
+                tracing::warn!(%session_id, error = %e, "Failed to delete expired session");
             }
             return Ok(None);
         }
 
         let record = Self::json_to_record(&id, &data, expiry_date)?;
+        self.remember(&record);
         Ok(Some(record))
     }
 
     async fn delete(&self, session_id: &Id) -> Result<(), Error> {
+        self.cache_lock().remove(session_id);
+
         let conn = self.conn.lock().await;
         let id = session_id.to_string();
 
@@ -122,6 +214,31 @@ impl SessionStore for DuckDBStore {
     }
 }
 
+/// Sessions are only dropped when loaded after they expired, so ones that are
+/// never used again stay until this runs; the server calls it every
+/// `SESSION_CLEANUP_INTERVAL`.
+#[async_trait]
+impl ExpiredDeletion for DuckDBStore {
+    async fn delete_expired(&self) -> Result<(), Error> {
+        let now = time::OffsetDateTime::now_utc();
+        self.cache_lock()
+            .retain(|_, cached| cached.record.expiry_date >= now);
+
+        // Served by idx_sessions_expiry_date.
+        let conn = self.conn.lock().await;
+        let deleted = conn
+            .execute(
+                "DELETE FROM sessions WHERE expiry_date < ?",
+                duckdb::params![chrono::Utc::now()],
+            )
+            .map_err(|e| Error::Backend(format!("Failed to delete expired sessions: {}", e)))?;
+        if deleted > 0 {
+            tracing::debug!(deleted, "Deleted expired sessions");
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -238,6 +355,74 @@ mod tests {
         assert_eq!(loaded.data, data);
     }
 
+    fn stored_expiry(conn: &duckdb::Connection, id: &Id) -> chrono::DateTime<chrono::Utc> {
+        conn.query_row(
+            "SELECT expiry_date FROM sessions WHERE id = ?",
+            duckdb::params![id.to_string()],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_expiry_only_saves_are_batched() {
+        let (store, _temp_dir) = create_test_store().await;
+        let mut record = create_test_record();
+        store.save(&record).await.unwrap();
+
+        let persisted_expiry = stored_expiry(&*store.conn.lock().await, &record.id);
+
+        // Extending the expiry alone stays in memory within the write interval.
+        record.expiry_date += time::Duration::hours(1);
+        store.save(&record).await.unwrap();
+        let expiry_after = stored_expiry(&*store.conn.lock().await, &record.id);
+        assert_eq!(expiry_after, persisted_expiry);
+        assert_eq!(
+            store.load(&record.id).await.unwrap().unwrap().expiry_date,
+            record.expiry_date
+        );
+
+        // A data change is written through immediately.
+        record
+            .data
+            .insert("theme".to_string(), serde_json::json!("dark"));
+        store.save(&record).await.unwrap();
+        let expiry_after = stored_expiry(&*store.conn.lock().await, &record.id);
+        assert!(expiry_after > persisted_expiry);
+    }
+
+    #[tokio::test]
+    async fn test_expiry_is_written_after_interval() {
+        let (store, _temp_dir) = create_test_store().await;
+        let store = store.with_write_interval(Duration::ZERO);
+        let mut record = create_test_record();
+        store.save(&record).await.unwrap();
+        let persisted_expiry = stored_expiry(&*store.conn.lock().await, &record.id);
+
+        record.expiry_date += time::Duration::hours(1);
+        store.save(&record).await.unwrap();
+        let expiry_after = stored_expiry(&*store.conn.lock().await, &record.id);
+        assert!(expiry_after > persisted_expiry);
+    }
+
+    #[tokio::test]
+    async fn test_clear_cache_reloads_from_database() {
+        let (store, _temp_dir) = create_test_store().await;
+        let record = create_test_record();
+        store.save(&record).await.unwrap();
+
+        store
+            .conn
+            .lock()
+            .await
+            .execute("DELETE FROM sessions", [])
+            .unwrap();
+        assert!(store.load(&record.id).await.unwrap().is_some());
+
+        store.clear_cache();
+        assert_eq!(store.load(&record.id).await.unwrap(), None);
+    }
+
     #[tokio::test]
     async fn test_expired_session_returns_none() {
         let (store, _temp_dir) = create_test_store().await;
@@ -259,4 +444,27 @@ mod tests {
         let loaded = store.load(&record.id).await.unwrap();
         assert_eq!(loaded, None, "Expired session should return None");
     }
+
+    #[tokio::test]
+    async fn test_delete_expired_removes_only_expired_rows() {
+        let (store, _temp_dir) = create_test_store().await;
+        let live = create_test_record();
+        let mut expired = create_test_record();
+        expired.id = Id::default();
+        expired.expiry_date = time::OffsetDateTime::now_utc() - time::Duration::hours(1);
+        store.save(&live).await.unwrap();
+        store.save(&expired).await.unwrap();
+
+        store.delete_expired().await.unwrap();
+
+        let ids: Vec<String> = {
+            let conn = store.conn.lock().await;
+            let mut stmt = conn.prepare("SELECT id FROM sessions").unwrap();
+            let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+            rows.map(Result::unwrap).collect()
+        };
+        assert_eq!(ids, vec![live.id.to_string()]);
+        assert!(!store.cache_lock().contains_key(&expired.id));
+        assert!(store.load(&live.id).await.unwrap().is_some());
+    }
 }
@@ -0,0 +1,271 @@
+//! Runtime settings
+//!
+//! Values admins can change through `/api/admin/settings` without a restart.
+//! They are stored as rows of `system_settings`, next to the `initialized`
+//! flag; a value that was never saved falls back to the environment (or
+//! built-in) default, so a fresh instance behaves exactly as configured.
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, routing::get, Json, Router};
+use duckdb::OptionalExt;
+
+use crate::config::format_bytes;
+use crate::http_errors::{bad_request, internal_error};
+use crate::models::Settings;
+use crate::retention::{validate_retention_days, DEFAULT_FAILED_UPLOAD_RETENTION_DAYS};
+use crate::versions::{DEFAULT_DATASET_VERSION_RETENTION, MAX_DATASET_VERSION_RETENTION};
+use crate::{AppState, ErrorResponse};
+
+/// `Cache-Control: max-age` of public tiles, TileJSON, styles and viewers.
+pub const DEFAULT_CACHE_TTL_SECS: u64 = 300;
+const MAX_CACHE_TTL_SECS: u64 = 365 * 24 * 60 * 60;
+
+const CACHE_TTL_KEY: &str = "cache_ttl_secs";
+const UPLOAD_MAX_SIZE_KEY: &str = "upload_max_size_bytes";
+const PUBLIC_BASE_URL_KEY: &str = "public_base_url";
+const REGISTRATION_ENABLED_KEY: &str = "registration_enabled";
+const FAILED_UPLOAD_RETENTION_KEY: &str = "failed_upload_retention_days";
+const DATASET_VERSION_RETENTION_KEY: &str = "dataset_version_retention";
+
+pub fn build_settings_router() -> Router<AppState> {
+    Router::new().route(
+        "/api/admin/settings",
+        get(get_settings).put(update_settings),
+    )
+}
+
+/// Settings in effect: saved values over the defaults in `state`.
+pub fn load_settings(
+    conn: &duckdb::Connection,
+    state: &AppState,
+) -> Result<Settings, duckdb::Error> {
+    let mut settings = Settings {
+        cache_ttl_secs: DEFAULT_CACHE_TTL_SECS,
+        upload_max_size_bytes: state.max_size,
+        public_base_url: state.public_base_url.clone(),
+        registration_enabled: false,
+        failed_upload_retention_days: DEFAULT_FAILED_UPLOAD_RETENTION_DAYS,
+        dataset_version_retention: DEFAULT_DATASET_VERSION_RETENTION,
+    };
+    let mut stmt = conn.prepare("SELECT key, value FROM system_settings")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    })?;
+    for row in rows {
+        let (key, value) = row?;
+        // Unparseable rows keep the default rather than failing every request.
+        match key.as_str() {
+            CACHE_TTL_KEY => {
+                if let Ok(ttl) = value.parse() {
+                    settings.cache_ttl_secs = ttl;
+                }
+            }
+            UPLOAD_MAX_SIZE_KEY => {
+                if let Ok(size) = value.parse() {
+                    settings.upload_max_size_bytes = size;
+                }
+            }
+            PUBLIC_BASE_URL_KEY => {
+                if !value.is_empty() {
+                    settings.public_base_url = Some(value);
+                }
+            }
+            REGISTRATION_ENABLED_KEY => settings.registration_enabled = value == "true",
+            FAILED_UPLOAD_RETENTION_KEY => {
+                if let Ok(days) = value.parse() {
+                    settings.failed_upload_retention_days = days;
+                }
+            }
+            DATASET_VERSION_RETENTION_KEY => {
+                if let Ok(count) = value.parse() {
+                    settings.dataset_version_retention = count;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(settings)
+}
+
+/// `datasetVersionRetention` in effect, for imports that hold only a
+/// connection; it has no environment default.
+pub fn dataset_version_retention(conn: &duckdb::Connection) -> Result<u64, duckdb::Error> {
+    let value: Option<String> = conn
+        .query_row(
+            "SELECT value FROM system_settings WHERE key = ?",
+            duckdb::params![DATASET_VERSION_RETENTION_KEY],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(value
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_DATASET_VERSION_RETENTION))
+}
+
+fn save_settings(conn: &duckdb::Connection, settings: &Settings) -> Result<(), duckdb::Error> {
+    let values = [
+        (CACHE_TTL_KEY, settings.cache_ttl_secs.to_string()),
+        (
+            UPLOAD_MAX_SIZE_KEY,
+            settings.upload_max_size_bytes.to_string(),
+        ),
+        (
+            PUBLIC_BASE_URL_KEY,
+            settings.public_base_url.clone().unwrap_or_default(),
+        ),
+        (
+            REGISTRATION_ENABLED_KEY,
+            settings.registration_enabled.to_string(),
+        ),
+        (
+            FAILED_UPLOAD_RETENTION_KEY,
+            settings.failed_upload_retention_days.to_string(),
+        ),
+        (
+            DATASET_VERSION_RETENTION_KEY,
+            settings.dataset_version_retention.to_string(),
+        ),
+    ];
+    for (key, value) in values {
+        conn.execute(
+            "INSERT OR REPLACE INTO system_settings (key, value) VALUES (?, ?)",
+            duckdb::params![key, value],
+        )?;
+    }
+    Ok(())
+}
+
+/// Check ranges and normalise the base URL (no trailing slash).
+pub fn validate_settings(mut settings: Settings) -> Result<Settings, String> {
+    if settings.cache_ttl_secs > MAX_CACHE_TTL_SECS {
+        return Err(format!("cacheTtlSecs must be at most {MAX_CACHE_TTL_SECS}"));
+    }
+    if settings.upload_max_size_bytes == 0 {
+        return Err("uploadMaxSizeBytes must be positive".to_string());
+    }
+    validate_retention_days(
+        settings.failed_upload_retention_days,
+        "failedUploadRetentionDays",
+    )?;
+    if settings.dataset_version_retention > MAX_DATASET_VERSION_RETENTION {
+        return Err(format!(
+            "datasetVersionRetention must be at most {MAX_DATASET_VERSION_RETENTION} (0 keeps every version)"
+        ));
+    }
+    settings.public_base_url = match settings.public_base_url.as_deref() {
+        Some(url) => normalize_base_url(url).map_err(|e| format!("publicBaseUrl {e}"))?,
+        None => None,
+    };
+    Ok(settings)
+}
+
+/// A base URL without its trailing slash, `None` when blank.
+pub fn normalize_base_url(url: &str) -> Result<Option<String>, String> {
+    match url.trim() {
+        "" => Ok(None),
+        url if url.starts_with("http://") || url.starts_with("https://") => {
+            Ok(Some(url.trim_end_matches('/').to_string()))
+        }
+        _ => Err("must start with http:// or https://".to_string()),
+    }
+}
+
+/// `path` under the public base URL, or as is when none is configured.
+pub fn public_url(settings: &Settings, path: &str) -> String {
+    format!(
+        "{}{path}",
+        settings.public_base_url.as_deref().unwrap_or_default()
+    )
+}
+
+/// Effective upload limit in bytes, with its label for error messages.
+pub async fn upload_max_size(
+    state: &AppState,
+) -> Result<(u64, String), (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.db.lock().await;
+    let max_size = load_settings(&conn, state)
+        .map_err(internal_error)?
+        .upload_max_size_bytes;
+    Ok((max_size, format_bytes(max_size)))
+}
+
+/// `Cache-Control` value for public responses.
+pub fn public_cache_control(settings: &Settings) -> String {
+    format!("public, max-age={}", settings.cache_ttl_secs)
+}
+
+async fn get_settings(
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.db.lock().await;
+    let settings = load_settings(&conn, &state).map_err(internal_error)?;
+    Ok(Json(settings))
+}
+
+async fn update_settings(
+    State(state): State<AppState>,
+    Json(settings): Json<Settings>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let settings = validate_settings(settings).map_err(|e| bad_request(&e))?;
+    let conn = state.db.lock().await;
+    save_settings(&conn, &settings).map_err(internal_error)?;
+    Ok(Json(settings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(public_base_url: Option<&str>) -> Settings {
+        Settings {
+            cache_ttl_secs: 60,
+            upload_max_size_bytes: 1024,
+            public_base_url: public_base_url.map(str::to_string),
+            registration_enabled: false,
+            failed_upload_retention_days: 30,
+            dataset_version_retention: 10,
+        }
+    }
+
+    #[test]
+    fn base_urls_are_normalised() {
+        assert_eq!(
+            validate_settings(settings(Some(" https://maps.example.com/ ")))
+                .unwrap()
+                .public_base_url
+                .as_deref(),
+            Some("https://maps.example.com")
+        );
+        assert_eq!(
+            validate_settings(settings(Some("")))
+                .unwrap()
+                .public_base_url,
+            None
+        );
+        assert!(validate_settings(settings(Some("maps.example.com"))).is_err());
+        assert_eq!(
+            public_url(
+                &settings(Some("https://maps.example.com")),
+                "/tiles/roads/tilejson.json"
+            ),
+            "https://maps.example.com/tiles/roads/tilejson.json"
+        );
+        assert_eq!(public_url(&settings(None), "/view/roads"), "/view/roads");
+    }
+
+    #[test]
+    fn out_of_range_values_are_rejected() {
+        let mut too_long = settings(None);
+        too_long.cache_ttl_secs = MAX_CACHE_TTL_SECS + 1;
+        assert!(validate_settings(too_long).is_err());
+        let mut empty = settings(None);
+        empty.upload_max_size_bytes = 0;
+        assert!(validate_settings(empty).is_err());
+        let mut forever = settings(None);
+        forever.failed_upload_retention_days = 100_000;
+        assert!(validate_settings(forever).is_err());
+        let mut hoarder = settings(None);
+        hoarder.dataset_version_retention = MAX_DATASET_VERSION_RETENTION + 1;
+        assert!(validate_settings(hoarder).is_err());
+        assert_eq!(public_cache_control(&settings(None)), "public, max-age=60");
+    }
+}
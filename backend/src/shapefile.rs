@@ -0,0 +1,267 @@
+//! Shapefile imports
+//!
+//! A shapefile zip may hold several shapefiles, each a `.shp`, `.shx` and
+//! `.dbf` sharing a name. Each becomes a dataset of its own, named
+//! `<name> (<shapefile>)`; see `layers.rs`. The upload's `layers` form field
+//! picks which shapefiles to import, by name and comma-separated; it is kept
+//! in `files.layer` until the import fans out, after which each file's
+//! `layer` names the one shapefile it reads.
+//!
+//! A shapefile's DBF is in whatever code page it was written with, named by
+//! the `.cpg` beside it. GDAL is told that encoding with the `ENCODING` open
+//! option so attributes are recoded to UTF-8 on import. An upload's
+//! `encoding` form field, stored in `files.encoding`, overrides the `.cpg`,
+//! for zips that lack one or name the wrong code page.
+
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+use zip::ZipArchive;
+
+use crate::columns::quote_literal;
+use crate::import::{detect_crs, gdal_source_path, load_dataset_table};
+use crate::layers::{create_layer_file, layer_name};
+use crate::track_import;
+
+const MAX_ENCODING_LENGTH: usize = 40;
+/// `.cpg` files hold a single code page name.
+const MAX_CPG_SIZE: u64 = 1024;
+
+/// The GDAL name of a code page as written in a `.cpg` or by a user: code
+/// page numbers become `CP<n>` and `8859-<n>` becomes `ISO-8859-<n>`.
+pub fn normalize_encoding(value: &str) -> Result<String, String> {
+    let value = value.trim();
+    if value.is_empty()
+        || value.len() > MAX_ENCODING_LENGTH
+        || !value
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b' '))
+    {
+        return Err(format!("Unknown encoding '{value}'"));
+    }
+    let upper = value.to_ascii_uppercase();
+    let encoding = if upper == "UTF8" || upper == "UTF-8" {
+        "UTF-8".to_string()
+    } else if let Some(part) = upper.strip_prefix("8859") {
+        format!("ISO-8859-{}", part.trim_start_matches(['-', '_']))
+    } else if let Some(code_page) = upper.strip_prefix("ANSI ") {
+        format!("CP{}", code_page.trim())
+    } else if upper.bytes().all(|b| b.is_ascii_digit()) {
+        format!("CP{upper}")
+    } else {
+        upper
+    };
+    Ok(encoding)
+}
+
+/// Names of the complete shapefiles in a zip, in archive order: the paths of
+/// their `.shp` without the extension.
+pub fn shapefile_layers(zip_path: &Path) -> Result<Vec<String>, String> {
+    let file = std::fs::File::open(zip_path).map_err(|_| "Unable to read zip file".to_string())?;
+    let mut archive = ZipArchive::new(file).map_err(|_| "Unable to read zip file".to_string())?;
+    let mut names = Vec::with_capacity(archive.len());
+    for i in 0..archive.len() {
+        let entry = archive
+            .by_index(i)
+            .map_err(|_| "Unable to read zip file".to_string())?;
+        if entry.is_file() && !entry.name().starts_with("__MACOSX/") {
+            names.push(entry.name().to_string());
+        }
+    }
+
+    let has = |name: String| names.iter().any(|entry| entry.eq_ignore_ascii_case(&name));
+    Ok(names
+        .iter()
+        .filter(|name| name.to_lowercase().ends_with(".shp"))
+        .map(|name| name[..name.len() - 4].to_string())
+        .filter(|layer| has(format!("{layer}.shx")) && has(format!("{layer}.dbf")))
+        .collect())
+}
+
+/// The shapefiles of a zip named by a comma-separated `selection`, or all of
+/// them without one. Names match case-insensitively.
+pub fn select_layers(zip_path: &Path, selection: Option<&str>) -> Result<Vec<String>, String> {
+    let layers = shapefile_layers(zip_path)?;
+    if layers.is_empty() {
+        return Err("Shapefile zip must include .shp/.shx/.dbf with the same name".to_string());
+    }
+    let Some(selection) = selection else {
+        return Ok(layers);
+    };
+    let mut selected: Vec<String> = Vec::new();
+    for name in selection
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        let layer = layers
+            .iter()
+            .find(|layer| layer.eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                format!(
+                    "Zip has no shapefile '{name}'; it holds {}",
+                    layers.join(", ")
+                )
+            })?;
+        if !selected.contains(layer) {
+            selected.push(layer.clone());
+        }
+    }
+    if selected.is_empty() {
+        return Err("layers must name at least one shapefile".to_string());
+    }
+    Ok(selected)
+}
+
+/// The file name of a shapefile inside its zip, for dataset names.
+fn layer_title(layer: &str) -> &str {
+    layer.rsplit('/').next().unwrap_or(layer)
+}
+
+/// The encoding named by `layer`'s `.cpg` in a shapefile zip, or without a
+/// layer by its first `.cpg`, if any.
+pub fn cpg_encoding(zip_path: &Path, layer: Option<&str>) -> Option<String> {
+    let file = std::fs::File::open(zip_path).ok()?;
+    let mut archive = ZipArchive::new(file).ok()?;
+    for i in 0..archive.len() {
+        let entry = archive.by_index(i).ok()?;
+        let is_cpg = match layer {
+            Some(layer) => entry.name().eq_ignore_ascii_case(&format!("{layer}.cpg")),
+            None => entry.name().to_lowercase().ends_with(".cpg"),
+        };
+        if !entry.is_file() || !is_cpg {
+            continue;
+        }
+        let mut contents = String::new();
+        entry
+            .take(MAX_CPG_SIZE)
+            .read_to_string(&mut contents)
+            .ok()?;
+        return normalize_encoding(&contents).ok();
+    }
+    None
+}
+
+/// `ST_Read` arguments after the path for reading with `encoding`, e.g.
+/// `, open_options = ['ENCODING=CP936']`.
+pub fn read_options(encoding: Option<&str>) -> String {
+    match encoding {
+        Some(encoding) => format!(
+            ", open_options = [{}]",
+            quote_literal(&format!("ENCODING={encoding}"))
+        ),
+        None => String::new(),
+    }
+}
+
+/// Read one shapefile of a zip into a file's dataset, with `encoding` or else
+/// the shapefile's `.cpg`.
+fn import_layer(
+    conn: &duckdb::Connection,
+    file_id: &str,
+    file_path: &Path,
+    layer: &str,
+    encoding: Option<&str>,
+) -> Result<(), String> {
+    let source = format!("{}/{layer}.shp", gdal_source_path(file_path)?);
+    let detected_crs = detect_crs(conn, &source);
+    let encoding = encoding
+        .map(str::to_string)
+        .or_else(|| cpg_encoding(file_path, Some(layer)));
+    load_dataset_table(
+        conn,
+        file_id,
+        detected_crs.as_deref(),
+        &format!(
+            "SELECT row_number() OVER ()::BIGINT AS fid, *\n         FROM ST_Read({}{})",
+            quote_literal(&source),
+            read_options(encoding.as_deref())
+        ),
+    )
+}
+
+/// Import the first selected shapefile of a zip into `file_id` and start
+/// importing each further one into a new file.
+pub async fn import_shapefile(
+    db: &Arc<Mutex<duckdb::Connection>>,
+    file_id: &str,
+    file_path: &Path,
+) -> Result<(), String> {
+    let conn = db.lock().await;
+    let (name, selection, encoding): (String, Option<String>, Option<String>) = conn
+        .query_row(
+            "SELECT name, layer, encoding FROM files WHERE id = ?",
+            duckdb::params![file_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .map_err(|e| format!("File lookup failed: {e}"))?;
+    let layers = select_layers(file_path, selection.as_deref())?;
+    let Some((first, rest)) = layers.split_first() else {
+        return Err("No shapefile selected".to_string());
+    };
+    import_layer(&conn, file_id, file_path, first, encoding.as_deref())?;
+
+    let dataset_name = if rest.is_empty() {
+        name.clone()
+    } else {
+        layer_name(&name, layer_title(first))
+    };
+    conn.execute(
+        "UPDATE files SET name = ?, layer = ? WHERE id = ?",
+        duckdb::params![dataset_name, first, file_id],
+    )
+    .map_err(|e| format!("Failed to record shapefile: {e}"))?;
+
+    let mut layer_files = Vec::with_capacity(rest.len());
+    for layer in rest {
+        let created = create_layer_file(
+            &conn,
+            file_id,
+            file_path,
+            &layer_name(&name, layer_title(layer)),
+            layer,
+        );
+        match created {
+            Ok((layer_id, layer_path)) => layer_files.push((layer_id, layer_path, layer.clone())),
+            Err(e) => tracing::error!(file_id, layer, error = %e, "Failed to add shapefile"),
+        }
+    }
+    drop(conn);
+
+    for (layer_id, layer_path, layer) in layer_files {
+        let db = db.clone();
+        let encoding = encoding.clone();
+        tokio::spawn(async move {
+            let import = async {
+                let conn = db.lock().await;
+                import_layer(&conn, &layer_id, &layer_path, &layer, encoding.as_deref())
+            };
+            let _ = track_import(&db, &layer_id, &layer_path, import).await;
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn code_pages_are_normalized() {
+        assert_eq!(normalize_encoding("936\r\n").unwrap(), "CP936");
+        assert_eq!(normalize_encoding("utf8").unwrap(), "UTF-8");
+        assert_eq!(normalize_encoding("88591").unwrap(), "ISO-8859-1");
+        assert_eq!(normalize_encoding("8859-15").unwrap(), "ISO-8859-15");
+        assert_eq!(normalize_encoding("ANSI 1252").unwrap(), "CP1252");
+        assert_eq!(normalize_encoding("gbk").unwrap(), "GBK");
+        assert!(normalize_encoding("GBK'; DROP").is_err());
+        assert!(normalize_encoding(" ").is_err());
+        assert_eq!(
+            read_options(Some("CP936")),
+            ", open_options = ['ENCODING=CP936']"
+        );
+        assert_eq!(read_options(None), "");
+    }
+}
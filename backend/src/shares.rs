@@ -0,0 +1,140 @@
+//! Sharing files with other users
+//!
+//! An owner can grant a named user `read` or `edit` access to one file. Shares
+//! are keyed by user id, so renaming is safe and deleting a user drops theirs.
+//! Shared files show up in the recipient's file list; what they may do with
+//! them is enforced by `authz::require_file_access`.
+
+use axum::{
+    extract::{Path as AxumPath, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{delete, get},
+    Json, Router,
+};
+use chrono::Utc;
+use duckdb::OptionalExt;
+
+use crate::authz::FileAccess;
+use crate::http_errors::{bad_request, internal_error};
+use crate::models::{FileShare, ShareRequest};
+use crate::{AppState, ErrorResponse};
+
+pub fn build_shares_router() -> Router<AppState> {
+    Router::new()
+        .route("/api/files/{id}/shares", get(list_shares).post(share_file))
+        .route("/api/files/{id}/shares/{username}", delete(unshare_file))
+}
+
+fn not_found(error: String) -> (StatusCode, Json<ErrorResponse>) {
+    (StatusCode::NOT_FOUND, Json(ErrorResponse { error }))
+}
+
+/// Owner of a file; `None` when the file does not exist.
+fn file_owner(
+    conn: &duckdb::Connection,
+    file_id: &str,
+) -> Result<Option<Option<String>>, (StatusCode, Json<ErrorResponse>)> {
+    conn.query_row(
+        "SELECT owner_id FROM files WHERE id = ?",
+        duckdb::params![file_id],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(internal_error)
+}
+
+fn load_shares(conn: &duckdb::Connection, file_id: &str) -> Result<Vec<FileShare>, duckdb::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT u.username, s.access, s.created_at
+         FROM file_shares s
+         JOIN users u ON u.id = s.user_id
+         WHERE s.file_id = ?
+         ORDER BY u.username",
+    )?;
+    let shares = stmt
+        .query_map(duckdb::params![file_id], |row| {
+            let access: String = row.get(1)?;
+            let created_at: chrono::NaiveDateTime = row.get(2)?;
+            Ok(FileShare {
+                username: row.get(0)?,
+                access: FileAccess::parse(&access).unwrap_or(FileAccess::Read),
+                created_at: created_at.and_utc().to_rfc3339(),
+            })
+        })?
+        .collect::<Result<_, _>>()?;
+    Ok(shares)
+}
+
+async fn list_shares(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.db.lock().await;
+    if file_owner(&conn, &id)?.is_none() {
+        return Err(not_found("File not found".to_string()));
+    }
+    let shares = load_shares(&conn, &id).map_err(internal_error)?;
+    Ok(Json(shares))
+}
+
+/// Grant or change a user's access; sharing again updates the access level.
+async fn share_file(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+    Json(req): Json<ShareRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    if req.access == FileAccess::Own {
+        return Err(bad_request("Access must be 'read' or 'edit'"));
+    }
+    let username = req.username.trim();
+
+    let conn = state.db.lock().await;
+    let owner_id =
+        file_owner(&conn, &id)?.ok_or_else(|| not_found("File not found".to_string()))?;
+    let user_id: String = conn
+        .query_row(
+            "SELECT id FROM users WHERE username = ?",
+            duckdb::params![username],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(internal_error)?
+        .ok_or_else(|| not_found(format!("User '{username}' not found")))?;
+    if owner_id.as_deref() == Some(user_id.as_str()) {
+        return Err(bad_request("The file's owner already has full access"));
+    }
+
+    conn.execute(
+        "INSERT INTO file_shares (file_id, user_id, access, created_at) VALUES (?, ?, ?, ?)
+         ON CONFLICT (file_id, user_id) DO UPDATE SET access = excluded.access",
+        duckdb::params![&id, &user_id, req.access.as_str(), Utc::now().naive_utc()],
+    )
+    .map_err(internal_error)?;
+    let share = load_shares(&conn, &id)
+        .map_err(internal_error)?
+        .into_iter()
+        .find(|share| share.username == username)
+        .ok_or_else(|| internal_error("Share was not saved"))?;
+
+    Ok(Json(share))
+}
+
+async fn unshare_file(
+    State(state): State<AppState>,
+    AxumPath((id, username)): AxumPath<(String, String)>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.db.lock().await;
+    let deleted = conn
+        .execute(
+            "DELETE FROM file_shares
+             WHERE file_id = ? AND user_id IN (SELECT id FROM users WHERE username = ?)",
+            duckdb::params![&id, &username],
+        )
+        .map_err(internal_error)?;
+    if deleted == 0 {
+        return Err(not_found(format!("File is not shared with '{username}'")));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,203 @@
+//! Slug changes
+//!
+//! `PUT /api/files/{id}/slug` renames a published dataset's slug in place,
+//! keeping its tile options, access, expiry and zoom range. The old slug is
+//! recorded in `slug_history`, and for `SLUG_REDIRECT_DAYS` public requests
+//! under it (`/tiles/{old}/...` and `/view/{old}`) that would find nothing are
+//! answered with a 301 to the same path under the dataset's current slug, so
+//! embedded maps keep working while their owners update them. A slug that is
+//! published or used by a tileset again stops redirecting. Signed links are
+//! tied to their slug and must be signed again.
+
+use axum::{
+    extract::{Path as AxumPath, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{Duration, Utc};
+use duckdb::OptionalExt;
+
+use crate::http_errors::{bad_request, internal_error};
+use crate::models::{PublicTileUrl, SlugRequest};
+use crate::settings::{load_settings, public_url};
+use crate::tilesets::slug_in_use;
+use crate::{validate_slug, AppState, ErrorResponse};
+
+/// How long an old slug keeps redirecting after it was changed.
+pub const SLUG_REDIRECT_DAYS: i64 = 90;
+
+pub async fn update_slug(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+    Json(req): Json<SlugRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let slug = validate_slug(&req.slug).map_err(|e| bad_request(&e))?;
+
+    let conn = state.db.lock().await;
+    let current: String = conn
+        .query_row(
+            "SELECT slug FROM published_files WHERE file_id = ?",
+            duckdb::params![&id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(internal_error)?
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    error: "File not published".to_string(),
+                }),
+            )
+        })?;
+    if slug != current {
+        if slug_in_use(&conn, &slug).map_err(internal_error)? {
+            return Err((
+                StatusCode::CONFLICT,
+                Json(ErrorResponse {
+                    error: "Slug already in use".to_string(),
+                }),
+            ));
+        }
+        conn.execute_batch("BEGIN TRANSACTION")
+            .map_err(internal_error)?;
+        let renamed = rename_slug(&conn, &id, &current, &slug);
+        match renamed {
+            Ok(()) => conn.execute_batch("COMMIT").map_err(internal_error)?,
+            Err(e) => {
+                conn.execute_batch("ROLLBACK").map_err(internal_error)?;
+                return Err(internal_error(e));
+            }
+        }
+    }
+    let settings = load_settings(&conn, &state).map_err(internal_error)?;
+
+    Ok(Json(PublicTileUrl {
+        url: public_url(&settings, &format!("/tiles/{slug}/{{z}}/{{x}}/{{y}}")),
+        slug,
+    }))
+}
+
+/// Move `file_id` from slug `from` to `to`, remembering `from`.
+fn rename_slug(
+    conn: &duckdb::Connection,
+    file_id: &str,
+    from: &str,
+    to: &str,
+) -> Result<(), duckdb::Error> {
+    conn.execute(
+        "UPDATE published_files SET slug = ? WHERE file_id = ?",
+        duckdb::params![to, file_id],
+    )?;
+    // The new slug no longer points anywhere else.
+    conn.execute(
+        "DELETE FROM slug_history WHERE slug = ?",
+        duckdb::params![to],
+    )?;
+    conn.execute(
+        "INSERT OR REPLACE INTO slug_history (slug, file_id, replaced_at) VALUES (?, ?, ?)",
+        duckdb::params![from, file_id, Utc::now().naive_utc()],
+    )?;
+    Ok(())
+}
+
+/// The current slug of the dataset that gave up `slug` within the grace period,
+/// unless `slug` is in use again.
+fn renamed_slug(conn: &duckdb::Connection, slug: &str) -> Result<Option<String>, duckdb::Error> {
+    let cutoff = Utc::now().naive_utc() - Duration::days(SLUG_REDIRECT_DAYS);
+    conn.query_row(
+        "SELECT p.slug FROM slug_history h
+         JOIN published_files p ON p.file_id = h.file_id
+         WHERE h.slug = ? AND h.replaced_at > ?
+           AND h.slug NOT IN (SELECT slug FROM published_files)
+           AND h.slug NOT IN (SELECT slug FROM tilesets)",
+        duckdb::params![slug, cutoff],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// The slug of a public path: the segment after `/tiles` or `/view`.
+fn path_slug(path: &str) -> Option<&str> {
+    let mut segments = path.split('/');
+    match (segments.next(), segments.next(), segments.next()) {
+        (Some(""), Some("tiles" | "view"), Some(slug)) if !slug.is_empty() => Some(slug),
+        _ => None,
+    }
+}
+
+/// `path` with its slug segment replaced by `to`.
+fn redirected_path(path: &str, to: &str) -> Option<String> {
+    let slug = path_slug(path)?;
+    let (section, rest) = path[1..].split_once('/')?;
+    Some(format!("/{section}/{to}{}", &rest[slug.len()..]))
+}
+
+/// Redirect public requests that found nothing under a recently changed slug.
+pub async fn redirect_renamed_slugs(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let uri = request.uri().clone();
+    let response = next.run(request).await;
+    if response.status() != StatusCode::NOT_FOUND {
+        return response;
+    }
+    let Some(slug) = path_slug(uri.path()) else {
+        return response;
+    };
+    let renamed = match state.read_pool.get().await {
+        Ok(conn) => renamed_slug(&conn, slug),
+        Err(e) => Err(e),
+    };
+    let path = match renamed {
+        Ok(Some(new_slug)) => redirected_path(uri.path(), &new_slug),
+        Ok(None) => None,
+        Err(e) => {
+            tracing::warn!(%slug, error = %e, "Failed to look up slug history");
+            None
+        }
+    };
+    let Some(path) = path else {
+        return response;
+    };
+    let location = match uri.query() {
+        Some(query) => format!("{path}?{query}"),
+        None => path,
+    };
+    (
+        StatusCode::MOVED_PERMANENTLY,
+        [(header::LOCATION, location)],
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_slug_segment_is_replaced() {
+        assert_eq!(
+            redirected_path("/tiles/old/3/4/2.png", "new").as_deref(),
+            Some("/tiles/new/3/4/2.png")
+        );
+        assert_eq!(
+            redirected_path("/view/old", "new").as_deref(),
+            Some("/view/new")
+        );
+        assert_eq!(
+            redirected_path("/tiles/old/ogc/tiles/WebMercatorQuad", "new").as_deref(),
+            Some("/tiles/new/ogc/tiles/WebMercatorQuad")
+        );
+        assert_eq!(
+            redirected_path("/tiles/t/0/0/0", "new").as_deref(),
+            Some("/tiles/new/0/0/0")
+        );
+        assert_eq!(redirected_path("/wmts/old", "new"), None);
+        assert_eq!(path_slug("/tiles/"), None);
+    }
+}
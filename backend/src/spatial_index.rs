@@ -0,0 +1,74 @@
+//! R-tree indexes on dataset geometry
+//!
+//! Every imported table gets an R-tree on `geom` so tile queries can skip
+//! features outside the requested envelope instead of scanning the table.
+//! Tables imported before indexes existed are indexed once at startup.
+
+use crate::columns::quote_identifier;
+
+pub fn spatial_index_name(table_name: &str) -> String {
+    format!("{table_name}_geom_rtree")
+}
+
+pub fn create_spatial_index(
+    conn: &duckdb::Connection,
+    table_name: &str,
+) -> Result<(), duckdb::Error> {
+    conn.execute(
+        &format!(
+            "CREATE INDEX IF NOT EXISTS {} ON {} USING RTREE (geom)",
+            quote_identifier(&spatial_index_name(table_name)),
+            quote_identifier(table_name)
+        ),
+        [],
+    )?;
+    Ok(())
+}
+
+pub fn has_spatial_index(
+    conn: &duckdb::Connection,
+    table_name: &str,
+) -> Result<bool, duckdb::Error> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM duckdb_indexes() WHERE table_name = ? AND index_name = ?",
+        duckdb::params![table_name, spatial_index_name(table_name)],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// Index every ready dataset table that doesn't have an R-tree yet; returns
+/// how many were created. Failures are logged and leave that table unindexed.
+pub fn ensure_spatial_indexes(conn: &duckdb::Connection) -> Result<usize, duckdb::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT table_name FROM files
+         WHERE status = 'ready' AND tile_format IS NULL AND table_name IS NOT NULL",
+    )?;
+    let tables: Vec<String> = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+
+    let mut created = 0;
+    for table_name in tables {
+        if has_spatial_index(conn, &table_name)? {
+            continue;
+        }
+        match create_spatial_index(conn, &table_name) {
+            Ok(()) => created += 1,
+            Err(e) => {
+                tracing::warn!(table = %table_name, error = %e, "Failed to create spatial index")
+            }
+        }
+    }
+    Ok(created)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index_name_is_derived_from_table() {
+        assert_eq!(spatial_index_name("layer_abc"), "layer_abc_geom_rtree");
+    }
+}
@@ -0,0 +1,717 @@
+//! Read-only SQL queries against a single dataset
+//!
+//! Users write one SELECT statement against a table called `dataset` (original
+//! column names, `fid`, `geom`) and the row count is capped by an outer LIMIT.
+//!
+//! The statement never runs on the catalog's connections. The dataset is
+//! copied into a fresh in-memory database, where it is the only table, and that
+//! database is locked down before the query runs: no file, network or extension
+//! access, and no setting can be changed back. Copying is a full read of the
+//! dataset, so datasets over `MAX_QUERY_DATASET_ROWS` rows cannot be queried.
+//!
+//! The statement is also checked token by token first, so mistakes get a clear
+//! error: writes, settings and statements other than SELECT are rejected,
+//! every table reference must be `dataset` or a CTE in scope, and functions
+//! that reach files, the environment or the catalog are refused.
+
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::columns::{output_column_aliases, quote_identifier, quote_literal, DatasetColumn};
+use crate::db::ensure_spatial_extension;
+
+pub const DATASET_TABLE_NAME: &str = "dataset";
+pub const DEFAULT_QUERY_LIMIT: u32 = 100;
+pub const MAX_QUERY_LIMIT: u32 = 1000;
+pub const MAX_QUERY_LENGTH: usize = 10_000;
+/// Largest dataset, in rows, that is copied into a query's sandbox.
+pub const MAX_QUERY_DATASET_ROWS: i64 = 1_000_000;
+
+/// Keywords that only appear in statements which change state or read outside
+/// the dataset. Quote a column with one of these names to use it.
+const DENIED_KEYWORDS: &[&str] = &[
+    "ALTER",
+    "ANALYZE",
+    "ATTACH",
+    "BEGIN",
+    "CALL",
+    "CHECKPOINT",
+    "COMMIT",
+    "COPY",
+    "CREATE",
+    "DELETE",
+    "DESCRIBE",
+    "DETACH",
+    "DROP",
+    "EXPLAIN",
+    "EXPORT",
+    "GRANT",
+    "IMPORT",
+    "INSERT",
+    "INSTALL",
+    "LOAD",
+    "MERGE",
+    "PIVOT",
+    "PRAGMA",
+    "RESET",
+    "REVOKE",
+    "ROLLBACK",
+    "SECRET",
+    "SET",
+    "SHOW",
+    "SUMMARIZE",
+    "TABLE",
+    "TRANSACTION",
+    "TRUNCATE",
+    "UNPIVOT",
+    "UPDATE",
+    "USE",
+    "VACUUM",
+];
+
+/// Function name prefixes that read files, settings or the catalog.
+const DENIED_FUNCTION_PREFIXES: &[&str] = &[
+    "current_setting",
+    "delta_",
+    "duckdb_",
+    "getenv",
+    "glob",
+    "iceberg_",
+    "install_",
+    "load_",
+    "mysql_",
+    "parquet_",
+    "postgres_",
+    "pragma_",
+    "query",
+    "read_",
+    "sniff_",
+    "sqlite_",
+    "st_read",
+    "which_secret",
+];
+
+/// Table functions that only generate values.
+const ALLOWED_TABLE_FUNCTIONS: &[&str] = &["generate_series", "range", "unnest"];
+
+/// Functions whose argument syntax uses FROM without naming a table.
+const FROM_ARGUMENT_FUNCTIONS: &[&str] = &["extract", "overlay", "substring", "trim"];
+
+/// Keywords that end the FROM clause of the current query level.
+const FROM_CLAUSE_END: &[&str] = &[
+    "EXCEPT",
+    "GROUP",
+    "HAVING",
+    "INTERSECT",
+    "LIMIT",
+    "OFFSET",
+    "ORDER",
+    "QUALIFY",
+    "SELECT",
+    "UNION",
+    "WHERE",
+    "WINDOW",
+];
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DatasetQueryRequest {
+    pub sql: String,
+    pub limit: Option<u32>,
+}
+
+impl DatasetQueryRequest {
+    pub fn limit(&self) -> Result<u32, String> {
+        match self.limit {
+            None => Ok(DEFAULT_QUERY_LIMIT),
+            Some(limit) if (1..=MAX_QUERY_LIMIT).contains(&limit) => Ok(limit),
+            Some(_) => Err(format!("limit must be between 1 and {MAX_QUERY_LIMIT}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// Bare identifier or keyword.
+    Word(String),
+    /// Double-quoted identifier (unescaped).
+    Quoted(String),
+    Str,
+    Number,
+    Symbol(char),
+}
+
+/// Split a statement into tokens with their byte offsets; comments are dropped.
+fn tokenize(sql: &str) -> Result<Vec<(usize, Token)>, String> {
+    let chars: Vec<(usize, char)> = sql.char_indices().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let (start, c) = chars[i];
+        let next = chars.get(i + 1).map(|(_, c)| *c);
+
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '-' && next == Some('-') {
+            while i < chars.len() && chars[i].1 != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && next == Some('*') {
+            i += 2;
+            loop {
+                match (chars.get(i), chars.get(i + 1)) {
+                    (Some((_, '*')), Some((_, '/'))) => break,
+                    (Some(_), _) => i += 1,
+                    (None, _) => return Err("Unterminated comment".to_string()),
+                }
+            }
+            i += 2;
+        } else if c == '\'' || c == '"' {
+            let mut value = String::new();
+            i += 1;
+            loop {
+                match chars.get(i).map(|(_, ch)| *ch) {
+                    Some(ch) if ch == c => {
+                        if chars.get(i + 1).map(|(_, n)| *n) == Some(c) {
+                            value.push(c);
+                            i += 2;
+                        } else {
+                            i += 1;
+                            break;
+                        }
+                    }
+                    Some(ch) => {
+                        value.push(ch);
+                        i += 1;
+                    }
+                    None => {
+                        return Err(if c == '\'' {
+                            "Unterminated string literal".to_string()
+                        } else {
+                            "Unterminated quoted identifier".to_string()
+                        })
+                    }
+                }
+            }
+            tokens.push((
+                start,
+                if c == '\'' {
+                    Token::Str
+                } else {
+                    Token::Quoted(value)
+                },
+            ));
+        } else if c.is_ascii_digit() || (c == '.' && next.is_some_and(|n| n.is_ascii_digit())) {
+            while i < chars.len() && (chars[i].1.is_ascii_alphanumeric() || chars[i].1 == '.') {
+                i += 1;
+            }
+            tokens.push((start, Token::Number));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut word = String::new();
+            while i < chars.len() && (chars[i].1.is_alphanumeric() || chars[i].1 == '_') {
+                word.push(chars[i].1);
+                i += 1;
+            }
+            tokens.push((start, Token::Word(word)));
+        } else if c == '$' || c == '?' {
+            return Err("Query parameters are not supported".to_string());
+        } else {
+            tokens.push((start, Token::Symbol(c)));
+            i += 1;
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn is_word(token: Option<&Token>, keyword: &str) -> bool {
+    matches!(token, Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword))
+}
+
+fn identifier(token: Option<&Token>) -> Option<String> {
+    match token {
+        Some(Token::Word(w)) | Some(Token::Quoted(w)) => Some(w.to_lowercase()),
+        _ => None,
+    }
+}
+
+/// Index just past the parenthesis group opening at `open`.
+fn skip_parens(tokens: &[Token], open: usize) -> usize {
+    let mut depth = 0usize;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        match token {
+            Token::Symbol('(') => depth += 1,
+            Token::Symbol(')') => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    return i + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    tokens.len()
+}
+
+/// Names defined by the WITH clause starting at `with`, each with the token
+/// index from which it can be referenced: after its own body, or from its name
+/// on in a RECURSIVE clause. Until then the name still means a catalog table.
+fn cte_names(tokens: &[Token], with: usize) -> Vec<(String, usize)> {
+    let mut names = Vec::new();
+    let mut i = with + 1;
+    let recursive = is_word(tokens.get(i), "RECURSIVE");
+    if recursive {
+        i += 1;
+    }
+
+    while let Some(name) = identifier(tokens.get(i)) {
+        let name_at = i;
+        i += 1;
+        if tokens.get(i) == Some(&Token::Symbol('(')) {
+            i = skip_parens(tokens, i);
+        }
+        if !is_word(tokens.get(i), "AS") {
+            break;
+        }
+        i += 1;
+        if is_word(tokens.get(i), "NOT") {
+            i += 1;
+        }
+        if is_word(tokens.get(i), "MATERIALIZED") {
+            i += 1;
+        }
+        if tokens.get(i) != Some(&Token::Symbol('(')) {
+            break;
+        }
+        i = skip_parens(tokens, i);
+        names.push((name, if recursive { name_at } else { i }));
+        if tokens.get(i) != Some(&Token::Symbol(',')) {
+            break;
+        }
+        i += 1;
+    }
+
+    names
+}
+
+/// One parenthesis level of the statement.
+#[derive(Default)]
+struct Scope {
+    /// FROM/JOIN introduce tables here (false inside e.g. `EXTRACT(... FROM ...)`).
+    query: bool,
+    in_from: bool,
+    expect_table: bool,
+    /// CTE names and the token index from which each is visible.
+    ctes: Vec<(String, usize)>,
+}
+
+/// Check that `sql` is a single read-only SELECT over `dataset` and return the
+/// statement without trailing semicolons.
+pub fn validate_query(sql: &str) -> Result<&str, String> {
+    if sql.len() > MAX_QUERY_LENGTH {
+        return Err(format!(
+            "Query is too long (max {MAX_QUERY_LENGTH} characters)"
+        ));
+    }
+
+    let mut spanned = tokenize(sql)?;
+    let mut end = sql.len();
+    while let Some((offset, Token::Symbol(';'))) = spanned.last() {
+        end = *offset;
+        spanned.pop();
+    }
+    let tokens: Vec<Token> = spanned.into_iter().map(|(_, token)| token).collect();
+
+    if tokens.is_empty() {
+        return Err("Query is empty".to_string());
+    }
+    if tokens.contains(&Token::Symbol(';')) {
+        return Err("Only a single statement is allowed".to_string());
+    }
+    if !is_word(tokens.first(), "SELECT") && !is_word(tokens.first(), "WITH") {
+        return Err("Only SELECT queries are allowed".to_string());
+    }
+
+    for (i, token) in tokens.iter().enumerate() {
+        if let Token::Word(word) = token {
+            if let Some(keyword) = DENIED_KEYWORDS
+                .iter()
+                .find(|k| word.eq_ignore_ascii_case(k))
+            {
+                return Err(format!("'{keyword}' is not allowed in queries"));
+            }
+        }
+        let Some(name) = identifier(Some(token)) else {
+            continue;
+        };
+        if tokens.get(i + 1) == Some(&Token::Symbol('('))
+            && DENIED_FUNCTION_PREFIXES
+                .iter()
+                .any(|prefix| name.starts_with(prefix))
+        {
+            return Err(format!("Function '{name}' is not allowed in queries"));
+        }
+    }
+
+    let mut scopes = vec![Scope {
+        query: true,
+        ..Default::default()
+    }];
+    for (i, token) in tokens.iter().enumerate() {
+        let next = tokens.get(i + 1);
+
+        let expected_table = scopes.last().is_some_and(|s| s.expect_table);
+        if expected_table {
+            if is_word(Some(token), "LATERAL") {
+                continue;
+            }
+            scopes.last_mut().expect("scope").expect_table = false;
+            match token {
+                Token::Word(_) | Token::Quoted(_) => {
+                    let name = identifier(Some(token)).expect("identifier");
+                    if next == Some(&Token::Symbol('(')) {
+                        if !ALLOWED_TABLE_FUNCTIONS.contains(&name.as_str()) {
+                            return Err(format!(
+                                "Table function '{name}' is not allowed in queries"
+                            ));
+                        }
+                    } else if next == Some(&Token::Symbol('.')) {
+                        return Err(format!(
+                            "Only the '{DATASET_TABLE_NAME}' table can be queried"
+                        ));
+                    } else if name != DATASET_TABLE_NAME
+                        && !scopes
+                            .iter()
+                            .any(|s| s.ctes.iter().any(|(cte, from)| *cte == name && i >= *from))
+                    {
+                        return Err(format!(
+                            "Unknown table '{name}'. Query the dataset as '{DATASET_TABLE_NAME}'"
+                        ));
+                    }
+                    continue;
+                }
+                Token::Str => {
+                    return Err("Reading files is not allowed in queries".to_string());
+                }
+                _ => {}
+            }
+        }
+
+        match token {
+            Token::Symbol('(') => {
+                let from_argument = i > 0
+                    && matches!(&tokens[i - 1], Token::Word(w)
+                        if FROM_ARGUMENT_FUNCTIONS.contains(&w.to_lowercase().as_str()));
+                // `FROM (a JOIN b)` groups table references rather than
+                // starting a subquery, so its first word is a table too.
+                let table_group = expected_table
+                    && !["SELECT", "WITH", "FROM", "VALUES"]
+                        .iter()
+                        .any(|keyword| is_word(next, keyword));
+                scopes.push(Scope {
+                    query: !from_argument,
+                    in_from: table_group,
+                    expect_table: table_group,
+                    ..Default::default()
+                });
+            }
+            Token::Symbol(')') => {
+                if scopes.len() > 1 {
+                    scopes.pop();
+                }
+            }
+            Token::Symbol(',') => {
+                let scope = scopes.last_mut().expect("scope");
+                if scope.in_from {
+                    scope.expect_table = true;
+                }
+            }
+            Token::Word(word) => {
+                let scope = scopes.last_mut().expect("scope");
+                if word.eq_ignore_ascii_case("WITH") {
+                    scope.ctes.extend(cte_names(&tokens, i));
+                } else if scope.query
+                    && (word.eq_ignore_ascii_case("JOIN")
+                        || (word.eq_ignore_ascii_case("FROM")
+                            && !is_word(tokens.get(i.wrapping_sub(1)), "DISTINCT")))
+                {
+                    scope.in_from = true;
+                    scope.expect_table = true;
+                } else if FROM_CLAUSE_END.iter().any(|k| word.eq_ignore_ascii_case(k)) {
+                    scope.in_from = false;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(sql[..end].trim_end())
+}
+
+/// The dataset's rows as the query sees them: `fid`, the property columns
+/// under their original names, and `geom`.
+fn dataset_select_sql(table_name: &str, columns: &[DatasetColumn]) -> String {
+    let pairs: Vec<(String, String)> = columns
+        .iter()
+        .map(|c| (c.normalized.clone(), c.original.clone()))
+        .collect();
+    let mut select_exprs = vec!["fid".to_string()];
+    select_exprs.extend(
+        output_column_aliases(&pairs)
+            .iter()
+            .map(|(normalized, alias)| {
+                format!(
+                    "{} AS {}",
+                    quote_identifier(normalized),
+                    quote_identifier(alias)
+                )
+            }),
+    );
+    select_exprs.push("geom".to_string());
+
+    format!(
+        "SELECT {} FROM {}",
+        select_exprs.join(", "),
+        quote_identifier(table_name)
+    )
+}
+
+/// Wrap a validated statement so at most `limit + 1` rows come back (the extra
+/// row signals truncation).
+pub fn build_query_sql(statement: &str, limit: u32) -> String {
+    format!(
+        "SELECT * FROM (\n{statement}\n) AS query_result LIMIT {}",
+        u64::from(limit) + 1
+    )
+}
+
+/// Refuse datasets of more than `MAX_QUERY_DATASET_ROWS` rows.
+pub fn check_query_dataset_rows(rows: i64) -> Result<(), String> {
+    if rows > MAX_QUERY_DATASET_ROWS {
+        return Err(format!(
+            "SQL queries are limited to datasets of at most {MAX_QUERY_DATASET_ROWS} rows; this one has {rows}"
+        ));
+    }
+    Ok(())
+}
+
+/// Open the database a query runs in: an in-memory one holding only a copy of
+/// the dataset as `dataset`, with external access and configuration locked.
+/// The copy goes through a Parquet file in `scratch_dir`, which is removed
+/// before this returns.
+pub fn open_query_sandbox(
+    conn: &duckdb::Connection,
+    table_name: &str,
+    columns: &[DatasetColumn],
+    scratch_dir: &Path,
+) -> Result<duckdb::Connection, String> {
+    std::fs::create_dir_all(scratch_dir).map_err(|e| e.to_string())?;
+    let snapshot = scratch_dir.join(format!("query-{}.parquet", uuid::Uuid::new_v4()));
+    let snapshot_literal = quote_literal(&snapshot.to_string_lossy());
+
+    let copied = conn
+        .execute_batch(&format!(
+            "COPY ({}) TO {snapshot_literal} (FORMAT parquet)",
+            dataset_select_sql(table_name, columns)
+        ))
+        .map_err(|e| e.to_string())
+        .and_then(|_| {
+            let sandbox = duckdb::Connection::open_in_memory().map_err(|e| e.to_string())?;
+            ensure_spatial_extension(&sandbox)?;
+            sandbox
+                .execute_batch(&format!(
+                    "CREATE TABLE {} AS SELECT * FROM read_parquet({snapshot_literal})",
+                    quote_identifier(DATASET_TABLE_NAME)
+                ))
+                .map_err(|e| e.to_string())?;
+            Ok(sandbox)
+        });
+    let _ = std::fs::remove_file(&snapshot);
+    let sandbox = copied?;
+
+    sandbox
+        .execute_batch(
+            "SET autoinstall_known_extensions = false;
+             SET autoload_known_extensions = false;
+             SET enable_external_access = false;
+             SET lock_configuration = true;",
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(sandbox)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_rejected(sql: &str) {
+        assert!(validate_query(sql).is_err(), "{sql}");
+    }
+
+    #[test]
+    fn accepts_read_only_selects() {
+        for sql in [
+            "SELECT * FROM dataset",
+            "select count(*) from dataset where \"Road Name\" like 'M%'",
+            "SELECT a.fid FROM dataset a JOIN dataset b ON a.fid = b.fid, dataset c",
+            "WITH big AS (SELECT * FROM dataset WHERE lanes > 2) SELECT * FROM big",
+            "WITH RECURSIVE t(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM t WHERE n < 3) SELECT * FROM t",
+            "SELECT extract(year FROM now()), trim(BOTH 'x' FROM 'xax')",
+            "SELECT * FROM dataset WHERE fid IN (SELECT fid FROM dataset LIMIT 2)",
+            "SELECT * FROM range(3), dataset",
+            "SELECT ST_AsText(geom) AS wkt FROM dataset -- trailing comment",
+            "SELECT 'DROP TABLE files' AS text, \"update\" FROM dataset",
+            "SELECT * FROM dataset WHERE lanes IS DISTINCT FROM 2",
+        ] {
+            assert!(validate_query(sql).is_ok(), "{sql}: {:?}", validate_query(sql));
+        }
+    }
+
+    #[test]
+    fn strips_trailing_semicolons() {
+        assert_eq!(
+            validate_query("SELECT 1 FROM dataset ;; ").unwrap(),
+            "SELECT 1 FROM dataset"
+        );
+    }
+
+    #[test]
+    fn rejects_statements_other_than_select() {
+        for sql in [
+            "",
+            "  ;",
+            "DELETE FROM dataset",
+            "DROP TABLE files",
+            "SELECT 1; DROP TABLE files",
+            "SET enable_external_access = true",
+            "COPY dataset TO 'out.csv'",
+            "WITH x AS (SELECT 1) INSERT INTO files SELECT * FROM x",
+            "SELECT * FROM (SUMMARIZE users)",
+            "SELECT * FROM dataset WHERE fid = ?",
+        ] {
+            assert_rejected(sql);
+        }
+    }
+
+    #[test]
+    fn rejects_tables_outside_the_dataset() {
+        for sql in [
+            "SELECT * FROM users",
+            "SELECT * FROM dataset, sessions",
+            "SELECT * FROM dataset JOIN \"files\" ON true",
+            "SELECT * FROM main.users",
+            "SELECT * FROM information_schema.tables",
+            "SELECT (SELECT password_hash FROM users LIMIT 1) FROM dataset",
+            "SELECT * FROM dataset WHERE fid IN (FROM users SELECT 1)",
+            "SELECT * FROM '/etc/passwd'",
+            "SELECT * FROM read_csv('/etc/passwd')",
+            "SELECT * FROM ST_Read('/tmp/x.shp')",
+            "SELECT * FROM duckdb_tables()",
+            "SELECT getenv('HOME')",
+            "SELECT \"getenv\"('HOME')",
+            "SELECT * FROM dataset a JOIN dataset b USING (fid), users",
+            "SELECT current_setting('threads')",
+            "SELECT extract(year FROM (SELECT created_at FROM users))",
+        ] {
+            assert_rejected(sql);
+        }
+    }
+
+    #[test]
+    fn cte_names_only_apply_in_scope() {
+        assert!(validate_query(
+            "SELECT * FROM (WITH users AS (SELECT * FROM dataset) SELECT * FROM users)"
+        )
+        .is_ok());
+        assert_rejected(
+            "SELECT * FROM (WITH users AS (SELECT 1) SELECT * FROM users), (SELECT * FROM users)",
+        );
+    }
+
+    #[test]
+    fn rejects_unterminated_tokens() {
+        assert_rejected("SELECT 'abc FROM dataset");
+        assert_rejected("SELECT \"abc FROM dataset");
+        assert_rejected("SELECT 1 /* comment");
+    }
+
+    #[test]
+    fn rejects_tables_inside_parenthesised_from_items() {
+        for sql in [
+            "SELECT * FROM (users CROSS JOIN dataset)",
+            "SELECT * FROM (dataset CROSS JOIN users)",
+            "SELECT * FROM ((users))",
+            "SELECT * FROM dataset JOIN (sessions JOIN dataset d ON true) ON true",
+            "SELECT * FROM (dataset, api_tokens)",
+            "SELECT * FROM (SELECT * FROM users) AS u",
+            "SELECT * FROM (FROM users)",
+            "SELECT * FROM dataset, (SELECT * FROM (layer_abc CROSS JOIN dataset))",
+        ] {
+            assert_rejected(sql);
+        }
+        for sql in [
+            "SELECT * FROM (dataset a CROSS JOIN dataset b)",
+            "SELECT * FROM (SELECT fid FROM dataset) AS d",
+            "SELECT * FROM (VALUES (1), (2)) AS v(x)",
+        ] {
+            assert!(
+                validate_query(sql).is_ok(),
+                "{sql}: {:?}",
+                validate_query(sql)
+            );
+        }
+    }
+
+    #[test]
+    fn cte_bodies_cannot_see_their_own_name() {
+        for sql in [
+            "WITH users AS (SELECT * FROM users) SELECT * FROM users",
+            "WITH a AS (SELECT * FROM b), b AS (SELECT * FROM dataset) SELECT * FROM a",
+            "WITH x AS (SELECT * FROM sessions) SELECT * FROM x",
+        ] {
+            assert_rejected(sql);
+        }
+        for sql in [
+            "WITH a AS (SELECT * FROM dataset), b AS (SELECT * FROM a) SELECT * FROM b",
+            "WITH RECURSIVE t(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM t WHERE n < 3) SELECT * FROM t",
+        ] {
+            assert!(validate_query(sql).is_ok(), "{sql}: {:?}", validate_query(sql));
+        }
+    }
+
+    #[test]
+    fn dataset_select_exposes_original_names() {
+        let columns = vec![DatasetColumn {
+            normalized: "road_name".to_string(),
+            original: "Road Name".to_string(),
+            mvt_type: "VARCHAR".to_string(),
+        }];
+        assert_eq!(
+            dataset_select_sql("layer_abc", &columns),
+            "SELECT fid, \"road_name\" AS \"Road Name\", geom FROM \"layer_abc\""
+        );
+        assert_eq!(
+            build_query_sql("SELECT * FROM dataset", 10),
+            "SELECT * FROM (\nSELECT * FROM dataset\n) AS query_result LIMIT 11"
+        );
+    }
+
+    #[test]
+    fn limit_defaults_and_bounds() {
+        let request = |limit| DatasetQueryRequest {
+            sql: String::new(),
+            limit,
+        };
+        assert_eq!(request(None).limit().unwrap(), DEFAULT_QUERY_LIMIT);
+        assert_eq!(request(Some(5)).limit().unwrap(), 5);
+        assert!(request(Some(0)).limit().is_err());
+        assert!(request(Some(MAX_QUERY_LIMIT + 1)).limit().is_err());
+    }
+
+    #[test]
+    fn large_datasets_are_not_copied() {
+        assert!(check_query_dataset_rows(MAX_QUERY_DATASET_ROWS).is_ok());
+        assert!(check_query_dataset_rows(MAX_QUERY_DATASET_ROWS + 1).is_err());
+    }
+}
@@ -0,0 +1,164 @@
+//! Storage usage
+//!
+//! `GET /api/admin/storage` reports where the disk goes: the DuckDB file and
+//! its WAL, each dataset's table, upload directory and tile cache, and the
+//! backups. Table sizes come from the blocks their column segments occupy, so
+//! they are estimates (small tables can share a block); directory sizes are
+//! summed on a blocking thread.
+
+use std::path::Path;
+
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+
+use crate::backup::{backup_root, database_path, wal_path};
+use crate::columns::quote_literal;
+use crate::http_errors::internal_error;
+use crate::models::{DatasetStorage, StorageStats};
+use crate::tile_cache::tile_cache_root;
+use crate::{AppState, ErrorResponse};
+
+pub fn build_storage_router() -> Router<AppState> {
+    Router::new().route("/api/admin/storage", get(get_storage))
+}
+
+/// Bytes used by the files under `path`; 0 when it doesn't exist. Symlinks
+/// are not followed.
+pub fn dir_size(path: &Path) -> u64 {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    std::fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| dir_size(&entry.path()))
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+/// Estimated bytes of `table_name`: the distinct persistent blocks its
+/// segments live in, times the block size.
+fn table_bytes(
+    conn: &duckdb::Connection,
+    table_name: &str,
+    block_size: u64,
+) -> Result<u64, duckdb::Error> {
+    let blocks: i64 = conn.query_row(
+        &format!(
+            "SELECT COUNT(DISTINCT block_id) FROM pragma_storage_info({}) WHERE persistent",
+            quote_literal(table_name)
+        ),
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(blocks as u64 * block_size)
+}
+
+fn load_dataset_tables(conn: &duckdb::Connection) -> Result<Vec<DatasetStorage>, duckdb::Error> {
+    let block_size: i64 =
+        conn.query_row("SELECT block_size FROM pragma_database_size()", [], |row| {
+            row.get(0)
+        })?;
+    let mut stmt = conn.prepare(
+        r"
+        SELECT f.id, f.name, f.table_name, t.estimated_size
+        FROM files f
+        LEFT JOIN duckdb_tables() t
+          ON t.table_name = f.table_name
+         AND t.database_name = current_database()
+         AND t.schema_name = 'main'
+        ORDER BY f.uploaded_at
+        ",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, Option<String>>(2)?,
+            row.get::<_, Option<i64>>(3)?,
+        ))
+    })?;
+
+    let mut datasets = Vec::new();
+    for row in rows {
+        let (id, name, table_name, row_count) = row?;
+        // A table_name without a table (import failed half-way) counts as empty.
+        let table_bytes = match (&table_name, row_count) {
+            (Some(table), Some(_)) => table_bytes(conn, table, block_size as u64)?,
+            _ => 0,
+        };
+        datasets.push(DatasetStorage {
+            id,
+            name,
+            table_name,
+            row_count: row_count.unwrap_or(0) as u64,
+            table_bytes,
+            upload_bytes: 0,
+            tile_cache_bytes: 0,
+        });
+    }
+    Ok(datasets)
+}
+
+async fn get_storage(
+    State(state): State<AppState>,
+) -> Result<Json<StorageStats>, (StatusCode, Json<ErrorResponse>)> {
+    let (db_path, datasets) = {
+        let conn = state.read_pool.get().await.map_err(internal_error)?;
+        (
+            database_path(&conn).ok(),
+            load_dataset_tables(&conn).map_err(internal_error)?,
+        )
+    };
+
+    let upload_dir = state.upload_dir.clone();
+    let stats = tokio::task::spawn_blocking(move || {
+        let tile_cache = tile_cache_root(&upload_dir);
+        let backups = backup_root(&upload_dir);
+        let mut datasets = datasets;
+        for dataset in &mut datasets {
+            dataset.upload_bytes = dir_size(&upload_dir.join(&dataset.id));
+            dataset.tile_cache_bytes = dir_size(&tile_cache.join(&dataset.id));
+        }
+        datasets.sort_by_key(|dataset| {
+            std::cmp::Reverse(dataset.table_bytes + dataset.upload_bytes + dataset.tile_cache_bytes)
+        });
+
+        let tile_cache_bytes = dir_size(&tile_cache);
+        let backups_bytes = dir_size(&backups);
+        StorageStats {
+            database_bytes: db_path.as_deref().map_or(0, dir_size),
+            wal_bytes: db_path
+                .as_deref()
+                .map_or(0, |path| dir_size(&wal_path(path))),
+            uploads_bytes: dir_size(&upload_dir).saturating_sub(tile_cache_bytes + backups_bytes),
+            tile_cache_bytes,
+            backups_bytes,
+            datasets,
+        }
+    })
+    .await
+    .map_err(internal_error)?;
+    Ok(Json(stats))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dir_size_sums_nested_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        std::fs::write(dir.path().join("a/one"), [0u8; 10]).unwrap();
+        std::fs::write(dir.path().join("a/b/two"), [0u8; 5]).unwrap();
+
+        assert_eq!(dir_size(dir.path()), 15);
+        assert_eq!(dir_size(&dir.path().join("a/one")), 10);
+        assert_eq!(dir_size(&dir.path().join("missing")), 0);
+    }
+}
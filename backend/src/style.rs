@@ -0,0 +1,162 @@
+//! MapLibre styles for public slugs
+//!
+//! A generated style has one source for the slug and, per vector layer, a
+//! fill, line and circle style layer filtered by geometry type, so any dataset
+//! renders without the caller knowing what it contains. Raster MBTiles get a
+//! single raster layer.
+
+use serde_json::{json, Value};
+
+use crate::models::TileJson;
+
+pub(crate) const PALETTE: &[&str] = &[
+    "#3b82f6", "#ef4444", "#10b981", "#f59e0b", "#8b5cf6", "#ec4899", "#14b8a6", "#f97316",
+];
+
+/// Style layers drawing one vector layer in `color`.
+fn vector_style_layers(source: &str, source_layer: &str, color: &str) -> Vec<Value> {
+    let geometry_filter = |types: &[&str]| json!(["match", ["geometry-type"], types, true, false]);
+    vec![
+        json!({
+            "id": format!("{source_layer}-fill"),
+            "type": "fill",
+            "source": source,
+            "source-layer": source_layer,
+            "filter": geometry_filter(&["Polygon", "MultiPolygon"]),
+            "paint": {
+                "fill-color": color,
+                "fill-opacity": 0.35,
+                "fill-outline-color": color
+            }
+        }),
+        json!({
+            "id": format!("{source_layer}-line"),
+            "type": "line",
+            "source": source,
+            "source-layer": source_layer,
+            "filter": geometry_filter(&["LineString", "MultiLineString"]),
+            "layout": { "line-cap": "round", "line-join": "round" },
+            "paint": { "line-color": color, "line-width": 1.5 }
+        }),
+        json!({
+            "id": format!("{source_layer}-circle"),
+            "type": "circle",
+            "source": source,
+            "source-layer": source_layer,
+            "filter": geometry_filter(&["Point", "MultiPoint"]),
+            "paint": {
+                "circle-color": color,
+                "circle-radius": 4,
+                "circle-stroke-color": "#ffffff",
+                "circle-stroke-width": 1
+            }
+        }),
+    ]
+}
+
+/// A MapLibre style (spec version 8) drawing everything in `tilejson`.
+pub fn build_style(slug: &str, tilejson: &TileJson) -> Value {
+    let mut source = json!({
+        "type": if tilejson.vector_layers.is_some() { "vector" } else { "raster" },
+        "tiles": tilejson.tiles,
+        "minzoom": tilejson.minzoom,
+        "maxzoom": tilejson.maxzoom,
+    });
+    if let Some(bounds) = tilejson.bounds {
+        source["bounds"] = json!(bounds);
+    }
+    if let Some(attribution) = &tilejson.attribution {
+        source["attribution"] = json!(attribution);
+    }
+
+    let layers: Vec<Value> = match &tilejson.vector_layers {
+        Some(vector_layers) => vector_layers
+            .iter()
+            .zip(PALETTE.iter().cycle())
+            .flat_map(|(layer, color)| vector_style_layers(slug, &layer.id, color))
+            .collect(),
+        None => vec![json!({ "id": slug, "type": "raster", "source": slug })],
+    };
+
+    let mut sources = serde_json::Map::new();
+    sources.insert(slug.to_string(), source);
+    let mut style = json!({
+        "version": 8,
+        "name": tilejson.name,
+        "sources": sources,
+        "layers": layers,
+    });
+    if let Some([minx, miny, maxx, maxy]) = tilejson.bounds {
+        style["center"] = json!([(minx + maxx) / 2.0, (miny + maxy) / 2.0]);
+        style["zoom"] = json!(tilejson.minzoom);
+    }
+    style
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::VectorLayer;
+
+    fn tilejson(vector_layers: Option<Vec<&str>>) -> TileJson {
+        TileJson {
+            tilejson: "3.0.0".to_string(),
+            name: "City".to_string(),
+            tiles: vec!["http://localhost/tiles/city/{z}/{x}/{y}?v=0".to_string()],
+            minzoom: 2,
+            maxzoom: 14,
+            bounds: Some([0.0, 0.0, 4.0, 2.0]),
+            description: None,
+            attribution: None,
+            license: None,
+            vector_layers: vector_layers.map(|ids| {
+                ids.into_iter()
+                    .map(|id| VectorLayer {
+                        id: id.to_string(),
+                        description: None,
+                        fields: Default::default(),
+                    })
+                    .collect()
+            }),
+        }
+    }
+
+    #[test]
+    fn vector_style_has_a_layer_per_geometry_type() {
+        let style = build_style("city", &tilejson(Some(vec!["roads", "parks"])));
+        assert_eq!(style["version"], 8);
+        assert_eq!(style["sources"]["city"]["type"], "vector");
+        assert_eq!(style["sources"]["city"]["maxzoom"], 14);
+        assert_eq!(style["center"], json!([2.0, 1.0]));
+
+        let layers = style["layers"].as_array().unwrap();
+        let ids: Vec<&str> = layers.iter().map(|l| l["id"].as_str().unwrap()).collect();
+        assert_eq!(
+            ids,
+            vec![
+                "roads-fill",
+                "roads-line",
+                "roads-circle",
+                "parks-fill",
+                "parks-line",
+                "parks-circle"
+            ]
+        );
+        assert!(layers.iter().all(|l| l["source"] == "city"));
+        assert_eq!(layers[3]["source-layer"], "parks");
+        assert_ne!(
+            layers[0]["paint"]["fill-color"],
+            layers[3]["paint"]["fill-color"]
+        );
+    }
+
+    #[test]
+    fn raster_style_has_one_raster_layer() {
+        let style = build_style("imagery", &tilejson(None));
+        assert_eq!(style["sources"]["imagery"]["type"], "raster");
+        assert_eq!(
+            style["layers"],
+            json!([{ "id": "imagery", "type": "raster", "source": "imagery" }])
+        );
+    }
+}
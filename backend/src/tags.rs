@@ -0,0 +1,223 @@
+//! Tags and folders
+//!
+//! Files can carry any number of tags and sit in one folder, a slash-separated
+//! path such as `projects/roads`, so instances with many datasets can be
+//! organized by project or team. `GET /api/files?tag=&folder=` filters on
+//! them; a folder filter also matches its subfolders. Tags compare
+//! case-insensitively and keep the case they were first given in.
+
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path as AxumPath, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+
+use crate::http_errors::{bad_request, internal_error};
+use crate::models::{AppState, FileFolder, FileTags};
+use crate::ErrorResponse;
+
+const MAX_TAGS: usize = 20;
+const MAX_TAG_LENGTH: usize = 64;
+const MAX_FOLDER_LENGTH: usize = 255;
+const MAX_FOLDER_DEPTH: usize = 8;
+
+/// Trim and deduplicate `tags`, keeping their first spelling and order.
+pub fn validate_tags(tags: &[String]) -> Result<Vec<String>, String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim();
+        if tag.is_empty() {
+            return Err("Tags cannot be empty".to_string());
+        }
+        if tag.chars().count() > MAX_TAG_LENGTH {
+            return Err(format!("Tags must be at most {MAX_TAG_LENGTH} characters"));
+        }
+        if tag.chars().any(char::is_control) {
+            return Err("Tags cannot contain control characters".to_string());
+        }
+        if !normalized
+            .iter()
+            .any(|existing| existing.to_lowercase() == tag.to_lowercase())
+        {
+            normalized.push(tag.to_string());
+        }
+    }
+    if normalized.len() > MAX_TAGS {
+        return Err(format!("A file can have at most {MAX_TAGS} tags"));
+    }
+    Ok(normalized)
+}
+
+/// Normalize `folder` to `a/b/c`, without leading or trailing slashes. An
+/// empty path is no folder.
+pub fn validate_folder(folder: &str) -> Result<Option<String>, String> {
+    let folder = folder.trim().trim_matches('/');
+    if folder.is_empty() {
+        return Ok(None);
+    }
+    let segments: Vec<&str> = folder.split('/').map(str::trim).collect();
+    if segments
+        .iter()
+        .any(|segment| segment.is_empty() || *segment == "." || *segment == "..")
+    {
+        return Err("Folder names cannot be empty, '.' or '..'".to_string());
+    }
+    if segments.len() > MAX_FOLDER_DEPTH {
+        return Err(format!(
+            "Folders can be nested at most {MAX_FOLDER_DEPTH} deep"
+        ));
+    }
+    let folder = segments.join("/");
+    if folder.chars().count() > MAX_FOLDER_LENGTH {
+        return Err(format!(
+            "Folder paths must be at most {MAX_FOLDER_LENGTH} characters"
+        ));
+    }
+    if folder.chars().any(char::is_control) {
+        return Err("Folder paths cannot contain control characters".to_string());
+    }
+    Ok(Some(folder))
+}
+
+/// Every file's tags, alphabetically.
+pub fn load_all_file_tags(
+    conn: &duckdb::Connection,
+) -> Result<HashMap<String, Vec<String>>, duckdb::Error> {
+    let mut stmt = conn.prepare("SELECT file_id, tag FROM file_tags ORDER BY lower(tag)")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    })?;
+    let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+    for row in rows {
+        let (file_id, tag) = row?;
+        tags.entry(file_id).or_default().push(tag);
+    }
+    Ok(tags)
+}
+
+fn load_file_tags(conn: &duckdb::Connection, id: &str) -> Result<Vec<String>, duckdb::Error> {
+    let mut stmt =
+        conn.prepare("SELECT tag FROM file_tags WHERE file_id = ? ORDER BY lower(tag)")?;
+    let tags = stmt.query_map(duckdb::params![id], |row| row.get(0))?;
+    tags.collect()
+}
+
+fn file_exists(conn: &duckdb::Connection, id: &str) -> Result<bool, duckdb::Error> {
+    conn.query_row(
+        "SELECT COUNT(*) > 0 FROM files WHERE id = ?",
+        duckdb::params![id],
+        |row| row.get(0),
+    )
+}
+
+fn file_not_found() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse {
+            error: "File not found".to_string(),
+        }),
+    )
+}
+
+fn replace_file_tags(
+    conn: &duckdb::Connection,
+    id: &str,
+    tags: &[String],
+) -> Result<(), duckdb::Error> {
+    conn.execute(
+        "DELETE FROM file_tags WHERE file_id = ?",
+        duckdb::params![id],
+    )?;
+    for tag in tags {
+        conn.execute(
+            "INSERT INTO file_tags (file_id, tag) VALUES (?, ?)",
+            duckdb::params![id, tag],
+        )?;
+    }
+    Ok(())
+}
+
+/// Replace a file's tags; an empty list removes them all.
+pub async fn set_file_tags(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+    Json(req): Json<FileTags>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let tags = validate_tags(&req.tags).map_err(|e| bad_request(&e))?;
+
+    let conn = state.db.lock().await;
+    if !file_exists(&conn, &id).map_err(internal_error)? {
+        return Err(file_not_found());
+    }
+    conn.execute_batch("BEGIN TRANSACTION")
+        .map_err(internal_error)?;
+    if let Err(e) = replace_file_tags(&conn, &id, &tags) {
+        let _ = conn.execute_batch("ROLLBACK");
+        return Err(internal_error(e));
+    }
+    conn.execute_batch("COMMIT").map_err(internal_error)?;
+
+    let tags = load_file_tags(&conn, &id).map_err(internal_error)?;
+    Ok(Json(FileTags { tags }))
+}
+
+/// Move a file into a folder, or out of any with `folder: null`.
+pub async fn set_file_folder(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+    Json(req): Json<FileFolder>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let folder = match req.folder.as_deref() {
+        Some(folder) => validate_folder(folder).map_err(|e| bad_request(&e))?,
+        None => None,
+    };
+
+    let conn = state.db.lock().await;
+    let updated = conn
+        .execute(
+            "UPDATE files SET folder = ? WHERE id = ?",
+            duckdb::params![&folder, &id],
+        )
+        .map_err(internal_error)?;
+    if updated == 0 {
+        return Err(file_not_found());
+    }
+    Ok(Json(FileFolder { folder }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_are_trimmed_and_deduplicated_case_insensitively() {
+        let tags = vec![
+            " Roads ".to_string(),
+            "roads".to_string(),
+            "Team A".to_string(),
+        ];
+        assert_eq!(
+            validate_tags(&tags),
+            Ok(vec!["Roads".to_string(), "Team A".to_string()])
+        );
+        assert!(validate_tags(&[" ".to_string()]).is_err());
+        assert!(validate_tags(&["a".repeat(MAX_TAG_LENGTH + 1)]).is_err());
+        let too_many: Vec<String> = (0..=MAX_TAGS).map(|i| format!("t{i}")).collect();
+        assert!(validate_tags(&too_many).is_err());
+    }
+
+    #[test]
+    fn folders_are_normalized() {
+        assert_eq!(
+            validate_folder("/projects/ roads /"),
+            Ok(Some("projects/roads".to_string()))
+        );
+        assert_eq!(validate_folder(" / "), Ok(None));
+        assert!(validate_folder("projects//roads").is_err());
+        assert!(validate_folder("projects/../admin").is_err());
+        assert!(validate_folder(&"a/".repeat(MAX_FOLDER_DEPTH + 1)).is_err());
+    }
+}
@@ -11,7 +11,7 @@ use crate::AppState;
 #[cfg(debug_assertions)]
 pub fn add_test_routes(router: Router<AppState>) -> Router<AppState> {
     if std::env::var("MAPFLOW_TEST_MODE").as_deref() == Ok("1") {
-        println!("Test mode enabled (debug only): exposing POST /api/test/reset");
+        tracing::info!("Test mode enabled (debug only): exposing POST /api/test/reset");
         router.route("/api/test/reset", post(reset_test_state))
     } else {
         router
@@ -28,9 +28,11 @@ async fn reset_test_state(State(state): State<AppState>) -> impl axum::response:
     let conn = state.db.lock().await;
 
     // Drop per-dataset tables.
-    // We use files.table_name as the source of truth.
-    if let Ok(mut stmt) = conn.prepare("SELECT table_name FROM files WHERE table_name IS NOT NULL")
-    {
+    // We use files.table_name and the retained versions as the source of truth.
+    if let Ok(mut stmt) = conn.prepare(
+        "SELECT table_name FROM files WHERE table_name IS NOT NULL
+         UNION ALL SELECT table_name FROM dataset_versions",
+    ) {
         if let Ok(rows) = stmt.query_map([], |row| row.get::<_, Option<String>>(0)) {
             for table in rows.flatten().flatten() {
                 // table is normalized/safe, but quote anyway.
@@ -41,29 +43,32 @@ async fn reset_test_state(State(state): State<AppState>) -> impl axum::response:
 
     // Order matters because of foreign key constraints (published_files.file_id -> files.id).
     if let Err(e) = conn.execute_batch(
-        "DELETE FROM published_files;\nDELETE FROM dataset_columns;\nDELETE FROM files;\nDELETE FROM sessions;\nDELETE FROM users;\nDELETE FROM system_settings;",
+        "DELETE FROM tileset_sources;\nDELETE FROM tilesets;\nDELETE FROM published_files;\nDELETE FROM slug_history;\nDELETE FROM export_jobs;\nDELETE FROM tile_seed_jobs;\nDELETE FROM file_retention;\nDELETE FROM file_tags;\nDELETE FROM remote_sources;\nDELETE FROM dataset_columns;\nDELETE FROM dataset_versions;\nDELETE FROM file_shares;\nDELETE FROM org_members;\nDELETE FROM orgs;\nDELETE FROM files;\nDELETE FROM sessions;\nDELETE FROM api_tokens;\nDELETE FROM webhooks;\nDELETE FROM password_reset_tokens;\nDELETE FROM users;\nDELETE FROM system_settings;",
     ) {
-        eprintln!("Test Reset DB Error: {:?}", e);
+        tracing::error!(error = ?e, "Test reset failed to clear the database");
         return (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(serde_json::json!({ "error": "DB cleanup failed" })),
         );
     }
 
+    drop(conn);
+    state.session_store.clear_cache();
+
     match fs::read_dir(&state.upload_dir).await {
         Ok(mut entries) => {
             while let Ok(Some(entry)) = entries.next_entry().await {
                 let path = entry.path();
                 if path.is_dir() {
                     if let Err(e) = fs::remove_dir_all(path).await {
-                        eprintln!("Test Reset FS Error: {:?}", e);
+                        tracing::error!(error = ?e, "Test reset failed to clear the upload directory");
                         return (
                             StatusCode::INTERNAL_SERVER_ERROR,
                             Json(serde_json::json!({ "error": "Upload dir cleanup failed" })),
                         );
                     }
                 } else if let Err(e) = fs::remove_file(path).await {
-                    eprintln!("Test Reset FS Error: {:?}", e);
+                    tracing::error!(error = ?e, "Test reset failed to clear the upload directory");
                     return (
                         StatusCode::INTERNAL_SERVER_ERROR,
                         Json(serde_json::json!({ "error": "Upload dir cleanup failed" })),
@@ -72,7 +77,7 @@ async fn reset_test_state(State(state): State<AppState>) -> impl axum::response:
             }
         }
         Err(e) => {
-            eprintln!("Test Reset FS Error: {:?}", e);
+            tracing::error!(error = ?e, "Test reset failed to clear the upload directory");
             return (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 Json(serde_json::json!({ "error": "Upload dir read failed" })),
@@ -0,0 +1,175 @@
+//! Dataset thumbnails
+//!
+//! When a vector dataset finishes importing, its features are drawn over their
+//! extent into a small square PNG kept next to the upload, so the file list can
+//! show what each dataset looks like. MBTiles and GeoTIFF files have none.
+
+use std::path::{Path, PathBuf};
+
+use axum::{
+    extract::{Path as AxumPath, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use duckdb::{Connection, OptionalExt};
+use serde_json::Value;
+
+use crate::http_errors::internal_error;
+use crate::mbtiles::resolve_mbtiles_path;
+use crate::raster::{layer_color, Canvas, Frame};
+use crate::tiles::web_mercator_geom_sql;
+use crate::{AppState, ErrorResponse};
+
+/// Width and height of a thumbnail, in pixels.
+pub const THUMBNAIL_SIZE: u32 = 128;
+const THUMBNAIL_FILE: &str = "thumbnail.png";
+/// Features drawn at most; a thumbnail of a larger dataset shows a sample.
+const MAX_THUMBNAIL_FEATURES: u32 = 20_000;
+/// Margin around the extent, as a share of its larger side.
+const MARGIN: f64 = 0.05;
+/// Half the side, in Web Mercator meters, of the view of a single point.
+const POINT_VIEW_HALF_SIZE: f64 = 1_000.0;
+const BACKGROUND: (u8, u8, u8) = (243, 244, 246);
+
+/// Where the thumbnail of the upload at `file_path` is kept.
+pub fn thumbnail_path(file_path: &Path) -> PathBuf {
+    file_path.with_file_name(THUMBNAIL_FILE)
+}
+
+/// Square Web Mercator view around `[minx, miny, maxx, maxy]`.
+fn view_bbox([minx, miny, maxx, maxy]: [f64; 4]) -> [f64; 4] {
+    let side = (maxx - minx).max(maxy - miny);
+    let half = if side > 0.0 {
+        side * (0.5 + MARGIN)
+    } else {
+        POINT_VIEW_HALF_SIZE
+    };
+    let (cx, cy) = ((minx + maxx) / 2.0, (miny + maxy) / 2.0);
+    [cx - half, cy - half, cx + half, cy + half]
+}
+
+/// Draw the thumbnail of an imported dataset and store it next to the upload.
+/// Does nothing for files without a feature table or features.
+pub fn write_thumbnail(conn: &Connection, file_id: &str, file_path: &Path) -> Result<(), String> {
+    let source: Option<(Option<String>, Option<String>)> = conn
+        .query_row(
+            "SELECT table_name, crs FROM files WHERE id = ? AND tile_format IS NULL",
+            duckdb::params![file_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    let Some((Some(table_name), crs)) = source else {
+        return Ok(());
+    };
+    let geom = web_mercator_geom_sql(crs.as_deref().unwrap_or("EPSG:4326"));
+
+    let extent: Option<[f64; 4]> = conn
+        .query_row(
+            &format!(
+                "SELECT ST_XMin(e), ST_YMin(e), ST_XMax(e), ST_YMax(e)
+                 FROM (SELECT ST_Extent_Agg({geom}) AS e FROM \"{table_name}\")"
+            ),
+            [],
+            |row| {
+                Ok(match (row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?) {
+                    (Some(minx), Some(miny), Some(maxx), Some(maxy)) => {
+                        Some([minx, miny, maxx, maxy])
+                    }
+                    _ => None,
+                })
+            },
+        )
+        .map_err(|e| e.to_string())?;
+    let Some(extent) = extent else {
+        return Ok(());
+    };
+    let bbox = view_bbox(extent);
+
+    // Simplify to about a pixel; finer detail cannot show.
+    let tolerance = (bbox[2] - bbox[0]) / f64::from(THUMBNAIL_SIZE);
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT ST_AsGeoJSON(ST_SimplifyPreserveTopology({geom}, {tolerance:?}))
+             FROM \"{table_name}\" WHERE geom IS NOT NULL LIMIT {MAX_THUMBNAIL_FEATURES}"
+        ))
+        .map_err(|e| e.to_string())?;
+    let geometries = stmt
+        .query_map([], |row| row.get::<_, Option<String>>(0))
+        .map_err(|e| e.to_string())?
+        .filter_map(|geojson| match geojson {
+            Ok(geojson) => geojson.and_then(|geojson| serde_json::from_str(&geojson).ok()),
+            Err(_) => None,
+        })
+        .collect::<Vec<Value>>();
+
+    let mut canvas = Canvas::new(THUMBNAIL_SIZE, THUMBNAIL_SIZE, Some(BACKGROUND))
+        .ok_or("Invalid thumbnail size")?;
+    let frame = Frame {
+        bbox,
+        y_down: false,
+    };
+    canvas.draw_layer(&frame, &geometries, layer_color(0));
+    let png = canvas.encode_png()?;
+    std::fs::write(thumbnail_path(file_path), png)
+        .map_err(|e| format!("Failed to write thumbnail: {}", e))
+}
+
+pub async fn get_file_thumbnail(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    let path: Option<String> = conn
+        .query_row(
+            "SELECT path FROM files WHERE id = ?",
+            duckdb::params![&id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(internal_error)?;
+    drop(conn);
+    let not_found = |error: &str| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: error.to_string(),
+            }),
+        )
+    };
+    let path = path.ok_or_else(|| not_found("File not found"))?;
+
+    match tokio::fs::read(thumbnail_path(&resolve_mbtiles_path(&path))).await {
+        Ok(png) => Ok(([(header::CONTENT_TYPE, "image/png")], png)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            Err(not_found("Thumbnail not available"))
+        }
+        Err(e) => Err(internal_error(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn views_are_square_with_a_margin() {
+        assert_eq!(
+            view_bbox([0.0, 0.0, 100.0, 50.0]),
+            [-5.0, -30.0, 105.0, 80.0]
+        );
+        assert_eq!(
+            view_bbox([10.0, 20.0, 10.0, 20.0]),
+            [-990.0, -980.0, 1010.0, 1020.0]
+        );
+    }
+
+    #[test]
+    fn thumbnails_sit_next_to_the_upload() {
+        assert_eq!(
+            thumbnail_path(Path::new("uploads/abc123/roads.geojson")),
+            Path::new("uploads/abc123/thumbnail.png")
+        );
+    }
+}
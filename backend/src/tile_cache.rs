@@ -0,0 +1,143 @@
+//! Tile cache
+//!
+//! Rendered vector tiles of imported datasets are kept on disk under
+//! `<upload_dir>/tile-cache/<file id>/<data version>/<variant>/<z>/<x>/<y>.mvt`.
+//! Every edit, append or re-import bumps the data version, so stale tiles are
+//! never served; the variant is a hash of the effective tile options, which
+//! keeps a public link's encoding overrides apart from the owner's preview.
+//! Filtered tiles and MBTiles files are not cached.
+//!
+//! Tiles of older versions are never read again, so they are purged whenever
+//! the version is bumped and when a dataset is unpublished; owners can also
+//! purge a dataset's tiles with `POST /api/files/{id}/cache/purge`.
+
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::models::TileOptions;
+
+pub const TILE_CACHE_DIR: &str = "tile-cache";
+
+pub fn tile_cache_root(upload_dir: &Path) -> PathBuf {
+    upload_dir.join(TILE_CACHE_DIR)
+}
+
+/// Identifies one rendered tile of one version of a dataset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TileKey {
+    pub file_id: String,
+    pub data_version: i64,
+    pub variant: String,
+    pub tile: (i32, i32, i32),
+}
+
+impl TileKey {
+    pub fn new(
+        file_id: &str,
+        data_version: i64,
+        options: &TileOptions,
+        tile: (i32, i32, i32),
+    ) -> Self {
+        TileKey {
+            file_id: file_id.to_string(),
+            data_version,
+            variant: options_variant(options),
+            tile,
+        }
+    }
+
+    fn path(&self, root: &Path) -> PathBuf {
+        let (z, x, y) = self.tile;
+        root.join(&self.file_id)
+            .join(self.data_version.to_string())
+            .join(&self.variant)
+            .join(z.to_string())
+            .join(x.to_string())
+            .join(format!("{y}.mvt"))
+    }
+}
+
+/// Short, stable name for a set of render options.
+fn options_variant(options: &TileOptions) -> String {
+    let json = serde_json::to_vec(options).expect("tile options serialize");
+    hex::encode(&Sha256::digest(json)[..8])
+}
+
+pub async fn read_cached_tile(root: &Path, key: &TileKey) -> Option<Vec<u8>> {
+    tokio::fs::read(key.path(root)).await.ok()
+}
+
+pub async fn tile_is_cached(root: &Path, key: &TileKey) -> bool {
+    tokio::fs::try_exists(key.path(root)).await.unwrap_or(false)
+}
+
+/// Store a tile. The write goes to a temporary file first, so readers never
+/// see a partial tile.
+pub async fn write_cached_tile(root: &Path, key: &TileKey, tile: &[u8]) -> std::io::Result<()> {
+    let path = key.path(root);
+    let dir = path.parent().expect("tile path has a parent");
+    tokio::fs::create_dir_all(dir).await?;
+    let partial = dir.join(format!(".{}.partial", uuid::Uuid::new_v4()));
+    tokio::fs::write(&partial, tile).await?;
+    if let Err(e) = tokio::fs::rename(&partial, &path).await {
+        let _ = tokio::fs::remove_file(&partial).await;
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Delete every cached tile of `file_id`, of all versions and variants.
+pub async fn purge_cached_tiles(root: &Path, file_id: &str) -> std::io::Result<()> {
+    match tokio::fs::remove_dir_all(root.join(file_id)).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Purge the cached tiles of `file_id` in the background, after its data
+/// changed. The data version already keeps them from being served, so a
+/// failure only leaves them taking up space.
+pub fn discard_cached_tiles(upload_dir: &Path, file_id: &str) {
+    let root = tile_cache_root(upload_dir);
+    let file_id = file_id.to_string();
+    tokio::spawn(async move {
+        if let Err(e) = purge_cached_tiles(&root, &file_id).await {
+            tracing::warn!(file_id, error = %e, "Failed to purge cached tiles");
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn tiles_are_keyed_by_version_and_options() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = tile_cache_root(dir.path());
+        let options = TileOptions::default();
+        let key = TileKey::new("file-1", 1, &options, (3, 4, 2));
+
+        assert_eq!(read_cached_tile(&root, &key).await, None);
+        write_cached_tile(&root, &key, b"tile").await.unwrap();
+        assert_eq!(
+            read_cached_tile(&root, &key).await.as_deref(),
+            Some(&b"tile"[..])
+        );
+
+        let next_version = TileKey::new("file-1", 2, &options, (3, 4, 2));
+        assert_eq!(read_cached_tile(&root, &next_version).await, None);
+        let other_options = TileOptions {
+            extent: Some(512),
+            ..Default::default()
+        };
+        let other_variant = TileKey::new("file-1", 1, &other_options, (3, 4, 2));
+        assert_ne!(other_variant.variant, key.variant);
+        assert_eq!(read_cached_tile(&root, &other_variant).await, None);
+
+        purge_cached_tiles(&root, "file-1").await.unwrap();
+        assert_eq!(read_cached_tile(&root, &key).await, None);
+        purge_cached_tiles(&root, "file-1").await.unwrap();
+    }
+}
@@ -0,0 +1,305 @@
+//! Tile debugging
+//!
+//! Vector tiles requested with `?debug=1` carry an extra `tile_debug` layer
+//! holding a single feature: the tile's outline, with properties telling how
+//! the tile came about. `z`, `x` and `y` locate it, `feature_count` and
+//! `layers` (`name=count`, comma-separated) count what it holds, `bytes` is its
+//! size without the debug layer, `generation_ms` how long it took to serve and
+//! `cached` whether it came from the tile cache. Debug layers are never
+//! cached, and public debug tiles are served with `no-store`.
+//!
+//! Layers are a repeated protobuf field, so the debug layer is encoded (with
+//! `pbf.rs`) and appended to the tile; the counts are read from the encoded tile itself, so
+//! they hold for cached tiles and tilesets alike.
+//!
+//! `GET /api/files/{id}/tiles/{z}/{x}/{y}/inspect` decodes the same tile the
+//! tile endpoint serves, with the same query, and reports each layer's name,
+//! extent, feature count, property keys and geometry types as JSON, so a tile
+//! can be checked without a PBF decoder.
+
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::time::Instant;
+
+use axum::{
+    extract::{Path as AxumPath, Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+
+use crate::http_errors::{bad_request, internal_error};
+use crate::models::{TileInspection, TileLayerInspection};
+use crate::pbf::{
+    command, fields, packed, write_bytes, write_key, write_varint_field, zigzag, Payload,
+    FEATURE_GEOMETRY, FEATURE_ID, FEATURE_TAGS, FEATURE_TYPE, GEOMETRY_LINESTRING, LAYER_EXTENT,
+    LAYER_FEATURES, LAYER_KEYS, LAYER_NAME, LAYER_VALUES, LAYER_VERSION, LINE_TO, MOVE_TO,
+    TILE_LAYERS, WIRE_FIXED64,
+};
+use crate::tile_routes::get_tile;
+use crate::tiles::TileQuery;
+use crate::{AppState, ErrorResponse};
+
+pub const DEBUG_LAYER_NAME: &str = "tile_debug";
+const DEBUG_EXTENT: u32 = 4096;
+/// Extent of layers that don't set one, as the spec defaults it.
+const DEFAULT_EXTENT: u32 = 4096;
+/// Tiles are read whole to be decoded; no generated tile comes near this.
+const MAX_INSPECTED_BYTES: usize = 64 * 1024 * 1024;
+
+const GEOMETRY_TYPES: [&str; 4] = ["Unknown", "Point", "LineString", "Polygon"];
+const VALUE_STRING: u32 = 1;
+const VALUE_DOUBLE: u32 = 3;
+const VALUE_UINT: u32 = 5;
+const VALUE_SINT: u32 = 6;
+const VALUE_BOOL: u32 = 7;
+
+/// A property value of the debug feature.
+enum DebugValue {
+    String(String),
+    Double(f64),
+    UInt(u64),
+    SInt(i64),
+    Bool(bool),
+}
+
+/// Append the debug layer of tile `z/x/y` to `tile` when `debug` holds the
+/// time serving it started; `cached` tells whether it came from the cache.
+pub fn with_debug_layer(
+    mut tile: Vec<u8>,
+    debug: Option<Instant>,
+    (z, x, y): (i32, i32, i32),
+    cached: bool,
+) -> Vec<u8> {
+    let Some(started) = debug else {
+        return tile;
+    };
+    let layers = layer_feature_counts(&tile).unwrap_or_default();
+    let feature_count: u64 = layers.iter().map(|(_, count)| count).sum();
+    let layer_list = layers
+        .iter()
+        .map(|(name, count)| format!("{name}={count}"))
+        .collect::<Vec<_>>()
+        .join(",");
+    let properties = [
+        ("z", DebugValue::SInt(i64::from(z))),
+        ("x", DebugValue::SInt(i64::from(x))),
+        ("y", DebugValue::SInt(i64::from(y))),
+        ("feature_count", DebugValue::UInt(feature_count)),
+        ("layers", DebugValue::String(layer_list)),
+        ("bytes", DebugValue::UInt(tile.len() as u64)),
+        (
+            "generation_ms",
+            DebugValue::Double(started.elapsed().as_secs_f64() * 1000.0),
+        ),
+        ("cached", DebugValue::Bool(cached)),
+    ];
+    write_bytes(&mut tile, TILE_LAYERS, &debug_layer(&properties));
+    tile
+}
+
+/// Name and number of features of each layer of an encoded tile, or `None`
+/// when it is not a valid one.
+pub fn layer_feature_counts(tile: &[u8]) -> Option<Vec<(String, u64)>> {
+    Some(
+        inspect_layers(tile)?
+            .into_iter()
+            .map(|layer| (layer.name, layer.feature_count))
+            .collect(),
+    )
+}
+
+/// Decode the layers of an encoded tile, or `None` when it is not a valid one.
+pub fn inspect_layers(tile: &[u8]) -> Option<Vec<TileLayerInspection>> {
+    let mut layers = Vec::new();
+    for (field, payload) in fields(tile)? {
+        if field != TILE_LAYERS {
+            continue;
+        }
+        let Payload::Bytes(layer) = payload else {
+            return None;
+        };
+        let mut inspected = TileLayerInspection {
+            name: String::new(),
+            extent: DEFAULT_EXTENT,
+            feature_count: 0,
+            property_keys: Vec::new(),
+            geometry_types: BTreeMap::new(),
+        };
+        for (field, payload) in fields(layer)? {
+            match (field, payload) {
+                (LAYER_NAME, Payload::Bytes(bytes)) => {
+                    inspected.name = String::from_utf8_lossy(bytes).into_owned();
+                }
+                (LAYER_FEATURES, Payload::Bytes(feature)) => {
+                    inspected.feature_count += 1;
+                    let geometry_type = fields(feature)?
+                        .into_iter()
+                        .find_map(|(field, payload)| match (field, payload) {
+                            (FEATURE_TYPE, Payload::Varint(value)) => Some(value),
+                            _ => None,
+                        })
+                        .unwrap_or(0);
+                    let name = usize::try_from(geometry_type)
+                        .ok()
+                        .and_then(|index| GEOMETRY_TYPES.get(index))
+                        .unwrap_or(&GEOMETRY_TYPES[0]);
+                    *inspected
+                        .geometry_types
+                        .entry(name.to_string())
+                        .or_insert(0) += 1;
+                }
+                (LAYER_KEYS, Payload::Bytes(bytes)) => inspected
+                    .property_keys
+                    .push(String::from_utf8_lossy(bytes).into_owned()),
+                (LAYER_EXTENT, Payload::Varint(extent)) => {
+                    inspected.extent = u32::try_from(extent).ok()?;
+                }
+                (LAYER_NAME | LAYER_FEATURES | LAYER_KEYS | LAYER_EXTENT, _) => return None,
+                _ => {}
+            }
+        }
+        layers.push(inspected);
+    }
+    Some(layers)
+}
+
+pub async fn inspect_tile(
+    State(state): State<AppState>,
+    AxumPath((id, z, x, y)): AxumPath<(String, i32, i32, i32)>,
+    Query(query): Query<TileQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let response = get_tile(State(state), AxumPath((id, z, x, y)), Query(query))
+        .await?
+        .into_response();
+    // No content is a tile with no layers.
+    let mut tile = Vec::new();
+    if response.status() != StatusCode::NO_CONTENT {
+        let header_value = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+        };
+        if header_value(header::CONTENT_TYPE) != Some("application/vnd.mapbox-vector-tile") {
+            return Err(bad_request("Only vector tiles can be inspected"));
+        }
+        let gzipped = header_value(header::CONTENT_ENCODING) == Some("gzip");
+        let body = axum::body::to_bytes(response.into_body(), MAX_INSPECTED_BYTES)
+            .await
+            .map_err(internal_error)?;
+        if gzipped {
+            flate2::read::GzDecoder::new(&body[..])
+                .read_to_end(&mut tile)
+                .map_err(internal_error)?;
+        } else {
+            tile = body.to_vec();
+        }
+    }
+
+    let layers =
+        inspect_layers(&tile).ok_or_else(|| internal_error("Tile is not a valid vector tile"))?;
+    Ok(Json(TileInspection {
+        z,
+        x,
+        y,
+        bytes: tile.len() as u64,
+        layers,
+    }))
+}
+
+fn debug_layer(properties: &[(&str, DebugValue)]) -> Vec<u8> {
+    let extent = i64::from(DEBUG_EXTENT);
+    // The outline as a line from the top left corner, clockwise: one MoveTo,
+    // then four LineTos, each relative to the last point.
+    let mut geometry = vec![
+        command(MOVE_TO, 1),
+        zigzag(0),
+        zigzag(0),
+        command(LINE_TO, 4),
+    ];
+    for (dx, dy) in [(extent, 0), (0, extent), (-extent, 0), (0, -extent)] {
+        geometry.extend([zigzag(dx), zigzag(dy)]);
+    }
+    let tags: Vec<u64> = (0..properties.len() as u64)
+        .flat_map(|index| [index, index])
+        .collect();
+
+    let mut feature = Vec::new();
+    write_varint_field(&mut feature, FEATURE_ID, 1);
+    write_bytes(&mut feature, FEATURE_TAGS, &packed(&tags));
+    write_varint_field(&mut feature, FEATURE_TYPE, GEOMETRY_LINESTRING);
+    write_bytes(&mut feature, FEATURE_GEOMETRY, &packed(&geometry));
+
+    let mut layer = Vec::new();
+    write_varint_field(&mut layer, LAYER_VERSION, 2);
+    write_bytes(&mut layer, LAYER_NAME, DEBUG_LAYER_NAME.as_bytes());
+    write_bytes(&mut layer, LAYER_FEATURES, &feature);
+    for (key, _) in properties {
+        write_bytes(&mut layer, LAYER_KEYS, key.as_bytes());
+    }
+    for (_, value) in properties {
+        let mut encoded = Vec::new();
+        match value {
+            DebugValue::String(s) => write_bytes(&mut encoded, VALUE_STRING, s.as_bytes()),
+            DebugValue::Double(d) => {
+                write_key(&mut encoded, VALUE_DOUBLE, WIRE_FIXED64);
+                encoded.extend(d.to_le_bytes());
+            }
+            DebugValue::UInt(n) => write_varint_field(&mut encoded, VALUE_UINT, *n),
+            DebugValue::SInt(n) => write_varint_field(&mut encoded, VALUE_SINT, zigzag(*n)),
+            DebugValue::Bool(b) => write_varint_field(&mut encoded, VALUE_BOOL, u64::from(*b)),
+        }
+        write_bytes(&mut layer, LAYER_VALUES, &encoded);
+    }
+    write_varint_field(&mut layer, LAYER_EXTENT, u64::from(DEBUG_EXTENT));
+    layer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mvt_reader::feature::Value;
+
+    #[test]
+    fn debug_layer_decodes_with_the_tile_stats() {
+        let first = with_debug_layer(Vec::new(), Some(Instant::now()), (3, 4, 2), false);
+        assert_eq!(
+            with_debug_layer(first.clone(), None, (3, 4, 2), true),
+            first
+        );
+        // A tile holding one layer with one feature: the first debug layer.
+        let tile = with_debug_layer(first.clone(), Some(Instant::now()), (3, 4, 2), true);
+        assert_eq!(
+            layer_feature_counts(&tile),
+            Some(vec![
+                (DEBUG_LAYER_NAME.to_string(), 1),
+                (DEBUG_LAYER_NAME.to_string(), 1)
+            ])
+        );
+        assert_eq!(layer_feature_counts(&[0x1a, 0x05, 0x0a]), None);
+        let layer = &inspect_layers(&first).unwrap()[0];
+        assert_eq!(layer.extent, DEBUG_EXTENT);
+        assert_eq!(layer.property_keys[..3], ["z", "x", "y"]);
+        assert_eq!(
+            layer.geometry_types,
+            BTreeMap::from([("LineString".to_string(), 1)])
+        );
+
+        let reader = mvt_reader::Reader::new(tile).unwrap();
+        let features = reader.get_features(1).unwrap();
+        let properties = features[0].properties.as_ref().unwrap();
+        assert_eq!(properties.get("x"), Some(&Value::SInt(4)));
+        assert_eq!(properties.get("feature_count"), Some(&Value::UInt(1)));
+        assert_eq!(
+            properties.get("layers"),
+            Some(&Value::String(format!("{DEBUG_LAYER_NAME}=1")))
+        );
+        assert_eq!(
+            properties.get("bytes"),
+            Some(&Value::UInt(first.len() as u64))
+        );
+        assert_eq!(properties.get("cached"), Some(&Value::Bool(true)));
+        assert!(matches!(properties.get("generation_ms"), Some(Value::Double(ms)) if *ms >= 0.0));
+    }
+}
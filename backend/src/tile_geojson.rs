@@ -0,0 +1,184 @@
+//! GeoJSON tiles
+//!
+//! `/tiles/{slug}/{z}/{x}/{y}.geojson` serves a tile's contents as a GeoJSON
+//! FeatureCollection, for debugging and for clients without an MVT decoder.
+//! Features are selected exactly as for the vector tile (envelope, buffer,
+//! clipping, simplification, clusters and feature limits), then their tile
+//! pixel coordinates are mapped back to longitude and latitude. Each feature
+//! carries its `fid` as `id`, its tile properties, and the name of its layer
+//! as a `layer` member.
+
+use duckdb::types::Value;
+use serde_json::{json, Map};
+
+use crate::features::{round_coordinates, value_ref_to_json};
+use crate::filter::CompiledFilter;
+use crate::models::TileOptions;
+use crate::read_pool::ReadConnection;
+use crate::tiles::{tile_features_sql, tile_origin_and_size, TileMode, WEB_MERCATOR_HALF_WORLD};
+
+/// One layer of a GeoJSON tile: its query and what reading it back needs.
+pub struct GeoJsonLayer {
+    pub name: String,
+    pub sql: String,
+    pub extent: u32,
+    pub precision: Option<u8>,
+}
+
+/// The features of a tile like `build_mvt_select_sql`, one row per feature:
+/// the geometry as GeoJSON in tile pixels, then the fields of the feature.
+#[allow(clippy::too_many_arguments)]
+pub fn build_geojson_layer(
+    conn: &duckdb::Connection,
+    source_id: &str,
+    table_name: &str,
+    source_crs: &str,
+    options: &TileOptions,
+    tile: (i32, i32, i32),
+    filter: Option<&CompiledFilter>,
+    mode: TileMode,
+) -> Result<GeoJsonLayer, duckdb::Error> {
+    let features = tile_features_sql(
+        conn, source_id, table_name, source_crs, options, tile, filter, mode, true,
+    )?;
+    Ok(GeoJsonLayer {
+        name: options.layer_name().to_string(),
+        sql: format!(
+            "SELECT ST_AsGeoJSON(feature.geom), feature.* FROM (\n            {features}\n        ) WHERE feature.geom IS NOT NULL"
+        ),
+        extent: options.extent(),
+        precision: options.precision,
+    })
+}
+
+/// Run each layer's query and collect the features of tile `z/x/y` into one
+/// FeatureCollection, in layer order.
+pub async fn collect_tile_features(
+    conn: ReadConnection,
+    layers: Vec<GeoJsonLayer>,
+    params: Vec<Value>,
+    tile: (i32, i32, i32),
+) -> Result<serde_json::Value, String> {
+    conn.run(move |conn| {
+        let mut features = Vec::new();
+        for layer in &layers {
+            let mut stmt = conn.prepare(&layer.sql).map_err(|e| e.to_string())?;
+            let mut rows = stmt
+                .query(duckdb::params_from_iter(params.iter()))
+                .map_err(|e| e.to_string())?;
+            let names = rows
+                .as_ref()
+                .map(|stmt| stmt.column_names())
+                .unwrap_or_default();
+            while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+                let geometry: String = row.get(0).map_err(|e| e.to_string())?;
+                let mut geometry: serde_json::Value =
+                    serde_json::from_str(&geometry).map_err(|e| e.to_string())?;
+                to_lon_lat(&mut geometry, tile, layer.extent);
+                if let Some(places) = layer.precision {
+                    round_coordinates(&mut geometry, places);
+                }
+                let mut id = serde_json::Value::Null;
+                let mut properties = Map::new();
+                for (index, name) in names.iter().enumerate().skip(1) {
+                    if name == "geom" {
+                        continue;
+                    }
+                    let value = value_ref_to_json(row.get_ref(index).map_err(|e| e.to_string())?);
+                    if name == "fid" {
+                        id = value;
+                    } else {
+                        properties.insert(name.clone(), value);
+                    }
+                }
+                features.push(json!({
+                    "type": "Feature",
+                    "id": id,
+                    "layer": layer.name,
+                    "geometry": geometry,
+                    "properties": properties,
+                }));
+            }
+        }
+        Ok(json!({ "type": "FeatureCollection", "features": features }))
+    })
+    .await
+}
+
+/// Map the positions of a GeoJSON geometry from pixels of an `extent`-pixel
+/// tile `z/x/y` (y down) to longitude and latitude.
+fn to_lon_lat(geometry: &mut serde_json::Value, tile: (i32, i32, i32), extent: u32) {
+    let (xmin, ymax, size) = tile_origin_and_size(tile);
+    let pixel = size / f64::from(extent);
+    map_positions(geometry, &|px, py| {
+        let x = xmin + px * pixel;
+        let y = ymax - py * pixel;
+        let lon = x / WEB_MERCATOR_HALF_WORLD * 180.0;
+        let lat = (y / WEB_MERCATOR_HALF_WORLD * std::f64::consts::PI)
+            .sinh()
+            .atan()
+            .to_degrees();
+        (lon, lat)
+    });
+}
+
+fn map_positions(value: &mut serde_json::Value, f: &dyn Fn(f64, f64) -> (f64, f64)) {
+    match value {
+        serde_json::Value::Object(object) => {
+            for key in ["coordinates", "geometries"] {
+                if let Some(member) = object.get_mut(key) {
+                    map_positions(member, f);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => {
+            let position = match items.as_slice() {
+                [px, py, ..] if px.is_number() => px.as_f64().zip(py.as_f64()),
+                _ => {
+                    for item in items {
+                        map_positions(item, f);
+                    }
+                    return;
+                }
+            };
+            // Positions beyond x and y (elevation) are dropped, as in MVT.
+            if let Some((px, py)) = position {
+                let (lon, lat) = f(px, py);
+                *items = vec![json!(lon), json!(lat)];
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tile_pixels_map_to_longitude_and_latitude() {
+        let mut geometry = json!({
+            "type": "LineString",
+            "coordinates": [[0, 0], [4096, 4096], [2048, 2048]]
+        });
+        to_lon_lat(&mut geometry, (1, 1, 0), 4096);
+        let coordinates = geometry["coordinates"].as_array().unwrap();
+        let position = |index: usize| {
+            let position = coordinates[index].as_array().unwrap();
+            (position[0].as_f64().unwrap(), position[1].as_f64().unwrap())
+        };
+        // Tile 1/1/0 is the north-east quarter of the world.
+        assert!((position(0).0 - 0.0).abs() < 1e-9);
+        assert!((position(0).1 - 85.051_128_78).abs() < 1e-6);
+        assert!((position(1).0 - 180.0).abs() < 1e-9);
+        assert!(position(1).1.abs() < 1e-9);
+        assert!((position(2).0 - 90.0).abs() < 1e-9);
+
+        let mut collection = json!({
+            "type": "GeometryCollection",
+            "geometries": [{ "type": "Point", "coordinates": [0, 4096] }]
+        });
+        to_lon_lat(&mut collection, (0, 0, 0), 4096);
+        assert_eq!(collection["geometries"][0]["coordinates"][0], -180.0);
+    }
+}
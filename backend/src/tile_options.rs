@@ -0,0 +1,651 @@
+//! Dataset-level tile options
+//!
+//! All per-dataset tile knobs live in one JSON document (`files.tile_options`)
+//! so they can be validated together, patched through the API and carried along
+//! with exports.
+
+use std::collections::HashSet;
+
+use duckdb::OptionalExt;
+
+use crate::columns::{resolve_column, DatasetColumn};
+use crate::features::order_by_clause;
+use crate::models::{FeatureLimitStrategy, TileOptions};
+
+pub const DEFAULT_LAYER_NAME: &str = "layer";
+pub const DEFAULT_EXTENT: u32 = 4096;
+pub const DEFAULT_BUFFER: u32 = 256;
+pub const MAX_TILE_ZOOM: u8 = 22;
+pub const DEFAULT_CLUSTER_RADIUS: u32 = 512;
+
+const MAX_LAYER_NAME_LENGTH: usize = 64;
+const MAX_SIMPLIFY_PIXELS: f64 = 16.0;
+const MAX_FEATURE_LIMIT: u32 = 1_000_000;
+/// Nine decimals of a degree are about a millimeter.
+const MAX_PRECISION: u8 = 9;
+
+/// Layer name used when none is configured: the dataset name lowercased, with
+/// anything outside `[a-z0-9_-]` collapsed to `_`.
+pub fn default_layer_name(dataset_name: &str) -> String {
+    let mut slug = String::with_capacity(dataset_name.len());
+    for c in dataset_name.chars() {
+        if c.is_ascii_alphanumeric() || matches!(c, '_' | '-') {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.ends_with('_') {
+            slug.push('_');
+        }
+    }
+    let slug: String = slug
+        .trim_matches(|c| c == '_' || c == '-')
+        .chars()
+        .take(MAX_LAYER_NAME_LENGTH)
+        .collect();
+    if slug.is_empty() {
+        DEFAULT_LAYER_NAME.to_string()
+    } else {
+        slug
+    }
+}
+
+impl TileOptions {
+    pub fn layer_name(&self) -> &str {
+        self.layer_name.as_deref().unwrap_or(DEFAULT_LAYER_NAME)
+    }
+
+    pub fn extent(&self) -> u32 {
+        self.extent.unwrap_or(DEFAULT_EXTENT)
+    }
+
+    pub fn buffer(&self) -> u32 {
+        self.buffer.unwrap_or(DEFAULT_BUFFER)
+    }
+
+    pub fn feature_limit_strategy(&self) -> FeatureLimitStrategy {
+        self.feature_limit_strategy.unwrap_or_default()
+    }
+
+    pub fn clip(&self) -> bool {
+        self.clip.unwrap_or(true)
+    }
+
+    pub fn cluster_radius(&self) -> u32 {
+        self.cluster_radius.unwrap_or(DEFAULT_CLUSTER_RADIUS)
+    }
+
+    /// Key of `column`'s property in tiles: its alias, or its original name.
+    pub fn property_key<'a>(
+        &'a self,
+        columns: &[DatasetColumn],
+        column: &'a DatasetColumn,
+    ) -> &'a str {
+        self.field_aliases
+            .iter()
+            .flatten()
+            .find(|(name, _)| {
+                resolve_column(columns, name).is_some_and(|c| c.normalized == column.normalized)
+            })
+            .map_or(column.original.as_str(), |(_, alias)| alias.as_str())
+    }
+
+    /// Whether tiles at zoom `z` hold clusters rather than features.
+    pub fn clusters_at(&self, z: i32) -> bool {
+        self.cluster_max_zoom.is_some_and(|max| z <= i32::from(max))
+    }
+
+    /// Whether tiles are served at zoom `z`.
+    pub fn covers_zoom(&self, z: i32) -> bool {
+        self.min_zoom.is_none_or(|min| z >= i32::from(min))
+            && self.max_zoom.is_none_or(|max| z <= i32::from(max))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self == &TileOptions::default()
+    }
+}
+
+/// Check the options against the server limits and the dataset's columns.
+pub fn validate_tile_options(
+    options: &TileOptions,
+    columns: &[DatasetColumn],
+) -> Result<(), String> {
+    if let Some(name) = &options.layer_name {
+        if name.is_empty() || name.len() > MAX_LAYER_NAME_LENGTH {
+            return Err(format!(
+                "layerName must be 1 to {MAX_LAYER_NAME_LENGTH} characters"
+            ));
+        }
+        if !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        {
+            return Err(
+                "layerName can only contain letters, numbers, '_', '-' and '.'".to_string(),
+            );
+        }
+    }
+
+    if let Some(extent) = options.extent {
+        if !(256..=16384).contains(&extent) || !extent.is_power_of_two() {
+            return Err("extent must be a power of two between 256 and 16384".to_string());
+        }
+    }
+
+    if options.buffer() > options.extent() {
+        return Err("buffer must not exceed extent".to_string());
+    }
+
+    if let Some(simplify) = options.simplify {
+        if !simplify.is_finite() || !(0.0..=MAX_SIMPLIFY_PIXELS).contains(&simplify) {
+            return Err(format!(
+                "simplify must be between 0 and {MAX_SIMPLIFY_PIXELS} pixels"
+            ));
+        }
+    }
+
+    if let Some(fields) = &options.fields {
+        let mut seen = HashSet::new();
+        for field in fields {
+            let column = resolve_column(columns, field)
+                .ok_or_else(|| format!("Unknown field '{field}' in fields"))?;
+            if !seen.insert(column.normalized.as_str()) {
+                return Err(format!("Field '{field}' is listed more than once"));
+            }
+        }
+    }
+
+    if let Some(aliases) = &options.field_aliases {
+        let mut aliased = HashSet::new();
+        for (field, alias) in aliases {
+            let column = resolve_column(columns, field)
+                .ok_or_else(|| format!("Unknown field '{field}' in fieldAliases"))?;
+            if !aliased.insert(column.normalized.as_str()) {
+                return Err(format!("Field '{field}' is aliased more than once"));
+            }
+            if alias.trim().is_empty() || alias.len() > MAX_LAYER_NAME_LENGTH {
+                return Err(format!(
+                    "Aliases must be 1 to {MAX_LAYER_NAME_LENGTH} characters"
+                ));
+            }
+        }
+        // Property keys share the feature struct with `geom` and `fid`, and
+        // DuckDB compares them case-insensitively.
+        let mut keys: HashSet<String> = ["geom".to_string(), "fid".to_string()].into();
+        for column in columns {
+            let key = options.property_key(columns, column);
+            if !keys.insert(key.to_lowercase()) {
+                return Err(format!("Property key '{key}' is used more than once"));
+            }
+        }
+    }
+
+    for zoom in [options.min_zoom, options.max_zoom].into_iter().flatten() {
+        if zoom > MAX_TILE_ZOOM {
+            return Err(format!("Zoom levels must be between 0 and {MAX_TILE_ZOOM}"));
+        }
+    }
+    if let (Some(min), Some(max)) = (options.min_zoom, options.max_zoom) {
+        if min > max {
+            return Err("minZoom must not exceed maxZoom".to_string());
+        }
+    }
+
+    if options.cluster_max_zoom.is_some_and(|z| z > MAX_TILE_ZOOM) {
+        return Err(format!(
+            "clusterMaxZoom must be between 0 and {MAX_TILE_ZOOM}"
+        ));
+    }
+    if let Some(radius) = options.cluster_radius {
+        if radius == 0 || radius > options.extent() {
+            return Err("clusterRadius must be between 1 and extent".to_string());
+        }
+    }
+
+    if let Some(limit) = options.feature_limit {
+        if !(1..=MAX_FEATURE_LIMIT).contains(&limit) {
+            return Err(format!(
+                "featureLimit must be between 1 and {MAX_FEATURE_LIMIT}"
+            ));
+        }
+    }
+    if let Some(sort) = &options.feature_limit_sort {
+        order_by_clause(columns, Some(sort)).map_err(|e| format!("featureLimitSort: {e}"))?;
+    }
+    if options.feature_limit_strategy == Some(FeatureLimitStrategy::Sort)
+        && options.feature_limit_sort.is_none()
+    {
+        return Err("featureLimitSort is required for the sort strategy".to_string());
+    }
+
+    if options
+        .precision
+        .is_some_and(|places| places > MAX_PRECISION)
+    {
+        return Err(format!(
+            "precision must be between 0 and {MAX_PRECISION} decimal places"
+        ));
+    }
+
+    Ok(())
+}
+
+/// A publish's `tileOptions` may only change how tiles are encoded; which
+/// properties they carry and under what keys is set with `includeFields` and
+/// `fieldAliases`.
+pub fn validate_publish_overrides(overrides: &TileOptions) -> Result<(), String> {
+    let encoding_only = TileOptions {
+        extent: overrides.extent,
+        buffer: overrides.buffer,
+        clip: overrides.clip,
+        ..Default::default()
+    };
+    if &encoding_only != overrides {
+        return Err("Only extent, buffer and clip can be set when publishing".to_string());
+    }
+    Ok(())
+}
+
+/// A publish's own zoom range, applied on top of the dataset's.
+pub fn validate_publish_zoom(minzoom: Option<u8>, maxzoom: Option<u8>) -> Result<(), String> {
+    if [minzoom, maxzoom]
+        .into_iter()
+        .flatten()
+        .any(|z| z > MAX_TILE_ZOOM)
+    {
+        return Err(format!("Zoom levels must be between 0 and {MAX_TILE_ZOOM}"));
+    }
+    if let (Some(min), Some(max)) = (minzoom, maxzoom) {
+        if min > max {
+            return Err("minzoom must not exceed maxzoom".to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Dataset options with a publish's encoding overrides, included fields and
+/// aliases applied.
+pub fn apply_publish_overrides(options: &TileOptions, overrides: &TileOptions) -> TileOptions {
+    TileOptions {
+        extent: overrides.extent.or(options.extent),
+        buffer: overrides.buffer.or(options.buffer),
+        clip: overrides.clip.or(options.clip),
+        fields: overrides.fields.clone().or_else(|| options.fields.clone()),
+        field_aliases: overrides
+            .field_aliases
+            .clone()
+            .or_else(|| options.field_aliases.clone()),
+        ..options.clone()
+    }
+}
+
+/// Apply a JSON merge patch (RFC 7386): `null` resets a field to its default.
+pub fn merge_tile_options(
+    current: &TileOptions,
+    patch: &serde_json::Value,
+) -> Result<TileOptions, String> {
+    let patch = patch
+        .as_object()
+        .ok_or_else(|| "Tile options patch must be a JSON object".to_string())?;
+
+    let mut merged = match serde_json::to_value(current) {
+        Ok(serde_json::Value::Object(map)) => map,
+        _ => serde_json::Map::new(),
+    };
+    for (key, value) in patch {
+        if value.is_null() {
+            merged.remove(key);
+        } else {
+            merged.insert(key.clone(), value.clone());
+        }
+    }
+
+    serde_json::from_value(serde_json::Value::Object(merged))
+        .map_err(|e| format!("Invalid tile options: {e}"))
+}
+
+/// Stored options for a dataset; `None` when the file does not exist.
+pub fn load_tile_options(
+    conn: &duckdb::Connection,
+    file_id: &str,
+) -> Result<Option<TileOptions>, duckdb::Error> {
+    let stored: Option<Option<String>> = conn
+        .query_row(
+            "SELECT tile_options FROM files WHERE id = ?",
+            duckdb::params![file_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    Ok(stored.map(|json| parse_stored_tile_options(json.as_deref())))
+}
+
+/// Options tiles are rendered with: the stored options, with the layer name
+/// defaulting to the slugified dataset name. `None` when the file does not exist.
+pub fn load_render_options(
+    conn: &duckdb::Connection,
+    file_id: &str,
+) -> Result<Option<TileOptions>, duckdb::Error> {
+    let stored: Option<(String, Option<String>)> = conn
+        .query_row(
+            "SELECT name, tile_options FROM files WHERE id = ?",
+            duckdb::params![file_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+
+    Ok(stored.map(|(name, json)| {
+        let mut options = parse_stored_tile_options(json.as_deref());
+        if options.layer_name.is_none() {
+            options.layer_name = Some(default_layer_name(&name));
+        }
+        options
+    }))
+}
+
+/// Options as stored in `files.tile_options`; unreadable JSON falls back to defaults.
+pub fn parse_stored_tile_options(json: Option<&str>) -> TileOptions {
+    json.and_then(|json| serde_json::from_str(json).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_tile_options(
+    conn: &duckdb::Connection,
+    file_id: &str,
+    options: &TileOptions,
+) -> Result<(), duckdb::Error> {
+    let json = (!options.is_empty())
+        .then(|| serde_json::to_string(options).expect("tile options serialize"));
+    conn.execute(
+        "UPDATE files SET tile_options = ? WHERE id = ?",
+        duckdb::params![json, file_id],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn publish_zoom_range_is_bounded_and_ordered() {
+        assert_eq!(validate_publish_zoom(None, None), Ok(()));
+        assert_eq!(validate_publish_zoom(Some(2), Some(12)), Ok(()));
+        assert!(validate_publish_zoom(None, Some(MAX_TILE_ZOOM + 1)).is_err());
+        assert!(validate_publish_zoom(Some(12), Some(2)).is_err());
+    }
+
+    fn columns() -> Vec<DatasetColumn> {
+        vec![DatasetColumn {
+            normalized: "road_name".to_string(),
+            original: "Road Name".to_string(),
+            mvt_type: "VARCHAR".to_string(),
+        }]
+    }
+
+    #[test]
+    fn defaults_apply_when_unset() {
+        let options = TileOptions::default();
+        assert_eq!(options.layer_name(), DEFAULT_LAYER_NAME);
+        assert_eq!(options.extent(), DEFAULT_EXTENT);
+        assert_eq!(options.buffer(), DEFAULT_BUFFER);
+        assert!(options.clip());
+        assert!(options.covers_zoom(0));
+        assert!(options.covers_zoom(22));
+        assert!(options.is_empty());
+    }
+
+    #[test]
+    fn default_layer_name_is_slugified_dataset_name() {
+        assert_eq!(default_layer_name("roads"), "roads");
+        assert_eq!(default_layer_name("Main Roads (2024)"), "main_roads_2024");
+        assert_eq!(default_layer_name("bus-stops_v2"), "bus-stops_v2");
+        assert_eq!(default_layer_name("道路"), DEFAULT_LAYER_NAME);
+        assert_eq!(default_layer_name(&"a".repeat(100)).len(), 64);
+        assert_eq!(
+            validate_tile_options(
+                &TileOptions {
+                    layer_name: Some(default_layer_name("Ünïcode names & more")),
+                    ..Default::default()
+                },
+                &[]
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn clustering_stops_after_its_max_zoom() {
+        assert!(!TileOptions::default().clusters_at(0));
+        let options = TileOptions {
+            cluster_max_zoom: Some(8),
+            ..Default::default()
+        };
+        assert!(options.clusters_at(0));
+        assert!(options.clusters_at(8));
+        assert!(!options.clusters_at(9));
+        assert_eq!(options.cluster_radius(), DEFAULT_CLUSTER_RADIUS);
+    }
+
+    #[test]
+    fn zoom_range_is_inclusive() {
+        let options = TileOptions {
+            min_zoom: Some(2),
+            max_zoom: Some(10),
+            ..Default::default()
+        };
+        assert!(!options.covers_zoom(1));
+        assert!(options.covers_zoom(2));
+        assert!(options.covers_zoom(10));
+        assert!(!options.covers_zoom(11));
+    }
+
+    #[test]
+    fn validate_accepts_full_options() {
+        let options = TileOptions {
+            layer_name: Some("roads".to_string()),
+            extent: Some(512),
+            buffer: Some(64),
+            clip: Some(false),
+            simplify: Some(1.5),
+            fields: Some(vec!["Road Name".to_string()]),
+            min_zoom: Some(4),
+            max_zoom: Some(14),
+            feature_limit: Some(5000),
+            feature_limit_strategy: Some(FeatureLimitStrategy::Sort),
+            feature_limit_sort: Some("-Road Name".to_string()),
+            cluster_max_zoom: Some(8),
+            cluster_radius: Some(256),
+            field_aliases: Some([("road_name".to_string(), "name".to_string())].into()),
+            precision: Some(6),
+        };
+        assert_eq!(validate_tile_options(&options, &columns()), Ok(()));
+    }
+
+    #[test]
+    fn validate_rejects_out_of_range_values() {
+        let cases = [
+            TileOptions {
+                layer_name: Some("my layer".to_string()),
+                ..Default::default()
+            },
+            TileOptions {
+                extent: Some(1000),
+                ..Default::default()
+            },
+            TileOptions {
+                extent: Some(256),
+                ..Default::default()
+            },
+            TileOptions {
+                simplify: Some(f64::NAN),
+                ..Default::default()
+            },
+            TileOptions {
+                fields: Some(vec!["missing".to_string()]),
+                ..Default::default()
+            },
+            TileOptions {
+                fields: Some(vec!["Road Name".to_string(), "road_name".to_string()]),
+                ..Default::default()
+            },
+            TileOptions {
+                min_zoom: Some(10),
+                max_zoom: Some(5),
+                ..Default::default()
+            },
+            TileOptions {
+                max_zoom: Some(23),
+                ..Default::default()
+            },
+            TileOptions {
+                feature_limit: Some(0),
+                ..Default::default()
+            },
+            TileOptions {
+                precision: Some(10),
+                ..Default::default()
+            },
+            TileOptions {
+                feature_limit: Some(10),
+                feature_limit_strategy: Some(FeatureLimitStrategy::Sort),
+                ..Default::default()
+            },
+            TileOptions {
+                feature_limit_sort: Some("-missing".to_string()),
+                ..Default::default()
+            },
+            TileOptions {
+                cluster_max_zoom: Some(23),
+                ..Default::default()
+            },
+            TileOptions {
+                extent: Some(512),
+                cluster_radius: Some(1024),
+                ..Default::default()
+            },
+            TileOptions {
+                field_aliases: Some([("missing".to_string(), "name".to_string())].into()),
+                ..Default::default()
+            },
+            TileOptions {
+                field_aliases: Some([("Road Name".to_string(), "FID".to_string())].into()),
+                ..Default::default()
+            },
+            TileOptions {
+                field_aliases: Some([("Road Name".to_string(), String::new())].into()),
+                ..Default::default()
+            },
+            TileOptions {
+                field_aliases: Some(
+                    [
+                        ("Road Name".to_string(), "name".to_string()),
+                        ("road_name".to_string(), "title".to_string()),
+                    ]
+                    .into(),
+                ),
+                ..Default::default()
+            },
+        ];
+        for options in cases {
+            assert!(
+                validate_tile_options(&options, &columns()).is_err(),
+                "{options:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn publish_overrides_only_change_encoding() {
+        let dataset = TileOptions {
+            layer_name: Some("roads".to_string()),
+            extent: Some(512),
+            buffer: Some(32),
+            ..Default::default()
+        };
+        let overrides = TileOptions {
+            buffer: Some(128),
+            clip: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(validate_publish_overrides(&overrides), Ok(()));
+        let applied = apply_publish_overrides(&dataset, &overrides);
+        assert_eq!(applied.layer_name(), "roads");
+        assert_eq!(applied.extent(), 512);
+        assert_eq!(applied.buffer(), 128);
+        assert!(!applied.clip());
+
+        assert!(validate_publish_overrides(&TileOptions {
+            layer_name: Some("other".to_string()),
+            ..Default::default()
+        })
+        .is_err());
+        assert!(validate_publish_overrides(&TileOptions {
+            fields: Some(vec!["Road Name".to_string()]),
+            ..Default::default()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn aliases_rename_property_keys() {
+        let columns = columns();
+        let options = TileOptions {
+            field_aliases: Some([("road_name".to_string(), "name".to_string())].into()),
+            ..Default::default()
+        };
+        assert_eq!(options.property_key(&columns, &columns[0]), "name");
+        assert_eq!(
+            TileOptions::default().property_key(&columns, &columns[0]),
+            "Road Name"
+        );
+    }
+
+    #[test]
+    fn included_fields_replace_the_dataset_fields() {
+        let dataset = TileOptions {
+            fields: Some(vec!["Road Name".to_string(), "owner".to_string()]),
+            ..Default::default()
+        };
+        let included = TileOptions {
+            fields: Some(vec!["lanes".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(
+            apply_publish_overrides(&dataset, &included).fields,
+            Some(vec!["lanes".to_string()])
+        );
+        assert_eq!(
+            apply_publish_overrides(&dataset, &TileOptions::default()).fields,
+            dataset.fields
+        );
+    }
+
+    #[test]
+    fn merge_patch_sets_and_resets_fields() {
+        let current = TileOptions {
+            layer_name: Some("roads".to_string()),
+            extent: Some(512),
+            ..Default::default()
+        };
+        let merged = merge_tile_options(
+            &current,
+            &serde_json::json!({ "extent": null, "featureLimit": 10 }),
+        )
+        .unwrap();
+        assert_eq!(
+            merged,
+            TileOptions {
+                layer_name: Some("roads".to_string()),
+                feature_limit: Some(10),
+                ..Default::default()
+            }
+        );
+
+        assert!(merge_tile_options(&current, &serde_json::json!({ "unknown": 1 })).is_err());
+        assert!(merge_tile_options(
+            &current,
+            &serde_json::json!({ "featureLimitStrategy": "biggest" })
+        )
+        .is_err());
+        assert!(merge_tile_options(&current, &serde_json::json!([1])).is_err());
+    }
+}
@@ -0,0 +1,852 @@
+//! Tile handlers
+//!
+//! `GET /api/files/{id}/tiles/{z}/{x}/{y}` serves a dataset's tiles to users
+//! who may read it, and `GET /tiles/{slug}/{z}/{x}/{y}` those of a published
+//! dataset or a tileset to anyone, as MVT, or drawn as a PNG or cut to GeoJSON
+//! by the row's extension. Vector datasets are rendered from their tables and
+//! cached on disk; MBTiles and GeoTIFF files are read from the stored file.
+
+use std::time::Instant;
+
+use axum::{
+    extract::{Path as AxumPath, Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+
+use crate::columns::{load_dataset_columns, resolve_column};
+use crate::filter::{compile_filter, CompiledFilter};
+use crate::geotiff::{load_geotiff_source, render_geotiff_tile};
+use crate::http_errors::{bad_request, internal_error};
+use crate::mbtiles;
+use crate::models::TileOptions;
+use crate::overzoom::{mbtiles_tile, overzoom_mvt, Overzoom};
+use crate::read_pool::ReadConnection;
+use crate::settings::{load_settings, public_cache_control};
+use crate::signing::SignedQuery;
+use crate::tile_cache::{read_cached_tile, tile_cache_root, write_cached_tile, TileKey};
+use crate::tile_debug::with_debug_layer;
+use crate::tile_geojson::{build_geojson_layer, collect_tile_features};
+use crate::tile_options::{
+    apply_publish_overrides, load_render_options, parse_stored_tile_options,
+};
+use crate::tiles::{
+    build_mvt_select_sql, build_tile_geometry_sql, draw_tile, encode_tile, mvt_params,
+    TileEncoding, TileMode, TileQuery,
+};
+use crate::tilesets::{load_tileset_sources, TilesetSource};
+use crate::versions::retained_version;
+use crate::{
+    check_not_expired, check_signed_access, find_tileset_by_slug, AppState, ErrorResponse,
+    PublishedSlugRow,
+};
+
+/// Type alias for (crs, status, table_name, tile_format, path, data_version, minzoom, maxzoom)
+/// of a file served as tiles
+type TileFileRow = (
+    Option<String>,
+    String,
+    Option<String>,
+    Option<String>,
+    String,
+    Option<i64>,
+    Option<i32>,
+    Option<i32>,
+);
+
+pub async fn get_tile(
+    State(state): State<AppState>,
+    AxumPath((id, z, x, y)): AxumPath<(String, i32, i32, i32)>,
+    Query(query): Query<TileQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    validate_tile_coords(z, x, y)?;
+    let mode = query.mode().map_err(|e| bad_request(&e))?;
+    let debug = query
+        .debug()
+        .map_err(|e| bad_request(&e))?
+        .then(Instant::now);
+
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+
+    // Get file metadata including tile_format
+    let (crs, status, table_name, tile_format, file_path, data_version, minzoom, maxzoom): TileFileRow = conn
+        .query_row(
+            "SELECT crs, status, table_name, tile_format, path, data_version, minzoom, maxzoom FROM files WHERE id = ?",
+            duckdb::params![id],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                    row.get(5)?,
+                    row.get(6)?,
+                    row.get(7)?,
+                ))
+            },
+        )
+        .map_err(|_| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    error: "File not found".to_string(),
+                }),
+            )
+        })?;
+
+    if status != "ready" {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(ErrorResponse {
+                error: "File is not ready for preview".to_string(),
+            }),
+        ));
+    }
+
+    if below_min_zoom(minzoom, z) {
+        return Ok(StatusCode::NO_CONTENT.into_response());
+    }
+
+    // GeoTIFF branch: drawn from the stored raster
+    if let Some(source) = load_geotiff_source(&conn, &id).map_err(internal_error)? {
+        if query.filter.is_some() || mode != TileMode::Features {
+            return Err(bad_request(
+                "Filter and tile modes are not supported for GeoTIFF files",
+            ));
+        }
+        if debug.is_some() {
+            return Err(bad_request(
+                "Debug tiles are not available for GeoTIFF files",
+            ));
+        }
+        let full_path = mbtiles::resolve_mbtiles_path(&file_path);
+        return match render_geotiff_tile(conn, full_path, source, (z, x, y)).await {
+            Ok(Some(png)) => Ok(([(header::CONTENT_TYPE, "image/png")], png).into_response()),
+            Ok(None) => Ok(StatusCode::NO_CONTENT.into_response()),
+            Err(e) => Err(internal_error(format!(
+                "Failed to draw GeoTIFF tile: {}",
+                e
+            ))),
+        };
+    }
+
+    // MBTiles branch
+    if let Some(format) = tile_format {
+        if query.filter.is_some() || mode != TileMode::Features {
+            return Err(bad_request(
+                "Filter and tile modes are not supported for MBTiles files",
+            ));
+        }
+        if debug.is_some() {
+            return Err(bad_request(
+                "Debug tiles are not available for MBTiles files",
+            ));
+        }
+        let full_path = mbtiles::resolve_mbtiles_path(&file_path);
+        drop(conn); // Release lock before async operation
+        match mbtiles_tile(&full_path, &format, (z, x, y), maxzoom).await {
+            Ok(Some(data)) => {
+                let ct = match format.as_str() {
+                    "mvt" => "application/vnd.mapbox-vector-tile",
+                    "png" => "image/png",
+                    _ => "application/octet-stream",
+                };
+
+                // Check if data is gzip-compressed (MBTiles tiles are often gzipped)
+                // Gzip magic bytes: 0x1f 0x8b
+                let is_gzipped = data.starts_with(&[0x1f, 0x8b]);
+
+                if is_gzipped {
+                    return Ok((
+                        [
+                            (header::CONTENT_TYPE, ct),
+                            (header::CONTENT_ENCODING, "gzip"),
+                        ],
+                        data,
+                    )
+                        .into_response());
+                } else {
+                    return Ok(([(header::CONTENT_TYPE, ct)], data).into_response());
+                }
+            }
+            Ok(None) => {
+                // Tile doesn't exist but coordinates are valid → 204 No Content
+                return Ok(StatusCode::NO_CONTENT.into_response());
+            }
+            Err(e) => {
+                return Err(internal_error(format!("Failed to read MBTiles: {}", e)));
+            }
+        }
+    }
+
+    // Dynamic generation branch (existing logic)
+    let table_name = table_name.ok_or_else(|| {
+        (
+            StatusCode::CONFLICT,
+            Json(ErrorResponse {
+                error: "File is not ready for preview".to_string(),
+            }),
+        )
+    })?;
+
+    let source_crs = crs.as_deref().unwrap_or("EPSG:4326");
+
+    // 2. Generate MVT
+    // logic:
+    //  - filter by source_id
+    //  - ST_Transform(geom, source_crs, 'EPSG:3857', always_xy := true)
+    //  - ST_TileEnvelope(z, x, y) to get tile bounds in 3857
+    //  - ST_AsMVTGeom(geom_3857, tile_env) to clip/transform to tile coords
+    //  - ST_AsMVT(...) to encode
+
+    // 2a. Build property struct keys based on captured column metadata.
+    // We keep property keys as original names for UX.
+    // Note: We exclude fid + geom.
+    let options = load_render_options(&conn, &id)
+        .map_err(internal_error)?
+        .unwrap_or_default();
+    // Beyond maxZoom tiles are cut from their ancestor at maxZoom, which is
+    // what gets rendered and cached.
+    let overzoom = Overzoom::beyond_max_zoom(&options, (z, x, y));
+    if overzoom.is_none() && !options.covers_zoom(z) {
+        return Ok(StatusCode::NO_CONTENT.into_response());
+    }
+    let rendered = overzoom.map_or((z, x, y), |overzoom| overzoom.parent);
+
+    let filter = compile_tile_filter(&conn, &id, query.filter.as_deref(), None)?;
+    let limit_headers = feature_limit_headers(&options);
+    // Filtered and density tiles depend on the query, so only plain tiles are cached.
+    let cache_key = (filter.is_none() && mode == TileMode::Features)
+        .then(|| TileKey::new(&id, data_version.unwrap_or(0), &options, rendered));
+    let cache_root = tile_cache_root(&state.upload_dir);
+    if let Some(key) = &cache_key {
+        if let Some(cached) = read_cached_tile(&cache_root, key).await {
+            let cached =
+                overzoom_mvt(overzoom.as_ref(), cached, &options).map_err(internal_error)?;
+            return Ok((
+                limit_headers,
+                [(header::CONTENT_TYPE, "application/vnd.mapbox-vector-tile")],
+                with_debug_layer(cached, debug, (z, x, y), true),
+            )
+                .into_response());
+        }
+    }
+    let select_sql = build_mvt_select_sql(
+        &conn,
+        &id,
+        &table_name,
+        source_crs,
+        &options,
+        rendered,
+        filter.as_ref(),
+        mode,
+    )
+    .map_err(internal_error)?;
+
+    // Params: z, x, y (for AsMVTGeom bounds), z, x, y (for intersects), then filter values
+    let params = mvt_params(rendered.0, rendered.1, rendered.2, filter.as_ref());
+    let mvt_blob = match encode_tile(conn, vec![select_sql.clone()], params).await {
+        Ok(blob) => blob,
+        Err(e) => {
+            tracing::error!(file_id = %id, z, x, y, error = ?e, sql = %select_sql, "Tile generation failed");
+            return Err(internal_error(format!("Tile generation failed: {}", e)));
+        }
+    };
+
+    tracing::debug!(
+        file_id = %id,
+        z,
+        x,
+        y,
+        bytes = mvt_blob.len(),
+        "Rendered tile"
+    );
+    if let Some(key) = &cache_key {
+        if let Err(e) = write_cached_tile(&cache_root, key, &mvt_blob).await {
+            tracing::warn!(file_id = %id, z, x, y, error = %e, "Failed to cache tile");
+        }
+    }
+    let mvt_blob = overzoom_mvt(overzoom.as_ref(), mvt_blob, &options).map_err(internal_error)?;
+
+    // Mapbox clients expect 200 with a valid PBF; an empty blob is an empty MVT.
+    Ok((
+        limit_headers,
+        [(header::CONTENT_TYPE, "application/vnd.mapbox-vector-tile")],
+        with_debug_layer(mvt_blob, debug, (z, x, y), false),
+    )
+        .into_response())
+}
+
+/// Report the per-tile feature limit (and how features were dropped) on tiles
+/// it applies to, so clients can tell a sparse tile from a cut-down one.
+fn feature_limit_headers(options: &TileOptions) -> axum::http::HeaderMap {
+    let mut headers = axum::http::HeaderMap::new();
+    if let Some(limit) = options.feature_limit {
+        headers.insert("x-feature-limit", limit.into());
+        headers.insert(
+            "x-feature-limit-strategy",
+            axum::http::HeaderValue::from_static(options.feature_limit_strategy().as_str()),
+        );
+    }
+    headers
+}
+
+/// Compile the `filter` query parameter of a tile request against the dataset's
+/// columns, or only those in `fields` when given: public tiles can be filtered
+/// on the properties they carry and no others.
+fn compile_tile_filter(
+    conn: &duckdb::Connection,
+    source_id: &str,
+    filter: Option<&str>,
+    fields: Option<&[String]>,
+) -> Result<Option<CompiledFilter>, (StatusCode, Json<ErrorResponse>)> {
+    let Some(filter) = filter.filter(|f| !f.trim().is_empty()) else {
+        return Ok(None);
+    };
+    let mut columns = load_dataset_columns(conn, source_id).map_err(internal_error)?;
+    if let Some(fields) = fields {
+        columns = fields
+            .iter()
+            .filter_map(|field| resolve_column(&columns, field).cloned())
+            .collect();
+    }
+    compile_filter(filter, &columns)
+        .map(Some)
+        .map_err(|e| bad_request(&e))
+}
+
+/// Whether zoom `z` is below a file's `minzoom`. Nothing is stored there, so
+/// such tiles are empty without a lookup; beyond its maxzoom, tiles are cut
+/// from the maxzoom tile instead.
+fn below_min_zoom(minzoom: Option<i32>, z: i32) -> bool {
+    minzoom.is_some_and(|min| z < min)
+}
+
+fn validate_tile_coords(z: i32, x: i32, y: i32) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    // Practical cap. This is plenty for web maps and keeps bounds math simple.
+    const MAX_Z: i32 = 22;
+
+    if z < 0 || x < 0 || y < 0 || z > MAX_Z {
+        return Err(bad_request("Invalid tile coordinates"));
+    }
+
+    let max_xy: i32 = 1_i32 << z;
+    if x >= max_xy || y >= max_xy {
+        return Err(bad_request("Invalid tile coordinates"));
+    }
+
+    Ok(())
+}
+
+/// Encode one layer per tileset source and concatenate them; 204 when no
+/// source's zoom range covers `z`.
+async fn render_tileset_tile(
+    conn: ReadConnection,
+    tileset_id: &str,
+    (z, x, y): (i32, i32, i32),
+    cache_control: &str,
+    debug: Option<Instant>,
+) -> Result<axum::response::Response, (StatusCode, Json<ErrorResponse>)> {
+    let sources = load_tileset_sources(&conn, tileset_id).map_err(internal_error)?;
+    let sources: Vec<&TilesetSource> = sources
+        .iter()
+        .filter(|source| source.options.covers_zoom(z))
+        .collect();
+    if sources.is_empty() {
+        return Ok(StatusCode::NO_CONTENT.into_response());
+    }
+
+    let layers = sources
+        .into_iter()
+        .map(|source| {
+            build_mvt_select_sql(
+                &conn,
+                &source.file_id,
+                &source.table_name,
+                &source.crs,
+                &source.options,
+                (z, x, y),
+                None,
+                TileMode::Features,
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(internal_error)?;
+    let tile = encode_tile(conn, layers, mvt_params(z, x, y, None))
+        .await
+        .map_err(|e| {
+            tracing::error!(tileset_id, z, x, y, error = ?e, "Tileset tile generation failed");
+            internal_error(format!("Tile generation failed: {}", e))
+        })?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/vnd.mapbox-vector-tile"),
+            (header::CACHE_CONTROL, cache_control),
+        ],
+        with_debug_layer(tile, debug, (z, x, y), false),
+    )
+        .into_response())
+}
+
+/// Draw each tileset source covering `z` as a layer of a PNG tile; 204 when
+/// none does.
+async fn draw_tileset_tile(
+    conn: ReadConnection,
+    tileset_id: &str,
+    (z, x, y): (i32, i32, i32),
+    cache_control: &str,
+) -> Result<axum::response::Response, (StatusCode, Json<ErrorResponse>)> {
+    let sources = load_tileset_sources(&conn, tileset_id).map_err(internal_error)?;
+    let sources: Vec<&TilesetSource> = sources
+        .iter()
+        .filter(|source| source.options.covers_zoom(z))
+        .collect();
+    if sources.is_empty() {
+        return Ok(StatusCode::NO_CONTENT.into_response());
+    }
+
+    let layers = sources
+        .into_iter()
+        .map(|source| {
+            let sql = build_tile_geometry_sql(
+                &conn,
+                &source.file_id,
+                &source.table_name,
+                &source.crs,
+                &source.options,
+                (z, x, y),
+                None,
+                TileMode::Features,
+            )?;
+            Ok((sql, source.options.extent()))
+        })
+        .collect::<Result<Vec<_>, duckdb::Error>>()
+        .map_err(internal_error)?;
+    let png = draw_tile(conn, layers, mvt_params(z, x, y, None))
+        .await
+        .map_err(|e| {
+            tracing::error!(tileset_id, z, x, y, error = %e, "Tileset PNG tile drawing failed");
+            internal_error(format!("Tile generation failed: {}", e))
+        })?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "image/png"),
+            (header::CACHE_CONTROL, cache_control),
+        ],
+        png,
+    )
+        .into_response())
+}
+
+async fn tileset_geojson_tile(
+    conn: ReadConnection,
+    tileset_id: &str,
+    (z, x, y): (i32, i32, i32),
+    cache_control: &str,
+) -> Result<axum::response::Response, (StatusCode, Json<ErrorResponse>)> {
+    let sources = load_tileset_sources(&conn, tileset_id).map_err(internal_error)?;
+    let layers = sources
+        .iter()
+        .filter(|source| source.options.covers_zoom(z))
+        .map(|source| {
+            build_geojson_layer(
+                &conn,
+                &source.file_id,
+                &source.table_name,
+                &source.crs,
+                &source.options,
+                (z, x, y),
+                None,
+                TileMode::Features,
+            )
+        })
+        .collect::<Result<Vec<_>, duckdb::Error>>()
+        .map_err(internal_error)?;
+    let collection = collect_tile_features(conn, layers, mvt_params(z, x, y, None), (z, x, y))
+        .await
+        .map_err(|e| {
+            tracing::error!(tileset_id, z, x, y, error = %e, "Tileset GeoJSON tile generation failed");
+            internal_error(format!("Tile generation failed: {}", e))
+        })?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/geo+json"),
+            (header::CACHE_CONTROL, cache_control),
+        ],
+        collection.to_string(),
+    )
+        .into_response())
+}
+
+pub async fn get_public_tile(
+    State(state): State<AppState>,
+    AxumPath((slug, z, x, y)): AxumPath<(String, i32, i32, String)>,
+    Query(query): Query<TileQuery>,
+    Query(signed): Query<SignedQuery>,
+) -> Result<axum::response::Response, (StatusCode, Json<ErrorResponse>)> {
+    let (y, encoding) =
+        TileEncoding::parse_row(&y).ok_or_else(|| bad_request("Invalid tile coordinates"))?;
+    serve_public_tile(&state, &slug, (z, x, y), query, signed, encoding).await
+}
+
+/// A published file's or tileset's tile as MVT, or drawn as a PNG.
+pub async fn serve_public_tile(
+    state: &AppState,
+    slug: &str,
+    (z, x, y): (i32, i32, i32),
+    query: TileQuery,
+    signed: SignedQuery,
+    encoding: TileEncoding,
+) -> Result<axum::response::Response, (StatusCode, Json<ErrorResponse>)> {
+    validate_tile_coords(z, x, y)?;
+    let mode = query.mode().map_err(|e| bad_request(&e))?;
+    let debug = query
+        .debug()
+        .map_err(|e| bad_request(&e))?
+        .then(Instant::now);
+    if debug.is_some() && encoding != TileEncoding::Mvt {
+        return Err(bad_request(&format!(
+            "Debug tiles are not available as {}",
+            encoding.label()
+        )));
+    }
+
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    // Debug tiles report how this one request went, so they are never stored.
+    let cache_control = match debug {
+        Some(_) => "no-store".to_string(),
+        None => public_cache_control(&load_settings(&conn, state).map_err(internal_error)?),
+    };
+
+    if let Some(tileset_id) = find_tileset_by_slug(&conn, slug)? {
+        if query.filter.is_some() || mode != TileMode::Features {
+            return Err(bad_request(
+                "Filter and tile modes are not supported for tilesets",
+            ));
+        }
+        match encoding {
+            TileEncoding::Png => {
+                return draw_tileset_tile(conn, &tileset_id, (z, x, y), &cache_control).await;
+            }
+            TileEncoding::GeoJson => {
+                return tileset_geojson_tile(conn, &tileset_id, (z, x, y), &cache_control).await;
+            }
+            TileEncoding::Mvt => {}
+        }
+        return render_tileset_tile(conn, &tileset_id, (z, x, y), &cache_control, debug).await;
+    }
+
+    // Step 1: Get file_id from published_files using slug (enforces uniqueness)
+    let (file_id, publish_overrides, signing_secret, expires_at, version): PublishedSlugRow = conn
+        .query_row(
+            "SELECT file_id, tile_options, signing_secret, expires_at, version
+             FROM published_files WHERE slug = ?",
+            duckdb::params![slug],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            },
+        )
+        .map_err(|_| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    error: "Public tile not found".to_string(),
+                }),
+            )
+        })?;
+    check_not_expired(expires_at)?;
+    check_signed_access(signing_secret.as_deref(), slug, &signed)?;
+    let publish_overrides = parse_stored_tile_options(publish_overrides.as_deref());
+    if !publish_overrides.covers_zoom(z) {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Zoom level is outside the published range".to_string(),
+            }),
+        ));
+    }
+
+    // Step 2: Get file metadata from files table, verifying is_public flag
+    let (crs, status, table_name, tile_format, file_path, data_version, minzoom, maxzoom): TileFileRow = conn
+        .query_row(
+            "SELECT crs, status, table_name, tile_format, path, data_version, minzoom, maxzoom FROM files WHERE id = ? AND is_public = TRUE",
+            duckdb::params![&file_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?, row.get(6)?, row.get(7)?)),
+        )
+        .map_err(|_| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    error: "File not found".to_string(),
+                }),
+            )
+        })?;
+
+    if status != "ready" {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(ErrorResponse {
+                error: "File is not ready".to_string(),
+            }),
+        ));
+    }
+
+    if below_min_zoom(minzoom, z) {
+        return Ok(StatusCode::NO_CONTENT.into_response());
+    }
+
+    // GeoTIFF branch: drawn from the stored raster
+    if let Some(source) = load_geotiff_source(&conn, &file_id).map_err(internal_error)? {
+        if query.filter.is_some() || mode != TileMode::Features {
+            return Err(bad_request(
+                "Filter and tile modes are not supported for GeoTIFF files",
+            ));
+        }
+        if debug.is_some() {
+            return Err(bad_request(
+                "Debug tiles are not available for GeoTIFF files",
+            ));
+        }
+        if encoding == TileEncoding::GeoJson {
+            return Err(bad_request(
+                "GeoJSON tiles are not available for GeoTIFF files",
+            ));
+        }
+        let full_path = mbtiles::resolve_mbtiles_path(&file_path);
+        let png = render_geotiff_tile(conn, full_path, source, (z, x, y))
+            .await
+            .map_err(|e| internal_error(format!("Failed to draw GeoTIFF tile: {}", e)))?;
+        return Ok(match png {
+            Some(png) => (
+                [
+                    (header::CONTENT_TYPE, "image/png"),
+                    (header::CACHE_CONTROL, cache_control.as_str()),
+                ],
+                png,
+            )
+                .into_response(),
+            None => StatusCode::NO_CONTENT.into_response(),
+        });
+    }
+
+    // MBTiles branch
+    if let Some(format) = tile_format {
+        if query.filter.is_some() || mode != TileMode::Features {
+            return Err(bad_request(
+                "Filter and tile modes are not supported for MBTiles files",
+            ));
+        }
+        if debug.is_some() {
+            return Err(bad_request(
+                "Debug tiles are not available for MBTiles files",
+            ));
+        }
+        if encoding == TileEncoding::Png && format != "png" {
+            return Err(bad_request(
+                "PNG tiles are not available for vector MBTiles files",
+            ));
+        }
+        if encoding == TileEncoding::GeoJson {
+            return Err(bad_request(
+                "GeoJSON tiles are not available for MBTiles files",
+            ));
+        }
+        let full_path = mbtiles::resolve_mbtiles_path(&file_path);
+        drop(conn); // Release lock before async operation
+        match mbtiles_tile(&full_path, &format, (z, x, y), maxzoom).await {
+            Ok(Some(data)) => {
+                let ct = match format.as_str() {
+                    "mvt" => "application/vnd.mapbox-vector-tile",
+                    "png" => "image/png",
+                    _ => "application/octet-stream",
+                };
+                return Ok((
+                    [
+                        (header::CONTENT_TYPE, ct),
+                        (header::CACHE_CONTROL, cache_control.as_str()),
+                    ],
+                    data,
+                )
+                    .into_response());
+            }
+            Ok(None) => {
+                return Ok(StatusCode::NO_CONTENT.into_response());
+            }
+            Err(e) => {
+                return Err(internal_error(format!("Failed to read MBTiles: {}", e)));
+            }
+        }
+    }
+
+    // Dynamic generation branch (existing logic)
+    let table_name = table_name.ok_or_else(|| {
+        (
+            StatusCode::CONFLICT,
+            Json(ErrorResponse {
+                error: "File is not ready".to_string(),
+            }),
+        )
+    })?;
+
+    // A pinned version that has since been replaced is read from its own
+    // table, with the columns and CRS it had.
+    let pinned = retained_version(&conn, &file_id, version).map_err(internal_error)?;
+    let (columns_source, table_name, crs) = match &pinned {
+        Some(version) => (
+            version.table_name.as_str(),
+            version.table_name.clone(),
+            version.crs.clone(),
+        ),
+        None => (file_id.as_str(), table_name, crs),
+    };
+    let source_crs = crs.as_deref().unwrap_or("EPSG:4326");
+
+    let options = apply_publish_overrides(
+        &load_render_options(&conn, &file_id)
+            .map_err(internal_error)?
+            .unwrap_or_default(),
+        &publish_overrides,
+    );
+    let overzoom = Overzoom::beyond_max_zoom(&options, (z, x, y));
+    if overzoom.is_none() && !options.covers_zoom(z) {
+        return Ok(StatusCode::NO_CONTENT.into_response());
+    }
+    let rendered = overzoom.map_or((z, x, y), |overzoom| overzoom.parent);
+
+    let filter = compile_tile_filter(
+        &conn,
+        columns_source,
+        query.filter.as_deref(),
+        options.fields.as_deref(),
+    )?;
+    let limit_headers = feature_limit_headers(&options);
+    if encoding == TileEncoding::Png {
+        let select_sql = build_tile_geometry_sql(
+            &conn,
+            columns_source,
+            &table_name,
+            source_crs,
+            &options,
+            rendered,
+            filter.as_ref(),
+            mode,
+        )
+        .map_err(internal_error)?;
+        let params = mvt_params(rendered.0, rendered.1, rendered.2, filter.as_ref());
+        let png = draw_tile(conn, vec![(select_sql, options.extent())], params)
+            .await
+            .map_err(|e| {
+                tracing::error!(%slug, z, x, y, error = %e, "Public PNG tile drawing failed");
+                internal_error(format!("Tile generation failed: {}", e))
+            })?;
+        let png = match overzoom {
+            Some(overzoom) => overzoom.cut_png(&png).map_err(internal_error)?,
+            None => png,
+        };
+        return Ok((
+            limit_headers,
+            [
+                (header::CONTENT_TYPE, "image/png"),
+                (header::CACHE_CONTROL, cache_control.as_str()),
+            ],
+            png,
+        )
+            .into_response());
+    }
+    if encoding == TileEncoding::GeoJson {
+        let layer = build_geojson_layer(
+            &conn,
+            columns_source,
+            &table_name,
+            source_crs,
+            &options,
+            rendered,
+            filter.as_ref(),
+            mode,
+        )
+        .map_err(internal_error)?;
+        let params = mvt_params(rendered.0, rendered.1, rendered.2, filter.as_ref());
+        let collection = collect_tile_features(conn, vec![layer], params, rendered)
+            .await
+            .map_err(|e| {
+                tracing::error!(%slug, z, x, y, error = %e, "Public GeoJSON tile generation failed");
+                internal_error(format!("Tile generation failed: {}", e))
+            })?;
+        return Ok((
+            limit_headers,
+            [
+                (header::CONTENT_TYPE, "application/geo+json"),
+                (header::CACHE_CONTROL, cache_control.as_str()),
+            ],
+            collection.to_string(),
+        )
+            .into_response());
+    }
+    // The cache follows the dataset's current data, so pinned versions that
+    // were replaced are not cached.
+    let cache_key = (filter.is_none() && mode == TileMode::Features && pinned.is_none())
+        .then(|| TileKey::new(&file_id, data_version.unwrap_or(0), &options, rendered));
+    let cache_root = tile_cache_root(&state.upload_dir);
+    if let Some(key) = &cache_key {
+        if let Some(cached) = read_cached_tile(&cache_root, key).await {
+            let cached =
+                overzoom_mvt(overzoom.as_ref(), cached, &options).map_err(internal_error)?;
+            return Ok((
+                limit_headers,
+                [
+                    (header::CONTENT_TYPE, "application/vnd.mapbox-vector-tile"),
+                    (header::CACHE_CONTROL, cache_control.as_str()),
+                ],
+                with_debug_layer(cached, debug, (z, x, y), true),
+            )
+                .into_response());
+        }
+    }
+    let select_sql = build_mvt_select_sql(
+        &conn,
+        columns_source,
+        &table_name,
+        source_crs,
+        &options,
+        rendered,
+        filter.as_ref(),
+        mode,
+    )
+    .map_err(internal_error)?;
+
+    let params = mvt_params(rendered.0, rendered.1, rendered.2, filter.as_ref());
+    let mvt_blob = match encode_tile(conn, vec![select_sql], params).await {
+        Ok(blob) => blob,
+        Err(e) => {
+            tracing::error!(%slug, z, x, y, error = ?e, "Public tile generation failed");
+            return Err(internal_error(format!("Tile generation failed: {}", e)));
+        }
+    };
+    if let Some(key) = &cache_key {
+        if let Err(e) = write_cached_tile(&cache_root, key, &mvt_blob).await {
+            tracing::warn!(%slug, z, x, y, error = %e, "Failed to cache tile");
+        }
+    }
+    let mvt_blob = overzoom_mvt(overzoom.as_ref(), mvt_blob, &options).map_err(internal_error)?;
+
+    Ok((
+        limit_headers,
+        [
+            (header::CONTENT_TYPE, "application/vnd.mapbox-vector-tile"),
+            (header::CACHE_CONTROL, cache_control.as_str()),
+        ],
+        with_debug_layer(mvt_blob, debug, (z, x, y), false),
+    )
+        .into_response())
+}
@@ -0,0 +1,436 @@
+//! Tile pre-seeding
+//!
+//! `POST /api/files/:id/seed` renders every tile of a bbox and zoom range into
+//! the tile cache in the background, so the first visitors of a big dataset
+//! don't wait for rendering. Jobs are rows of `tile_seed_jobs` that move from
+//! `pending` through `processing` to `ready` or `failed`, counting rendered
+//! tiles as they go. Tiles are seeded for the dataset's own options and, when
+//! it is published, for the public link's overrides. `mapflow seed` runs the
+//! same job from the command line.
+
+use std::f64::consts::PI;
+use std::ops::RangeInclusive;
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{Path as AxumPath, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use axum_login::AuthSession;
+use chrono::Utc;
+use duckdb::OptionalExt;
+
+use crate::auth::{AuthBackend, User};
+use crate::authz::file_access;
+use crate::http_errors::{bad_request, internal_error};
+use crate::models::{AppState, TileOptions, TileSeedJob, TileSeedRequest};
+use crate::ready_file::load_ready_file;
+use crate::tile_cache::{tile_cache_root, tile_is_cached, write_cached_tile, TileKey};
+use crate::tile_options::{
+    apply_publish_overrides, load_render_options, parse_stored_tile_options, MAX_TILE_ZOOM,
+};
+use crate::tiles::{build_mvt_select_sql, encode_tile, mvt_params, TileMode};
+use crate::ErrorResponse;
+
+/// Upper bound on the tiles of one job; zoom 14 over a city is ~1,000 tiles,
+/// over a country ~100,000.
+pub const MAX_SEED_TILES: u64 = 100_000;
+/// How often a running job saves its progress.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+/// Web Mercator stops short of the poles.
+const MAX_MERCATOR_LATITUDE: f64 = 85.051_128_779_806_59;
+
+/// Tile columns and rows covering a WGS84 `bbox` at zoom `z`.
+pub fn tile_range(bbox: [f64; 4], z: u8) -> (RangeInclusive<u32>, RangeInclusive<u32>) {
+    let [west, south, east, north] = bbox;
+    let n = 2f64.powi(i32::from(z));
+    let max_index = n - 1.0;
+    let column = |lon: f64| ((lon + 180.0) / 360.0 * n).floor().clamp(0.0, max_index) as u32;
+    let row = |lat: f64| {
+        let lat = lat
+            .clamp(-MAX_MERCATOR_LATITUDE, MAX_MERCATOR_LATITUDE)
+            .to_radians();
+        ((1.0 - (lat.tan() + 1.0 / lat.cos()).ln() / PI) / 2.0 * n)
+            .floor()
+            .clamp(0.0, max_index) as u32
+    };
+    (column(west)..=column(east), row(north)..=row(south))
+}
+
+pub fn count_tiles(bbox: [f64; 4], min_zoom: u8, max_zoom: u8) -> u64 {
+    (min_zoom..=max_zoom)
+        .map(|z| {
+            let (columns, rows) = tile_range(bbox, z);
+            u64::from(columns.end() - columns.start() + 1)
+                * u64::from(rows.end() - rows.start() + 1)
+        })
+        .sum()
+}
+
+/// Check a request against the server limits; `extent` stands in for a
+/// missing bbox. Returns the bbox and the number of tiles.
+pub fn validate_seed_request(
+    req: &TileSeedRequest,
+    extent: Option<[f64; 4]>,
+) -> Result<([f64; 4], u64), String> {
+    if req.max_zoom > MAX_TILE_ZOOM {
+        return Err(format!("Zoom levels must be between 0 and {MAX_TILE_ZOOM}"));
+    }
+    if req.min_zoom > req.max_zoom {
+        return Err("minZoom must not exceed maxZoom".to_string());
+    }
+    let bbox = req
+        .bbox
+        .or(extent)
+        .ok_or("The dataset has no extent; pass a bbox")?;
+    let [west, south, east, north] = bbox;
+    let valid = bbox.iter().all(|value| value.is_finite())
+        && (-180.0..=180.0).contains(&west)
+        && (-180.0..=180.0).contains(&east)
+        && (-90.0..=90.0).contains(&south)
+        && (-90.0..=90.0).contains(&north)
+        && west <= east
+        && south <= north;
+    if !valid {
+        return Err("bbox must be [west, south, east, north] in WGS84 degrees".to_string());
+    }
+    let total = count_tiles(bbox, req.min_zoom, req.max_zoom);
+    if total > MAX_SEED_TILES {
+        return Err(format!(
+            "The request covers {total} tiles; seed at most {MAX_SEED_TILES} at a time"
+        ));
+    }
+    Ok((bbox, total))
+}
+
+fn seed_job_from_row(row: &duckdb::Row<'_>) -> Result<TileSeedJob, duckdb::Error> {
+    let bbox: String = row.get(3)?;
+    let created_at: chrono::NaiveDateTime = row.get(8)?;
+    let finished_at: Option<chrono::NaiveDateTime> = row.get(9)?;
+    Ok(TileSeedJob {
+        id: row.get(0)?,
+        file_id: row.get(1)?,
+        status: row.get(2)?,
+        bbox: serde_json::from_str(&bbox).unwrap_or_default(),
+        min_zoom: row.get(4)?,
+        max_zoom: row.get(5)?,
+        total_tiles: row.get(6)?,
+        rendered_tiles: row.get(7)?,
+        created_at: created_at.and_utc().to_rfc3339(),
+        finished_at: finished_at.map(|ts| ts.and_utc().to_rfc3339()),
+        error: row.get(10)?,
+    })
+}
+
+pub fn load_seed_job(
+    conn: &duckdb::Connection,
+    job_id: &str,
+) -> Result<Option<TileSeedJob>, duckdb::Error> {
+    conn.query_row(
+        "SELECT id, file_id, status, bbox, min_zoom, max_zoom, total_tiles, rendered_tiles,
+                created_at, finished_at, error
+         FROM tile_seed_jobs WHERE id = ?",
+        duckdb::params![job_id],
+        seed_job_from_row,
+    )
+    .optional()
+}
+
+/// Validate a request for a ready dataset and record it as a pending job.
+pub async fn create_seed_job(
+    state: &AppState,
+    file_id: &str,
+    req: &TileSeedRequest,
+) -> Result<TileSeedJob, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.db.lock().await;
+    let file = load_ready_file(&conn, file_id, "MBTiles files are already tiled")?;
+    let extent = match req.bbox {
+        Some(_) => None,
+        None => {
+            let stored_bounds: Option<String> = conn
+                .query_row(
+                    "SELECT bbox FROM files WHERE id = ?",
+                    duckdb::params![file_id],
+                    |row| row.get(0),
+                )
+                .map_err(internal_error)?;
+            crate::dataset_bbox(
+                &conn,
+                stored_bounds.as_deref(),
+                Some(&file.table_name),
+                Some(&file.crs),
+            )
+        }
+    };
+    let (bbox, total) = validate_seed_request(req, extent).map_err(|e| bad_request(&e))?;
+
+    let job_id = crate::create_id();
+    conn.execute(
+        "INSERT INTO tile_seed_jobs
+             (id, file_id, status, bbox, min_zoom, max_zoom, total_tiles, rendered_tiles, created_at)
+         VALUES (?, ?, 'pending', ?, ?, ?, ?, 0, ?)",
+        duckdb::params![
+            &job_id,
+            file_id,
+            serde_json::to_string(&bbox).expect("bbox serializes"),
+            req.min_zoom,
+            req.max_zoom,
+            total,
+            Utc::now().naive_utc()
+        ],
+    )
+    .map_err(internal_error)?;
+    load_seed_job(&conn, &job_id)
+        .map_err(internal_error)?
+        .ok_or_else(|| internal_error("Seed job was not saved"))
+}
+
+/// The render options tiles are served with: the dataset's own and, if it is
+/// published, the public link's.
+fn seed_variants(
+    conn: &duckdb::Connection,
+    file_id: &str,
+) -> Result<Vec<TileOptions>, duckdb::Error> {
+    let options = load_render_options(conn, file_id)?.unwrap_or_default();
+    let published: Option<Option<String>> = conn
+        .query_row(
+            "SELECT tile_options FROM published_files WHERE file_id = ?",
+            duckdb::params![file_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let mut variants = vec![options.clone()];
+    if let Some(overrides) = published {
+        let public =
+            apply_publish_overrides(&options, &parse_stored_tile_options(overrides.as_deref()));
+        if public != options {
+            variants.push(public);
+        }
+    }
+    Ok(variants)
+}
+
+fn save_progress(conn: &duckdb::Connection, job_id: &str, rendered: u64) {
+    let _ = conn.execute(
+        "UPDATE tile_seed_jobs SET rendered_tiles = ? WHERE id = ?",
+        duckdb::params![rendered, job_id],
+    );
+}
+
+/// Render the job's tiles into the cache, skipping those already there.
+/// `on_progress` sees the rendered and total tile counts after every tile.
+pub async fn run_seed_job(
+    state: &AppState,
+    job: &TileSeedJob,
+    mut on_progress: impl FnMut(u64, u64),
+) -> Result<u64, String> {
+    {
+        let conn = state.db.lock().await;
+        let _ = conn.execute(
+            "UPDATE tile_seed_jobs SET status = 'processing' WHERE id = ?",
+            duckdb::params![&job.id],
+        );
+    }
+
+    let result = seed_tiles(state, job, &mut on_progress).await;
+
+    let conn = state.db.lock().await;
+    let now = Utc::now().naive_utc();
+    match &result {
+        Ok(rendered) => {
+            tracing::info!(file_id = %job.file_id, job_id = %job.id, rendered, "Seeded tiles");
+            let _ = conn.execute(
+                "UPDATE tile_seed_jobs SET status = 'ready', rendered_tiles = ?, finished_at = ? WHERE id = ?",
+                duckdb::params![rendered, now, &job.id],
+            );
+        }
+        Err(e) => {
+            tracing::error!(file_id = %job.file_id, job_id = %job.id, error = %e, "Failed to seed tiles");
+            let _ = conn.execute(
+                "UPDATE tile_seed_jobs SET status = 'failed', error = ?, finished_at = ? WHERE id = ?",
+                duckdb::params![e, now, &job.id],
+            );
+        }
+    }
+    result
+}
+
+async fn seed_tiles(
+    state: &AppState,
+    job: &TileSeedJob,
+    on_progress: &mut impl FnMut(u64, u64),
+) -> Result<u64, String> {
+    let (table_name, crs, data_version, variants) = {
+        let conn = state.read_pool.get().await.map_err(|e| e.to_string())?;
+        let (table_name, crs, data_version): (Option<String>, Option<String>, i64) = conn
+            .query_row(
+                "SELECT table_name, crs, COALESCE(data_version, 0) FROM files WHERE id = ? AND status = 'ready'",
+                duckdb::params![&job.file_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .map_err(|_| "File is not ready".to_string())?;
+        let variants = seed_variants(&conn, &job.file_id).map_err(|e| e.to_string())?;
+        (
+            table_name.ok_or("File is not ready")?,
+            crs.unwrap_or_else(|| "EPSG:4326".to_string()),
+            data_version,
+            variants,
+        )
+    };
+    let root = tile_cache_root(&state.upload_dir);
+
+    let mut rendered = 0;
+    let mut last_saved = Instant::now();
+    for z in job.min_zoom..=job.max_zoom {
+        let (columns, rows) = tile_range(job.bbox, z);
+        for x in columns {
+            for y in rows.clone() {
+                let tile = (i32::from(z), x as i32, y as i32);
+                for options in variants
+                    .iter()
+                    .filter(|options| options.covers_zoom(tile.0))
+                {
+                    let key = TileKey::new(&job.file_id, data_version, options, tile);
+                    if tile_is_cached(&root, &key).await {
+                        continue;
+                    }
+                    let conn = state.read_pool.get().await.map_err(|e| e.to_string())?;
+                    let sql = build_mvt_select_sql(
+                        &conn,
+                        &job.file_id,
+                        &table_name,
+                        &crs,
+                        options,
+                        tile,
+                        None,
+                        TileMode::Features,
+                    )
+                    .map_err(|e| e.to_string())?;
+                    let (z, x, y) = tile;
+                    let blob = encode_tile(conn, vec![sql], mvt_params(z, x, y, None))
+                        .await
+                        .map_err(|e| format!("Tile generation failed: {e}"))?;
+                    write_cached_tile(&root, &key, &blob)
+                        .await
+                        .map_err(|e| format!("Failed to write tile cache: {e}"))?;
+                }
+                rendered += 1;
+                on_progress(rendered, job.total_tiles);
+                if last_saved.elapsed() >= PROGRESS_INTERVAL {
+                    save_progress(&*state.db.lock().await, &job.id, rendered);
+                    last_saved = Instant::now();
+                }
+            }
+        }
+    }
+    Ok(rendered)
+}
+
+pub async fn seed_file_tiles(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+    Json(req): Json<TileSeedRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let job = create_seed_job(&state, &id, &req).await?;
+    let background_job = job.clone();
+    tokio::spawn(async move {
+        let _ = run_seed_job(&state, &background_job, |_, _| {}).await;
+    });
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+fn seed_job_not_found() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse {
+            error: "Seed job not found".to_string(),
+        }),
+    )
+}
+
+/// A seed job is visible to whoever can read its file.
+fn check_seed_job_access(
+    conn: &duckdb::Connection,
+    user: Option<&User>,
+    job_id: &str,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    let file_id: String = conn
+        .query_row(
+            "SELECT file_id FROM tile_seed_jobs WHERE id = ?",
+            duckdb::params![job_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(internal_error)?
+        .ok_or_else(seed_job_not_found)?;
+    let Some(user) = user else {
+        return Ok(());
+    };
+    let can_read = file_access(conn, user, &file_id)
+        .map_err(internal_error)?
+        .flatten()
+        .is_some();
+    if can_read {
+        Ok(())
+    } else {
+        Err(seed_job_not_found())
+    }
+}
+
+pub async fn get_seed_job(
+    State(state): State<AppState>,
+    auth_session: AuthSession<AuthBackend>,
+    AxumPath(job_id): AxumPath<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    check_seed_job_access(&conn, auth_session.user.as_ref(), &job_id)?;
+    load_seed_job(&conn, &job_id)
+        .map_err(internal_error)?
+        .map(Json)
+        .ok_or_else(seed_job_not_found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(bbox: Option<[f64; 4]>, min_zoom: u8, max_zoom: u8) -> TileSeedRequest {
+        TileSeedRequest {
+            bbox,
+            min_zoom,
+            max_zoom,
+        }
+    }
+
+    #[test]
+    fn bbox_maps_to_xyz_tiles() {
+        let world = [-180.0, -90.0, 180.0, 90.0];
+        assert_eq!(tile_range(world, 0), (0..=0, 0..=0));
+        assert_eq!(tile_range(world, 2), (0..=3, 0..=3));
+        // Central London at zoom 10.
+        assert_eq!(
+            tile_range([-0.2, 51.45, -0.05, 51.55], 10),
+            (511..=511, 340..=340)
+        );
+        assert_eq!(count_tiles(world, 0, 2), 1 + 4 + 16);
+    }
+
+    #[test]
+    fn requests_are_bounded() {
+        let extent = Some([-0.2, 51.45, -0.05, 51.55]);
+        assert_eq!(
+            validate_seed_request(&request(None, 10, 10), extent),
+            Ok(([-0.2, 51.45, -0.05, 51.55], 1))
+        );
+        assert!(validate_seed_request(&request(None, 0, 5), None).is_err());
+        assert!(validate_seed_request(&request(extent, 5, 4), None).is_err());
+        assert!(validate_seed_request(&request(extent, 0, MAX_TILE_ZOOM + 1), None).is_err());
+        assert!(
+            validate_seed_request(&request(Some([10.0, 0.0, -10.0, 5.0]), 0, 2), None).is_err()
+        );
+        let world = Some([-180.0, -85.0, 180.0, 85.0]);
+        assert!(validate_seed_request(&request(world, 0, 12), None).is_err());
+    }
+}
@@ -0,0 +1,185 @@
+//! TileJSON documents
+//!
+//! Dynamic datasets describe a single vector layer named after their tile
+//! options, so clients can read `source-layer` instead of guessing it. MBTiles
+//! pass through the layers recorded in their own metadata.
+
+use std::collections::BTreeMap;
+
+use crate::columns::{resolve_column, DatasetColumn};
+use crate::models::{LayerInfo, TileJson, TileOptions, VectorLayer};
+
+pub const TILEJSON_VERSION: &str = "3.0.0";
+
+/// TileJSON field description for a column's DuckDB type.
+pub fn field_type_name(mvt_type: &str) -> &'static str {
+    match mvt_type {
+        "BOOLEAN" => "Boolean",
+        "INTEGER" | "BIGINT" | "DOUBLE" | "FLOAT" => "Number",
+        _ => "String",
+    }
+}
+
+/// The vector layer of a dynamic dataset, limited to the configured `fields`
+/// and under their aliases, plus the `point_count` of clusters when they are
+/// enabled.
+pub fn dataset_vector_layer(options: &TileOptions, columns: &[DatasetColumn]) -> VectorLayer {
+    let selected: Vec<&DatasetColumn> = match &options.fields {
+        Some(fields) => fields
+            .iter()
+            .filter_map(|field| resolve_column(columns, field))
+            .collect(),
+        None => columns.iter().collect(),
+    };
+
+    VectorLayer {
+        id: options.layer_name().to_string(),
+        description: None,
+        fields: selected
+            .into_iter()
+            .map(|column| {
+                (
+                    options.property_key(columns, column).to_string(),
+                    field_type_name(&column.mvt_type).to_string(),
+                )
+            })
+            .chain(
+                options
+                    .cluster_max_zoom
+                    .map(|_| ("point_count".to_string(), "Number".to_string())),
+            )
+            .collect(),
+    }
+}
+
+/// Smallest `[minx, miny, maxx, maxy]` covering both extents.
+pub fn union_bounds(current: Option<[f64; 4]>, other: [f64; 4]) -> [f64; 4] {
+    match current {
+        Some(b) => [
+            b[0].min(other[0]),
+            b[1].min(other[1]),
+            b[2].max(other[2]),
+            b[3].max(other[3]),
+        ],
+        None => other,
+    }
+}
+
+/// Narrow the advertised zoom range to what a publish serves.
+pub fn restrict_zoom_range(tilejson: &mut TileJson, minzoom: Option<u8>, maxzoom: Option<u8>) {
+    if let Some(min) = minzoom {
+        tilejson.minzoom = tilejson.minzoom.max(min);
+    }
+    if let Some(max) = maxzoom {
+        tilejson.maxzoom = tilejson.maxzoom.min(max);
+    }
+}
+
+pub fn mbtiles_vector_layers(layers: Vec<LayerInfo>) -> Vec<VectorLayer> {
+    layers
+        .into_iter()
+        .map(|layer| VectorLayer {
+            id: layer.id,
+            description: layer.description,
+            fields: layer
+                .fields
+                .into_iter()
+                .map(|field| (field.name, field.r#type))
+                .collect::<BTreeMap<_, _>>(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(original: &str, mvt_type: &str) -> DatasetColumn {
+        DatasetColumn {
+            normalized: original.to_lowercase().replace(' ', "_"),
+            original: original.to_string(),
+            mvt_type: mvt_type.to_string(),
+        }
+    }
+
+    #[test]
+    fn union_covers_both_extents() {
+        let a = [0.0, 0.0, 2.0, 2.0];
+        assert_eq!(union_bounds(None, a), a);
+        assert_eq!(
+            union_bounds(Some(a), [-1.0, 1.0, 1.0, 5.0]),
+            [-1.0, 0.0, 2.0, 5.0]
+        );
+    }
+
+    #[test]
+    fn publish_zoom_range_narrows_advertised_zooms() {
+        let mut tilejson = TileJson {
+            tilejson: TILEJSON_VERSION.to_string(),
+            name: "roads".to_string(),
+            tiles: Vec::new(),
+            minzoom: 4,
+            maxzoom: 22,
+            bounds: None,
+            description: None,
+            attribution: None,
+            license: None,
+            vector_layers: None,
+        };
+        restrict_zoom_range(&mut tilejson, Some(2), Some(12));
+        assert_eq!((tilejson.minzoom, tilejson.maxzoom), (4, 12));
+        restrict_zoom_range(&mut tilejson, Some(6), None);
+        assert_eq!((tilejson.minzoom, tilejson.maxzoom), (6, 12));
+    }
+
+    #[test]
+    fn dataset_layer_follows_tile_options() {
+        let columns = vec![
+            column("Road Name", "VARCHAR"),
+            column("lanes", "INTEGER"),
+            column("oneway", "BOOLEAN"),
+        ];
+
+        let layer = dataset_vector_layer(
+            &TileOptions {
+                layer_name: Some("roads".to_string()),
+                ..Default::default()
+            },
+            &columns,
+        );
+        assert_eq!(layer.id, "roads");
+        assert_eq!(layer.fields.len(), 3);
+        assert_eq!(layer.fields["Road Name"], "String");
+        assert_eq!(layer.fields["lanes"], "Number");
+        assert_eq!(layer.fields["oneway"], "Boolean");
+
+        let layer = dataset_vector_layer(
+            &TileOptions {
+                fields: Some(vec!["road_name".to_string()]),
+                ..Default::default()
+            },
+            &columns,
+        );
+        assert_eq!(layer.fields.keys().collect::<Vec<_>>(), vec!["Road Name"]);
+
+        let layer = dataset_vector_layer(
+            &TileOptions {
+                fields: Some(Vec::new()),
+                cluster_max_zoom: Some(6),
+                ..Default::default()
+            },
+            &columns,
+        );
+        assert_eq!(layer.fields.keys().collect::<Vec<_>>(), vec!["point_count"]);
+
+        let layer = dataset_vector_layer(
+            &TileOptions {
+                field_aliases: Some([("road_name".to_string(), "name".to_string())].into()),
+                ..Default::default()
+            },
+            &columns,
+        );
+        assert_eq!(layer.fields["name"], "String");
+        assert!(!layer.fields.contains_key("Road Name"));
+    }
+}
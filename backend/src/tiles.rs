@@ -1,37 +1,302 @@
-use duckdb::Connection;
+use duckdb::{types::Value, Connection};
+use serde::Deserialize;
 
+use crate::columns::{
+    load_dataset_columns, quote_identifier, quote_literal, resolve_column, DatasetColumn,
+};
+use crate::features::order_by_clause;
+use crate::filter::CompiledFilter;
+use crate::models::{FeatureLimitStrategy, TileOptions};
+use crate::raster::{layer_color, Canvas, Frame};
+use crate::read_pool::ReadConnection;
+
+/// Query parameters accepted by the tile endpoints.
+#[derive(Debug, Default, Deserialize)]
+pub struct TileQuery {
+    pub filter: Option<String>,
+    pub mode: Option<String>,
+    pub debug: Option<String>,
+}
+
+impl TileQuery {
+    pub fn mode(&self) -> Result<TileMode, String> {
+        match self.mode.as_deref().map(str::trim) {
+            None | Some("") | Some("features") => Ok(TileMode::Features),
+            Some("density") => Ok(TileMode::Density),
+            Some(other) => Err(format!(
+                "Unsupported tile mode '{other}' (expected features or density)"
+            )),
+        }
+    }
+
+    /// Whether the tile should carry the `tile_debug` layer.
+    pub fn debug(&self) -> Result<bool, String> {
+        match self.debug.as_deref().map(str::trim) {
+            None | Some("") | Some("0") | Some("false") => Ok(false),
+            Some("1") | Some("true") => Ok(true),
+            Some(other) => Err(format!(
+                "Unsupported debug value '{other}' (expected 1 or 0)"
+            )),
+        }
+    }
+}
+
+/// What a dynamic tile holds, picked by the `mode` query parameter.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TileMode {
+    /// The features, or their clusters up to `clusterMaxZoom`.
+    #[default]
+    Features,
+    /// Feature counts on a fixed grid, for heatmaps.
+    Density,
+}
+
+/// Cells per tile side in `TileMode::Density`.
+const DENSITY_GRID_SIZE: u32 = 64;
+
+/// Encoding of a public tile, picked by the extension on its `y` path segment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TileEncoding {
+    Mvt,
+    Png,
+    GeoJson,
+}
+
+impl TileEncoding {
+    /// Split a `y` path segment such as `5`, `5.png` or `5.geojson` into the
+    /// row and encoding.
+    pub fn parse_row(segment: &str) -> Option<(i32, Self)> {
+        let (row, encoding) = if let Some(row) = segment.strip_suffix(".png") {
+            (row, Self::Png)
+        } else if let Some(row) = segment.strip_suffix(".geojson") {
+            (row, Self::GeoJson)
+        } else {
+            (segment, Self::Mvt)
+        };
+        Some((row.parse().ok()?, encoding))
+    }
+
+    /// How the encoding is named in error messages.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Mvt => "MVT",
+            Self::Png => "PNG",
+            Self::GeoJson => "GeoJSON",
+        }
+    }
+}
+
+/// Parameters for `build_mvt_select_sql`: tile coordinates for the MVT bounds and
+/// the intersects check, followed by the filter's own parameters.
+pub fn mvt_params(z: i32, x: i32, y: i32, filter: Option<&CompiledFilter>) -> Vec<Value> {
+    let mut params: Vec<Value> = [z, x, y, z, x, y].into_iter().map(Value::Int).collect();
+    if let Some(filter) = filter {
+        params.extend(filter.params.iter().cloned());
+    }
+    params
+}
+
+/// Half the Web Mercator world width in meters.
+pub const WEB_MERCATOR_HALF_WORLD: f64 = 20_037_508.342_789_244;
+
+/// Side in Web Mercator meters of the grid vertices snap to for the
+/// `precision` tile option: its last decimal of a degree, measured at the
+/// equator where a degree spans the fewest meters. `None` unless the grid is
+/// coarser than a tile pixel at zoom `z`, since tile encoding rounds to pixels
+/// anyway.
+pub fn precision_grid(options: &TileOptions, extent: u32, z: i32) -> Option<f64> {
+    let places = options.precision?;
+    let grid = WEB_MERCATOR_HALF_WORLD / 180.0 / 10f64.powi(i32::from(places));
+    (grid > mercator_pixel_size(extent, f64::from(z))).then_some(grid)
+}
+
+/// SQL expression projecting the `geom` column from `source_crs` to Web Mercator.
+/// Data already in Web Mercator is used as is, sparing a transform per row.
+pub fn web_mercator_geom_sql(source_crs: &str) -> String {
+    if source_crs.eq_ignore_ascii_case("EPSG:3857") {
+        return "geom".to_string();
+    }
+    format!(
+        "ST_Transform(geom, {}, 'EPSG:3857', always_xy := true)",
+        quote_literal(source_crs)
+    )
+}
+
+/// Bounding-box test of the raw `geom` column against tile `z/x/y`, for source
+/// CRSes whose axes map monotonically onto Web Mercator's, so it never drops a
+/// feature the exact check would keep. The coordinates are inlined so DuckDB can
+/// fold the envelope and answer the test from the R-tree index.
+fn tile_index_prefilter_sql(source_crs: &str, (z, x, y): (i32, i32, i32)) -> Option<String> {
+    let envelope = format!("ST_TileEnvelope({z}, {x}, {y})");
+    match source_crs.to_ascii_uppercase().as_str() {
+        "EPSG:3857" => Some(format!("ST_Intersects_Extent(geom, {envelope})")),
+        "EPSG:4326" | "OGC:CRS84" => Some(format!(
+            "ST_Intersects_Extent(geom, ST_Transform({envelope}, 'EPSG:3857', 'EPSG:4326', always_xy := true))"
+        )),
+        _ => None,
+    }
+}
+
+/// Web Mercator bounds `(xmin, ymax)` and width of tile `z/x/y`.
+pub fn tile_origin_and_size((z, x, y): (i32, i32, i32)) -> (f64, f64, f64) {
+    let size = 2.0 * WEB_MERCATOR_HALF_WORLD / 2f64.powi(z);
+    (
+        -WEB_MERCATOR_HALF_WORLD + f64::from(x) * size,
+        WEB_MERCATOR_HALF_WORLD - f64::from(y) * size,
+        size,
+    )
+}
+
+/// Trailing clauses that cut the features of a view down to `limit` using the
+/// configured strategy. `geom` is the feature geometry in the CRS of `bbox`,
+/// the `[minx, miny, maxx, maxy]` the grid strategy divides into cells.
+pub fn feature_limit_sql(
+    options: &TileOptions,
+    columns: &[DatasetColumn],
+    geom: &str,
+    bbox: [f64; 4],
+    limit: u32,
+) -> String {
+    let order_by = match options.feature_limit_strategy() {
+        FeatureLimitStrategy::Fid | FeatureLimitStrategy::Grid => "fid".to_string(),
+        // Hashing the fid keeps the sample identical across requests and zooms.
+        FeatureLimitStrategy::Random => "hash(fid), fid".to_string(),
+        FeatureLimitStrategy::Sort => {
+            order_by_clause(columns, options.feature_limit_sort.as_deref())
+                .unwrap_or_else(|_| "fid".to_string())
+        }
+    };
+
+    let mut sql = String::new();
+    if options.feature_limit_strategy() == FeatureLimitStrategy::Grid {
+        // A side x side grid has at most `limit` cells; each keeps its lowest fid.
+        let side = f64::from(limit).sqrt().floor().max(1.0);
+        let [xmin, ymin, xmax, ymax] = bbox;
+        let max_index = side - 1.0;
+        let cell_index = |offset: String, cell: f64| {
+            format!("least(greatest(floor({offset} / {cell:?}), 0), {max_index:?})")
+        };
+        let centroid = format!("ST_Centroid({geom})");
+        sql.push_str(&format!(
+            "\n            QUALIFY row_number() OVER (PARTITION BY {}, {} ORDER BY fid) = 1",
+            cell_index(
+                format!("(ST_X({centroid}) - {xmin:?})"),
+                (xmax - xmin) / side
+            ),
+            cell_index(
+                format!("({ymax:?} - ST_Y({centroid}))"),
+                (ymax - ymin) / side
+            ),
+        ));
+    }
+    sql.push_str(&format!("\n            ORDER BY {order_by} LIMIT {limit}"));
+    sql
+}
+
+/// Size in Web Mercator meters of one pixel of a `tile_size`-pixel tile at zoom `z`.
+pub fn mercator_pixel_size(tile_size: u32, z: f64) -> f64 {
+    2.0 * WEB_MERCATOR_HALF_WORLD / (f64::from(tile_size) * 2f64.powf(z))
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn build_mvt_select_sql(
     conn: &Connection,
     source_id: &str,
     table_name: &str,
     source_crs: &str,
+    options: &TileOptions,
+    tile: (i32, i32, i32),
+    filter: Option<&CompiledFilter>,
+    mode: TileMode,
+) -> Result<String, duckdb::Error> {
+    let features = tile_features_sql(
+        conn, source_id, table_name, source_crs, options, tile, filter, mode, true,
+    )?;
+    let layer_name = quote_literal(options.layer_name());
+    let extent = options.extent();
+    Ok(format!(
+        "SELECT ST_AsMVT(feature, {layer_name}, {extent}, 'geom', 'fid') FROM (\n            {features}\n        )"
+    ))
+}
+
+/// Like `build_mvt_select_sql`, but selecting each feature's tile geometry as
+/// GeoJSON in tile pixels of `options.extent()`, one row per feature, for
+/// drawing the tile as an image.
+#[allow(clippy::too_many_arguments)]
+pub fn build_tile_geometry_sql(
+    conn: &Connection,
+    source_id: &str,
+    table_name: &str,
+    source_crs: &str,
+    options: &TileOptions,
+    tile: (i32, i32, i32),
+    filter: Option<&CompiledFilter>,
+    mode: TileMode,
+) -> Result<String, duckdb::Error> {
+    let features = tile_features_sql(
+        conn, source_id, table_name, source_crs, options, tile, filter, mode, false,
+    )?;
+    Ok(format!(
+        "SELECT ST_AsGeoJSON(feature.geom) FROM (\n            {features}\n        ) WHERE feature.geom IS NOT NULL"
+    ))
+}
+
+/// The features of a tile as `struct_pack(geom, fid, properties...)` values
+/// named `feature`, with properties only when `with_properties`.
+#[allow(clippy::too_many_arguments)]
+pub fn tile_features_sql(
+    conn: &Connection,
+    source_id: &str,
+    table_name: &str,
+    source_crs: &str,
+    options: &TileOptions,
+    tile: (i32, i32, i32),
+    filter: Option<&CompiledFilter>,
+    mode: TileMode,
+    with_properties: bool,
 ) -> Result<String, duckdb::Error> {
+    let (z, _, _) = tile;
     // Build property struct keys based on captured column metadata.
     // We keep property keys as original names for UX.
     // Note: We exclude fid + geom.
-    let mut props_stmt = conn.prepare(
-        "SELECT normalized_name, original_name\n         FROM dataset_columns\n         WHERE source_id = ?\n         ORDER BY ordinal",
-    )?;
-    let props_iter = props_stmt.query_map(duckdb::params![source_id], |row| {
-        let normalized: String = row.get(0)?;
-        let original: String = row.get(1)?;
-        Ok((normalized, original))
-    })?;
+    let columns = load_dataset_columns(conn, source_id)?;
+    let properties: Vec<&DatasetColumn> = match &options.fields {
+        _ if !with_properties => Vec::new(),
+        Some(fields) => fields
+            .iter()
+            .filter_map(|field| resolve_column(&columns, field))
+            .collect(),
+        None => columns.iter().collect(),
+    };
+
+    let extent = options.extent();
+    let buffer = options.buffer();
+    let clip = options.clip();
+
+    let mut geom_3857 = web_mercator_geom_sql(source_crs);
+    if let Some(pixels) = options.simplify.filter(|p| *p > 0.0) {
+        // Tolerance in meters: `pixels` tile pixels at this zoom.
+        let tolerance = pixels * mercator_pixel_size(extent, f64::from(z));
+        geom_3857 = format!("ST_SimplifyPreserveTopology({geom_3857}, {tolerance:?})");
+    }
+    if let Some(grid) = precision_grid(options, extent, z) {
+        geom_3857 = format!("ST_ReducePrecision({geom_3857}, {grid:?})");
+    }
 
     let mut struct_fields = Vec::new();
     struct_fields.push(format!(
-        "geom := ST_AsMVTGeom(\n                    ST_Transform(geom, '{source_crs}', 'EPSG:3857', always_xy := true),\n                    ST_Extent(ST_TileEnvelope(?, ?, ?)),\n                    4096, 256, true\n                )"
+        "geom := ST_AsMVTGeom(\n                    {geom_3857},\n                    ST_Extent(ST_TileEnvelope(?, ?, ?)),\n                    {extent}, {buffer}, {clip}\n                )"
     ));
     struct_fields.push("fid := fid".to_string());
 
-    for entry in props_iter {
-        let (normalized, original) = entry?;
-
-        // Use the original column name as the MVT property key.
+    for column in properties {
+        // Use the alias or original column name as the MVT property key.
         // DuckDB `struct_pack` uses identifier keys; quoted identifiers allow spaces/symbols.
-        // Escape embedded double quotes per SQL identifier rules.
-        let key = original.replace('"', "\"\"");
-        struct_fields.push(format!("\"{key}\" := \"{normalized}\""));
+        struct_fields.push(format!(
+            "{} := {}",
+            quote_identifier(options.property_key(&columns, column)),
+            quote_identifier(&column.normalized)
+        ));
     }
 
     let struct_expr = format!(
@@ -39,7 +304,169 @@ pub fn build_mvt_select_sql(
         struct_fields.join(",\n                ")
     );
 
+    let prefilter_clause = tile_index_prefilter_sql(source_crs, tile)
+        .map(|sql| format!("{sql} AND "))
+        .unwrap_or_default();
+    let filter_clause = filter
+        .map(|f| format!(" AND {}", f.sql))
+        .unwrap_or_default();
+    let intersects_geom = web_mercator_geom_sql(source_crs);
+    let where_clause = format!(
+        "{prefilter_clause}ST_Intersects(\n                {intersects_geom},\n                ST_TileEnvelope(?, ?, ?)\n            ){filter_clause}"
+    );
+    if mode == TileMode::Density {
+        // A cell is a fixed share of the tile, so cells of all tiles line up.
+        let cell = tile_origin_and_size(tile).2 / f64::from(DENSITY_GRID_SIZE);
+        return Ok(grid_features_sql(
+            table_name,
+            &intersects_geom,
+            options,
+            &where_clause,
+            cell,
+            CellPoint::Center,
+            with_properties.then_some("weight"),
+        ));
+    }
+    if options.clusters_at(z) {
+        let cell = f64::from(options.cluster_radius()) * mercator_pixel_size(extent, f64::from(z));
+        return Ok(grid_features_sql(
+            table_name,
+            &intersects_geom,
+            options,
+            &where_clause,
+            cell,
+            CellPoint::Mean,
+            with_properties.then_some("point_count"),
+        ));
+    }
+    let limit_clause = options
+        .feature_limit
+        .map(|limit| {
+            let (xmin, ymax, size) = tile_origin_and_size(tile);
+            let bbox = [xmin, ymax - size, xmin + size, ymax];
+            feature_limit_sql(options, &columns, &intersects_geom, bbox, limit)
+        })
+        .unwrap_or_default();
+
     Ok(format!(
-        "SELECT ST_AsMVT(feature, 'layer', 4096, 'geom', 'fid') FROM (\n            SELECT {struct_expr} as feature\n            FROM \"{table_name}\"\n            WHERE ST_Intersects(\n                ST_Transform(geom, '{source_crs}', 'EPSG:3857', always_xy := true),\n                ST_TileEnvelope(?, ?, ?)\n            )\n        )"
+        "SELECT {struct_expr} as feature\n            FROM \"{table_name}\"\n            WHERE {where_clause}{limit_clause}"
     ))
 }
+
+/// Where `grid_features_sql` puts the point standing for a cell.
+enum CellPoint {
+    /// The mean position of the cell's features.
+    Mean,
+    /// The middle of the cell.
+    Center,
+}
+
+/// One point per occupied cell of a `cell`-meter Web Mercator grid aligned on
+/// the world, in place of the features: point clusters and density tiles.
+/// The point takes the lowest fid of its features as id and, when
+/// `count_property` is given, their number under that name. Features count
+/// by their centroid; the `feature_limit` does not apply.
+fn grid_features_sql(
+    table_name: &str,
+    geom_3857: &str,
+    options: &TileOptions,
+    where_clause: &str,
+    cell: f64,
+    point: CellPoint,
+    count_property: Option<&str>,
+) -> String {
+    let extent = options.extent();
+    let buffer = options.buffer();
+    let clip = options.clip();
+    let point = match point {
+        CellPoint::Mean => "ST_Point(avg(x), avg(y))".to_string(),
+        CellPoint::Center => format!(
+            "ST_Point((cx + 0.5) * {cell:?} - {WEB_MERCATOR_HALF_WORLD:?}, (cy + 0.5) * {cell:?} - {WEB_MERCATOR_HALF_WORLD:?})"
+        ),
+    };
+    let count = count_property
+        .map(|name| format!(",\n                {} := count(*)", quote_identifier(name)))
+        .unwrap_or_default();
+    format!(
+        "SELECT struct_pack(
+                geom := ST_AsMVTGeom(
+                    {point},
+                    ST_Extent(ST_TileEnvelope(?, ?, ?)),
+                    {extent}, {buffer}, {clip}
+                ),
+                fid := min(fid){count}
+            ) as feature
+            FROM (
+                SELECT fid, x, y,
+                    floor((x + {WEB_MERCATOR_HALF_WORLD:?}) / {cell:?}) AS cx,
+                    floor((y + {WEB_MERCATOR_HALF_WORLD:?}) / {cell:?}) AS cy
+                FROM (
+                    SELECT fid, ST_X(ST_Centroid({geom_3857})) AS x, ST_Y(ST_Centroid({geom_3857})) AS y
+                    FROM \"{table_name}\"
+                    WHERE {where_clause}
+                )
+            )
+            GROUP BY cx, cy"
+    )
+}
+
+/// Run one `build_mvt_select_sql` query per layer and concatenate the
+/// encoded layers into a tile.
+///
+/// Tile queries are most of the traffic and can take seconds on large
+/// datasets. DuckDB calls block their thread, so they run on the blocking pool
+/// with the request's read connection instead of on an async worker.
+pub async fn encode_tile(
+    conn: ReadConnection,
+    layers: Vec<String>,
+    params: Vec<Value>,
+) -> Result<Vec<u8>, duckdb::Error> {
+    conn.run(move |conn| {
+        let mut tile = Vec::new();
+        for sql in &layers {
+            let layer: Option<Vec<u8>> =
+                conn.query_row(sql, duckdb::params_from_iter(params.iter()), |row| {
+                    row.get(0)
+                })?;
+            tile.extend(layer.unwrap_or_default());
+        }
+        Ok(tile)
+    })
+    .await
+}
+
+/// Size in pixels of the PNG tiles drawn by `draw_tile`.
+pub const PNG_TILE_SIZE: u32 = 256;
+
+/// Run one `build_tile_geometry_sql` query per layer, each with its tile
+/// extent, and draw the layers in palette order onto a transparent PNG tile.
+pub async fn draw_tile(
+    conn: ReadConnection,
+    layers: Vec<(String, u32)>,
+    params: Vec<Value>,
+) -> Result<Vec<u8>, String> {
+    conn.run(move |conn| {
+        let mut canvas = Canvas::new(PNG_TILE_SIZE, PNG_TILE_SIZE, None)
+            .ok_or_else(|| "Invalid tile size".to_string())?;
+        for (index, (sql, extent)) in layers.iter().enumerate() {
+            let mut stmt = conn.prepare(sql).map_err(|e| e.to_string())?;
+            let geometries = stmt
+                .query_map(duckdb::params_from_iter(params.iter()), |row| {
+                    row.get::<_, String>(0)
+                })
+                .map_err(|e| e.to_string())?
+                .map(|geojson| {
+                    let geojson = geojson.map_err(|e| e.to_string())?;
+                    serde_json::from_str(&geojson).map_err(|e| e.to_string())
+                })
+                .collect::<Result<Vec<serde_json::Value>, String>>()?;
+            let frame = Frame {
+                bbox: [0.0, 0.0, f64::from(*extent), f64::from(*extent)],
+                y_down: true,
+            };
+            canvas.draw_layer(&frame, &geometries, layer_color(index));
+        }
+        canvas.encode_png()
+    })
+    .await
+}
@@ -0,0 +1,147 @@
+//! Composite tilesets
+//!
+//! A tileset publishes several ready datasets under one slug. Each tile is the
+//! concatenation of one `ST_AsMVT` per source: layers are a repeated protobuf
+//! field, so concatenated single-layer tiles decode as one multi-layer tile.
+//! Sources keep their own tile options; their layer names must be distinct.
+
+use std::collections::HashSet;
+
+use crate::models::TileOptions;
+use crate::tile_options::load_render_options;
+
+pub const MAX_TILESET_SOURCES: usize = 16;
+
+/// A dataset rendered as one layer of a tileset, in tileset order.
+#[derive(Debug)]
+pub struct TilesetSource {
+    pub file_id: String,
+    pub table_name: String,
+    pub crs: String,
+    pub options: TileOptions,
+}
+
+/// Check the requested source list before any dataset is looked up.
+pub fn validate_tileset_files(files: &[String]) -> Result<(), String> {
+    if files.is_empty() {
+        return Err("A tileset needs at least one file".to_string());
+    }
+    if files.len() > MAX_TILESET_SOURCES {
+        return Err(format!(
+            "A tileset can combine at most {MAX_TILESET_SOURCES} files"
+        ));
+    }
+    let mut seen = HashSet::new();
+    for file_id in files {
+        if !seen.insert(file_id.as_str()) {
+            return Err(format!("File '{file_id}' is listed more than once"));
+        }
+    }
+    Ok(())
+}
+
+/// Every source must encode into its own layer.
+pub fn check_layer_names(sources: &[TilesetSource]) -> Result<(), String> {
+    let mut seen = HashSet::new();
+    for source in sources {
+        let layer_name = source.options.layer_name();
+        if !seen.insert(layer_name) {
+            return Err(format!(
+                "Layer name '{layer_name}' is used by more than one file; set a distinct layerName in their tile options"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Whether `slug` is taken by a published dataset or another tileset.
+pub fn slug_in_use(conn: &duckdb::Connection, slug: &str) -> Result<bool, duckdb::Error> {
+    let count: i64 = conn.query_row(
+        "SELECT (SELECT COUNT(*) FROM published_files WHERE slug = ?)
+              + (SELECT COUNT(*) FROM tilesets WHERE slug = ?)",
+        duckdb::params![slug, slug],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// File ids of a tileset in layer order.
+pub fn load_tileset_files(
+    conn: &duckdb::Connection,
+    tileset_id: &str,
+) -> Result<Vec<String>, duckdb::Error> {
+    let mut stmt =
+        conn.prepare("SELECT file_id FROM tileset_sources WHERE tileset_id = ? ORDER BY ordinal")?;
+    let files = stmt
+        .query_map(duckdb::params![tileset_id], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    Ok(files)
+}
+
+/// Sources of a tileset with their render options. Sources that are no longer
+/// ready dynamic datasets are skipped.
+pub fn load_tileset_sources(
+    conn: &duckdb::Connection,
+    tileset_id: &str,
+) -> Result<Vec<TilesetSource>, duckdb::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT f.id, f.table_name, f.crs
+         FROM tileset_sources s JOIN files f ON f.id = s.file_id
+         WHERE s.tileset_id = ? AND f.status = 'ready'
+           AND f.tile_format IS NULL AND f.table_name IS NOT NULL
+         ORDER BY s.ordinal",
+    )?;
+    let rows: Vec<(String, String, Option<String>)> = stmt
+        .query_map(duckdb::params![tileset_id], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })?
+        .collect::<Result<_, _>>()?;
+
+    let mut sources = Vec::with_capacity(rows.len());
+    for (file_id, table_name, crs) in rows {
+        let options = load_render_options(conn, &file_id)?.unwrap_or_default();
+        sources.push(TilesetSource {
+            file_id,
+            table_name,
+            crs: crs.unwrap_or_else(|| "EPSG:4326".to_string()),
+            options,
+        });
+    }
+    Ok(sources)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(layer_name: &str) -> TilesetSource {
+        TilesetSource {
+            file_id: layer_name.to_string(),
+            table_name: format!("layer_{layer_name}"),
+            crs: "EPSG:4326".to_string(),
+            options: TileOptions {
+                layer_name: Some(layer_name.to_string()),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn tileset_files_must_be_unique_and_bounded() {
+        let files = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        assert_eq!(validate_tileset_files(&files(&["a", "b"])), Ok(()));
+        assert!(validate_tileset_files(&[]).is_err());
+        assert!(validate_tileset_files(&files(&["a", "a"])).is_err());
+        let too_many: Vec<String> = (0..=MAX_TILESET_SOURCES).map(|i| i.to_string()).collect();
+        assert!(validate_tileset_files(&too_many).is_err());
+    }
+
+    #[test]
+    fn layer_names_must_be_distinct() {
+        assert_eq!(
+            check_layer_names(&[source("roads"), source("parks")]),
+            Ok(())
+        );
+        assert!(check_layer_names(&[source("roads"), source("roads")]).is_err());
+    }
+}
@@ -0,0 +1,245 @@
+//! User management
+//!
+//! Admin-only endpoints to list, create and re-role accounts. The first admin
+//! is still created through `/api/auth/init`. An admin cannot demote or delete
+//! their own account, so the instance always keeps at least one admin. When an
+//! admin enables registration in the settings, anyone can sign up as a viewer.
+
+use axum::{
+    extract::{Path as AxumPath, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{get, patch, post},
+    Json, Router,
+};
+use axum_login::AuthSession;
+use chrono::Utc;
+
+use crate::auth::{AuthBackend, Role};
+use crate::http_errors::{bad_request, internal_error};
+use crate::models::{CreateUserRequest, RegisterRequest, UpdateUserRequest, UserItem};
+use crate::settings::load_settings;
+use crate::{AppState, ErrorResponse};
+
+const MAX_USERNAME_LENGTH: usize = 64;
+
+pub fn build_users_router() -> Router<AppState> {
+    Router::new()
+        .route("/api/users", get(list_users).post(create_user))
+        .route("/api/users/{id}", patch(update_user).delete(delete_user))
+}
+
+/// Self-service sign-up, open only while registration is enabled in the
+/// runtime settings.
+pub fn build_registration_router() -> Router<AppState> {
+    Router::new().route("/api/auth/register", post(register))
+}
+
+fn validate_username(username: &str) -> Result<String, String> {
+    let username = username.trim();
+    if username.is_empty() {
+        return Err("Username cannot be empty".to_string());
+    }
+    if username.chars().count() > MAX_USERNAME_LENGTH {
+        return Err(format!(
+            "Username must be {MAX_USERNAME_LENGTH} characters or less"
+        ));
+    }
+    Ok(username.to_string())
+}
+
+fn user_not_found() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse {
+            error: "User not found".to_string(),
+        }),
+    )
+}
+
+fn load_user(conn: &duckdb::Connection, id: &str) -> Result<Option<UserItem>, duckdb::Error> {
+    use duckdb::OptionalExt;
+
+    conn.query_row(
+        "SELECT id, username, role, created_at FROM users WHERE id = ?",
+        duckdb::params![id],
+        user_from_row,
+    )
+    .optional()
+}
+
+fn user_from_row(row: &duckdb::Row<'_>) -> Result<UserItem, duckdb::Error> {
+    let role: String = row.get(2)?;
+    let created_at: chrono::NaiveDateTime = row.get(3)?;
+    Ok(UserItem {
+        id: row.get(0)?,
+        username: row.get(1)?,
+        role: Role::parse(&role).unwrap_or(Role::Viewer),
+        created_at: created_at.and_utc().to_rfc3339(),
+    })
+}
+
+async fn list_users(
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.db.lock().await;
+    let mut stmt = conn
+        .prepare("SELECT id, username, role, created_at FROM users ORDER BY created_at, username")
+        .map_err(internal_error)?;
+    let users: Vec<UserItem> = stmt
+        .query_map([], user_from_row)
+        .map_err(internal_error)?
+        .collect::<Result<_, _>>()
+        .map_err(internal_error)?;
+    Ok(Json(users))
+}
+
+async fn create_user(
+    State(state): State<AppState>,
+    Json(req): Json<CreateUserRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let user = insert_user(&state, &req.username, &req.password, req.role, |_| Ok(())).await?;
+    Ok((StatusCode::CREATED, Json(user)))
+}
+
+async fn register(
+    State(state): State<AppState>,
+    Json(req): Json<RegisterRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let user = insert_user(&state, &req.username, &req.password, Role::Viewer, |conn| {
+        let settings = load_settings(conn, &state).map_err(internal_error)?;
+        if settings.registration_enabled {
+            Ok(())
+        } else {
+            Err((
+                StatusCode::FORBIDDEN,
+                Json(ErrorResponse {
+                    error: "Registration is disabled".to_string(),
+                }),
+            ))
+        }
+    })
+    .await?;
+    Ok((StatusCode::CREATED, Json(user)))
+}
+
+/// Validate and create an account. `check` runs under the database lock before
+/// anything is written.
+pub(crate) async fn insert_user(
+    state: &AppState,
+    username: &str,
+    password: &str,
+    role: Role,
+    check: impl FnOnce(&duckdb::Connection) -> Result<(), (StatusCode, Json<ErrorResponse>)>,
+) -> Result<UserItem, (StatusCode, Json<ErrorResponse>)> {
+    let username = validate_username(username).map_err(|e| bad_request(&e))?;
+    crate::validate_password_complexity(password)
+        .map_err(|e| bad_request(&format!("Invalid password: {e}")))?;
+    // Hash before taking the lock: bcrypt is deliberately slow.
+    let password_hash = crate::hash_password(password).map_err(internal_error)?;
+
+    let conn = state.db.lock().await;
+    check(&conn)?;
+    let exists: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM users WHERE username = ?",
+            duckdb::params![&username],
+            |row| row.get(0),
+        )
+        .map_err(internal_error)?;
+    if exists > 0 {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(ErrorResponse {
+                error: format!("Username '{username}' already exists"),
+            }),
+        ));
+    }
+
+    let id = uuid::Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO users (id, username, password_hash, role, created_at) VALUES (?, ?, ?, ?, ?)",
+        duckdb::params![
+            &id,
+            &username,
+            &password_hash,
+            role.as_str(),
+            Utc::now().naive_utc()
+        ],
+    )
+    .map_err(internal_error)?;
+    load_user(&conn, &id)
+        .map_err(internal_error)?
+        .ok_or_else(user_not_found)
+}
+
+async fn update_user(
+    State(state): State<AppState>,
+    auth_session: AuthSession<AuthBackend>,
+    AxumPath(id): AxumPath<String>,
+    Json(req): Json<UpdateUserRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    if auth_session.user.is_some_and(|me| me.id == id) && req.role != Role::Admin {
+        return Err(bad_request("You cannot remove your own admin role"));
+    }
+
+    let conn = state.db.lock().await;
+    let updated = conn
+        .execute(
+            "UPDATE users SET role = ? WHERE id = ?",
+            duckdb::params![req.role.as_str(), &id],
+        )
+        .map_err(internal_error)?;
+    if updated == 0 {
+        return Err(user_not_found());
+    }
+    let user = load_user(&conn, &id)
+        .map_err(internal_error)?
+        .ok_or_else(user_not_found)?;
+
+    Ok(Json(user))
+}
+
+async fn delete_user(
+    State(state): State<AppState>,
+    auth_session: AuthSession<AuthBackend>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    if auth_session.user.is_some_and(|me| me.id == id) {
+        return Err(bad_request("You cannot delete your own account"));
+    }
+
+    let conn = state.db.lock().await;
+    let deleted = conn
+        .execute("DELETE FROM users WHERE id = ?", duckdb::params![&id])
+        .map_err(internal_error)?;
+    if deleted == 0 {
+        return Err(user_not_found());
+    }
+    for table in [
+        "file_shares",
+        "org_members",
+        "api_tokens",
+        "password_reset_tokens",
+    ] {
+        conn.execute(
+            &format!("DELETE FROM {table} WHERE user_id = ?"),
+            duckdb::params![&id],
+        )
+        .map_err(internal_error)?;
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usernames_are_trimmed_and_bounded() {
+        assert_eq!(validate_username("  alice "), Ok("alice".to_string()));
+        assert!(validate_username("   ").is_err());
+        assert!(validate_username(&"a".repeat(MAX_USERNAME_LENGTH + 1)).is_err());
+    }
+}
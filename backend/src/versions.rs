@@ -0,0 +1,468 @@
+//! Dataset versions
+//!
+//! Importing into a dataset again (refreshing a URL import) used to drop its
+//! table and load the new data in its place, and appends and attribute updates
+//! changed it in place. Now the table being replaced or changed is
+//! first copied to `<table>_v<n>` and kept as version `n`, with the CRS,
+//! feature count and columns it had; its columns are recorded under the copy's
+//! table name. The dataset's own table is always the current version, one past
+//! the newest retained one. Retained versions are never written to again.
+//!
+//! `GET /api/files/{id}/versions` lists a dataset's versions, and a publish
+//! can pin one with `version`; unpinned publishes follow the current data.
+//!
+//! Only the newest `datasetVersionRetention` retained versions are kept; older
+//! ones are dropped when the next is retained, in the same transaction, unless
+//! a publish pins them.
+
+use axum::{
+    extract::{Path as AxumPath, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{NaiveDateTime, Utc};
+use duckdb::OptionalExt;
+
+use crate::columns::quote_identifier;
+use crate::http_errors::internal_error;
+use crate::models::DatasetVersion;
+use crate::settings::dataset_version_retention;
+use crate::spatial_index::create_spatial_index;
+use crate::{AppState, ErrorResponse};
+
+/// Retained versions kept per dataset unless an admin sets
+/// `datasetVersionRetention`.
+pub const DEFAULT_DATASET_VERSION_RETENTION: u64 = 10;
+pub const MAX_DATASET_VERSION_RETENTION: u64 = 1000;
+
+/// A retained version's table, which also keys its columns, and its CRS.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetainedVersion {
+    pub table_name: String,
+    pub crs: Option<String>,
+}
+
+fn file_not_found() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse {
+            error: "File not found".to_string(),
+        }),
+    )
+}
+
+/// A dataset's retained versions, oldest first, then its current one.
+pub async fn list_dataset_versions(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    let file: Option<(NaiveDateTime, Option<i64>)> = conn
+        .query_row(
+            "SELECT uploaded_at, feature_count FROM files WHERE id = ?",
+            duckdb::params![&id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(internal_error)?;
+    let (uploaded_at, feature_count) = file.ok_or_else(file_not_found)?;
+    let mut versions = load_retained_versions(&conn, &id).map_err(internal_error)?;
+
+    // A failed import still has a current version, without a feature count
+    // when it never loaded any data.
+    let (version, created_at) = match versions.last() {
+        Some(last) => (
+            last.version + 1,
+            last.replaced_at.clone().unwrap_or_default(),
+        ),
+        None => (1, uploaded_at.and_utc().to_rfc3339()),
+    };
+    versions.push(DatasetVersion {
+        version,
+        created_at,
+        replaced_at: None,
+        feature_count,
+        current: true,
+    });
+    Ok(Json(versions))
+}
+
+fn load_retained_versions(
+    conn: &duckdb::Connection,
+    file_id: &str,
+) -> Result<Vec<DatasetVersion>, duckdb::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT version, created_at, replaced_at, feature_count
+         FROM dataset_versions WHERE file_id = ? ORDER BY version",
+    )?;
+    let versions = stmt.query_map(duckdb::params![file_id], |row| {
+        let created_at: NaiveDateTime = row.get(1)?;
+        let replaced_at: NaiveDateTime = row.get(2)?;
+        Ok(DatasetVersion {
+            version: row.get(0)?,
+            created_at: created_at.and_utc().to_rfc3339(),
+            replaced_at: Some(replaced_at.and_utc().to_rfc3339()),
+            feature_count: row.get(3)?,
+            current: false,
+        })
+    })?;
+    versions.collect()
+}
+
+/// The number of a dataset's current version.
+pub fn current_version(conn: &duckdb::Connection, file_id: &str) -> Result<i32, duckdb::Error> {
+    conn.query_row(
+        "SELECT (COALESCE(MAX(version), 0) + 1)::INTEGER FROM dataset_versions WHERE file_id = ?",
+        duckdb::params![file_id],
+        |row| row.get(0),
+    )
+}
+
+/// The table of version `version` of a dataset if it is a retained one; `None`
+/// when no version is given or it is still the current one.
+pub fn retained_version(
+    conn: &duckdb::Connection,
+    file_id: &str,
+    version: Option<i32>,
+) -> Result<Option<RetainedVersion>, duckdb::Error> {
+    let Some(version) = version else {
+        return Ok(None);
+    };
+    conn.query_row(
+        "SELECT table_name, crs FROM dataset_versions WHERE file_id = ? AND version = ?",
+        duckdb::params![file_id, version],
+        |row| {
+            Ok(RetainedVersion {
+                table_name: row.get(0)?,
+                crs: row.get(1)?,
+            })
+        },
+    )
+    .optional()
+}
+
+/// Keep the table a new import of `file_id` is about to replace as its next
+/// retained version, dropping the versions past `datasetVersionRetention` in
+/// the same transaction. Tables of imports that never finished, which have no
+/// feature count, are not kept. Returns the new version's number.
+pub fn retain_current_version(
+    conn: &duckdb::Connection,
+    file_id: &str,
+) -> Result<Option<i32>, duckdb::Error> {
+    let table_name: Option<String> = conn
+        .query_row(
+            "SELECT f.table_name FROM files f
+             JOIN duckdb_tables() t
+               ON t.table_name = f.table_name
+              AND t.schema_name = 'main'
+              AND t.database_name = current_database()
+             WHERE f.id = ? AND f.feature_count IS NOT NULL",
+            duckdb::params![file_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let Some(table_name) = table_name else {
+        return Ok(None);
+    };
+
+    let keep = dataset_version_retention(conn)?;
+    let version = current_version(conn, file_id)?;
+    let retained = format!("{table_name}_v{version}");
+    conn.execute_batch("BEGIN TRANSACTION")?;
+    let result = copy_version(conn, file_id, &table_name, &retained, version)
+        .and_then(|()| prune_versions(conn, file_id, keep));
+    if let Err(e) = result {
+        let _ = conn.execute_batch("ROLLBACK");
+        return Err(e);
+    }
+    conn.execute_batch("COMMIT")?;
+    // Outside the transaction: a failed index must not undo the copy.
+    if let Err(e) = create_spatial_index(conn, &retained) {
+        tracing::warn!(table = %retained, error = %e, "Failed to create spatial index");
+    }
+    Ok(Some(version))
+}
+
+/// Copy `table_name` to `retained` and record it, with its columns, as
+/// version `version` of `file_id`.
+fn copy_version(
+    conn: &duckdb::Connection,
+    file_id: &str,
+    table_name: &str,
+    retained: &str,
+    version: i32,
+) -> Result<(), duckdb::Error> {
+    conn.execute_batch(&format!(
+        "CREATE OR REPLACE TABLE {} AS SELECT * FROM {}",
+        quote_identifier(retained),
+        quote_identifier(table_name)
+    ))?;
+    // The current version began when the one before it was replaced.
+    conn.execute(
+        "INSERT INTO dataset_versions
+             (file_id, version, table_name, crs, feature_count, created_at, replaced_at)
+         SELECT f.id, ?, ?, f.crs, f.feature_count,
+                COALESCE(
+                    (SELECT MAX(v.replaced_at) FROM dataset_versions v WHERE v.file_id = f.id),
+                    f.uploaded_at
+                ),
+                ?
+         FROM files f WHERE f.id = ?",
+        duckdb::params![version, retained, Utc::now().naive_utc(), file_id],
+    )?;
+    conn.execute(
+        "DELETE FROM dataset_columns WHERE source_id = ?",
+        duckdb::params![retained],
+    )?;
+    conn.execute(
+        "INSERT INTO dataset_columns (source_id, normalized_name, original_name, ordinal, mvt_type)
+         SELECT ?, normalized_name, original_name, ordinal, mvt_type
+         FROM dataset_columns WHERE source_id = ?",
+        duckdb::params![retained, file_id],
+    )?;
+    Ok(())
+}
+
+/// Drop `file_id`'s retained versions older than its newest `keep`, except
+/// the one its publish pins. A `keep` of 0 keeps them all.
+fn prune_versions(
+    conn: &duckdb::Connection,
+    file_id: &str,
+    keep: u64,
+) -> Result<(), duckdb::Error> {
+    if keep == 0 {
+        return Ok(());
+    }
+    let tables: Vec<String> = {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT table_name FROM (
+                 SELECT version, table_name FROM dataset_versions
+                 WHERE file_id = ? ORDER BY version DESC OFFSET {keep}
+             ) old
+             WHERE old.version NOT IN (
+                 SELECT version FROM published_files
+                 WHERE file_id = ? AND version IS NOT NULL
+             )"
+        ))?;
+        let tables = stmt.query_map(duckdb::params![file_id, file_id], |row| row.get(0))?;
+        tables.collect::<Result<_, _>>()?
+    };
+    for table in &tables {
+        drop_version_table(conn, table)?;
+        conn.execute(
+            "DELETE FROM dataset_versions WHERE file_id = ? AND table_name = ?",
+            duckdb::params![file_id, table],
+        )?;
+    }
+    Ok(())
+}
+
+/// Drop a retained version's table and its columns.
+fn drop_version_table(conn: &duckdb::Connection, table: &str) -> Result<(), duckdb::Error> {
+    conn.execute_batch(&format!("DROP TABLE IF EXISTS {}", quote_identifier(table)))?;
+    conn.execute(
+        "DELETE FROM dataset_columns WHERE source_id = ?",
+        duckdb::params![table],
+    )?;
+    Ok(())
+}
+
+/// Undo `retain_current_version` after the import that replaced the data
+/// failed: version `version` becomes the current data again, with its CRS,
+/// columns and feature count. The import failed before recording new stats.
+pub fn restore_retained_version(
+    conn: &duckdb::Connection,
+    file_id: &str,
+    version: i32,
+) -> Result<(), duckdb::Error> {
+    let Some(retained) = retained_version(conn, file_id, Some(version))? else {
+        return Ok(());
+    };
+    let suffix = format!("_v{version}");
+    let table_name = retained
+        .table_name
+        .strip_suffix(&suffix)
+        .unwrap_or(&retained.table_name)
+        .to_string();
+    conn.execute_batch(&format!(
+        "CREATE OR REPLACE TABLE {table} AS SELECT * FROM {retained};
+         DROP TABLE {retained};",
+        table = quote_identifier(&table_name),
+        retained = quote_identifier(&retained.table_name)
+    ))?;
+    conn.execute(
+        "DELETE FROM dataset_columns WHERE source_id = ?",
+        duckdb::params![file_id],
+    )?;
+    conn.execute(
+        "UPDATE dataset_columns SET source_id = ? WHERE source_id = ?",
+        duckdb::params![file_id, &retained.table_name],
+    )?;
+    conn.execute(
+        "UPDATE files SET table_name = ?, crs = ?,
+             feature_count = (SELECT feature_count FROM dataset_versions
+                              WHERE file_id = ? AND version = ?)
+         WHERE id = ?",
+        duckdb::params![&table_name, &retained.crs, file_id, version, file_id],
+    )?;
+    conn.execute(
+        "DELETE FROM dataset_versions WHERE file_id = ? AND version = ?",
+        duckdb::params![file_id, version],
+    )?;
+    if let Err(e) = create_spatial_index(conn, &table_name) {
+        tracing::warn!(table = %table_name, error = %e, "Failed to create spatial index");
+    }
+    Ok(())
+}
+
+/// Drop a dataset's retained versions and their columns.
+pub fn drop_retained_versions(
+    conn: &duckdb::Connection,
+    file_id: &str,
+) -> Result<(), duckdb::Error> {
+    let tables: Vec<String> = {
+        let mut stmt = conn.prepare("SELECT table_name FROM dataset_versions WHERE file_id = ?")?;
+        let tables = stmt.query_map(duckdb::params![file_id], |row| row.get(0))?;
+        tables.collect::<Result<_, _>>()?
+    };
+    for table in &tables {
+        drop_version_table(conn, table)?;
+    }
+    conn.execute(
+        "DELETE FROM dataset_versions WHERE file_id = ?",
+        duckdb::params![file_id],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations::migrate;
+
+    fn dataset(conn: &duckdb::Connection, id: &str, rows: i64) {
+        let table = format!("layer_{id}");
+        conn.execute_batch(&format!(
+            "CREATE OR REPLACE TABLE {table} AS SELECT range::BIGINT AS fid FROM range({rows});
+             DELETE FROM dataset_columns WHERE source_id = '{id}';
+             INSERT INTO dataset_columns VALUES ('{id}', 'name', 'Name', 1, 'string');"
+        ))
+        .unwrap();
+        conn.execute(
+            "UPDATE files SET table_name = ?, feature_count = ? WHERE id = ?",
+            duckdb::params![table, rows, id],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn replaced_tables_are_kept_as_numbered_versions() {
+        let conn = duckdb::Connection::open_in_memory().unwrap();
+        migrate(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO files (id, name, size, uploaded_at, status, path, type, crs)
+             VALUES ('a', 'a', 0, TIMESTAMP '2026-01-01 00:00:00', 'ready', 'a.geojson', 'geojson', 'EPSG:4326')",
+        )
+        .unwrap();
+
+        // Nothing to keep before the first import.
+        assert_eq!(retain_current_version(&conn, "a").unwrap(), None);
+        dataset(&conn, "a", 3);
+        assert_eq!(retain_current_version(&conn, "a").unwrap(), Some(1));
+        dataset(&conn, "a", 5);
+        assert_eq!(retain_current_version(&conn, "a").unwrap(), Some(2));
+        assert_eq!(current_version(&conn, "a").unwrap(), 3);
+
+        let first = retained_version(&conn, "a", Some(1)).unwrap().unwrap();
+        assert_eq!(first.table_name, "layer_a_v1");
+        assert_eq!(first.crs.as_deref(), Some("EPSG:4326"));
+        let rows: i64 = conn
+            .query_row("SELECT COUNT(*) FROM layer_a_v1", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(rows, 3);
+        let columns: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM dataset_columns WHERE source_id = 'layer_a_v1'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(columns, 1);
+        assert_eq!(retained_version(&conn, "a", Some(3)).unwrap(), None);
+        assert_eq!(retained_version(&conn, "a", None).unwrap(), None);
+
+        let versions = load_retained_versions(&conn, "a").unwrap();
+        assert_eq!(versions[0].created_at, "2026-01-01T00:00:00+00:00");
+        assert_eq!(
+            versions[1].created_at,
+            versions[0].replaced_at.clone().unwrap()
+        );
+        assert_eq!(versions[1].feature_count, Some(5));
+
+        // A failed import of version 3 puts version 2 back.
+        dataset(&conn, "a", 0);
+        restore_retained_version(&conn, "a", 2).unwrap();
+        assert_eq!(current_version(&conn, "a").unwrap(), 2);
+        let (table, count): (String, i64) = conn
+            .query_row(
+                "SELECT table_name, feature_count FROM files WHERE id = 'a'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((table.as_str(), count), ("layer_a", 5));
+        assert_eq!(retain_current_version(&conn, "a").unwrap(), Some(2));
+
+        drop_retained_versions(&conn, "a").unwrap();
+        assert_eq!(current_version(&conn, "a").unwrap(), 1);
+        let tables: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM duckdb_tables() WHERE table_name LIKE 'layer_a_v%'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(tables, 0);
+    }
+
+    #[test]
+    fn only_the_newest_and_pinned_versions_are_kept() {
+        let conn = duckdb::Connection::open_in_memory().unwrap();
+        migrate(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO files (id, name, size, uploaded_at, status, path, type)
+             VALUES ('a', 'a', 0, TIMESTAMP '2026-01-01 00:00:00', 'ready', 'a.geojson', 'geojson');
+             INSERT INTO system_settings (key, value) VALUES ('dataset_version_retention', '2');",
+        )
+        .unwrap();
+        dataset(&conn, "a", 1);
+        assert_eq!(retain_current_version(&conn, "a").unwrap(), Some(1));
+        conn.execute_batch(
+            "INSERT INTO published_files (file_id, slug, version) VALUES ('a', 'a', 1)",
+        )
+        .unwrap();
+        for rows in 2..=4 {
+            dataset(&conn, "a", rows);
+            retain_current_version(&conn, "a").unwrap();
+        }
+
+        let kept: Vec<i32> = load_retained_versions(&conn, "a")
+            .unwrap()
+            .iter()
+            .map(|v| v.version)
+            .collect();
+        assert_eq!(kept, vec![1, 3, 4]);
+        assert_eq!(current_version(&conn, "a").unwrap(), 5);
+        let (tables, columns): (i64, i64) = conn
+            .query_row(
+                "SELECT
+                     (SELECT COUNT(*) FROM duckdb_tables() WHERE table_name = 'layer_a_v2'),
+                     (SELECT COUNT(*) FROM dataset_columns WHERE source_id = 'layer_a_v2')",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((tables, columns), (0, 0));
+    }
+}
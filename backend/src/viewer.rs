@@ -0,0 +1,117 @@
+//! Shareable map viewer for public slugs
+//!
+//! `/view/:slug` serves a standalone page that loads a pinned MapLibre build
+//! from a CDN, renders the slug's generated `style.json` and fits the map to
+//! its bounds. The page's query string is passed on, so signed links keep
+//! working. With a public base URL configured the style is loaded from there.
+//!
+//! The page runs no inline script: the slug and base URL are data attributes
+//! read by `/view/viewer.js`, so its Content-Security-Policy only allows that
+//! script and the pinned MapLibre files.
+
+use duckdb::OptionalExt;
+
+const VIEWER_TEMPLATE: &str = include_str!("../assets/viewer.html");
+pub const VIEWER_SCRIPT: &str = include_str!("../assets/viewer.js");
+
+const MAPLIBRE_JS: &str = "https://unpkg.com/maplibre-gl@4.7.1/dist/maplibre-gl.js";
+const MAPLIBRE_CSS: &str = "https://unpkg.com/maplibre-gl@4.7.1/dist/maplibre-gl.css";
+
+/// What the viewer needs to know about a public slug.
+pub struct PublicSlug {
+    /// The tileset's name, or the published dataset's.
+    pub name: String,
+    pub signing_secret: Option<String>,
+    pub expires_at: Option<chrono::NaiveDateTime>,
+}
+
+/// `None` when nothing is served under the slug. Expired links are still
+/// returned (they may already be private) so the caller can answer 410.
+pub fn public_slug_name(
+    conn: &duckdb::Connection,
+    slug: &str,
+) -> Result<Option<PublicSlug>, duckdb::Error> {
+    conn.query_row(
+        "SELECT name, NULL, NULL FROM tilesets WHERE slug = ?
+         UNION ALL
+         SELECT f.name, p.signing_secret, p.expires_at
+         FROM published_files p JOIN files f ON f.id = p.file_id
+         WHERE p.slug = ? AND (f.is_public = TRUE OR p.expires_at IS NOT NULL)
+         LIMIT 1",
+        duckdb::params![slug, slug],
+        |row| {
+            Ok(PublicSlug {
+                name: row.get(0)?,
+                signing_secret: row.get(1)?,
+                expires_at: row.get(2)?,
+            })
+        },
+    )
+    .optional()
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub fn render_viewer_page(title: &str, slug: &str, base_url: &str) -> String {
+    VIEWER_TEMPLATE
+        .replace("{{title}}", &escape_html(title))
+        .replace("{{slug}}", &escape_html(slug))
+        .replace("{{base_url}}", &escape_html(base_url))
+}
+
+/// Content-Security-Policy of the viewer page. Styles and tiles are fetched
+/// from `base_url` when one is configured.
+pub fn viewer_csp(base_url: &str) -> String {
+    let data = if base_url.is_empty() {
+        "'self'".to_string()
+    } else {
+        format!("'self' {base_url}")
+    };
+    format!(
+        "default-src 'none'; script-src 'self' {MAPLIBRE_JS}; \
+         style-src 'unsafe-inline' {MAPLIBRE_CSS}; worker-src blob:; \
+         connect-src {data}; img-src data: blob: {data}; base-uri 'none'; form-action 'none'"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn page_embeds_escaped_title_and_slug() {
+        let page = render_viewer_page("Roads & <Rails>", "roads", "");
+        assert!(page.contains("<title>Roads &amp; &lt;Rails&gt;</title>"));
+        assert!(page.contains(r#"data-slug="roads" data-base-url="""#));
+        assert!(!page.contains("{{"));
+
+        let page = render_viewer_page("x", "\"><script>", "https://maps.example.com");
+        assert!(page.contains(r#"data-base-url="https://maps.example.com""#));
+        assert!(page.contains(r#"data-slug="&quot;&gt;&lt;script&gt;""#));
+    }
+
+    #[test]
+    fn page_runs_only_allowed_scripts() {
+        let page = render_viewer_page("x", "roads", "");
+        assert!(!page.contains("<script>"));
+        let csp = viewer_csp("");
+        assert!(csp.starts_with("default-src 'none';"));
+        assert!(csp.contains(&format!("script-src 'self' {MAPLIBRE_JS};")));
+        assert!(page.contains(MAPLIBRE_JS) && page.contains(MAPLIBRE_CSS));
+        assert!(viewer_csp("https://maps.example.com")
+            .contains("connect-src 'self' https://maps.example.com;"));
+    }
+}
@@ -0,0 +1,360 @@
+//! Webhooks for dataset lifecycle events
+//!
+//! Admins register URLs through `/api/admin/webhooks` that are called when a
+//! file becomes ready, fails, is published or is unpublished, so downstream
+//! systems can refresh without polling. Each call is a JSON `POST` of
+//! `{event, fileId, slug?, occurredAt}` with the event name in
+//! `X-MapFlow-Event` and `X-MapFlow-Signature: sha256=<hex>`, the HMAC-SHA256
+//! of the body under the hook's secret. The secret is shown once, when the hook
+//! is created.
+//!
+//! Deliveries run in the background and never hold up the change that caused
+//! them. A hook that does not answer with a 2xx is retried a few times with
+//! backoff, then given up on.
+
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use axum::{
+    extract::{Path as AxumPath, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{delete, get},
+    Json, Router,
+};
+use chrono::Utc;
+use duckdb::Connection;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tokio::sync::Mutex;
+
+use crate::http_errors::{bad_request, internal_error};
+use crate::models::{CreateWebhookRequest, WebhookItem};
+use crate::signing::generate_signing_secret;
+use crate::{AppState, ErrorResponse};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const EVENT_HEADER: &str = "x-mapflow-event";
+const SIGNATURE_HEADER: &str = "x-mapflow-signature";
+const MAX_URL_LENGTH: usize = 2048;
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+const DELIVERY_ATTEMPTS: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookEvent {
+    FileReady,
+    FileFailed,
+    FilePublished,
+    FileUnpublished,
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 4] = [
+        WebhookEvent::FileReady,
+        WebhookEvent::FileFailed,
+        WebhookEvent::FilePublished,
+        WebhookEvent::FileUnpublished,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            WebhookEvent::FileReady => "file.ready",
+            WebhookEvent::FileFailed => "file.failed",
+            WebhookEvent::FilePublished => "file.published",
+            WebhookEvent::FileUnpublished => "file.unpublished",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|event| event.name() == name)
+    }
+}
+
+pub fn build_webhooks_router() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/api/admin/webhooks",
+            get(list_webhooks).post(create_webhook),
+        )
+        .route("/api/admin/webhooks/{id}", delete(delete_webhook))
+}
+
+/// `sha256=<hex>` signature of a delivery body.
+pub fn sign_payload(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+fn validate_url(url: &str) -> Result<String, String> {
+    let url = url.trim();
+    if url.len() > MAX_URL_LENGTH {
+        return Err(format!("url must be at most {MAX_URL_LENGTH} characters"));
+    }
+    let parsed = reqwest::Url::parse(url).map_err(|_| "url must be an absolute URL".to_string())?;
+    if !matches!(parsed.scheme(), "http" | "https") || parsed.host_str().is_none() {
+        return Err("url must be an http or https URL".to_string());
+    }
+    Ok(url.to_string())
+}
+
+/// Event names in `ALL` order, or every event when none are given.
+fn validate_events(events: Option<&[String]>) -> Result<Vec<WebhookEvent>, String> {
+    let Some(names) = events else {
+        return Ok(WebhookEvent::ALL.to_vec());
+    };
+    let mut events = Vec::with_capacity(names.len());
+    for name in names {
+        let event = WebhookEvent::parse(name).ok_or_else(|| {
+            let known: Vec<&str> = WebhookEvent::ALL.iter().map(|e| e.name()).collect();
+            format!(
+                "Unknown event '{name}'; expected one of {}",
+                known.join(", ")
+            )
+        })?;
+        events.push(event);
+    }
+    if events.is_empty() {
+        return Err("events must not be empty".to_string());
+    }
+    Ok(WebhookEvent::ALL
+        .into_iter()
+        .filter(|event| events.contains(event))
+        .collect())
+}
+
+fn webhook_from_row(row: &duckdb::Row<'_>) -> Result<WebhookItem, duckdb::Error> {
+    let events: String = row.get(2)?;
+    let created_at: chrono::NaiveDateTime = row.get(3)?;
+    Ok(WebhookItem {
+        id: row.get(0)?,
+        url: row.get(1)?,
+        events: events.split(',').map(str::to_string).collect(),
+        created_at: created_at.and_utc().to_rfc3339(),
+        secret: None,
+    })
+}
+
+async fn list_webhooks(
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    let mut stmt = conn
+        .prepare("SELECT id, url, events, created_at FROM webhooks ORDER BY created_at")
+        .map_err(internal_error)?;
+    let hooks: Vec<WebhookItem> = stmt
+        .query_map([], webhook_from_row)
+        .map_err(internal_error)?
+        .collect::<Result<_, _>>()
+        .map_err(internal_error)?;
+    Ok(Json(hooks))
+}
+
+async fn create_webhook(
+    State(state): State<AppState>,
+    Json(req): Json<CreateWebhookRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let url = validate_url(&req.url).map_err(|e| bad_request(&e))?;
+    let events = validate_events(req.events.as_deref()).map_err(|e| bad_request(&e))?;
+    let events: Vec<&str> = events.into_iter().map(WebhookEvent::name).collect();
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let secret = generate_signing_secret();
+    let conn = state.db.lock().await;
+    conn.execute(
+        "INSERT INTO webhooks (id, url, secret, events, created_at) VALUES (?, ?, ?, ?, ?)",
+        duckdb::params![&id, &url, &secret, events.join(","), Utc::now().naive_utc()],
+    )
+    .map_err(internal_error)?;
+    let mut hook = conn
+        .query_row(
+            "SELECT id, url, events, created_at FROM webhooks WHERE id = ?",
+            duckdb::params![&id],
+            webhook_from_row,
+        )
+        .map_err(internal_error)?;
+    hook.secret = Some(secret);
+
+    Ok((StatusCode::CREATED, Json(hook)))
+}
+
+async fn delete_webhook(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.db.lock().await;
+    let deleted = conn
+        .execute("DELETE FROM webhooks WHERE id = ?", duckdb::params![&id])
+        .map_err(internal_error)?;
+    if deleted == 0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Webhook not found".to_string(),
+            }),
+        ));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Call the hooks subscribed to `event` for a file in the background.
+/// `slug` is the public slug of publish events.
+pub fn notify(db: &Arc<Mutex<Connection>>, event: WebhookEvent, file_id: &str, slug: Option<&str>) {
+    let db = db.clone();
+    let body = serde_json::json!({
+        "event": event.name(),
+        "fileId": file_id,
+        "slug": slug,
+        "occurredAt": Utc::now().to_rfc3339(),
+    })
+    .to_string();
+    tokio::spawn(async move {
+        let hooks = {
+            let conn = db.lock().await;
+            match subscribed_hooks(&conn, event) {
+                Ok(hooks) => hooks,
+                Err(e) => {
+                    tracing::warn!(event = event.name(), error = %e, "Failed to load webhooks");
+                    return;
+                }
+            }
+        };
+        for (url, secret) in hooks {
+            tokio::spawn(deliver(url, secret, event, body.clone()));
+        }
+    });
+}
+
+/// URLs and secrets of the hooks subscribed to `event`.
+fn subscribed_hooks(
+    conn: &Connection,
+    event: WebhookEvent,
+) -> Result<Vec<(String, String)>, duckdb::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT url, secret FROM webhooks WHERE list_contains(string_split(events, ','), ?)",
+    )?;
+    let hooks = stmt.query_map(duckdb::params![event.name()], |row| {
+        Ok((row.get(0)?, row.get(1)?))
+    })?;
+    hooks.collect()
+}
+
+fn http_client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .timeout(DELIVERY_TIMEOUT)
+            .build()
+            .expect("webhook HTTP client")
+    })
+}
+
+async fn deliver(url: String, secret: String, event: WebhookEvent, body: String) {
+    let signature = sign_payload(&secret, body.as_bytes());
+    for attempt in 1..=DELIVERY_ATTEMPTS {
+        let result = http_client()
+            .post(&url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, event.name())
+            .header(SIGNATURE_HEADER, &signature)
+            .body(body.clone())
+            .send()
+            .await;
+        let error = match result {
+            Ok(response) if response.status().is_success() => return,
+            Ok(response) => format!("HTTP {}", response.status()),
+            Err(e) => e.to_string(),
+        };
+        if attempt == DELIVERY_ATTEMPTS {
+            tracing::warn!(
+                url = %url,
+                event = event.name(),
+                error = %error,
+                "Giving up on webhook delivery"
+            );
+        } else {
+            tracing::debug!(
+                url = %url,
+                event = event.name(),
+                attempt,
+                error = %error,
+                "Retrying webhook delivery"
+            );
+            tokio::time::sleep(Duration::from_secs(2u64.pow(attempt))).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signatures_cover_the_body() {
+        let signature = sign_payload("secret", b"{\"event\":\"file.ready\"}");
+        assert!(signature.starts_with("sha256="));
+        assert_eq!(signature.len(), "sha256=".len() + 64);
+        assert_eq!(
+            signature,
+            sign_payload("secret", b"{\"event\":\"file.ready\"}")
+        );
+        assert_ne!(
+            signature,
+            sign_payload("other", b"{\"event\":\"file.ready\"}")
+        );
+        assert_ne!(
+            signature,
+            sign_payload("secret", b"{\"event\":\"file.failed\"}")
+        );
+    }
+
+    #[test]
+    fn urls_must_be_http() {
+        assert!(validate_url(" https://example.com/hook ").is_ok());
+        assert!(validate_url("http://10.0.0.5:8080/refresh").is_ok());
+        assert!(validate_url("ftp://example.com/hook").is_err());
+        assert!(validate_url("/relative").is_err());
+        assert!(validate_url("file:///etc/passwd").is_err());
+    }
+
+    #[test]
+    fn events_default_to_all_and_reject_unknown_names() {
+        assert_eq!(validate_events(None).unwrap(), WebhookEvent::ALL.to_vec());
+        assert_eq!(
+            validate_events(Some(&[
+                "file.published".to_string(),
+                "file.ready".to_string(),
+                "file.ready".to_string(),
+            ]))
+            .unwrap(),
+            vec![WebhookEvent::FileReady, WebhookEvent::FilePublished]
+        );
+        assert!(validate_events(Some(&[])).is_err());
+        assert!(validate_events(Some(&["file.deleted".to_string()])).is_err());
+    }
+
+    #[test]
+    fn hooks_are_selected_by_event() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r"
+            CREATE TABLE webhooks (id VARCHAR, url VARCHAR, secret VARCHAR, events VARCHAR);
+            INSERT INTO webhooks VALUES
+                ('a', 'http://a', 's1', 'file.ready,file.failed'),
+                ('b', 'http://b', 's2', 'file.published');
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(
+            subscribed_hooks(&conn, WebhookEvent::FileFailed).unwrap(),
+            vec![("http://a".to_string(), "s1".to_string())]
+        );
+        assert!(subscribed_hooks(&conn, WebhookEvent::FileUnpublished)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,622 @@
+//! WMS
+//!
+//! A basic WMS 1.3.0 (and 1.1.1) endpoint at `/wms` for viewers that can only
+//! embed WMS layers. `GetCapabilities` lists the published vector datasets
+//! anyone may read, and `GetMap` rasterizes the requested layers over a bbox
+//! to a PNG, drawn in the same colours and symbols as the generated MapLibre
+//! style: polygons filled and outlined, lines stroked and points as circles.
+//!
+//! Maps can be requested in `EPSG:3857`, `EPSG:4326` or `CRS:84`; WMS 1.3.0
+//! orders an `EPSG:4326` bbox latitude first. Like `/tiles` the endpoint is
+//! anonymous, and a signed publish needs its `expires` and `token`. A layer
+//! follows its tiles: it is drawn only at the web map zooms the dataset and
+//! the publish serve, and its features are cut to the dataset's feature limit.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use duckdb::OptionalExt;
+use serde_json::Value;
+
+use crate::columns::{load_dataset_columns, quote_identifier, quote_literal};
+use crate::models::TileOptions;
+use crate::raster::{layer_color, parse_color, Canvas, Frame};
+use crate::settings::{load_settings, public_cache_control};
+use crate::signing::SignedQuery;
+use crate::tile_options::{apply_publish_overrides, parse_stored_tile_options};
+use crate::tiles::{feature_limit_sql, mercator_pixel_size, WEB_MERCATOR_HALF_WORLD};
+use crate::viewer::escape_html as escape_xml;
+use crate::{
+    check_not_expired, check_signed_access, dataset_bbox, public_origin, AppState, ErrorResponse,
+};
+
+/// Largest width or height of a map, in pixels.
+pub const MAX_MAP_SIZE: u32 = 4096;
+/// Features drawn per layer; the rest of a dense layer is left out.
+const MAX_MAP_FEATURES: u32 = 100_000;
+const SUPPORTED_CRS: &[&str] = &["EPSG:3857", "EPSG:4326", "CRS:84"];
+const PNG: &str = "image/png";
+/// Scale denominator of web map zoom 0: 256px tiles of 0.28mm pixels.
+const ZOOM_0_SCALE: f64 = 559_082_264.028_717_8;
+
+pub fn build_wms_router() -> Router<AppState> {
+    Router::new().route("/wms", get(wms))
+}
+
+/// A WMS service exception report.
+fn exception(status: StatusCode, code: Option<&str>, text: &str) -> Response {
+    let code = code
+        .map(|code| format!(r#" code="{code}""#))
+        .unwrap_or_default();
+    let body = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<ServiceExceptionReport xmlns="http://www.opengis.net/ogc" version="1.3.0">
+  <ServiceException{code}>{}</ServiceException>
+</ServiceExceptionReport>
+"#,
+        escape_xml(text)
+    );
+    (status, [(header::CONTENT_TYPE, "text/xml")], body).into_response()
+}
+
+fn invalid(text: &str) -> Response {
+    exception(StatusCode::BAD_REQUEST, None, text)
+}
+
+/// KVP parameters, whose names are case-insensitive.
+struct Params(HashMap<String, String>);
+
+impl Params {
+    fn new(params: HashMap<String, String>) -> Self {
+        Self(
+            params
+                .into_iter()
+                .map(|(name, value)| (name.to_ascii_lowercase(), value))
+                .collect(),
+        )
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .get(name)
+            .map(String::as_str)
+            .filter(|value| !value.is_empty())
+    }
+
+    fn require(&self, name: &str) -> Result<&str, Response> {
+        self.get(name)
+            .ok_or_else(|| invalid(&format!("Missing parameter {}", name.to_ascii_uppercase())))
+    }
+
+    fn size(&self, name: &str) -> Result<u32, Response> {
+        match self.require(name)?.parse::<u32>() {
+            Ok(size) if (1..=MAX_MAP_SIZE).contains(&size) => Ok(size),
+            _ => Err(invalid(&format!(
+                "{} must be between 1 and {MAX_MAP_SIZE}",
+                name.to_ascii_uppercase()
+            ))),
+        }
+    }
+}
+
+/// The part of the map's CRS a GetMap request covers.
+#[derive(Debug, PartialEq)]
+struct View {
+    /// CRS to reproject features to, with x first.
+    crs: &'static str,
+    /// `[minx, miny, maxx, maxy]` in `crs`.
+    bbox: [f64; 4],
+    width: u32,
+    height: u32,
+}
+
+impl View {
+    fn parse(params: &Params) -> Result<Self, Response> {
+        let version = params.get("version").unwrap_or("1.3.0");
+        let crs_param = if version == "1.1.1" { "srs" } else { "crs" };
+        let crs = params.require(crs_param)?.to_ascii_uppercase();
+        let (crs, lat_first) = match crs.as_str() {
+            "EPSG:3857" | "EPSG:900913" => ("EPSG:3857", false),
+            "EPSG:4326" => ("EPSG:4326", version != "1.1.1"),
+            "CRS:84" => ("EPSG:4326", false),
+            _ => {
+                return Err(exception(
+                    StatusCode::BAD_REQUEST,
+                    Some(if version == "1.1.1" {
+                        "InvalidSRS"
+                    } else {
+                        "InvalidCRS"
+                    }),
+                    &format!("{crs} is not one of {}", SUPPORTED_CRS.join(", ")),
+                ))
+            }
+        };
+        let values: Vec<f64> = params
+            .require("bbox")?
+            .split(',')
+            .map(|value| value.trim().parse::<f64>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| invalid("BBOX must be four numbers"))?;
+        let [a, b, c, d] = values[..] else {
+            return Err(invalid("BBOX must be four numbers"));
+        };
+        let bbox = if lat_first {
+            [b, a, d, c]
+        } else {
+            [a, b, c, d]
+        };
+        if !bbox.iter().all(|value| value.is_finite()) || bbox[0] >= bbox[2] || bbox[1] >= bbox[3] {
+            return Err(invalid("BBOX minimums must be below its maximums"));
+        }
+        Ok(Self {
+            crs,
+            bbox,
+            width: params.size("width")?,
+            height: params.size("height")?,
+        })
+    }
+
+    /// Web map zoom whose 256px tiles match the view's resolution, rounded
+    /// down; a degree counts as it measures at the equator.
+    fn zoom(&self) -> i32 {
+        let mut pixel = (self.bbox[2] - self.bbox[0]) / f64::from(self.width);
+        if self.crs == "EPSG:4326" {
+            pixel *= 2.0 * WEB_MERCATOR_HALF_WORLD / 360.0;
+        }
+        (mercator_pixel_size(256, 0.0) / pixel).log2().floor() as i32
+    }
+}
+
+async fn wms(
+    State(state): State<AppState>,
+    Query(params): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Result<Response, Response> {
+    let params = Params::new(params);
+    if let Some(service) = params.get("service") {
+        if !service.eq_ignore_ascii_case("WMS") {
+            return Err(invalid("SERVICE must be WMS"));
+        }
+    }
+    let request = params.require("request")?;
+    if request.eq_ignore_ascii_case("GetCapabilities") {
+        get_capabilities(&state, &headers).await
+    } else if request.eq_ignore_ascii_case("GetMap") {
+        get_map(&state, &params).await
+    } else {
+        Err(exception(
+            StatusCode::BAD_REQUEST,
+            Some("OperationNotSupported"),
+            &format!("Unsupported request {request}"),
+        ))
+    }
+}
+
+fn internal(error: impl std::fmt::Display) -> Response {
+    tracing::error!(error = %error, "WMS request failed");
+    exception(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        None,
+        "Internal server error",
+    )
+}
+
+/// A published vector dataset as the map draws it.
+struct WmsLayer {
+    slug: String,
+    title: String,
+    /// The dataset's table, or that of the version the publish pins.
+    table_name: String,
+    /// Key of the table's columns: the file, or the pinned version's table.
+    source_id: String,
+    crs: String,
+    /// Stored `[minx, miny, maxx, maxy]` of the current table, as JSON.
+    stored_bounds: Option<String>,
+    /// The dataset's tile options with the publish's overrides applied.
+    options: TileOptions,
+    /// The publish's own options, which hold the zoom range it serves.
+    publish: TileOptions,
+}
+
+/// Columns `WmsLayer::from_row` reads, for published vector datasets that are
+/// ready and whose slug is not taken by a tileset.
+const LAYER_SQL: &str = "
+    SELECT p.slug, f.name, COALESCE(v.table_name, f.table_name), v.table_name, p.file_id,
+           IF(v.table_name IS NULL, f.crs, v.crs),
+           IF(v.table_name IS NULL, COALESCE(f.tile_bounds, f.bbox), NULL),
+           f.tile_options, p.tile_options
+    FROM published_files p
+    JOIN files f ON f.id = p.file_id
+    LEFT JOIN dataset_versions v ON v.file_id = p.file_id AND v.version = p.version
+    WHERE f.status = 'ready' AND f.table_name IS NOT NULL AND f.tile_format IS NULL
+      AND NOT EXISTS (SELECT 1 FROM tilesets t WHERE t.slug = p.slug)";
+
+impl WmsLayer {
+    fn from_row(row: &duckdb::Row) -> Result<Self, duckdb::Error> {
+        let table_name: String = row.get(2)?;
+        let version_table: Option<String> = row.get(3)?;
+        let file_id: String = row.get(4)?;
+        let dataset_options: Option<String> = row.get(7)?;
+        let publish_options: Option<String> = row.get(8)?;
+        let publish = parse_stored_tile_options(publish_options.as_deref());
+        Ok(Self {
+            slug: row.get(0)?,
+            title: row.get(1)?,
+            source_id: version_table.unwrap_or(file_id),
+            table_name,
+            crs: row
+                .get::<_, Option<String>>(5)?
+                .unwrap_or_else(|| "EPSG:4326".to_string()),
+            stored_bounds: row.get(6)?,
+            options: apply_publish_overrides(
+                &parse_stored_tile_options(dataset_options.as_deref()),
+                &publish,
+            ),
+            publish,
+        })
+    }
+
+    /// Whether the layer's tiles are served at zoom `z`: within the publish's
+    /// range and not below the dataset's, beyond whose top tiles are overzoomed.
+    fn drawn_at(&self, z: i32) -> bool {
+        self.publish.covers_zoom(z) && self.options.min_zoom.is_none_or(|min| z >= i32::from(min))
+    }
+
+    /// Lowest and highest zoom the layer is drawn at, when bounded.
+    fn zoom_range(&self) -> (Option<u8>, Option<u8>) {
+        let min = self.publish.min_zoom.max(self.options.min_zoom);
+        (min, self.publish.max_zoom)
+    }
+}
+
+/// Published vector datasets anyone may read, with their bounds.
+fn load_layers(
+    conn: &duckdb::Connection,
+) -> Result<Vec<(WmsLayer, Option<[f64; 4]>)>, duckdb::Error> {
+    let mut stmt = conn.prepare(&format!(
+        "{LAYER_SQL}
+           AND f.is_public AND p.signing_secret IS NULL
+           AND (p.expires_at IS NULL OR p.expires_at > ?)
+         ORDER BY p.slug"
+    ))?;
+    let layers = stmt
+        .query_map(
+            duckdb::params![chrono::Utc::now().naive_utc()],
+            WmsLayer::from_row,
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(layers
+        .into_iter()
+        .map(|layer| {
+            let bounds = dataset_bbox(
+                conn,
+                layer.stored_bounds.as_deref(),
+                Some(&layer.table_name),
+                Some(&layer.crs),
+            );
+            (layer, bounds)
+        })
+        .collect())
+}
+
+async fn get_capabilities(state: &AppState, headers: &HeaderMap) -> Result<Response, Response> {
+    let conn = state.read_pool.get().await.map_err(internal)?;
+    let settings = load_settings(&conn, state).map_err(internal)?;
+    let layers = load_layers(&conn).map_err(internal)?;
+    drop(conn);
+    let root = public_origin(state, &settings, headers);
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/xml".to_string()),
+            (header::CACHE_CONTROL, public_cache_control(&settings)),
+        ],
+        capabilities_xml(&root, &layers),
+    )
+        .into_response())
+}
+
+fn capabilities_xml(root: &str, layers: &[(WmsLayer, Option<[f64; 4]>)]) -> String {
+    let href = format!("{}/wms?", escape_xml(root));
+    let dcp = format!(
+        r#"<DCPType><HTTP><Get><OnlineResource xlink:type="simple" xlink:href="{href}"/></Get></HTTP></DCPType>"#
+    );
+    let mut xml = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<WMS_Capabilities xmlns="http://www.opengis.net/wms" xmlns:xlink="http://www.w3.org/1999/xlink" version="1.3.0">
+  <Service>
+    <Name>WMS</Name>
+    <Title>MapFlow</Title>
+    <OnlineResource xlink:type="simple" xlink:href="{href}"/>
+    <MaxWidth>{MAX_MAP_SIZE}</MaxWidth>
+    <MaxHeight>{MAX_MAP_SIZE}</MaxHeight>
+  </Service>
+  <Capability>
+    <Request>
+      <GetCapabilities><Format>text/xml</Format>{dcp}</GetCapabilities>
+      <GetMap><Format>{PNG}</Format>{dcp}</GetMap>
+    </Request>
+    <Exception><Format>XML</Format></Exception>
+    <Layer>
+      <Title>MapFlow</Title>
+"#
+    );
+    for crs in SUPPORTED_CRS {
+        let _ = writeln!(xml, "      <CRS>{crs}</CRS>");
+    }
+    for (layer, bounds) in layers {
+        let _ = write!(
+            xml,
+            "      <Layer queryable=\"0\">\n        <Name>{}</Name>\n        <Title>{}</Title>\n",
+            escape_xml(&layer.slug),
+            escape_xml(&layer.title)
+        );
+        if let Some([minx, miny, maxx, maxy]) = bounds {
+            let _ = write!(
+                xml,
+                "        <EX_GeographicBoundingBox><westBoundLongitude>{minx}</westBoundLongitude><eastBoundLongitude>{maxx}</eastBoundLongitude><southBoundLatitude>{miny}</southBoundLatitude><northBoundLatitude>{maxy}</northBoundLatitude></EX_GeographicBoundingBox>\n        <BoundingBox CRS=\"CRS:84\" minx=\"{minx}\" miny=\"{miny}\" maxx=\"{maxx}\" maxy=\"{maxy}\"/>\n"
+            );
+        }
+        // Zoom z covers scales down to, not including, that of zoom z + 1.
+        let (min_zoom, max_zoom) = layer.zoom_range();
+        if let Some(max) = max_zoom {
+            let _ = writeln!(
+                xml,
+                "        <MinScaleDenominator>{}</MinScaleDenominator>",
+                zoom_scale(i32::from(max) + 1)
+            );
+        }
+        if let Some(min) = min_zoom {
+            let _ = writeln!(
+                xml,
+                "        <MaxScaleDenominator>{}</MaxScaleDenominator>",
+                zoom_scale(i32::from(min))
+            );
+        }
+        xml.push_str("      </Layer>\n");
+    }
+    xml.push_str("    </Layer>\n  </Capability>\n</WMS_Capabilities>\n");
+    xml
+}
+
+/// Scale denominator of web map zoom `z`.
+fn zoom_scale(z: i32) -> f64 {
+    ZOOM_0_SCALE / 2f64.powi(z)
+}
+
+/// A published vector dataset, after the publish's expiry and signature are
+/// checked.
+fn load_layer(
+    conn: &duckdb::Connection,
+    slug: &str,
+    signed: &SignedQuery,
+) -> Result<WmsLayer, Response> {
+    let not_defined = || {
+        exception(
+            StatusCode::NOT_FOUND,
+            Some("LayerNotDefined"),
+            &format!("Layer {slug} is not defined"),
+        )
+    };
+    let reject = |(status, error): (StatusCode, Json<ErrorResponse>)| {
+        exception(status, None, &error.0.error)
+    };
+    let publish: Option<(Option<String>, Option<chrono::NaiveDateTime>, bool, String)> = conn
+        .query_row(
+            "SELECT p.signing_secret, p.expires_at, f.is_public, f.status
+             FROM published_files p JOIN files f ON f.id = p.file_id
+             WHERE p.slug = ? AND f.table_name IS NOT NULL AND f.tile_format IS NULL
+               AND NOT EXISTS (SELECT 1 FROM tilesets t WHERE t.slug = p.slug)",
+            duckdb::params![slug],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .optional()
+        .map_err(internal)?;
+    let (signing_secret, expires_at, is_public, status) = publish.ok_or_else(not_defined)?;
+    check_not_expired(expires_at).map_err(reject)?;
+    if !is_public {
+        return Err(not_defined());
+    }
+    check_signed_access(signing_secret.as_deref(), slug, signed).map_err(reject)?;
+    if status != "ready" {
+        return Err(exception(StatusCode::CONFLICT, None, "File is not ready"));
+    }
+    conn.query_row(
+        &format!("{LAYER_SQL} AND p.slug = ?"),
+        duckdb::params![slug],
+        WmsLayer::from_row,
+    )
+    .optional()
+    .map_err(internal)?
+    .ok_or_else(not_defined)
+}
+
+/// GeoJSON geometries of a layer intersecting the view, in the view's CRS,
+/// cut to the layer's feature limit.
+fn load_geometries(
+    conn: &duckdb::Connection,
+    layer: &WmsLayer,
+    view: &View,
+) -> Result<Vec<Value>, duckdb::Error> {
+    let [minx, miny, maxx, maxy] = view.bbox;
+    let limit = layer
+        .options
+        .feature_limit
+        .map_or(MAX_MAP_FEATURES, |limit| limit.min(MAX_MAP_FEATURES));
+    let columns = load_dataset_columns(conn, &layer.source_id)?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT ST_AsGeoJSON(g) FROM (
+             SELECT *, ST_Transform(geom, {}, '{}', always_xy := true) AS g FROM {}
+         )
+         WHERE ST_Intersects(g, ST_MakeEnvelope(?, ?, ?, ?)){}",
+        quote_literal(&layer.crs),
+        view.crs,
+        quote_identifier(&layer.table_name),
+        feature_limit_sql(&layer.options, &columns, "g", view.bbox, limit)
+    ))?;
+    let rows = stmt.query_map(duckdb::params![minx, miny, maxx, maxy], |row| {
+        row.get::<_, Option<String>>(0)
+    })?;
+    let mut geometries = Vec::new();
+    for text in rows {
+        if let Some(geometry) = text?.and_then(|text| serde_json::from_str(&text).ok()) {
+            geometries.push(geometry);
+        }
+    }
+    Ok(geometries)
+}
+
+async fn get_map(state: &AppState, params: &Params) -> Result<Response, Response> {
+    let format = params.require("format")?;
+    if !format.eq_ignore_ascii_case(PNG) {
+        return Err(exception(
+            StatusCode::BAD_REQUEST,
+            Some("InvalidFormat"),
+            &format!("FORMAT must be {PNG}"),
+        ));
+    }
+    let slugs: Vec<&str> = params
+        .require("layers")?
+        .split(',')
+        .map(str::trim)
+        .filter(|slug| !slug.is_empty())
+        .collect();
+    let view = View::parse(params)?;
+    let transparent = params
+        .get("transparent")
+        .is_some_and(|value| value.eq_ignore_ascii_case("true"));
+    let background = match params.get("bgcolor") {
+        Some(value) => parse_color(value).ok_or_else(|| invalid("BGCOLOR must be 0xRRGGBB"))?,
+        None => (255, 255, 255),
+    };
+    let signed = SignedQuery {
+        expires: params.get("expires").and_then(|value| value.parse().ok()),
+        token: params.get("token").map(str::to_string),
+    };
+
+    let conn = state.read_pool.get().await.map_err(internal)?;
+    let cache_control = public_cache_control(&load_settings(&conn, state).map_err(internal)?);
+    let zoom = view.zoom();
+    let mut layers = Vec::with_capacity(slugs.len());
+    for slug in slugs {
+        let layer = load_layer(&conn, slug, &signed)?;
+        // A layer out of its zoom range keeps its colour but draws nothing.
+        layers.push(if layer.drawn_at(zoom) {
+            load_geometries(&conn, &layer, &view).map_err(internal)?
+        } else {
+            Vec::new()
+        });
+    }
+    drop(conn);
+
+    let png = tokio::task::spawn_blocking(move || {
+        let background = (!transparent).then_some(background);
+        let mut canvas = Canvas::new(view.width, view.height, background)
+            .ok_or_else(|| "Invalid map size".to_string())?;
+        let frame = Frame {
+            bbox: view.bbox,
+            y_down: false,
+        };
+        for (index, geometries) in layers.iter().enumerate() {
+            canvas.draw_layer(&frame, geometries, layer_color(index));
+        }
+        canvas.encode_png()
+    })
+    .await
+    .map_err(internal)?
+    .map_err(internal)?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, PNG.to_string()),
+            (header::CACHE_CONTROL, cache_control),
+        ],
+        png,
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(pairs: &[(&str, &str)]) -> Params {
+        Params::new(
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn wms_1_3_0_orders_epsg_4326_latitude_first() {
+        let size = [("WIDTH", "100"), ("HEIGHT", "50")];
+        let view = |pairs: &[(&str, &str)]| {
+            View::parse(&params(&[pairs, &size[..]].concat()))
+                .ok()
+                .map(|view| view.bbox)
+        };
+        assert_eq!(
+            view(&[("CRS", "EPSG:4326"), ("BBOX", "10,20,30,40")]),
+            Some([20.0, 10.0, 40.0, 30.0])
+        );
+        assert_eq!(
+            view(&[("CRS", "CRS:84"), ("BBOX", "10,20,30,40")]),
+            Some([10.0, 20.0, 30.0, 40.0])
+        );
+        assert_eq!(
+            view(&[
+                ("VERSION", "1.1.1"),
+                ("SRS", "EPSG:4326"),
+                ("BBOX", "10,20,30,40")
+            ]),
+            Some([10.0, 20.0, 30.0, 40.0])
+        );
+        assert_eq!(view(&[("CRS", "EPSG:27700"), ("BBOX", "0,0,1,1")]), None);
+        assert_eq!(view(&[("CRS", "CRS:84"), ("BBOX", "1,1,0,0")]), None);
+        assert_eq!(view(&[("CRS", "CRS:84"), ("BBOX", "0,0,1")]), None);
+    }
+
+    #[test]
+    fn view_zoom_matches_tile_resolution() {
+        let zoom = |crs: &str, bbox: &str, width: &str| {
+            View::parse(&params(&[
+                ("CRS", crs),
+                ("BBOX", bbox),
+                ("WIDTH", width),
+                ("HEIGHT", "256"),
+            ]))
+            .ok()
+            .map(|view| view.zoom())
+        };
+        let world = "-20037508.342789244,-20037508.342789244,20037508.342789244,20037508.342789244";
+        assert_eq!(zoom("EPSG:3857", world, "256"), Some(0));
+        assert_eq!(zoom("EPSG:3857", world, "512"), Some(1));
+        assert_eq!(zoom("EPSG:3857", world, "1000"), Some(1));
+        assert_eq!(zoom("CRS:84", "-180,-90,180,90", "256"), Some(0));
+        assert_eq!(zoom("CRS:84", "0,0,1.4,1", "256"), Some(8));
+        assert_eq!(zoom_scale(1), ZOOM_0_SCALE / 2.0);
+    }
+
+    #[test]
+    fn map_size_is_bounded() {
+        let parse = |width: &str| {
+            View::parse(&params(&[
+                ("CRS", "CRS:84"),
+                ("BBOX", "0,0,1,1"),
+                ("WIDTH", width),
+                ("HEIGHT", "10"),
+            ]))
+            .is_ok()
+        };
+        assert!(parse("4096"));
+        assert!(!parse("4097"));
+        assert!(!parse("0"));
+    }
+}
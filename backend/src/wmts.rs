@@ -0,0 +1,403 @@
+//! WMTS
+//!
+//! Published datasets and tilesets as an OGC WMTS 1.0.0 service, for GIS
+//! clients that speak nothing newer. `/wmts` answers the KVP requests
+//! (`SERVICE=WMTS&REQUEST=GetCapabilities` and `GetTile`) and
+//! `/wmts/1.0.0/WMTSCapabilities.xml` the RESTful one, whose layers' resource
+//! URLs are `/wmts/1.0.0/{layer}/{style}/WebMercatorQuad/{z}/{row}/{col}`.
+//!
+//! Each layer is a public slug in the `WebMercatorQuad` (Google Maps
+//! compatible) tile matrix set, and its tiles are those
+//! `/tiles/{slug}/{z}/{x}/{y}` serves: Mapbox Vector Tiles, or the PNGs of a
+//! raster MBTiles. Signed publishes are left out of the capabilities but still
+//! answer `GetTile` with their `expires` and `token`.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+use axum::{
+    extract::{Path as AxumPath, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+
+use crate::http_errors::internal_error;
+use crate::models::TileJson;
+use crate::ogc_tiles::tile_matrix_limits;
+use crate::settings::{load_settings, public_cache_control};
+use crate::signing::SignedQuery;
+use crate::tile_options::MAX_TILE_ZOOM;
+use crate::tile_routes::serve_public_tile;
+use crate::tiles::{TileEncoding, TileQuery};
+use crate::viewer::escape_html as escape_xml;
+use crate::{public_origin, public_tilejson, AppState, ErrorResponse};
+
+const TILE_MATRIX_SET: &str = "WebMercatorQuad";
+/// Scale denominator of zoom 0 at the standard 0.28 mm pixel.
+const SCALE_DENOMINATOR_Z0: f64 = 559_082_264.028_717_8;
+/// Half the width of the Web Mercator square, in metres.
+const ORIGIN_SHIFT: f64 = 20_037_508.342_789_244;
+const MVT: &str = "application/vnd.mapbox-vector-tile";
+const PNG: &str = "image/png";
+
+pub fn build_wmts_router() -> Router<AppState> {
+    Router::new()
+        .route("/wmts", get(wmts_kvp))
+        .route("/wmts/1.0.0/WMTSCapabilities.xml", get(get_capabilities))
+        .route(
+            "/wmts/1.0.0/{layer}/{style}/{tile_matrix_set}/{tile_matrix}/{tile_row}/{tile_col}",
+            get(get_rest_tile),
+        )
+}
+
+/// An OWS exception report, which WMTS clients show to their users.
+fn exception(status: StatusCode, code: &str, locator: &str, text: &str) -> Response {
+    let body = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<ows:ExceptionReport xmlns:ows="http://www.opengis.net/ows/1.1" version="2.0.0">
+  <ows:Exception exceptionCode="{code}" locator="{locator}">
+    <ows:ExceptionText>{}</ows:ExceptionText>
+  </ows:Exception>
+</ows:ExceptionReport>
+"#,
+        escape_xml(text)
+    );
+    (status, [(header::CONTENT_TYPE, "application/xml")], body).into_response()
+}
+
+/// KVP parameters, whose names are case-insensitive.
+struct KvpParams(HashMap<String, String>);
+
+impl KvpParams {
+    fn new(params: HashMap<String, String>) -> Self {
+        Self(
+            params
+                .into_iter()
+                .map(|(name, value)| (name.to_ascii_lowercase(), value))
+                .collect(),
+        )
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .get(name)
+            .map(String::as_str)
+            .filter(|value| !value.is_empty())
+    }
+
+    fn require(&self, name: &str) -> Result<&str, Response> {
+        self.get(name).ok_or_else(|| {
+            exception(
+                StatusCode::BAD_REQUEST,
+                "MissingParameterValue",
+                name,
+                &format!("Missing parameter {}", name.to_ascii_uppercase()),
+            )
+        })
+    }
+
+    fn require_number(&self, name: &str) -> Result<i32, Response> {
+        self.require(name)?.parse().map_err(|_| {
+            exception(
+                StatusCode::BAD_REQUEST,
+                "InvalidParameterValue",
+                name,
+                &format!("{} must be an integer", name.to_ascii_uppercase()),
+            )
+        })
+    }
+}
+
+async fn wmts_kvp(
+    state: State<AppState>,
+    Query(params): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Result<Response, Response> {
+    let params = KvpParams::new(params);
+    if !params.require("service")?.eq_ignore_ascii_case("WMTS") {
+        return Err(exception(
+            StatusCode::BAD_REQUEST,
+            "InvalidParameterValue",
+            "service",
+            "SERVICE must be WMTS",
+        ));
+    }
+    let request = params.require("request")?;
+    if request.eq_ignore_ascii_case("GetCapabilities") {
+        return get_capabilities(state, headers)
+            .await
+            .map(IntoResponse::into_response)
+            .map_err(IntoResponse::into_response);
+    }
+    if !request.eq_ignore_ascii_case("GetTile") {
+        return Err(exception(
+            StatusCode::BAD_REQUEST,
+            "OperationNotSupported",
+            "request",
+            &format!("Unsupported request {request}"),
+        ));
+    }
+    let layer = params.require("layer")?.to_string();
+    let tile_matrix_set = params.require("tilematrixset")?;
+    let z = params.require_number("tilematrix")?;
+    let row = params.require_number("tilerow")?;
+    let col = params.require_number("tilecol")?;
+    if tile_matrix_set != TILE_MATRIX_SET {
+        return Err(exception(
+            StatusCode::BAD_REQUEST,
+            "InvalidParameterValue",
+            "tilematrixset",
+            &format!("TILEMATRIXSET must be {TILE_MATRIX_SET}"),
+        ));
+    }
+    let signed = SignedQuery {
+        expires: params.get("expires").and_then(|value| value.parse().ok()),
+        token: params.get("token").map(str::to_string),
+    };
+    serve_public_tile(
+        &state,
+        &layer,
+        (z, col, row),
+        TileQuery::default(),
+        signed,
+        TileEncoding::Mvt,
+    )
+    .await
+    .map_err(IntoResponse::into_response)
+}
+
+async fn get_rest_tile(
+    state: State<AppState>,
+    AxumPath((layer, _style, tile_matrix_set, z, row, col)): AxumPath<(
+        String,
+        String,
+        String,
+        i32,
+        i32,
+        i32,
+    )>,
+    Query(signed): Query<SignedQuery>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    if tile_matrix_set != TILE_MATRIX_SET {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Tile matrix set not found".to_string(),
+            }),
+        ));
+    }
+    serve_public_tile(
+        &state,
+        &layer,
+        (z, col, row),
+        TileQuery::default(),
+        signed,
+        TileEncoding::Mvt,
+    )
+    .await
+}
+
+/// Public slugs whose tiles anyone may read: published, ready, unexpired and
+/// unsigned datasets, and tilesets.
+fn load_layers(conn: &duckdb::Connection) -> Result<Vec<(String, TileJson)>, duckdb::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT slug FROM published_files UNION SELECT slug FROM tilesets ORDER BY slug",
+    )?;
+    let slugs = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(slugs
+        .into_iter()
+        .filter_map(|slug| {
+            let tilejson = public_tilejson(conn, &slug, "", &SignedQuery::default()).ok()?;
+            Some((slug, tilejson))
+        })
+        .collect())
+}
+
+async fn get_capabilities(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let conn = state.read_pool.get().await.map_err(internal_error)?;
+    let settings = load_settings(&conn, &state).map_err(internal_error)?;
+    let layers = load_layers(&conn).map_err(internal_error)?;
+    drop(conn);
+    let root = public_origin(&state, &settings, &headers);
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/xml".to_string()),
+            (header::CACHE_CONTROL, public_cache_control(&settings)),
+        ],
+        capabilities_xml(&root, &layers),
+    ))
+}
+
+fn operation(name: &str, href: &str) -> String {
+    format!(
+        r#"    <ows:Operation name="{name}">
+      <ows:DCP>
+        <ows:HTTP>
+          <ows:Get xlink:href="{href}">
+            <ows:Constraint name="GetEncoding">
+              <ows:AllowedValues><ows:Value>KVP</ows:Value></ows:AllowedValues>
+            </ows:Constraint>
+          </ows:Get>
+        </ows:HTTP>
+      </ows:DCP>
+    </ows:Operation>
+"#
+    )
+}
+
+fn layer_xml(xml: &mut String, root: &str, slug: &str, tilejson: &TileJson) {
+    let format = if tilejson.vector_layers.is_some() {
+        MVT
+    } else {
+        PNG
+    };
+    let slug = escape_xml(slug);
+    let _ = write!(
+        xml,
+        "    <Layer>\n      <ows:Title>{}</ows:Title>\n",
+        escape_xml(&tilejson.name)
+    );
+    if let Some([minx, miny, maxx, maxy]) = tilejson.bounds {
+        let _ = write!(
+            xml,
+            "      <ows:WGS84BoundingBox>\n        <ows:LowerCorner>{minx} {miny}</ows:LowerCorner>\n        <ows:UpperCorner>{maxx} {maxy}</ows:UpperCorner>\n      </ows:WGS84BoundingBox>\n"
+        );
+    }
+    let _ = write!(
+        xml,
+        "      <ows:Identifier>{slug}</ows:Identifier>\n      <Style isDefault=\"true\"><ows:Identifier>default</ows:Identifier></Style>\n      <Format>{format}</Format>\n      <TileMatrixSetLink>\n        <TileMatrixSet>{TILE_MATRIX_SET}</TileMatrixSet>\n"
+    );
+    // Without bounds every tile of the published zoom range may hold data.
+    let bounds = tilejson.bounds.unwrap_or([-180.0, -90.0, 180.0, 90.0]);
+    xml.push_str("        <TileMatrixSetLimits>\n");
+    for limits in tile_matrix_limits(bounds, tilejson.minzoom, tilejson.maxzoom) {
+        let _ = write!(
+            xml,
+            "          <TileMatrixLimits><TileMatrix>{}</TileMatrix><MinTileRow>{}</MinTileRow><MaxTileRow>{}</MaxTileRow><MinTileCol>{}</MinTileCol><MaxTileCol>{}</MaxTileCol></TileMatrixLimits>\n",
+            limits.tile_matrix,
+            limits.min_tile_row,
+            limits.max_tile_row,
+            limits.min_tile_col,
+            limits.max_tile_col
+        );
+    }
+    let _ = write!(
+        xml,
+        "        </TileMatrixSetLimits>\n      </TileMatrixSetLink>\n      <ResourceURL format=\"{format}\" resourceType=\"tile\" template=\"{}/wmts/1.0.0/{slug}/{{Style}}/{{TileMatrixSet}}/{{TileMatrix}}/{{TileRow}}/{{TileCol}}\"/>\n    </Layer>\n",
+        escape_xml(root)
+    );
+}
+
+fn tile_matrix_set_xml(xml: &mut String) {
+    let _ = write!(
+        xml,
+        "    <TileMatrixSet>\n      <ows:Identifier>{TILE_MATRIX_SET}</ows:Identifier>\n      <ows:SupportedCRS>urn:ogc:def:crs:EPSG::3857</ows:SupportedCRS>\n      <WellKnownScaleSet>urn:ogc:def:wkss:OGC:1.0:GoogleMapsCompatible</WellKnownScaleSet>\n"
+    );
+    for z in 0..=MAX_TILE_ZOOM {
+        let size = 1u32 << z;
+        let _ = write!(
+            xml,
+            "      <TileMatrix>\n        <ows:Identifier>{z}</ows:Identifier>\n        <ScaleDenominator>{}</ScaleDenominator>\n        <TopLeftCorner>{} {ORIGIN_SHIFT}</TopLeftCorner>\n        <TileWidth>256</TileWidth>\n        <TileHeight>256</TileHeight>\n        <MatrixWidth>{size}</MatrixWidth>\n        <MatrixHeight>{size}</MatrixHeight>\n      </TileMatrix>\n",
+            SCALE_DENOMINATOR_Z0 / f64::from(size),
+            -ORIGIN_SHIFT
+        );
+    }
+    xml.push_str("    </TileMatrixSet>\n");
+}
+
+/// The GetCapabilities document for `layers`, with URLs under `root`.
+fn capabilities_xml(root: &str, layers: &[(String, TileJson)]) -> String {
+    let root_attr = escape_xml(root);
+    let mut xml = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<Capabilities xmlns="http://www.opengis.net/wmts/1.0" xmlns:ows="http://www.opengis.net/ows/1.1" xmlns:xlink="http://www.w3.org/1999/xlink" version="1.0.0">
+  <ows:ServiceIdentification>
+    <ows:Title>MapFlow</ows:Title>
+    <ows:ServiceType>OGC WMTS</ows:ServiceType>
+    <ows:ServiceTypeVersion>1.0.0</ows:ServiceTypeVersion>
+  </ows:ServiceIdentification>
+  <ows:OperationsMetadata>
+"#,
+    );
+    let kvp = format!("{root_attr}/wmts?");
+    xml.push_str(&operation("GetCapabilities", &kvp));
+    xml.push_str(&operation("GetTile", &kvp));
+    xml.push_str("  </ows:OperationsMetadata>\n  <Contents>\n");
+    for (slug, tilejson) in layers {
+        layer_xml(&mut xml, root, slug, tilejson);
+    }
+    tile_matrix_set_xml(&mut xml);
+    let _ = write!(
+        xml,
+        "  </Contents>\n  <ServiceMetadataURL xlink:href=\"{root_attr}/wmts/1.0.0/WMTSCapabilities.xml\"/>\n</Capabilities>\n"
+    );
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tilejson(name: &str, vector: bool) -> TileJson {
+        TileJson {
+            tilejson: "3.0.0".to_string(),
+            name: name.to_string(),
+            tiles: Vec::new(),
+            minzoom: 0,
+            maxzoom: 2,
+            bounds: Some([-1.0, -1.0, 1.0, 1.0]),
+            description: None,
+            attribution: None,
+            license: None,
+            vector_layers: vector.then(Vec::new),
+        }
+    }
+
+    #[test]
+    fn capabilities_list_layers_and_tile_matrices() {
+        let xml = capabilities_xml(
+            "https://maps.example.com",
+            &[
+                ("roads".to_string(), tilejson("Roads & Rails", true)),
+                ("relief".to_string(), tilejson("Relief", false)),
+            ],
+        );
+        assert!(xml.contains("<ows:Title>Roads &amp; Rails</ows:Title>"));
+        assert!(xml.contains("<ows:Identifier>roads</ows:Identifier>"));
+        assert!(xml.contains(
+            r#"template="https://maps.example.com/wmts/1.0.0/roads/{Style}/{TileMatrixSet}/{TileMatrix}/{TileRow}/{TileCol}""#
+        ));
+        assert!(xml.contains("<Format>application/vnd.mapbox-vector-tile</Format>"));
+        assert!(xml.contains("<Format>image/png</Format>"));
+        assert!(xml.contains("<TileMatrixLimits><TileMatrix>2</TileMatrix><MinTileRow>1</MinTileRow><MaxTileRow>2</MaxTileRow><MinTileCol>1</MinTileCol><MaxTileCol>2</MaxTileCol></TileMatrixLimits>"));
+        assert!(xml.contains("<ScaleDenominator>559082264.028"));
+        assert_eq!(
+            xml.matches("<TileMatrix>\n").count(),
+            usize::from(MAX_TILE_ZOOM) + 1
+        );
+        assert!(xml.contains(r#"<ows:Get xlink:href="https://maps.example.com/wmts?">"#));
+    }
+
+    #[test]
+    fn kvp_parameter_names_ignore_case() {
+        let params = KvpParams::new(HashMap::from([
+            ("Service".to_string(), "WMTS".to_string()),
+            ("TILEROW".to_string(), "3".to_string()),
+            ("tilecol".to_string(), "x".to_string()),
+        ]));
+        assert_eq!(params.get("service"), Some("WMTS"));
+        assert_eq!(params.require_number("tilerow").ok(), Some(3));
+        assert_eq!(
+            params.require_number("tilecol").unwrap_err().status(),
+            StatusCode::BAD_REQUEST
+        );
+        assert!(params.require("layer").is_err());
+    }
+}
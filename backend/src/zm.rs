@@ -0,0 +1,141 @@
+//! Z and M coordinates
+//!
+//! Tiles are two-dimensional, so Z and M values only weigh them down, and
+//! mixing 2D and 3D geometries trips up type checks downstream. Imports drop
+//! both by default. An upload sent with `zValues=elevation` keeps each
+//! feature's lowest and highest Z as the `z_min` and `z_max` properties
+//! instead, for datasets whose elevation matters, such as GPX tracks. The
+//! choice is stored in `files.z_values` and reported by the dataset's preview;
+//! appends follow it. M values are always dropped.
+
+use crate::columns::quote_identifier;
+
+/// Properties that hold a feature's Z range with `zValues=elevation`.
+pub const ELEVATION_COLUMNS: [&str; 2] = ["z_min", "z_max"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ZValues {
+    #[default]
+    Drop,
+    Elevation,
+}
+
+impl ZValues {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "drop" => Some(Self::Drop),
+            "elevation" => Some(Self::Elevation),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Drop => "drop",
+            Self::Elevation => "elevation",
+        }
+    }
+}
+
+/// How imports into `file_id` treat Z values.
+pub fn stored_z_values(conn: &duckdb::Connection, file_id: &str) -> Result<ZValues, String> {
+    let stored: Option<String> = conn
+        .query_row(
+            "SELECT z_values FROM files WHERE id = ?",
+            duckdb::params![file_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("File lookup failed: {e}"))?;
+    Ok(stored
+        .as_deref()
+        .and_then(ZValues::parse)
+        .unwrap_or_default())
+}
+
+/// Add the elevation columns to a freshly imported table when `file_id` keeps
+/// elevation and any geometry has Z; they are filled by
+/// `flatten_new_features`. Fails when the data already has such columns.
+pub fn add_elevation_columns(
+    conn: &duckdb::Connection,
+    file_id: &str,
+    table_name: &str,
+) -> Result<(), String> {
+    if stored_z_values(conn, file_id)? != ZValues::Elevation {
+        return Ok(());
+    }
+    let failed = |e: duckdb::Error| format!("Failed to read Z values: {e}");
+    let table = quote_identifier(table_name);
+    let has_z: Option<bool> = conn
+        .query_row(
+            &format!("SELECT bool_or(ST_HasZ(geom)) FROM {table}"),
+            [],
+            |row| row.get(0),
+        )
+        .map_err(failed)?;
+    if has_z != Some(true) {
+        return Ok(());
+    }
+    for column in ELEVATION_COLUMNS {
+        conn.execute(
+            &format!("ALTER TABLE {table} ADD COLUMN {column} DOUBLE"),
+            [],
+        )
+        .map_err(|_| {
+            format!("The data already has a {column} column; upload it with zValues=drop")
+        })?;
+    }
+    Ok(())
+}
+
+/// Drop the Z and M values of the features of `table_name` after `after_fid`,
+/// first copying their Z range into the elevation columns when the table has
+/// them and `file_id` keeps elevation.
+pub fn flatten_new_features(
+    conn: &duckdb::Connection,
+    file_id: &str,
+    table_name: &str,
+    after_fid: i64,
+) -> Result<(), String> {
+    let failed = |e: duckdb::Error| format!("Failed to drop Z values: {e}");
+    let table = quote_identifier(table_name);
+    let elevation_columns: i64 = conn
+        .query_row(
+            "SELECT count(*) FROM information_schema.columns
+             WHERE table_schema = 'main' AND table_name = ? AND column_name IN (?, ?)",
+            duckdb::params![table_name, ELEVATION_COLUMNS[0], ELEVATION_COLUMNS[1]],
+            |row| row.get(0),
+        )
+        .map_err(failed)?;
+    if elevation_columns == 2 && stored_z_values(conn, file_id)? == ZValues::Elevation {
+        conn.execute(
+            &format!(
+                "UPDATE {table} SET z_min = ST_ZMin(geom), z_max = ST_ZMax(geom)
+                 WHERE fid > ? AND ST_HasZ(geom)"
+            ),
+            duckdb::params![after_fid],
+        )
+        .map_err(failed)?;
+    }
+    conn.execute(
+        &format!(
+            "UPDATE {table} SET geom = ST_Force2D(geom)
+             WHERE fid > ? AND (ST_HasZ(geom) OR ST_HasM(geom))"
+        ),
+        duckdb::params![after_fid],
+    )
+    .map_err(failed)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn z_values_round_trip() {
+        assert_eq!(ZValues::parse(" Elevation "), Some(ZValues::Elevation));
+        assert_eq!(ZValues::parse("drop"), Some(ZValues::Drop));
+        assert_eq!(ZValues::parse("keep"), None);
+        assert_eq!(ZValues::default().as_str(), "drop");
+    }
+}
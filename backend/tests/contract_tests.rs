@@ -0,0 +1,1150 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use backend::{
+    build_style, build_test_router, init_database, ApiTokenItem, AppState, AuthBackend, BackupInfo,
+    CheckStatus, DatasetMetadata, DatasetQueryResponse, DatasetStorage, DatasetVersion,
+    DuckDBStore, ErrorResponse, ExportJob, FeatureLimitStrategy, FeatureListResponse,
+    FeatureMeasurements, FeaturePropertiesResponse, FeatureProperty, FeatureRow, FieldInfo,
+    FileAccess, FileEvents, FileFolder, FileItem, FileRetention, FileSchemaResponse, FileShare,
+    FileTags, GeoJsonFeature, GeoJsonFeatureCollection, HealthResponse, LayerInfo, Measurement,
+    OgcBoundingBox, OgcCollection, OgcCollections, OgcExtent, OgcFeatureCollection, OgcLink,
+    OgcSpatialExtent, OgcTileLayer, OgcTileMatrixLimits, OgcTileSet, OgcTileSets, OrgItem,
+    OrgMember, PasswordResetResponse, PreviewMeta, PublicTileMeta, PublicTileUrl, PublishAccess,
+    PublishResponse, ReadPool, RemoteRefreshResponse, RemoteRefreshStatus, Role, Settings,
+    SignedUrlResponse, StorageStats, TileInspection, TileJson, TileLayerInspection, TileOptions,
+    TileSeedJob, TilesetResponse, UserItem, VectorLayer, WebhookItem,
+};
+use http_body_util::BodyExt; // for collect()
+use serde_json::Value;
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tempfile::TempDir;
+use tower::ServiceExt; // for oneshot
+
+// The schemas under docs/dev/contracts are the shared contract between the backend and the
+// frontend. These tests check real responses against them; the frontend unit suite checks
+// the fields it reads against the same files.
+
+fn contracts_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("repo root")
+        .join("docs/dev/contracts")
+}
+
+fn load_schema(file_name: &str) -> Value {
+    let path = contracts_dir().join(file_name);
+    let raw = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("Failed to read contract {path:?}: {e}"));
+    serde_json::from_str(&raw).unwrap_or_else(|e| panic!("Invalid JSON in {path:?}: {e}"))
+}
+
+thread_local! {
+    // Schema files a value has been validated against on this test's thread.
+    static VALIDATED: RefCell<BTreeSet<String>> = const { RefCell::new(BTreeSet::new()) };
+}
+
+fn mark_validated(file_name: &str) {
+    VALIDATED.with(|files| files.borrow_mut().insert(file_name.to_string()));
+}
+
+fn schema_for(endpoint: &str) -> Value {
+    let index = load_schema("index.json");
+    let file_name = index[endpoint]
+        .as_str()
+        .unwrap_or_else(|| panic!("No contract registered for {endpoint}"));
+    mark_validated(file_name);
+    load_schema(file_name)
+}
+
+fn type_matches(expected: &str, value: &Value) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        other => panic!("Unsupported schema type {other}"),
+    }
+}
+
+// Minimal JSON Schema subset used by the contracts:
+// $ref (sibling file), type, enum, required, properties, additionalProperties, items, minItems, maxItems.
+fn validate(schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        mark_validated(reference);
+        validate(&load_schema(reference), value, path, errors);
+        return;
+    }
+
+    if let Some(expected) = schema.get("type") {
+        let allowed: Vec<&str> = match expected {
+            Value::String(s) => vec![s.as_str()],
+            Value::Array(items) => items.iter().filter_map(Value::as_str).collect(),
+            _ => panic!("Invalid type declaration at {path}"),
+        };
+        if !allowed.iter().any(|t| type_matches(t, value)) {
+            errors.push(format!("{path}: expected {allowed:?}, got {value}"));
+            return;
+        }
+    }
+
+    if let Some(options) = schema.get("enum").and_then(Value::as_array) {
+        if !options.contains(value) {
+            errors.push(format!("{path}: {value} not in {options:?}"));
+        }
+    }
+
+    if let Some(object) = value.as_object() {
+        if let Some(required) = schema.get("required").and_then(Value::as_array) {
+            for key in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(key) {
+                    errors.push(format!("{path}: missing required field '{key}'"));
+                }
+            }
+        }
+
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for (key, child) in object {
+            match properties.and_then(|p| p.get(key)) {
+                Some(child_schema) => {
+                    validate(child_schema, child, &format!("{path}.{key}"), errors)
+                }
+                None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                    errors.push(format!("{path}: unexpected field '{key}'"));
+                }
+                None => {}
+            }
+        }
+    }
+
+    if let Some(items) = value.as_array() {
+        if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+            if (items.len() as u64) < min {
+                errors.push(format!("{path}: expected at least {min} items"));
+            }
+        }
+        if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+            if (items.len() as u64) > max {
+                errors.push(format!("{path}: expected at most {max} items"));
+            }
+        }
+        if let Some(item_schema) = schema.get("items") {
+            for (i, item) in items.iter().enumerate() {
+                validate(item_schema, item, &format!("{path}[{i}]"), errors);
+            }
+        }
+    }
+}
+
+fn assert_contract(endpoint: &str, value: &Value) {
+    let mut errors = Vec::new();
+    validate(&schema_for(endpoint), value, "$", &mut errors);
+    assert!(
+        errors.is_empty(),
+        "{endpoint} response violates contract:\n  {}\nbody: {value}",
+        errors.join("\n  ")
+    );
+}
+
+async fn setup_app() -> (axum::Router, TempDir) {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let upload_dir = temp_dir.path().join("uploads");
+    std::fs::create_dir_all(&upload_dir).expect("create upload dir");
+
+    let db_path = temp_dir.path().join("test.duckdb");
+    let conn = init_database(&db_path);
+    let db = Arc::new(tokio::sync::Mutex::new(conn));
+
+    let state = AppState {
+        upload_dir,
+        db: db.clone(),
+        max_size: 10 * 1024 * 1024, // 10MB
+        max_size_label: "10MB".to_string(),
+        auth_backend: AuthBackend::new(db.clone()),
+        session_store: DuckDBStore::new(db.clone()),
+        read_pool: ReadPool::new(db),
+        upload_scanner: None,
+        postgis_export_connection: None,
+        postgis_export_schemas: vec!["public".to_string()],
+        public_base_url: None,
+        trust_forwarded_headers: false,
+        remote_allow_private_hosts: false,
+        password_reset_delivery: None,
+        file_events: FileEvents::default(),
+    };
+
+    (build_test_router(state), temp_dir)
+}
+
+async fn send(app: &axum::Router, request: Request<Body>) -> (StatusCode, Value) {
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body_bytes = response.into_body().collect().await.unwrap().to_bytes();
+    let json = serde_json::from_slice(&body_bytes).unwrap_or(Value::Null);
+    (status, json)
+}
+
+async fn get_json(app: &axum::Router, uri: &str) -> (StatusCode, Value) {
+    let request = Request::builder()
+        .method("GET")
+        .uri(uri)
+        .body(Body::empty())
+        .unwrap();
+    send(app, request).await
+}
+
+async fn upload_geojson(app: &axum::Router) -> Value {
+    let boundary = "------------------------boundaryXYZ";
+    let geojson_content = r#"{
+        "type": "FeatureCollection",
+        "features": [
+            {
+                "type": "Feature",
+                "properties": { "name": "Test Point", "lanes": 2, "note": null },
+                "geometry": { "type": "Point", "coordinates": [10.0, 20.0] }
+            }
+        ]
+    }"#;
+    let body_data = format!(
+        "--{boundary}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"points.geojson\"\r\n\r\n{geojson_content}\r\n--{boundary}--\r\n"
+    );
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/api/uploads")
+        .header(
+            "content-type",
+            format!("multipart/form-data; boundary={boundary}"),
+        )
+        .body(Body::from(body_data))
+        .unwrap();
+
+    let (status, body) = send(app, request).await;
+    assert_eq!(status, StatusCode::CREATED);
+    body
+}
+
+async fn wait_until_ready(app: &axum::Router, file_id: &str) {
+    for _ in 0..120 {
+        let (_, files) = get_json(app, "/api/files").await;
+        let status = files
+            .as_array()
+            .and_then(|items| items.iter().find(|f| f["id"] == file_id))
+            .and_then(|f| f["status"].as_str().map(str::to_string));
+        match status.as_deref() {
+            Some("ready") => return,
+            Some("failed") => panic!("File processing failed"),
+            _ => tokio::time::sleep(std::time::Duration::from_millis(250)).await,
+        }
+    }
+    panic!("Timeout waiting for file to be ready");
+}
+
+#[test]
+fn test_contract_index_references_existing_schemas() {
+    let index = load_schema("index.json");
+    let entries = index.as_object().expect("index is an object");
+    assert!(!entries.is_empty());
+    for (endpoint, file_name) in entries {
+        let file_name = file_name.as_str().expect("schema file name");
+        let schema = load_schema(file_name);
+        assert!(
+            schema.get("type").is_some(),
+            "{endpoint}: {file_name} has no top-level type"
+        );
+    }
+}
+
+#[test]
+fn test_contract_models_serialize_to_schema() {
+    // Fully populated models catch fields added on the Rust side without a contract update.
+    let item = FileItem {
+        id: "a1b2c3".to_string(),
+        name: "roads".to_string(),
+        file_type: "geojson".to_string(),
+        size: 42,
+        uploaded_at: "2026-02-04T10:00:00+00:00".to_string(),
+        status: "ready".to_string(),
+        crs: Some("EPSG:4326".to_string()),
+        path: "./uploads/a1b2c3/roads.geojson".to_string(),
+        table_name: Some("layer_a1b2c3".to_string()),
+        error: Some("boom".to_string()),
+        is_public: Some(true),
+        public_slug: Some("roads".to_string()),
+        org_id: Some("7d1e2f3a-0000-4000-8000-000000000000".to_string()),
+        geometry_type: Some("LineString".to_string()),
+        feature_count: Some(12),
+        folder: Some("projects/roads".to_string()),
+        tags: vec!["transport".to_string()],
+    };
+    assert_contract("POST /api/uploads", &serde_json::to_value(&item).unwrap());
+
+    let preview = PreviewMeta {
+        id: "a1b2c3".to_string(),
+        name: "roads".to_string(),
+        crs: None,
+        bbox: Some([0.0, 1.0, 2.0, 3.0]),
+        tile_format: Some("mvt".to_string()),
+        minzoom: Some(0),
+        maxzoom: Some(14),
+        data_version: 3,
+        layer_name: Some("roads".to_string()),
+        geometry_type: Some("LineString".to_string()),
+        feature_count: Some(12),
+        z_values: Some("elevation".to_string()),
+    };
+    assert_contract(
+        "GET /api/files/:id/preview",
+        &serde_json::to_value(&preview).unwrap(),
+    );
+
+    let measurements = FeatureMeasurements {
+        fid: 7,
+        geometry_type: Some("POLYGON".to_string()),
+        length: Some(Measurement {
+            value: 1.5,
+            unit: "km",
+            base_value: 1500.0,
+            base_unit: "m",
+        }),
+        area: Some(Measurement {
+            value: 2.5,
+            unit: "ha",
+            base_value: 25_000.0,
+            base_unit: "m²",
+        }),
+        perimeter: Some(Measurement {
+            value: 640.0,
+            unit: "m",
+            base_value: 640.0,
+            base_unit: "m",
+        }),
+    };
+    assert_contract(
+        "GET /api/files/:id/features/:fid/measure",
+        &serde_json::to_value(&measurements).unwrap(),
+    );
+
+    let inspection = TileInspection {
+        z: 3,
+        x: 4,
+        y: 2,
+        bytes: 1024,
+        layers: vec![TileLayerInspection {
+            name: "roads".to_string(),
+            extent: 4096,
+            feature_count: 12,
+            property_keys: vec!["name".to_string(), "lanes".to_string()],
+            geometry_types: [("LineString".to_string(), 12)].into_iter().collect(),
+        }],
+    };
+    assert_contract(
+        "GET /api/files/:id/tiles/:z/:x/:y/inspect",
+        &serde_json::to_value(&inspection).unwrap(),
+    );
+
+    let published = PublishResponse {
+        url: "/tiles/roads/{z}/{x}/{y}".to_string(),
+        slug: "roads".to_string(),
+        is_public: true,
+        access: PublishAccess::Signed,
+        signing_secret: Some("ab".repeat(32)),
+        expires_at: Some("2030-01-01T00:00:00+00:00".to_string()),
+        minzoom: Some(2),
+        maxzoom: Some(12),
+        tile_options: Some(TileOptions {
+            buffer: Some(64),
+            clip: Some(false),
+            ..Default::default()
+        }),
+        allowed_origins: Some(vec!["https://maps.example.com".to_string()]),
+        version: Some(2),
+    };
+    assert_contract(
+        "POST /api/files/:id/publish",
+        &serde_json::to_value(&published).unwrap(),
+    );
+
+    let public_url = PublicTileUrl {
+        slug: "roads".to_string(),
+        url: "/tiles/roads/{z}/{x}/{y}".to_string(),
+    };
+    assert_contract(
+        "GET /api/files/:id/public-url",
+        &serde_json::to_value(&public_url).unwrap(),
+    );
+
+    let signed_url = SignedUrlResponse {
+        url: "/tiles/roads/{z}/{x}/{y}?expires=1700000000&token=ab".to_string(),
+        expires_at: "2023-11-14T22:13:20+00:00".to_string(),
+    };
+    assert_contract(
+        "POST /api/files/:id/signed-url",
+        &serde_json::to_value(&signed_url).unwrap(),
+    );
+
+    let job = ExportJob {
+        id: "d4e5f6".to_string(),
+        file_id: "a1b2c3".to_string(),
+        format: "gpkg".to_string(),
+        status: "ready".to_string(),
+        created_at: "2026-02-04T10:00:00+00:00".to_string(),
+        finished_at: Some("2026-02-04T10:00:05+00:00".to_string()),
+        error: Some("boom".to_string()),
+        download_url: Some("/api/exports/d4e5f6/download".to_string()),
+    };
+    assert_contract(
+        "GET /api/exports/:job_id",
+        &serde_json::to_value(&job).unwrap(),
+    );
+
+    let seed_job = TileSeedJob {
+        id: "e5f6a7".to_string(),
+        file_id: "a1b2c3".to_string(),
+        status: "failed".to_string(),
+        bbox: [-0.2, 51.45, -0.05, 51.55],
+        min_zoom: 0,
+        max_zoom: 14,
+        total_tiles: 120,
+        rendered_tiles: 37,
+        created_at: "2026-02-04T10:00:00+00:00".to_string(),
+        finished_at: Some("2026-02-04T10:00:05+00:00".to_string()),
+        error: Some("boom".to_string()),
+    };
+    assert_contract(
+        "GET /api/seed-jobs/:job_id",
+        &serde_json::to_value(&seed_job).unwrap(),
+    );
+
+    let backup = BackupInfo {
+        name: "mapflow-20260204T100000Z.zip".to_string(),
+        size: 48_213,
+        created_at: "2026-02-04T10:00:00+00:00".to_string(),
+        download_url: "/api/admin/backups/mapflow-20260204T100000Z.zip".to_string(),
+    };
+    assert_contract(
+        "POST /api/admin/backup",
+        &serde_json::to_value(&backup).unwrap(),
+    );
+
+    let mut retention = FileRetention {
+        retention_days: None,
+        effective_retention_days: 30,
+        purge_at: Some("2026-03-06T10:00:00+00:00".to_string()),
+    };
+    assert_contract(
+        "GET /api/files/:id/retention",
+        &serde_json::to_value(&retention).unwrap(),
+    );
+    retention.retention_days = Some(0);
+    retention.effective_retention_days = 0;
+    retention.purge_at = None;
+    assert_contract(
+        "PUT /api/files/:id/retention",
+        &serde_json::to_value(&retention).unwrap(),
+    );
+
+    let tags = FileTags {
+        tags: vec!["roads".to_string(), "Team A".to_string()],
+    };
+    assert_contract(
+        "PUT /api/files/:id/tags",
+        &serde_json::to_value(&tags).unwrap(),
+    );
+    let folder = FileFolder { folder: None };
+    assert_contract(
+        "PUT /api/files/:id/folder",
+        &serde_json::to_value(&folder).unwrap(),
+    );
+
+    let metadata = DatasetMetadata {
+        description: Some("Roads of the city".to_string()),
+        attribution: None,
+        license: Some("ODbL-1.0".to_string()),
+    };
+    assert_contract(
+        "GET /api/files/:id/metadata",
+        &serde_json::to_value(&metadata).unwrap(),
+    );
+
+    let versions = vec![
+        DatasetVersion {
+            version: 1,
+            created_at: "2026-02-04T10:00:00+00:00".to_string(),
+            replaced_at: Some("2026-02-05T02:00:00+00:00".to_string()),
+            feature_count: Some(120),
+            current: false,
+        },
+        DatasetVersion {
+            version: 2,
+            created_at: "2026-02-05T02:00:00+00:00".to_string(),
+            replaced_at: None,
+            feature_count: Some(124),
+            current: true,
+        },
+    ];
+    assert_contract(
+        "GET /api/files/:id/versions",
+        &serde_json::to_value(&versions).unwrap(),
+    );
+
+    let refresh = RemoteRefreshResponse {
+        id: "a1b2c3".to_string(),
+        changed: false,
+    };
+    assert_contract(
+        "POST /api/files/:id/refresh",
+        &serde_json::to_value(&refresh).unwrap(),
+    );
+    let refresh_status = RemoteRefreshStatus {
+        id: "a1b2c3".to_string(),
+        fetched_at: Some("2026-01-02T00:00:00+00:00".to_string()),
+        last_refresh_error: Some("Remote fetch failed: HTTP 503".to_string()),
+        last_refresh_failed_at: Some("2026-01-03T00:00:00+00:00".to_string()),
+    };
+    assert_contract(
+        "GET /api/files/:id/refresh",
+        &serde_json::to_value(&refresh_status).unwrap(),
+    );
+
+    let storage = StorageStats {
+        database_bytes: 12_845_056,
+        wal_bytes: 0,
+        uploads_bytes: 2_048_000,
+        tile_cache_bytes: 512_000,
+        backups_bytes: 0,
+        datasets: vec![
+            DatasetStorage {
+                id: "a1b2c3".to_string(),
+                name: "roads.geojson".to_string(),
+                table_name: Some("layer_a1b2c3".to_string()),
+                row_count: 1200,
+                table_bytes: 262_144,
+                upload_bytes: 2_048_000,
+                tile_cache_bytes: 512_000,
+            },
+            DatasetStorage {
+                id: "d4e5f6".to_string(),
+                name: "broken.zip".to_string(),
+                table_name: None,
+                row_count: 0,
+                table_bytes: 0,
+                upload_bytes: 0,
+                tile_cache_bytes: 0,
+            },
+        ],
+    };
+    assert_contract(
+        "GET /api/admin/storage",
+        &serde_json::to_value(&storage).unwrap(),
+    );
+
+    let link = |href: &str, rel: &str, media_type: &str| OgcLink {
+        href: href.to_string(),
+        rel: rel.to_string(),
+        media_type: Some(media_type.to_string()),
+        title: None,
+        templated: None,
+    };
+    let collection = OgcCollection {
+        id: "a1b2c3".to_string(),
+        title: "roads".to_string(),
+        item_type: "feature".to_string(),
+        crs: vec!["http://www.opengis.net/def/crs/OGC/1.3/CRS84".to_string()],
+        description: Some("Roads of the city".to_string()),
+        attribution: Some("© OpenStreetMap contributors".to_string()),
+        license: Some("ODbL-1.0".to_string()),
+        extent: Some(OgcExtent {
+            spatial: OgcSpatialExtent {
+                bbox: vec![[-0.5, 51.3, 0.3, 51.7]],
+                crs: "http://www.opengis.net/def/crs/OGC/1.3/CRS84".to_string(),
+            },
+        }),
+        links: vec![
+            link(
+                "https://maps.example.com/ogc/collections/a1b2c3",
+                "self",
+                "application/json",
+            ),
+            link(
+                "https://maps.example.com/ogc/collections/a1b2c3/items",
+                "items",
+                "application/geo+json",
+            ),
+        ],
+    };
+    assert_contract(
+        "GET /ogc/collections/:id",
+        &serde_json::to_value(&collection).unwrap(),
+    );
+    let collections = OgcCollections {
+        links: vec![link(
+            "https://maps.example.com/ogc/collections",
+            "self",
+            "application/json",
+        )],
+        collections: vec![collection],
+    };
+    assert_contract(
+        "GET /ogc/collections",
+        &serde_json::to_value(&collections).unwrap(),
+    );
+    let items = OgcFeatureCollection {
+        kind: "FeatureCollection".to_string(),
+        features: vec![GeoJsonFeature {
+            kind: "Feature".to_string(),
+            id: 1,
+            geometry: serde_json::json!({ "type": "Point", "coordinates": [0.0, 51.5] }),
+            properties: serde_json::Map::from_iter([(
+                "Road Name".to_string(),
+                serde_json::json!("Main St"),
+            )]),
+        }],
+        number_matched: 1200,
+        number_returned: 1,
+        time_stamp: "2026-02-04T10:00:00Z".to_string(),
+        links: vec![link(
+            "https://maps.example.com/ogc/collections/a1b2c3/items?limit=1&offset=1",
+            "next",
+            "application/geo+json",
+        )],
+    };
+    assert_contract(
+        "GET /ogc/collections/:id/items",
+        &serde_json::to_value(&items).unwrap(),
+    );
+
+    let ogc_tiles = "https://maps.example.com/tiles/roads/ogc/tiles";
+    let mut item = link(
+        &format!("{ogc_tiles}/WebMercatorQuad/{{tileMatrix}}/{{tileRow}}/{{tileCol}}"),
+        "item",
+        "application/vnd.mapbox-vector-tile",
+    );
+    item.templated = Some(true);
+    let tileset = OgcTileSet {
+        title: "roads".to_string(),
+        description: Some("Roads of the city".to_string()),
+        attribution: Some("© OpenStreetMap contributors".to_string()),
+        license: Some("ODbL-1.0".to_string()),
+        data_type: "vector".to_string(),
+        crs: "http://www.opengis.net/def/crs/EPSG/0/3857".to_string(),
+        tile_matrix_set_uri: "http://www.opengis.net/def/tilematrixset/OGC/1.0/WebMercatorQuad"
+            .to_string(),
+        tile_matrix_set_limits: Some(vec![OgcTileMatrixLimits {
+            tile_matrix: "10".to_string(),
+            min_tile_row: 339,
+            max_tile_row: 341,
+            min_tile_col: 510,
+            max_tile_col: 512,
+        }]),
+        bounding_box: Some(OgcBoundingBox {
+            lower_left: [-0.5, 51.3],
+            upper_right: [0.3, 51.7],
+            crs: "http://www.opengis.net/def/crs/OGC/1.3/CRS84".to_string(),
+        }),
+        layers: Some(vec![OgcTileLayer {
+            id: "roads".to_string(),
+            data_type: "vector".to_string(),
+            properties_schema: serde_json::json!({
+                "type": "object",
+                "properties": { "Road Name": { "type": "string" } }
+            }),
+        }]),
+        links: vec![
+            link(
+                &format!("{ogc_tiles}/WebMercatorQuad"),
+                "self",
+                "application/json",
+            ),
+            item,
+        ],
+    };
+    assert_contract(
+        "GET /tiles/:slug/ogc/tiles/WebMercatorQuad",
+        &serde_json::to_value(&tileset).unwrap(),
+    );
+    let tilesets = OgcTileSets {
+        links: vec![link(ogc_tiles, "self", "application/json")],
+        tilesets: vec![OgcTileSet {
+            tile_matrix_set_limits: None,
+            bounding_box: None,
+            layers: None,
+            ..tileset
+        }],
+    };
+    assert_contract(
+        "GET /tiles/:slug/ogc/tiles",
+        &serde_json::to_value(&tilesets).unwrap(),
+    );
+
+    let mut webhook = WebhookItem {
+        id: "5c6d7e8f-0000-4000-8000-000000000000".to_string(),
+        url: "https://cache.example.com/refresh".to_string(),
+        events: vec!["file.ready".to_string(), "file.published".to_string()],
+        created_at: "2026-02-04T10:00:00+00:00".to_string(),
+        secret: Some("cd".repeat(32)),
+    };
+    assert_contract(
+        "POST /api/admin/webhooks",
+        &serde_json::to_value(&webhook).unwrap(),
+    );
+    webhook.secret = None;
+    assert_contract(
+        "GET /api/admin/webhooks",
+        &serde_json::to_value(vec![&webhook]).unwrap(),
+    );
+
+    let options = TileOptions {
+        layer_name: Some("roads".to_string()),
+        extent: Some(4096),
+        buffer: Some(64),
+        clip: Some(true),
+        simplify: Some(1.0),
+        fields: Some(vec!["Road Name".to_string()]),
+        min_zoom: Some(2),
+        max_zoom: Some(14),
+        feature_limit: Some(5000),
+        feature_limit_strategy: Some(FeatureLimitStrategy::Grid),
+        feature_limit_sort: None,
+        cluster_max_zoom: Some(8),
+        cluster_radius: Some(512),
+        field_aliases: Some([("Road Name".to_string(), "name".to_string())].into()),
+        precision: Some(6),
+    };
+    assert_contract(
+        "GET /api/files/:id/tile-options",
+        &serde_json::to_value(&options).unwrap(),
+    );
+
+    let tilejson = TileJson {
+        tilejson: "3.0.0".to_string(),
+        name: "roads".to_string(),
+        tiles: vec!["/api/files/a1b2c3/tiles/{z}/{x}/{y}?v=3".to_string()],
+        minzoom: 0,
+        maxzoom: 14,
+        bounds: Some([0.0, 1.0, 2.0, 3.0]),
+        description: Some("Roads of the city".to_string()),
+        attribution: Some("© OpenStreetMap contributors".to_string()),
+        license: Some("ODbL-1.0".to_string()),
+        vector_layers: Some(vec![VectorLayer {
+            id: "roads".to_string(),
+            description: Some("Road network".to_string()),
+            fields: [("Road Name".to_string(), "String".to_string())].into(),
+        }]),
+    };
+    assert_contract(
+        "GET /api/files/:id/tilejson",
+        &serde_json::to_value(&tilejson).unwrap(),
+    );
+
+    let meta = PublicTileMeta {
+        slug: "roads".to_string(),
+        name: "roads".to_string(),
+        tile_source: "vector".to_string(),
+        tile_url: "/tiles/roads/{z}/{x}/{y}?v=3".to_string(),
+        viewer_url: "/view/roads".to_string(),
+        minzoom: 0,
+        maxzoom: 14,
+        bounds: Some([0.0, 1.0, 2.0, 3.0]),
+        attribution: Some("© OpenStreetMap contributors".to_string()),
+        layer_name: Some("roads".to_string()),
+        vector_layers: tilejson.vector_layers.unwrap(),
+    };
+    assert_contract(
+        "GET /tiles/:slug/meta",
+        &serde_json::to_value(&meta).unwrap(),
+    );
+
+    let tileset = TilesetResponse {
+        id: "g7h8i9".to_string(),
+        name: "City".to_string(),
+        slug: "city".to_string(),
+        url: "/tiles/city/{z}/{x}/{y}".to_string(),
+        files: vec!["a1b2c3".to_string()],
+        created_at: "2026-02-04T10:00:00+00:00".to_string(),
+    };
+    assert_contract(
+        "POST /api/tilesets",
+        &serde_json::to_value(&tileset).unwrap(),
+    );
+
+    let user = UserItem {
+        id: "5f0c1a2b-0000-4000-8000-000000000000".to_string(),
+        username: "vera".to_string(),
+        role: Role::Viewer,
+        created_at: "2026-02-04T10:00:00+00:00".to_string(),
+    };
+    assert_contract("POST /api/users", &serde_json::to_value(&user).unwrap());
+    assert_contract(
+        "GET /api/users",
+        &serde_json::to_value(vec![&user]).unwrap(),
+    );
+
+    let share = FileShare {
+        username: "vera".to_string(),
+        access: FileAccess::Edit,
+        created_at: "2026-02-04T10:00:00+00:00".to_string(),
+    };
+    assert_contract(
+        "POST /api/files/:id/shares",
+        &serde_json::to_value(&share).unwrap(),
+    );
+    assert_contract(
+        "GET /api/files/:id/shares",
+        &serde_json::to_value(vec![&share]).unwrap(),
+    );
+
+    let reset = PasswordResetResponse {
+        message: "If the account exists, a reset token has been issued".to_string(),
+        token: Some("ab".repeat(32)),
+        expires_at: Some("2026-02-04T11:00:00+00:00".to_string()),
+    };
+    assert_contract(
+        "POST /api/auth/reset-request",
+        &serde_json::to_value(&reset).unwrap(),
+    );
+
+    let mut token = ApiTokenItem {
+        id: "0b9c8d7e-0000-4000-8000-000000000000".to_string(),
+        name: "nightly upload".to_string(),
+        prefix: "mf_1a2b3c4".to_string(),
+        created_at: "2026-02-04T10:00:00+00:00".to_string(),
+        last_used_at: None,
+        token: Some(format!("mf_{}", "ab".repeat(32))),
+    };
+    assert_contract("POST /api/tokens", &serde_json::to_value(&token).unwrap());
+    token.token = None;
+    token.last_used_at = Some("2026-02-05T02:00:00+00:00".to_string());
+    assert_contract(
+        "GET /api/tokens",
+        &serde_json::to_value(vec![&token]).unwrap(),
+    );
+
+    let org = OrgItem {
+        id: "7d1e2f3a-0000-4000-8000-000000000000".to_string(),
+        name: "GIS team".to_string(),
+        created_at: "2026-02-04T10:00:00+00:00".to_string(),
+        member_count: 2,
+    };
+    assert_contract("POST /api/orgs", &serde_json::to_value(&org).unwrap());
+    assert_contract("GET /api/orgs", &serde_json::to_value(vec![&org]).unwrap());
+    let member = OrgMember {
+        username: "vera".to_string(),
+        joined_at: "2026-02-04T10:00:00+00:00".to_string(),
+    };
+    assert_contract(
+        "POST /api/orgs/:id/members",
+        &serde_json::to_value(&member).unwrap(),
+    );
+    assert_contract(
+        "GET /api/orgs/:id/members",
+        &serde_json::to_value(vec![&member]).unwrap(),
+    );
+
+    let mut settings = Settings {
+        cache_ttl_secs: 300,
+        upload_max_size_bytes: 200 * 1024 * 1024,
+        public_base_url: None,
+        registration_enabled: false,
+        failed_upload_retention_days: 30,
+        dataset_version_retention: 10,
+    };
+    assert_contract(
+        "GET /api/admin/settings",
+        &serde_json::to_value(&settings).unwrap(),
+    );
+    settings.public_base_url = Some("https://maps.example.com".to_string());
+    assert_contract(
+        "PUT /api/admin/settings",
+        &serde_json::to_value(&settings).unwrap(),
+    );
+
+    let mut row = serde_json::Map::new();
+    row.insert("Road Name".to_string(), Value::from("Main St"));
+    let query = DatasetQueryResponse {
+        columns: vec!["Road Name".to_string()],
+        rows: vec![row],
+        truncated: true,
+    };
+    assert_contract(
+        "POST /api/files/:id/query",
+        &serde_json::to_value(&query).unwrap(),
+    );
+
+    assert_contract(
+        "GET /api/files",
+        &serde_json::to_value(vec![&item]).unwrap(),
+    );
+    assert_contract(
+        "GET /api/tilesets",
+        &serde_json::to_value(vec![&tileset]).unwrap(),
+    );
+    assert_contract(
+        "GET /tiles/:slug/style.json",
+        &build_style("roads", &tilejson),
+    );
+    assert_contract(
+        "error",
+        &serde_json::to_value(ErrorResponse {
+            error: "File not found".to_string(),
+        })
+        .unwrap(),
+    );
+
+    let mut properties = serde_json::Map::new();
+    properties.insert("Road Name".to_string(), Value::from("Main St"));
+    let features = FeatureListResponse {
+        total: 12,
+        limit: 50,
+        offset: 0,
+        fields: vec![FieldInfo {
+            name: "Road Name".to_string(),
+            r#type: "VARCHAR".to_string(),
+        }],
+        rows: vec![FeatureRow {
+            fid: 7,
+            properties: properties.clone(),
+        }],
+    };
+    assert_contract(
+        "GET /api/files/:id/features",
+        &serde_json::to_value(&features).unwrap(),
+    );
+    let collection = GeoJsonFeatureCollection {
+        kind: "FeatureCollection".to_string(),
+        total: 12,
+        limit: 50,
+        offset: 0,
+        features: vec![GeoJsonFeature {
+            kind: "Feature".to_string(),
+            id: 7,
+            geometry: serde_json::json!({ "type": "Point", "coordinates": [0.0, 1.0] }),
+            properties,
+        }],
+    };
+    assert_contract(
+        "GET /api/files/:id/features?format=geojson",
+        &serde_json::to_value(&collection).unwrap(),
+    );
+    let feature = FeaturePropertiesResponse {
+        fid: 7,
+        properties: vec![FeatureProperty {
+            key: "Road Name".to_string(),
+            value: Value::from("Main St"),
+        }],
+    };
+    assert_contract(
+        "GET /api/files/:id/features/:fid",
+        &serde_json::to_value(&feature).unwrap(),
+    );
+
+    let schema = FileSchemaResponse {
+        layers: vec![LayerInfo {
+            id: "roads".to_string(),
+            description: Some("Road network".to_string()),
+            fields: vec![FieldInfo {
+                name: "Road Name".to_string(),
+                r#type: "VARCHAR".to_string(),
+            }],
+        }],
+        spatial_index: Some(true),
+    };
+    assert_contract(
+        "GET /api/files/:id/schema",
+        &serde_json::to_value(&schema).unwrap(),
+    );
+
+    let failed = CheckStatus {
+        status: "error".to_string(),
+        error: Some("upload directory is not writable".to_string()),
+    };
+    let health = HealthResponse {
+        status: "error".to_string(),
+        checks: [
+            ("database".to_string(), failed.clone()),
+            ("spatial".to_string(), failed.clone()),
+            ("uploadDir".to_string(), failed),
+        ]
+        .into(),
+    };
+    assert_contract("GET /health", &serde_json::to_value(&health).unwrap());
+
+    // Every contract must have been checked against a model above, so a schema
+    // edited by hand cannot drift from what the backend serializes.
+    let index = load_schema("index.json");
+    let validated = VALIDATED.with(|files| files.borrow().clone());
+    let unchecked: Vec<&str> = index
+        .as_object()
+        .expect("index is an object")
+        .values()
+        .filter_map(Value::as_str)
+        .filter(|file_name| !validated.contains(*file_name))
+        .collect();
+    assert!(
+        unchecked.is_empty(),
+        "Contracts without a serialized model: {unchecked:?}"
+    );
+}
+
+#[test]
+fn test_contract_validator_rejects_drift() {
+    // Guard the validator itself: a camelCase/snake_case swap must be reported.
+    let drifted = serde_json::json!([{
+        "id": "a1b2c3",
+        "name": "roads",
+        "type": "geojson",
+        "size": 42,
+        "uploaded_at": "2026-02-04T10:00:00+00:00",
+        "status": "ready",
+        "crs": null,
+        "path": "./uploads/a1b2c3/roads.geojson"
+    }]);
+    let mut errors = Vec::new();
+    validate(&schema_for("GET /api/files"), &drifted, "$", &mut errors);
+    assert!(errors.iter().any(|e| e.contains("'uploadedAt'")));
+    assert!(errors.iter().any(|e| e.contains("'uploaded_at'")));
+}
+
+#[tokio::test]
+async fn test_contract_file_lifecycle_responses() {
+    let (app, _temp) = setup_app().await;
+
+    let (status, health) = get_json(&app, "/health").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_contract("GET /health", &health);
+
+    let uploaded = upload_geojson(&app).await;
+    assert_contract("POST /api/uploads", &uploaded);
+    let file_id = uploaded["id"].as_str().expect("id").to_string();
+
+    let (status, files) = get_json(&app, "/api/files").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_contract("GET /api/files", &files);
+
+    wait_until_ready(&app, &file_id).await;
+
+    let (status, files) = get_json(&app, "/api/files").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_contract("GET /api/files", &files);
+
+    let (status, preview) = get_json(&app, &format!("/api/files/{file_id}/preview")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_contract("GET /api/files/:id/preview", &preview);
+
+    let (status, schema) = get_json(&app, &format!("/api/files/{file_id}/schema")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_contract("GET /api/files/:id/schema", &schema);
+
+    let (status, feature) = get_json(&app, &format!("/api/files/{file_id}/features/1")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_contract("GET /api/files/:id/features/:fid", &feature);
+
+    let (status, features) = get_json(&app, &format!("/api/files/{file_id}/features")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_contract("GET /api/files/:id/features", &features);
+
+    let (status, collection) = get_json(
+        &app,
+        &format!("/api/files/{file_id}/features?format=geojson"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_contract("GET /api/files/:id/features?format=geojson", &collection);
+
+    let (status, sample) = get_json(&app, &format!("/api/files/{file_id}/sample")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_contract("GET /api/files/:id/sample", &sample);
+
+    let (status, options) = get_json(&app, &format!("/api/files/{file_id}/tile-options")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_contract("GET /api/files/:id/tile-options", &options);
+
+    let (status, tilejson) = get_json(&app, &format!("/api/files/{file_id}/tilejson")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_contract("GET /api/files/:id/tilejson", &tilejson);
+
+    let (status, versions) = get_json(&app, &format!("/api/files/{file_id}/versions")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_contract("GET /api/files/:id/versions", &versions);
+
+    let request = Request::builder()
+        .method("POST")
+        .uri(format!("/api/files/{file_id}/query"))
+        .header("content-type", "application/json")
+        .body(Body::from(r#"{"sql":"SELECT * FROM dataset"}"#))
+        .unwrap();
+    let (status, query) = send(&app, request).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_contract("POST /api/files/:id/query", &query);
+
+    let request = Request::builder()
+        .method("POST")
+        .uri(format!("/api/files/{file_id}/publish"))
+        .header("content-type", "application/json")
+        .body(Body::from(r#"{"slug":"contract-points"}"#))
+        .unwrap();
+    let (status, published) = send(&app, request).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_contract("POST /api/files/:id/publish", &published);
+
+    let (status, public_url) = get_json(&app, &format!("/api/files/{file_id}/public-url")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_contract("GET /api/files/:id/public-url", &public_url);
+
+    let (status, tilejson) = get_json(&app, "/tiles/contract-points/tilejson.json").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_contract("GET /tiles/:slug/tilejson.json", &tilejson);
+
+    let (status, style) = get_json(&app, "/tiles/contract-points/style.json").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_contract("GET /tiles/:slug/style.json", &style);
+
+    let (status, meta) = get_json(&app, "/tiles/contract-points/meta").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_contract("GET /tiles/:slug/meta", &meta);
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/api/tilesets")
+        .header("content-type", "application/json")
+        .body(Body::from(format!(
+            r#"{{"slug":"contract-map","files":["{file_id}"]}}"#
+        )))
+        .unwrap();
+    let (status, tileset) = send(&app, request).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_contract("POST /api/tilesets", &tileset);
+
+    let (status, tilesets) = get_json(&app, "/api/tilesets").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_contract("GET /api/tilesets", &tilesets);
+
+    // Published files expose isPublic/publicSlug in the list.
+    let (_, files) = get_json(&app, "/api/files").await;
+    assert_contract("GET /api/files", &files);
+    assert_eq!(files[0]["publicSlug"], "contract-points");
+}
+
+#[tokio::test]
+async fn test_contract_error_responses() {
+    let (app, _temp) = setup_app().await;
+
+    for uri in [
+        "/api/files/missing/preview",
+        "/api/files/missing/schema",
+        "/api/files/missing/features/1",
+        "/api/files/missing/public-url",
+        "/api/files/missing/tiles/0/0/0",
+        "/api/files/missing/tiles/0/5/5",
+        "/tiles/missing/0/0/0",
+    ] {
+        let (status, body) = get_json(&app, uri).await;
+        assert!(status.is_client_error(), "{uri} returned {status}");
+        assert_contract("error", &body);
+    }
+}
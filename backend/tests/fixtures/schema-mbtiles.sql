@@ -0,0 +1,43 @@
+-- Catalog of an early release: MBTiles support, public links without options,
+-- no users, shares, tilesets or schema_version.
+CREATE TABLE files (
+    id VARCHAR PRIMARY KEY,
+    name VARCHAR NOT NULL,
+    type VARCHAR NOT NULL,
+    size BIGINT NOT NULL,
+    uploaded_at TIMESTAMP NOT NULL,
+    status VARCHAR NOT NULL,
+    crs VARCHAR,
+    path VARCHAR NOT NULL,
+    table_name VARCHAR,
+    error VARCHAR,
+    is_public BOOLEAN DEFAULT FALSE,
+    tile_format VARCHAR,
+    minzoom INTEGER,
+    maxzoom INTEGER,
+    tile_bounds VARCHAR
+);
+
+CREATE TABLE published_files (
+    file_id VARCHAR PRIMARY KEY,
+    slug VARCHAR UNIQUE NOT NULL,
+    published_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (file_id) REFERENCES files(id)
+);
+
+CREATE TABLE dataset_columns (
+    source_id VARCHAR NOT NULL,
+    normalized_name VARCHAR NOT NULL,
+    original_name VARCHAR NOT NULL,
+    ordinal BIGINT NOT NULL,
+    mvt_type VARCHAR NOT NULL,
+    PRIMARY KEY (source_id, normalized_name)
+);
+
+INSERT INTO files (id, name, type, size, uploaded_at, status, crs, path, table_name, is_public)
+VALUES ('a1b2c3', 'roads.geojson', 'geojson', 1024, TIMESTAMP '2025-06-01 10:00:00', 'ready',
+        'EPSG:4326', './uploads/a1b2c3/roads.geojson', 'layer_a1b2c3', TRUE);
+
+INSERT INTO published_files (file_id, slug) VALUES ('a1b2c3', 'roads');
+
+INSERT INTO dataset_columns VALUES ('a1b2c3', 'name', 'Name', 2, 'VARCHAR');
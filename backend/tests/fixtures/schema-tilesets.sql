@@ -0,0 +1,78 @@
+-- Catalog of a later release: accounts, shares and tilesets, but tilesets
+-- without owners and files without organisations or import statistics.
+CREATE TABLE files (
+    id VARCHAR PRIMARY KEY,
+    name VARCHAR NOT NULL,
+    type VARCHAR NOT NULL,
+    size BIGINT NOT NULL,
+    uploaded_at TIMESTAMP NOT NULL,
+    status VARCHAR NOT NULL,
+    crs VARCHAR,
+    path VARCHAR NOT NULL,
+    table_name VARCHAR,
+    error VARCHAR,
+    is_public BOOLEAN DEFAULT FALSE,
+    tile_format VARCHAR,
+    minzoom INTEGER,
+    maxzoom INTEGER,
+    tile_bounds VARCHAR,
+    tile_options VARCHAR,
+    data_version BIGINT DEFAULT 0,
+    owner_id VARCHAR
+);
+
+CREATE TABLE published_files (
+    file_id VARCHAR PRIMARY KEY,
+    slug VARCHAR UNIQUE NOT NULL,
+    published_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    tile_options VARCHAR,
+    FOREIGN KEY (file_id) REFERENCES files(id)
+);
+
+CREATE TABLE tilesets (
+    id VARCHAR PRIMARY KEY,
+    slug VARCHAR UNIQUE NOT NULL,
+    name VARCHAR NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE tileset_sources (
+    tileset_id VARCHAR NOT NULL,
+    file_id VARCHAR NOT NULL,
+    ordinal INTEGER NOT NULL,
+    PRIMARY KEY (tileset_id, file_id),
+    FOREIGN KEY (file_id) REFERENCES files(id)
+);
+
+CREATE TABLE users (
+    id VARCHAR PRIMARY KEY,
+    username VARCHAR UNIQUE NOT NULL,
+    password_hash VARCHAR NOT NULL,
+    role VARCHAR NOT NULL,
+    created_at TIMESTAMP NOT NULL
+);
+
+CREATE TABLE sessions (
+    id VARCHAR PRIMARY KEY,
+    data VARCHAR NOT NULL,
+    expiry_date TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE system_settings (
+    key VARCHAR PRIMARY KEY,
+    value VARCHAR NOT NULL
+);
+
+INSERT INTO users VALUES ('u1', 'alice', '$2b$12$abcdefghijklmnopqrstuv', 'admin', TIMESTAMP '2025-09-01 09:00:00');
+
+INSERT INTO files (id, name, type, size, uploaded_at, status, crs, path, table_name, is_public, data_version, owner_id)
+VALUES ('d4e5f6', 'parcels.geojson', 'geojson', 2048, TIMESTAMP '2025-09-02 10:00:00', 'ready',
+        'EPSG:4326', './uploads/d4e5f6/parcels.geojson', 'layer_d4e5f6', TRUE, 3, 'u1');
+
+INSERT INTO published_files (file_id, slug, tile_options) VALUES ('d4e5f6', 'parcels', '{"extent":512}');
+
+INSERT INTO tilesets (id, slug, name) VALUES ('t1', 'basemap', 'Basemap');
+INSERT INTO tileset_sources VALUES ('t1', 'd4e5f6', 0);
+
+INSERT INTO system_settings VALUES ('initialized', '1');
@@ -0,0 +1,154 @@
+use backend::{init_database, is_initialized, latest_version, schema_version};
+use std::path::Path;
+use tempfile::TempDir;
+
+// Catalogs written by older releases, before the schema was versioned. Opening
+// them must keep their rows and add everything the current schema has.
+
+fn open_fixture(temp: &TempDir, fixture: &str) -> duckdb::Connection {
+    let db_path = temp.path().join("mapflow.duckdb");
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let sql = std::fs::read_to_string(fixtures.join(fixture)).unwrap();
+    duckdb::Connection::open(&db_path)
+        .unwrap()
+        .execute_batch(&sql)
+        .unwrap();
+    init_database(&db_path)
+}
+
+fn columns(conn: &duckdb::Connection, table: &str) -> Vec<String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT column_name FROM information_schema.columns
+             WHERE table_schema = 'main' AND table_name = ?
+             ORDER BY ordinal_position",
+        )
+        .unwrap();
+    stmt.query_map(duckdb::params![table], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+fn assert_current_schema(conn: &duckdb::Connection) {
+    assert_eq!(schema_version(conn).unwrap(), latest_version());
+    let files = columns(conn, "files");
+    for column in [
+        "tile_options",
+        "data_version",
+        "owner_id",
+        "org_id",
+        "bbox",
+        "feature_count",
+        "geometry_type",
+    ] {
+        assert!(files.contains(&column.to_string()), "files.{column}");
+    }
+    let published = columns(conn, "published_files");
+    for column in [
+        "tile_options",
+        "signing_secret",
+        "expires_at",
+        "allowed_origins",
+        "version",
+    ] {
+        assert!(
+            published.contains(&column.to_string()),
+            "published_files.{column}"
+        );
+    }
+    for column in ["minzoom", "maxzoom"] {
+        assert!(
+            !published.contains(&column.to_string()),
+            "published_files.{column}"
+        );
+    }
+    assert!(columns(conn, "tilesets").contains(&"owner_id".to_string()));
+    for table in [
+        "export_jobs",
+        "tile_seed_jobs",
+        "file_shares",
+        "orgs",
+        "api_tokens",
+        "password_reset_tokens",
+    ] {
+        assert!(!columns(conn, table).is_empty(), "{table}");
+    }
+}
+
+#[test]
+fn test_migrate_from_mbtiles_release() {
+    let temp = TempDir::new().unwrap();
+    let conn = open_fixture(&temp, "schema-mbtiles.sql");
+    assert_current_schema(&conn);
+
+    let (name, slug, data_version): (String, String, i64) = conn
+        .query_row(
+            "SELECT f.name, p.slug, f.data_version
+             FROM files f JOIN published_files p ON p.file_id = f.id
+             WHERE f.id = 'a1b2c3'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .unwrap();
+    assert_eq!(name, "roads.geojson");
+    assert_eq!(slug, "roads");
+    assert_eq!(data_version, 0);
+    assert!(!is_initialized(&conn).unwrap());
+}
+
+#[test]
+fn test_migrate_from_tilesets_release() {
+    let temp = TempDir::new().unwrap();
+    let conn = open_fixture(&temp, "schema-tilesets.sql");
+    assert_current_schema(&conn);
+
+    let (owner_id, data_version, overrides): (String, i64, String) = conn
+        .query_row(
+            "SELECT f.owner_id, f.data_version, p.tile_options
+             FROM files f JOIN published_files p ON p.file_id = f.id
+             WHERE f.id = 'd4e5f6'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .unwrap();
+    assert_eq!(owner_id, "u1");
+    assert_eq!(data_version, 3);
+    assert_eq!(overrides, r#"{"extent":512}"#);
+    let tileset_owner: Option<String> = conn
+        .query_row("SELECT owner_id FROM tilesets WHERE id = 't1'", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(tileset_owner, None);
+    assert!(is_initialized(&conn).unwrap());
+}
+
+#[test]
+fn test_migrated_database_reopens_without_changes() {
+    let temp = TempDir::new().unwrap();
+    let db_path = temp.path().join("mapflow.duckdb");
+    drop(init_database(&db_path));
+
+    let conn = init_database(&db_path);
+    let applied: i64 = conn
+        .query_row("SELECT COUNT(*) FROM schema_version", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(applied, i64::from(latest_version()));
+}
+
+#[test]
+#[should_panic(expected = "newer than this build supports")]
+fn test_newer_schema_is_refused() {
+    let temp = TempDir::new().unwrap();
+    let db_path = temp.path().join("mapflow.duckdb");
+    let conn = init_database(&db_path);
+    conn.execute(
+        "INSERT INTO schema_version (version, name, applied_at) VALUES (?, 'future', NOW())",
+        duckdb::params![latest_version() + 1],
+    )
+    .unwrap();
+    drop(conn);
+
+    init_database(&db_path);
+}
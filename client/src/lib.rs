@@ -0,0 +1,379 @@
+//! Typed client for the MapFlow HTTP API
+//!
+//! Wraps the session-authenticated `/api/*` routes and the public `/tiles/*`
+//! routes so Rust services can upload data, wait for the import to finish,
+//! publish it and build tile URLs without hand-crafting multipart requests.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), mapflow_client::Error> {
+//! use mapflow_client::{Client, PollOptions};
+//!
+//! let client = Client::new("http://localhost:3000")?;
+//! client.login("admin", "Secret123!").await?;
+//!
+//! let file = client.upload_path("roads.geojson").await?;
+//! let file = client.wait_until_ready(&file.id, PollOptions::default()).await?;
+//! let published = client.publish(&file.id, Some("roads")).await?;
+//! println!("{}", client.absolute_url(&published.url));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use reqwest::{multipart, Response, StatusCode};
+use serde::de::DeserializeOwned;
+
+mod models;
+
+use models::ErrorResponse;
+pub use models::{
+    FieldInfo, FileItem, FileSchemaResponse, FileStatus, LayerInfo, LoginResponse, PreviewMeta,
+    PublicTileUrl, PublishRequest, PublishResponse,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("API error ({status}): {message}")]
+    Api { status: StatusCode, message: String },
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid file path: {0}")]
+    InvalidPath(String),
+    #[error("File not found: {0}")]
+    FileNotFound(String),
+    #[error("Import failed for {id}: {}", error.as_deref().unwrap_or("unknown error"))]
+    ImportFailed { id: String, error: Option<String> },
+    #[error("Timed out waiting for {id} (last status: {last_status:?})")]
+    Timeout {
+        id: String,
+        last_status: Option<FileStatus>,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Polling cadence for [`Client::wait_until_ready`].
+#[derive(Debug, Clone, Copy)]
+pub struct PollOptions {
+    pub interval: Duration,
+    pub timeout: Duration,
+}
+
+impl Default for PollOptions {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_millis(500),
+            timeout: Duration::from_secs(300),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+}
+
+impl Client {
+    /// Create a client with its own cookie store, so a successful [`Client::login`]
+    /// authenticates every later call on the same instance.
+    pub fn new(base_url: impl Into<String>) -> Result<Self> {
+        let http = reqwest::Client::builder().cookie_store(true).build()?;
+        Ok(Self::with_http_client(base_url, http))
+    }
+
+    /// Use a preconfigured `reqwest::Client` (proxies, timeouts, TLS roots...).
+    /// It must have a cookie store enabled for session authentication to work.
+    pub fn with_http_client(base_url: impl Into<String>, http: reqwest::Client) -> Self {
+        let base_url = base_url.into().trim_end_matches('/').to_string();
+        Self { http, base_url }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Resolve a server-relative path (e.g. `PublishResponse::url`) against the base URL.
+    pub fn absolute_url(&self, path: &str) -> String {
+        if path.starts_with("http://") || path.starts_with("https://") {
+            return path.to_string();
+        }
+        if path.starts_with('/') {
+            format!("{}{}", self.base_url, path)
+        } else {
+            format!("{}/{}", self.base_url, path)
+        }
+    }
+
+    /// Authenticated preview tile URL: `/api/files/:id/tiles/:z/:x/:y`.
+    pub fn tile_url(&self, file_id: &str, z: u32, x: u32, y: u32) -> String {
+        self.absolute_url(&format!("/api/files/{file_id}/tiles/{z}/{x}/{y}"))
+    }
+
+    /// Public tile URL for a published slug: `/tiles/:slug/:z/:x/:y`.
+    pub fn public_tile_url(&self, slug: &str, z: u32, x: u32, y: u32) -> String {
+        self.absolute_url(&format!("/tiles/{slug}/{z}/{x}/{y}"))
+    }
+
+    /// Public XYZ template (`.../{z}/{x}/{y}`) suitable for map libraries.
+    pub fn public_tile_template(&self, slug: &str) -> String {
+        self.absolute_url(&format!("/tiles/{slug}/{{z}}/{{x}}/{{y}}"))
+    }
+
+    pub async fn login(&self, username: &str, password: &str) -> Result<LoginResponse> {
+        let response = self
+            .http
+            .post(self.absolute_url("/api/auth/login"))
+            .json(&serde_json::json!({ "username": username, "password": password }))
+            .send()
+            .await?;
+        parse_json(response).await
+    }
+
+    pub async fn logout(&self) -> Result<()> {
+        let response = self
+            .http
+            .post(self.absolute_url("/api/auth/logout"))
+            .send()
+            .await?;
+        ensure_success(response).await.map(|_| ())
+    }
+
+    pub async fn list_files(&self) -> Result<Vec<FileItem>> {
+        let response = self
+            .http
+            .get(self.absolute_url("/api/files"))
+            .send()
+            .await?;
+        parse_json(response).await
+    }
+
+    pub async fn get_file(&self, file_id: &str) -> Result<FileItem> {
+        self.list_files()
+            .await?
+            .into_iter()
+            .find(|item| item.id == file_id)
+            .ok_or_else(|| Error::FileNotFound(file_id.to_string()))
+    }
+
+    /// Upload raw bytes. `file_name` decides the format by extension
+    /// (`.geojson`, `.zip`, `.kml`, `.mbtiles`, ...).
+    pub async fn upload_bytes(&self, file_name: &str, bytes: Vec<u8>) -> Result<FileItem> {
+        let part = multipart::Part::bytes(bytes).file_name(file_name.to_string());
+        let form = multipart::Form::new().part("file", part);
+        let response = self
+            .http
+            .post(self.absolute_url("/api/uploads"))
+            .multipart(form)
+            .send()
+            .await?;
+        parse_json(response).await
+    }
+
+    pub async fn upload_path(&self, path: impl AsRef<Path>) -> Result<FileItem> {
+        let path = path.as_ref();
+        let file_name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| Error::InvalidPath(path.display().to_string()))?
+            .to_string();
+        let bytes = tokio::fs::read(path).await?;
+        self.upload_bytes(&file_name, bytes).await
+    }
+
+    /// Poll the file list until the import reaches `ready`.
+    /// A `failed` import is reported as [`Error::ImportFailed`].
+    pub async fn wait_until_ready(&self, file_id: &str, options: PollOptions) -> Result<FileItem> {
+        let started = Instant::now();
+
+        loop {
+            let item = self.get_file(file_id).await?;
+            match item.status {
+                FileStatus::Ready => return Ok(item),
+                FileStatus::Failed => {
+                    return Err(Error::ImportFailed {
+                        id: item.id,
+                        error: item.error,
+                    })
+                }
+                status if started.elapsed() >= options.timeout => {
+                    return Err(Error::Timeout {
+                        id: file_id.to_string(),
+                        last_status: Some(status),
+                    })
+                }
+                _ => {}
+            }
+            tokio::time::sleep(options.interval).await;
+        }
+    }
+
+    pub async fn preview(&self, file_id: &str) -> Result<PreviewMeta> {
+        let response = self
+            .http
+            .get(self.absolute_url(&format!("/api/files/{file_id}/preview")))
+            .send()
+            .await?;
+        parse_json(response).await
+    }
+
+    pub async fn schema(&self, file_id: &str) -> Result<FileSchemaResponse> {
+        let response = self
+            .http
+            .get(self.absolute_url(&format!("/api/files/{file_id}/schema")))
+            .send()
+            .await?;
+        parse_json(response).await
+    }
+
+    /// Publish a ready file. Without a slug the server uses the file id.
+    pub async fn publish(&self, file_id: &str, slug: Option<&str>) -> Result<PublishResponse> {
+        let request = PublishRequest {
+            slug: slug.map(str::to_string),
+        };
+        let response = self
+            .http
+            .post(self.absolute_url(&format!("/api/files/{file_id}/publish")))
+            .json(&request)
+            .send()
+            .await?;
+        parse_json(response).await
+    }
+
+    pub async fn unpublish(&self, file_id: &str) -> Result<()> {
+        let response = self
+            .http
+            .post(self.absolute_url(&format!("/api/files/{file_id}/unpublish")))
+            .send()
+            .await?;
+        ensure_success(response).await.map(|_| ())
+    }
+
+    pub async fn public_url(&self, file_id: &str) -> Result<PublicTileUrl> {
+        let response = self
+            .http
+            .get(self.absolute_url(&format!("/api/files/{file_id}/public-url")))
+            .send()
+            .await?;
+        parse_json(response).await
+    }
+
+    /// Fetch an authenticated preview tile. `None` means the server answered
+    /// 204 (no tile at these coordinates).
+    pub async fn get_tile(&self, file_id: &str, z: u32, x: u32, y: u32) -> Result<Option<Vec<u8>>> {
+        self.fetch_tile(self.tile_url(file_id, z, x, y)).await
+    }
+
+    /// Fetch a public tile for a published slug (no session needed).
+    pub async fn get_public_tile(
+        &self,
+        slug: &str,
+        z: u32,
+        x: u32,
+        y: u32,
+    ) -> Result<Option<Vec<u8>>> {
+        self.fetch_tile(self.public_tile_url(slug, z, x, y)).await
+    }
+
+    async fn fetch_tile(&self, url: String) -> Result<Option<Vec<u8>>> {
+        let response = ensure_success(self.http.get(url).send().await?).await?;
+        if response.status() == StatusCode::NO_CONTENT {
+            return Ok(None);
+        }
+        Ok(Some(response.bytes().await?.to_vec()))
+    }
+}
+
+async fn ensure_success(response: Response) -> Result<Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    // The backend reports failures as `{ "error": "..." }`; fall back to the raw body.
+    let body = response.text().await.unwrap_or_default();
+    let message = serde_json::from_str::<ErrorResponse>(&body)
+        .map(|e| e.error)
+        .unwrap_or_else(|_| {
+            if body.is_empty() {
+                status.canonical_reason().unwrap_or("").to_string()
+            } else {
+                body
+            }
+        });
+    Err(Error::Api { status, message })
+}
+
+async fn parse_json<T: DeserializeOwned>(response: Response) -> Result<T> {
+    Ok(ensure_success(response).await?.json::<T>().await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base_url_trailing_slash_is_trimmed() {
+        let client = Client::new("http://localhost:3000/").unwrap();
+        assert_eq!(client.base_url(), "http://localhost:3000");
+        assert_eq!(
+            client.absolute_url("/api/files"),
+            "http://localhost:3000/api/files"
+        );
+    }
+
+    #[test]
+    fn absolute_url_keeps_absolute_input() {
+        let client = Client::new("http://localhost:3000").unwrap();
+        assert_eq!(
+            client.absolute_url("https://tiles.example.com/a"),
+            "https://tiles.example.com/a"
+        );
+        assert_eq!(
+            client.absolute_url("health"),
+            "http://localhost:3000/health"
+        );
+    }
+
+    #[test]
+    fn tile_urls_match_server_routes() {
+        let client = Client::new("https://maps.example.com").unwrap();
+        assert_eq!(
+            client.tile_url("abc123", 3, 4, 5),
+            "https://maps.example.com/api/files/abc123/tiles/3/4/5"
+        );
+        assert_eq!(
+            client.public_tile_url("roads", 0, 0, 0),
+            "https://maps.example.com/tiles/roads/0/0/0"
+        );
+        assert_eq!(
+            client.public_tile_template("roads"),
+            "https://maps.example.com/tiles/roads/{z}/{x}/{y}"
+        );
+    }
+
+    #[test]
+    fn file_item_deserializes_backend_shape() {
+        let json = r#"{
+            "id": "a1b2c3",
+            "name": "roads",
+            "type": "geojson",
+            "size": 42,
+            "uploadedAt": "2026-02-04T10:00:00+00:00",
+            "status": "ready",
+            "crs": "EPSG:4326",
+            "path": "./uploads/a1b2c3/roads.geojson",
+            "table_name": "layer_a1b2c3",
+            "isPublic": true,
+            "publicSlug": "roads"
+        }"#;
+        let item: FileItem = serde_json::from_str(json).unwrap();
+        assert_eq!(item.status, FileStatus::Ready);
+        assert_eq!(item.public_slug.as_deref(), Some("roads"));
+        assert!(item.error.is_none());
+    }
+}
@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+
+/// File metadata as returned by `GET /api/files` and `POST /api/uploads`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileItem {
+    pub id: String,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub file_type: String,
+    pub size: u64,
+    #[serde(rename = "uploadedAt")]
+    pub uploaded_at: String,
+    pub status: FileStatus,
+    pub crs: Option<String>,
+    pub path: String,
+    #[serde(default)]
+    pub table_name: Option<String>,
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(rename = "isPublic", default)]
+    pub is_public: Option<bool>,
+    #[serde(rename = "publicSlug", default)]
+    pub public_slug: Option<String>,
+}
+
+/// Import lifecycle: uploaded → processing → ready/failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileStatus {
+    Uploaded,
+    Processing,
+    Ready,
+    Failed,
+}
+
+impl FileStatus {
+    pub fn is_terminal(self) -> bool {
+        matches!(self, FileStatus::Ready | FileStatus::Failed)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PreviewMeta {
+    pub id: String,
+    pub name: String,
+    pub crs: Option<String>,
+    pub bbox: Option<[f64; 4]>, // minx, miny, maxx, maxy in WGS84
+    #[serde(rename = "tileFormat", default)]
+    pub tile_format: Option<String>,
+    #[serde(rename = "minZoom", default)]
+    pub minzoom: Option<i32>,
+    #[serde(rename = "maxZoom", default)]
+    pub maxzoom: Option<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldInfo {
+    pub name: String,
+    pub r#type: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayerInfo {
+    pub id: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub fields: Vec<FieldInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileSchemaResponse {
+    pub layers: Vec<LayerInfo>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PublishRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slug: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PublishResponse {
+    pub url: String,
+    pub slug: String,
+    pub is_public: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PublicTileUrl {
+    pub slug: String,
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginResponse {
+    pub username: String,
+    pub role: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct ErrorResponse {
+    pub error: String,
+}
@@ -0,0 +1,128 @@
+use backend::{build_test_router, init_database, AppState};
+use mapflow_client::{Client, Error, FileStatus, PollOptions};
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+
+// Helper to run the backend on an ephemeral port and return its base URL
+async fn spawn_server() -> (String, TempDir) {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let upload_dir = temp_dir.path().join("uploads");
+    std::fs::create_dir_all(&upload_dir).expect("create upload dir");
+
+    let db_path = temp_dir.path().join("test.duckdb");
+    let conn = init_database(&db_path);
+    let db = Arc::new(tokio::sync::Mutex::new(conn));
+    let state = AppState::new(upload_dir, db, 10 * 1024 * 1024);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind");
+    let addr = listener.local_addr().expect("local addr");
+    tokio::spawn(async move {
+        axum::serve(listener, build_test_router(state))
+            .await
+            .expect("server");
+    });
+
+    (format!("http://{addr}"), temp_dir)
+}
+
+const POINTS_GEOJSON: &str = r#"{
+    "type": "FeatureCollection",
+    "features": [
+        {
+            "type": "Feature",
+            "properties": { "name": "Test Point" },
+            "geometry": { "type": "Point", "coordinates": [0.0, 0.0] }
+        }
+    ]
+}"#;
+
+fn fast_poll() -> PollOptions {
+    PollOptions {
+        interval: Duration::from_millis(100),
+        timeout: Duration::from_secs(30),
+    }
+}
+
+#[tokio::test]
+async fn test_client_upload_publish_and_fetch_public_tile() {
+    let (base_url, _temp) = spawn_server().await;
+    let client = Client::new(base_url).unwrap();
+
+    let uploaded = client
+        .upload_bytes("points.geojson", POINTS_GEOJSON.as_bytes().to_vec())
+        .await
+        .unwrap();
+    assert_eq!(uploaded.name, "points");
+    assert_eq!(uploaded.status, FileStatus::Uploaded);
+
+    let ready = client
+        .wait_until_ready(&uploaded.id, fast_poll())
+        .await
+        .unwrap();
+    assert!(ready.table_name.is_some());
+
+    let preview = client.preview(&ready.id).await.unwrap();
+    assert!(preview.bbox.is_some());
+
+    let tile = client.get_tile(&ready.id, 0, 0, 0).await.unwrap();
+    assert!(tile.is_some_and(|bytes| !bytes.is_empty()));
+
+    let published = client
+        .publish(&ready.id, Some("client-points"))
+        .await
+        .unwrap();
+    assert_eq!(published.slug, "client-points");
+    assert_eq!(published.url, "/tiles/client-points/{z}/{x}/{y}");
+
+    let public_url = client.public_url(&ready.id).await.unwrap();
+    assert_eq!(public_url.slug, "client-points");
+
+    let public_tile = client
+        .get_public_tile("client-points", 0, 0, 0)
+        .await
+        .unwrap();
+    assert!(public_tile.is_some_and(|bytes| !bytes.is_empty()));
+
+    client.unpublish(&ready.id).await.unwrap();
+    let err = client.public_url(&ready.id).await.unwrap_err();
+    assert!(matches!(err, Error::Api { status, .. } if status == 404));
+}
+
+#[tokio::test]
+async fn test_client_surfaces_api_error_message() {
+    let (base_url, _temp) = spawn_server().await;
+    let client = Client::new(base_url).unwrap();
+
+    let err = client
+        .upload_bytes("notes.txt", b"hello".to_vec())
+        .await
+        .unwrap_err();
+    match err {
+        Error::Api { status, message } => {
+            assert_eq!(status, 400);
+            assert!(message.starts_with("Unsupported file type"));
+        }
+        other => panic!("expected API error, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_client_reports_failed_import() {
+    let (base_url, _temp) = spawn_server().await;
+    let client = Client::new(base_url).unwrap();
+
+    // Valid JSON object, but not something GDAL can read as features.
+    let uploaded = client
+        .upload_bytes("broken.geojson", br#"{"hello": "world"}"#.to_vec())
+        .await
+        .unwrap();
+
+    let err = client
+        .wait_until_ready(&uploaded.id, fast_poll())
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::ImportFailed { ref id, .. } if id == &uploaded.id));
+}
@@ -1,12 +1,24 @@
 import React, { useEffect, useMemo, useState } from 'react';
 import { useAuth } from './AuthContext.jsx';
-import {
-  hasActiveJobs as computeHasActiveJobs,
-  mergeServerFilesWithOptimistic,
-} from './polling.js';
+import { applyFileEvent, mergeServerFilesWithOptimistic } from './polling.js';
 import { publishFile, unpublishFile } from './api.js';
 import { formatSize, parseType, validateSlug } from './utils.js';
 
+function FileThumbnail({ fileId }) {
+  const [missing, setMissing] = useState(false);
+
+  if (missing) return <span className="file-thumbnail" aria-hidden="true" />;
+  return (
+    <img
+      className="file-thumbnail"
+      src={`/api/files/${fileId}/thumbnail`}
+      alt=""
+      loading="lazy"
+      onError={() => setMissing(true)}
+    />
+  );
+}
+
 function PublishModal({ file, onClose, onSuccess }) {
   const [slug, setSlug] = useState(file?.id || '');
   const [error, setError] = useState('');
@@ -205,6 +217,26 @@ function DetailSidebar({ file }) {
         </div>
       )}
 
+      {file.geometryType && (
+        <div className="detail-group">
+          <div className="detail-label">Geometry</div>
+          <div className="detail-value" data-testid="file-geometry">
+            {file.geometryType}
+            {file.featureCount != null && ` · ${file.featureCount.toLocaleString()} features`}
+          </div>
+        </div>
+      )}
+
+      {(file.folder || file.tags?.length > 0) && (
+        <div className="detail-group">
+          <div className="detail-label">Folder / Tags</div>
+          <div className="detail-value" data-testid="file-tags">
+            {file.folder || '--'}
+            {file.tags?.length > 0 && ` · ${file.tags.join(', ')}`}
+          </div>
+        </div>
+      )}
+
       {isReady && (
         <div className="detail-group">
           <div className="detail-label">字段信息</div>
@@ -372,28 +404,30 @@ export default function App() {
     [files, selectedId],
   );
 
-  const hasActiveJobs = useMemo(() => computeHasActiveJobs(files), [files]);
-
-  // Polling Logic
+  // Live updates: the server streams list changes. The list is reloaded
+  // whenever the stream (re)connects, since events may have been missed.
   useEffect(() => {
-    if (!hasActiveJobs) return;
+    const source = new EventSource('/api/files/events');
 
-    const intervalId = setInterval(async () => {
+    source.onopen = async () => {
       try {
         const res = await fetch('/api/files');
         if (!res.ok) return;
         const data = await res.json();
-
-        setFiles((prevFiles) => {
-          return mergeServerFilesWithOptimistic(prevFiles, data);
-        });
+        setFiles((prevFiles) => mergeServerFilesWithOptimistic(prevFiles, data));
       } catch (err) {
-        console.error('Polling failed', err);
+        console.error('Reloading files failed', err);
       }
-    }, 2000); // Poll every 2 seconds
+    };
+    for (const type of ['created', 'updated', 'deleted']) {
+      source.addEventListener(type, (event) => {
+        const data = JSON.parse(event.data);
+        setFiles((prevFiles) => applyFileEvent(prevFiles, type, data));
+      });
+    }
 
-    return () => clearInterval(intervalId);
-  }, [hasActiveJobs]);
+    return () => source.close();
+  }, []);
 
   useEffect(() => {
     let cancelled = false;
@@ -462,7 +496,11 @@ export default function App() {
         throw new Error(data.error || '上传失败');
       }
       const data = await res.json();
-      setFiles((prev) => [data, ...prev.filter((item) => item.id !== tempId)]);
+      setFiles((prev) => {
+        const rest = prev.filter((item) => item.id !== tempId);
+        // The event stream may have added it already, possibly with a newer status.
+        return rest.some((item) => item.id === data.id) ? rest : [data, ...rest];
+      });
       setSelectedId(data.id);
     } catch (error) {
       const message = error instanceof Error ? error.message : '上传失败';
@@ -491,7 +529,7 @@ export default function App() {
           <label className="upload-button">
             <input
               type="file"
-              accept=".zip,.geojson,.json,.geojsonl,.geojsons,.kml,.gpx,.topojson,.mbtiles"
+              accept=".zip,.geojson,.json,.geojsonl,.geojsons,.kml,.gpx,.topojson,.mbtiles,.tif,.tiff,.gz,.tgz"
               onChange={handleFileChange}
               data-testid="file-input"
             />
@@ -511,7 +549,7 @@ export default function App() {
         <div className="panel-header">
           <h2>上传文件</h2>
           <span className="panel-meta">
-            支持 .zip / .geojson / .geojsonl / .kml / .gpx / .topojson / .mbtiles，单文件最大
+            支持 .zip / .geojson / .geojsonl / .kml / .gpx / .topojson / .mbtiles / .tif 及其 .gz / .tar.gz，单文件最大
             200MB（可配置）
           </span>
         </div>
@@ -542,7 +580,14 @@ export default function App() {
                     onClick={() => setSelectedId(item.id)}
                     data-testid={`file-row-${item.id}`}
                   >
-                    <div>{item.name}</div>
+                    <div className="file-name">
+                      {item.status === 'ready' ? (
+                        <FileThumbnail fileId={item.id} />
+                      ) : (
+                        <span className="file-thumbnail" aria-hidden="true" />
+                      )}
+                      <span>{item.name}</span>
+                    </div>
                     <div>{item.type}</div>
                     <div>{formatSize(item.size || 0)}</div>
                     <div className="muted">
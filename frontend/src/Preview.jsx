@@ -297,7 +297,8 @@ export default function Preview() {
 
     // 1. Tile Layer source
     // URL pattern: /api/files/{id}/tiles/{z}/{x}/{y} (no .mvt extension)
-    const tileUrl = `${window.location.origin}/api/files/${id}/tiles/{z}/{x}/{y}`;
+    // `v` changes when the dataset is edited so cached tiles are not reused.
+    const tileUrl = `${window.location.origin}/api/files/${id}/tiles/{z}/{x}/{y}?v=${meta.dataVersion ?? 0}`;
 
     let tileLayer;
 
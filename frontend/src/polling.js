@@ -6,6 +6,13 @@ export function mergeServerFilesWithOptimistic(prevFiles, serverFiles) {
   return [...stillUploadingOptimistic, ...serverFiles];
 }
 
-export function hasActiveJobs(files) {
-  return files.some((f) => f && (f.status === 'uploaded' || f.status === 'processing'));
+/** Apply a `created`, `updated` or `deleted` event from `/api/files/events`. */
+export function applyFileEvent(files, type, data) {
+  if (type === 'deleted') {
+    return files.filter((f) => f.id !== data.id);
+  }
+  if (!files.some((f) => f.id === data.id)) {
+    return [data, ...files];
+  }
+  return files.map((f) => (f.id === data.id ? data : f));
 }
@@ -7,7 +7,9 @@ export function formatSize(bytes) {
 }
 
 export function parseType(fileName) {
-  const lower = fileName.toLowerCase();
+  // A gzipped file has its inner file's type; a tarball's is known once unpacked.
+  const lower = fileName.toLowerCase().replace(/\.gz$/, '');
+  if (lower.endsWith('.tar') || lower.endsWith('.tgz')) return 'unknown';
   if (lower.endsWith('.zip')) return 'shapefile';
   if (lower.endsWith('.geojson') || lower.endsWith('.json')) return 'geojson';
   if (lower.endsWith('.geojsonl') || lower.endsWith('.geojsons')) return 'geojsonl';
@@ -15,6 +17,7 @@ export function parseType(fileName) {
   if (lower.endsWith('.gpx')) return 'gpx';
   if (lower.endsWith('.topojson')) return 'topojson';
   if (lower.endsWith('.mbtiles')) return 'mbtiles';
+  if (lower.endsWith('.tif') || lower.endsWith('.tiff')) return 'geotiff';
   return 'unknown';
 }
 
@@ -26,7 +26,7 @@ test('upload file and verify status auto-updates from processing to ready', asyn
   await expect(row).toBeVisible();
 
   // 3. Status should eventually become '已就绪' (Ready) without reload
-  // This validates the polling mechanism.
+  // This validates the live file event stream.
   // Note: Depending on speed, it might jump straight to ready, or show '等待处理' -> '已就绪'.
   // We strictly wait for '已就绪'.
   await expect(row.getByText('已就绪')).toBeVisible({ timeout: 10000 });
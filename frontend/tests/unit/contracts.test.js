@@ -0,0 +1,63 @@
+import { readFileSync } from 'node:fs';
+import { describe, expect, it } from 'vitest';
+
+// Shared with backend/tests/contract_tests.rs, which validates real responses against the
+// same schemas. Here we only check that every field the UI reads is part of the contract.
+const contractsDir = new URL('../../../docs/dev/contracts/', import.meta.url);
+
+function loadSchema(fileName) {
+  return JSON.parse(readFileSync(new URL(fileName, contractsDir), 'utf8'));
+}
+
+function resolveObjectSchema(schema) {
+  if (schema.$ref) return resolveObjectSchema(loadSchema(schema.$ref));
+  if (schema.type === 'array') return resolveObjectSchema(schema.items);
+  return schema;
+}
+
+const index = loadSchema('index.json');
+
+// Fields read by src/App.jsx, src/Preview.jsx and src/api.js, per endpoint.
+const frontendExpectations = {
+  'GET /api/files': [
+    'id',
+    'name',
+    'type',
+    'size',
+    'uploadedAt',
+    'status',
+    'crs',
+    'error',
+    'isPublic',
+    'publicSlug',
+  ],
+  'POST /api/uploads': ['id', 'name', 'status'],
+  'GET /api/files/:id/preview': ['id', 'name', 'crs', 'bbox', 'tileFormat', 'minZoom', 'maxZoom'],
+  'GET /api/files/:id/features/:fid': ['fid', 'properties'],
+  'GET /api/files/:id/schema': ['layers'],
+  'POST /api/files/:id/publish': ['slug', 'url'],
+  error: ['error'],
+};
+
+describe('API contracts', () => {
+  it('registers a schema for every endpoint the UI consumes', () => {
+    for (const endpoint of Object.keys(frontendExpectations)) {
+      expect(index[endpoint], endpoint).toBeTypeOf('string');
+    }
+  });
+
+  for (const [endpoint, fields] of Object.entries(frontendExpectations)) {
+    it(`${endpoint} exposes the fields the UI reads`, () => {
+      const schema = resolveObjectSchema(loadSchema(index[endpoint]));
+      const declared = Object.keys(schema.properties ?? {});
+      for (const field of fields) {
+        expect(declared, `${endpoint} is missing "${field}"`).toContain(field);
+      }
+    });
+  }
+
+  it('file status values match the polling state machine', () => {
+    const item = loadSchema('file-item.schema.json');
+    expect(item.properties.status.enum).toEqual(['uploaded', 'processing', 'ready', 'failed']);
+  });
+});
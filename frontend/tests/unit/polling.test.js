@@ -1,15 +1,8 @@
 import { describe, expect, it } from 'vitest';
 
-import { hasActiveJobs, mergeServerFilesWithOptimistic } from '../../src/polling.js';
+import { applyFileEvent, mergeServerFilesWithOptimistic } from '../../src/polling.js';
 
 describe('polling helpers', () => {
-  it('hasActiveJobs: true when uploaded/processing exists', () => {
-    expect(hasActiveJobs([{ status: 'ready' }])).toBe(false);
-    expect(hasActiveJobs([{ status: 'uploaded' }])).toBe(true);
-    expect(hasActiveJobs([{ status: 'processing' }])).toBe(true);
-    expect(hasActiveJobs([{ status: 'failed' }])).toBe(false);
-  });
-
   it('mergeServerFilesWithOptimistic: keeps uploading optimistic not present on server', () => {
     const prev = [
       { id: 'temp-1', status: 'uploading', name: 'a' },
@@ -31,4 +24,21 @@ describe('polling helpers', () => {
       { id: 'same', status: 'uploaded', name: 'x' },
     ]);
   });
+
+  it('applyFileEvent: adds created, replaces updated and removes deleted files', () => {
+    const files = [{ id: 'a', status: 'uploaded' }];
+
+    const created = applyFileEvent(files, 'created', { id: 'b', status: 'uploaded' });
+    expect(created).toEqual([
+      { id: 'b', status: 'uploaded' },
+      { id: 'a', status: 'uploaded' },
+    ]);
+    expect(applyFileEvent(created, 'updated', { id: 'a', status: 'ready' })).toEqual([
+      { id: 'b', status: 'uploaded' },
+      { id: 'a', status: 'ready' },
+    ]);
+    expect(applyFileEvent(created, 'deleted', { id: 'b' })).toEqual([
+      { id: 'a', status: 'uploaded' },
+    ]);
+  });
 });
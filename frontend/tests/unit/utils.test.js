@@ -62,6 +62,18 @@ describe('parseType', () => {
     expect(parseType('data.mbtiles')).toBe('mbtiles');
   });
 
+  it('recognizes geotiff', () => {
+    expect(parseType('dem.tif')).toBe('geotiff');
+    expect(parseType('IMAGE.TIFF')).toBe('geotiff');
+  });
+
+  it('recognizes gzipped files by their inner type', () => {
+    expect(parseType('roads.geojson.gz')).toBe('geojson');
+    expect(parseType('ROADS.JSON.GZ')).toBe('geojson');
+    expect(parseType('bundle.tar.gz')).toBe('unknown');
+    expect(parseType('bundle.tgz')).toBe('unknown');
+  });
+
   it('returns unknown for unrecognized extensions', () => {
     expect(parseType('data.txt')).toBe('unknown');
     expect(parseType('data')).toBe('unknown');